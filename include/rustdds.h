@@ -0,0 +1,108 @@
+/*
+ * C interface of RustDDS. See the documentation of module rustdds::ffi.
+ *
+ * Build the library with
+ *   cargo rustc --release --features ffi --crate-type cdylib
+ */
+#ifndef RUSTDDS_H
+#define RUSTDDS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define RUSTDDS_OK 0
+#define RUSTDDS_NO_DATA 1
+#define RUSTDDS_ERROR (-1)
+#define RUSTDDS_BAD_PARAMETER (-2)
+#define RUSTDDS_TIMEOUT (-3)
+
+/* Representation identifiers for the encoding of serialized data */
+#define RUSTDDS_CDR_BE 0x0000
+#define RUSTDDS_CDR_LE 0x0001
+#define RUSTDDS_PL_CDR_BE 0x0002
+#define RUSTDDS_PL_CDR_LE 0x0003
+
+typedef struct RustddsParticipant RustddsParticipant;
+typedef struct RustddsTopic RustddsTopic;
+typedef struct RustddsWriter RustddsWriter;
+typedef struct RustddsReader RustddsReader;
+
+typedef struct RustddsQos {
+  bool reliable;
+  bool transient_local;
+  /* KeepLast depth. Zero or negative means KeepAll. */
+  int32_t history_depth;
+} RustddsQos;
+
+typedef struct RustddsSample {
+  uint8_t *data;
+  size_t len;
+  uint16_t encoding;
+  /* Nanoseconds since the Unix epoch, or -1 if not known */
+  int64_t source_timestamp_ns;
+} RustddsSample;
+
+typedef enum RustddsStatusKind {
+  RUSTDDS_SAMPLE_REJECTED,
+  RUSTDDS_LIVELINESS_CHANGED,
+  RUSTDDS_REQUESTED_DEADLINE_MISSED,
+  RUSTDDS_REQUESTED_INCOMPATIBLE_QOS,
+  RUSTDDS_SAMPLE_LOST,
+  RUSTDDS_SUBSCRIPTION_MATCHED,
+  RUSTDDS_LIVELINESS_LOST,
+  RUSTDDS_OFFERED_DEADLINE_MISSED,
+  RUSTDDS_OFFERED_INCOMPATIBLE_QOS,
+  RUSTDDS_PUBLICATION_MATCHED,
+} RustddsStatusKind;
+
+typedef struct RustddsStatus {
+  RustddsStatusKind kind;
+  int32_t total_count;
+  int32_t total_count_change;
+  int32_t current_count;
+  int32_t current_count_change;
+} RustddsStatus;
+
+typedef void (*RustddsStatusCallback)(const RustddsStatus *status, void *user_data);
+
+RustddsParticipant *rustdds_participant_new(uint16_t domain_id);
+void rustdds_participant_delete(RustddsParticipant *participant);
+
+RustddsTopic *rustdds_topic_new(const RustddsParticipant *participant, const char *name,
+                                const char *type_name, const RustddsQos *qos);
+void rustdds_topic_delete(RustddsTopic *topic);
+
+RustddsWriter *rustdds_writer_new(const RustddsParticipant *participant,
+                                  const RustddsTopic *topic, const RustddsQos *qos);
+void rustdds_writer_delete(RustddsWriter *writer);
+int rustdds_writer_write(const RustddsWriter *writer, const uint8_t *data, size_t len,
+                         uint16_t encoding);
+int rustdds_writer_set_status_callback(RustddsWriter *writer, RustddsStatusCallback callback,
+                                       void *user_data);
+int rustdds_writer_process_status(RustddsWriter *writer);
+
+RustddsReader *rustdds_reader_new(const RustddsParticipant *participant,
+                                  const RustddsTopic *topic, const RustddsQos *qos);
+void rustdds_reader_delete(RustddsReader *reader);
+int rustdds_reader_take(RustddsReader *reader, RustddsSample *sample);
+void rustdds_sample_release(RustddsSample *sample);
+int rustdds_reader_set_status_callback(RustddsReader *reader, RustddsStatusCallback callback,
+                                       void *user_data);
+int rustdds_reader_process_status(RustddsReader *reader);
+
+#ifndef _WIN32
+int rustdds_reader_fd(const RustddsReader *reader);
+int rustdds_reader_status_fd(RustddsReader *reader);
+int rustdds_writer_status_fd(RustddsWriter *writer);
+#endif
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RUSTDDS_H */
@@ -0,0 +1,497 @@
+// Codecs of the submessage bodies, i.e. what follows the submessage header.
+// The byte order is given by the E flag of the submessage header, so decoding
+// takes the header flags, and encoding the byte order.
+
+use crate::{split, DecodeError, EncodeError, LITTLE_ENDIAN_FLAG};
+
+/// Entity id, RTPS spec v2.5 Section 9.3.1. The entity key and kind are
+/// encoded the same in either byte order.
+pub type EntityId = [u8; 4];
+
+// Flags of DATA, RTPS spec v2.5 Section 9.4.5.3
+const DATA_INLINE_QOS_FLAG: u8 = 0x02;
+const DATA_DATA_FLAG: u8 = 0x04;
+const DATA_KEY_FLAG: u8 = 0x08;
+
+// Length of the DATA fields after octetsToInlineQos: readerId, writerId, and
+// writerSN
+const DATA_HEADER_LEN: u16 = 16;
+
+// The parameter that ends a parameter list
+const PID_SENTINEL: u16 = 0x0001;
+
+/// Set of sequence numbers, RTPS spec v2.5 Section 9.4.2.6
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SequenceNumberSet {
+  pub base: i64,
+  /// At most [`MAX_BITS`](Self::MAX_BITS)
+  pub num_bits: u32,
+  /// Bit 0, i.e. the most significant bit of `bitmap[0]`, stands for `base`.
+  /// Words after the first `(num_bits + 31) / 32` are not encoded.
+  pub bitmap: [u32; 8],
+}
+
+impl SequenceNumberSet {
+  pub const MAX_BITS: u32 = 256;
+
+  // How many bitmap words are encoded
+  fn word_count(&self) -> usize {
+    (self.num_bits as usize + 31) / 32
+  }
+
+  fn encoded_len(&self) -> usize {
+    8 + 4 + 4 * self.word_count()
+  }
+
+  fn decode(decoder: &mut Decoder) -> Result<Self, DecodeError> {
+    let base = decoder.sequence_number()?;
+    let num_bits = decoder.u32()?;
+    if num_bits > Self::MAX_BITS {
+      return Err(DecodeError::InvalidValue("numBits"));
+    }
+    let mut set = Self {
+      base,
+      num_bits,
+      bitmap: [0; 8],
+    };
+    for index in 0..set.word_count() {
+      set.bitmap[index] = decoder.u32()?;
+    }
+    Ok(set)
+  }
+
+  fn encode(&self, encoder: &mut Encoder) {
+    encoder.sequence_number(self.base);
+    encoder.u32(self.num_bits);
+    for word in &self.bitmap[..self.word_count()] {
+      encoder.u32(*word);
+    }
+  }
+}
+
+/// DATA submessage body, RTPS spec v2.5 Section 9.4.5.3
+///
+/// The inline QoS parameter list and the serialized payload are not decoded.
+/// `inline_qos` is the parameter list including the sentinel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Data<'a> {
+  pub extra_flags: u16,
+  pub reader_id: EntityId,
+  pub writer_id: EntityId,
+  pub writer_sn: i64,
+  /// Present if the Q flag is set
+  pub inline_qos: Option<&'a [u8]>,
+  /// Present if the D or K flag is set
+  pub serialized_payload: Option<&'a [u8]>,
+}
+
+impl<'a> Data<'a> {
+  /// Decodes the body of a DATA submessage with header flags `flags`.
+  pub fn decode(flags: u8, content: &'a [u8]) -> Result<Self, DecodeError> {
+    let mut decoder = Decoder::new(flags, content);
+    let extra_flags = decoder.u16()?;
+    let octets_to_inline_qos = decoder.u16()?;
+    // The inline QoS must not overlap the fields below
+    if octets_to_inline_qos < DATA_HEADER_LEN {
+      return Err(DecodeError::InvalidValue("octetsToInlineQos"));
+    }
+    let reader_id = decoder.entity_id()?;
+    let writer_id = decoder.entity_id()?;
+    let writer_sn = decoder.sequence_number()?;
+    // Later versions of RTPS may add fields here, which we skip.
+    decoder.take(usize::from(octets_to_inline_qos - DATA_HEADER_LEN))?;
+
+    let inline_qos = if flags & DATA_INLINE_QOS_FLAG != 0 {
+      Some(decoder.parameter_list()?)
+    } else {
+      None
+    };
+    let serialized_payload = if flags & (DATA_DATA_FLAG | DATA_KEY_FLAG) != 0 {
+      Some(decoder.rest())
+    } else {
+      None
+    };
+    Ok(Self {
+      extra_flags,
+      reader_id,
+      writer_id,
+      writer_sn,
+      inline_qos,
+      serialized_payload,
+    })
+  }
+
+  pub fn encoded_len(&self) -> usize {
+    4 + usize::from(DATA_HEADER_LEN)
+      + self.inline_qos.map_or(0, <[u8]>::len)
+      + self.serialized_payload.map_or(0, <[u8]>::len)
+  }
+
+  /// Encodes into the start of `out`. Returns the encoded length.
+  pub fn encode(&self, little_endian: bool, out: &mut [u8]) -> Result<usize, EncodeError> {
+    let mut encoder = Encoder::new(little_endian, out, self.encoded_len())?;
+    encoder.u16(self.extra_flags);
+    encoder.u16(DATA_HEADER_LEN);
+    encoder.bytes(&self.reader_id);
+    encoder.bytes(&self.writer_id);
+    encoder.sequence_number(self.writer_sn);
+    encoder.bytes(self.inline_qos.unwrap_or_default());
+    encoder.bytes(self.serialized_payload.unwrap_or_default());
+    Ok(encoder.len)
+  }
+}
+
+/// HEARTBEAT submessage body, RTPS spec v2.5 Section 9.4.5.7
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Heartbeat {
+  pub reader_id: EntityId,
+  pub writer_id: EntityId,
+  pub first_sn: i64,
+  pub last_sn: i64,
+  pub count: i32,
+}
+
+impl Heartbeat {
+  pub const ENCODED_LEN: usize = 28;
+
+  /// Decodes the body of a HEARTBEAT submessage with header flags `flags`.
+  /// Group information, which follows if the G flag is set, is skipped.
+  pub fn decode(flags: u8, content: &[u8]) -> Result<Self, DecodeError> {
+    let mut decoder = Decoder::new(flags, content);
+    Ok(Self {
+      reader_id: decoder.entity_id()?,
+      writer_id: decoder.entity_id()?,
+      first_sn: decoder.sequence_number()?,
+      last_sn: decoder.sequence_number()?,
+      count: decoder.i32()?,
+    })
+  }
+
+  /// Encodes into the start of `out`. Returns the encoded length.
+  pub fn encode(&self, little_endian: bool, out: &mut [u8]) -> Result<usize, EncodeError> {
+    let mut encoder = Encoder::new(little_endian, out, Self::ENCODED_LEN)?;
+    encoder.bytes(&self.reader_id);
+    encoder.bytes(&self.writer_id);
+    encoder.sequence_number(self.first_sn);
+    encoder.sequence_number(self.last_sn);
+    encoder.i32(self.count);
+    Ok(encoder.len)
+  }
+}
+
+/// ACKNACK submessage body, RTPS spec v2.5 Section 9.4.5.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AckNack {
+  pub reader_id: EntityId,
+  pub writer_id: EntityId,
+  pub reader_sn_state: SequenceNumberSet,
+  pub count: i32,
+}
+
+impl AckNack {
+  /// Decodes the body of an ACKNACK submessage with header flags `flags`.
+  pub fn decode(flags: u8, content: &[u8]) -> Result<Self, DecodeError> {
+    let mut decoder = Decoder::new(flags, content);
+    Ok(Self {
+      reader_id: decoder.entity_id()?,
+      writer_id: decoder.entity_id()?,
+      reader_sn_state: SequenceNumberSet::decode(&mut decoder)?,
+      count: decoder.i32()?,
+    })
+  }
+
+  pub fn encoded_len(&self) -> usize {
+    8 + self.reader_sn_state.encoded_len() + 4
+  }
+
+  /// Encodes into the start of `out`. Returns the encoded length.
+  pub fn encode(&self, little_endian: bool, out: &mut [u8]) -> Result<usize, EncodeError> {
+    let mut encoder = Encoder::new(little_endian, out, self.encoded_len())?;
+    encoder.bytes(&self.reader_id);
+    encoder.bytes(&self.writer_id);
+    self.reader_sn_state.encode(&mut encoder);
+    encoder.i32(self.count);
+    Ok(encoder.len)
+  }
+}
+
+/// GAP submessage body, RTPS spec v2.5 Section 9.4.5.5
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+  pub reader_id: EntityId,
+  pub writer_id: EntityId,
+  pub gap_start: i64,
+  pub gap_list: SequenceNumberSet,
+}
+
+impl Gap {
+  /// Decodes the body of a GAP submessage with header flags `flags`.
+  pub fn decode(flags: u8, content: &[u8]) -> Result<Self, DecodeError> {
+    let mut decoder = Decoder::new(flags, content);
+    Ok(Self {
+      reader_id: decoder.entity_id()?,
+      writer_id: decoder.entity_id()?,
+      gap_start: decoder.sequence_number()?,
+      gap_list: SequenceNumberSet::decode(&mut decoder)?,
+    })
+  }
+
+  pub fn encoded_len(&self) -> usize {
+    8 + 8 + self.gap_list.encoded_len()
+  }
+
+  /// Encodes into the start of `out`. Returns the encoded length.
+  pub fn encode(&self, little_endian: bool, out: &mut [u8]) -> Result<usize, EncodeError> {
+    let mut encoder = Encoder::new(little_endian, out, self.encoded_len())?;
+    encoder.bytes(&self.reader_id);
+    encoder.bytes(&self.writer_id);
+    encoder.sequence_number(self.gap_start);
+    self.gap_list.encode(&mut encoder);
+    Ok(encoder.len)
+  }
+}
+
+// Reads the fields of a submessage body in turn
+struct Decoder<'a> {
+  rest: &'a [u8],
+  little_endian: bool,
+}
+
+impl<'a> Decoder<'a> {
+  fn new(flags: u8, content: &'a [u8]) -> Self {
+    Self {
+      rest: content,
+      little_endian: flags & LITTLE_ENDIAN_FLAG != 0,
+    }
+  }
+
+  fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+    let (bytes, rest) = split(self.rest, len)?;
+    self.rest = rest;
+    Ok(bytes)
+  }
+
+  fn array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+    let mut array = [0; N];
+    array.copy_from_slice(self.take(N)?);
+    Ok(array)
+  }
+
+  fn u16(&mut self) -> Result<u16, DecodeError> {
+    let bytes = self.array()?;
+    Ok(if self.little_endian {
+      u16::from_le_bytes(bytes)
+    } else {
+      u16::from_be_bytes(bytes)
+    })
+  }
+
+  fn u32(&mut self) -> Result<u32, DecodeError> {
+    let bytes = self.array()?;
+    Ok(if self.little_endian {
+      u32::from_le_bytes(bytes)
+    } else {
+      u32::from_be_bytes(bytes)
+    })
+  }
+
+  fn i32(&mut self) -> Result<i32, DecodeError> {
+    self.u32().map(|value| value as i32)
+  }
+
+  fn entity_id(&mut self) -> Result<EntityId, DecodeError> {
+    self.array()
+  }
+
+  // High i32 word first, then low u32 word, RTPS spec v2.5 Section 9.3.2
+  fn sequence_number(&mut self) -> Result<i64, DecodeError> {
+    let high = self.i32()?;
+    let low = self.u32()?;
+    Ok((i64::from(high) << 32) + i64::from(low))
+  }
+
+  // Parameters up to and including the sentinel, whose length is ignored
+  fn parameter_list(&mut self) -> Result<&'a [u8], DecodeError> {
+    let start = self.rest;
+    loop {
+      let parameter_id = self.u16()?;
+      let length = self.u16()?;
+      if parameter_id == PID_SENTINEL {
+        let len = start.len() - self.rest.len();
+        return Ok(&start[..len]);
+      }
+      self.take(usize::from(length))?;
+    }
+  }
+
+  fn rest(self) -> &'a [u8] {
+    self.rest
+  }
+}
+
+// Writes the fields of a submessage body in turn. The output is checked to be
+// long enough at the start.
+struct Encoder<'a> {
+  out: &'a mut [u8],
+  len: usize,
+  little_endian: bool,
+}
+
+impl<'a> Encoder<'a> {
+  fn new(little_endian: bool, out: &'a mut [u8], needed: usize) -> Result<Self, EncodeError> {
+    if out.len() < needed {
+      return Err(EncodeError {
+        needed,
+        available: out.len(),
+      });
+    }
+    Ok(Self {
+      out,
+      len: 0,
+      little_endian,
+    })
+  }
+
+  fn bytes(&mut self, bytes: &[u8]) {
+    self.out[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+    self.len += bytes.len();
+  }
+
+  fn u16(&mut self, value: u16) {
+    self.bytes(&if self.little_endian {
+      value.to_le_bytes()
+    } else {
+      value.to_be_bytes()
+    });
+  }
+
+  fn u32(&mut self, value: u32) {
+    self.bytes(&if self.little_endian {
+      value.to_le_bytes()
+    } else {
+      value.to_be_bytes()
+    });
+  }
+
+  fn i32(&mut self, value: i32) {
+    self.u32(value as u32);
+  }
+
+  fn sequence_number(&mut self, value: i64) {
+    self.i32((value >> 32) as i32);
+    self.u32(value as u32);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn heartbeat_round_trip() {
+    let heartbeat = Heartbeat {
+      reader_id: [0, 0, 3, 0xC7],
+      writer_id: [0, 0, 3, 0xC2],
+      first_sn: 42,
+      last_sn: (1 << 32) + 7,
+      count: 9,
+    };
+    let mut bytes = [0; Heartbeat::ENCODED_LEN];
+    assert_eq!(heartbeat.encode(false, &mut bytes), Ok(28));
+    assert_eq!(bytes[8..16], [0, 0, 0, 0, 0, 0, 0, 42]);
+    assert_eq!(bytes[16..24], [0, 0, 0, 1, 0, 0, 0, 7]);
+    assert_eq!(Heartbeat::decode(0x00, &bytes), Ok(heartbeat));
+
+    assert_eq!(heartbeat.encode(true, &mut bytes), Ok(28));
+    assert_eq!(bytes[24..], [9, 0, 0, 0]);
+    assert_eq!(Heartbeat::decode(0x01, &bytes), Ok(heartbeat));
+
+    assert_eq!(
+      heartbeat.encode(true, &mut [0; 27]),
+      Err(EncodeError {
+        needed: 28,
+        available: 27
+      })
+    );
+  }
+
+  #[test]
+  fn acknack_and_gap_round_trip() {
+    let mut bitmap = [0; 8];
+    bitmap[0] = 0x8000_0001;
+    bitmap[1] = 0x4000_0000;
+    let set = SequenceNumberSet {
+      base: 100,
+      num_bits: 34,
+      bitmap,
+    };
+    let acknack = AckNack {
+      reader_id: [1, 2, 3, 4],
+      writer_id: [5, 6, 7, 8],
+      reader_sn_state: set,
+      count: 3,
+    };
+    let mut bytes = [0; 64];
+    let len = acknack.encode(true, &mut bytes).unwrap();
+    assert_eq!(len, 32);
+    assert_eq!(AckNack::decode(0x01, &bytes[..len]), Ok(acknack));
+    assert!(AckNack::decode(0x01, &bytes[..len - 1]).is_err());
+
+    let gap = Gap {
+      reader_id: [1, 2, 3, 4],
+      writer_id: [5, 6, 7, 8],
+      gap_start: 90,
+      gap_list: set,
+    };
+    let len = gap.encode(false, &mut bytes).unwrap();
+    assert_eq!(len, 36);
+    assert_eq!(Gap::decode(0x00, &bytes[..len]), Ok(gap));
+
+    // At most 256 bits
+    bytes[24..28].copy_from_slice(&257u32.to_be_bytes());
+    assert_eq!(
+      Gap::decode(0x00, &bytes[..len]),
+      Err(DecodeError::InvalidValue("numBits"))
+    );
+  }
+
+  #[test]
+  fn data_round_trip() {
+    let inline_qos = [
+      0x0f, 0x00, 0x04, 0x00, 1, 2, 3, 4, // a parameter of 4 bytes
+      0x01, 0x00, 0x00, 0x00, // PID_SENTINEL
+    ];
+    let payload = [0x00, 0x01, 0x00, 0x00, 7, 7];
+    let data = Data {
+      extra_flags: 0,
+      reader_id: [0, 0, 0, 0],
+      writer_id: [1, 1, 1, 0x02],
+      writer_sn: 5,
+      inline_qos: Some(&inline_qos),
+      serialized_payload: Some(&payload),
+    };
+    let mut bytes = [0; 64];
+    let len = data.encode(true, &mut bytes).unwrap();
+    assert_eq!(len, 20 + inline_qos.len() + payload.len());
+    // E, Q, and D flags
+    assert_eq!(Data::decode(0x07, &bytes[..len]), Ok(data));
+
+    // Without the Q and D flags, the rest is not looked at
+    let header_only = Data {
+      inline_qos: None,
+      serialized_payload: None,
+      ..data
+    };
+    assert_eq!(Data::decode(0x01, &bytes[..len]), Ok(header_only));
+
+    // octetsToInlineQos overlapping writerSN
+    bytes[2] = 15;
+    assert_eq!(
+      Data::decode(0x07, &bytes[..len]),
+      Err(DecodeError::InvalidValue("octetsToInlineQos"))
+    );
+    // octetsToInlineQos past the end
+    bytes[2] = 200;
+    assert!(Data::decode(0x01, &bytes[..len]).is_err());
+  }
+}
@@ -0,0 +1,299 @@
+//! RTPS wire format core of [RustDDS](https://crates.io/crates/rustdds), which
+//! does not need the standard library.
+//!
+//! This is the first part of factoring the RTPS message encoding and decoding
+//! into a `no_std` core, so that the wire format logic can be reused on
+//! embedded targets. So far it has the codecs of the message header, the
+//! submessage header, and the DATA, HEARTBEAT, ACKNACK, and GAP submessage
+//! bodies, and splitting a message into submessages. RustDDS decodes these
+//! with it, and re-exports it as `rustdds::rtps_core`.
+//!
+//! The protocol state machines, i.e. the RTPS Reader and Writer with their
+//! writer and reader proxies, are not here. They are still in RustDDS, which
+//! needs the standard library, and moving them is future work.
+//!
+//! # Example
+//!
+//! ```
+//! use rustdds_rtps_core::{submessages, Header, SubmessageHeader};
+//!
+//! let header = Header {
+//!   protocol_version: [2, 4],
+//!   vendor_id: [0x01, 0x12],
+//!   guid_prefix: [1; 12],
+//! };
+//! let info_ts = SubmessageHeader {
+//!   kind: 0x09,
+//!   flags: 0x03, // little-endian, invalidate: no timestamp follows
+//!   content_length: 0,
+//! };
+//! let mut message = [0; 24];
+//! message[..20].copy_from_slice(&header.encode());
+//! message[20..].copy_from_slice(&info_ts.encode());
+//!
+//! let (decoded, rest) = Header::decode(&message).unwrap();
+//! assert_eq!(decoded, header);
+//! let mut iter = submessages(rest);
+//! assert_eq!(iter.next(), Some(Ok((info_ts, &[][..]))));
+//! assert_eq!(iter.next(), None);
+//! ```
+
+#![no_std]
+
+use core::fmt;
+
+mod bodies;
+
+pub use bodies::{AckNack, Data, EntityId, Gap, Heartbeat, SequenceNumberSet};
+
+/// Length of the RTPS message header in bytes
+pub const HEADER_LEN: usize = 20;
+/// Length of a submessage header in bytes
+pub const SUBMESSAGE_HEADER_LEN: usize = 4;
+
+const PROTOCOL_RTPS: [u8; 4] = *b"RTPS";
+
+// Submessage kinds, whose content length 0 means that there is no content
+const PAD: u8 = 0x01;
+const INFO_TS: u8 = 0x09;
+
+// The E flag of all submessages
+const LITTLE_ENDIAN_FLAG: u8 = 0x01;
+
+/// Why decoding failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+  /// The input ended before `needed` bytes
+  TooShort { needed: usize, available: usize },
+  /// The message does not start with "RTPS"
+  NotRtps,
+  /// The named field has a value that is not allowed
+  InvalidValue(&'static str),
+}
+
+impl fmt::Display for DecodeError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::TooShort { needed, available } => {
+        write!(f, "Needed {needed} bytes, but only {available} are left")
+      }
+      Self::NotRtps => f.write_str("Not an RTPS message"),
+      Self::InvalidValue(field) => write!(f, "Invalid value of {field}"),
+    }
+  }
+}
+
+/// Encoding failed, because the output buffer is shorter than `needed` bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeError {
+  pub needed: usize,
+  pub available: usize,
+}
+
+impl fmt::Display for EncodeError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "Needed {} bytes, but the buffer has only {}",
+      self.needed, self.available
+    )
+  }
+}
+
+/// RTPS message header, RTPS spec v2.5 Section 9.4.4
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+  /// Major and minor version
+  pub protocol_version: [u8; 2],
+  pub vendor_id: [u8; 2],
+  pub guid_prefix: [u8; 12],
+}
+
+impl Header {
+  /// Decodes the header at the start of a message. Returns the header, and
+  /// the rest of the message, i.e. the submessages.
+  pub fn decode(bytes: &[u8]) -> Result<(Self, &[u8]), DecodeError> {
+    let (header, rest) = split(bytes, HEADER_LEN)?;
+    if header[..4] != PROTOCOL_RTPS {
+      return Err(DecodeError::NotRtps);
+    }
+    let mut guid_prefix = [0; 12];
+    guid_prefix.copy_from_slice(&header[8..]);
+    let header = Self {
+      protocol_version: [header[4], header[5]],
+      vendor_id: [header[6], header[7]],
+      guid_prefix,
+    };
+    Ok((header, rest))
+  }
+
+  pub fn encode(&self) -> [u8; HEADER_LEN] {
+    let mut bytes = [0; HEADER_LEN];
+    bytes[..4].copy_from_slice(&PROTOCOL_RTPS);
+    bytes[4..6].copy_from_slice(&self.protocol_version);
+    bytes[6..8].copy_from_slice(&self.vendor_id);
+    bytes[8..].copy_from_slice(&self.guid_prefix);
+    bytes
+  }
+}
+
+/// Submessage header, RTPS spec v2.5 Section 9.4.5.1
+///
+/// The byte order of `content_length`, and of the submessage content, is
+/// given by the E flag in `flags`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubmessageHeader {
+  pub kind: u8,
+  pub flags: u8,
+  pub content_length: u16,
+}
+
+impl SubmessageHeader {
+  /// Is the E flag set, i.e. is the submessage little-endian
+  pub fn is_little_endian(&self) -> bool {
+    self.flags & LITTLE_ENDIAN_FLAG != 0
+  }
+
+  /// Decodes the submessage header at the start of `bytes`. Returns the
+  /// header, and the rest of `bytes`.
+  pub fn decode(bytes: &[u8]) -> Result<(Self, &[u8]), DecodeError> {
+    let (header, rest) = split(bytes, SUBMESSAGE_HEADER_LEN)?;
+    let (kind, flags) = (header[0], header[1]);
+    let length_bytes = [header[2], header[3]];
+    let content_length = if flags & LITTLE_ENDIAN_FLAG != 0 {
+      u16::from_le_bytes(length_bytes)
+    } else {
+      u16::from_be_bytes(length_bytes)
+    };
+    let header = Self {
+      kind,
+      flags,
+      content_length,
+    };
+    Ok((header, rest))
+  }
+
+  pub fn encode(&self) -> [u8; SUBMESSAGE_HEADER_LEN] {
+    let length_bytes = if self.is_little_endian() {
+      self.content_length.to_le_bytes()
+    } else {
+      self.content_length.to_be_bytes()
+    };
+    [self.kind, self.flags, length_bytes[0], length_bytes[1]]
+  }
+}
+
+/// Splits the submessages of a message, i.e. what follows the message header,
+/// into submessage headers and contents.
+pub fn submessages(bytes: &[u8]) -> Submessages<'_> {
+  Submessages { rest: bytes }
+}
+
+/// Iterator over the submessages of a message, see [`submessages`]. Stops
+/// after the first error.
+#[derive(Debug, Clone)]
+pub struct Submessages<'a> {
+  rest: &'a [u8],
+}
+
+impl<'a> Iterator for Submessages<'a> {
+  type Item = Result<(SubmessageHeader, &'a [u8]), DecodeError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.rest.is_empty() {
+      return None;
+    }
+    let result = SubmessageHeader::decode(self.rest).and_then(|(header, rest)| {
+      // Content length 0 means that the submessage extends to the end of the
+      // message, except for PAD and INFO_TS, RTPS spec v2.5 Section 9.4.5.1.3
+      let content_length = match (header.content_length, header.kind) {
+        (0, PAD | INFO_TS) => 0,
+        (0, _) => rest.len(),
+        (length, _) => usize::from(length),
+      };
+      let (content, rest) = split(rest, content_length)?;
+      self.rest = rest;
+      Ok((header, content))
+    });
+    if result.is_err() {
+      self.rest = &[];
+    }
+    Some(result)
+  }
+}
+
+fn split(bytes: &[u8], len: usize) -> Result<(&[u8], &[u8]), DecodeError> {
+  if bytes.len() < len {
+    Err(DecodeError::TooShort {
+      needed: len,
+      available: bytes.len(),
+    })
+  } else {
+    Ok(bytes.split_at(len))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  extern crate std;
+
+  use std::{vec, vec::Vec};
+
+  use super::*;
+
+  #[test]
+  fn header_round_trip() {
+    let header = Header {
+      protocol_version: [2, 4],
+      vendor_id: [0x01, 0x12],
+      guid_prefix: [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+    };
+    let bytes = header.encode();
+    assert_eq!(&bytes[..8], b"RTPS\x02\x04\x01\x12");
+    assert_eq!(Header::decode(&bytes), Ok((header, &[][..])));
+
+    let mut not_rtps = bytes;
+    not_rtps[0] = b'X';
+    assert_eq!(Header::decode(&not_rtps), Err(DecodeError::NotRtps));
+    assert_eq!(
+      Header::decode(&bytes[..19]),
+      Err(DecodeError::TooShort {
+        needed: 20,
+        available: 19
+      })
+    );
+  }
+
+  #[test]
+  fn submessages_are_split() {
+    let submessages_bytes = [
+      0x09, 0x03, 0x00, 0x00, // INFO_TS without timestamp: no content
+      0x0e, 0x01, 0x04, 0x00, // INFO_DST, little-endian, 4 bytes
+      1, 2, 3, 4, //
+      0x15, 0x00, 0x00, 0x00, // DATA, content up to the end of the message
+      5, 6, 7,
+    ];
+    let parts: Vec<_> = submessages(&submessages_bytes)
+      .map(|r| r.map(|(header, content)| (header.kind, content.to_vec())))
+      .collect();
+    assert_eq!(
+      parts,
+      vec![
+        Ok((0x09, vec![])),
+        Ok((0x0e, vec![1, 2, 3, 4])),
+        Ok((0x15, vec![5, 6, 7])),
+      ]
+    );
+
+    // Content longer than the message
+    let mut iter = submessages(&submessages_bytes[4..10]);
+    assert_eq!(
+      iter.next(),
+      Some(Err(DecodeError::TooShort {
+        needed: 4,
+        available: 2
+      }))
+    );
+    assert_eq!(iter.next(), None);
+  }
+}
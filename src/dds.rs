@@ -7,11 +7,21 @@ mod helpers;
 pub(crate) mod participant;
 pub use participant::DomainParticipant;
 
+pub(crate) mod checksum;
+pub(crate) mod compression;
 pub(crate) mod dds_entity;
 pub(crate) mod ddsdata;
+pub(crate) mod delivery;
+pub(crate) mod delivery_journal;
+pub(crate) mod deserialization_pool;
+pub(crate) mod fragment_stream;
 pub(crate) mod pubsub;
 pub(crate) mod readcondition;
+pub(crate) mod reader_multiplexer;
 pub(crate) mod topic;
+#[cfg(feature = "opentelemetry")]
+pub(crate) mod trace_context;
+pub(crate) mod type_support;
 pub(crate) mod typedesc;
 
 pub mod result;
@@ -8,6 +8,7 @@
 /// to data.
 pub mod no_key {
   use bytes::Bytes;
+  use serde::de::DeserializeSeed;
 
   use crate::RepresentationIdentifier;
 
@@ -28,11 +29,16 @@ pub mod no_key {
 
     /// This method has a default implementation, but the default will make a
     /// copy of all the input data in memory and then call from_bytes() .
+    /// DataReaders call this, so that adapters can keep a reference to the
+    /// received buffers instead of copying them.
     // In order to avoid the copy, implement also this method.
     fn from_vec_bytes(
       input_vec_bytes: &[Bytes],
       encoding: RepresentationIdentifier,
     ) -> Result<D, Self::Error> {
+      if let [single] = input_vec_bytes {
+        return Self::from_bytes(single, encoding);
+      }
       let total_len = input_vec_bytes.iter().map(Bytes::len).sum();
       let mut total_payload = Vec::with_capacity(total_len);
       for iv in input_vec_bytes {
@@ -42,6 +48,23 @@ pub mod no_key {
     }
   }
 
+  /// A [`DeserializerAdapter`] that can also deserialize with a serde
+  /// [`DeserializeSeed`], e.g. into an arena or a reused buffer, instead of
+  /// into `D`. See
+  /// [`DataReader::take_with_deserializer`](crate::with_key::DataReader::take_with_deserializer).
+  pub trait SeedDeserializerAdapter<D>: DeserializerAdapter<D> {
+    /// Deserialize data from bytes with `seed`.
+    /// `encoding` must be something given by `supported_encodings()`, or
+    /// implementation may fail with Err or `panic!()`.
+    fn from_bytes_seed<S, T>(
+      input_bytes: &[u8],
+      encoding: RepresentationIdentifier,
+      seed: S,
+    ) -> Result<T, Self::Error>
+    where
+      S: for<'de> DeserializeSeed<'de, Value = T>;
+  }
+
   /// trait for connecting a Serializer implementation and DataWriter
   /// together - no_key version.
   pub trait SerializerAdapter<D> {
@@ -59,6 +82,7 @@ pub mod with_key {
 
   use crate::{Keyed, RepresentationIdentifier};
   use super::no_key;
+  pub use super::no_key::SeedDeserializerAdapter;
 
   /// trait for connecting a Deserializer implementation and DataReader
   /// together - with_key version.
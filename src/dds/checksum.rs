@@ -0,0 +1,55 @@
+// End-to-end checksum of serialized payloads according to the
+// PayloadChecksum QoS policy.
+//
+// The checksum covers the data after the encapsulation header, as sent, i.e.
+// after possible compression. It is a vendor-specific inline QoS parameter,
+// whose value is the CRC-32C as big-endian u32.
+
+use crate::{
+  dds::{qos::policy::PayloadChecksum, with_key::datawriter::InlineQosParameter},
+  structure::parameter_id::ParameterId,
+};
+
+const PARAMETER_ID: u16 = ParameterId::PID_RUSTDDS_PAYLOAD_CHECKSUM.value();
+
+pub(crate) fn to_inline_qos(checksum: PayloadChecksum, payload: &[u8]) -> InlineQosParameter {
+  let value = match checksum {
+    PayloadChecksum::Crc32c => crc32c::crc32c(payload),
+  };
+  InlineQosParameter {
+    parameter_id: PARAMETER_ID,
+    value: value.to_be_bytes().to_vec(),
+  }
+}
+
+// Checks the payload against the checksum parameter. Payloads without a
+// checksum pass.
+pub(crate) fn is_intact(parameters: &[InlineQosParameter], payload: &[u8]) -> bool {
+  is_intact_crc32c(parameters, crc32c::crc32c(payload))
+}
+
+// Like is_intact(), but with the CRC-32C of the payload already computed,
+// e.g. piecewise over a streamed payload.
+pub(crate) fn is_intact_crc32c(parameters: &[InlineQosParameter], crc32c: u32) -> bool {
+  match parameters.iter().find(|p| p.parameter_id == PARAMETER_ID) {
+    None => true,
+    Some(parameter) => parameter.value == crc32c.to_be_bytes(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn checksum_detects_corruption() {
+    let mut payload = b"123456789".to_vec();
+    let parameter = to_inline_qos(PayloadChecksum::Crc32c, &payload);
+    // The standard check value of CRC-32C
+    assert_eq!(parameter.value, 0xe306_9283_u32.to_be_bytes());
+    assert!(is_intact(std::slice::from_ref(&parameter), &payload));
+    assert!(is_intact(&[], &payload));
+    payload[3] ^= 0x10;
+    assert!(!is_intact(&[parameter], &payload));
+  }
+}
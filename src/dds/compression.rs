@@ -0,0 +1,191 @@
+// Compression of serialized payloads according to the Compression QoS policy.
+//
+// Only the data after the encapsulation header is compressed, so a compressed
+// payload keeps its representation identifier. The sample carries a
+// vendor-specific inline QoS parameter, whose value is the algorithm and the
+// length of the uncompressed data, both as big-endian u32.
+
+#[cfg(feature = "compression")]
+use std::io::{Read, Write};
+
+use bytes::Bytes;
+
+use crate::{
+  dds::{qos::policy::CompressionAlgorithm, with_key::datawriter::InlineQosParameter},
+  messages::submessages::elements::parameter_list::ParameterList,
+  structure::parameter_id::ParameterId,
+};
+
+const PARAMETER_ID: u16 = ParameterId::PID_RUSTDDS_COMPRESSED_PAYLOAD.value();
+
+// Gives the compressed data and the parameter to send with it, or None if
+// compression does not make `data` smaller, or is not available in this
+// build.
+pub(crate) fn compress(
+  algorithm: CompressionAlgorithm,
+  data: &[u8],
+) -> Option<(Bytes, InlineQosParameter)> {
+  let uncompressed_len = u32::try_from(data.len()).ok()?;
+  let compressed = encode(algorithm, data)?;
+  if compressed.len() >= data.len() {
+    return None;
+  }
+  let mut value = algorithm_id(algorithm).to_be_bytes().to_vec();
+  value.extend_from_slice(&uncompressed_len.to_be_bytes());
+  let parameter = InlineQosParameter {
+    parameter_id: PARAMETER_ID,
+    value,
+  };
+  Some((Bytes::from(compressed), parameter))
+}
+
+// The compression parameter of a sample, if it was compressed
+pub(crate) fn parameter(parameters: &[InlineQosParameter]) -> Option<InlineQosParameter> {
+  parameters
+    .iter()
+    .find(|p| p.parameter_id == PARAMETER_ID)
+    .cloned()
+}
+
+// The uncompressed length that the inline QoS of a compressed sample claims
+pub(crate) fn uncompressed_len(inline_qos: &ParameterList) -> Option<usize> {
+  inline_qos
+    .parameters
+    .iter()
+    .find(|p| p.parameter_id.value() == PARAMETER_ID)
+    .and_then(|p| parse(&p.value))
+    .map(|(_algorithm, uncompressed_len)| uncompressed_len)
+}
+
+// Decompresses `data`, which was compressed as `parameter` tells.
+pub(crate) fn decompress(data: &[u8], parameter: &InlineQosParameter) -> Result<Bytes, String> {
+  let (algorithm, uncompressed_len) = parse(&parameter.value)
+    .ok_or_else(|| format!("Unknown payload compression {:x?}", parameter.value))?;
+  let decompressed = decode(algorithm, data, uncompressed_len)?;
+  if decompressed.len() == uncompressed_len {
+    Ok(Bytes::from(decompressed))
+  } else {
+    Err(format!(
+      "Decompressed payload is {} bytes, expected {uncompressed_len}",
+      decompressed.len()
+    ))
+  }
+}
+
+fn algorithm_id(algorithm: CompressionAlgorithm) -> u32 {
+  match algorithm {
+    CompressionAlgorithm::Zlib => 0,
+    CompressionAlgorithm::Zstd => 1,
+  }
+}
+
+fn parse(value: &[u8]) -> Option<(CompressionAlgorithm, usize)> {
+  let algorithm = match u32::from_be_bytes(value.get(0..4)?.try_into().ok()?) {
+    0 => CompressionAlgorithm::Zlib,
+    1 => CompressionAlgorithm::Zstd,
+    _ => return None,
+  };
+  let uncompressed_len = u32::from_be_bytes(value.get(4..8)?.try_into().ok()?);
+  Some((algorithm, uncompressed_len as usize))
+}
+
+#[cfg(feature = "compression")]
+fn encode(algorithm: CompressionAlgorithm, data: &[u8]) -> Option<Vec<u8>> {
+  match algorithm {
+    CompressionAlgorithm::Zlib => {
+      let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+      encoder.write_all(data).ok()?;
+      encoder.finish().ok()
+    }
+    CompressionAlgorithm::Zstd => zstd::bulk::compress(data, zstd::DEFAULT_COMPRESSION_LEVEL).ok(),
+  }
+}
+
+#[cfg(not(feature = "compression"))]
+fn encode(_algorithm: CompressionAlgorithm, _data: &[u8]) -> Option<Vec<u8>> {
+  None
+}
+
+// Decompresses at most one byte more than the expected length, so that the
+// caller can detect a wrong length. The expected length comes from the sender,
+// so the buffer grows with the actual output instead of being allocated up
+// front. The message receiver has already checked the expected length against
+// MessageReceiverLimits::max_sample_size.
+#[cfg(feature = "compression")]
+fn decode(
+  algorithm: CompressionAlgorithm,
+  data: &[u8],
+  uncompressed_len: usize,
+) -> Result<Vec<u8>, String> {
+  let limit = uncompressed_len as u64 + 1;
+  let mut decompressed = Vec::new();
+  match algorithm {
+    CompressionAlgorithm::Zlib => flate2::read::ZlibDecoder::new(data)
+      .take(limit)
+      .read_to_end(&mut decompressed)
+      .map_err(|e| format!("zlib decompression failed: {e}"))?,
+    CompressionAlgorithm::Zstd => zstd::stream::read::Decoder::new(data)
+      .and_then(|decoder| decoder.take(limit).read_to_end(&mut decompressed))
+      .map_err(|e| format!("zstd decompression failed: {e}"))?,
+  };
+  Ok(decompressed)
+}
+
+#[cfg(not(feature = "compression"))]
+fn decode(
+  _algorithm: CompressionAlgorithm,
+  _data: &[u8],
+  _uncompressed_len: usize,
+) -> Result<Vec<u8>, String> {
+  Err("Received a compressed payload, but feature \"compression\" is not enabled".to_string())
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod tests {
+  use super::*;
+  use crate::messages::submessages::elements::parameter::Parameter;
+
+  #[test]
+  fn compression_round_trip() {
+    let data = "{\"name\": \"value\", \"other\": 12345}, ".repeat(100);
+    for algorithm in [CompressionAlgorithm::Zlib, CompressionAlgorithm::Zstd] {
+      let (compressed, param) = compress(algorithm, data.as_bytes()).unwrap();
+      assert!(compressed.len() * 10 < data.len());
+      assert_eq!(parameter(std::slice::from_ref(&param)), Some(param.clone()));
+      let decompressed = decompress(&compressed, &param).unwrap();
+      assert_eq!(decompressed, data.as_bytes());
+    }
+  }
+
+  #[test]
+  fn incompressible_data_is_not_compressed() {
+    let data: Vec<u8> = (0..64).collect();
+    assert!(compress(CompressionAlgorithm::Zstd, &data).is_none());
+  }
+
+  #[test]
+  fn wrong_length_is_rejected() {
+    let data = vec![7; 1000];
+    let (compressed, mut param) = compress(CompressionAlgorithm::Zlib, &data).unwrap();
+    param.value[4..8].copy_from_slice(&999_u32.to_be_bytes());
+    assert!(decompress(&compressed, &param).is_err());
+  }
+
+  #[test]
+  fn claimed_length_does_not_allocate() {
+    let data = vec![7; 1000];
+    for algorithm in [CompressionAlgorithm::Zlib, CompressionAlgorithm::Zstd] {
+      let (compressed, mut param) = compress(algorithm, &data).unwrap();
+      param.value[4..8].copy_from_slice(&u32::MAX.to_be_bytes());
+      let inline_qos = ParameterList {
+        parameters: vec![Parameter {
+          parameter_id: ParameterId::PID_RUSTDDS_COMPRESSED_PAYLOAD,
+          value: param.value.clone(),
+        }],
+      };
+      assert_eq!(uncompressed_len(&inline_qos), Some(u32::MAX as usize));
+      // Fails on the length, after decompressing only the actual data
+      assert!(decompress(&compressed, &param).is_err());
+    }
+  }
+}
@@ -13,13 +13,19 @@ use crate::{
 // Contents of a DATA submessage or several DATAFRAG submessages. This is either
 // a new sample, or key, or a key hash. The latter two are used to indicate
 // dispose or unregister.
+//
+// Data and key may also be accompanied by a key hash (PID_KEY_HASH in inline
+// QoS). This is `None` for NO_KEY topics, or if the remote writer did not send
+// one.
 pub enum DDSData {
   Data {
     serialized_payload: SerializedPayload,
+    key_hash: Option<KeyHash>,
   },
   DisposeByKey {
     change_kind: ChangeKind,
     key: SerializedPayload,
+    key_hash: Option<KeyHash>,
   },
   DisposeByKeyHash {
     change_kind: ChangeKind,
@@ -29,10 +35,17 @@ pub enum DDSData {
 
 impl DDSData {
   pub fn new(serialized_payload: SerializedPayload) -> Self {
-    Self::Data { serialized_payload }
+    Self::Data {
+      serialized_payload,
+      key_hash: None,
+    }
   }
   pub fn new_disposed_by_key(change_kind: ChangeKind, key: SerializedPayload) -> Self {
-    Self::DisposeByKey { change_kind, key }
+    Self::DisposeByKey {
+      change_kind,
+      key,
+      key_hash: None,
+    }
   }
 
   pub fn new_disposed_by_key_hash(change_kind: ChangeKind, key_hash: KeyHash) -> Self {
@@ -42,6 +55,28 @@ impl DDSData {
     }
   }
 
+  // Attach a key hash to Data or DisposeByKey. DisposeByKeyHash already has
+  // one, so it is not modified.
+  pub fn with_key_hash(mut self, new_key_hash: Option<KeyHash>) -> Self {
+    match self {
+      DDSData::Data {
+        ref mut key_hash, ..
+      }
+      | DDSData::DisposeByKey {
+        ref mut key_hash, ..
+      } => *key_hash = new_key_hash,
+      DDSData::DisposeByKeyHash { .. } => (),
+    }
+    self
+  }
+
+  pub fn key_hash(&self) -> Option<KeyHash> {
+    match self {
+      DDSData::Data { key_hash, .. } | DDSData::DisposeByKey { key_hash, .. } => *key_hash,
+      DDSData::DisposeByKeyHash { key_hash, .. } => Some(*key_hash),
+    }
+  }
+
   pub fn change_kind(&self) -> ChangeKind {
     match self {
       DDSData::Data {..} /*| DDSData::DataFrags {..}*/ => ChangeKind::Alive,
@@ -52,7 +87,9 @@ impl DDSData {
   // What is the serialized size of this?
   pub fn payload_size(&self) -> usize {
     match self {
-      DDSData::Data { serialized_payload } => serialized_payload.len_serialized(),
+      DDSData::Data {
+        serialized_payload, ..
+      } => serialized_payload.len_serialized(),
       DDSData::DisposeByKey { key, .. } => key.len_serialized(),
       DDSData::DisposeByKeyHash { .. } => 16,
       // This is a fundamental constant of the RTPS
@@ -68,7 +105,9 @@ impl DDSData {
   #[cfg(test)]
   fn payload_bytes(&self) -> Bytes {
     match &self {
-      DDSData::Data { serialized_payload } => serialized_payload.value.clone(),
+      DDSData::Data {
+        serialized_payload, ..
+      } => serialized_payload.value.clone(),
       DDSData::DisposeByKey { key, .. } => key.value.clone(),
       DDSData::DisposeByKeyHash { key_hash, .. } => Bytes::from(key_hash.to_vec()),
     }
@@ -76,7 +115,9 @@ impl DDSData {
 
   pub fn bytes_slice(&self, from: usize, to: usize) -> Bytes {
     match &self {
-      DDSData::Data { serialized_payload } => serialized_payload.bytes_slice(from, to),
+      DDSData::Data {
+        serialized_payload, ..
+      } => serialized_payload.bytes_slice(from, to),
       DDSData::DisposeByKey { key, .. } => key.bytes_slice(from, to),
       DDSData::DisposeByKeyHash { key_hash, .. } => {
         // This may be a bit of overengineering, since this
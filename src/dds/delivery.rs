@@ -0,0 +1,274 @@
+// Delivery confirmation callbacks, see DataWriter::set_delivery_listener().
+//
+// The RTPS Writer keeps a DeliveryTracker, which follows the acknowledgements
+// of the matched reliable readers. Samples are reported in sequence number
+// order, when every reliable reader has acknowledged them or they have been
+// given up. The reports go through a channel to a thread that calls the
+// listener, so that the listener may block, e.g. to write again, without
+// stalling the event loop.
+
+use std::{
+  cmp::max,
+  collections::BTreeMap,
+  sync::{mpsc, Arc, Mutex},
+  thread,
+};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::structure::sequence_number::SequenceNumber;
+
+/// Receives delivery confirmations of the samples written by a reliable
+/// DataWriter, see
+/// [`DataWriter::set_delivery_listener`](crate::with_key::DataWriter::set_delivery_listener).
+///
+/// Every sample written after the listener was set is reported exactly once,
+/// to either method, and in increasing sequence number order.
+pub trait DeliveryListener: Send + Sync {
+  /// All matched reliable DataReaders have acknowledged the sample.
+  fn on_acknowledged(&self, sequence_number: SequenceNumber);
+
+  /// The sample will not be delivered to all matched reliable DataReaders.
+  fn on_dropped(&self, sequence_number: SequenceNumber, reason: DropReason);
+}
+
+/// Why a sample was not delivered, see [`DeliveryListener::on_dropped`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropReason {
+  /// The [`Lifespan`](crate::policy::Lifespan) of the sample passed before
+  /// it could be delivered.
+  Expired,
+  /// The sample was removed from the history of the DataWriter, e.g. by
+  /// [`History::KeepLast`](crate::policy::History::KeepLast), before it was
+  /// delivered.
+  Removed,
+  /// The DataWriter was deleted before the sample was delivered.
+  WriterDeleted,
+}
+
+// A sample and its fate. `None` is acknowledged.
+type Delivery = (SequenceNumber, Option<DropReason>);
+
+// Where the reports of a listener go
+#[derive(Clone)]
+struct DeliverySink {
+  // The first sample written after the listener was set
+  first: SequenceNumber,
+  reports: mpsc::Sender<Delivery>,
+}
+
+// Shared by the DataWriter and its RTPS Writer
+#[derive(Clone, Default)]
+pub(crate) struct DeliveryListenerSlot(Arc<Mutex<Option<DeliverySink>>>);
+
+impl DeliveryListenerSlot {
+  // Reports the samples from `first` on to `listener`, which is called from a
+  // thread of its own. This replaces the previous listener, whose thread
+  // finishes the reports it already has.
+  pub fn set(&self, listener: Arc<dyn DeliveryListener>, first: SequenceNumber) {
+    let (reports, report_receiver) = mpsc::channel::<Delivery>();
+    let spawned = thread::Builder::new()
+      .name("RustDDS delivery listener".to_string())
+      .spawn(move || {
+        for (sequence_number, dropped) in report_receiver {
+          match dropped {
+            Some(reason) => listener.on_dropped(sequence_number, reason),
+            None => listener.on_acknowledged(sequence_number),
+          }
+        }
+      });
+    match spawned {
+      Ok(_) => *self.lock() = Some(DeliverySink { first, reports }),
+      Err(e) => error!("Cannot start delivery listener thread: {e:?}"),
+    }
+  }
+
+  pub fn clear(&self) {
+    *self.lock() = None;
+  }
+
+  fn sink(&self) -> Option<DeliverySink> {
+    self.lock().clone()
+  }
+
+  fn lock(&self) -> std::sync::MutexGuard<'_, Option<DeliverySink>> {
+    self.0.lock().unwrap_or_else(|e| e.into_inner())
+  }
+}
+
+pub(crate) struct DeliveryTracker {
+  listener: DeliveryListenerSlot,
+  // Samples before this have been reported
+  reported_before: SequenceNumber,
+  // Not yet reported samples that some reader will not get
+  dropped: BTreeMap<SequenceNumber, DropReason>,
+}
+
+impl DeliveryTracker {
+  pub fn new(listener: DeliveryListenerSlot) -> Self {
+    Self {
+      listener,
+      reported_before: SequenceNumber::new(1),
+      dropped: BTreeMap::new(),
+    }
+  }
+
+  // Records that `sequence_number` will not be delivered to some reader. The
+  // first reason is kept.
+  pub fn dropped(&mut self, sequence_number: SequenceNumber, reason: DropReason) {
+    if sequence_number >= self.reported_before {
+      self.dropped.entry(sequence_number).or_insert(reason);
+    }
+  }
+
+  // Reports the samples before `acked_before`, which all readers have either
+  // acknowledged or been told they will not get.
+  pub fn acked_before(&mut self, acked_before: SequenceNumber) {
+    self.report(acked_before, None);
+  }
+
+  // Reports the samples up to and including `last` as dropped for `reason`,
+  // unless already known to be dropped for some other reason.
+  pub fn drop_up_to(&mut self, last: SequenceNumber, reason: DropReason) {
+    self.report(last.plus_1(), Some(reason));
+  }
+
+  fn report(&mut self, before: SequenceNumber, unacked_reason: Option<DropReason>) {
+    if before <= self.reported_before {
+      return;
+    }
+    let still_pending = self.dropped.split_off(&before);
+    let dropped = std::mem::replace(&mut self.dropped, still_pending);
+    if let Some(sink) = self.listener.sink() {
+      // Samples written before the listener was set are not reported
+      let mut sn = max(self.reported_before, sink.first);
+      while sn < before {
+        let report = (sn, dropped.get(&sn).copied().or(unacked_reason));
+        if sink.reports.send(report).is_err() {
+          break; // The listener thread is gone, e.g. it panicked
+        }
+        sn = sn.plus_1();
+      }
+    }
+    self.reported_before = before;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use super::*;
+
+  type Report = (i64, Option<DropReason>);
+
+  struct Recorder(Mutex<mpsc::Sender<Report>>);
+
+  impl DeliveryListener for Recorder {
+    fn on_acknowledged(&self, sequence_number: SequenceNumber) {
+      let _ = self.0.lock().unwrap().send((sequence_number.into(), None));
+    }
+    fn on_dropped(&self, sequence_number: SequenceNumber, reason: DropReason) {
+      let _ = self
+        .0
+        .lock()
+        .unwrap()
+        .send((sequence_number.into(), Some(reason)));
+    }
+  }
+
+  // A listener, and what it has been told so far
+  fn recorder() -> (Arc<Recorder>, impl Fn() -> Vec<Report>) {
+    let (sender, receiver) = mpsc::channel();
+    let reports = move || {
+      // The listener thread is quick, but not synchronous
+      let mut reports = Vec::new();
+      while let Ok(report) = receiver.recv_timeout(Duration::from_millis(100)) {
+        reports.push(report);
+      }
+      reports
+    };
+    (Arc::new(Recorder(Mutex::new(sender))), reports)
+  }
+
+  #[test]
+  fn deliveries_are_reported_in_order() {
+    let (recorder, reports) = recorder();
+    let slot = DeliveryListenerSlot::default();
+    slot.set(recorder.clone(), SequenceNumber::new(1));
+    let mut tracker = DeliveryTracker::new(slot.clone());
+
+    tracker.dropped(SequenceNumber::new(2), DropReason::Expired);
+    tracker.dropped(SequenceNumber::new(2), DropReason::Removed);
+    tracker.acked_before(SequenceNumber::new(3));
+    // Going backwards reports nothing
+    tracker.acked_before(SequenceNumber::new(2));
+    // Already reported
+    tracker.dropped(SequenceNumber::new(1), DropReason::Removed);
+    tracker.dropped(SequenceNumber::new(4), DropReason::Removed);
+    tracker.drop_up_to(SequenceNumber::new(5), DropReason::WriterDeleted);
+    assert_eq!(
+      reports(),
+      vec![
+        (1, None),
+        (2, Some(DropReason::Expired)),
+        (3, Some(DropReason::WriterDeleted)),
+        (4, Some(DropReason::Removed)),
+        (5, Some(DropReason::WriterDeleted)),
+      ]
+    );
+
+    // Without a listener, nothing is reported later either
+    slot.clear();
+    tracker.acked_before(SequenceNumber::new(8));
+    slot.set(recorder, SequenceNumber::new(1));
+    tracker.acked_before(SequenceNumber::new(9));
+    assert_eq!(reports(), vec![(8, None)]);
+  }
+
+  #[test]
+  fn samples_written_before_the_listener_are_not_reported() {
+    let (recorder, reports) = recorder();
+    let slot = DeliveryListenerSlot::default();
+    let mut tracker = DeliveryTracker::new(slot.clone());
+    tracker.acked_before(SequenceNumber::new(2));
+
+    // Samples 1..=4 were written before the listener was set
+    slot.set(recorder, SequenceNumber::new(5));
+    tracker.acked_before(SequenceNumber::new(4));
+    tracker.acked_before(SequenceNumber::new(7));
+    assert_eq!(reports(), vec![(5, None), (6, None)]);
+  }
+
+  #[test]
+  fn listener_may_block_without_blocking_the_tracker() {
+    struct Blocking(Mutex<mpsc::Receiver<()>>, Mutex<mpsc::Sender<i64>>);
+    impl DeliveryListener for Blocking {
+      fn on_acknowledged(&self, sequence_number: SequenceNumber) {
+        self.0.lock().unwrap().recv().unwrap();
+        self.1.lock().unwrap().send(sequence_number.into()).unwrap();
+      }
+      fn on_dropped(&self, _sequence_number: SequenceNumber, _reason: DropReason) {}
+    }
+    let (unblock, unblock_receiver) = mpsc::channel();
+    let (acked_sender, acked) = mpsc::channel();
+    let slot = DeliveryListenerSlot::default();
+    slot.set(
+      Arc::new(Blocking(
+        Mutex::new(unblock_receiver),
+        Mutex::new(acked_sender),
+      )),
+      SequenceNumber::new(1),
+    );
+    let mut tracker = DeliveryTracker::new(slot);
+
+    // Returns while the listener is still blocked in the first report
+    tracker.acked_before(SequenceNumber::new(3));
+    assert!(acked.try_recv().is_err());
+    unblock.send(()).unwrap();
+    unblock.send(()).unwrap();
+    assert_eq!(acked.recv_timeout(Duration::from_secs(1)), Ok(1));
+    assert_eq!(acked.recv_timeout(Duration::from_secs(1)), Ok(2));
+  }
+}
@@ -0,0 +1,254 @@
+// Journal of the samples delivered to the application, see
+// SimpleDataReader::set_delivery_journal().
+//
+// The journals belong to the DomainParticipant and are found by name, so that
+// a DataReader that is deleted and created again can continue where the
+// previous one stopped, instead of delivering again the samples that it
+// receives again e.g. from TransientLocal history.
+//
+// For each writer, the journal keeps the ranges of delivered sequence numbers.
+// Nothing is known of the samples outside them, so those are delivered.
+// Sequence numbers skipped by a writer, or lost in BestEffort communication,
+// split the ranges, so their number is bounded by forgetting the oldest ones.
+
+use std::{
+  collections::BTreeMap,
+  sync::{Arc, Mutex, MutexGuard},
+  time::{Duration, Instant},
+};
+
+use crate::structure::{guid::GUID, sequence_number::SequenceNumber};
+
+// How many ranges of delivered sequence numbers are remembered per writer
+const MAX_DELIVERED_RANGES: usize = 1024;
+
+// The delivery journals of one DomainParticipant
+#[derive(Debug, Default)]
+pub(crate) struct DeliveryJournals {
+  journals: Mutex<BTreeMap<String, Arc<DeliveryJournal>>>,
+}
+
+impl DeliveryJournals {
+  // The journal called `name`, created if it does not exist. Entries of
+  // writers that have not delivered anything within `retention` are
+  // forgotten.
+  pub fn journal(&self, name: &str, retention: Duration) -> Arc<DeliveryJournal> {
+    let now = Instant::now();
+    let mut journals = self.journals.lock().unwrap_or_else(|e| e.into_inner());
+    // Also drop the journals that nobody uses and that have forgotten
+    // everything
+    journals.retain(|_, journal| {
+      let mut inner = journal.lock();
+      inner.prune(now);
+      Arc::strong_count(journal) > 1 || !inner.writers.is_empty()
+    });
+    let journal = journals.entry(name.to_string()).or_default();
+    journal.lock().retention = retention;
+    journal.clone()
+  }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct DeliveryJournal {
+  inner: Mutex<Journal>,
+}
+
+#[derive(Debug, Default)]
+struct Journal {
+  retention: Duration,
+  writers: BTreeMap<GUID, WriterJournal>,
+  // Writers that have been idle for longer than retention are removed at the
+  // first access after this
+  next_prune: Option<Instant>,
+}
+
+impl Journal {
+  fn prune(&mut self, now: Instant) {
+    if self.next_prune.is_some_and(|next| now <= next) {
+      return;
+    }
+    let retention = self.retention;
+    self.writers.retain(|_, w| !w.is_expired(now, retention));
+    self.next_prune = self
+      .writers
+      .values()
+      .map(|w| w.last_delivery + retention)
+      .min();
+  }
+
+  fn writer(&self, writer_guid: GUID, now: Instant) -> Option<&WriterJournal> {
+    self
+      .writers
+      .get(&writer_guid)
+      .filter(|w| !w.is_expired(now, self.retention))
+  }
+}
+
+#[derive(Debug)]
+struct WriterJournal {
+  // Delivered sequence numbers, as ranges from start to end (exclusive)
+  delivered: BTreeMap<SequenceNumber, SequenceNumber>,
+  last_delivery: Instant,
+}
+
+impl WriterJournal {
+  fn is_expired(&self, now: Instant, retention: Duration) -> bool {
+    now.duration_since(self.last_delivery) > retention
+  }
+
+  fn contains(&self, sequence_number: SequenceNumber) -> bool {
+    self
+      .delivered
+      .range(..=sequence_number)
+      .next_back()
+      .is_some_and(|(_start, end)| sequence_number < *end)
+  }
+
+  // `sequence_number` must not be contained already
+  fn insert(&mut self, sequence_number: SequenceNumber) {
+    let mut start = sequence_number;
+    let mut end = sequence_number.plus_1();
+    // Join with the range that ends here, and the one that starts after this
+    if let Some((&before_start, &before_end)) = self.delivered.range(..start).next_back() {
+      if before_end == start {
+        start = before_start;
+      }
+    }
+    if let Some(after_end) = self.delivered.remove(&end) {
+      end = after_end;
+    }
+    self.delivered.insert(start, end);
+    if self.delivered.len() > MAX_DELIVERED_RANGES {
+      // Forget the oldest deliveries, rather than guess about the gaps
+      self.delivered.pop_first();
+    }
+  }
+}
+
+impl DeliveryJournal {
+  fn lock(&self) -> MutexGuard<'_, Journal> {
+    self.inner.lock().unwrap_or_else(|e| e.into_inner())
+  }
+
+  pub fn was_delivered(&self, writer_guid: GUID, sequence_number: SequenceNumber) -> bool {
+    self
+      .lock()
+      .writer(writer_guid, Instant::now())
+      .is_some_and(|w| w.contains(sequence_number))
+  }
+
+  // Records a delivery. Returns false, if the sample was delivered already.
+  pub fn deliver(&self, writer_guid: GUID, sequence_number: SequenceNumber) -> bool {
+    self.deliver_at(writer_guid, sequence_number, Instant::now())
+  }
+
+  fn deliver_at(&self, writer_guid: GUID, sequence_number: SequenceNumber, now: Instant) -> bool {
+    let mut journal = self.lock();
+    journal.prune(now);
+    if journal
+      .writer(writer_guid, now)
+      .is_some_and(|w| w.contains(sequence_number))
+    {
+      return false;
+    }
+    let retention = journal.retention;
+    let writer = journal
+      .writers
+      .entry(writer_guid)
+      .or_insert_with(|| WriterJournal {
+        delivered: BTreeMap::new(),
+        last_delivery: now,
+      });
+    if writer.is_expired(now, retention) {
+      // Not pruned yet
+      writer.delivered.clear();
+    }
+    writer.insert(sequence_number);
+    writer.last_delivery = now;
+    if journal.next_prune.is_none() {
+      journal.next_prune = Some(now + retention);
+    }
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::structure::guid::EntityKind;
+
+  #[test]
+  fn samples_are_delivered_once() {
+    let journals = DeliveryJournals::default();
+    let journal = journals.journal("test", Duration::from_secs(60));
+    let writer = GUID::GUID_UNKNOWN;
+    let sn = SequenceNumber::new;
+
+    assert!(journal.deliver(writer, sn(5)));
+    assert!(!journal.deliver(writer, sn(5)));
+    assert!(journal.deliver(writer, sn(7)));
+    assert!(journal.deliver(writer, sn(6)));
+    assert!(journal.was_delivered(writer, sn(7)));
+    assert!(!journal.was_delivered(writer, sn(8)));
+    // Nothing is known of samples older than the first one delivered
+    assert!(!journal.was_delivered(writer, sn(3)));
+    assert!(journal.deliver(writer, sn(3)));
+    assert!(!journal.deliver(writer, sn(3)));
+    assert!(!journal.was_delivered(writer, sn(4)));
+
+    // The same journal is found by name
+    let again = journals.journal("test", Duration::from_secs(60));
+    assert!(!again.deliver(writer, sn(6)));
+    assert!(journals
+      .journal("other", Duration::from_secs(60))
+      .deliver(writer, sn(6)));
+  }
+
+  #[test]
+  fn gaps_are_bounded() {
+    let journal = DeliveryJournal::default();
+    journal.lock().retention = Duration::from_secs(60);
+    let writer = GUID::GUID_UNKNOWN;
+    // Every other SN is never delivered
+    for i in 0..=MAX_DELIVERED_RANGES as i64 {
+      assert!(journal.deliver(writer, SequenceNumber::new(2 * i)));
+    }
+    assert_eq!(
+      journal.lock().writers[&writer].delivered.len(),
+      MAX_DELIVERED_RANGES
+    );
+    // The oldest delivery is forgotten, and the gaps are not delivered
+    assert!(!journal.was_delivered(writer, SequenceNumber::new(0)));
+    assert!(journal.was_delivered(writer, SequenceNumber::new(2)));
+    assert!(!journal.was_delivered(writer, SequenceNumber::new(1)));
+    assert!(!journal.was_delivered(writer, SequenceNumber::new(3)));
+    // Filling a gap joins the ranges
+    assert!(journal.deliver(writer, SequenceNumber::new(3)));
+    assert_eq!(
+      journal.lock().writers[&writer].delivered.len(),
+      MAX_DELIVERED_RANGES - 1
+    );
+  }
+
+  #[test]
+  fn idle_writers_are_forgotten() {
+    let journals = DeliveryJournals::default();
+    let retention = Duration::from_secs(10);
+    let journal = journals.journal("test", retention);
+    let writer = GUID::GUID_UNKNOWN;
+    let start = Instant::now();
+    let sn = SequenceNumber::new(1);
+
+    assert!(journal.deliver_at(writer, sn, start));
+    assert!(!journal.deliver_at(writer, sn, start + retention));
+    // Idle for longer than retention
+    let later = start + 3 * retention;
+    assert!(journal.deliver_at(writer, sn, later));
+    assert!(!journal.deliver_at(writer, sn, later));
+
+    // Pruned at the next access after retention
+    let other = GUID::dummy_test_guid(EntityKind::WRITER_NO_KEY_USER_DEFINED);
+    assert!(journal.deliver_at(other, sn, later + 2 * retention));
+    assert!(!journal.lock().writers.contains_key(&writer));
+  }
+}
@@ -0,0 +1,132 @@
+use std::{
+  io,
+  panic::{self, AssertUnwindSafe},
+  sync::{mpsc, Arc, Mutex},
+  thread::{self, JoinHandle},
+};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A pool of worker threads for deserializing received samples. It is owned
+/// by the DomainParticipant and shared by its DataReaders, so that heavy
+/// deserialization runs neither in the RTPS event loop nor entirely on the
+/// application thread.
+pub(crate) struct DeserializationPool {
+  // Option, so that it can be dropped before joining the threads
+  job_sender: Mutex<Option<mpsc::Sender<Job>>>,
+  threads: Vec<JoinHandle<()>>,
+}
+
+impl DeserializationPool {
+  pub fn new(thread_count: usize) -> io::Result<Self> {
+    let (job_sender, job_receiver) = mpsc::channel::<Job>();
+    let job_receiver = Arc::new(Mutex::new(job_receiver));
+    let mut threads = Vec::with_capacity(thread_count);
+    for i in 0..thread_count {
+      let job_receiver = job_receiver.clone();
+      threads.push(
+        thread::Builder::new()
+          .name(format!("RustDDS deserialization thread {i}"))
+          .spawn(move || loop {
+            // Hold the lock only while receiving, not while running the job.
+            let job = job_receiver.lock().unwrap().recv();
+            match job {
+              Ok(job) => job(),
+              Err(_) => return, // Pool was dropped
+            }
+          })?,
+      );
+    }
+    Ok(Self {
+      job_sender: Mutex::new(Some(job_sender)),
+      threads,
+    })
+  }
+
+  /// Applies `f` to all `items` in the worker threads. Results are returned in
+  /// the same order as the items. Blocks until all are done.
+  ///
+  /// Returns `None` if `f` panicked for some item.
+  pub fn map<T, R>(&self, items: Vec<T>, f: fn(T) -> R) -> Option<Vec<R>>
+  where
+    T: Send + 'static,
+    R: Send + 'static,
+  {
+    let count = items.len();
+    let (result_sender, result_receiver) = mpsc::channel();
+    {
+      let job_sender = self.job_sender.lock().unwrap();
+      let job_sender = job_sender
+        .as_ref()
+        .expect("DeserializationPool used after drop");
+      for (index, item) in items.into_iter().enumerate() {
+        let result_sender = result_sender.clone();
+        let job: Job = Box::new(move || {
+          // A panic is caught here, so that the worker thread survives it. The
+          // result is then missing.
+          if let Ok(result) = panic::catch_unwind(AssertUnwindSafe(|| f(item))) {
+            // The receiver is gone only if the caller panicked.
+            let _ = result_sender.send((index, result));
+          }
+        });
+        if let Err(mpsc::SendError(job)) = job_sender.send(job) {
+          // All workers have exited. Do the work here instead.
+          error!("DeserializationPool has no workers left");
+          job();
+        }
+      }
+    }
+    drop(result_sender);
+
+    let mut results: Vec<Option<R>> = (0..count).map(|_| None).collect();
+    for (index, result) in result_receiver {
+      results[index] = Some(result);
+    }
+    // A missing result means that the job panicked.
+    results.into_iter().collect()
+  }
+}
+
+impl Drop for DeserializationPool {
+  fn drop(&mut self) {
+    // Closing the job channel makes the workers exit.
+    self.job_sender.lock().unwrap().take();
+    for handle in self.threads.drain(..) {
+      handle
+        .join()
+        .unwrap_or_else(|_| error!("Deserialization thread had panicked"));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn map_keeps_the_order_of_items() {
+    let pool = DeserializationPool::new(3).unwrap();
+    let items: Vec<u64> = (0..100).collect();
+    let results = pool.map(items, |x| {
+      // Make later items finish earlier
+      thread::sleep(std::time::Duration::from_micros(100 - x));
+      x * 2
+    });
+    assert_eq!(results, Some((0..100).map(|x| x * 2).collect::<Vec<u64>>()));
+  }
+
+  #[test]
+  fn map_reports_a_panicked_job() {
+    let pool = DeserializationPool::new(2).unwrap();
+    let results = pool.map(vec![1, 0, 2], |x: u32| {
+      assert!(x > 0, "bad item");
+      x
+    });
+    assert_eq!(results, None);
+    // The workers survive it
+    assert_eq!(pool.map(vec![3], |x: u32| x + 1), Some(vec![4]));
+  }
+}
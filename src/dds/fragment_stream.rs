@@ -0,0 +1,372 @@
+// Streaming reception of large fragmented samples, see
+// SimpleDataReader::set_fragment_streaming().
+//
+// The RTPS Reader passes the fragments of a sample to a FragmentStream as soon
+// as they are contiguous, and the application reads them from there. The
+// complete sample is never assembled, so the memory needed is that of the
+// fragments received out of order and not yet read.
+
+use std::{
+  collections::VecDeque,
+  fmt, io,
+  pin::Pin,
+  sync::{Arc, Condvar, Mutex, MutexGuard},
+  task::{Context, Poll, Waker},
+  time::{Duration, Instant},
+};
+
+use bytes::{Buf, Bytes};
+
+use crate::structure::{guid::GUID, sequence_number::SequenceNumber};
+
+// At most this many streams wait for the application to take them. Further
+// large samples are assembled as usual, until the application catches up.
+const MAX_STARTED_STREAMS: usize = 16;
+
+// Streaming configuration and the started streams of one DataReader. Shared
+// by the DataReader and its RTPS Reader.
+#[derive(Debug, Default)]
+pub(crate) struct FragmentStreams {
+  inner: Mutex<Streams>,
+}
+
+#[derive(Debug, Default)]
+struct Streams {
+  // Samples of at least this many bytes are streamed. None = no streaming.
+  min_size: Option<usize>,
+  // Streams not yet taken by the application
+  started: VecDeque<FragmentStream>,
+}
+
+impl FragmentStreams {
+  fn lock(&self) -> MutexGuard<'_, Streams> {
+    self.inner.lock().unwrap_or_else(|e| e.into_inner())
+  }
+
+  pub fn set_min_size(&self, min_size: Option<usize>) {
+    let mut streams = self.lock();
+    streams.min_size = min_size;
+    if min_size.is_none() {
+      // Nobody is going to take these
+      streams.started.clear();
+    }
+  }
+
+  // Can a new stream be started for a sample of `size` bytes?
+  pub fn accepts(&self, size: usize) -> bool {
+    let streams = self.lock();
+    streams.min_size.is_some_and(|min_size| size >= min_size)
+      && streams.started.len() < MAX_STARTED_STREAMS
+  }
+
+  // Starts a stream for a sample of `size` bytes. The application gets the
+  // reading end from take().
+  pub fn start(
+    &self,
+    writer_guid: GUID,
+    sequence_number: SequenceNumber,
+    size: usize,
+  ) -> FragmentStreamWriter {
+    let shared = Arc::new(Shared::default());
+    self.lock().started.push_back(FragmentStream {
+      writer_guid,
+      sequence_number,
+      size,
+      shared: shared.clone(),
+      read_timeout: None,
+    });
+    FragmentStreamWriter {
+      shared,
+      received: 0,
+      crc32c: 0,
+      finished: false,
+    }
+  }
+
+  pub fn take(&self) -> Option<FragmentStream> {
+    self.lock().started.pop_front()
+  }
+}
+
+#[derive(Default)]
+struct Shared {
+  state: Mutex<State>,
+  readable: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+  chunks: VecDeque<Bytes>,
+  end: Option<Result<(), String>>,
+  waker: Option<Waker>,
+}
+
+impl Shared {
+  fn lock(&self) -> MutexGuard<'_, State> {
+    self.state.lock().unwrap_or_else(|e| e.into_inner())
+  }
+
+  fn update(&self, f: impl FnOnce(&mut State)) {
+    let mut state = self.lock();
+    f(&mut state);
+    if let Some(waker) = state.waker.take() {
+      waker.wake();
+    }
+    self.readable.notify_all();
+  }
+}
+
+// Copies from the received chunks to `buf`. None means that the reader must
+// wait for more data.
+fn read_chunks(state: &mut State, buf: &mut [u8]) -> Option<io::Result<usize>> {
+  if buf.is_empty() {
+    return Some(Ok(0));
+  }
+  let mut count = 0;
+  while count < buf.len() {
+    let Some(chunk) = state.chunks.front_mut() else {
+      break;
+    };
+    let n = chunk.len().min(buf.len() - count);
+    buf[count..count + n].copy_from_slice(&chunk[..n]);
+    chunk.advance(n);
+    count += n;
+    if chunk.is_empty() {
+      state.chunks.pop_front();
+    }
+  }
+  if count > 0 {
+    return Some(Ok(count));
+  }
+  match &state.end {
+    None => None,
+    Some(Ok(())) => Some(Ok(0)), // end of sample
+    Some(Err(reason)) => Some(Err(io::Error::new(
+      io::ErrorKind::InvalidData,
+      reason.clone(),
+    ))),
+  }
+}
+
+/// A sample that is received in fragments and read as they arrive, see
+/// [`SimpleDataReader::set_fragment_streaming`](crate::with_key::SimpleDataReader::set_fragment_streaming).
+///
+/// The bytes are the serialized payload as sent, i.e. they start with the
+/// 4-byte encapsulation header, which tells the data representation.
+/// Reading blocks until more fragments have been received, or the read
+/// timeout, if any, passes. Read gives an error, if the sample cannot be
+/// received after all: it was corrupted, the DataWriter was unmatched or
+/// announced that it no longer has the sample, or its fragments stopped
+/// arriving.
+pub struct FragmentStream {
+  writer_guid: GUID,
+  sequence_number: SequenceNumber,
+  size: usize,
+  shared: Arc<Shared>,
+  read_timeout: Option<Duration>,
+}
+
+impl FragmentStream {
+  /// GUID of the DataWriter that sent the sample
+  pub fn writer_guid(&self) -> GUID {
+    self.writer_guid
+  }
+
+  /// Sequence number of the sample given by its DataWriter
+  pub fn sequence_number(&self) -> SequenceNumber {
+    self.sequence_number
+  }
+
+  /// Total size of the serialized payload in bytes
+  pub fn size(&self) -> usize {
+    self.size
+  }
+
+  /// Sets how long a blocking read waits for more fragments. `None`, the
+  /// default, waits until the sample is received or fails. A read that times
+  /// out gives an error of kind [`TimedOut`](io::ErrorKind::TimedOut), and can
+  /// be retried.
+  pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+    self.read_timeout = timeout;
+  }
+}
+
+impl io::Read for FragmentStream {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let deadline = self.read_timeout.map(|timeout| Instant::now() + timeout);
+    let mut state = self.shared.lock();
+    loop {
+      if let Some(result) = read_chunks(&mut state, buf) {
+        return result;
+      }
+      state = match deadline {
+        None => self
+          .shared
+          .readable
+          .wait(state)
+          .unwrap_or_else(|e| e.into_inner()),
+        Some(deadline) => {
+          let timeout = deadline.saturating_duration_since(Instant::now());
+          if timeout.is_zero() {
+            return Err(io::Error::new(
+              io::ErrorKind::TimedOut,
+              "Timed out waiting for fragments",
+            ));
+          }
+          self
+            .shared
+            .readable
+            .wait_timeout(state, timeout)
+            .unwrap_or_else(|e| e.into_inner())
+            .0
+        }
+      };
+    }
+  }
+}
+
+impl futures::io::AsyncRead for FragmentStream {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+  ) -> Poll<io::Result<usize>> {
+    let mut state = self.shared.lock();
+    match read_chunks(&mut state, buf) {
+      Some(result) => Poll::Ready(result),
+      None => {
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+      }
+    }
+  }
+}
+
+impl fmt::Debug for FragmentStream {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("FragmentStream")
+      .field("writer_guid", &self.writer_guid)
+      .field("sequence_number", &self.sequence_number)
+      .field("size", &self.size)
+      .finish_non_exhaustive()
+  }
+}
+
+// The RTPS Reader end of a FragmentStream. Dropping this without finish()
+// fails the stream.
+pub(crate) struct FragmentStreamWriter {
+  shared: Arc<Shared>,
+  received: usize,
+  // Of the data after the encapsulation header, see checksum.rs
+  crc32c: u32,
+  finished: bool,
+}
+
+impl FragmentStreamWriter {
+  // Appends the next bytes of the serialized payload
+  pub fn push(&mut self, data: Bytes) {
+    const HEADER_SIZE: usize = 4;
+    let skip = HEADER_SIZE.saturating_sub(self.received).min(data.len());
+    self.crc32c = crc32c::crc32c_append(self.crc32c, &data[skip..]);
+    self.received += data.len();
+    if Arc::strong_count(&self.shared) == 1 {
+      return; // The application dropped the stream. Nobody reads this.
+    }
+    self.shared.update(|state| state.chunks.push_back(data));
+  }
+
+  pub fn crc32c(&self) -> u32 {
+    self.crc32c
+  }
+
+  pub fn finish(mut self) {
+    self.finished = true;
+    self.shared.update(|state| state.end = Some(Ok(())));
+  }
+
+  pub fn fail(mut self, reason: String) {
+    self.finished = true;
+    self.shared.update(|state| state.end = Some(Err(reason)));
+  }
+}
+
+impl Drop for FragmentStreamWriter {
+  fn drop(&mut self) {
+    if !self.finished {
+      self.shared.update(|state| {
+        state.end = Some(Err("Reception of the sample was abandoned".to_string()));
+      });
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{io::Read, thread};
+
+  use super::*;
+
+  #[test]
+  fn stream_is_read_as_written() {
+    let streams = FragmentStreams::default();
+    streams.set_min_size(Some(1));
+    let mut writer = streams.start(GUID::GUID_UNKNOWN, SequenceNumber::new(1), 10);
+    let mut stream = streams.take().unwrap();
+    assert!(streams.take().is_none());
+    assert_eq!(stream.size(), 10);
+
+    writer.push(Bytes::from_static(b"\x00\x01\x00\x00ab"));
+    let mut buf = [0; 3];
+    assert_eq!(stream.read(&mut buf).unwrap(), 3);
+    assert_eq!(&buf, b"\x00\x01\x00");
+
+    let reader = thread::spawn(move || {
+      let mut rest = Vec::new();
+      stream.read_to_end(&mut rest).map(|_| rest)
+    });
+    writer.push(Bytes::from_static(b"cdef"));
+    assert_eq!(writer.crc32c(), crc32c::crc32c(b"abcdef"));
+    writer.finish();
+    assert_eq!(reader.join().unwrap().unwrap(), b"\x00abcdef");
+  }
+
+  #[test]
+  fn abandoned_stream_fails() {
+    let streams = FragmentStreams::default();
+    let mut writer = streams.start(GUID::GUID_UNKNOWN, SequenceNumber::new(1), 10);
+    let mut stream = streams.take().unwrap();
+    writer.push(Bytes::from_static(b"abc"));
+    drop(writer);
+    let mut data = Vec::new();
+    assert!(stream.read_to_end(&mut data).is_err());
+    assert_eq!(data, b"abc");
+  }
+
+  #[test]
+  fn read_times_out() {
+    let streams = FragmentStreams::default();
+    let mut writer = streams.start(GUID::GUID_UNKNOWN, SequenceNumber::new(1), 10);
+    let mut stream = streams.take().unwrap();
+    stream.set_read_timeout(Some(Duration::from_millis(10)));
+    let mut buf = [0; 4];
+    let error = stream.read(&mut buf).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+    writer.push(Bytes::from_static(b"abcd"));
+    assert_eq!(stream.read(&mut buf).unwrap(), 4);
+  }
+
+  #[test]
+  fn streams_wait_to_be_taken_up_to_a_limit() {
+    let streams = FragmentStreams::default();
+    assert!(!streams.accepts(100));
+    streams.set_min_size(Some(10));
+    assert!(!streams.accepts(9));
+    let writers: Vec<_> = (1..=MAX_STARTED_STREAMS as i64)
+      .map(|sn| streams.start(GUID::GUID_UNKNOWN, SequenceNumber::new(sn), 10))
+      .collect();
+    assert!(!streams.accepts(10));
+    assert!(streams.take().is_some());
+    assert!(streams.accepts(10));
+    drop(writers);
+  }
+}
@@ -8,7 +8,7 @@ use log::error;
 use serde::{Deserialize, Serialize};
 pub use cdr_encoding_size::*;
 
-use crate::serialization::cdr_serializer::to_bytes;
+use crate::{serialization::cdr_serializer::to_bytes, structure::guid::GUID};
 // use crate::serialization::{cdr_serializer::to_bytes, };
 use crate::serialization::pl_cdr_adapters::{PlCdrDeserializeError, PlCdrSerializeError};
 
@@ -38,7 +38,7 @@ pub trait Keyed {
 
 // See RTPS spec Section 8.7.10 Key Hash
 // and Section 9.6.3.8 KeyHash
-#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Clone, Copy)]
 pub struct KeyHash([u8; 16]);
 
 impl KeyHash {
@@ -50,6 +50,10 @@ impl KeyHash {
     Vec::from(self.0)
   }
 
+  pub(crate) fn to_u128(self) -> u128 {
+    u128::from_be_bytes(self.0)
+  }
+
   pub fn into_pl_cdr_bytes(self) -> Result<Vec<u8>, PlCdrSerializeError> {
     Ok(self.to_vec())
   }
@@ -61,6 +65,40 @@ impl KeyHash {
   }
 }
 
+/// Identifies an instance of a WITH_KEY topic. This is InstanceHandle_t in
+/// the DDS specification.
+///
+/// The handle is derived from the [`KeyHash`] of the instance key, so the same
+/// key has the same handle in every DataReader and DataWriter of the Topic.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Clone, Copy)]
+pub struct InstanceHandle(KeyHash);
+
+impl InstanceHandle {
+  pub(crate) fn from_key<K: Key>(key: &K) -> Self {
+    Self(key.hash_key(false))
+  }
+
+  pub fn key_hash(&self) -> KeyHash {
+    self.0
+  }
+}
+
+/// Instances of the DDS built-in topics are keyed by GUID. This gives the
+/// same InstanceHandle as such a key would.
+impl From<GUID> for InstanceHandle {
+  fn from(guid: GUID) -> Self {
+    Self(KeyHash(guid.to_bytes()))
+  }
+}
+
+/// This is meaningful only for handles of instances that are keyed by
+/// GUID, such as those of the DDS built-in topics.
+impl From<InstanceHandle> for GUID {
+  fn from(handle: InstanceHandle) -> Self {
+    GUID::from_bytes(handle.0 .0)
+  }
+}
+
 /// Trait for instance lookup key in a WITH_KEY topic.
 ///
 /// The corresponding data sample type must implement [`Keyed`].
@@ -226,3 +264,56 @@ impl BuiltInTopicKey {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, CdrEncodingSize,
+  )]
+  struct TwoStringKey {
+    a: String,
+    b: String,
+  }
+
+  impl Key for TwoStringKey {}
+
+  #[test]
+  fn key_hash_short_key_is_big_endian_cdr() {
+    let key_hash = 0x0102_0304_u32.hash_key(false);
+    assert_eq!(
+      key_hash.to_vec(),
+      vec![1, 2, 3, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+    );
+  }
+
+  #[test]
+  fn key_hash_long_key_is_md5() {
+    let key = TwoStringKey {
+      a: "ab".to_string(),
+      b: "c".to_string(),
+    };
+    // Big-endian CDR: length-prefixed, null-terminated strings, aligned to 4.
+    let cdr_bytes = [0, 0, 0, 3, b'a', b'b', 0, 0, 0, 0, 0, 2, b'c', 0];
+    assert_eq!(
+      key.hash_key(false).to_vec(),
+      md5::compute(cdr_bytes).to_vec()
+    );
+    // Security forces MD5 also for short keys
+    assert_eq!(
+      7_u32.hash_key(true).to_vec(),
+      md5::compute([0, 0, 0, 7]).to_vec()
+    );
+  }
+
+  #[test]
+  fn instance_handle_of_guid_matches_guid_key() {
+    use crate::structure::guid::EntityKind;
+
+    let guid = GUID::dummy_test_guid(EntityKind::READER_WITH_KEY_USER_DEFINED);
+    let handle = InstanceHandle::from(guid);
+    assert_eq!(handle, InstanceHandle::from_key(&guid));
+    assert_eq!(GUID::from(handle), guid);
+  }
+}
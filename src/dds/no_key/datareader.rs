@@ -3,18 +3,23 @@ use std::{
   pin::Pin,
   task::{Context, Poll},
 };
+#[cfg(not(target_os = "windows"))]
+use std::os::fd::{AsRawFd, RawFd};
 
 use mio_06::{self, Evented};
 use futures::stream::{FusedStream, Stream};
+use serde::de::DeserializeSeed;
 
 use crate::{
   dds::{
-    adapters::no_key::DeserializerAdapter,
+    adapters::no_key::{DeserializerAdapter, SeedDeserializerAdapter},
+    fragment_stream::FragmentStream,
     no_key::datasample::DataSample,
-    qos::{HasQoSPolicy, QosPolicies},
+    qos::{HasQoSPolicy, MutQosPolicy, QosPolicies},
     readcondition::ReadCondition,
-    result::ReadResult,
+    result::{CreateResult, QosError, ReadResult},
     statusevents::DataReaderStatus,
+    topic::Topic,
     with_key::{
       datareader as datareader_with_key,
       datasample::{DataSample as WithKeyDataSample, Sample},
@@ -22,6 +27,8 @@ use crate::{
       DataReaderStream as WithKeyDataReaderStream,
     },
   },
+  discovery::sedp_messages::PublicationBuiltinTopicData,
+  rtps::rtps_writer_proxy::WriterProxyStatistics,
   serialization::CDRDeserializerAdapter,
   structure::entity::RTPSEntity,
   StatusEvented, GUID,
@@ -72,6 +79,10 @@ where
     }
   }
 
+  pub(crate) fn topic(&self) -> &Topic {
+    self.keyed_datareader.topic()
+  }
+
   /// Reads amount of samples found with `max_samples` and `read_condition`
   /// parameters.
   ///
@@ -417,6 +428,62 @@ where
   }
   */
 
+  /// Enables a DataReader that was created disabled.
+  /// See [`SimpleDataReader::enable`](crate::with_key::SimpleDataReader::enable).
+  pub fn enable(&self) -> CreateResult<()> {
+    self.keyed_datareader.enable()
+  }
+
+  /// Has the DataReader been enabled?
+  pub fn is_enabled(&self) -> bool {
+    self.keyed_datareader.is_enabled()
+  }
+
+  /// File descriptor for polling status changes outside mio, e.g. from C
+  /// code. See
+  /// [`with_key::SimpleDataReader::as_status_raw_fd`](crate::with_key::SimpleDataReader::as_status_raw_fd).
+  #[cfg(not(target_os = "windows"))]
+  pub fn as_status_raw_fd(&mut self) -> RawFd {
+    self.keyed_datareader.as_status_raw_fd()
+  }
+
+  /// Reception statistics for each currently matched DataWriter. This is a
+  /// RustDDS extension for diagnosing communication problems.
+  pub fn matched_writer_statistics(&self) -> ReadResult<Vec<WriterProxyStatistics>> {
+    self.keyed_datareader.matched_writer_statistics()
+  }
+
+  /// Receives large samples as [`FragmentStream`]s. See
+  /// [`with_key::SimpleDataReader::set_fragment_streaming`](crate::with_key::SimpleDataReader::set_fragment_streaming).
+  pub fn set_fragment_streaming(&self, min_size: Option<usize>) {
+    self.keyed_datareader.set_fragment_streaming(min_size);
+  }
+
+  /// The next streamed sample, if any. See
+  /// [`with_key::SimpleDataReader::set_fragment_streaming`](crate::with_key::SimpleDataReader::set_fragment_streaming).
+  pub fn take_fragment_stream(&self) -> Option<FragmentStream> {
+    self.keyed_datareader.take_fragment_stream()
+  }
+
+  /// Delivers each sample at most once, even across recreation of the
+  /// DataReader. See
+  /// [`with_key::SimpleDataReader::set_delivery_journal`](crate::with_key::SimpleDataReader::set_delivery_journal).
+  pub fn set_delivery_journal(&mut self, name: &str, retention: std::time::Duration) {
+    self.keyed_datareader.set_delivery_journal(name, retention);
+  }
+
+  /// Stops using the journal set with
+  /// [`set_delivery_journal`](Self::set_delivery_journal).
+  pub fn remove_delivery_journal(&mut self) {
+    self.keyed_datareader.remove_delivery_journal();
+  }
+
+  /// Gets the discovery data of the currently matched DataWriters. See
+  /// [`with_key::DataReader::get_matched_publications`](crate::with_key::DataReader::get_matched_publications).
+  pub fn get_matched_publications(&self) -> impl Iterator<Item = PublicationBuiltinTopicData> {
+    self.keyed_datareader.get_matched_publications()
+  }
+
   /// An async stream for reading the (bare) data samples
   pub fn async_sample_stream(self) -> DataReaderStream<D, DA> {
     DataReaderStream {
@@ -425,6 +492,56 @@ where
   }
 }
 
+impl<D, DA> DataReader<D, DA>
+where
+  D: Send + 'static,
+  DA: DeserializerAdapter<D> + 'static,
+{
+  /// Same as [`take`](Self::take), but deserializes in the worker pool of the
+  /// DomainParticipant. See
+  /// [`with_key::DataReader::take_offloaded`](crate::with_key::DataReader::take_offloaded).
+  pub fn take_offloaded(
+    &mut self,
+    max_samples: usize,
+    read_condition: ReadCondition,
+  ) -> ReadResult<Vec<DataSample<D>>> {
+    let values: Vec<WithKeyDataSample<NoKeyWrapper<D>>> = self
+      .keyed_datareader
+      .take_offloaded(max_samples, read_condition)?;
+    Ok(
+      values
+        .into_iter()
+        .filter_map(DataSample::<D>::from_with_key)
+        .collect(),
+    )
+  }
+}
+
+impl<D, DA> DataReader<D, DA>
+where
+  D: 'static,
+  DA: DeserializerAdapter<D> + SeedDeserializerAdapter<D>,
+{
+  /// Takes up to `max_samples` samples received since the previous access,
+  /// deserializing each with a serde
+  /// [`DeserializeSeed`](serde::de::DeserializeSeed) made by `seed`, instead
+  /// of into `D`. See
+  /// [`with_key::DataReader::take_with_deserializer`](crate::with_key::DataReader::take_with_deserializer).
+  pub fn take_with_deserializer<S, T>(
+    &mut self,
+    max_samples: usize,
+    seed: impl FnMut() -> S,
+  ) -> ReadResult<Vec<T>>
+  where
+    S: for<'de> DeserializeSeed<'de, Value = T>,
+  {
+    let values: Vec<Sample<T, ()>> = self
+      .keyed_datareader
+      .take_with_deserializer(max_samples, seed)?;
+    Ok(values.into_iter().filter_map(Sample::value).collect())
+  }
+}
+
 /// WARNING! UNTESTED
 //  TODO: test
 // This is  not part of DDS spec. We implement mio Evented so that the
@@ -506,6 +623,18 @@ where
   }
 }
 
+/// For polling outside mio. See
+/// [`with_key::DataReader`](crate::with_key::DataReader) for the semantics.
+#[cfg(not(target_os = "windows"))]
+impl<D, DA> AsRawFd for DataReader<D, DA>
+where
+  DA: DeserializerAdapter<D>,
+{
+  fn as_raw_fd(&self) -> RawFd {
+    self.keyed_datareader.as_raw_fd()
+  }
+}
+
 /// WARNING! UNTESTED
 //  TODO: test
 use crate::with_key::SimpleDataReaderEventStream;
@@ -549,6 +678,23 @@ where
   }
 }
 
+impl<D, DA> MutQosPolicy for DataReader<D, DA>
+where
+  D: 'static,
+  DA: DeserializerAdapter<D>,
+{
+  /// Change the QoS policies. Policies set in `new_qos` replace the current
+  /// ones, and the rest are kept.
+  ///
+  /// Changing a policy that cannot be changed after creation fails with
+  /// [`QosError::ImmutablePolicy`], which lists the changeable policies. The
+  /// new QoS is announced via Discovery, and matching with remote DataWriters
+  /// is re-evaluated.
+  fn set_qos(&mut self, new_qos: &QosPolicies) -> Result<(), QosError> {
+    self.keyed_datareader.set_qos(new_qos)
+  }
+}
+
 impl<D, DA> RTPSEntity for DataReader<D, DA>
 where
   D: 'static,
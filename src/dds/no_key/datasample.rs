@@ -1,3 +1,5 @@
+use std::time::Instant;
+
 use crate::{
   dds::{
     no_key::wrappers::NoKeyWrapper,
@@ -78,6 +80,7 @@ pub struct DeserializedCacheChange<D> {
   pub writer_guid: GUID,               // 8 bytes
   pub sequence_number: SequenceNumber, // 8 bytes
   pub write_options: WriteOptions,     // 16 bytes
+  pub reception_instant: Instant,      // monotonic pair of receive_instant
 
   // the data sample itself is stored here
   pub sample: D, /* TODO: make this a Box<> for easier detaching an
@@ -91,6 +94,7 @@ impl<D> DeserializedCacheChange<D> {
       writer_guid: cc.writer_guid,
       sequence_number: cc.sequence_number,
       write_options: cc.write_options.clone(),
+      reception_instant: cc.reception_instant,
       sample: deserialized,
     }
   }
@@ -104,6 +108,7 @@ impl<D> DeserializedCacheChange<D> {
         writer_guid: kdcc.writer_guid,
         sequence_number: kdcc.sequence_number,
         write_options: kdcc.write_options,
+        reception_instant: kdcc.reception_instant,
         sample: sample.d,
       }),
       Sample::Dispose(_key) => None,
@@ -118,6 +123,16 @@ impl<D> DeserializedCacheChange<D> {
     self.write_options.source_timestamp()
   }
 
+  /// Wall-clock time when this sample was received
+  pub fn reception_timestamp(&self) -> Timestamp {
+    self.receive_instant
+  }
+
+  /// Monotonic time when this sample was received
+  pub fn reception_instant(&self) -> Instant {
+    self.reception_instant
+  }
+
   pub fn writer_guid(&self) -> GUID {
     self.writer_guid
   }
@@ -1,23 +1,30 @@
 use std::time::Duration;
+#[cfg(not(target_os = "windows"))]
+use std::os::fd::RawFd;
 
 use mio_06::Evented;
+use bytes::Bytes;
+use speedy::Endianness;
 
 use crate::{
   dds::{
     adapters::no_key::SerializerAdapter,
     dds_entity::DDSEntity,
+    delivery::DeliveryListener,
     pubsub::Publisher,
-    qos::{HasQoSPolicy, QosPolicies},
-    result::{unwrap_no_key_write_error, WriteResult},
+    qos::{HasQoSPolicy, MutQosPolicy, QosPolicies},
+    result::{unwrap_no_key_write_error, CreateResult, QosError, WriteResult},
     statusevents::{DataWriterStatus, StatusReceiverStream},
     topic::Topic,
-    with_key::datawriter as datawriter_with_key,
+    with_key::datawriter::{self as datawriter_with_key, SampleBuffer},
   },
   discovery::sedp_messages::SubscriptionBuiltinTopicData,
   serialization::CDRSerializerAdapter,
-  structure::{entity::RTPSEntity, rpc::SampleIdentity, time::Timestamp},
+  structure::{duration, entity::RTPSEntity, rpc::SampleIdentity, time::Timestamp},
   StatusEvented, GUID,
 };
+#[cfg(any(test, feature = "ffi", feature = "python"))]
+use crate::RepresentationIdentifier;
 use super::wrappers::{NoKeyWrapper, SAWrapper};
 
 /// Simplified type for CDR encoding
@@ -102,6 +109,108 @@ where
       .map_err(unwrap_no_key_write_error)
   }
 
+  /// Gets a buffer for serializing a sample directly.
+  /// See [`with_key::DataWriter::reserve_sample`](crate::with_key::DataWriter::reserve_sample).
+  pub fn reserve_sample(&self, capacity: usize) -> SampleBuffer {
+    self.keyed_datawriter.reserve_sample(capacity)
+  }
+
+  /// Writes a sample serialized into a buffer from
+  /// [`reserve_sample`](Self::reserve_sample).
+  /// See [`with_key::DataWriter::write_sample`](crate::with_key::DataWriter::write_sample).
+  pub fn write_sample(
+    &self,
+    sample: SampleBuffer,
+    write_options: datawriter_with_key::WriteOptions,
+  ) -> WriteResult<SampleIdentity, Bytes> {
+    self
+      .keyed_datawriter
+      .write_sample(&(), sample, write_options)
+  }
+
+  #[cfg(any(test, feature = "ffi", feature = "python"))]
+  pub(crate) fn write_with_encoding(
+    &self,
+    data: D,
+    encoding: RepresentationIdentifier,
+    write_options: datawriter_with_key::WriteOptions,
+  ) -> WriteResult<SampleIdentity, D> {
+    self
+      .keyed_datawriter
+      .write_with_encoding(NoKeyWrapper::<D> { d: data }, encoding, write_options)
+      .map_err(unwrap_no_key_write_error)
+  }
+
+  /// Enables a DataWriter that was created disabled.
+  /// See [`with_key::DataWriter::enable`](crate::with_key::DataWriter::enable).
+  pub fn enable(&self) -> CreateResult<()> {
+    self.keyed_datawriter.enable()
+  }
+
+  /// Has the DataWriter been enabled?
+  pub fn is_enabled(&self) -> bool {
+    self.keyed_datawriter.is_enabled()
+  }
+
+  /// File descriptor for polling status changes outside mio. See
+  /// [`with_key::DataWriter::as_status_raw_fd`](crate::with_key::DataWriter::as_status_raw_fd).
+  #[cfg(not(target_os = "windows"))]
+  pub fn as_status_raw_fd(&mut self) -> RawFd {
+    self.keyed_datawriter.as_status_raw_fd()
+  }
+
+  /// Sets the maximum time a sample may wait for sending after write.
+  /// See [`with_key::DataWriter::set_max_send_age`](crate::with_key::DataWriter::set_max_send_age).
+  pub fn set_max_send_age(&mut self, max_send_age: Option<duration::Duration>) {
+    self.keyed_datawriter.set_max_send_age(max_send_age);
+  }
+
+  /// Maximum time (DDS Duration since write) a sample may wait for sending,
+  /// or `None` if there is no limit.
+  pub fn max_send_age(&self) -> Option<duration::Duration> {
+    self.keyed_datawriter.max_send_age()
+  }
+
+  /// Number of samples dropped so far, incremented once per sample that the
+  /// RTPS Writer finds past its `max_send_age` when it is about to send it.
+  pub fn stale_samples_dropped_count(&self) -> u64 {
+    self.keyed_datawriter.stale_samples_dropped_count()
+  }
+
+  /// Sets a listener that is told when samples have been acknowledged by all
+  /// reliable DataReaders, or dropped.
+  /// See [`with_key::DataWriter::set_delivery_listener`](crate::with_key::DataWriter::set_delivery_listener).
+  pub fn set_delivery_listener<L>(&self, listener: L)
+  where
+    L: DeliveryListener + 'static,
+  {
+    self.keyed_datawriter.set_delivery_listener(listener);
+  }
+
+  /// Removes the listener set with
+  /// [`set_delivery_listener`](Self::set_delivery_listener).
+  pub fn remove_delivery_listener(&self) {
+    self.keyed_datawriter.remove_delivery_listener();
+  }
+
+  /// Sets a function to inspect, modify or veto samples before they are
+  /// written.
+  /// See [`with_key::DataWriter::set_sample_hook`](crate::with_key::DataWriter::set_sample_hook).
+  pub fn set_sample_hook<F>(&mut self, hook: F)
+  where
+    D: 'static,
+    F: Fn(&mut D) -> Result<(), String> + Send + Sync + 'static,
+  {
+    self
+      .keyed_datawriter
+      .set_sample_hook(move |sample: &mut NoKeyWrapper<D>| hook(&mut sample.d));
+  }
+
+  /// Removes the function set with [`set_sample_hook`](Self::set_sample_hook).
+  pub fn remove_sample_hook(&mut self) {
+    self.keyed_datawriter.remove_sample_hook();
+  }
+
   /// Waits for all acknowledgements to finish
   ///
   /// # Examples
@@ -129,6 +238,19 @@ where
   pub fn wait_for_acknowledgments(&self, max_wait: Duration) -> WriteResult<bool, ()> {
     self.keyed_datawriter.wait_for_acknowledgments(max_wait)
   }
+
+  /// Sends out the samples held back by the
+  /// [`Batching`](crate::policy::Batching) QoS policy. See
+  /// [`with_key::DataWriter::flush`](crate::with_key::DataWriter::flush).
+  pub fn flush(&self) -> WriteResult<(), ()> {
+    self.keyed_datawriter.flush()
+  }
+
+  /// Sets the byte order of RTPS submessages sent by this DataWriter. See
+  /// [`with_key::DataWriter::set_rtps_endianness`](crate::with_key::DataWriter::set_rtps_endianness).
+  pub fn set_rtps_endianness(&self, endianness: Endianness) -> WriteResult<(), ()> {
+    self.keyed_datawriter.set_rtps_endianness(endianness)
+  }
   /*
   // status queries
   /// Unimplemented. <b>Do not use</b>.
@@ -332,11 +454,12 @@ where
     self.keyed_datawriter.assert_liveliness()
   }
 
-  /// Unimplemented. <b>Do not use</b>.
+  /// Gets the discovery data of the currently matched DataReaders. See
+  /// [`with_key::DataWriter::get_matched_subscriptions`](crate::with_key::DataWriter::get_matched_subscriptions).
   ///
   /// # Examples
-  // TODO: enable run when implemented
-  /// ```no_run ignore
+  ///
+  /// ```
   /// # use serde::{Serialize, Deserialize};
   /// # use rustdds::*;
   /// # use rustdds::no_key::DataWriter;
@@ -351,7 +474,7 @@ where
   /// #
   /// // NoKey is important
   /// let topic = domain_participant.create_topic("some_topic".to_string(), "SomeType".to_string(), &qos, TopicKind::NoKey).unwrap();
-  /// let data_writer = publisher.create_datawriter_no_key::<SomeType, CDRSerializerAdapter<_>>(topic, None).unwrap();
+  /// let data_writer = publisher.create_datawriter_no_key::<SomeType, CDRSerializerAdapter<_>>(&topic, None).unwrap();
   ///
   /// for sub in data_writer.get_matched_subscriptions().iter() {
   ///   // handle subscriptions
@@ -360,6 +483,12 @@ where
   pub fn get_matched_subscriptions(&self) -> Vec<SubscriptionBuiltinTopicData> {
     self.keyed_datawriter.get_matched_subscriptions()
   }
+
+  /// Gets the smoothed round trip times to the matched DataReaders. See
+  /// [`with_key::DataWriter::round_trip_times`](crate::with_key::DataWriter::round_trip_times).
+  pub fn round_trip_times(&self) -> Vec<(GUID, Duration)> {
+    self.keyed_datawriter.round_trip_times()
+  }
   /*
   /// Gets mio receiver for all implemented Status changes
   ///
@@ -429,6 +558,19 @@ impl<D, SA: SerializerAdapter<D>> HasQoSPolicy for DataWriter<D, SA> {
   }
 }
 
+impl<D, SA: SerializerAdapter<D>> MutQosPolicy for DataWriter<D, SA> {
+  /// Change the QoS policies. Policies set in `new_qos` replace the current
+  /// ones, and the rest are kept.
+  ///
+  /// Changing a policy that cannot be changed after creation fails with
+  /// [`QosError::ImmutablePolicy`], which lists the changeable policies. The
+  /// new QoS is announced via Discovery, and matching with remote DataReaders
+  /// is re-evaluated.
+  fn set_qos(&mut self, new_qos: &QosPolicies) -> Result<(), QosError> {
+    self.keyed_datawriter.set_qos(new_qos)
+  }
+}
+
 impl<D, SA: SerializerAdapter<D>> DDSEntity for DataWriter<D, SA> {}
 
 //-------------------------------------------------------------------------------
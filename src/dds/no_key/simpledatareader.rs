@@ -1,4 +1,6 @@
 use std::{io, task::Waker};
+#[cfg(not(target_os = "windows"))]
+use std::os::fd::{AsRawFd, RawFd};
 
 use futures::stream::{FusedStream, Stream, StreamExt};
 #[allow(unused_imports)]
@@ -8,8 +10,12 @@ use mio_08;
 
 use crate::{
   dds::{
-    adapters::no_key::*, no_key::datasample::DeserializedCacheChange, qos::*, result::ReadResult,
-    statusevents::*, with_key,
+    adapters::no_key::*,
+    no_key::datasample::DeserializedCacheChange,
+    qos::*,
+    result::{CreateResult, ReadResult},
+    statusevents::*,
+    with_key,
   },
   serialization::CDRDeserializerAdapter,
   structure::entity::RTPSEntity,
@@ -60,6 +66,22 @@ where
     }
   }
 
+  pub fn enable(&self) -> CreateResult<()> {
+    self.keyed_simpledatareader.enable()
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.keyed_simpledatareader.is_enabled()
+  }
+
+  /// File descriptor for polling status changes outside mio, e.g. from C
+  /// code. See
+  /// [`with_key::SimpleDataReader::as_status_raw_fd`](crate::with_key::SimpleDataReader::as_status_raw_fd).
+  #[cfg(not(target_os = "windows"))]
+  pub fn as_status_raw_fd(&mut self) -> RawFd {
+    self.keyed_simpledatareader.as_status_raw_fd()
+  }
+
   pub fn qos(&self) -> &QosPolicies {
     self.keyed_simpledatareader.qos()
   }
@@ -163,6 +185,19 @@ where
   }
 }
 
+/// For polling outside mio. See
+/// [`with_key::SimpleDataReader`](crate::with_key::SimpleDataReader) for the
+/// semantics.
+#[cfg(not(target_os = "windows"))]
+impl<D, DA> AsRawFd for SimpleDataReader<D, DA>
+where
+  DA: DeserializerAdapter<D>,
+{
+  fn as_raw_fd(&self) -> RawFd {
+    self.keyed_simpledatareader.as_raw_fd()
+  }
+}
+
 use crate::with_key::SimpleDataReaderEventStream;
 
 impl<'a, D, DA>
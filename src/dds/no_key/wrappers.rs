@@ -1,6 +1,7 @@
 use std::{marker::PhantomData, ops::Deref};
 
 use bytes::Bytes;
+use serde::de::DeserializeSeed;
 
 use crate::{
   dds::adapters::*, messages::submessages::submessages::RepresentationIdentifier, Keyed,
@@ -92,6 +93,29 @@ where
   ) -> Result<NoKeyWrapper<D>, DA::Error> {
     DA::from_bytes(input_bytes, encoding).map(|d| NoKeyWrapper::<D> { d })
   }
+
+  fn from_vec_bytes(
+    input_vec_bytes: &[Bytes],
+    encoding: RepresentationIdentifier,
+  ) -> Result<NoKeyWrapper<D>, DA::Error> {
+    DA::from_vec_bytes(input_vec_bytes, encoding).map(|d| NoKeyWrapper::<D> { d })
+  }
+}
+
+impl<D, DA> no_key::SeedDeserializerAdapter<NoKeyWrapper<D>> for DAWrapper<DA>
+where
+  DA: no_key::SeedDeserializerAdapter<D>,
+{
+  fn from_bytes_seed<S, T>(
+    input_bytes: &[u8],
+    encoding: RepresentationIdentifier,
+    seed: S,
+  ) -> Result<T, DA::Error>
+  where
+    S: for<'de> DeserializeSeed<'de, Value = T>,
+  {
+    DA::from_bytes_seed(input_bytes, encoding, seed)
+  }
 }
 
 // then, implement with_key DA
@@ -1,9 +1,9 @@
 // use mio::Token;
 use std::{
-  collections::HashMap,
+  collections::{BTreeMap, BTreeSet, HashMap},
   io,
   io::ErrorKind,
-  net::Ipv4Addr,
+  net::{Ipv4Addr, SocketAddr},
   pin::Pin,
   sync::{atomic, Arc, Mutex, RwLock, Weak},
   task::{Context, Poll},
@@ -16,34 +16,51 @@ use mio_extras::channel as mio_channel;
 use mio_06::{self, Evented};
 use mio_08::{self, Interest, Registry};
 use futures::stream::{FusedStream, Stream};
+use speedy::Endianness;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 
 use crate::{
-  create_error_out_of_resources, create_error_poisoned,
+  create_error_bad_parameter, create_error_out_of_resources, create_error_poisoned,
   dds::{
+    delivery_journal::DeliveryJournals,
+    deserialization_pool::DeserializationPool,
+    key::InstanceHandle,
     pubsub::*,
     qos::*,
     result::*,
     statusevents::{
-      sync_status_channel, DomainParticipantStatusEvent, StatusChannelReceiver, StatusChannelSender,
+      sync_status_channel, DomainParticipantStatusEvent, ParticipantDescription,
+      StatusChannelReceiver, StatusChannelSender,
     },
     topic::*,
+    type_support::{CdrTypeSupport, RegisteredType, TypeSupport},
     typedesc::TypeDesc,
   },
   discovery::{
+    builtin_endpoint::BuiltinEndpointOptions,
     discovery::{Discovery, DiscoveryCommand},
-    discovery_db::DiscoveryDB,
-    sedp_messages::DiscoveredTopicData,
+    discovery_db::{discovery_db_read, discovery_db_write, DiscoveryDB},
+    discovery_filter::DiscoveryFilter,
+    discovery_snapshot::DiscoverySnapshot,
+    sedp_messages::{
+      DiscoveredTopicData, PublicationBuiltinTopicData, SubscriptionBuiltinTopicData,
+    },
+    static_discovery::StaticDiscovery,
   },
-  network::{constant::*, udp_listener::UDPListener},
+  network::{constant::*, socket_options::SocketOptions, udp_listener::UDPListener},
+  ping::{PingRequester, RttStatistics},
   rtps::{
     constant::*,
-    dp_event_loop::{DPEventLoop, DomainInfo, EventLoopCommand},
+    dp_event_loop::{DPEventLoop, DomainInfo, EventLoopCommand, RtpsPingOptions},
+    event_scheduler::EventLoopWeights,
+    local_endpoints,
+    message_receiver::{MessageReceiverLimits, MessageReceiverStatistics},
     reader::*,
-    writer::WriterIngredients,
+    writer::{WriterCacheCleaning, WriterIngredients},
   },
   structure::{dds_cache::DDSCache, entity::RTPSEntity, guid::*, locator::Locator},
+  trace::{ParticipantTrace, TraceSampling},
   StatusEvented,
 };
 #[cfg(feature = "security")]
@@ -66,6 +83,19 @@ pub struct DomainParticipantBuilder {
   which interfaces the DomainParticipant will talk to. */
   only_networks: Option<Vec<String>>, // if specified, run RTPS only over these interfaces
 
+  socket_options: SocketOptions,
+  message_receiver_limits: MessageReceiverLimits,
+  discovery_filter: DiscoveryFilter,
+  static_discovery: StaticDiscovery,
+  builtin_endpoints: BuiltinEndpointOptions,
+  rtps_ping_options: RtpsPingOptions,
+  event_loop_weights: EventLoopWeights,
+  writer_cache_cleaning: WriterCacheCleaning,
+  trace_sampling: TraceSampling,
+  user_data: Option<policy::UserData>,
+  deserialization_threads: usize,
+  rtps_endianness: Endianness,
+
   #[cfg(feature = "security")]
   security_plugins: Option<SecurityPlugins>,
   #[cfg(feature = "security")]
@@ -77,6 +107,18 @@ impl DomainParticipantBuilder {
     DomainParticipantBuilder {
       domain_id,
       only_networks: None,
+      socket_options: SocketOptions::default(),
+      message_receiver_limits: MessageReceiverLimits::default(),
+      discovery_filter: DiscoveryFilter::default(),
+      static_discovery: StaticDiscovery::default(),
+      builtin_endpoints: BuiltinEndpointOptions::default(),
+      rtps_ping_options: RtpsPingOptions::default(),
+      event_loop_weights: EventLoopWeights::default(),
+      writer_cache_cleaning: WriterCacheCleaning::default(),
+      trace_sampling: TraceSampling::default(),
+      user_data: None,
+      deserialization_threads: 0,
+      rtps_endianness: Endianness::LittleEndian,
       #[cfg(feature = "security")]
       security_plugins: None,
       #[cfg(feature = "security")]
@@ -84,6 +126,125 @@ impl DomainParticipantBuilder {
     }
   }
 
+  /// Tune the UDP sockets used by the DomainParticipant, e.g. increase
+  /// kernel receive buffer size for high-throughput use.
+  pub fn socket_options(mut self, socket_options: SocketOptions) -> Self {
+    self.socket_options = socket_options;
+    self
+  }
+
+  /// Set limits on how much of a single incoming RTPS message (UDP datagram)
+  /// is processed. This protects against pathological or malicious packets.
+  pub fn message_receiver_limits(mut self, limits: MessageReceiverLimits) -> Self {
+    self.message_receiver_limits = limits;
+    self
+  }
+
+  /// Set how the event loop of the DomainParticipant shares its time between
+  /// received user data, ACKNACKs, timers, and Discovery, when all of them
+  /// have work. This keeps e.g. a burst of Discovery traffic from delaying
+  /// user data samples.
+  pub fn event_loop_weights(mut self, weights: EventLoopWeights) -> Self {
+    self.event_loop_weights = weights;
+    self
+  }
+
+  /// Set when the DataWriters of this participant remove acknowledged samples
+  /// from their history. Cleaning more often bounds memory use under heavy
+  /// writing, at the cost of more frequent work in the event loop.
+  pub fn writer_cache_cleaning(mut self, cache_cleaning: WriterCacheCleaning) -> Self {
+    self.writer_cache_cleaning = cache_cleaning;
+    self
+  }
+
+  /// Set how many of the per-sample tracing events the endpoints of this
+  /// participant emit. Has an effect only with feature `tracing`. Thinning
+  /// the events keeps tracing affordable at high sample rates.
+  pub fn trace_sampling(mut self, sampling: TraceSampling) -> Self {
+    self.trace_sampling = sampling;
+    self
+  }
+
+  /// Decide which discovered remote participants and endpoints are admitted
+  /// for matching. By default, everything is admitted.
+  pub fn discovery_filter(mut self, filter: DiscoveryFilter) -> Self {
+    self.discovery_filter = filter;
+    self
+  }
+
+  /// Match with the given remote participants and endpoints without SEDP,
+  /// see [`StaticDiscovery`].
+  pub fn static_discovery(mut self, static_discovery: StaticDiscovery) -> Self {
+    self.static_discovery = static_discovery;
+    self
+  }
+
+  /// Choose which built-in Discovery endpoints to create, e.g. leave out SEDP
+  /// when all endpoints are known from
+  /// [`static_discovery`](Self::static_discovery), or run a send-only
+  /// participant. See [`BuiltinEndpointOptions`].
+  pub fn builtin_endpoints(mut self, options: BuiltinEndpointOptions) -> Self {
+    self.builtin_endpoints = options;
+    self
+  }
+
+  /// Addresses to send RTPS ping (DDSPING) messages to, when
+  /// [`DomainParticipant::send_rtps_pings`] is called. The pings are sent
+  /// from the unicast listening sockets, so they open NAT bindings and
+  /// firewall holes for traffic from the peers.
+  pub fn rtps_ping_peers(mut self, peers: Vec<SocketAddr>) -> Self {
+    self.rtps_ping_options.peers = peers;
+    self
+  }
+
+  /// Reply to received RTPS ping messages by sending our SPDP participant
+  /// announcement to the address the ping came from. This lets a peer
+  /// discover us even if multicast does not reach it. Disabled by default.
+  ///
+  /// Each source address is answered at most once per second, so that
+  /// a flood of pings does not turn into a flood of announcements.
+  ///
+  /// Pings are always used as liveness hints of known remote participants.
+  pub fn reply_to_rtps_pings(mut self, reply: bool) -> Self {
+    self.rtps_ping_options.reply_with_spdp = reply;
+    self
+  }
+
+  /// USER_DATA to announce in the participant discovery data, e.g.
+  /// deployment metadata. Remote participants see it in
+  /// [`ParticipantDescription`](crate::dds::statusevents::ParticipantDescription).
+  /// It can be changed later with [`DomainParticipant::set_user_data`].
+  pub fn user_data(mut self, user_data: policy::UserData) -> Self {
+    self.user_data = Some(user_data);
+    self
+  }
+
+  /// Start a pool of this many threads for deserializing received samples.
+  /// DataReaders use the pool in
+  /// [`take_offloaded`](crate::with_key::DataReader::take_offloaded), so that
+  /// a batch of heavy samples, e.g. images, is deserialized in parallel.
+  /// The default is 0, i.e. no pool, and all deserialization happens on the
+  /// thread calling read or take.
+  pub fn deserialization_threads(mut self, thread_count: usize) -> Self {
+    self.deserialization_threads = thread_count;
+    self
+  }
+
+  /// Byte order of the RTPS submessages sent by the DataWriters of this
+  /// participant, including the built-in Discovery writers. The default is
+  /// little-endian. Remote participants must accept either, but some
+  /// embedded implementations only handle their native byte order.
+  ///
+  /// This does not change the encoding of sample data, which is decided by
+  /// the serializer adapter, e.g.
+  /// [`CDRSerializerAdapter<D, BigEndian>`](crate::CDRSerializerAdapter).
+  /// It can be overridden per DataWriter with
+  /// [`DataWriter::set_rtps_endianness`](crate::with_key::DataWriter::set_rtps_endianness).
+  pub fn rtps_endianness(mut self, endianness: Endianness) -> Self {
+    self.rtps_endianness = endianness;
+    self
+  }
+
   #[cfg(feature = "security")]
   /// Low-level security configuration, which allows supplying custom plugins.
   pub fn security(
@@ -125,11 +286,27 @@ impl DomainParticipantBuilder {
   pub fn build(#[allow(unused_mut)] mut self) -> CreateResult<DomainParticipant> {
     // QosPolicies with possible security properties, otherwise default
     let participant_qos = QosPolicies {
+      user_data: self.user_data.take(),
       #[cfg(feature = "security")]
       property: self.sec_properties,
       ..Default::default()
     };
 
+    #[cfg(feature = "security")]
+    if self.security_plugins.is_some()
+      && self.builtin_endpoints != BuiltinEndpointOptions::default()
+    {
+      return create_error_bad_parameter!(
+        "Built-in endpoints cannot be left out when security is enabled"
+      );
+    }
+    #[cfg(feature = "security")]
+    if self.security_plugins.is_some() && !self.static_discovery.is_empty() {
+      return create_error_bad_parameter!(
+        "Static discovery is not available when security is enabled"
+      );
+    }
+
     let candidate_participant_guid = GUID::new_participant_guid();
     #[cfg(not(feature = "security"))]
     let participant_guid = candidate_participant_guid;
@@ -242,6 +419,14 @@ impl DomainParticipantBuilder {
     #[cfg(feature = "security")]
     let security_plugins_handle = self.security_plugins.map(SecurityPluginsHandle::new);
 
+    let deserialization_pool = if self.deserialization_threads > 0 {
+      Some(Arc::new(DeserializationPool::new(
+        self.deserialization_threads,
+      )?))
+    } else {
+      None
+    };
+
     // intermediate DP wrapper
     let dp = DomainParticipantDisc::new(
       self.domain_id,
@@ -254,6 +439,15 @@ impl DomainParticipantBuilder {
       status_sender.clone(),
       status_receiver,
       security_plugins_handle.clone(),
+      self.socket_options,
+      self.message_receiver_limits,
+      self.rtps_ping_options,
+      self.event_loop_weights,
+      self.writer_cache_cleaning,
+      self.trace_sampling,
+      deserialization_pool,
+      self.rtps_endianness,
+      self.builtin_endpoints,
     )?;
     let self_locators = dp.self_locators();
 
@@ -262,11 +456,21 @@ impl DomainParticipantBuilder {
       dpi: Arc::new(Mutex::new(dp)),
     };
 
+    // The ping requester is created before Discovery starts, so that it is
+    // matched with the first remote participants, too.
+    if self.builtin_endpoints.ping_enabled() {
+      let requester = PingRequester::new(&dp)?;
+      dp.dpi.lock()?.ping_requester = Some(Arc::new(Mutex::new(requester)));
+    }
+
     let (discovery_started_sender, discovery_started_receiver) = std::sync::mpsc::channel();
 
     // Construct and start background thread
     let dp_clone = dp.weak_clone();
     let disc_db_clone = dp.discovery_db();
+    let discovery_filter = self.discovery_filter;
+    let static_discovery = self.static_discovery;
+    let builtin_endpoints = self.builtin_endpoints;
     let discovery_handle = thread::Builder::new()
       .name("RustDDS discovery thread".to_string())
       .spawn(move || {
@@ -280,6 +484,9 @@ impl DomainParticipantBuilder {
           self_locators,
           status_sender,
           security_plugins_handle,
+          discovery_filter,
+          static_discovery,
+          builtin_endpoints,
         ) {
           discovery.discovery_event_loop(); // run the event loop
         }
@@ -303,6 +510,28 @@ impl DomainParticipantBuilder {
   }
 }
 
+// An EntityId reserved for a DataReader or DataWriter that is being created.
+// If the creation fails, dropping this releases the EntityId. Once the
+// endpoint exists, it takes care of the release.
+pub(crate) struct EntityIdReservation {
+  participant: DomainParticipant,
+  entity_id: Option<EntityId>,
+}
+
+impl EntityIdReservation {
+  pub fn keep(mut self) {
+    self.entity_id = None;
+  }
+}
+
+impl Drop for EntityIdReservation {
+  fn drop(&mut self) {
+    if let Some(entity_id) = self.entity_id {
+      self.participant.release_endpoint_entity_id(entity_id);
+    }
+  }
+}
+
 /// DDS DomainParticipant
 ///
 /// It is recommended that only one DomainParticipant per OS process is created,
@@ -421,6 +650,111 @@ impl DomainParticipant {
     self.dpi.lock()?.find_topic(&w, name, timeout)
   }
 
+  /// Registers the data type `D` with serializer adapter `SA` and
+  /// deserializer adapter `DA` under the DDS type name `type_name`. The
+  /// returned [`TypeSupport`] creates Topics, DataWriters and DataReaders of
+  /// this type.
+  ///
+  /// Registering the same type again under the same name returns a new
+  /// handle to the registration. Registering a different type, or different
+  /// adapters, under an already registered name fails with
+  /// [`CreateError::BadParameter`].
+  pub fn register_type<D: 'static, SA: 'static, DA: 'static>(
+    &self,
+    type_name: &str,
+  ) -> CreateResult<TypeSupport<D, SA, DA>> {
+    let registration = TypeSupport::<D, SA, DA>::registration(type_name);
+    let w = self.weak_clone();
+    let mut dp = self.dpi.lock()?;
+    match dp.dpi.registered_types.get(type_name) {
+      Some(existing) if existing.type_id != registration.type_id => {
+        return create_error_bad_parameter!(
+          "Type name {} is already registered for {}",
+          type_name,
+          existing.rust_type_name
+        );
+      }
+      Some(_) => (),
+      None => {
+        dp.dpi
+          .registered_types
+          .insert(type_name.to_string(), registration);
+      }
+    }
+    Ok(TypeSupport::new(type_name.to_string(), w))
+  }
+
+  /// Registers the data type `D` with CDR serialization. See
+  /// [`Self::register_type`].
+  pub fn register_type_cdr<D: 'static>(&self, type_name: &str) -> CreateResult<CdrTypeSupport<D>> {
+    self.register_type(type_name)
+  }
+
+  /// Gets the registration of a type name, if it was registered with data type
+  /// `D` and adapters `SA` and `DA`. This maps e.g. type names of
+  /// [discovered topics](Self::discovered_topics) to Rust types.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::*;
+  /// #
+  /// #[derive(Serialize, Deserialize)]
+  /// struct Temperature {
+  ///   celsius: f32,
+  /// }
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// domain_participant
+  ///   .register_type_cdr::<Temperature>("Temperature")
+  ///   .unwrap();
+  ///
+  /// for discovered in domain_participant.discovered_topics() {
+  ///   let type_support = domain_participant.type_support::<
+  ///     Temperature,
+  ///     CDRSerializerAdapter<Temperature>,
+  ///     CDRDeserializerAdapter<Temperature>,
+  ///   >(discovered.type_name());
+  ///   if let Some(type_support) = type_support {
+  ///     let topic = type_support
+  ///       .create_topic(discovered.topic_name().clone(), &qos, TopicKind::NoKey)
+  ///       .unwrap();
+  ///     let reader = type_support
+  ///       .create_datareader_no_key(&subscriber, &topic, None)
+  ///       .unwrap();
+  ///   }
+  /// }
+  /// ```
+  pub fn type_support<D: 'static, SA: 'static, DA: 'static>(
+    &self,
+    type_name: &str,
+  ) -> Option<TypeSupport<D, SA, DA>> {
+    let registration = TypeSupport::<D, SA, DA>::registration(type_name);
+    let w = self.weak_clone();
+    let dp = self.dpi.lock().ok()?;
+    dp.dpi
+      .registered_types
+      .get(type_name)
+      .filter(|existing| existing.type_id == registration.type_id)
+      .map(|_| TypeSupport::new(type_name.to_string(), w))
+  }
+
+  /// Lists the types registered with [`Self::register_type`].
+  pub fn registered_types(&self) -> Vec<RegisteredType> {
+    self
+      .dpi
+      .lock()
+      .unwrap()
+      .dpi
+      .registered_types
+      .values()
+      .cloned()
+      .collect()
+  }
+
   /// # Examples
   ///
   /// ```
@@ -462,6 +796,77 @@ impl DomainParticipant {
     self.dpi.lock().unwrap().discovered_topics()
   }
 
+  /// Gets a copy of the discovery database: known participants with their
+  /// locators and liveliness state, local and remote readers and writers with
+  /// their QoS, and discovered topics. The snapshot is serializable with
+  /// serde, e.g. to JSON for a monitoring dashboard.
+  ///
+  /// Use [`Self::status_listener`] to follow changes after the snapshot.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use rustdds::DomainParticipant;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let snapshot = domain_participant.discovery_snapshot();
+  /// for participant in snapshot.participants.iter() {
+  ///   // do something
+  /// }
+  /// ```
+  pub fn discovery_snapshot(&self) -> DiscoverySnapshot {
+    discovery_db_read(&self.discovery_db()).snapshot()
+  }
+
+  /// Looks up a discovered DomainParticipant by its instance handle.
+  ///
+  /// Instance handles of participants, DataReaders and DataWriters are
+  /// derived from their GUIDs, so a handle can also be made from a GUID, e.g.
+  /// one reported in a [`DomainParticipantStatusEvent`], with
+  /// [`InstanceHandle::from`].
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use rustdds::*;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let handle = InstanceHandle::from(GUID::GUID_UNKNOWN);
+  /// assert!(domain_participant.discovered_participant_data(handle).is_none());
+  /// ```
+  pub fn discovered_participant_data(
+    &self,
+    handle: InstanceHandle,
+  ) -> Option<ParticipantDescription> {
+    let guid = GUID::from(handle);
+    discovery_db_read(&self.discovery_db())
+      .find_participant_proxy(guid.prefix)
+      .filter(|p| p.participant_guid == guid)
+      .map(ParticipantDescription::from)
+  }
+
+  /// Looks up a discovered remote DataWriter by its instance handle. See
+  /// [`Self::discovered_participant_data`].
+  pub fn discovered_publication_data(
+    &self,
+    handle: InstanceHandle,
+  ) -> Option<PublicationBuiltinTopicData> {
+    discovery_db_read(&self.discovery_db())
+      .get_topic_writer(&GUID::from(handle))
+      .map(|dwd| dwd.publication_topic_data.clone())
+  }
+
+  /// Looks up a discovered remote DataReader by its instance handle. See
+  /// [`Self::discovered_participant_data`].
+  pub fn discovered_subscription_data(
+    &self,
+    handle: InstanceHandle,
+  ) -> Option<SubscriptionBuiltinTopicData> {
+    discovery_db_read(&self.discovery_db())
+      .get_topic_reader(&GUID::from(handle))
+      .map(|drd| drd.subscription_topic_data.clone())
+  }
+
   /// Manually asserts liveliness, affecting all writers with
   /// LIVELINESS QoS of MANUAL_BY_PARTICIPANT created by
   /// this particular participant.
@@ -478,6 +883,36 @@ impl DomainParticipant {
     self.dpi.lock()?.assert_liveliness()
   }
 
+  /// Send RTPS ping messages to the peers configured with
+  /// [`DomainParticipantBuilder::rtps_ping_peers`]. Call this periodically to
+  /// keep NAT bindings open.
+  pub fn send_rtps_pings(&self) -> WriteResult<(), ()> {
+    self.dpi.lock()?.send_rtps_pings()
+  }
+
+  /// Send `count` pings to the remote participant `remote_participant`, one
+  /// at a time, and collect the round-trip times. Each ping waits for its
+  /// reply at most `timeout` before the next one is sent. Concurrent calls
+  /// are serialized. See the [`ping`](crate::ping) module.
+  ///
+  /// Fails with [`WriteError::NotEnabled`], if this participant was built
+  /// without the [`ping`](BuiltinEndpointOptions::ping) endpoints.
+  pub fn measure_rtt(
+    &self,
+    remote_participant: GuidPrefix,
+    count: usize,
+    timeout: Duration,
+  ) -> WriteResult<RttStatistics, ()> {
+    let requester = self
+      .dpi
+      .lock()?
+      .ping_requester
+      .clone()
+      .ok_or(WriteError::NotEnabled { data: () })?;
+    let mut requester = requester.lock()?;
+    Ok(requester.measure_rtt(remote_participant, count, timeout))
+  }
+
   /// Get a `DomainDomainParticipantStatusListener` that can be used
   /// to get `DomainParticipantStatusEvent`s for this DomainParticipant.
   pub fn status_listener(&self) -> DomainParticipantStatusListener {
@@ -494,17 +929,100 @@ impl DomainParticipant {
     self.dpi.lock().unwrap().dds_cache()
   }
 
-  #[cfg(feature = "security")] // just to avoid warning
   pub(crate) fn qos(&self) -> QosPolicies {
     self.dpi.lock().unwrap().qos()
   }
 
+  pub(crate) fn builtin_endpoint_options(&self) -> BuiltinEndpointOptions {
+    self.dpi.lock().unwrap().builtin_endpoint_options()
+  }
+
+  /// USER_DATA announced in the participant discovery data (SPDP)
+  pub fn user_data(&self) -> Option<policy::UserData> {
+    self.qos().user_data()
+  }
+
+  /// Changes the USER_DATA announced to other participants. The new value is
+  /// sent out immediately and included in all following participant
+  /// announcements. `None` removes the user data.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// # use rustdds::{DomainParticipant, policy::UserData};
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// domain_participant
+  ///   .set_user_data(Some(UserData { value: b"host=node1".to_vec() }))
+  ///   .unwrap();
+  /// ```
+  pub fn set_user_data(&self, user_data: Option<policy::UserData>) -> WriteResult<(), ()> {
+    self.dpi.lock()?.set_user_data(user_data)
+  }
+
+  /// How many received messages and submessages have been rejected due to
+  /// [`DomainParticipantBuilder::message_receiver_limits`], and how many
+  /// submessages of unknown or vendor-specific kinds have been skipped.
+  pub fn message_receiver_statistics(&self) -> MessageReceiverStatistics {
+    let dpd = self.dpi.lock().unwrap();
+    let statistics = dpd.dpi.message_receiver_statistics.clone();
+    drop(dpd);
+    let statistics = statistics.lock().unwrap_or_else(|e| e.into_inner());
+    statistics.clone()
+  }
+
   pub(crate) fn discovery_db(&self) -> Arc<RwLock<DiscoveryDB>> {
     self.dpi.lock().unwrap().dpi.discovery_db.clone()
   }
 
-  pub(crate) fn new_entity_id(&self, entity_kind: EntityKind) -> EntityId {
-    self.dpi.lock().unwrap().new_entity_id(entity_kind)
+  pub(crate) fn deserialization_pool(&self) -> Option<Arc<DeserializationPool>> {
+    self.dpi.lock().unwrap().dpi.deserialization_pool.clone()
+  }
+
+  pub(crate) fn delivery_journals(&self) -> Arc<DeliveryJournals> {
+    self.dpi.lock().unwrap().dpi.delivery_journals.clone()
+  }
+
+  pub(crate) fn new_endpoint_entity_id(&self, entity_kind: EntityKind) -> EntityId {
+    self.dpi.lock().unwrap().new_endpoint_entity_id(entity_kind)
+  }
+
+  // The EntityId is released again when the returned reservation is dropped,
+  // unless it is kept.
+  pub(crate) fn reserve_endpoint_entity_id(
+    &self,
+    entity_id: EntityId,
+  ) -> CreateResult<EntityIdReservation> {
+    self
+      .dpi
+      .lock()
+      .unwrap()
+      .reserve_endpoint_entity_id(entity_id)?;
+    Ok(EntityIdReservation {
+      participant: self.clone(),
+      entity_id: Some(entity_id),
+    })
+  }
+
+  pub(crate) fn release_endpoint_entity_id(&self, entity_id: EntityId) {
+    self
+      .dpi
+      .lock()
+      .unwrap()
+      .release_endpoint_entity_id(entity_id);
+  }
+
+  // QoS of a local Topic was changed. Re-announce it, if it has been announced.
+  pub(crate) fn local_topic_qos_updated(&self, topic: &Topic) {
+    let dpd = self.dpi.lock().unwrap();
+    if discovery_db_write(&dpd.dpi.discovery_db).update_local_topic_qos(topic) {
+      dpd
+        .discovery_command_sender
+        .try_send(DiscoveryCommand::AddTopic {
+          topic_name: topic.name(),
+        })
+        .unwrap_or_else(|e| error!("Cannot re-announce topic {}: {e:?}", topic.name()));
+    }
   }
 
   pub(crate) fn self_locators(&self) -> HashMap<mio_06::Token, Vec<Locator>> {
@@ -767,8 +1285,8 @@ pub(crate) struct DomainParticipantDisc {
   // Discovery control
   discovery_command_sender: mio_channel::SyncSender<DiscoveryCommand>,
   discovery_join_handle: mio_channel::Receiver<JoinHandle<()>>,
-  // This allows deterministic generation of EntityIds for DataReader, DataWriter, etc.
-  entity_id_generator: atomic::AtomicU32,
+  // Behind its own lock, because measuring takes long
+  ping_requester: Option<Arc<Mutex<PingRequester>>>,
 }
 
 impl DomainParticipantDisc {
@@ -784,6 +1302,15 @@ impl DomainParticipantDisc {
     status_sender: StatusChannelSender<DomainParticipantStatusEvent>,
     status_receiver: StatusChannelReceiver<DomainParticipantStatusEvent>,
     security_plugins_handle: Option<SecurityPluginsHandle>,
+    socket_options: SocketOptions,
+    message_receiver_limits: MessageReceiverLimits,
+    rtps_ping_options: RtpsPingOptions,
+    event_loop_weights: EventLoopWeights,
+    writer_cache_cleaning: WriterCacheCleaning,
+    trace_sampling: TraceSampling,
+    deserialization_pool: Option<Arc<DeserializationPool>>,
+    rtps_endianness: Endianness,
+    builtin_endpoints: BuiltinEndpointOptions,
   ) -> CreateResult<Self> {
     let dpi = DomainParticipantInner::new(
       domain_id,
@@ -795,24 +1322,35 @@ impl DomainParticipantDisc {
       status_sender,
       status_receiver,
       security_plugins_handle,
+      socket_options,
+      message_receiver_limits,
+      rtps_ping_options,
+      event_loop_weights,
+      writer_cache_cleaning,
+      trace_sampling,
+      deserialization_pool,
+      rtps_endianness,
+      builtin_endpoints,
     )?;
 
     Ok(Self {
       dpi,
       discovery_command_sender,
       discovery_join_handle,
-      entity_id_generator: atomic::AtomicU32::new(0),
+      ping_requester: None,
     })
   }
 
-  // This generates identifiers that consist of given EntityKind and arbitrary,
-  // unique identifier.
-  pub(crate) fn new_entity_id(&self, entity_kind: EntityKind) -> EntityId {
-    let [_goldilocks, papa_byte, mama_byte, baby_byte] = self
-      .entity_id_generator
-      .fetch_add(1, atomic::Ordering::Relaxed)
-      .to_be_bytes();
-    EntityId::new([papa_byte, mama_byte, baby_byte], entity_kind)
+  pub(crate) fn new_endpoint_entity_id(&self, entity_kind: EntityKind) -> EntityId {
+    self.dpi.new_endpoint_entity_id(entity_kind)
+  }
+
+  pub(crate) fn reserve_endpoint_entity_id(&self, entity_id: EntityId) -> CreateResult<()> {
+    self.dpi.reserve_endpoint_entity_id(entity_id)
+  }
+
+  pub(crate) fn release_endpoint_entity_id(&self, entity_id: EntityId) {
+    self.dpi.release_endpoint_entity_id(entity_id);
   }
 
   pub fn create_publisher(
@@ -872,11 +1410,27 @@ impl DomainParticipantDisc {
     self.dpi.dds_cache()
   }
 
-  #[cfg(feature = "security")] // just to avoid warning
   pub(crate) fn qos(&self) -> QosPolicies {
     self.dpi.qos()
   }
 
+  pub(crate) fn builtin_endpoint_options(&self) -> BuiltinEndpointOptions {
+    self.dpi.builtin_endpoint_options
+  }
+
+  pub(crate) fn set_user_data(
+    &mut self,
+    user_data: Option<policy::UserData>,
+  ) -> WriteResult<(), ()> {
+    self.dpi.set_user_data(user_data);
+    // Re-send participant data now instead of waiting for the periodic
+    // announcement.
+    self
+      .discovery_command_sender
+      .send(DiscoveryCommand::RefreshParticipantInfo)
+      .map_err(|_e| WriteError::WouldBlock { data: () })
+  }
+
   // pub(crate) fn discovery_db(&self) -> Arc<RwLock<DiscoveryDB>> {
   //   self.dpi.lock().unwrap().discovery_db.clone()
   // }
@@ -892,6 +1446,14 @@ impl DomainParticipantDisc {
       .map_err(|_e| WriteError::WouldBlock { data: () })
   }
 
+  pub(crate) fn send_rtps_pings(&self) -> WriteResult<(), ()> {
+    self
+      .dpi
+      .stop_poll_sender
+      .send(EventLoopCommand::SendRtpsPings)
+      .map_err(|_e| WriteError::WouldBlock { data: () })
+  }
+
   pub(crate) fn self_locators(&self) -> HashMap<mio_06::Token, Vec<Locator>> {
     self.dpi.self_locators.clone()
   }
@@ -912,6 +1474,9 @@ impl Drop for DomainParticipantDisc {
   fn drop(&mut self) {
     info!("===== RustDDS shutting down ===== .drop() DomainParticipantDisc");
 
+    // Remove the ping endpoints while Discovery is still there to hear it.
+    self.ping_requester = None;
+
     debug!("Wan dp_event_loop about stop.");
     if self
       .dpi
@@ -946,9 +1511,14 @@ pub(crate) struct DomainParticipantInner {
   participant_id: u16,
 
   my_guid: GUID,
-  #[cfg(feature = "security")] // just to avoid warning
   my_qos_policies: QosPolicies,
 
+  // This allows deterministic generation of EntityIds for DataReader, DataWriter, etc.
+  entity_id_generator: atomic::AtomicU32,
+  // EntityIds of the local DataReaders and DataWriters, whether generated or
+  // requested by the application. Used to detect collisions.
+  endpoint_entity_ids: Mutex<BTreeSet<EntityId>>,
+
   // Adding Readers
   sender_add_reader: mio_channel::SyncSender<ReaderIngredients>,
   sender_remove_reader: mio_channel::SyncSender<GUID>,
@@ -973,10 +1543,30 @@ pub(crate) struct DomainParticipantInner {
   self_locators: HashMap<mio_06::Token, Vec<Locator>>,
 
   security_plugins_handle: Option<SecurityPluginsHandle>,
+
+  // Counts of messages rejected by the MessageReceiver in the event loop
+  message_receiver_statistics: Arc<Mutex<MessageReceiverStatistics>>,
+
+  // Worker threads for DataReaders to offload deserialization, if configured
+  deserialization_pool: Option<Arc<DeserializationPool>>,
+
+  // Journals of delivered samples, shared by DataReaders by name
+  delivery_journals: Arc<DeliveryJournals>,
+
+  // Data types registered by the application, by DDS type name
+  registered_types: BTreeMap<String, RegisteredType>,
+
+  // Default byte order of RTPS messages from our Writers
+  rtps_endianness: Endianness,
+
+  // Which built-in Discovery endpoints exist
+  builtin_endpoint_options: BuiltinEndpointOptions,
 }
 
 impl Drop for DomainParticipantInner {
   fn drop(&mut self) {
+    local_endpoints::remove_participant(self.my_guid.prefix);
+
     // if send has an error simply leave as we have lost control of the
     // ev_loop_thread anyways
     if self.stop_poll_sender.send(EventLoopCommand::Stop).is_err() {
@@ -1004,40 +1594,62 @@ impl DomainParticipantInner {
   fn new(
     domain_id: u16,
     participant_guid: GUID,
-    _qos_policies: QosPolicies,
+    qos_policies: QosPolicies,
     discovery_update_notification_receiver: mio_channel::Receiver<DiscoveryNotificationType>,
     discovery_command_sender: mio_channel::SyncSender<DiscoveryCommand>,
     spdp_liveness_sender: mio_channel::SyncSender<GuidPrefix>,
     status_sender: StatusChannelSender<DomainParticipantStatusEvent>,
     status_receiver: StatusChannelReceiver<DomainParticipantStatusEvent>,
     security_plugins_handle: Option<SecurityPluginsHandle>,
+    socket_options: SocketOptions,
+    message_receiver_limits: MessageReceiverLimits,
+    rtps_ping_options: RtpsPingOptions,
+    event_loop_weights: EventLoopWeights,
+    writer_cache_cleaning: WriterCacheCleaning,
+    trace_sampling: TraceSampling,
+    deserialization_pool: Option<Arc<DeserializationPool>>,
+    rtps_endianness: Endianness,
+    builtin_endpoints: BuiltinEndpointOptions,
   ) -> CreateResult<Self> {
-    #[cfg(not(feature = "security"))]
-    let _dummy = _qos_policies; // to make clippy happy
-
     let mut listeners = HashMap::new();
 
-    match UDPListener::new_multicast(
-      "0.0.0.0",
-      spdp_well_known_multicast_port(domain_id),
-      Ipv4Addr::new(239, 255, 0, 1),
-    ) {
-      Ok(l) => {
-        listeners.insert(DISCOVERY_MUL_LISTENER_TOKEN, l);
+    // A send-only participant has no use for multicast, which is only sent to
+    // readers.
+    let listen_multicast = !builtin_endpoints.send_only;
+
+    if listen_multicast {
+      match UDPListener::new_multicast_with_options(
+        "0.0.0.0",
+        spdp_well_known_multicast_port(domain_id),
+        Ipv4Addr::new(239, 255, 0, 1),
+        &socket_options,
+      ) {
+        Ok(l) => {
+          listeners.insert(DISCOVERY_MUL_LISTENER_TOKEN, l);
+        }
+        Err(e) => warn!("Cannot get multicast discovery listener: {e:?}"),
       }
-      Err(e) => warn!("Cannot get multicast discovery listener: {e:?}"),
     }
 
     let mut participant_id = 0;
 
     let mut discovery_listener = None;
 
+    // The participant_id is selected by finding a free discovery port. With
+    // SO_REUSEPORT, binding would succeed also on a port that another
+    // participant already has, so it is not used here.
+    let probe_socket_options = SocketOptions {
+      reuse_port: None,
+      ..socket_options.clone()
+    };
+
     // Magic value 120 below is from RTPS spec 2.5 Section "9.6.2.3 Default Port
     // Numbers"
     while discovery_listener.is_none() && participant_id < 120 {
-      discovery_listener = UDPListener::new_unicast(
+      discovery_listener = UDPListener::new_unicast_with_options(
         "0.0.0.0",
         spdp_well_known_unicast_port(domain_id, participant_id),
+        &probe_socket_options,
       )
       .ok();
       if discovery_listener.is_none() {
@@ -1056,26 +1668,30 @@ impl DomainParticipantInner {
 
     // Now the user traffic listeners
 
-    match UDPListener::new_multicast(
-      "0.0.0.0",
-      user_traffic_multicast_port(domain_id),
-      Ipv4Addr::new(239, 255, 0, 1),
-    ) {
-      Ok(l) => {
-        listeners.insert(USER_TRAFFIC_MUL_LISTENER_TOKEN, l);
+    if listen_multicast {
+      match UDPListener::new_multicast_with_options(
+        "0.0.0.0",
+        user_traffic_multicast_port(domain_id),
+        Ipv4Addr::new(239, 255, 0, 1),
+        &socket_options,
+      ) {
+        Ok(l) => {
+          listeners.insert(USER_TRAFFIC_MUL_LISTENER_TOKEN, l);
+        }
+        Err(e) => warn!("Cannot get multicast user traffic listener: {e:?}"),
       }
-      Err(e) => warn!("Cannot get multicast user traffic listener: {e:?}"),
     }
 
-    let user_traffic_listener = UDPListener::new_unicast(
+    let user_traffic_listener = UDPListener::new_unicast_with_options(
       "0.0.0.0",
       user_traffic_unicast_port(domain_id, participant_id),
+      &socket_options,
     )
     .or_else(|e| {
       if matches!(e.kind(), ErrorKind::AddrInUse) {
         // If we do not get the preferred listening port,
         // try again, with "any" port number.
-        UDPListener::new_unicast("0.0.0.0", 0).or_else(|e| {
+        UDPListener::new_unicast_with_options("0.0.0.0", 0, &socket_options).or_else(|e| {
           create_error_out_of_resources!(
             "Could not open unicast user traffic listener, any port number: {:?}",
             e
@@ -1092,7 +1708,7 @@ impl DomainParticipantInner {
     let self_locators: HashMap<mio_06::Token, Vec<Locator>> = listeners
       .iter()
       .map(|(t, l)| match l.to_locator_address() {
-        Ok(locs) => (*t, locs),
+        Ok(locs) => (*t, socket_options.announced_locators(locs)),
         Err(e) => {
           error!("No local network address for token {:?}: {:?}", t, e);
           (*t, vec![])
@@ -1131,6 +1747,8 @@ impl DomainParticipantInner {
     let (stop_poll_sender, stop_poll_receiver) = mio_channel::channel();
 
     // Launch the background thread for DomainParticipant
+    let message_receiver_statistics = Arc::new(Mutex::new(MessageReceiverStatistics::default()));
+    let message_receiver_statistics_clone = message_receiver_statistics.clone();
     let disc_db_clone = discovery_db.clone();
     let security_plugins_clone = security_plugins_handle.clone();
     let ev_loop_handle = thread::Builder::new()
@@ -1163,8 +1781,15 @@ impl DomainParticipantInner {
           spdp_liveness_sender,
           status_sender,
           security_plugins_clone,
+          socket_options,
+          message_receiver_limits,
+          message_receiver_statistics_clone,
+          rtps_ping_options,
+          event_loop_weights,
+          writer_cache_cleaning,
+          trace_sampling,
         );
-        dp_event_loop.event_loop();
+        ParticipantTrace::new(domain_id, participant_guid).in_scope(|| dp_event_loop.event_loop());
       })?;
 
     info!(
@@ -1175,12 +1800,15 @@ impl DomainParticipantInner {
       cfg!(security)
     );
 
+    local_endpoints::add_participant(participant_guid.prefix);
+
     Ok(Self {
       domain_id,
       participant_id,
-      #[cfg(feature = "security")]
-      my_qos_policies: _qos_policies,
+      my_qos_policies: qos_policies,
       my_guid: participant_guid,
+      entity_id_generator: atomic::AtomicU32::new(0),
+      endpoint_entity_ids: Mutex::new(BTreeSet::new()),
       sender_add_reader,
       sender_remove_reader,
       stop_poll_sender,
@@ -1193,6 +1821,12 @@ impl DomainParticipantInner {
       status_receiver,
       self_locators,
       security_plugins_handle,
+      message_receiver_statistics,
+      deserialization_pool,
+      delivery_journals: Arc::default(),
+      registered_types: BTreeMap::new(),
+      rtps_endianness,
+      builtin_endpoint_options: builtin_endpoints,
     })
   }
 
@@ -1200,11 +1834,68 @@ impl DomainParticipantInner {
     self.dds_cache.clone()
   }
 
-  #[cfg(feature = "security")] // just to avoid warning
   pub(crate) fn qos(&self) -> QosPolicies {
     self.my_qos_policies.clone()
   }
 
+  // This generates identifiers that consist of given EntityKind and arbitrary,
+  // unique identifier.
+  pub(crate) fn new_entity_id(&self, entity_kind: EntityKind) -> EntityId {
+    let [_goldilocks, papa_byte, mama_byte, baby_byte] = self
+      .entity_id_generator
+      .fetch_add(1, atomic::Ordering::Relaxed)
+      .to_be_bytes();
+    EntityId::new([papa_byte, mama_byte, baby_byte], entity_kind)
+  }
+
+  // Generates an EntityId for a DataReader or DataWriter, skipping those in
+  // use. Reserve it with reserve_endpoint_entity_id().
+  pub(crate) fn new_endpoint_entity_id(&self, entity_kind: EntityKind) -> EntityId {
+    let in_use = self
+      .endpoint_entity_ids
+      .lock()
+      .unwrap_or_else(|e| e.into_inner());
+    loop {
+      let entity_id = self.new_entity_id(entity_kind);
+      if !in_use.contains(&entity_id) {
+        return entity_id;
+      }
+    }
+  }
+
+  pub(crate) fn reserve_endpoint_entity_id(&self, entity_id: EntityId) -> CreateResult<()> {
+    let newly_reserved = self
+      .endpoint_entity_ids
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .insert(entity_id);
+    if newly_reserved {
+      Ok(())
+    } else {
+      create_error_bad_parameter!(
+        "EntityId {entity_id:?} is already in use in participant {:?}",
+        self.my_guid.prefix
+      )
+    }
+  }
+
+  // The DataReader or DataWriter is gone, and its EntityId may be reused.
+  pub(crate) fn release_endpoint_entity_id(&self, entity_id: EntityId) {
+    self
+      .endpoint_entity_ids
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .remove(&entity_id);
+  }
+
+  fn new_guid(&self, entity_kind: EntityKind) -> GUID {
+    self.my_guid.from_prefix(self.new_entity_id(entity_kind))
+  }
+
+  pub(crate) fn set_user_data(&mut self, user_data: Option<policy::UserData>) {
+    self.my_qos_policies.user_data = user_data;
+  }
+
   // Publisher and subscriber creation
   //
   // There are no delete function for publisher or subscriber. Deletion is
@@ -1218,6 +1909,7 @@ impl DomainParticipantInner {
   ) -> CreateResult<Publisher> {
     Ok(Publisher::new(
       domain_participant.clone(),
+      self.new_guid(EntityKind::WRITER_GROUP_USER_DEFINED),
       self.discovery_db.clone(),
       qos.clone(),
       qos.clone(),
@@ -1225,6 +1917,7 @@ impl DomainParticipantInner {
       self.remove_writer_sender.clone(),
       discovery_command,
       self.security_plugins_handle.clone(),
+      self.rtps_endianness,
     ))
   }
 
@@ -1236,6 +1929,7 @@ impl DomainParticipantInner {
   ) -> CreateResult<Subscriber> {
     Ok(Subscriber::new(
       domain_participant.clone(),
+      self.new_guid(EntityKind::READER_GROUP_USER_DEFINED),
       self.discovery_db.clone(),
       qos.clone(),
       self.sender_add_reader.clone(),
@@ -1288,13 +1982,28 @@ impl DomainParticipantInner {
       };
     }
 
+    // A Topic with another type elsewhere in the domain is still created, but
+    // it does not interoperate with those. Such definitions are reported in the
+    // InconsistentTopic status of the new Topic.
+    let inconsistent_topic_counter =
+      discovery_db_write(&self.discovery_db).register_local_topic(&name, &type_desc);
+    let inconsistent_count = inconsistent_topic_counter.load(atomic::Ordering::Relaxed);
+    if inconsistent_count > 0 {
+      warn!(
+        "create_topic: Topic {} has type {}, but {} remote definitions have another type",
+        name, type_desc, inconsistent_count
+      );
+    }
+
     let topic_type_desc = TypeDesc::new(type_desc);
     let topic = Topic::new(
       domain_participant_weak,
+      self.new_guid(EntityKind::TOPIC_VENDOR_SPECIFIC),
       name.clone(),
       topic_type_desc.clone(),
       qos,
       topic_kind,
+      inconsistent_topic_counter,
     );
 
     // Create the topic cache entry
@@ -1352,12 +2061,16 @@ impl DomainParticipantInner {
     domain_participant_weak: &DomainParticipantWeak,
     name: &str,
   ) -> CreateResult<Option<Topic>> {
-    let db = self
+    // Clone the topic data out, because create_topic() needs the
+    // DiscoveryDB, too.
+    let discovered_topic = self
       .discovery_db
       .read()
       .map_err(|_| CreateError::Poisoned {
         reason: "discovery db".to_string(),
-      })?;
+      })?
+      .get_topic(name)
+      .cloned();
 
     let build_topic_fn = |d: &DiscoveredTopicData| {
       let qos = d.topic_data.qos();
@@ -1370,9 +2083,9 @@ impl DomainParticipantInner {
       self.create_topic(domain_participant_weak, name, type_desc, &qos, topic_kind)
     };
 
-    if let Some(d) = db.get_topic(name) {
+    if let Some(d) = discovered_topic {
       // build a Topic from DiscoveredTopicData
-      build_topic_fn(d).map(Some)
+      build_topic_fn(&d).map(Some)
     } else {
       Ok(None)
     }
@@ -1462,6 +2175,8 @@ mod tests {
   use std::{
     collections::BTreeSet,
     net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    thread,
+    time::{Duration, Instant},
   };
 
   use enumflags2::BitFlags;
@@ -1470,7 +2185,10 @@ mod tests {
   use byteorder::LittleEndian;
 
   use crate::{
-    dds::{qos::QosPolicies, topic::TopicKind},
+    dds::{
+      qos::{policy::Reliability, QosPolicies},
+      topic::TopicKind,
+    },
     messages::{
       header::Header,
       protocol_id::ProtocolId,
@@ -1478,17 +2196,26 @@ mod tests {
       submessages::submessages::{AckNack, SubmessageHeader, SubmessageKind, *},
       vendor_id::VendorId,
     },
-    network::{constant::user_traffic_unicast_port, udp_sender::UDPSender},
-    rtps::{submessage::*, Message, Submessage},
+    network::{
+      constant::user_traffic_unicast_port, socket_options::SocketOptions, udp_sender::UDPSender,
+    },
+    rtps::{
+      message_receiver::{
+        is_rtps_ping, rtps_ping_message, MessageReceiverLimits, MessageReceiverStatistics,
+      },
+      submessage::*,
+      Message, Submessage,
+    },
     serialization::cdr_serializer::CDRSerializerAdapter,
     structure::{
-      guid::{EntityId, GUID},
+      entity::RTPSEntity,
+      guid::{EntityId, EntityKind, GUID},
       locator::Locator,
       sequence_number::{SequenceNumber, SequenceNumberSet},
     },
     test::random_data::RandomData,
   };
-  use super::DomainParticipant;
+  use super::{DomainParticipant, DomainParticipantBuilder};
 
   // TODO: improve basic test when more or the structure is known
   #[test]
@@ -1503,6 +2230,58 @@ mod tests {
 
     // TODO: get result data from Reader
   }
+
+  #[test]
+  fn dp_entity_guids() {
+    let domain_participant = DomainParticipant::new(0).expect("Participant creation failed!");
+    let qos = QosPolicies::qos_none();
+    let publisher = domain_participant.create_publisher(&qos).unwrap();
+    let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+    let topic = domain_participant
+      .create_topic(
+        "dp_entity_guids".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(&topic, None)
+      .unwrap();
+
+    assert_eq!(
+      domain_participant.entity_kind(),
+      EntityKind::PARTICIPANT_BUILT_IN
+    );
+    assert_eq!(
+      publisher.entity_kind(),
+      EntityKind::WRITER_GROUP_USER_DEFINED
+    );
+    assert_eq!(
+      subscriber.entity_kind(),
+      EntityKind::READER_GROUP_USER_DEFINED
+    );
+    assert!(topic.entity_kind().is_topic());
+    assert!(writer.entity_kind().is_endpoint() && writer.entity_kind().is_with_key());
+
+    let guids: BTreeSet<GUID> = [
+      publisher.guid(),
+      subscriber.guid(),
+      topic.guid(),
+      writer.guid(),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(guids.len(), 4);
+    assert!(guids
+      .iter()
+      .all(|g| g.prefix == domain_participant.guid_prefix()));
+    assert_eq!(topic.clone().guid(), topic.guid());
+    assert_ne!(
+      publisher,
+      domain_participant.create_publisher(&qos).unwrap()
+    );
+  }
   #[test]
   fn dp_writer_heartbeat_test() {
     let domain_participant = DomainParticipant::new(0).expect("Participant creation failed!");
@@ -1591,4 +2370,322 @@ mod tests {
     let locators = vec![Locator::UdpV4(socket_address)];
     sender.send_to_locator_list(&_data, &locators);
   }
+
+  #[test]
+  fn dp_reuse_port_keeps_participant_ids_distinct() {
+    let socket_options = SocketOptions {
+      reuse_port: Some(true),
+      ..SocketOptions::default()
+    };
+    let dp_1 = DomainParticipantBuilder::new(0)
+      .socket_options(socket_options.clone())
+      .build()
+      .expect("Participant creation failed!");
+    let dp_2 = DomainParticipantBuilder::new(0)
+      .socket_options(socket_options)
+      .build()
+      .expect("Participant creation failed!");
+    assert_ne!(dp_1.participant_id(), dp_2.participant_id());
+  }
+
+  #[test]
+  fn dp_message_receiver_statistics() {
+    let domain_participant = DomainParticipantBuilder::new(0)
+      .message_receiver_limits(MessageReceiverLimits {
+        max_message_size: 100,
+        ..MessageReceiverLimits::default()
+      })
+      .build()
+      .expect("Participant creation failed!");
+    assert_eq!(
+      domain_participant.message_receiver_statistics(),
+      MessageReceiverStatistics::default()
+    );
+
+    // RTPS header and padding, over the size limit
+    let mut data = b"RTPS\x02\x03\x01\x0f".to_vec();
+    data.resize(200, 0);
+    let port = user_traffic_unicast_port(
+      domain_participant.domain_id(),
+      domain_participant.participant_id(),
+    );
+    let addrs = vec![SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port)];
+    let sender = UDPSender::new_with_random_port().unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while domain_participant
+      .message_receiver_statistics()
+      .rejections
+      .oversize_messages
+      == 0
+    {
+      assert!(
+        Instant::now() < deadline,
+        "Oversize message was not rejected"
+      );
+      sender.send_to_all(&data, &addrs);
+      thread::sleep(Duration::from_millis(100));
+    }
+  }
+
+  #[test]
+  fn dp_rtps_ping_and_reply() {
+    let peer = std::net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    peer
+      .set_read_timeout(Some(Duration::from_millis(100)))
+      .unwrap();
+    let domain_participant = DomainParticipantBuilder::new(0)
+      .rtps_ping_peers(vec![peer.local_addr().unwrap()])
+      .reply_to_rtps_pings(true)
+      .build()
+      .expect("Participant creation failed!");
+
+    let user_traffic_port = user_traffic_unicast_port(
+      domain_participant.domain_id(),
+      domain_participant.participant_id(),
+    );
+    let mut buf = [0; 64 * 1024];
+
+    // Pings come from the listening sockets
+    domain_participant.send_rtps_pings().unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let participant_address = loop {
+      assert!(Instant::now() < deadline, "No RTPS ping from participant");
+      if let Ok((len, source)) = peer.recv_from(&mut buf) {
+        if is_rtps_ping(&buf[..len]) && source.port() == user_traffic_port {
+          break source;
+        }
+      }
+    };
+
+    // Replying to the ping source gets the SPDP announcement
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+      assert!(Instant::now() < deadline, "No SPDP reply to RTPS ping");
+      peer
+        .send_to(&rtps_ping_message(), participant_address)
+        .unwrap();
+      if let Ok((len, _source)) = peer.recv_from(&mut buf) {
+        if len > 20 && buf[0..4] == b"RTPS"[..] {
+          break;
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn dp_discovery_snapshot() {
+    let domain_participant = DomainParticipant::new(0).expect("Participant creation failed!");
+    let qos = QosPolicies::builder()
+      .reliability(Reliability::BestEffort)
+      .build();
+    let publisher = domain_participant
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let topic = domain_participant
+      .create_topic(
+        "dp_discovery_snapshot".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .expect("Failed to create topic");
+    let data_writer = publisher
+      .create_datawriter::<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>>(&topic, None)
+      .expect("Failed to create datawriter");
+
+    // Local writers are added to the discovery database asynchronously
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let (snapshot, writer_index) = loop {
+      let snapshot = domain_participant.discovery_snapshot();
+      if let Some(i) = snapshot
+        .writers
+        .iter()
+        .position(|w| w.guid == data_writer.guid())
+      {
+        break (snapshot, i);
+      }
+      assert!(
+        Instant::now() < deadline,
+        "Writer not in discovery snapshot"
+      );
+      thread::sleep(Duration::from_millis(10));
+    };
+
+    assert_eq!(snapshot.local_participant, domain_participant.guid());
+    let writer = &snapshot.writers[writer_index];
+    assert!(writer.local);
+    assert_eq!(writer.topic_name, "dp_discovery_snapshot");
+    assert_eq!(writer.qos.reliability(), Some(Reliability::BestEffort));
+    assert!(snapshot
+      .topics
+      .iter()
+      .any(|t| t.name == "dp_discovery_snapshot" && t.type_name == "RandomData"));
+
+    let json = serde_json::to_value(&snapshot).unwrap();
+    assert_eq!(
+      json["writers"][writer_index]["topic_name"],
+      serde_json::json!("dp_discovery_snapshot")
+    );
+    assert_eq!(
+      json["writers"][writer_index]["qos"]["reliability"],
+      serde_json::json!("BestEffort")
+    );
+  }
+
+  #[test]
+  fn dp_builtin_endpoint_options() {
+    use crate::{
+      dds::result::CreateError,
+      discovery::{
+        builtin_endpoint::{BuiltinEndpointOptions, BuiltinEndpointSet},
+        discovery_db::discovery_db_read,
+      },
+      rtps::constant::{DISCOVERY_MUL_LISTENER_TOKEN, USER_TRAFFIC_MUL_LISTENER_TOKEN},
+    };
+
+    let without_sedp = DomainParticipantBuilder::new(0)
+      .builtin_endpoints(BuiltinEndpointOptions {
+        sedp: false,
+        participant_message: false,
+        ..BuiltinEndpointOptions::default()
+      })
+      .build()
+      .expect("Participant creation failed!");
+    let observer = DomainParticipant::new(0).expect("Participant creation failed!");
+
+    // Remote participants see that only the SPDP endpoints exist
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let announced = loop {
+      if let Some(p) = discovery_db_read(&observer.discovery_db())
+        .find_participant_proxy(without_sedp.guid().prefix)
+      {
+        break p.available_builtin_endpoints;
+      }
+      assert!(Instant::now() < deadline, "Participant not discovered");
+      thread::sleep(Duration::from_millis(50));
+    };
+    assert_eq!(
+      announced,
+      BuiltinEndpointSet::from_u32(
+        BuiltinEndpointSet::PARTICIPANT_ANNOUNCER | BuiltinEndpointSet::PARTICIPANT_DETECTOR
+      )
+    );
+
+    let send_only = DomainParticipantBuilder::new(0)
+      .builtin_endpoints(BuiltinEndpointOptions {
+        send_only: true,
+        ..BuiltinEndpointOptions::default()
+      })
+      .build()
+      .expect("Participant creation failed!");
+    let locators = send_only.self_locators();
+    assert!(!locators.contains_key(&DISCOVERY_MUL_LISTENER_TOKEN));
+    assert!(!locators.contains_key(&USER_TRAFFIC_MUL_LISTENER_TOKEN));
+
+    let qos = QosPolicies::qos_none();
+    let topic = send_only
+      .create_topic(
+        "dp_send_only".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let subscriber = send_only.create_subscriber(&qos).unwrap();
+    assert!(matches!(
+      subscriber.create_datareader_cdr::<RandomData>(&topic, None),
+      Err(CreateError::BadParameter { .. })
+    ));
+    let publisher = send_only.create_publisher(&qos).unwrap();
+    publisher
+      .create_datawriter_cdr::<RandomData>(&topic, None)
+      .expect("Failed to create datawriter");
+
+    // Nothing would assert the liveliness of this
+    let finite_lease = QosPolicies::builder()
+      .liveliness(crate::policy::Liveliness::Automatic {
+        lease_duration: crate::Duration::from_secs(1),
+      })
+      .build();
+    assert!(matches!(
+      publisher.create_datawriter_cdr::<RandomData>(&topic, Some(finite_lease)),
+      Err(CreateError::BadParameter { .. })
+    ));
+  }
+
+  #[test]
+  fn dp_create_topic_counts_inconsistent_types() {
+    use chrono::Utc;
+
+    use crate::{
+      dds::statusevents::TopicStatus,
+      discovery::{
+        discovery_db::{discovery_db_write, DiscoveredVia},
+        sedp_messages::{DiscoveredTopicData, TopicBuiltinTopicData},
+      },
+    };
+
+    let domain_participant = DomainParticipant::new(0).expect("Participant creation failed!");
+    let qos = QosPolicies::qos_none();
+    let remote_definition = |type_name: &str| {
+      let topic_data = DiscoveredTopicData::new(
+        Utc::now(),
+        TopicBuiltinTopicData::new(
+          None,
+          "dp_inconsistent_topic".to_string(),
+          type_name.to_string(),
+          &qos,
+        ),
+      );
+      discovery_db_write(&domain_participant.discovery_db()).update_topic_data(
+        &topic_data,
+        GUID::new_participant_guid(), // some other participant
+        DiscoveredVia::Publication,
+      );
+    };
+
+    remote_definition("RemoteType");
+    // The Topic is created anyway, and the known definition is in its status.
+    let topic = domain_participant
+      .create_topic(
+        "dp_inconsistent_topic".to_string(),
+        "LocalType".to_string(),
+        &qos,
+        TopicKind::NoKey,
+      )
+      .unwrap();
+    match topic.try_recv_status() {
+      Some(TopicStatus::InconsistentTopic { count }) => {
+        assert_eq!((count.count(), count.count_change()), (1, 1));
+      }
+      other => panic!("Expected InconsistentTopic status, got {other:?}"),
+    }
+    assert_eq!(topic.try_recv_status(), None);
+
+    // A Topic with the matching type starts from zero.
+    let matching_topic = domain_participant
+      .create_topic(
+        "dp_inconsistent_topic".to_string(),
+        "RemoteType".to_string(),
+        &qos,
+        TopicKind::NoKey,
+      )
+      .unwrap();
+    assert_eq!(matching_topic.inconsistent_topic_status().count(), 0);
+
+    // Inconsistent definitions discovered later are counted.
+    remote_definition("ThirdType");
+    remote_definition("ThirdType");
+    match matching_topic.try_recv_status() {
+      Some(TopicStatus::InconsistentTopic { count }) => {
+        assert_eq!(count.count(), 2);
+        assert_eq!(count.count_change(), 2);
+      }
+      other => panic!("Expected InconsistentTopic status, got {other:?}"),
+    }
+    assert_eq!(matching_topic.try_recv_status(), None);
+    let status = topic.inconsistent_topic_status();
+    assert_eq!((status.count(), status.count_change()), (3, 2));
+  }
 }
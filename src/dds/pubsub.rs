@@ -1,19 +1,22 @@
 use std::{
+  collections::BTreeMap,
   fmt::Debug,
-  sync::{Arc, Mutex, MutexGuard, RwLock},
+  sync::{atomic::AtomicU64, Arc, Mutex, MutexGuard, RwLock},
   time::Duration,
 };
 
 use serde::{Deserialize, Serialize};
 use mio_extras::channel as mio_channel;
 use byteorder::LittleEndian;
+use speedy::Endianness;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 
 use crate::{
-  create_error_dropped, create_error_internal, create_error_poisoned,
+  create_error_bad_parameter, create_error_dropped, create_error_internal, create_error_poisoned,
   dds::{
     adapters,
+    delivery::DeliveryListenerSlot,
     key::Keyed,
     no_key,
     no_key::{
@@ -21,7 +24,8 @@ use crate::{
     },
     participant::*,
     qos::*,
-    result::{CreateError, CreateResult, WaitResult},
+    reader_multiplexer::{self, AnySample, ReadinessQueue},
+    result::{CreateError, CreateResult, ReadResult, WaitResult},
     statusevents::{sync_status_channel, DataReaderStatus},
     topic::*,
     with_key,
@@ -35,7 +39,7 @@ use crate::{
   mio_source,
   rtps::{
     reader::ReaderIngredients,
-    writer::{WriterCommand, WriterIngredients},
+    writer::{RoundTripTimes, WriterCommand, WriterIngredients},
   },
   serialization::{cdr_deserializer::CDRDeserializerAdapter, cdr_serializer::CDRSerializerAdapter},
   structure::{
@@ -96,6 +100,7 @@ impl Publisher {
   #[allow(clippy::too_many_arguments)]
   pub(super) fn new(
     dp: DomainParticipantWeak,
+    guid: GUID,
     discovery_db: Arc<RwLock<DiscoveryDB>>,
     qos: QosPolicies,
     default_dw_qos: QosPolicies,
@@ -103,10 +108,12 @@ impl Publisher {
     remove_writer_sender: mio_channel::SyncSender<GUID>,
     discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
     security_plugins_handle: Option<SecurityPluginsHandle>,
+    rtps_endianness: Endianness,
   ) -> Self {
     Self {
       inner: Arc::new(Mutex::new(InnerPublisher::new(
         dp,
+        guid,
         discovery_db,
         qos,
         default_dw_qos,
@@ -114,6 +121,7 @@ impl Publisher {
         remove_writer_sender,
         discovery_command,
         security_plugins_handle,
+        rtps_endianness,
       ))),
     }
   }
@@ -236,6 +244,49 @@ impl Publisher {
     self.create_datawriter_no_key::<D, CDRSerializerAdapter<D, LittleEndian>>(topic, qos)
   }
 
+  /// Creates a DataWriter like [`create_datawriter`](Self::create_datawriter),
+  /// but with the given entity key instead of a generated one. This gives
+  /// the DataWriter a well-known GUID, e.g. for static discovery or for
+  /// constrained peers that are configured with the GUIDs of their peers.
+  ///
+  /// The entity kind is user-defined writer with key. Creation fails with
+  /// [`CreateError::BadParameter`], if the resulting EntityId is already in
+  /// use in the DomainParticipant. The EntityId can be reused once the
+  /// DataWriter is dropped.
+  pub fn create_datawriter_with_entity_key<D, SA>(
+    &self,
+    entity_key: [u8; 3],
+    topic: &Topic,
+    qos: Option<QosPolicies>,
+  ) -> CreateResult<WithKeyDataWriter<D, SA>>
+  where
+    D: Keyed,
+    SA: adapters::with_key::SerializerAdapter<D>,
+  {
+    let entity_id = EntityId::new(entity_key, EntityKind::WRITER_WITH_KEY_USER_DEFINED);
+    self
+      .inner_lock()
+      .create_datawriter(self, Some(entity_id), topic, qos, false)
+  }
+
+  /// Like [`create_datawriter_with_entity_key`](Self::create_datawriter_with_entity_key),
+  /// but for no_key Topics. The entity kind is user-defined writer without
+  /// key.
+  pub fn create_datawriter_no_key_with_entity_key<D, SA>(
+    &self,
+    entity_key: [u8; 3],
+    topic: &Topic,
+    qos: Option<QosPolicies>,
+  ) -> CreateResult<NoKeyDataWriter<D, SA>>
+  where
+    SA: adapters::no_key::SerializerAdapter<D>,
+  {
+    let entity_id = EntityId::new(entity_key, EntityKind::WRITER_NO_KEY_USER_DEFINED);
+    self
+      .inner_lock()
+      .create_datawriter_no_key(self, Some(entity_id), topic, qos, false)
+  }
+
   // Versions with callee-specified EntityId. These are for Discovery use only.
 
   pub(crate) fn create_datawriter_with_entity_id_with_key<D, SA>(
@@ -254,7 +305,6 @@ impl Publisher {
       .create_datawriter(self, Some(entity_id), topic, qos, writer_like_stateless)
   }
 
-  #[cfg(feature = "security")] // to avoid "never used" warning
   pub(crate) fn create_datawriter_with_entity_id_no_key<D, SA>(
     &self,
     entity_id: EntityId,
@@ -274,25 +324,72 @@ impl Publisher {
     )
   }
 
+  // A DataWriter of serialized data for a Topic of either kind, e.g. for
+  // forwarding. The entity kind follows the Topic kind.
+  pub(crate) fn create_raw_datawriter<D, SA>(
+    &self,
+    topic: &Topic,
+    qos: Option<QosPolicies>,
+  ) -> CreateResult<WithKeyDataWriter<NoKeyWrapper<D>, SAWrapper<SA>>>
+  where
+    SA: adapters::no_key::SerializerAdapter<D>,
+  {
+    let entity_kind = match topic.kind() {
+      TopicKind::WithKey => EntityKind::WRITER_WITH_KEY_USER_DEFINED,
+      TopicKind::NoKey => EntityKind::WRITER_NO_KEY_USER_DEFINED,
+    };
+    let mut inner = self.inner_lock();
+    let entity_id = inner.unwrap_or_new_entity_id(None, entity_kind);
+    inner.create_datawriter::<NoKeyWrapper<D>, SAWrapper<SA>>(
+      self,
+      Some(entity_id),
+      topic,
+      qos,
+      false,
+    )
+  }
+
   // delete_datawriter should not be needed. The DataWriter object itself should
   // be deleted to accomplish this.
 
   // lookup datawriter: maybe not necessary? App should remember datawriters it
   // has created.
 
-  // Suspend and resume publications are performance optimization methods.
-  // The minimal correct implementation is to do nothing. See DDS spec 2.2.2.4.1.8
-  // and .9
-  /// **NOT IMPLEMENTED. DO NOT USE**
-  #[deprecated(note = "unimplemented")]
+  /// Suspends publications (DDS spec 2.2.2.4.1.8). Samples written by the
+  /// DataWriters of this Publisher are held back until
+  /// [`resume_publications`](Self::resume_publications) is called, and then
+  /// sent together. This includes DataWriters created while suspended.
+  ///
+  /// Held back samples are not announced to DataReaders, so none of them is
+  /// delivered before the resume. Samples written with
+  /// [`to_single_reader`](crate::WriteOptionsBuilder::to_single_reader) are
+  /// not held back. [`flush`](Self::flush) has no effect while suspended.
+  ///
+  /// Calls can be nested. Publications resume when each suspend has been
+  /// matched by a resume.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// # use rustdds::*;
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let publisher = domain_participant.create_publisher(&qos).unwrap();
+  ///
+  /// publisher.suspend_publications();
+  /// // Write to several DataWriters of this Publisher here.
+  /// publisher.resume_publications(); // Now everything is sent.
+  /// ```
   pub fn suspend_publications(&self) {
-    unimplemented!();
+    self.inner_lock().suspend_publications();
   }
 
-  /// **NOT IMPLEMENTED. DO NOT USE**
-  #[deprecated(note = "unimplemented")]
+  /// Resumes publications suspended by
+  /// [`suspend_publications`](Self::suspend_publications), and sends the
+  /// samples written while suspended. Calling this without a matching
+  /// suspend does nothing.
   pub fn resume_publications(&self) {
-    unimplemented!();
+    self.inner_lock().resume_publications();
   }
 
   // coherent change set
@@ -378,10 +475,39 @@ impl Publisher {
     self.inner_lock().set_default_datawriter_qos(q);
   }
 
+  /// Sends out the samples held back by the
+  /// [`Batching`](crate::policy::Batching) QoS policy in all DataWriters of
+  /// this Publisher. See
+  /// [`DataWriter::flush`](crate::with_key::DataWriter::flush).
+  pub fn flush(&self) {
+    self.inner_lock().flush();
+  }
+
   // This is used on DataWriter .drop()
   pub(crate) fn remove_writer(&self, guid: GUID) {
     self.inner_lock().remove_writer(guid);
   }
+
+  // This is used on drop of a DataWriter that was never enabled
+  pub(crate) fn release_entity_id(&self, entity_id: EntityId) {
+    self.inner_lock().release_entity_id(entity_id);
+  }
+
+  pub(crate) fn discovery_db(&self) -> Arc<RwLock<DiscoveryDB>> {
+    self.inner_lock().discovery_db.clone()
+  }
+
+  // This is used on DataWriter .enable()
+  pub(crate) fn enable_datawriter<D, SA>(
+    &self,
+    data_writer: &WithKeyDataWriter<D, SA>,
+  ) -> CreateResult<()>
+  where
+    D: Keyed,
+    SA: adapters::with_key::SerializerAdapter<D>,
+  {
+    self.inner_lock().enable_datawriter(data_writer)
+  }
 } // impl
 
 impl PartialEq for Publisher {
@@ -398,11 +524,19 @@ impl Debug for Publisher {
   }
 }
 
+/// The GUID of a Publisher has EntityKind
+/// [`WRITER_GROUP_USER_DEFINED`](EntityKind::WRITER_GROUP_USER_DEFINED).
+impl RTPSEntity for Publisher {
+  fn guid(&self) -> GUID {
+    self.inner_lock().identity()
+  }
+}
+
 // "Inner" struct
 
 #[derive(Clone)]
 struct InnerPublisher {
-  id: EntityId,
+  guid: GUID,
   domain_participant: DomainParticipantWeak,
   discovery_db: Arc<RwLock<DiscoveryDB>>,
   my_qos_policies: QosPolicies,
@@ -411,6 +545,13 @@ struct InnerPublisher {
   remove_writer_sender: mio_channel::SyncSender<GUID>,
   discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
   security_plugins_handle: Option<SecurityPluginsHandle>,
+  // Command channels of the DataWriters created by this Publisher, for flush
+  // and suspend/resume
+  writer_commands: BTreeMap<GUID, mio_channel::SyncSender<WriterCommand>>,
+  // Nesting depth of suspend_publications() calls
+  suspend_count: u32,
+  // Byte order of RTPS messages from new DataWriters
+  rtps_endianness: Endianness,
 }
 
 // public interface for Publisher
@@ -418,6 +559,7 @@ impl InnerPublisher {
   #[allow(clippy::too_many_arguments)]
   fn new(
     dp: DomainParticipantWeak,
+    guid: GUID,
     discovery_db: Arc<RwLock<DiscoveryDB>>,
     qos: QosPolicies,
     default_dw_qos: QosPolicies,
@@ -425,14 +567,10 @@ impl InnerPublisher {
     remove_writer_sender: mio_channel::SyncSender<GUID>,
     discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
     security_plugins_handle: Option<SecurityPluginsHandle>,
+    rtps_endianness: Endianness,
   ) -> Self {
-    // We generate an arbitrary but unique id to distinguish Publishers from each
-    // other. EntityKind is just some value, since we do not show it to anyone.
-    let id = EntityId::MAX;
-    // dp.clone().upgrade().unwrap().new_entity_id(EntityKind::UNKNOWN_BUILT_IN);
-
     Self {
-      id,
+      guid,
       domain_participant: dp,
       discovery_db,
       my_qos_policies: qos,
@@ -441,11 +579,14 @@ impl InnerPublisher {
       remove_writer_sender,
       discovery_command,
       security_plugins_handle,
+      writer_commands: BTreeMap::new(),
+      suspend_count: 0,
+      rtps_endianness,
     }
   }
 
   pub fn create_datawriter<D, SA>(
-    &self,
+    &mut self,
     outer: &Publisher,
     entity_id_opt: Option<EntityId>,
     topic: &Topic,
@@ -461,6 +602,8 @@ impl InnerPublisher {
     let writer_waker = Arc::new(Mutex::new(None));
     // Status reports back from Writer to DataWriter.
     let (status_sender, status_receiver) = sync_status_channel(4)?;
+    let stale_samples_dropped = Arc::new(AtomicU64::new(0));
+    let delivery_listener = DeliveryListenerSlot::default();
 
     // DDS Spec 2.2.2.4.1.5 create_datawriter:
     // If no QoS is specified, we should take the Publisher default
@@ -480,6 +623,25 @@ impl InnerPublisher {
       .participant()
       .ok_or("upgrade fail")
       .or_else(|e| create_error_dropped!("Where is my DomainParticipant? {}", e))?;
+    if entity_id.kind().is_user_defined()
+      && !dp.builtin_endpoint_options().participant_message_enabled()
+    {
+      // Liveliness of these is asserted via DCPSParticipantMessage, so remote
+      // DataReaders would soon consider the DataWriter dead.
+      if let Some(
+        policy::Liveliness::Automatic { lease_duration }
+        | policy::Liveliness::ManualByParticipant { lease_duration },
+      ) = writer_qos.liveliness
+      {
+        if lease_duration != crate::Duration::INFINITE {
+          return create_error_bad_parameter!(
+            "DataWriter liveliness {:?} needs DCPSParticipantMessage endpoints",
+            writer_qos.liveliness
+          );
+        }
+      }
+    }
+    let reservation = dp.reserve_endpoint_entity_id(entity_id)?;
 
     // Get a handle to the topic cache
     let topic_cache_handle = match dp.dds_cache().read() {
@@ -552,15 +714,19 @@ impl InnerPublisher {
       like_stateless: writer_like_stateless,
       qos_policies: writer_qos.clone(),
       status_sender,
+      stale_samples_dropped: Arc::clone(&stale_samples_dropped),
+      round_trip_times: RoundTripTimes::default(),
+      delivery_listener: delivery_listener.clone(),
+      endianness: self.rtps_endianness,
       security_plugins: self.security_plugins_handle.clone(),
     };
 
-    // Send writer ingredients to DP event loop, where the actual writer will be
-    // constructed
-    self
-      .add_writer_sender
-      .send(new_writer)
-      .or_else(|e| create_error_poisoned!("Adding a new writer failed: {}", e))?;
+    self.writer_commands.insert(guid, dwcc_upload.clone());
+    if self.suspend_count > 0 {
+      dwcc_upload
+        .send(WriterCommand::SuspendPublications)
+        .unwrap_or_else(|e| error!("Cannot suspend Writer {:?} : {:?}", guid, e));
+    }
 
     let data_writer = WithKeyDataWriter::<D, SA>::new(
       outer.clone(),
@@ -571,7 +737,51 @@ impl InnerPublisher {
       writer_waker,
       self.discovery_command.clone(),
       status_receiver,
+      stale_samples_dropped,
+      delivery_listener,
+      new_writer,
     )?;
+    reservation.keep();
+
+    if self.autoenable_created_entities() {
+      self.enable_datawriter(&data_writer)?;
+    }
+
+    // Return the DataWriter to user
+    Ok(data_writer)
+  }
+
+  // Creates the RTPS Writer and announces the DataWriter via Discovery, unless
+  // already done.
+  fn enable_datawriter<D, SA>(&self, data_writer: &WithKeyDataWriter<D, SA>) -> CreateResult<()>
+  where
+    D: Keyed,
+    SA: adapters::with_key::SerializerAdapter<D>,
+  {
+    let dp = self
+      .participant()
+      .ok_or("upgrade fail")
+      .or_else(|e| create_error_dropped!("Where is my DomainParticipant? {}", e))?;
+    let mut ingredients = data_writer.writer_ingredients();
+    let new_writer = match ingredients.take() {
+      Some(new_writer) => new_writer,
+      None => return Ok(()), // already enabled
+    };
+    let guid = new_writer.guid;
+    let topic = data_writer.topic();
+
+    // Send writer ingredients to DP event loop, where the actual writer will be
+    // constructed. If they cannot be sent, the DataWriter stays disabled.
+    match self.add_writer_sender.send(new_writer) {
+      Ok(()) => (),
+      Err(mio_channel::SendError::Disconnected(new_writer)) => {
+        *ingredients = Some(new_writer);
+        return create_error_poisoned!("Adding a new writer failed: event loop is gone");
+      }
+      // The ingredients are queued, but waking up the event loop failed.
+      Err(e) => return create_error_poisoned!("Adding a new writer failed: {}", e),
+    }
+    drop(ingredients);
 
     // notify Discovery DB
     let mut db = self
@@ -609,7 +819,7 @@ impl InnerPublisher {
     };
 
     // Update topic to DiscoveryDB & inform Discovery about it
-    let dwd = DiscoveredWriterData::new(&data_writer, topic, &dp, security_info);
+    let dwd = DiscoveredWriterData::new(data_writer, topic, &dp, security_info);
     db.update_local_topic_writer(dwd);
     db.update_topic_data_p(topic);
 
@@ -626,23 +836,20 @@ impl InnerPublisher {
     }
 
     // Inform Discovery about the new writer
-    let writer_guid = self.domain_participant.guid().from_prefix(entity_id);
     self
       .discovery_command
-      .try_send(DiscoveryCommand::AddLocalWriter { guid: writer_guid })
+      .try_send(DiscoveryCommand::AddLocalWriter { guid })
       .or_else(|e| {
         create_error_internal!(
-          "Cannot inform Discovery about the new writer {writer_guid:?}. Error: {}",
+          "Cannot inform Discovery about the new writer {guid:?}. Error: {}",
           e
         )
       })?;
-
-    // Return the DataWriter to user
-    Ok(data_writer)
+    Ok(())
   }
 
   pub fn create_datawriter_no_key<D, SA>(
-    &self,
+    &mut self,
     outer: &Publisher,
     entity_id_opt: Option<EntityId>,
     topic: &Topic,
@@ -683,16 +890,68 @@ impl InnerPublisher {
   ) -> EntityId {
     // If the entity_id is given, then just use that. If not, then pull an arbitrary
     // number out of participant's hat.
-    entity_id_opt.unwrap_or_else(|| self.participant().unwrap().new_entity_id(entity_kind))
+    entity_id_opt.unwrap_or_else(|| {
+      self
+        .participant()
+        .unwrap()
+        .new_endpoint_entity_id(entity_kind)
+    })
   }
 
-  pub(crate) fn remove_writer(&self, guid: GUID) {
+  fn autoenable_created_entities(&self) -> bool {
+    self
+      .my_qos_policies
+      .entity_factory()
+      .map_or(true, |ef| ef.autoenable_created_entities)
+  }
+
+  pub(crate) fn remove_writer(&mut self, guid: GUID) {
+    self.writer_commands.remove(&guid);
     try_send_timeout(&self.remove_writer_sender, guid, None)
       .unwrap_or_else(|e| error!("Cannot remove Writer {:?} : {:?}", guid, e));
+    // The event loop removes the old Writer before adding any new one, so the
+    // EntityId can be reused right away.
+    self.release_entity_id(guid.entity_id);
+  }
+
+  pub(crate) fn release_entity_id(&self, entity_id: EntityId) {
+    if let Some(dp) = self.participant() {
+      dp.release_endpoint_entity_id(entity_id);
+    }
+  }
+
+  fn flush(&self) {
+    self.send_to_writers(|| WriterCommand::Flush);
   }
 
-  pub(crate) fn identity(&self) -> EntityId {
-    self.id
+  fn suspend_publications(&mut self) {
+    self.suspend_count += 1;
+    if self.suspend_count == 1 {
+      self.send_to_writers(|| WriterCommand::SuspendPublications);
+    }
+  }
+
+  fn resume_publications(&mut self) {
+    match self.suspend_count {
+      0 => warn!("resume_publications: Publications were not suspended."),
+      1 => {
+        self.suspend_count = 0;
+        self.send_to_writers(|| WriterCommand::ResumePublications);
+      }
+      _ => self.suspend_count -= 1,
+    }
+  }
+
+  fn send_to_writers(&self, command: impl Fn() -> WriterCommand) {
+    for (guid, writer_command) in &self.writer_commands {
+      writer_command
+        .send(command())
+        .unwrap_or_else(|e| error!("Cannot send command to Writer {:?} : {:?}", guid, e));
+    }
+  }
+
+  pub(crate) fn identity(&self) -> GUID {
+    self.guid
   }
 }
 
@@ -738,8 +997,10 @@ pub struct Subscriber {
 }
 
 impl Subscriber {
+  #[allow(clippy::too_many_arguments)]
   pub(super) fn new(
     domain_participant: DomainParticipantWeak,
+    guid: GUID,
     discovery_db: Arc<RwLock<DiscoveryDB>>,
     qos: QosPolicies,
     sender_add_reader: mio_channel::SyncSender<ReaderIngredients>,
@@ -750,6 +1011,7 @@ impl Subscriber {
     Self {
       inner: Arc::new(InnerSubscriber::new(
         domain_participant,
+        guid,
         discovery_db,
         qos,
         sender_add_reader,
@@ -886,6 +1148,49 @@ impl Subscriber {
     self.create_datareader_no_key::<D, CDRDeserializerAdapter<D>>(topic, qos)
   }
 
+  /// Creates a DataReader like [`create_datareader`](Self::create_datareader),
+  /// but with the given entity key instead of a generated one. This gives
+  /// the DataReader a well-known GUID, e.g. for static discovery or for
+  /// constrained peers that are configured with the GUIDs of their peers.
+  ///
+  /// The entity kind is user-defined reader with key. Creation fails with
+  /// [`CreateError::BadParameter`], if the resulting EntityId is already in
+  /// use in the DomainParticipant. The EntityId can be reused once the
+  /// DataReader is dropped.
+  pub fn create_datareader_with_entity_key<D, SA>(
+    &self,
+    entity_key: [u8; 3],
+    topic: &Topic,
+    qos: Option<QosPolicies>,
+  ) -> CreateResult<WithKeyDataReader<D, SA>>
+  where
+    D: Keyed + 'static,
+    SA: adapters::with_key::DeserializerAdapter<D>,
+  {
+    let entity_id = EntityId::new(entity_key, EntityKind::READER_WITH_KEY_USER_DEFINED);
+    self
+      .inner
+      .create_datareader(self, topic, Some(entity_id), qos, false)
+  }
+
+  /// Like [`create_datareader_with_entity_key`](Self::create_datareader_with_entity_key),
+  /// but for no_key Topics. The entity kind is user-defined reader without
+  /// key.
+  pub fn create_datareader_no_key_with_entity_key<D: 'static, SA>(
+    &self,
+    entity_key: [u8; 3],
+    topic: &Topic,
+    qos: Option<QosPolicies>,
+  ) -> CreateResult<NoKeyDataReader<D, SA>>
+  where
+    SA: adapters::no_key::DeserializerAdapter<D>,
+  {
+    let entity_id = EntityId::new(entity_key, EntityKind::READER_NO_KEY_USER_DEFINED);
+    self
+      .inner
+      .create_datareader_no_key(self, topic, Some(entity_id), qos, false)
+  }
+
   // versions with callee-specified EntityId. These are for Discovery use only.
 
   pub(crate) fn create_datareader_with_entity_id_with_key<D: 'static, SA>(
@@ -904,7 +1209,6 @@ impl Subscriber {
       .create_datareader(self, topic, Some(entity_id), qos, reader_like_stateless)
   }
 
-  #[cfg(feature = "security")] // to avoid "never used" warning
   pub(crate) fn create_datareader_with_entity_id_no_key<D: 'static, SA>(
     &self,
     topic: &Topic,
@@ -920,6 +1224,26 @@ impl Subscriber {
       .create_datareader_no_key(self, topic, Some(entity_id), qos, reader_like_stateless)
   }
 
+  // A DataReader of serialized data for a Topic of either kind, e.g. for
+  // forwarding. The entity kind follows the Topic kind.
+  pub(crate) fn create_raw_simple_datareader<D: 'static, SA>(
+    &self,
+    topic: &Topic,
+    qos: Option<QosPolicies>,
+  ) -> CreateResult<with_key::SimpleDataReader<NoKeyWrapper<D>, DAWrapper<SA>>>
+  where
+    SA: adapters::no_key::DeserializerAdapter<D>,
+  {
+    let entity_kind = match topic.kind() {
+      TopicKind::WithKey => EntityKind::READER_WITH_KEY_USER_DEFINED,
+      TopicKind::NoKey => EntityKind::READER_NO_KEY_USER_DEFINED,
+    };
+    let entity_id = self.inner.unwrap_or_new_entity_id(None, entity_kind);
+    self
+      .inner
+      .create_simple_datareader_internal(self, Some(entity_id), topic, qos, false)
+  }
+
   // Retrieves a previously created DataReader belonging to the Subscriber.
   // TODO: Is this even possible. Would probably need to return reference and
   // store references on creation
@@ -959,22 +1283,150 @@ impl Subscriber {
   pub(crate) fn remove_reader(&self, guid: GUID) {
     self.inner.remove_reader(guid);
   }
+
+  // This is used on drop of a DataReader that was never enabled
+  pub(crate) fn release_entity_id(&self, entity_id: EntityId) {
+    self.inner.release_entity_id(entity_id);
+  }
+
+  /// Lets [`take_any`](Self::take_any) take samples from a DataReader of this
+  /// Subscriber.
+  ///
+  /// The Subscriber keeps only a weak reference, so the application still
+  /// owns the DataReader and can use it directly. A dropped DataReader is
+  /// detached automatically.
+  pub fn attach_for_take_any<D, DA>(&self, reader: &Arc<Mutex<WithKeyDataReader<D, DA>>>)
+  where
+    D: Keyed + 'static,
+    D::K: 'static,
+    DA: adapters::with_key::DeserializerAdapter<D> + 'static,
+    WithKeyDataReader<D, DA>: Send,
+  {
+    reader_multiplexer::attach(&self.inner.readiness_queue, reader);
+  }
+
+  /// Like [`attach_for_take_any`](Self::attach_for_take_any), but for
+  /// DataReaders of no_key Topics.
+  pub fn attach_no_key_for_take_any<D, DA>(&self, reader: &Arc<Mutex<NoKeyDataReader<D, DA>>>)
+  where
+    D: 'static,
+    DA: adapters::no_key::DeserializerAdapter<D> + 'static,
+    NoKeyDataReader<D, DA>: Send,
+  {
+    reader_multiplexer::attach(&self.inner.readiness_queue, reader);
+  }
+
+  /// Stops [`take_any`](Self::take_any) from taking samples from the
+  /// DataReader with the given GUID.
+  pub fn detach_from_take_any(&self, reader_guid: GUID) {
+    self.inner.readiness_queue.detach(reader_guid);
+  }
+
+  /// Waits until an attached DataReader may have new samples, or `timeout`
+  /// expires. Returns the GUID of the DataReader, or `None` on timeout. The
+  /// samples can then be taken with [`take_any`](Self::take_any), or from the
+  /// DataReader itself.
+  ///
+  /// This allows serving many low-rate Topics with a single loop instead of
+  /// a polling loop for each DataReader. It is a RustDDS extension.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use std::sync::{Arc, Mutex};
+  /// # use std::time::Duration;
+  /// # use rustdds::*;
+  /// # use serde::{Deserialize, Serialize};
+  /// #
+  /// # let domain_participant = DomainParticipant::new(0).unwrap();
+  /// # let qos = QosPolicyBuilder::new().build();
+  /// #[derive(Deserialize, Serialize)]
+  /// struct Temperature(f32);
+  /// #[derive(Deserialize, Serialize)]
+  /// struct Humidity(f32);
+  ///
+  /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// let temperature_topic = domain_participant.create_topic("temperature".to_string(),
+  ///   "Temperature".to_string(), &qos, TopicKind::NoKey).unwrap();
+  /// let humidity_topic = domain_participant.create_topic("humidity".to_string(),
+  ///   "Humidity".to_string(), &qos, TopicKind::NoKey).unwrap();
+  ///
+  /// let temperature_reader = Arc::new(Mutex::new(subscriber
+  ///   .create_datareader_no_key_cdr::<Temperature>(&temperature_topic, None).unwrap()));
+  /// let humidity_reader = Arc::new(Mutex::new(subscriber
+  ///   .create_datareader_no_key_cdr::<Humidity>(&humidity_topic, None).unwrap()));
+  /// subscriber.attach_no_key_for_take_any(&temperature_reader);
+  /// subscriber.attach_no_key_for_take_any(&humidity_reader);
+  ///
+  /// if subscriber.poll_any(Duration::from_millis(10)).is_some() {
+  ///   while let Some(sample) = subscriber.take_any().unwrap() {
+  ///     match sample.topic_name() {
+  ///       "temperature" => {
+  ///         let t = sample.downcast::<no_key::DataSample<Temperature>>().unwrap();
+  ///         println!("temperature {}", t.value().0);
+  ///       }
+  ///       _ => {
+  ///         let h = sample.downcast::<no_key::DataSample<Humidity>>().unwrap();
+  ///         println!("humidity {}", h.value().0);
+  ///       }
+  ///     }
+  ///   }
+  /// }
+  /// ```
+  pub fn poll_any(&self, timeout: Duration) -> Option<GUID> {
+    self.inner.readiness_queue.wait(timeout)
+  }
+
+  /// Takes the next sample from any attached DataReader that has samples, or
+  /// returns `None` if none has. This does not block.
+  ///
+  /// DataReaders take turns, so that a busy Topic does not starve the others.
+  /// The sample is taken as with `take_next_sample()` of the DataReader, and
+  /// tagged with the Topic name and the DataReader GUID.
+  pub fn take_any(&self) -> ReadResult<Option<AnySample>> {
+    self.inner.readiness_queue.take()
+  }
+
+  // This is used on DataReader .enable()
+  pub(crate) fn enable_datareader<D, SA>(
+    &self,
+    datareader: &with_key::SimpleDataReader<D, SA>,
+  ) -> CreateResult<()>
+  where
+    D: Keyed + 'static,
+    SA: adapters::with_key::DeserializerAdapter<D>,
+  {
+    self.inner.enable_datareader(datareader)
+  }
+}
+
+/// The GUID of a Subscriber has EntityKind
+/// [`READER_GROUP_USER_DEFINED`](EntityKind::READER_GROUP_USER_DEFINED).
+impl RTPSEntity for Subscriber {
+  fn guid(&self) -> GUID {
+    self.inner.guid
+  }
 }
 
 #[derive(Clone)]
 pub struct InnerSubscriber {
   domain_participant: DomainParticipantWeak,
+  guid: GUID,
   discovery_db: Arc<RwLock<DiscoveryDB>>,
   qos: QosPolicies,
   sender_add_reader: mio_channel::SyncSender<ReaderIngredients>,
   sender_remove_reader: mio_channel::SyncSender<GUID>,
   discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
   security_plugins_handle: Option<SecurityPluginsHandle>,
+  // DataReaders attached for take_any()
+  readiness_queue: Arc<ReadinessQueue>,
 }
 
 impl InnerSubscriber {
+  #[allow(clippy::too_many_arguments)]
   pub(super) fn new(
     domain_participant: DomainParticipantWeak,
+    guid: GUID,
     discovery_db: Arc<RwLock<DiscoveryDB>>,
     qos: QosPolicies,
     sender_add_reader: mio_channel::SyncSender<ReaderIngredients>,
@@ -984,12 +1436,14 @@ impl InnerSubscriber {
   ) -> Self {
     Self {
       domain_participant,
+      guid,
       discovery_db,
       qos,
       sender_add_reader,
       sender_remove_reader,
       discovery_command,
       security_plugins_handle,
+      readiness_queue: Arc::default(),
     }
   }
 
@@ -1033,14 +1487,15 @@ impl InnerSubscriber {
     let (send, rec) = mio_channel::sync_channel::<()>(4);
     // status change channel from Reader to DataReader
     let (status_sender, status_receiver) = sync_status_channel::<DataReaderStatus>(4)?;
+    // DataReader also reports some statuses itself
+    let status_sender_clone = status_sender.clone();
 
     // reader command channel from Datareader to Reader
     let (reader_command_sender, reader_command_receiver) =
-      mio_channel::sync_channel::<ReaderCommand>(0);
-    // The buffer length is zero, i.e. sender and receiver must rendezvous at
-    // send/receive. This is needed to synchronize sending of wakers from
-    // DataReader to Reader. If the capacity is increased, then some data
-    // available for reading notifications may be missed.
+      mio_channel::sync_channel::<ReaderCommand>(4);
+    // The buffer must not be zero-length: the Reader receives commands only
+    // after the event loop has seen the channel become readable, so a
+    // rendezvous would never happen.
 
     // Use subscriber QoS as basis, modify by Topic settings, and modify by
     // specified QoS.
@@ -1056,6 +1511,10 @@ impl InnerSubscriber {
       Some(dp) => dp,
       None => return create_error_dropped!("DomainParticipant doesn't exist anymore."),
     };
+    if entity_id.kind().is_user_defined() && dp.builtin_endpoint_options().send_only {
+      return create_error_bad_parameter!("Cannot create a DataReader in a send-only participant");
+    }
+    let reservation = dp.reserve_endpoint_entity_id(entity_id)?;
 
     // Get a handle to the topic cache
     let topic_cache_handle = match dp.dds_cache().read() {
@@ -1137,9 +1596,58 @@ impl InnerSubscriber {
       data_reader_command_receiver: reader_command_receiver,
       data_reader_waker: data_reader_waker.clone(),
       poll_event_sender,
+      readiness_queue: Some(self.readiness_queue.clone()),
+      fragment_streams: Arc::default(),
       security_plugins: self.security_plugins_handle.clone(),
     };
 
+    let datareader = with_key::SimpleDataReader::<D, SA>::new(
+      outer.clone(),
+      entity_id,
+      topic.clone(),
+      qos,
+      rec,
+      topic_cache_handle,
+      self.discovery_command.clone(),
+      status_receiver,
+      status_sender_clone,
+      reader_command_sender,
+      data_reader_waker,
+      poll_event_source,
+      new_reader,
+    )?;
+    reservation.keep();
+
+    if self.autoenable_created_entities() {
+      self.enable_datareader(&datareader)?;
+    }
+
+    // Return the DataReader to user
+    Ok(datareader)
+  }
+
+  // Creates the RTPS Reader and announces the DataReader via Discovery, unless
+  // already done.
+  fn enable_datareader<D, SA>(
+    &self,
+    datareader: &with_key::SimpleDataReader<D, SA>,
+  ) -> CreateResult<()>
+  where
+    D: Keyed + 'static,
+    SA: adapters::with_key::DeserializerAdapter<D>,
+  {
+    let dp = match self.participant() {
+      Some(dp) => dp,
+      None => return create_error_dropped!("DomainParticipant doesn't exist anymore."),
+    };
+    let mut ingredients = datareader.reader_ingredients();
+    let new_reader = match ingredients.take() {
+      Some(new_reader) => new_reader,
+      None => return Ok(()), // already enabled
+    };
+    let reader_guid = new_reader.guid;
+    let topic = datareader.topic();
+
     #[cfg(not(feature = "security"))]
     let security_info: Option<EndpointSecurityInfo> = None;
     #[cfg(feature = "security")]
@@ -1189,29 +1697,21 @@ impl InnerSubscriber {
       }
     }
 
-    let datareader = with_key::SimpleDataReader::<D, SA>::new(
-      outer.clone(),
-      entity_id,
-      topic.clone(),
-      qos,
-      rec,
-      topic_cache_handle,
-      self.discovery_command.clone(),
-      status_receiver,
-      reader_command_sender,
-      data_reader_waker,
-      poll_event_source,
-    )?;
-
     // Send reader ingredients to DP event loop, where the actual reader will be
-    // constructed
-    self
-      .sender_add_reader
-      .try_send(new_reader)
-      .or_else(|e| create_error_poisoned!("Cannot add DataReader. Error: {}", e))?;
+    // constructed. If they cannot be sent, the DataReader stays disabled.
+    match self.sender_add_reader.try_send(new_reader) {
+      Ok(()) => (),
+      Err(mio_channel::TrySendError::Full(new_reader))
+      | Err(mio_channel::TrySendError::Disconnected(new_reader)) => {
+        *ingredients = Some(new_reader);
+        return create_error_poisoned!("Cannot add DataReader. Event loop is not responding.");
+      }
+      // The ingredients are queued, but waking up the event loop failed.
+      Err(e) => return create_error_poisoned!("Cannot add DataReader. Error: {}", e),
+    }
+    drop(ingredients);
 
     // Inform Discovery about the new reader
-    let reader_guid = self.domain_participant.guid().from_prefix(entity_id);
     self
       .discovery_command
       .try_send(DiscoveryCommand::AddLocalReader { guid: reader_guid })
@@ -1222,8 +1722,7 @@ impl InnerSubscriber {
         )
       })?;
 
-    // Return the DataReader to user
-    Ok(datareader)
+    Ok(())
   }
 
   pub fn create_datareader<D: 'static, SA>(
@@ -1305,9 +1804,25 @@ impl InnerSubscriber {
     self.domain_participant.clone().upgrade()
   }
 
+  fn autoenable_created_entities(&self) -> bool {
+    self
+      .qos
+      .entity_factory()
+      .map_or(true, |ef| ef.autoenable_created_entities)
+  }
+
   pub(crate) fn remove_reader(&self, guid: GUID) {
     try_send_timeout(&self.sender_remove_reader, guid, None)
       .unwrap_or_else(|e| error!("Cannot remove Reader {:?} : {:?}", guid, e));
+    // The event loop removes the old Reader before adding any new one, so the
+    // EntityId can be reused right away.
+    self.release_entity_id(guid.entity_id);
+  }
+
+  pub(crate) fn release_entity_id(&self, entity_id: EntityId) {
+    if let Some(dp) = self.participant() {
+      dp.release_endpoint_entity_id(entity_id);
+    }
   }
 
   fn unwrap_or_new_entity_id(
@@ -1317,11 +1832,73 @@ impl InnerSubscriber {
   ) -> EntityId {
     // If the entity_id is given, then just use that. If not, then pull an arbitrary
     // number out of participant's hat.
-    entity_id_opt.unwrap_or_else(|| self.participant().unwrap().new_entity_id(entity_kind))
+    entity_id_opt.unwrap_or_else(|| {
+      self
+        .participant()
+        .unwrap()
+        .new_endpoint_entity_id(entity_kind)
+    })
   }
 }
 
 // -------------------------------------------------------------------
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+  use super::*;
+  use crate::test::random_data::RandomData;
+
+  #[test]
+  fn endpoints_with_requested_entity_keys() {
+    let participant = DomainParticipant::new(0).unwrap();
+    let qos = QosPolicies::qos_none();
+    let publisher = participant.create_publisher(&qos).unwrap();
+    let subscriber = participant.create_subscriber(&qos).unwrap();
+    let topic = participant
+      .create_topic(
+        "requested_entity_keys".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let key = [0x12, 0x34, 0x56];
+
+    let writer = publisher
+      .create_datawriter_with_entity_key::<RandomData, CDRSerializerAdapter<_>>(key, &topic, None)
+      .unwrap();
+    assert_eq!(
+      writer.guid().entity_id,
+      EntityId::new(key, EntityKind::WRITER_WITH_KEY_USER_DEFINED)
+    );
+    let collision = publisher
+      .create_datawriter_with_entity_key::<RandomData, CDRSerializerAdapter<_>>(key, &topic, None);
+    assert!(matches!(collision, Err(CreateError::BadParameter { .. })));
+    // Another entity kind does not collide
+    let reader = subscriber
+      .create_datareader_with_entity_key::<RandomData, CDRDeserializerAdapter<_>>(key, &topic, None)
+      .unwrap();
+    assert_eq!(reader.guid().entity_id.entity_key, key);
+
+    // A generated EntityId cannot be requested while in use
+    let generated = publisher
+      .create_datawriter_cdr::<RandomData>(&topic, None)
+      .unwrap();
+    let generated_key = generated.guid().entity_id.entity_key;
+    assert!(publisher
+      .create_datawriter_with_entity_key::<RandomData, CDRSerializerAdapter<_>>(
+        generated_key,
+        &topic,
+        None
+      )
+      .is_err());
+
+    // Dropping the DataWriter frees its EntityId
+    let guid = writer.guid();
+    drop(writer);
+    let writer = publisher
+      .create_datawriter_with_entity_key::<RandomData, CDRSerializerAdapter<_>>(key, &topic, None)
+      .unwrap();
+    assert_eq!(writer.guid(), guid);
+  }
+}
@@ -1,6 +1,7 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use speedy::{Readable, Writable};
+use serde::{Deserialize, Serialize};
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 
@@ -31,10 +32,10 @@ pub trait MutQosPolicy {
 /// DDS spec 2.3.3 defines this as "long" with named constants from 0 to 22.
 /// numbering is from IDL PSM, but it should be unnecessary at the Rust
 /// application interface
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
 pub enum QosPolicyId {
   // Invalid  // We should represent this using Option<QosPolicyId> where needed
-  // UserData,  // 1
+  UserData,     // 1
   Durability,   // 2
   Presentation, // 3
   Deadline,
@@ -52,15 +53,26 @@ pub enum QosPolicyId {
   DestinationOrder,
   History, // 13
   ResourceLimits,
-  // EntityFactory, // 15
-  // WriterDataLifeCycle,
-  // ReaderDataLifeCycle, // 17
-  // TopicData, // 18
-  // GroupData,
+  EntityFactory, // 15
+  WriterDataLifecycle,
+  ReaderDataLifecycle, // 17
+  TopicData,           // 18
+  GroupData,
   // TransportPriority, // 20
   Lifespan,
   // DurabilityService, // 22
-  Property, // No Id in the security spec (But this is from older DDS/RTPs spec.)
+  PublishMode,            // RustDDS extension
+  HistoryReplay,          // RustDDS extension
+  SharedSubscription,     // RustDDS extension
+  IgnoreLocal,            // RustDDS extension
+  TimeOrderedDelivery,    // RustDDS extension
+  InstanceLimit,          // RustDDS extension
+  Batching,               // RustDDS extension
+  AdaptiveTiming,         // RustDDS extension
+  Compression,            // RustDDS extension
+  PayloadChecksum,        // RustDDS extension
+  NotificationCoalescing, // RustDDS extension
+  Property,               // No Id in the security spec (But this is from older DDS/RTPs spec.)
 }
 
 /// Utility for building [QosPolicies]
@@ -77,7 +89,24 @@ pub struct QosPolicyBuilder {
   destination_order: Option<policy::DestinationOrder>,
   history: Option<policy::History>,
   resource_limits: Option<policy::ResourceLimits>,
+  entity_factory: Option<policy::EntityFactory>,
   lifespan: Option<policy::Lifespan>,
+  user_data: Option<policy::UserData>,
+  topic_data: Option<policy::TopicData>,
+  group_data: Option<policy::GroupData>,
+  writer_data_lifecycle: Option<policy::WriterDataLifecycle>,
+  reader_data_lifecycle: Option<policy::ReaderDataLifecycle>,
+  publish_mode: Option<policy::PublishMode>,
+  history_replay: Option<policy::HistoryReplay>,
+  shared_subscription: Option<policy::SharedSubscription>,
+  ignore_local: Option<policy::IgnoreLocal>,
+  time_ordered_delivery: Option<policy::TimeOrderedDelivery>,
+  instance_limit: Option<policy::InstanceLimit>,
+  batching: Option<policy::Batching>,
+  adaptive_timing: Option<policy::AdaptiveTiming>,
+  compression: Option<policy::Compression>,
+  payload_checksum: Option<policy::PayloadChecksum>,
+  notification_coalescing: Option<policy::NotificationCoalescing>,
   #[cfg(feature = "security")]
   property: Option<policy::Property>,
 }
@@ -171,6 +200,120 @@ impl QosPolicyBuilder {
     self
   }
 
+  #[must_use]
+  pub fn user_data(mut self, user_data: policy::UserData) -> Self {
+    self.user_data = Some(user_data);
+    self
+  }
+
+  #[must_use]
+  pub fn topic_data(mut self, topic_data: policy::TopicData) -> Self {
+    self.topic_data = Some(topic_data);
+    self
+  }
+
+  #[must_use]
+  pub fn group_data(mut self, group_data: policy::GroupData) -> Self {
+    self.group_data = Some(group_data);
+    self
+  }
+
+  #[must_use]
+  pub const fn entity_factory(mut self, entity_factory: policy::EntityFactory) -> Self {
+    self.entity_factory = Some(entity_factory);
+    self
+  }
+
+  #[must_use]
+  pub const fn writer_data_lifecycle(
+    mut self,
+    writer_data_lifecycle: policy::WriterDataLifecycle,
+  ) -> Self {
+    self.writer_data_lifecycle = Some(writer_data_lifecycle);
+    self
+  }
+
+  #[must_use]
+  pub const fn reader_data_lifecycle(
+    mut self,
+    reader_data_lifecycle: policy::ReaderDataLifecycle,
+  ) -> Self {
+    self.reader_data_lifecycle = Some(reader_data_lifecycle);
+    self
+  }
+
+  #[must_use]
+  pub const fn publish_mode(mut self, publish_mode: policy::PublishMode) -> Self {
+    self.publish_mode = Some(publish_mode);
+    self
+  }
+
+  #[must_use]
+  pub const fn history_replay(mut self, history_replay: policy::HistoryReplay) -> Self {
+    self.history_replay = Some(history_replay);
+    self
+  }
+
+  #[must_use]
+  pub fn shared_subscription(mut self, shared_subscription: policy::SharedSubscription) -> Self {
+    self.shared_subscription = Some(shared_subscription);
+    self
+  }
+
+  #[must_use]
+  pub const fn ignore_local(mut self, ignore_local: policy::IgnoreLocal) -> Self {
+    self.ignore_local = Some(ignore_local);
+    self
+  }
+
+  #[must_use]
+  pub const fn time_ordered_delivery(
+    mut self,
+    time_ordered_delivery: policy::TimeOrderedDelivery,
+  ) -> Self {
+    self.time_ordered_delivery = Some(time_ordered_delivery);
+    self
+  }
+
+  #[must_use]
+  pub const fn instance_limit(mut self, instance_limit: policy::InstanceLimit) -> Self {
+    self.instance_limit = Some(instance_limit);
+    self
+  }
+
+  #[must_use]
+  pub const fn batching(mut self, batching: policy::Batching) -> Self {
+    self.batching = Some(batching);
+    self
+  }
+
+  #[must_use]
+  pub const fn adaptive_timing(mut self, adaptive_timing: policy::AdaptiveTiming) -> Self {
+    self.adaptive_timing = Some(adaptive_timing);
+    self
+  }
+
+  #[must_use]
+  pub const fn compression(mut self, compression: policy::Compression) -> Self {
+    self.compression = Some(compression);
+    self
+  }
+
+  #[must_use]
+  pub const fn payload_checksum(mut self, payload_checksum: policy::PayloadChecksum) -> Self {
+    self.payload_checksum = Some(payload_checksum);
+    self
+  }
+
+  #[must_use]
+  pub const fn notification_coalescing(
+    mut self,
+    notification_coalescing: policy::NotificationCoalescing,
+  ) -> Self {
+    self.notification_coalescing = Some(notification_coalescing);
+    self
+  }
+
   #[cfg(feature = "security")]
   #[must_use]
   pub fn property(mut self, property: policy::Property) -> Self {
@@ -191,7 +334,24 @@ impl QosPolicyBuilder {
       destination_order: self.destination_order,
       history: self.history,
       resource_limits: self.resource_limits,
+      entity_factory: self.entity_factory,
       lifespan: self.lifespan,
+      user_data: self.user_data,
+      topic_data: self.topic_data,
+      group_data: self.group_data,
+      writer_data_lifecycle: self.writer_data_lifecycle,
+      reader_data_lifecycle: self.reader_data_lifecycle,
+      publish_mode: self.publish_mode,
+      history_replay: self.history_replay,
+      shared_subscription: self.shared_subscription,
+      ignore_local: self.ignore_local,
+      time_ordered_delivery: self.time_ordered_delivery,
+      instance_limit: self.instance_limit,
+      batching: self.batching,
+      adaptive_timing: self.adaptive_timing,
+      compression: self.compression,
+      payload_checksum: self.payload_checksum,
+      notification_coalescing: self.notification_coalescing,
       #[cfg(feature = "security")]
       property: self.property,
     }
@@ -201,7 +361,7 @@ impl QosPolicyBuilder {
 /// Describes a set of RTPS/DDS QoS policies
 ///
 /// QosPolicies are constructed using a [`QosPolicyBuilder`]
-#[derive(Clone, Debug, PartialEq, Eq, Default)]
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub struct QosPolicies {
   // pub(crate) because as we want to have some builtin QoS Policies as constant.
   pub(crate) durability: Option<policy::Durability>,
@@ -215,8 +375,26 @@ pub struct QosPolicies {
   pub(crate) destination_order: Option<policy::DestinationOrder>,
   pub(crate) history: Option<policy::History>,
   pub(crate) resource_limits: Option<policy::ResourceLimits>,
+  pub(crate) entity_factory: Option<policy::EntityFactory>,
   pub(crate) lifespan: Option<policy::Lifespan>,
+  pub(crate) user_data: Option<policy::UserData>,
+  pub(crate) topic_data: Option<policy::TopicData>,
+  pub(crate) group_data: Option<policy::GroupData>,
+  pub(crate) writer_data_lifecycle: Option<policy::WriterDataLifecycle>,
+  pub(crate) reader_data_lifecycle: Option<policy::ReaderDataLifecycle>,
+  pub(crate) publish_mode: Option<policy::PublishMode>,
+  pub(crate) history_replay: Option<policy::HistoryReplay>,
+  pub(crate) shared_subscription: Option<policy::SharedSubscription>,
+  pub(crate) ignore_local: Option<policy::IgnoreLocal>,
+  pub(crate) time_ordered_delivery: Option<policy::TimeOrderedDelivery>,
+  pub(crate) instance_limit: Option<policy::InstanceLimit>,
+  pub(crate) batching: Option<policy::Batching>,
+  pub(crate) adaptive_timing: Option<policy::AdaptiveTiming>,
+  pub(crate) compression: Option<policy::Compression>,
+  pub(crate) payload_checksum: Option<policy::PayloadChecksum>,
+  pub(crate) notification_coalescing: Option<policy::NotificationCoalescing>,
   #[cfg(feature = "security")]
+  #[serde(skip)]
   pub(crate) property: Option<policy::Property>,
 }
 
@@ -295,6 +473,74 @@ impl QosPolicies {
     self.lifespan
   }
 
+  pub fn user_data(&self) -> Option<policy::UserData> {
+    self.user_data.clone()
+  }
+
+  pub fn topic_data(&self) -> Option<policy::TopicData> {
+    self.topic_data.clone()
+  }
+
+  pub fn group_data(&self) -> Option<policy::GroupData> {
+    self.group_data.clone()
+  }
+
+  pub const fn entity_factory(&self) -> Option<policy::EntityFactory> {
+    self.entity_factory
+  }
+
+  pub const fn writer_data_lifecycle(&self) -> Option<policy::WriterDataLifecycle> {
+    self.writer_data_lifecycle
+  }
+
+  pub const fn reader_data_lifecycle(&self) -> Option<policy::ReaderDataLifecycle> {
+    self.reader_data_lifecycle
+  }
+
+  pub const fn publish_mode(&self) -> Option<policy::PublishMode> {
+    self.publish_mode
+  }
+
+  pub const fn history_replay(&self) -> Option<policy::HistoryReplay> {
+    self.history_replay
+  }
+
+  pub fn shared_subscription(&self) -> Option<policy::SharedSubscription> {
+    self.shared_subscription.clone()
+  }
+
+  pub const fn ignore_local(&self) -> Option<policy::IgnoreLocal> {
+    self.ignore_local
+  }
+
+  pub const fn time_ordered_delivery(&self) -> Option<policy::TimeOrderedDelivery> {
+    self.time_ordered_delivery
+  }
+
+  pub const fn instance_limit(&self) -> Option<policy::InstanceLimit> {
+    self.instance_limit
+  }
+
+  pub const fn batching(&self) -> Option<policy::Batching> {
+    self.batching
+  }
+
+  pub const fn adaptive_timing(&self) -> Option<policy::AdaptiveTiming> {
+    self.adaptive_timing
+  }
+
+  pub const fn compression(&self) -> Option<policy::Compression> {
+    self.compression
+  }
+
+  pub const fn payload_checksum(&self) -> Option<policy::PayloadChecksum> {
+    self.payload_checksum
+  }
+
+  pub const fn notification_coalescing(&self) -> Option<policy::NotificationCoalescing> {
+    self.notification_coalescing
+  }
+
   #[cfg(feature = "security")]
   pub fn property(&self) -> Option<policy::Property> {
     self.property.clone()
@@ -318,12 +564,231 @@ impl QosPolicies {
       destination_order: other.destination_order.or(self.destination_order),
       history: other.history.or(self.history),
       resource_limits: other.resource_limits.or(self.resource_limits),
+      entity_factory: other.entity_factory.or(self.entity_factory),
       lifespan: other.lifespan.or(self.lifespan),
+      user_data: other.user_data.clone().or(self.user_data.clone()),
+      topic_data: other.topic_data.clone().or(self.topic_data.clone()),
+      group_data: other.group_data.clone().or(self.group_data.clone()),
+      writer_data_lifecycle: other.writer_data_lifecycle.or(self.writer_data_lifecycle),
+      reader_data_lifecycle: other.reader_data_lifecycle.or(self.reader_data_lifecycle),
+      publish_mode: other.publish_mode.or(self.publish_mode),
+      history_replay: other.history_replay.or(self.history_replay),
+      shared_subscription: other
+        .shared_subscription
+        .clone()
+        .or(self.shared_subscription.clone()),
+      ignore_local: other.ignore_local.or(self.ignore_local),
+      time_ordered_delivery: other.time_ordered_delivery.or(self.time_ordered_delivery),
+      instance_limit: other.instance_limit.or(self.instance_limit),
+      batching: other.batching.or(self.batching),
+      adaptive_timing: other.adaptive_timing.or(self.adaptive_timing),
+      compression: other.compression.or(self.compression),
+      payload_checksum: other.payload_checksum.or(self.payload_checksum),
+      notification_coalescing: other
+        .notification_coalescing
+        .or(self.notification_coalescing),
       #[cfg(feature = "security")]
       property: other.property.clone().or(self.property.clone()),
     }
   }
 
+  /// Merge two QosPolicies the other way around
+  ///
+  /// Constructs a QosPolicy, where each policy is taken from `self`, or from
+  /// `defaults` if it is not defined in `self`. This is the same as
+  /// `defaults.modify_by(self)`.
+  #[must_use]
+  pub fn with_defaults(&self, defaults: &Self) -> Self {
+    defaults.modify_by(self)
+  }
+
+  /// The policies that differ between `self` and `other`, including those
+  /// defined in only one of them. This is useful for finding out why two
+  /// QosPolicies are not equal, e.g. in logs and tests.
+  pub fn diff(&self, other: &Self) -> BTreeSet<QosPolicyId> {
+    // Destructure to not forget any policy
+    let Self {
+      durability,
+      presentation,
+      deadline,
+      latency_budget,
+      ownership,
+      liveliness,
+      time_based_filter,
+      reliability,
+      destination_order,
+      history,
+      resource_limits,
+      entity_factory,
+      lifespan,
+      user_data,
+      topic_data,
+      group_data,
+      writer_data_lifecycle,
+      reader_data_lifecycle,
+      publish_mode,
+      history_replay,
+      shared_subscription,
+      ignore_local,
+      time_ordered_delivery,
+      instance_limit,
+      batching,
+      adaptive_timing,
+      compression,
+      payload_checksum,
+      notification_coalescing,
+      #[cfg(feature = "security")]
+      property,
+    } = self;
+
+    let differences = [
+      (*durability != other.durability, QosPolicyId::Durability),
+      (
+        *presentation != other.presentation,
+        QosPolicyId::Presentation,
+      ),
+      (*deadline != other.deadline, QosPolicyId::Deadline),
+      (
+        *latency_budget != other.latency_budget,
+        QosPolicyId::LatencyBudget,
+      ),
+      (*ownership != other.ownership, QosPolicyId::Ownership),
+      (*liveliness != other.liveliness, QosPolicyId::Liveliness),
+      (
+        *time_based_filter != other.time_based_filter,
+        QosPolicyId::TimeBasedFilter,
+      ),
+      (*reliability != other.reliability, QosPolicyId::Reliability),
+      (
+        *destination_order != other.destination_order,
+        QosPolicyId::DestinationOrder,
+      ),
+      (*history != other.history, QosPolicyId::History),
+      (
+        *resource_limits != other.resource_limits,
+        QosPolicyId::ResourceLimits,
+      ),
+      (
+        *entity_factory != other.entity_factory,
+        QosPolicyId::EntityFactory,
+      ),
+      (*lifespan != other.lifespan, QosPolicyId::Lifespan),
+      (*user_data != other.user_data, QosPolicyId::UserData),
+      (*topic_data != other.topic_data, QosPolicyId::TopicData),
+      (*group_data != other.group_data, QosPolicyId::GroupData),
+      (
+        *writer_data_lifecycle != other.writer_data_lifecycle,
+        QosPolicyId::WriterDataLifecycle,
+      ),
+      (
+        *reader_data_lifecycle != other.reader_data_lifecycle,
+        QosPolicyId::ReaderDataLifecycle,
+      ),
+      (
+        *publish_mode != other.publish_mode,
+        QosPolicyId::PublishMode,
+      ),
+      (
+        *history_replay != other.history_replay,
+        QosPolicyId::HistoryReplay,
+      ),
+      (
+        *shared_subscription != other.shared_subscription,
+        QosPolicyId::SharedSubscription,
+      ),
+      (
+        *ignore_local != other.ignore_local,
+        QosPolicyId::IgnoreLocal,
+      ),
+      (
+        *time_ordered_delivery != other.time_ordered_delivery,
+        QosPolicyId::TimeOrderedDelivery,
+      ),
+      (
+        *instance_limit != other.instance_limit,
+        QosPolicyId::InstanceLimit,
+      ),
+      (*batching != other.batching, QosPolicyId::Batching),
+      (
+        *adaptive_timing != other.adaptive_timing,
+        QosPolicyId::AdaptiveTiming,
+      ),
+      (*compression != other.compression, QosPolicyId::Compression),
+      (
+        *payload_checksum != other.payload_checksum,
+        QosPolicyId::PayloadChecksum,
+      ),
+      (
+        *notification_coalescing != other.notification_coalescing,
+        QosPolicyId::NotificationCoalescing,
+      ),
+      #[cfg(feature = "security")]
+      (*property != other.property, QosPolicyId::Property),
+    ];
+    differences
+      .into_iter()
+      .filter_map(|(differs, policy)| differs.then_some(policy))
+      .collect()
+  }
+
+  /// Compute the policies of an existing entity after `set_qos(new_qos)`.
+  ///
+  /// Policies defined in `new_qos` replace the current ones, and the rest are
+  /// kept, as in [`modify_by`](Self::modify_by). Fails if a policy that
+  /// cannot be changed after entity creation would change.
+  pub(crate) fn updated_by(&self, new_qos: &Self) -> Result<Self, QosError> {
+    let updated = self.modify_by(new_qos);
+    match self.immutable_policy_change_wrt(&updated) {
+      Some(policy) => Err(QosError::ImmutablePolicy { policy }),
+      None => Ok(updated),
+    }
+  }
+
+  /// Find a policy that differs between `self` and `other`, and is not
+  /// changeable after entity creation. Changeability is defined in the table in
+  /// DDS spec v1.4 Section "2.2.3 Supported QoS".
+  ///
+  /// The policies that can be changed are listed in
+  /// [`QosError::ImmutablePolicy`].
+  fn immutable_policy_change_wrt(&self, other: &Self) -> Option<QosPolicyId> {
+    let ownership_kind =
+      |o: Option<policy::Ownership>| o.map(|o| matches!(o, policy::Ownership::Exclusive { .. }));
+
+    if self.durability != other.durability {
+      Some(QosPolicyId::Durability)
+    } else if self.presentation != other.presentation {
+      Some(QosPolicyId::Presentation)
+    } else if ownership_kind(self.ownership) != ownership_kind(other.ownership) {
+      Some(QosPolicyId::Ownership)
+    } else if self.liveliness != other.liveliness {
+      Some(QosPolicyId::Liveliness)
+    } else if self.reliability != other.reliability {
+      Some(QosPolicyId::Reliability)
+    } else if self.destination_order != other.destination_order {
+      Some(QosPolicyId::DestinationOrder)
+    } else if self.history != other.history {
+      Some(QosPolicyId::History)
+    } else if self.resource_limits != other.resource_limits {
+      Some(QosPolicyId::ResourceLimits)
+    } else if self.publish_mode != other.publish_mode {
+      Some(QosPolicyId::PublishMode)
+    } else if self.shared_subscription != other.shared_subscription {
+      Some(QosPolicyId::SharedSubscription)
+    } else if self.ignore_local != other.ignore_local {
+      Some(QosPolicyId::IgnoreLocal)
+    } else if self.time_ordered_delivery != other.time_ordered_delivery {
+      Some(QosPolicyId::TimeOrderedDelivery)
+    } else if self.notification_coalescing != other.notification_coalescing {
+      Some(QosPolicyId::NotificationCoalescing)
+    } else {
+      #[cfg(feature = "security")]
+      if self.property != other.property {
+        return Some(QosPolicyId::Property);
+      }
+      None
+    }
+  }
+
   /// Check if policy complies to another policy.
   ///
   /// `self` is the "offered" (publisher) QoS
@@ -449,6 +914,24 @@ impl QosPolicies {
       history,
       resource_limits,
       lifespan,
+      user_data,
+      topic_data,
+      group_data,
+      // These are local settings of the DataWriter / DataReader, not sent to others
+      entity_factory: _,
+      writer_data_lifecycle: _,
+      reader_data_lifecycle: _,
+      publish_mode: _,
+      history_replay: _,
+      shared_subscription,
+      ignore_local: _,
+      time_ordered_delivery: _,
+      instance_limit: _,
+      batching: _,
+      adaptive_timing: _,
+      compression,
+      payload_checksum: _,
+      notification_coalescing: _,
       #[cfg(feature = "security")]
         property: _, // TODO: properties to parameter list?
     } = self;
@@ -530,6 +1013,32 @@ impl QosPolicies {
     }
     emit_option!(PID_RESOURCE_LIMITS, resource_limits, policy::ResourceLimits);
     emit_option!(PID_LIFESPAN, lifespan, policy::Lifespan);
+    emit_option!(PID_USER_DATA, user_data, policy::UserData);
+    emit_option!(PID_TOPIC_DATA, topic_data, policy::TopicData);
+    emit_option!(PID_GROUP_DATA, group_data, policy::GroupData);
+
+    if let Some(SharedSubscription {
+      group,
+      distribution,
+    }) = shared_subscription
+    {
+      let shared_ser = SharedSubscriptionSerialization {
+        distribution: *distribution,
+        group: group.into(),
+      };
+      emit!(
+        PID_RUSTDDS_SHARED_SUBSCRIPTION,
+        &shared_ser,
+        SharedSubscriptionSerialization
+      );
+    }
+
+    // Announcing Compression tells DataWriters that we can decompress, so
+    // do it only if we can.
+    #[cfg(feature = "compression")]
+    emit_option!(PID_RUSTDDS_COMPRESSION, compression, policy::Compression);
+    #[cfg(not(feature = "compression"))]
+    let _ = compression;
 
     Ok(pl)
   }
@@ -594,6 +1103,45 @@ impl QosPolicies {
 
     let resource_limits: Option<policy::ResourceLimits> = get_option!(PID_RESOURCE_LIMITS);
     let lifespan: Option<policy::Lifespan> = get_option!(PID_LIFESPAN);
+    let user_data: Option<policy::UserData> = get_option!(PID_USER_DATA);
+    let topic_data: Option<policy::TopicData> = get_option!(PID_TOPIC_DATA);
+    let group_data: Option<policy::GroupData> = get_option!(PID_GROUP_DATA);
+
+    // Vendor-specific parameter ids may mean something else to other
+    // implementations, so this one is ignored if it does not parse.
+    let shared_subscription: Option<policy::SharedSubscription> = get_option_from_pl_map(
+      pl_map,
+      ctx,
+      ParameterId::PID_RUSTDDS_SHARED_SUBSCRIPTION,
+      "shared subscription",
+    )
+    .unwrap_or_default()
+    .map(
+      |ss: SharedSubscriptionSerialization| policy::SharedSubscription {
+        group: ss.group.into(),
+        distribution: ss.distribution,
+      },
+    );
+    let compression: Option<policy::Compression> = get_option_from_pl_map(
+      pl_map,
+      ctx,
+      ParameterId::PID_RUSTDDS_COMPRESSION,
+      "compression",
+    )
+    .unwrap_or_default();
+    // not transmitted
+    let entity_factory: Option<policy::EntityFactory> = None;
+    let writer_data_lifecycle: Option<policy::WriterDataLifecycle> = None;
+    let reader_data_lifecycle: Option<policy::ReaderDataLifecycle> = None;
+    let publish_mode: Option<policy::PublishMode> = None;
+    let history_replay: Option<policy::HistoryReplay> = None;
+    let ignore_local: Option<policy::IgnoreLocal> = None;
+    let time_ordered_delivery: Option<policy::TimeOrderedDelivery> = None;
+    let instance_limit: Option<policy::InstanceLimit> = None;
+    let batching: Option<policy::Batching> = None;
+    let adaptive_timing: Option<policy::AdaptiveTiming> = None;
+    let payload_checksum: Option<policy::PayloadChecksum> = None;
+    let notification_coalescing: Option<policy::NotificationCoalescing> = None;
 
     #[cfg(feature = "security")]
     let property: Option<policy::Property> = None; // TODO: Should also properties be read?
@@ -612,7 +1160,24 @@ impl QosPolicies {
       destination_order,
       history,
       resource_limits,
+      entity_factory,
       lifespan,
+      user_data,
+      topic_data,
+      group_data,
+      writer_data_lifecycle,
+      reader_data_lifecycle,
+      publish_mode,
+      history_replay,
+      shared_subscription,
+      ignore_local,
+      time_ordered_delivery,
+      instance_limit,
+      batching,
+      adaptive_timing,
+      compression,
+      payload_checksum,
+      notification_coalescing,
       #[cfg(feature = "security")]
       property,
     })
@@ -645,6 +1210,13 @@ struct ReliabilitySerialization {
   pub max_blocking_time: Duration,
 }
 
+// The string is last, so that no alignment padding is needed.
+#[derive(Writable, Readable, Clone)]
+struct SharedSubscriptionSerialization {
+  pub distribution: policy::SampleDistribution,
+  pub group: StringWithNul,
+}
+
 // DDS spec v1.4 p.139
 // TODO: Replace this with Option construct so that
 // None means no limit and Some(limit) gives the limit when defined.
@@ -658,6 +1230,7 @@ pub mod policy {
   use std::cmp::Ordering;
 
   use speedy::{Readable, Writable};
+  use serde::{Deserialize, Serialize};
   #[allow(unused_imports)]
   use log::{debug, error, info, trace, warn};
   #[cfg(feature = "security")]
@@ -667,32 +1240,69 @@ pub mod policy {
   #[cfg(feature = "security")]
   use crate::serialization::speedy_pl_cdr_helpers::*;
 
-  /*
+  /// DDS 2.2.3.1 USER_DATA
+  ///
+  /// Application data attached to a DomainParticipant, DataReader, or
+  /// DataWriter. It is sent to remote participants via Discovery.
+  #[derive(
+    Clone, Debug, PartialEq, Eq, Hash, Default, Readable, Writable, Serialize, Deserialize,
+  )]
   pub struct UserData {
     pub value: Vec<u8>,
   }
 
+  /// DDS 2.2.3.2 TOPIC_DATA
+  ///
+  /// Application data attached to a Topic. DataReaders and DataWriters announce
+  /// the TopicData of their Topic via Discovery.
+  #[derive(
+    Clone, Debug, PartialEq, Eq, Hash, Default, Readable, Writable, Serialize, Deserialize,
+  )]
   pub struct TopicData {
     pub value: Vec<u8>,
   }
 
+  /// DDS 2.2.3.3 GROUP_DATA
+  ///
+  /// Application data attached to a Publisher or Subscriber. Its DataWriters
+  /// or DataReaders announce the GroupData via Discovery.
+  #[derive(
+    Clone, Debug, PartialEq, Eq, Hash, Default, Readable, Writable, Serialize, Deserialize,
+  )]
   pub struct GroupData {
     pub value: Vec<u8>,
   }
 
+  /*
   pub struct TransportPriority {
     pub value: i32,
   }
   */
 
   /// DDS 2.2.3.16 LIFESPAN
-  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Readable, Writable)]
+  ///
+  /// A DataWriter does not send expired samples anymore. Reliable
+  /// DataReaders that request one get a GAP instead.
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Readable, Writable, Serialize, Deserialize)]
   pub struct Lifespan {
     pub duration: Duration,
   }
 
   /// DDS 2.2.3.4 DURABILITY
-  #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Readable, Writable)]
+  #[derive(
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Readable,
+    Writable,
+    Serialize,
+    Deserialize,
+  )]
   pub enum Durability {
     Volatile,
     TransientLocal,
@@ -701,7 +1311,7 @@ pub mod policy {
   }
 
   /// DDS 2.2.3.6 PRESENTATION
-  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Readable, Writable)]
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Readable, Writable, Serialize, Deserialize)]
   pub struct Presentation {
     pub access_scope: PresentationAccessScope,
     pub coherent_access: bool,
@@ -709,7 +1319,20 @@ pub mod policy {
   }
 
   /// Access scope that is part of DDS 2.2.3.6 PRESENTATION
-  #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Readable, Writable)]
+  #[derive(
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Readable,
+    Writable,
+    Serialize,
+    Deserialize,
+  )]
   pub enum PresentationAccessScope {
     Instance,
     Topic,
@@ -717,24 +1340,37 @@ pub mod policy {
   }
 
   /// DDS 2.2.3.7 DEADLINE
-  #[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd, Hash, Readable, Writable)]
+  #[derive(
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Readable,
+    Writable,
+    Serialize,
+    Deserialize,
+  )]
   pub struct Deadline(pub Duration);
 
   /// DDS 2.2.3.8 LATENCY_BUDGET
-  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Readable, Writable)]
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Readable, Writable, Serialize, Deserialize)]
   pub struct LatencyBudget {
     pub duration: Duration,
   }
 
   /// DDS 2.2.3.9 OWNERSHIP
-  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
   pub enum Ownership {
     Shared,
     Exclusive { strength: i32 }, // This also implements OwnershipStrength
   }
 
   /// DDS 2.2.3.11 LIVELINESS
-  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Readable, Writable)]
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Readable, Writable, Serialize, Deserialize)]
   pub enum Liveliness {
     Automatic { lease_duration: Duration },
     ManualByParticipant { lease_duration: Duration },
@@ -777,7 +1413,7 @@ pub mod policy {
   }
 
   /// DDS 2.2.3.12 TIME_BASED_FILTER
-  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Readable, Writable)]
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Readable, Writable, Serialize, Deserialize)]
   pub struct TimeBasedFilter {
     pub minimum_separation: Duration,
   }
@@ -789,7 +1425,7 @@ pub mod policy {
   */
 
   /// DDS 2.2.3.14 RELIABILITY
-  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
   pub enum Reliability {
     BestEffort,
     Reliable { max_blocking_time: Duration },
@@ -819,14 +1455,27 @@ pub mod policy {
   }
 
   /// DDS 2.2.3.17 DESTINATION_ORDER
-  #[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd, Hash, Readable, Writable)]
+  #[derive(
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Readable,
+    Writable,
+    Serialize,
+    Deserialize,
+  )]
   pub enum DestinationOrder {
     ByReceptionTimestamp,
     BySourceTimeStamp,
   }
 
   /// DDS 2.2.3.18 HISTORY
-  #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
   pub enum History {
     // Variants must be in this order ot derive Ord correctly.
     KeepLast { depth: i32 },
@@ -844,13 +1493,313 @@ pub mod policy {
   ///
   /// Negative values are needed, because DDS spec defines the special value
   /// const long LENGTH_UNLIMITED = -1;
-  #[derive(Copy, Clone, Debug, PartialEq, Eq, Writable, Readable)]
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Writable, Readable, Serialize, Deserialize)]
   pub struct ResourceLimits {
     pub max_samples: i32,
     pub max_instances: i32,
     pub max_samples_per_instance: i32,
   }
 
+  /// DDS 2.2.3.20 ENTITY_FACTORY
+  ///
+  /// Set in the QoS of a Publisher or Subscriber. If
+  /// `autoenable_created_entities` is `false`, the DataWriters or DataReaders
+  /// created from it are disabled, until their `enable()` is called. A
+  /// disabled entity is not announced via Discovery and does not send or
+  /// receive data. The default (policy not set) is `true`.
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+  pub struct EntityFactory {
+    pub autoenable_created_entities: bool,
+  }
+
+  /// DDS 2.2.3.21 WRITER_DATA_LIFECYCLE
+  ///
+  /// If `autodispose_unregistered_instances` is set, unregistering an
+  /// instance also disposes it. The default (policy not set) is `true`.
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+  pub struct WriterDataLifecycle {
+    pub autodispose_unregistered_instances: bool,
+  }
+
+  /// DDS 2.2.3.22 READER_DATA_LIFECYCLE
+  ///
+  /// How long a DataReader keeps the data of instances that are not alive.
+  /// Once an instance has been in the NotAliveNoWriters or NotAliveDisposed
+  /// state for the corresponding delay, the instance and all of its samples are
+  /// purged from the DataReader, whether read or not.
+  ///
+  /// The default (policy not set) is [`Duration::INFINITE`] for both, i.e.
+  /// nothing is purged.
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+  pub struct ReaderDataLifecycle {
+    pub autopurge_nowriter_samples_delay: Duration,
+    pub autopurge_disposed_samples_delay: Duration,
+  }
+
+  /// How a DataWriter sends the samples it writes. This is a RustDDS
+  /// extension, similar to the PUBLISH_MODE QoS of some other DDS
+  /// implementations. It is local to the DataWriter and not sent to remote
+  /// participants. The default is `Synchronous`.
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+  pub enum PublishMode {
+    /// Samples are sent as soon as the RTPS Writer gets them. A large sample
+    /// is sent as a burst of all its fragments.
+    Synchronous,
+    /// Samples are queued for sending and sent at most `bytes_per_period`
+    /// bytes every `period`. This paces large, fragmented samples and
+    /// keeps them from occupying the network and event loop in one go.
+    ///
+    /// At least one message is sent every period, even if it is larger than
+    /// `bytes_per_period`. Periods shorter than the internal timer resolution
+    /// (100 ms) are rounded up to it.
+    ///
+    /// At most 4 MiB of messages wait to be sent. When more are queued, the
+    /// DataWriter blocks in `write` like when its own queue is full, and
+    /// returns [`WriteError::WouldBlock`](crate::dds::result::WriteError::WouldBlock)
+    /// after the `max_blocking_time` of Reliability.
+    Asynchronous {
+      bytes_per_period: u32,
+      period: Duration,
+    },
+  }
+
+  /// How a DataWriter replays its history to late-joining DataReaders. This
+  /// is a RustDDS extension. It is local to the DataWriter and not sent to
+  /// remote participants.
+  ///
+  /// A Reliable DataWriter with TransientLocal Durability sends the samples
+  /// written before a DataReader was matched to that DataReader as repair
+  /// data. Replaying a long history to a late joiner in one go can flood the
+  /// network, so the replay is sent in batches of at most `max_batch_size`
+  /// samples, one batch every `batch_period`. Each matched DataReader is paced
+  /// separately. A `max_batch_size` of zero is treated as one. Periods shorter
+  /// than the internal timer resolution (100 ms) are rounded up to it.
+  ///
+  /// With `latest_per_instance`, only the latest sample of each instance is
+  /// replayed. The older samples are announced to the DataReader as no longer
+  /// relevant (GAP).
+  ///
+  /// Without this policy, the history is replayed one sample at a time.
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+  pub struct HistoryReplay {
+    pub max_batch_size: u32,
+    pub batch_period: Duration,
+    pub latest_per_instance: bool,
+  }
+
+  /// Load balancing among DataReaders. This is a RustDDS extension.
+  ///
+  /// DataReaders of a Topic that have SharedSubscription with the same
+  /// `group` form a shared reader group, like the consumers of a message
+  /// queue: each sample is given to the application by only one member of the
+  /// group. The members can be in different participants and processes, as
+  /// long as they all run RustDDS.
+  ///
+  /// There is no coordinator. Each member sees the other members via
+  /// Discovery, and selects its own share of the samples. All members still
+  /// receive and acknowledge every sample at the RTPS level, so DataWriters
+  /// are not affected. While members are joining or leaving, and Discovery
+  /// has not yet reached all of them, a sample may be given to two members or
+  /// to none. All members should have the same `distribution` and otherwise
+  /// identical QoS.
+  #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+  pub struct SharedSubscription {
+    pub group: String,
+    pub distribution: SampleDistribution,
+  }
+
+  /// How the samples are divided among the members of a
+  /// [`SharedSubscription`] group
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Readable, Writable, Serialize, Deserialize)]
+  pub enum SampleDistribution {
+    /// Samples of each DataWriter are dealt to the members in turn, by
+    /// sequence number. Samples of one instance may go to different members.
+    RoundRobin,
+    /// All samples of an instance go to the same member, selected by the
+    /// key hash of the instance.
+    KeyAffinity,
+  }
+
+  /// Which local endpoints a DataWriter or DataReader does not match with.
+  /// This is a RustDDS extension, similar to the IGNORELOCAL QoS of some other
+  /// DDS implementations. It is local to the endpoint and not sent to remote
+  /// participants. The default is `Nothing`.
+  ///
+  /// This is useful e.g. for bridges and loggers, which both write and read a
+  /// Topic, but do not want to receive their own samples. Unlike filtering in
+  /// the application, the samples are not sent to the ignored DataReaders at
+  /// all. If either endpoint of a pair ignores the other, they do not match.
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+  pub enum IgnoreLocal {
+    /// Match with all endpoints.
+    Nothing,
+    /// Do not match with endpoints of the same DomainParticipant.
+    Participant,
+    /// Do not match with endpoints of any DomainParticipant in the same
+    /// process.
+    Process,
+  }
+
+  /// Makes a DataReader deliver the samples of all matched DataWriters merged
+  /// in source timestamp order, instead of the order of arrival. This is a
+  /// RustDDS extension, local to the DataReader.
+  ///
+  /// Each received sample is held back for `hold_back` before it becomes
+  /// available, so that samples from other DataWriters with earlier source
+  /// timestamps can still overtake it. A sample that arrives later than the
+  /// window, after a sample with a later source timestamp has already been
+  /// delivered, is delivered out of order. Samples without a source timestamp
+  /// are ordered by their reception time.
+  ///
+  /// The reception timestamp in the [`SampleInfo`](crate::SampleInfo) of a
+  /// reordered sample may be adjusted forward slightly, so that reception
+  /// timestamps follow the delivery order.
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+  pub struct TimeOrderedDelivery {
+    pub hold_back: Duration,
+  }
+
+  /// Upper bound on the number of instances a DataReader keeps track of. This
+  /// is a RustDDS extension, local to the DataReader.
+  ///
+  /// A DataReader remembers every instance it has received, also after the
+  /// instance is disposed or unregistered, unless it is purged according to
+  /// [`ReaderDataLifecycle`]. On a keyed Topic with an unbounded number of
+  /// keys, this would grow without limit.
+  ///
+  /// When a sample of a new instance arrives and `max_instances` instances are
+  /// already tracked, an existing instance is removed according to
+  /// `eviction`, together with its samples. If there is nothing to evict, the
+  /// new sample is rejected and reported as
+  /// [`SampleRejected`](crate::dds::statusevents::DataReaderStatus::SampleRejected)
+  /// with reason `ByInstancesLimit`.
+  ///
+  /// Unlike `max_instances` of [`ResourceLimits`], this counts also the
+  /// instances that are not alive and have no samples left.
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+  pub struct InstanceLimit {
+    pub max_instances: u32,
+    pub eviction: InstanceEviction,
+  }
+
+  /// Makes a DataWriter send its samples in batches, i.e. several samples in
+  /// one RTPS message. This is a RustDDS extension, local to the DataWriter.
+  ///
+  /// Written samples are held back until `max_samples` of them are pending,
+  /// `max_delay` has passed since the first of them was written, or the
+  /// application calls [`flush`](crate::with_key::DataWriter::flush). Then
+  /// they are sent together, with a single HEARTBEAT. A `max_samples` of zero
+  /// is treated as one. Delays shorter than the internal timer resolution
+  /// (100 ms) are rounded up to it, so for low latency, flush explicitly at
+  /// the end of each burst.
+  ///
+  /// Samples written for a single DataReader are sent immediately. Samples
+  /// that need fragmentation are held back like the others, but sent in
+  /// messages of their own. Batching has no effect with
+  /// [`PublishMode::Asynchronous`].
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+  pub struct Batching {
+    pub max_samples: u32,
+    pub max_delay: Duration,
+  }
+
+  /// Lets a reliable DataWriter adapt its timing to each matched DataReader,
+  /// based on the measured round trip time (RTT) from HEARTBEAT to ACKNACK.
+  /// This is a RustDDS extension, local to the DataWriter.
+  ///
+  /// Periodic heartbeats to a reader are sent every four retransmission
+  /// timeouts (smoothed RTT plus four times its variation), but not more
+  /// often than every `min_heartbeat_period`, nor less often than the usual
+  /// heartbeat period. Repairs requested by a reader are sent after at most
+  /// its smoothed RTT, if that is shorter than the usual response delay.
+  /// Until a reader has been measured, the usual timing applies.
+  ///
+  /// Delays shorter than the internal timer resolution (100 ms) are rounded
+  /// up to it. The measured times are available from
+  /// [`round_trip_times`](crate::with_key::DataWriter::round_trip_times)
+  /// regardless of this policy.
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+  pub struct AdaptiveTiming {
+    pub min_heartbeat_period: Duration,
+  }
+
+  /// Compresses large serialized samples of a DataWriter. This is a RustDDS
+  /// extension, and needs the `compression` crate feature.
+  ///
+  /// DataReaders announce the policy in Discovery. A DataWriter compresses a
+  /// sample with `algorithm` if its serialized payload is at least
+  /// `threshold` bytes, compression makes it smaller, and every matched
+  /// DataReader has announced the same `algorithm`. Otherwise the sample is
+  /// sent as is. DataReaders decompress samples transparently, and ignore
+  /// `threshold`. Setting the policy on the Topic applies it to both sides.
+  ///
+  /// The decision is made when the sample is written, so a DataReader that
+  /// does not support compression, matched later, cannot decode compressed
+  /// samples from the history of the DataWriter.
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Readable, Writable, Serialize, Deserialize)]
+  pub struct Compression {
+    pub algorithm: CompressionAlgorithm,
+    pub threshold: u32,
+  }
+
+  /// Algorithm of the [`Compression`] policy
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Readable, Writable, Serialize, Deserialize)]
+  pub enum CompressionAlgorithm {
+    /// zlib (RFC 1950) at the default compression level
+    Zlib,
+    /// Zstandard (RFC 8878) at the default compression level
+    Zstd,
+  }
+
+  /// Makes a DataWriter send a checksum of the serialized payload with each
+  /// sample, in a vendor-specific inline QoS parameter. This is a RustDDS
+  /// extension, local to the DataWriter.
+  ///
+  /// This protects against corruption that the UDP checksum does not catch,
+  /// e.g. due to faulty checksum offloading in network hardware. DataReaders
+  /// check the payload of every sample that has a checksum, and discard the
+  /// sample if it does not match. Reliable DataReaders then request it again.
+  /// Discarded samples are counted in
+  /// [`WriterProxyStatistics`](crate::WriterProxyStatistics).
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+  pub enum PayloadChecksum {
+    /// CRC-32C (Castagnoli), as in iSCSI and SCTP
+    Crc32c,
+  }
+
+  /// Makes a DataReader wake up the application less often than once per
+  /// received sample. This is a RustDDS extension, local to the DataReader.
+  ///
+  /// The DataReader is notified, i.e. its async streams are woken up and its
+  /// mio event sources become readable, when `max_samples` new samples are
+  /// pending, or at the latest `max_delay` after the first of them arrived. A
+  /// `max_samples` of zero means no limit, so that the DataReader is notified
+  /// at most once every `max_delay`. This reduces context switches on high
+  /// rate topics, where the application takes samples in batches anyway.
+  ///
+  /// Samples are available to `take` as soon as they arrive, regardless of
+  /// this policy. This policy cannot be changed after the DataReader is
+  /// created.
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+  pub struct NotificationCoalescing {
+    pub max_samples: u32,
+    pub max_delay: Duration,
+  }
+
+  /// Which instance is removed, when [`InstanceLimit`] is reached
+  #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+  pub enum InstanceEviction {
+    /// Nothing is removed. Samples of new instances are rejected.
+    Reject,
+    /// The least recently updated instance that is not alive, i.e. disposed
+    /// or without writers. If all instances are alive, the new sample is
+    /// rejected.
+    NotAlive,
+    /// Like `NotAlive`, but if all instances are alive, the least recently
+    /// updated alive instance is removed.
+    LeastRecentlyUpdated,
+  }
+
   #[cfg(feature = "security")]
   use crate::security;
   // DDS Security spec v1.1
@@ -999,3 +1948,305 @@ pub mod policy {
     }
   }
 } // mod policy
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn qos_update_changeable_and_immutable() {
+    let current = QosPolicyBuilder::new()
+      .reliable(Duration::from_millis(100))
+      .deadline(policy::Deadline(Duration::from_secs(1)))
+      .ownership(policy::Ownership::Exclusive { strength: 1 })
+      .build();
+
+    let updated = current
+      .updated_by(
+        &QosPolicyBuilder::new()
+          .deadline(policy::Deadline(Duration::from_secs(2)))
+          .ownership(policy::Ownership::Exclusive { strength: 5 })
+          .build(),
+      )
+      .unwrap();
+    assert_eq!(
+      updated.deadline,
+      Some(policy::Deadline(Duration::from_secs(2)))
+    );
+    assert_eq!(
+      updated.ownership,
+      Some(policy::Ownership::Exclusive { strength: 5 })
+    );
+    assert_eq!(updated.reliability, current.reliability); // kept
+
+    // Setting an immutable policy to its current value is not a change
+    assert!(current.updated_by(&current).is_ok());
+
+    assert!(matches!(
+      current.updated_by(&QosPolicyBuilder::new().best_effort().build()),
+      Err(QosError::ImmutablePolicy {
+        policy: QosPolicyId::Reliability
+      })
+    ));
+    assert!(matches!(
+      current.updated_by(
+        &QosPolicyBuilder::new()
+          .ownership(policy::Ownership::Shared)
+          .build()
+      ),
+      Err(QosError::ImmutablePolicy {
+        policy: QosPolicyId::Ownership
+      })
+    ));
+    assert!(matches!(
+      current.updated_by(
+        &QosPolicyBuilder::new()
+          .durability(policy::Durability::TransientLocal)
+          .build()
+      ),
+      Err(QosError::ImmutablePolicy {
+        policy: QosPolicyId::Durability
+      })
+    ));
+  }
+
+  #[test]
+  fn qos_update_changeable_policies() {
+    // Each of these can be changed after entity creation
+    let changes = [
+      QosPolicyBuilder::new()
+        .writer_data_lifecycle(policy::WriterDataLifecycle {
+          autodispose_unregistered_instances: false,
+        })
+        .build(),
+      QosPolicyBuilder::new()
+        .reader_data_lifecycle(policy::ReaderDataLifecycle {
+          autopurge_nowriter_samples_delay: Duration::from_secs(1),
+          autopurge_disposed_samples_delay: Duration::from_secs(1),
+        })
+        .build(),
+      QosPolicyBuilder::new()
+        .history_replay(policy::HistoryReplay {
+          max_batch_size: 10,
+          batch_period: Duration::from_millis(10),
+          latest_per_instance: true,
+        })
+        .build(),
+      QosPolicyBuilder::new()
+        .entity_factory(policy::EntityFactory {
+          autoenable_created_entities: false,
+        })
+        .build(),
+      QosPolicyBuilder::new()
+        .instance_limit(policy::InstanceLimit {
+          max_instances: 10,
+          eviction: policy::InstanceEviction::NotAlive,
+        })
+        .build(),
+      QosPolicyBuilder::new()
+        .batching(policy::Batching {
+          max_samples: 10,
+          max_delay: Duration::from_millis(10),
+        })
+        .build(),
+      QosPolicyBuilder::new()
+        .adaptive_timing(policy::AdaptiveTiming {
+          min_heartbeat_period: Duration::from_millis(10),
+        })
+        .build(),
+      QosPolicyBuilder::new()
+        .compression(policy::Compression {
+          algorithm: policy::CompressionAlgorithm::Zstd,
+          threshold: 1000,
+        })
+        .build(),
+      QosPolicyBuilder::new()
+        .payload_checksum(policy::PayloadChecksum::Crc32c)
+        .build(),
+    ];
+    let current = QosPolicyBuilder::new().build();
+    for change in &changes {
+      assert_eq!(
+        current.updated_by(change).unwrap(),
+        current.modify_by(change)
+      );
+    }
+  }
+
+  #[test]
+  fn qos_user_data_parameter_list() {
+    let qos = QosPolicyBuilder::new()
+      .user_data(policy::UserData {
+        value: vec![0xa, 0xb, 0xc, 0xd, 0xe],
+      })
+      .group_data(policy::GroupData::default())
+      .build();
+
+    let pl = qos
+      .to_parameter_list(speedy::Endianness::LittleEndian)
+      .unwrap();
+    // USER_DATA is a sequence<octet>: length and bytes. The ParameterList
+    // serialization adds padding.
+    assert_eq!(
+      pl[0],
+      Parameter::new(
+        ParameterId::PID_USER_DATA,
+        vec![5, 0, 0, 0, 0xa, 0xb, 0xc, 0xd, 0xe]
+      )
+    );
+    assert_eq!(
+      pl[1],
+      Parameter::new(ParameterId::PID_GROUP_DATA, vec![0, 0, 0, 0])
+    );
+
+    let mut pl_map = BTreeMap::new();
+    for p in &pl {
+      pl_map
+        .entry(p.parameter_id)
+        .or_insert_with(Vec::new)
+        .push(p);
+    }
+    let qos2 = QosPolicies::from_parameter_list(speedy::Endianness::LittleEndian, &pl_map).unwrap();
+    assert_eq!(qos, qos2);
+
+    // UserData can be changed after creation
+    let changed = QosPolicyBuilder::new()
+      .user_data(policy::UserData { value: vec![1] })
+      .build();
+    assert_eq!(
+      qos.updated_by(&changed).unwrap().user_data(),
+      changed.user_data()
+    );
+  }
+
+  #[test]
+  fn qos_shared_subscription_parameter_list() {
+    let qos = QosPolicyBuilder::new()
+      .shared_subscription(policy::SharedSubscription {
+        group: "workers".to_string(),
+        distribution: policy::SampleDistribution::KeyAffinity,
+      })
+      .build();
+
+    let pl = qos
+      .to_parameter_list(speedy::Endianness::LittleEndian)
+      .unwrap();
+    assert_eq!(
+      pl,
+      vec![Parameter::new(
+        ParameterId::PID_RUSTDDS_SHARED_SUBSCRIPTION,
+        vec![1, 0, 0, 0, 8, 0, 0, 0, b'w', b'o', b'r', b'k', b'e', b'r', b's', 0]
+      )]
+    );
+
+    let mut pl_map = BTreeMap::new();
+    pl_map.insert(pl[0].parameter_id, vec![&pl[0]]);
+    let qos2 = QosPolicies::from_parameter_list(speedy::Endianness::LittleEndian, &pl_map).unwrap();
+    assert_eq!(qos, qos2);
+
+    // Another implementation may use the same vendor-specific id for something
+    // else. That is ignored.
+    let other = Parameter::new(ParameterId::PID_RUSTDDS_SHARED_SUBSCRIPTION, vec![7, 0]);
+    pl_map.insert(other.parameter_id, vec![&other]);
+    let qos3 = QosPolicies::from_parameter_list(speedy::Endianness::LittleEndian, &pl_map).unwrap();
+    assert_eq!(qos3.shared_subscription(), None);
+
+    // The group cannot be changed after creation
+    assert!(matches!(
+      qos.updated_by(&QosPolicies::qos_none()),
+      Ok(ref q) if *q == qos
+    ));
+    assert!(matches!(
+      qos.updated_by(
+        &QosPolicyBuilder::new()
+          .shared_subscription(policy::SharedSubscription {
+            group: "others".to_string(),
+            distribution: policy::SampleDistribution::KeyAffinity,
+          })
+          .build()
+      ),
+      Err(QosError::ImmutablePolicy {
+        policy: QosPolicyId::SharedSubscription
+      })
+    ));
+  }
+
+  #[cfg(feature = "compression")]
+  #[test]
+  fn qos_compression_parameter_list() {
+    let qos = QosPolicyBuilder::new()
+      .compression(policy::Compression {
+        algorithm: policy::CompressionAlgorithm::Zstd,
+        threshold: 1024,
+      })
+      .build();
+
+    let pl = qos
+      .to_parameter_list(speedy::Endianness::LittleEndian)
+      .unwrap();
+    assert_eq!(
+      pl,
+      vec![Parameter::new(
+        ParameterId::PID_RUSTDDS_COMPRESSION,
+        vec![1, 0, 0, 0, 0, 4, 0, 0]
+      )]
+    );
+
+    let mut pl_map = BTreeMap::new();
+    pl_map.insert(pl[0].parameter_id, vec![&pl[0]]);
+    let qos2 = QosPolicies::from_parameter_list(speedy::Endianness::LittleEndian, &pl_map).unwrap();
+    assert_eq!(qos, qos2);
+
+    // Unknown algorithm
+    let other = Parameter::new(
+      ParameterId::PID_RUSTDDS_COMPRESSION,
+      vec![9, 0, 0, 0, 0, 0, 0, 0],
+    );
+    pl_map.insert(other.parameter_id, vec![&other]);
+    let qos3 = QosPolicies::from_parameter_list(speedy::Endianness::LittleEndian, &pl_map).unwrap();
+    assert_eq!(qos3.compression(), None);
+  }
+
+  #[test]
+  fn qos_serde_round_trip() {
+    let qos = QosPolicyBuilder::new()
+      .reliable(Duration::from_millis(100))
+      .history(policy::History::KeepLast { depth: 10 })
+      .ownership(policy::Ownership::Exclusive { strength: 3 })
+      .user_data(policy::UserData {
+        value: vec![1, 2, 3],
+      })
+      .shared_subscription(policy::SharedSubscription {
+        group: "workers".to_string(),
+        distribution: policy::SampleDistribution::KeyAffinity,
+      })
+      .build();
+    let json = serde_json::to_string(&qos).unwrap();
+    let qos2: QosPolicies = serde_json::from_str(&json).unwrap();
+    assert_eq!(qos, qos2);
+  }
+
+  #[test]
+  fn qos_diff_and_defaults() {
+    let defaults = QosPolicyBuilder::new()
+      .reliable(Duration::from_millis(100))
+      .durability(policy::Durability::Volatile)
+      .build();
+    let specific = QosPolicyBuilder::new()
+      .durability(policy::Durability::TransientLocal)
+      .deadline(policy::Deadline(Duration::from_secs(1)))
+      .build();
+
+    let merged = specific.with_defaults(&defaults);
+    assert_eq!(merged, defaults.modify_by(&specific));
+    assert_eq!(merged.reliability, defaults.reliability);
+    assert_eq!(merged.durability, specific.durability);
+
+    assert!(merged.diff(&merged).is_empty());
+    assert_eq!(
+      defaults.diff(&merged),
+      BTreeSet::from([QosPolicyId::Durability, QosPolicyId::Deadline])
+    );
+    assert_eq!(defaults.diff(&merged), merged.diff(&defaults));
+  }
+}
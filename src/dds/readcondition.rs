@@ -18,6 +18,19 @@ pub struct ReadCondition {
 }
 
 impl ReadCondition {
+  /// Condition reads samples whose states are included in the given masks
+  pub fn new(
+    sample_state_mask: BitFlags<SampleState>,
+    view_state_mask: BitFlags<ViewState>,
+    instance_state_mask: BitFlags<InstanceState>,
+  ) -> Self {
+    Self {
+      sample_state_mask,
+      view_state_mask,
+      instance_state_mask,
+    }
+  }
+
   /// Condition reads all available samples
   pub fn any() -> Self {
     Self {
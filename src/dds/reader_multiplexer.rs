@@ -0,0 +1,347 @@
+// Taking samples from any of the DataReaders of a Subscriber, see
+// Subscriber::take_any().
+//
+// The readers notify the Subscriber's ReadinessQueue through their
+// ChangeNotifiers, so take_any() only looks at readers that may have data.
+// The queue holds weak references: the application owns the DataReaders.
+
+use std::{
+  any::Any,
+  collections::{BTreeMap, VecDeque},
+  fmt,
+  sync::{Arc, Condvar, Mutex, MutexGuard, Weak},
+  time::{Duration, Instant},
+};
+
+use crate::{
+  dds::{
+    adapters, key::Keyed, no_key::datareader::DataReader as NoKeyDataReader, result::ReadResult,
+    topic::TopicDescription, with_key::datareader::DataReader as WithKeyDataReader,
+  },
+  structure::{entity::RTPSEntity, guid::GUID},
+};
+
+// A DataReader of any type
+pub(crate) trait TakeAny {
+  fn topic_name(&self) -> String;
+  fn take_any(&mut self) -> ReadResult<Option<Box<dyn Any>>>;
+}
+
+impl<D, DA> TakeAny for WithKeyDataReader<D, DA>
+where
+  D: Keyed + 'static,
+  D::K: 'static,
+  DA: adapters::with_key::DeserializerAdapter<D>,
+{
+  fn topic_name(&self) -> String {
+    self.topic().name()
+  }
+
+  fn take_any(&mut self) -> ReadResult<Option<Box<dyn Any>>> {
+    Ok(
+      self
+        .take_next_sample()?
+        .map(|sample| Box::new(sample) as Box<dyn Any>),
+    )
+  }
+}
+
+impl<D, DA> TakeAny for NoKeyDataReader<D, DA>
+where
+  D: 'static,
+  DA: adapters::no_key::DeserializerAdapter<D>,
+{
+  fn topic_name(&self) -> String {
+    self.topic().name()
+  }
+
+  fn take_any(&mut self) -> ReadResult<Option<Box<dyn Any>>> {
+    Ok(
+      self
+        .take_next_sample()?
+        .map(|sample| Box::new(sample) as Box<dyn Any>),
+    )
+  }
+}
+
+pub(crate) type AttachedReader = Weak<Mutex<dyn TakeAny + Send>>;
+
+#[derive(Default)]
+struct Readiness {
+  attached: BTreeMap<GUID, AttachedReader>,
+  // Attached readers that may have samples, in the order they got them. No
+  // duplicates.
+  ready: VecDeque<GUID>,
+}
+
+/// Readiness of the DataReaders of a Subscriber that are attached for
+/// [`Subscriber::take_any`](crate::Subscriber::take_any).
+#[derive(Default)]
+pub(crate) struct ReadinessQueue {
+  readiness: Mutex<Readiness>,
+  became_ready: Condvar,
+}
+
+impl ReadinessQueue {
+  fn lock(&self) -> MutexGuard<'_, Readiness> {
+    self.readiness.lock().unwrap_or_else(|e| e.into_inner())
+  }
+
+  pub fn attach(&self, guid: GUID, reader: AttachedReader) {
+    self.lock().attached.insert(guid, reader);
+    // There may already be samples waiting
+    self.ready(guid);
+  }
+
+  pub fn detach(&self, guid: GUID) {
+    let mut readiness = self.lock();
+    readiness.attached.remove(&guid);
+    readiness.ready.retain(|g| *g != guid);
+  }
+
+  // Called by the ChangeNotifier of the reader `guid` when it has new
+  // samples. This must not call into readers, because the caller holds the
+  // topic cache lock.
+  pub fn ready(&self, guid: GUID) {
+    let mut readiness = self.lock();
+    if readiness.attached.contains_key(&guid) && !readiness.ready.contains(&guid) {
+      readiness.ready.push_back(guid);
+      self.became_ready.notify_all();
+    }
+  }
+
+  pub fn wait(&self, timeout: Duration) -> Option<GUID> {
+    let deadline = Instant::now() + timeout;
+    let mut readiness = self.lock();
+    loop {
+      while let Some(guid) = readiness.ready.front().copied() {
+        if readiness
+          .attached
+          .get(&guid)
+          .is_some_and(|reader| reader.strong_count() > 0)
+        {
+          return Some(guid);
+        }
+        // The DataReader has been dropped
+        readiness.ready.pop_front();
+        readiness.attached.remove(&guid);
+      }
+      let remaining = deadline.saturating_duration_since(Instant::now());
+      if remaining.is_zero() {
+        return None;
+      }
+      readiness = self
+        .became_ready
+        .wait_timeout(readiness, remaining)
+        .unwrap_or_else(|e| e.into_inner())
+        .0;
+    }
+  }
+
+  pub fn take(&self) -> ReadResult<Option<AnySample>> {
+    loop {
+      // Do not hold our lock while taking, see ready().
+      let (reader_guid, reader) = {
+        let mut readiness = self.lock();
+        let Some(guid) = readiness.ready.pop_front() else {
+          return Ok(None);
+        };
+        match readiness.attached.get(&guid).and_then(Weak::upgrade) {
+          Some(reader) => (guid, reader),
+          None => {
+            // The DataReader has been dropped
+            readiness.attached.remove(&guid);
+            continue;
+          }
+        }
+      };
+      let mut reader = reader.lock().unwrap_or_else(|e| e.into_inner());
+      if let Some(sample) = reader.take_any()? {
+        // There may be more. Go to the back of the queue to let others have a
+        // turn.
+        self.ready(reader_guid);
+        return Ok(Some(AnySample {
+          reader_guid,
+          topic_name: reader.topic_name(),
+          sample,
+        }));
+      }
+    }
+  }
+}
+
+impl fmt::Debug for ReadinessQueue {
+  // Need manual implementation, because the readers cannot be Debug formatted.
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("ReadinessQueue")
+      .field("ready", &self.lock().ready)
+      .finish()
+  }
+}
+
+/// A sample taken with [`Subscriber::take_any`](crate::Subscriber::take_any).
+///
+/// The sample is a [`with_key::DataSample`](crate::with_key::DataSample) or a
+/// [`no_key::DataSample`](crate::no_key::DataSample), depending on the kind of
+/// the DataReader. Use [`topic_name`](Self::topic_name) or
+/// [`reader_guid`](Self::reader_guid) to see which one it is, and
+/// [`downcast`](Self::downcast) to get it.
+pub struct AnySample {
+  reader_guid: GUID,
+  topic_name: String,
+  sample: Box<dyn Any>,
+}
+
+impl AnySample {
+  /// GUID of the DataReader that the sample was taken from
+  pub fn reader_guid(&self) -> GUID {
+    self.reader_guid
+  }
+
+  /// Name of the Topic of the sample
+  pub fn topic_name(&self) -> &str {
+    &self.topic_name
+  }
+
+  /// Gives the sample as `S`, or gives back `self` if the sample is of some
+  /// other type.
+  pub fn downcast<S: 'static>(self) -> Result<S, Self> {
+    let Self {
+      reader_guid,
+      topic_name,
+      sample,
+    } = self;
+    sample.downcast::<S>().map(|s| *s).map_err(|sample| Self {
+      reader_guid,
+      topic_name,
+      sample,
+    })
+  }
+}
+
+impl fmt::Debug for AnySample {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("AnySample")
+      .field("reader_guid", &self.reader_guid)
+      .field("topic_name", &self.topic_name)
+      .finish_non_exhaustive()
+  }
+}
+
+// Attaches `reader` to `queue` until it is detached or dropped
+pub(crate) fn attach<R>(queue: &ReadinessQueue, reader: &Arc<Mutex<R>>)
+where
+  R: TakeAny + RTPSEntity + Send + 'static,
+{
+  let guid = reader.lock().unwrap_or_else(|e| e.into_inner()).guid();
+  let reader: Arc<Mutex<dyn TakeAny + Send>> = reader.clone();
+  queue.attach(guid, Arc::downgrade(&reader));
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use super::*;
+  use crate::{
+    dds::{no_key, participant::DomainParticipant, qos::QosPolicies, with_key},
+    structure::topic_kind::TopicKind,
+    test::random_data::RandomData,
+  };
+
+  #[test]
+  fn take_any_from_several_readers() {
+    let participant = DomainParticipant::new(0).unwrap();
+    let qos = QosPolicies::qos_none();
+    let publisher = participant.create_publisher(&qos).unwrap();
+    let subscriber = participant.create_subscriber(&qos).unwrap();
+    let keyed_topic = participant
+      .create_topic(
+        "take_any_keyed".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let no_key_topic = participant
+      .create_topic(
+        "take_any_no_key".to_string(),
+        "String".to_string(),
+        &qos,
+        TopicKind::NoKey,
+      )
+      .unwrap();
+
+    let keyed_writer = publisher
+      .create_datawriter_cdr::<RandomData>(&keyed_topic, None)
+      .unwrap();
+    let no_key_writer = publisher
+      .create_datawriter_no_key_cdr::<String>(&no_key_topic, None)
+      .unwrap();
+    let keyed_reader = Arc::new(Mutex::new(
+      subscriber
+        .create_datareader_cdr::<RandomData>(&keyed_topic, None)
+        .unwrap(),
+    ));
+    let no_key_reader = Arc::new(Mutex::new(
+      subscriber
+        .create_datareader_no_key_cdr::<String>(&no_key_topic, None)
+        .unwrap(),
+    ));
+    subscriber.attach_for_take_any(&keyed_reader);
+    subscriber.attach_no_key_for_take_any(&no_key_reader);
+    let keyed_guid = keyed_reader.lock().unwrap().guid();
+
+    assert!(subscriber.take_any().unwrap().is_none());
+    assert_eq!(subscriber.poll_any(Duration::from_millis(10)), None);
+
+    for a in 0..2 {
+      let data = RandomData {
+        a,
+        b: "keyed".to_string(),
+      };
+      keyed_writer.write(data, None).unwrap();
+    }
+    no_key_writer.write("no_key".to_string(), None).unwrap();
+
+    assert!(subscriber.poll_any(Duration::from_secs(5)).is_some());
+    let mut keyed = Vec::new();
+    let mut no_key = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while (keyed.len() < 2 || no_key.is_empty()) && Instant::now() < deadline {
+      subscriber.poll_any(Duration::from_millis(100));
+      while let Some(sample) = subscriber.take_any().unwrap() {
+        if sample.topic_name() == "take_any_keyed" {
+          assert_eq!(sample.reader_guid(), keyed_guid);
+          // Not a no_key sample
+          let sample = sample.downcast::<no_key::DataSample<String>>().unwrap_err();
+          let sample = sample
+            .downcast::<with_key::DataSample<RandomData>>()
+            .unwrap();
+          keyed.push(sample.into_value().value().unwrap().a);
+        } else {
+          let sample = sample.downcast::<no_key::DataSample<String>>().unwrap();
+          no_key.push(sample.into_value());
+        }
+      }
+    }
+    assert_eq!(keyed, vec![0, 1]);
+    assert_eq!(no_key, vec!["no_key".to_string()]);
+
+    // Dropped and detached readers are not taken from
+    drop(keyed_reader);
+    subscriber.detach_from_take_any(no_key_reader.lock().unwrap().guid());
+    keyed_writer
+      .write(
+        RandomData {
+          a: 2,
+          b: "keyed".to_string(),
+        },
+        None,
+      )
+      .unwrap();
+    no_key_writer.write("detached".to_string(), None).unwrap();
+    assert_eq!(subscriber.poll_any(Duration::from_millis(200)), None);
+    assert!(subscriber.take_any().unwrap().is_none());
+  }
+}
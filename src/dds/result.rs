@@ -8,6 +8,7 @@
 use std::sync::PoisonError;
 
 use crate::{
+  dds::qos::QosPolicyId,
   no_key::wrappers::NoKeyWrapper,
   serialization::{cdr_deserializer, cdr_serializer},
   TopicKind,
@@ -40,6 +41,15 @@ pub enum ReadError {
   #[error("Cannot communicate with background thread. It may have panicked. Details: {reason}")]
   Poisoned { reason: String },
 
+  /// The DataReader has not been enabled yet. See
+  /// [`EntityFactory`](crate::policy::EntityFactory).
+  #[error("DataReader is not enabled")]
+  NotEnabled,
+
+  /// The operation cannot be used with the QoS policies of the DataReader.
+  #[error("Not supported with the DataReader QoS: {reason}")]
+  UnsupportedQos { reason: String },
+
   /// Something that should not go wrong went wrong anyway.
   /// This is usually a bug in RustDDS
   #[error("Internal error: {reason}")]
@@ -101,7 +111,11 @@ pub type ReadResult<T> = std::result::Result<T, ReadError>;
 ///
 /// Note: This type contains payload data type `D`. This means that `WriteError`
 /// implements `Debug` only if `D` does.
+///
+/// New kinds of errors may be added in the future, so matching on this type
+/// needs a wildcard arm.
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum WriteError<D> {
   /// Data serializer (`SerializerAdapter`) reported an error when called.
   /// Reason field gives more details on what went wrong.
@@ -123,6 +137,21 @@ pub enum WriteError<D> {
   #[error("Write operation timed out while blocking")]
   WouldBlock { data: D },
 
+  /// The DataWriter has not been enabled yet. See
+  /// [`EntityFactory`](crate::policy::EntityFactory).
+  #[error("DataWriter is not enabled")]
+  NotEnabled { data: D },
+
+  /// The sample hook of the DataWriter refused the sample. See
+  /// [`DataWriter::set_sample_hook`](crate::with_key::DataWriter::set_sample_hook).
+  #[error("Sample vetoed: {reason}")]
+  Vetoed { reason: String, data: D },
+
+  /// The [`WriteOptions`](crate::WriteOptions) given are not valid, e.g. an
+  /// inline QoS parameter uses a reserved parameter id.
+  #[error("Bad parameter: {reason}")]
+  BadParameter { reason: String, data: D },
+
   /// Something that should not go wrong went wrong anyway.
   /// This is usually a bug in RustDDS
   #[error("Internal error: {reason}")]
@@ -159,6 +188,9 @@ impl<D> WriteError<D> {
       WriteError::Poisoned { reason, data: _ } => WriteError::Poisoned { reason, data: () },
       WriteError::Io(e) => WriteError::Io(e),
       WriteError::WouldBlock { data: _ } => WriteError::WouldBlock { data: () },
+      WriteError::NotEnabled { data: _ } => WriteError::NotEnabled { data: () },
+      WriteError::Vetoed { reason, data: _ } => WriteError::Vetoed { reason, data: () },
+      WriteError::BadParameter { reason, data: _ } => WriteError::BadParameter { reason, data: () },
       WriteError::Internal { reason } => WriteError::Internal { reason },
     }
   }
@@ -180,6 +212,15 @@ pub(crate) fn unwrap_no_key_write_error<D>(
       data: data.d,
     },
     WriteError::WouldBlock { data } => WriteError::WouldBlock { data: data.d },
+    WriteError::NotEnabled { data } => WriteError::NotEnabled { data: data.d },
+    WriteError::Vetoed { reason, data } => WriteError::Vetoed {
+      reason,
+      data: data.d,
+    },
+    WriteError::BadParameter { reason, data } => WriteError::BadParameter {
+      reason,
+      data: data.d,
+    },
     WriteError::Internal { reason } => WriteError::Internal { reason },
     WriteError::Io(io) => WriteError::Io(io),
   }
@@ -309,4 +350,15 @@ pub type WaitResult<T> = std::result::Result<T, WaitError>;
 pub enum QosError {
   #[error("Parameter value or combination of values was bad. Details: {details}")]
   BadParameter { details: String },
+
+  /// Attempted to change a policy that cannot be changed after the entity has
+  /// been created. This is IMMUTABLE_POLICY in the DDS specification.
+  ///
+  /// The policies that can be changed are Deadline, LatencyBudget,
+  /// TimeBasedFilter, Lifespan, WriterDataLifecycle, ReaderDataLifecycle,
+  /// UserData, TopicData, GroupData, HistoryReplay, EntityFactory,
+  /// InstanceLimit, Batching, AdaptiveTiming, Compression, PayloadChecksum, and
+  /// the strength of Ownership.
+  #[error("Policy {policy:?} cannot be changed after creation")]
+  ImmutablePolicy { policy: QosPolicyId },
 }
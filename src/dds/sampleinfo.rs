@@ -1,7 +1,12 @@
+use std::time::Instant;
+
 use enumflags2::{bitflags, BitFlags};
 
 use crate::{
-  dds::with_key::datawriter::WriteOptions,
+  dds::{
+    key::InstanceHandle,
+    with_key::datawriter::{InlineQosParameter, WriteOptions},
+  },
   structure::{guid::GUID, rpc::SampleIdentity, sequence_number::SequenceNumber, time::Timestamp},
 };
 
@@ -191,6 +196,9 @@ pub struct SampleInfo {
   /// disposed it, or there are no writers alive.
   pub(crate) instance_state: InstanceState,
 
+  /// Identifies the instance this sample belongs to.
+  pub(crate) instance_handle: InstanceHandle,
+
   /// For each instance the middleware internally maintains these counts
   /// relative to each DataReader. The counts capture snapshots of the
   /// corresponding counters at the time the sample was received.
@@ -227,6 +235,11 @@ pub struct SampleInfo {
   /// the instance (i.e. wrote this sample)
   pub(crate) publication_handle: GUID,
   pub(crate) sequence_number: SequenceNumber,
+
+  /// Time of reception as wall-clock time and monotonic time, taken at the
+  /// same moment
+  pub(crate) reception_timestamp: Timestamp,
+  pub(crate) reception_instant: Instant,
 }
 
 impl SampleInfo {
@@ -236,6 +249,20 @@ impl SampleInfo {
     self.write_options.source_timestamp()
   }
 
+  /// Wall-clock time when this sample was received. This is comparable to
+  /// [`source_timestamp`](Self::source_timestamp) for measuring latency,
+  /// provided that the clocks of the sender and receiver are synchronized.
+  pub fn reception_timestamp(&self) -> Timestamp {
+    self.reception_timestamp
+  }
+
+  /// Monotonic time when this sample was received. Use this for measuring
+  /// local durations, e.g. time from reception to processing, as it is not
+  /// affected by clock adjustments.
+  pub fn reception_instant(&self) -> Instant {
+    self.reception_instant
+  }
+
   pub fn sample_state(&self) -> SampleState {
     self.sample_state
   }
@@ -260,6 +287,10 @@ impl SampleInfo {
   //   self.instance_state = instance_state;
   // }
 
+  pub fn instance_handle(&self) -> InstanceHandle {
+    self.instance_handle
+  }
+
   pub fn disposed_generation_count(&self) -> i32 {
     self.generation_counts.disposed_generation_count
   }
@@ -294,6 +325,21 @@ impl SampleInfo {
     self.write_options.related_sample_identity()
   }
 
+  /// Inline QoS parameters received with the sample, except those that
+  /// RustDDS interprets itself (key hash, status info, related sample
+  /// identity, and the RustDDS vendor-specific parameters).
+  pub fn inline_qos_parameters(&self) -> &[InlineQosParameter] {
+    self.write_options.inline_qos_parameters()
+  }
+
+  /// The OpenTelemetry span context of the DataWriter at the time of
+  /// writing, if it was propagated. This is a remote span context, to be used
+  /// as the parent of spans that process the sample.
+  #[cfg(feature = "opentelemetry")]
+  pub fn trace_context(&self) -> Option<opentelemetry::trace::SpanContext> {
+    self.write_options.trace_context()
+  }
+
   pub fn sample_identity(&self) -> SampleIdentity {
     SampleIdentity {
       writer_guid: self.publication_handle,
@@ -11,6 +11,8 @@ use std::{
   sync::{Arc, Mutex},
   task::{Context, Poll, Waker},
 };
+#[cfg(not(target_os = "windows"))]
+use std::os::fd::{AsRawFd, RawFd};
 
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
@@ -19,13 +21,18 @@ use mio_06::Evented;
 use mio_extras::channel as mio_channel;
 use mio_08::{self, event, Interest, Registry, Token};
 use chrono::Utc;
+use serde::Serialize;
 
 use crate::{
-  dds::{qos::QosPolicyId, topic::TopicData},
-  discovery::SpdpDiscoveredParticipantData,
+  dds::{
+    key::InstanceHandle,
+    qos::{policy, QosPolicyId},
+    topic::TopicData,
+  },
+  discovery::{DiscoveredReaderData, DiscoveredWriterData, SpdpDiscoveredParticipantData},
   messages::{protocol_version::ProtocolVersion, vendor_id::VendorId},
   mio_source::*,
-  structure::guid::GuidPrefix,
+  structure::{guid::GuidPrefix, locator::Locator},
   Duration, QosPolicies, GUID,
 };
 #[cfg(feature = "security")]
@@ -65,6 +72,13 @@ impl<E> StatusReceiver<E> {
       enabled: false,
     }
   }
+
+  // Like as_status_source(), but for polling outside mio
+  #[cfg(not(target_os = "windows"))]
+  pub fn as_status_raw_fd(&mut self) -> RawFd {
+    self.enabled = true;
+    self.channel_receiver.as_raw_fd()
+  }
 }
 
 impl<'a, E> StatusEvented<'a, E, StatusReceiverStream<'a, E>> for StatusReceiver<E> {
@@ -199,6 +213,16 @@ impl<'a, E> StatusEvented<'a, E, StatusReceiverStream<'a, E>> for StatusChannelR
   }
 }
 
+/// The file descriptor is readable when there may be status events to
+/// receive. It stays readable until [`try_recv`](Self::try_recv) is called,
+/// so call it until it returns an error before polling again.
+#[cfg(not(target_os = "windows"))]
+impl<T> AsRawFd for StatusChannelReceiver<T> {
+  fn as_raw_fd(&self) -> RawFd {
+    self.signal_receiver.as_raw_fd()
+  }
+}
+
 impl<T> event::Source for StatusChannelReceiver<T> {
   fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
     self.signal_receiver.register(registry, token, interests)
@@ -263,12 +287,17 @@ impl<'a, T> FusedStream for StatusReceiverStream<'a, T> {
 // -------------------------------------------------------------------------------
 // -------------------------------------------------------------------------------
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub enum DomainParticipantStatusEvent {
   ParticipantDiscovered {
     dpd: ParticipantDescription,
   },
+  /// Discovery data of an already known participant has changed, e.g. it has
+  /// set new USER_DATA.
+  ParticipantUpdated {
+    dpd: ParticipantDescription,
+  },
   ParticipantLost {
     id: GuidPrefix,
     reason: LostReason,
@@ -288,6 +317,25 @@ pub enum DomainParticipantStatusEvent {
   TopicLost {
     name: String,
   },
+  /// Another participant announces the same GUID prefix as this
+  /// participant, e.g. because of a cloned process or VM image. Messages meant
+  /// for either participant may be delivered to the wrong one, so
+  /// communication is unreliable until one of them is recreated. A new
+  /// DomainParticipant gets a new random GUID prefix.
+  ///
+  /// The discovery data of the other participant is ignored. This is reported
+  /// once for each other participant, identified by its unicast locators.
+  GuidPrefixCollision {
+    remote: ParticipantDescription,
+    remote_locators: Vec<Locator>,
+  },
+  /// The endpoint authorizer of the
+  /// [`DiscoveryFilter`](crate::DiscoveryFilter) vetoed a remote Reader or
+  /// Writer, so it is not matched.
+  RemoteEndpointRejected {
+    endpoint: EndpointDescription,
+    reason: String,
+  },
   /// New Reader detected (or created locally). Detection happens regardless of
   /// the remote being matched or not by a local Endpoint.
   ReaderDetected {
@@ -352,7 +400,7 @@ pub enum DomainParticipantStatusEvent {
 }
 
 /// Why some remote entity is considered to be no longer with us.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum LostReason {
   /// Participant announced via Discovery that it is leaving
   Disposed,
@@ -366,7 +414,7 @@ pub enum LostReason {
 /// This is a rewrite/summary of SpdpDiscoveredParticipantData from discovery.
 /// The original is not used to avoid circular dependency between participant
 /// and discovery. Some of the more technical details have been left out
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ParticipantDescription {
   pub updated_time: chrono::DateTime<Utc>,
   pub protocol_version: ProtocolVersion,
@@ -374,8 +422,22 @@ pub struct ParticipantDescription {
   pub guid: GUID,
   pub lease_duration: Option<Duration>,
   pub entity_name: Option<String>,
+  /// USER_DATA announced by the participant
+  pub user_data: Option<policy::UserData>,
   #[cfg(feature = "security")]
   pub supports_security: bool,
+  /// Properties announced by the participant. These are available only with
+  /// feature "security".
+  #[cfg(feature = "security")]
+  #[serde(skip)]
+  pub property: Option<policy::Property>,
+}
+
+impl ParticipantDescription {
+  /// Instance handle of the participant, derived from its GUID
+  pub fn instance_handle(&self) -> InstanceHandle {
+    InstanceHandle::from(self.guid)
+  }
 }
 
 impl From<&SpdpDiscoveredParticipantData> for ParticipantDescription {
@@ -387,8 +449,11 @@ impl From<&SpdpDiscoveredParticipantData> for ParticipantDescription {
       guid: dpd.participant_guid,
       lease_duration: dpd.lease_duration,
       entity_name: dpd.entity_name.clone(),
+      user_data: dpd.user_data.clone(),
       #[cfg(feature = "security")]
       supports_security: dpd.supports_security(),
+      #[cfg(feature = "security")]
+      property: dpd.property.clone(),
     }
   }
 }
@@ -396,7 +461,7 @@ impl From<&SpdpDiscoveredParticipantData> for ParticipantDescription {
 /// This is a summary of SubscriptionBuiltinTopicData /
 /// PublicationBuiltinTopicData from discovery. The original is not used to
 /// avoid circular dependency between participant and discovery.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct EndpointDescription {
   pub updated_time: chrono::DateTime<Utc>,
   pub guid: GUID,
@@ -405,6 +470,45 @@ pub struct EndpointDescription {
   pub qos: QosPolicies,
 }
 
+impl EndpointDescription {
+  /// Instance handle of the endpoint, derived from its GUID
+  pub fn instance_handle(&self) -> InstanceHandle {
+    InstanceHandle::from(self.guid)
+  }
+}
+
+impl From<&DiscoveredReaderData> for EndpointDescription {
+  fn from(drd: &DiscoveredReaderData) -> Self {
+    EndpointDescription {
+      updated_time: Utc::now(),
+      guid: drd.reader_proxy.remote_reader_guid,
+      topic_name: drd.subscription_topic_data.topic_name().clone(),
+      type_name: drd.subscription_topic_data.type_name().clone(),
+      qos: drd.subscription_topic_data.qos(),
+    }
+  }
+}
+
+impl From<&DiscoveredWriterData> for EndpointDescription {
+  fn from(dwd: &DiscoveredWriterData) -> Self {
+    EndpointDescription {
+      updated_time: Utc::now(),
+      guid: dwd.writer_proxy.remote_writer_guid,
+      topic_name: dwd.publication_topic_data.topic_name.clone(),
+      type_name: dwd.publication_topic_data.type_name.clone(),
+      qos: dwd.publication_topic_data.qos(),
+    }
+  }
+}
+
+/// Status of a [`Topic`](crate::Topic)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopicStatus {
+  /// A remote DataReader or DataWriter has announced a Topic with the same
+  /// name, but another data type.
+  InconsistentTopic { count: CountWithChange },
+}
+
 #[derive(Debug, Clone)]
 pub enum DataReaderStatus {
   /// Sample was rejected, because resource limits would have been exceeded.
@@ -438,19 +542,25 @@ pub enum DataReaderStatus {
   // DataAvailable variant is not implemented, as it seems to bring little additional value,
   // because the normal data waiting mechanism already uses the same mio::poll structure.
   /// A sample has been lost (never received).
-  /// TODO: Implement this.
-  /// * Check that the following interpretation is correct:
   /// * For a BEST_EFFORT reader: Whenever we skip ahead in SequenceNumber,
   ///   possibly because a message is lost, or messages arrive out of order.
-  /// * For a RELIABLE reader: Whenever we skip ahead in SequenceNumbers that
-  ///   are delivered via DataReader. The reason may be that we receive a
-  ///   HEARTBEAT or GAP submessage indicating that some samples we are
-  ///   expecting are not available.
+  /// * For a RELIABLE reader: Whenever we receive a HEARTBEAT or GAP submessage
+  ///   indicating that some samples we are still expecting are not available,
+  ///   and never will be.
+  ///
+  /// Samples published before the remote Writer was matched are not counted as
+  /// lost.
   SampleLost { count: CountWithChange },
 
   /// The DataReader has found a DataWriter that matches the Topic and has
   /// compatible QoS, or has ceased to be matched with a DataWriter that was
   /// previously considered to be matched.
+  ///
+  /// A Reliable DataReader may match a DataWriter that does not offer
+  /// Reliable, e.g. one that specifies no Reliability policy. Then samples
+  /// from that DataWriter are received BestEffort. The effective reliability
+  /// of each match is in
+  /// [`WriterProxyStatistics`](crate::WriterProxyStatistics).
   SubscriptionMatched {
     total: CountWithChange,
     current: CountWithChange,
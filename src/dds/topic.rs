@@ -1,13 +1,24 @@
-use std::{fmt::Debug, sync::Arc};
+use std::{
+  fmt::Debug,
+  sync::{
+    atomic::{AtomicI32, Ordering},
+    Arc, Mutex, RwLock,
+  },
+};
+
+use serde::Serialize;
 
 use crate::{
   dds::{
     dds_entity::DDSEntity,
     participant::{DomainParticipant, DomainParticipantWeak},
-    qos::{HasQoSPolicy, QosPolicies},
+    qos::{HasQoSPolicy, MutQosPolicy, QosPolicies},
+    result::QosError,
+    statusevents::{CountWithChange, TopicStatus},
     typedesc::TypeDesc,
   },
   discovery::sedp_messages::TopicBuiltinTopicData,
+  structure::{entity::RTPSEntity, guid::GUID},
 };
 pub use crate::structure::topic_kind::TopicKind;
 
@@ -24,7 +35,7 @@ pub trait TopicDescription {
 /// This is a more usable version of TopicBuiltinTopicData from Discovery.
 ///
 /// It is used for describing discovered topics.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct TopicData {
   pub name: String,
   pub type_name: String,
@@ -65,26 +76,28 @@ impl From<&TopicBuiltinTopicData> for TopicData {
 /// ```
 #[derive(Clone)]
 pub struct Topic {
-  // TODO: Do we really need set_qos operation?
-  // Maybe not. Let's make Topic immutable.
   inner: Arc<InnerTopic>,
 }
 
 impl Topic {
   pub(crate) fn new(
     my_domain_participant: &DomainParticipantWeak,
+    my_guid: GUID,
     my_name: String,
     my_typedesc: TypeDesc,
     my_qos_policies: &QosPolicies,
     topic_kind: TopicKind,
+    inconsistent_topic_counter: Arc<AtomicI32>,
   ) -> Self {
     Self {
       inner: Arc::new(InnerTopic::new(
         my_domain_participant,
+        my_guid,
         my_name,
         my_typedesc,
         my_qos_policies,
         topic_kind,
+        inconsistent_topic_counter,
       )),
     }
   }
@@ -121,14 +134,32 @@ impl Topic {
   pub fn kind(&self) -> TopicKind {
     self.inner.kind()
   }
-  /*
-  // DDS spec 2.2.2.3.2 Topic Class
-  // specifies only method get_inconsistent_topic_status
-  // TODO: implement
-  pub(crate) fn get_inconsistent_topic_status() -> Result<InconsistentTopicStatus> {
-    unimplemented!()
+
+  /// Gets the INCONSISTENT_TOPIC status (DDS spec 2.2.2.3.2): how many times
+  /// a remote DataReader or DataWriter has announced a Topic with this name,
+  /// but another type. Definitions already known when this Topic was created
+  /// are included. Such endpoints never match with ours.
+  ///
+  /// The change count is relative to the previous call of this method or
+  /// [`try_recv_status`](Self::try_recv_status) on this Topic or its clones.
+  pub fn inconsistent_topic_status(&self) -> CountWithChange {
+    self.inner.inconsistent_topic_status()
+  }
+
+  /// Returns the changed status of this Topic, if it has changed since it was
+  /// last read.
+  ///
+  /// Topic status can also be observed as
+  /// [`DomainParticipantStatusEvent::InconsistentTopic`](crate::dds::statusevents::DomainParticipantStatusEvent::InconsistentTopic)
+  /// events from the DomainParticipant.
+  pub fn try_recv_status(&self) -> Option<TopicStatus> {
+    let count = self.inconsistent_topic_status();
+    if count.count_change() > 0 {
+      Some(TopicStatus::InconsistentTopic { count })
+    } else {
+      None
+    }
   }
-  */
 }
 
 impl PartialEq for Topic {
@@ -162,23 +193,70 @@ impl TopicDescription for Topic {
   }
 }
 
+/// Topics are not RTPS Entities, but DDS gives them an identity, the
+/// BuiltinTopicKey. RustDDS uses a GUID with EntityKind
+/// [`TOPIC_VENDOR_SPECIFIC`](crate::structure::guid::EntityKind::TOPIC_VENDOR_SPECIFIC)
+/// for it. Each Topic created by
+/// [`create_topic`](crate::DomainParticipant::create_topic) or
+/// [`find_topic`](crate::DomainParticipant::find_topic) gets a new GUID, but
+/// clones of a Topic share it.
+impl RTPSEntity for Topic {
+  fn guid(&self) -> GUID {
+    self.inner.my_guid
+  }
+}
+
 impl HasQoSPolicy for Topic {
   fn qos(&self) -> QosPolicies {
     self.inner.qos()
   }
 }
 
+impl MutQosPolicy for Topic {
+  /// Change the QoS policies. Policies set in `new_qos` replace the current
+  /// ones, and the rest are kept. The change is visible through all clones of
+  /// this Topic.
+  ///
+  /// Changing a policy that cannot be changed after creation fails with
+  /// [`QosError::ImmutablePolicy`], which lists the changeable policies. If
+  /// there are local DataReaders or DataWriters on this Topic, the new QoS is
+  /// announced via Discovery.
+  ///
+  /// Topic QoS is not used in matching, so existing DataReaders and DataWriters
+  /// keep their own QoS.
+  fn set_qos(&mut self, new_qos: &QosPolicies) -> Result<(), QosError> {
+    let changed = {
+      let mut qos = self.inner.my_qos_policies.write().unwrap();
+      let updated_qos = qos.updated_by(new_qos)?;
+      let changed = updated_qos != *qos;
+      *qos = updated_qos;
+      changed
+    };
+    if changed {
+      if let Some(dp) = self.participant() {
+        dp.local_topic_qos_updated(self);
+      }
+    }
+    Ok(())
+  }
+}
+
 // impl DDSEntity for Topic {}
 
 // -------------------------------- InnerTopic -----------------------------
 
-#[derive(Clone)]
 pub struct InnerTopic {
   my_domain_participant: DomainParticipantWeak,
+  my_guid: GUID,
   my_name: String,
   my_typedesc: TypeDesc,
-  my_qos_policies: QosPolicies,
+  // Lock, because clones of the Topic share this, and set_qos can change it
+  my_qos_policies: RwLock<QosPolicies>,
   topic_kind: TopicKind, // WITH_KEY or NO_KEY
+  // Discovery counts inconsistent remote definitions of the topic name here.
+  // The other count is the one seen when the status was last read.
+  inconsistent_topic_counter: Arc<AtomicI32>,
+  inconsistent_topic_count_read: Mutex<i32>,
 }
 
 impl InnerTopic {
@@ -186,17 +264,22 @@ impl InnerTopic {
   // create new Topic objects from an application point of view.
   fn new(
     my_domain_participant: &DomainParticipantWeak,
+    my_guid: GUID,
     my_name: String,
     my_typedesc: TypeDesc,
     my_qos_policies: &QosPolicies,
     topic_kind: TopicKind,
+    inconsistent_topic_counter: Arc<AtomicI32>,
   ) -> Self {
     Self {
       my_domain_participant: my_domain_participant.clone(),
+      my_guid,
       my_name,
       my_typedesc,
-      my_qos_policies: my_qos_policies.clone(),
+      my_qos_policies: RwLock::new(my_qos_policies.clone()),
       topic_kind,
+      inconsistent_topic_counter,
+      inconsistent_topic_count_read: Mutex::new(0),
     }
   }
 
@@ -215,10 +298,14 @@ impl InnerTopic {
   pub fn kind(&self) -> TopicKind {
     self.topic_kind
   }
-  /*
-  pub(crate) fn get_inconsistent_topic_status() -> Result<TopicStatus> {
-    unimplemented!()
-  } */
+
+  fn inconsistent_topic_status(&self) -> CountWithChange {
+    let mut count_read = self.inconsistent_topic_count_read.lock().unwrap();
+    let count = self.inconsistent_topic_counter.load(Ordering::Relaxed);
+    let change = count - *count_read;
+    *count_read = count;
+    CountWithChange::new(count, change)
+  }
 }
 
 impl PartialEq for InnerTopic {
@@ -261,7 +348,7 @@ impl TopicDescription for InnerTopic {
 
 impl HasQoSPolicy for InnerTopic {
   fn qos(&self) -> QosPolicies {
-    self.my_qos_policies.clone()
+    self.my_qos_policies.read().unwrap().clone()
   }
 }
 
@@ -0,0 +1,147 @@
+// Propagation of OpenTelemetry trace context from DataWriters to DataReaders.
+//
+// The span context is sent as a W3C "traceparent" string, e.g.
+// "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01", in a
+// vendor-specific inline QoS parameter of each sample. "tracestate" is not
+// propagated. The Reader keeps the parameter only if the sender is RustDDS,
+// see rtps::compatibility, as other vendors may use the same parameter id.
+
+use opentelemetry::{
+  trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState},
+  Context,
+};
+
+use crate::{dds::with_key::datawriter::InlineQosParameter, structure::parameter_id::ParameterId};
+
+const PARAMETER_ID: u16 = ParameterId::PID_RUSTDDS_TRACE_CONTEXT.value();
+const TRACEPARENT_LEN: usize = 55;
+
+pub(crate) fn to_inline_qos(span_context: &SpanContext) -> InlineQosParameter {
+  InlineQosParameter {
+    parameter_id: PARAMETER_ID,
+    value: format!(
+      "00-{:032x}-{:016x}-{:02x}",
+      span_context.trace_id(),
+      span_context.span_id(),
+      span_context.trace_flags()
+    )
+    .into_bytes(),
+  }
+}
+
+pub(crate) fn is_present(parameters: &[InlineQosParameter]) -> bool {
+  parameters.iter().any(|p| p.parameter_id == PARAMETER_ID)
+}
+
+// Gives a remote span context, or None if there is no valid trace context.
+pub(crate) fn from_inline_qos(parameters: &[InlineQosParameter]) -> Option<SpanContext> {
+  let parameter = parameters.iter().find(|p| p.parameter_id == PARAMETER_ID)?;
+  // The received value may have alignment padding at the end.
+  let traceparent = std::str::from_utf8(parameter.value.get(..TRACEPARENT_LEN)?).ok()?;
+  let mut fields = traceparent.split('-');
+  let (version, trace_id, span_id, flags) = (
+    fields.next()?,
+    fields.next()?,
+    fields.next()?,
+    fields.next()?,
+  );
+  if version != "00" || trace_id.len() != 32 || span_id.len() != 16 || flags.len() != 2 {
+    return None;
+  }
+  let span_context = SpanContext::new(
+    TraceId::from_hex(trace_id).ok()?,
+    SpanId::from_hex(span_id).ok()?,
+    TraceFlags::new(u8::from_str_radix(flags, 16).ok()?),
+    true, // is_remote
+    TraceState::default(),
+  );
+  Some(span_context).filter(SpanContext::is_valid)
+}
+
+// The span context of the current OpenTelemetry Context, if it is valid
+pub(crate) fn current_span_context() -> Option<SpanContext> {
+  let context = Context::current();
+  let span_context = context.span().span_context().clone();
+  Some(span_context).filter(SpanContext::is_valid)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn trace_context_round_trip() {
+    let span_context = SpanContext::new(
+      TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+      SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+      TraceFlags::SAMPLED,
+      false,
+      TraceState::default(),
+    );
+    let mut parameter = to_inline_qos(&span_context);
+    assert_eq!(
+      parameter.value,
+      b"00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+    );
+
+    // As received, with padding to a multiple of 4 bytes
+    parameter.value.push(0);
+    let received = from_inline_qos(&[parameter]).unwrap();
+    assert_eq!(received.trace_id(), span_context.trace_id());
+    assert_eq!(received.span_id(), span_context.span_id());
+    assert!(received.is_sampled());
+    assert!(received.is_remote());
+
+    let invalid = InlineQosParameter {
+      parameter_id: PARAMETER_ID,
+      value: b"00-00000000000000000000000000000000-00f067aa0ba902b7-01\0".to_vec(),
+    };
+    assert!(from_inline_qos(&[invalid]).is_none());
+  }
+
+  #[test]
+  fn write_options_carry_current_trace_context() {
+    use crate::dds::with_key::datawriter::{WriteOptions, WriteOptionsBuilder};
+
+    let span_context = SpanContext::new(
+      TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap(),
+      SpanId::from_hex("b7ad6b7169203331").unwrap(),
+      TraceFlags::default(),
+      false,
+      TraceState::default(),
+    );
+
+    // Nothing to propagate
+    assert!(WriteOptions::default()
+      .with_current_trace_context()
+      .trace_context()
+      .is_none());
+
+    let _guard = Context::new()
+      .with_remote_span_context(span_context.clone())
+      .attach();
+    let current = WriteOptions::default()
+      .with_current_trace_context()
+      .trace_context()
+      .unwrap();
+    assert_eq!(current.span_id(), span_context.span_id());
+
+    // Explicit trace context overrides the current one
+    let explicit = SpanContext::new(
+      span_context.trace_id(),
+      SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+      TraceFlags::SAMPLED,
+      false,
+      TraceState::default(),
+    );
+    let options = WriteOptionsBuilder::new()
+      .trace_context(&explicit)
+      .build()
+      .with_current_trace_context();
+    assert_eq!(options.rustdds_parameters().len(), 1);
+    assert_eq!(
+      options.trace_context().unwrap().span_id(),
+      explicit.span_id()
+    );
+  }
+}
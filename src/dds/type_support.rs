@@ -0,0 +1,297 @@
+use std::{any::TypeId, fmt, marker::PhantomData};
+
+use crate::{
+  create_error_bad_parameter,
+  dds::{
+    adapters::{no_key, with_key},
+    no_key::{
+      datareader::DataReader as NoKeyDataReader, datawriter::DataWriter as NoKeyDataWriter,
+    },
+    participant::DomainParticipantWeak,
+    pubsub::{Publisher, Subscriber},
+    qos::QosPolicies,
+    result::{CreateError, CreateResult},
+    topic::{Topic, TopicDescription, TopicKind},
+    with_key::{
+      datareader::DataReader as WithKeyDataReader, datawriter::DataWriter as WithKeyDataWriter,
+    },
+  },
+  serialization::{CDRDeserializerAdapter, CDRSerializerAdapter},
+  Keyed,
+};
+
+/// A data type registered to a [`DomainParticipant`](crate::DomainParticipant)
+/// under a DDS type name, together with its serializer and deserializer
+/// adapters.
+///
+/// Registering the type once binds the type parameters, so that the same
+/// registration can create any number of Topics, DataWriters and
+/// DataReaders without repeating them. It also lets the application map type
+/// names of discovered Topics back to its Rust types, see
+/// [`DomainParticipant::type_support`](crate::DomainParticipant::type_support).
+///
+/// `SA` is used for DataWriters and `DA` for DataReaders.
+///
+/// # Examples
+///
+/// ```
+/// # use serde::{Serialize, Deserialize};
+/// # use rustdds::*;
+/// #
+/// #[derive(Serialize, Deserialize)]
+/// struct Temperature {
+///   celsius: f32,
+/// }
+///
+/// let domain_participant = DomainParticipant::new(0).unwrap();
+/// let qos = QosPolicyBuilder::new().build();
+/// let temperature = domain_participant
+///   .register_type_cdr::<Temperature>("Temperature")
+///   .unwrap();
+///
+/// let publisher = domain_participant.create_publisher(&qos).unwrap();
+/// for room in ["kitchen", "sauna"] {
+///   let topic = temperature
+///     .create_topic(format!("{room}_temperature"), &qos, TopicKind::NoKey)
+///     .unwrap();
+///   let writer = temperature
+///     .create_datawriter_no_key(&publisher, &topic, None)
+///     .unwrap();
+/// }
+/// ```
+pub struct TypeSupport<D, SA, DA> {
+  type_name: String,
+  participant: DomainParticipantWeak,
+  data_phantom: PhantomData<D>,
+  ser_phantom: PhantomData<SA>,
+  deser_phantom: PhantomData<DA>,
+}
+
+/// [`TypeSupport`] with CDR serialization
+pub type CdrTypeSupport<D> = TypeSupport<D, CDRSerializerAdapter<D>, CDRDeserializerAdapter<D>>;
+
+impl<D, SA, DA> TypeSupport<D, SA, DA>
+where
+  D: 'static,
+  SA: 'static,
+  DA: 'static,
+{
+  pub(crate) fn new(type_name: String, participant: DomainParticipantWeak) -> Self {
+    Self {
+      type_name,
+      participant,
+      data_phantom: PhantomData,
+      ser_phantom: PhantomData,
+      deser_phantom: PhantomData,
+    }
+  }
+
+  pub(crate) fn registration(type_name: &str) -> RegisteredType {
+    RegisteredType {
+      type_name: type_name.to_string(),
+      rust_type_name: std::any::type_name::<D>(),
+      type_id: TypeId::of::<Self>(),
+    }
+  }
+
+  /// The DDS type name this type is registered with
+  pub fn type_name(&self) -> &str {
+    &self.type_name
+  }
+
+  /// Creates a Topic of this type. See
+  /// [`DomainParticipant::create_topic`](crate::DomainParticipant::create_topic).
+  pub fn create_topic(
+    &self,
+    name: String,
+    qos: &QosPolicies,
+    topic_kind: TopicKind,
+  ) -> CreateResult<Topic> {
+    self
+      .participant
+      .create_topic(name, self.type_name.clone(), qos, topic_kind)
+  }
+
+  pub fn create_datawriter(
+    &self,
+    publisher: &Publisher,
+    topic: &Topic,
+    qos: Option<QosPolicies>,
+  ) -> CreateResult<WithKeyDataWriter<D, SA>>
+  where
+    D: Keyed,
+    SA: with_key::SerializerAdapter<D>,
+  {
+    self.check_topic_type(topic)?;
+    publisher.create_datawriter::<D, SA>(topic, qos)
+  }
+
+  pub fn create_datawriter_no_key(
+    &self,
+    publisher: &Publisher,
+    topic: &Topic,
+    qos: Option<QosPolicies>,
+  ) -> CreateResult<NoKeyDataWriter<D, SA>>
+  where
+    SA: no_key::SerializerAdapter<D>,
+  {
+    self.check_topic_type(topic)?;
+    publisher.create_datawriter_no_key::<D, SA>(topic, qos)
+  }
+
+  pub fn create_datareader(
+    &self,
+    subscriber: &Subscriber,
+    topic: &Topic,
+    qos: Option<QosPolicies>,
+  ) -> CreateResult<WithKeyDataReader<D, DA>>
+  where
+    D: Keyed,
+    DA: with_key::DeserializerAdapter<D>,
+  {
+    self.check_topic_type(topic)?;
+    subscriber.create_datareader::<D, DA>(topic, qos)
+  }
+
+  pub fn create_datareader_no_key(
+    &self,
+    subscriber: &Subscriber,
+    topic: &Topic,
+    qos: Option<QosPolicies>,
+  ) -> CreateResult<NoKeyDataReader<D, DA>>
+  where
+    DA: no_key::DeserializerAdapter<D>,
+  {
+    self.check_topic_type(topic)?;
+    subscriber.create_datareader_no_key::<D, DA>(topic, qos)
+  }
+
+  fn check_topic_type(&self, topic: &Topic) -> CreateResult<()> {
+    let topic_type = topic.get_type();
+    if topic_type.name() == self.type_name {
+      Ok(())
+    } else {
+      create_error_bad_parameter!(
+        "Topic {} has type {}, but this is type support for {}",
+        topic.name(),
+        topic_type.name(),
+        self.type_name
+      )
+    }
+  }
+}
+
+impl<D, SA, DA> Clone for TypeSupport<D, SA, DA> {
+  fn clone(&self) -> Self {
+    Self {
+      type_name: self.type_name.clone(),
+      participant: self.participant.clone(),
+      data_phantom: PhantomData,
+      ser_phantom: PhantomData,
+      deser_phantom: PhantomData,
+    }
+  }
+}
+
+impl<D, SA, DA> fmt::Debug for TypeSupport<D, SA, DA> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("TypeSupport")
+      .field("type_name", &self.type_name)
+      .field("rust_type", &std::any::type_name::<D>())
+      .finish()
+  }
+}
+
+/// A type registration of a DomainParticipant, as listed by
+/// [`DomainParticipant::registered_types`](crate::DomainParticipant::registered_types).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegisteredType {
+  /// DDS type name
+  pub type_name: String,
+  /// Name of the Rust data type, for diagnostics only
+  pub rust_type_name: &'static str,
+  // Identifies the data type together with its adapters
+  pub(crate) type_id: TypeId,
+}
+
+#[cfg(test)]
+mod tests {
+  use serde::{Deserialize, Serialize};
+
+  use super::*;
+  use crate::{DomainParticipant, QosPolicyBuilder};
+
+  #[derive(Serialize, Deserialize, Debug, Clone)]
+  struct Reading {
+    id: i32,
+    value: f64,
+  }
+
+  impl Keyed for Reading {
+    type K = i32;
+    fn key(&self) -> i32 {
+      self.id
+    }
+  }
+
+  #[test]
+  fn one_registration_serves_many_topics() {
+    let participant = DomainParticipant::new(0).unwrap();
+    let qos = QosPolicyBuilder::new().build();
+    let readings = participant.register_type_cdr::<Reading>("Reading").unwrap();
+    let publisher = participant.create_publisher(&qos).unwrap();
+    let subscriber = participant.create_subscriber(&qos).unwrap();
+
+    for topic_name in ["ts_test_a", "ts_test_b"] {
+      let topic = readings
+        .create_topic(topic_name.to_string(), &qos, TopicKind::WithKey)
+        .unwrap();
+      assert_eq!(topic.get_type().name(), "Reading");
+      readings
+        .create_datawriter(&publisher, &topic, None)
+        .unwrap();
+      readings
+        .create_datareader(&subscriber, &topic, None)
+        .unwrap();
+    }
+
+    // Registering again is fine, but not with another type.
+    assert!(participant.register_type_cdr::<Reading>("Reading").is_ok());
+    assert!(matches!(
+      participant.register_type_cdr::<String>("Reading"),
+      Err(CreateError::BadParameter { .. })
+    ));
+    assert_eq!(participant.registered_types().len(), 1);
+
+    // Discovered type names map back to the registration of the right type
+    assert!(participant
+      .type_support::<Reading, CDRSerializerAdapter<Reading>, CDRDeserializerAdapter<Reading>>(
+        "Reading"
+      )
+      .is_some());
+    assert!(participant
+      .type_support::<String, CDRSerializerAdapter<String>, CDRDeserializerAdapter<String>>(
+        "Reading"
+      )
+      .is_none());
+    assert!(participant
+      .type_support::<Reading, CDRSerializerAdapter<Reading>, CDRDeserializerAdapter<Reading>>(
+        "Other"
+      )
+      .is_none());
+
+    // Endpoints cannot be created on a Topic of another type.
+    let other_topic = participant
+      .create_topic(
+        "ts_test_other".to_string(),
+        "Other".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    assert!(matches!(
+      readings.create_datawriter(&publisher, &other_topic, None),
+      Err(CreateError::BadParameter { .. })
+    ));
+  }
+}
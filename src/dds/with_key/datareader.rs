@@ -1,28 +1,41 @@
 use std::{
+  collections::HashMap,
   io,
   pin::Pin,
   sync::{Arc, Mutex},
-  task::{Context, Poll},
+  task::{Context, Poll, Wake, Waker},
+  thread::{self, Thread},
+  time::Instant,
 };
+#[cfg(not(target_os = "windows"))]
+use std::os::fd::{AsRawFd, RawFd};
 
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
+use serde::de::DeserializeSeed;
 use mio_06::{self, Evented};
 use mio_08;
-use futures::stream::{FusedStream, Stream};
+use futures::{
+  future,
+  stream::{FusedStream, Stream},
+};
 
 use super::datasample_cache::DataSampleCache;
 use crate::{
   dds::{
     adapters::with_key::*,
+    fragment_stream::FragmentStream,
     key::*,
     qos::*,
     readcondition::*,
-    result::ReadResult,
+    result::{CreateResult, QosError, ReadResult},
+    sampleinfo::{InstanceState, SampleInfo},
     statusevents::*,
+    topic::{Topic, TopicDescription},
     with_key::{datasample::*, simpledatareader::*},
   },
   discovery::sedp_messages::PublicationBuiltinTopicData,
+  rtps::rtps_writer_proxy::WriterProxyStatistics,
   serialization::CDRDeserializerAdapter,
   structure::{duration::Duration, entity::RTPSEntity, guid::GUID, time::Timestamp},
 };
@@ -30,6 +43,10 @@ use crate::{
 /// Simplified type for CDR encoding
 pub type DataReaderCdr<D> = DataReader<D, CDRDeserializerAdapter<D>>;
 
+/// A sample with the source timestamp it was written at, see
+/// [`DataReader::read_as_of`].
+pub type TimestampedSample<D> = (Timestamp, Sample<D, <D as Keyed>::K>);
+
 /// Parameter for reading [Readers](../struct.With_Key_DataReader.html) data
 /// with key or with next from current key.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -73,6 +90,7 @@ pub enum SelectByKey {
 pub struct DataReader<D: Keyed, DA: DeserializerAdapter<D> = CDRDeserializerAdapter<D>> {
   simple_data_reader: SimpleDataReader<D, DA>,
   datasample_cache: DataSampleCache<D>, // DataReader-local cache of deserialized samples
+  sample_rejected_count: i32,
 }
 
 impl<D: 'static, DA> DataReader<D, DA>
@@ -86,21 +104,61 @@ where
     Self {
       simple_data_reader,
       datasample_cache: dsc,
+      sample_rejected_count: 0,
     }
   }
 
+  pub(crate) fn topic(&self) -> &Topic {
+    self.simple_data_reader.topic()
+  }
+
   // Gets all unseen cache_changes from the TopicCache. Deserializes
   // the serialized payload and stores the DataSamples (the actual data and the
   // samplestate) to local container, datasample_cache.
+  // Samples that would exceed ResourceLimits are rejected and reported via
+  // DataReaderStatus::SampleRejected.
+  // Instances whose ReaderDataLifecycle autopurge delay has expired are
+  // removed here, so the application never sees them after the deadline.
+  // Instances evicted due to InstanceLimit are forgotten here as well.
+  // If more instances are due later, the RTPS Reader is asked to notify us
+  // then, so that the purge happens even if no new samples arrive.
   fn fill_and_lock_local_datasample_cache(&mut self) -> ReadResult<()> {
-    while let Some(dcc) = self.simple_data_reader.try_take_one()? {
+    for dcc in self.simple_data_reader.try_take_all()? {
+      self.add_to_local_datasample_cache(dcc);
+    }
+    let now = Instant::now();
+    let mut purged = self.datasample_cache.purge_not_alive_instances(now);
+    purged.extend(self.datasample_cache.take_evicted_instances());
+    self.simple_data_reader.forget_instances(&purged);
+
+    if let Some(next) = self.datasample_cache.next_autopurge() {
       self
-        .datasample_cache
-        .fill_from_deserialized_cache_change(dcc);
+        .simple_data_reader
+        .wake_up_after(next.saturating_duration_since(now));
     }
     Ok(())
   }
 
+  fn add_to_local_datasample_cache(&mut self, dcc: DeserializedCacheChange<D>) {
+    if let Err(reason) = self
+      .datasample_cache
+      .fill_from_deserialized_cache_change(dcc)
+    {
+      self.sample_rejected_count += 1;
+      debug!(
+        "Sample rejected: {:?} topic={:?}",
+        reason,
+        self.simple_data_reader.topic().name()
+      );
+      self
+        .simple_data_reader
+        .send_status_change(DataReaderStatus::SampleRejected {
+          count: CountWithChange::new(self.sample_rejected_count, 1),
+          last_reason: reason,
+        });
+    }
+  }
+
   fn drain_read_notifications(&self) {
     self.simple_data_reader.drain_read_notifications();
   }
@@ -130,6 +188,11 @@ where
   /// Reads amount of samples found with `max_samples` and `read_condition`
   /// parameters.
   ///
+  /// Reading does not remove the samples from this DataReader. They are
+  /// marked [`SampleState::Read`](crate::SampleState::Read) and their
+  /// instances viewed, which is remembered over later read and take calls. Use
+  /// [`ReadCondition::not_read`] to get only samples not seen before.
+  ///
   /// # Arguments
   ///
   /// * `max_samples` - Limits maximum amount of samples read
@@ -250,6 +313,9 @@ where
 
   /// Reads next unread sample
   ///
+  /// The sample is marked read, but it stays available for later `read` and
+  /// `take` calls.
+  ///
   /// # Examples
   ///
   /// ```
@@ -705,6 +771,316 @@ where
     Ok(result)
   }
 
+  /// Returns the current state of the Topic as seen by this DataReader: the
+  /// latest data sample of each instance, indexed by key.
+  ///
+  /// Only instances whose latest data sample matches `read_condition` are
+  /// included. Use `ReadCondition::any()` to get all instances, including
+  /// disposed ones, or `ReadCondition::new(SampleState::any(),
+  /// ViewState::any(), InstanceState::Alive.into())` to get only live
+  /// instances. The view and instance states are reported in the `SampleInfo`
+  /// as usual.
+  ///
+  /// Unlike `read`, this does not mark samples read or instances viewed, so it
+  /// does not interfere with other read or take calls. Instances that have
+  /// been disposed and have no data samples left in the cache are not
+  /// included.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::*;
+  /// # use rustdds::with_key::DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType { a: i32 }
+  /// # impl Keyed for SomeType {
+  /// #   type K = i32;
+  /// #
+  /// #   fn key(&self) -> Self::K {
+  /// #     self.a
+  /// #   }
+  /// # }
+  ///
+  /// let topic = domain_participant.create_topic("some_topic".to_string(), "SomeType".to_string(), &qos, TopicKind::WithKey).unwrap();
+  /// let mut data_reader = subscriber.create_datareader::<SomeType, CDRDeserializerAdapter<_>>(&topic, None).unwrap();
+  ///
+  /// // Wait for data to arrive...
+  ///
+  /// let world = data_reader.snapshot(ReadCondition::any()).unwrap();
+  /// if let Some(sample) = world.get(&3) {
+  ///   // do something
+  /// }
+  /// ```
+  pub fn snapshot(
+    &mut self,
+    read_condition: ReadCondition,
+  ) -> ReadResult<HashMap<D::K, DataSample<&D>>> {
+    self.drain_read_notifications();
+    self.fill_and_lock_local_datasample_cache()?;
+    Ok(self.datasample_cache.snapshot(read_condition))
+  }
+
+  /// Reconstructs the state of the topic as it was at source timestamp
+  /// `source_time`: for each instance, the latest sample written at or before
+  /// that time, together with its source timestamp. A disposed instance maps
+  /// to [`Sample::Dispose`].
+  ///
+  /// This looks at the history retained in the topic cache, so how far back
+  /// one can go depends on the History and ResourceLimits QoS of the topic.
+  /// Samples without a source timestamp are considered to be written when
+  /// they were received. Like [`snapshot`](Self::snapshot), this does not
+  /// read or take any samples.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::*;
+  /// # use rustdds::with_key::{DataReader, Sample};
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new()
+  ///   .history(policy::History::KeepAll)
+  ///   .build();
+  /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType { a: i32 }
+  /// # impl Keyed for SomeType {
+  /// #   type K = i32;
+  /// #
+  /// #   fn key(&self) -> Self::K {
+  /// #     self.a
+  /// #   }
+  /// # }
+  ///
+  /// let topic = domain_participant.create_topic("some_topic".to_string(), "SomeType".to_string(), &qos, TopicKind::WithKey).unwrap();
+  /// let mut data_reader = subscriber.create_datareader::<SomeType, CDRDeserializerAdapter<_>>(&topic, None).unwrap();
+  ///
+  /// // What did the world look like ten seconds ago?
+  /// let then = Timestamp::now() - rustdds::Duration::from_secs(10);
+  /// let world = data_reader.read_as_of(then).unwrap();
+  /// if let Some((_written, Sample::Value(sample))) = world.get(&3) {
+  ///   // do something
+  /// }
+  /// ```
+  pub fn read_as_of(
+    &mut self,
+    source_time: Timestamp,
+  ) -> ReadResult<HashMap<D::K, TimestampedSample<D>>> {
+    let mut world = HashMap::new();
+    for dcc in self.simple_data_reader.read_as_of(source_time)? {
+      let written = dcc
+        .write_options
+        .source_timestamp()
+        .unwrap_or(dcc.receive_instant);
+      let key = match &dcc.sample {
+        Sample::Value(d) => d.key(),
+        Sample::Dispose(k) => k.clone(),
+      };
+      // Samples come in source timestamp order, so the last one wins.
+      world.insert(key, (written, dcc.sample));
+    }
+    Ok(world)
+  }
+
+  /// Lists the instances currently known to this DataReader, in key order.
+  ///
+  /// Each item is the instance handle, the key, the current instance state,
+  /// and the [`SampleInfo`] of the latest sample of the instance. The
+  /// SampleInfo is `None` if all samples of the instance have already been
+  /// taken. Like [`snapshot`](Self::snapshot), this does not read or take
+  /// any samples, nor mark instances as viewed.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::*;
+  /// # use rustdds::with_key::DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType { a: i32 }
+  /// # impl Keyed for SomeType {
+  /// #   type K = i32;
+  /// #
+  /// #   fn key(&self) -> Self::K {
+  /// #     self.a
+  /// #   }
+  /// # }
+  ///
+  /// let topic = domain_participant.create_topic("some_topic".to_string(), "SomeType".to_string(), &qos, TopicKind::WithKey).unwrap();
+  /// let mut data_reader = subscriber.create_datareader::<SomeType, CDRDeserializerAdapter<_>>(&topic, None).unwrap();
+  ///
+  /// // Wait for data to arrive...
+  ///
+  /// for (_handle, key, state, _latest) in data_reader.instances().unwrap() {
+  ///   if state == InstanceState::Alive {
+  ///     println!("instance {key} is alive");
+  ///   }
+  /// }
+  /// ```
+  pub fn instances(
+    &mut self,
+  ) -> ReadResult<impl Iterator<Item = (InstanceHandle, D::K, InstanceState, Option<SampleInfo>)>>
+  {
+    self.drain_read_notifications();
+    self.fill_and_lock_local_datasample_cache()?;
+    Ok(self.datasample_cache.instances().into_iter())
+  }
+
+  /// Returns the handle of the instance with key `key`, or `None` if the
+  /// instance is not known to this DataReader.
+  pub fn lookup_instance(&mut self, key: &D::K) -> ReadResult<Option<InstanceHandle>> {
+    self.drain_read_notifications();
+    self.fill_and_lock_local_datasample_cache()?;
+    Ok(self.datasample_cache.lookup_instance(key))
+  }
+
+  // Offers the NOT_READ samples of `instance` to `predicate` in arrival order,
+  // and takes the first match. Samples are marked read as they are checked, so
+  // each sample is offered only once.
+  fn try_take_matching_instance_sample<F>(
+    &mut self,
+    instance: &D::K,
+    predicate: &mut F,
+  ) -> ReadResult<Option<DataSample<D>>>
+  where
+    F: FnMut(&DataSample<&D>) -> bool,
+  {
+    self.drain_read_notifications();
+    self.fill_and_lock_local_datasample_cache()?;
+
+    let selected = self.select_instance_keys_for_access(instance, ReadCondition::not_read());
+    for selected_key in selected.chunks(1) {
+      let is_match = self
+        .datasample_cache
+        .read_by_keys(selected_key)
+        .first()
+        .is_some_and(&mut *predicate);
+      if is_match {
+        return Ok(self.take_by_keys(selected_key).pop());
+      }
+    }
+    Ok(None)
+  }
+
+  /// Blocks until a sample of instance `instance` that satisfies `predicate`
+  /// is available, or `max_wait` has elapsed. The matching sample is taken
+  /// and returned. Returns `Ok(None)` on timeout.
+  ///
+  /// Only samples that have not been read yet are considered. Samples that
+  /// do not match are marked read, but stay in the DataReader. Samples of
+  /// other instances are not affected. Dispose samples are offered to
+  /// `predicate` as well.
+  ///
+  /// This waits on the same notifications as the async interface, so it is
+  /// not necessary to register the DataReader to a `Poll`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::*;
+  /// # use rustdds::with_key::{DataReader, Sample};
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  ///
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new().build();
+  /// let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize)]
+  /// # struct SomeType { a: i32, status: u8 }
+  /// # impl Keyed for SomeType {
+  /// #   type K = i32;
+  /// #
+  /// #   fn key(&self) -> Self::K {
+  /// #     self.a
+  /// #   }
+  /// # }
+  ///
+  /// let topic = domain_participant.create_topic("some_topic".to_string(), "SomeType".to_string(), &qos, TopicKind::WithKey).unwrap();
+  /// let mut data_reader = subscriber.create_datareader::<SomeType, CDRDeserializerAdapter<_>>(&topic, None).unwrap();
+  ///
+  /// // Wait for instance 3 to report status 1
+  /// let done = data_reader.wait_for_instance(
+  ///   &3,
+  ///   |s| matches!(s.value(), Sample::Value(v) if v.status == 1),
+  ///   std::time::Duration::from_millis(10),
+  /// );
+  /// ```
+  pub fn wait_for_instance<F>(
+    &mut self,
+    instance: &D::K,
+    mut predicate: F,
+    max_wait: std::time::Duration,
+  ) -> ReadResult<Option<DataSample<D>>>
+  where
+    F: FnMut(&DataSample<&D>) -> bool,
+  {
+    let deadline = Instant::now() + max_wait;
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+
+    let result = loop {
+      if let Some(s) = self.try_take_matching_instance_sample(instance, &mut predicate)? {
+        break Some(s);
+      }
+      // Store waker, then check again in case something arrived just now.
+      self.simple_data_reader.set_waker(Some(waker.clone()));
+      if let Some(s) = self.try_take_matching_instance_sample(instance, &mut predicate)? {
+        break Some(s);
+      }
+      let now = Instant::now();
+      if now >= deadline {
+        break None;
+      }
+      thread::park_timeout(deadline - now);
+    };
+    self.simple_data_reader.set_waker(None);
+    Ok(result)
+  }
+
+  /// Async version of [`wait_for_instance`](Self::wait_for_instance). There
+  /// is no timeout parameter. Use the timeout facility of your async runtime
+  /// instead.
+  pub async fn async_wait_for_instance<F>(
+    &mut self,
+    instance: &D::K,
+    mut predicate: F,
+  ) -> ReadResult<DataSample<D>>
+  where
+    F: FnMut(&DataSample<&D>) -> bool,
+  {
+    future::poll_fn(|cx| {
+      match self.try_take_matching_instance_sample(instance, &mut predicate) {
+        Err(e) => return Poll::Ready(Err(e)),
+        Ok(Some(s)) => return Poll::Ready(Ok(s)),
+        Ok(None) => {}
+      }
+      // Store waker, then check again in case something arrived just now.
+      self.simple_data_reader.set_waker(Some(cx.waker().clone()));
+      match self.try_take_matching_instance_sample(instance, &mut predicate) {
+        Err(e) => Poll::Ready(Err(e)),
+        Ok(Some(s)) => Poll::Ready(Ok(s)),
+        Ok(None) => Poll::Pending,
+      }
+    })
+    .await
+  }
+
   /// Return values:
   /// true - got all historical data
   /// false - timeout before all historical data was received
@@ -716,13 +1092,69 @@ where
   // get_matched_publications returns a list of handles
   // get_matched_publication_data returns PublicationBuiltinTopicData for a handle
   // But we do not believe in handle-oriented programming, so just return
-  // the actual data right away. Since the handles are quite opaque, about the
-  // only thing that could be done with the handles would be counting how many
-  // we got.
+  // the actual data right away. The handles are available from the data.
 
+  /// Gets the discovery data of the currently matched DataWriters. Use
+  /// [`PublicationBuiltinTopicData::instance_handle`] to get their instance
+  /// handles.
+  ///
+  /// The set of matched writers is maintained in the background thread, so
+  /// this call blocks until it replies.
   pub fn get_matched_publications(&self) -> impl Iterator<Item = PublicationBuiltinTopicData> {
-    // TODO: Obviously not implemented
-    vec![].into_iter()
+    self.simple_data_reader.matched_publications().into_iter()
+  }
+
+  /// Enables a DataReader that was created disabled.
+  /// See [`SimpleDataReader::enable`](crate::with_key::SimpleDataReader::enable).
+  pub fn enable(&self) -> CreateResult<()> {
+    self.simple_data_reader.enable()
+  }
+
+  /// Has the DataReader been enabled?
+  pub fn is_enabled(&self) -> bool {
+    self.simple_data_reader.is_enabled()
+  }
+
+  /// File descriptor for polling status changes outside mio, e.g. from C
+  /// code. See
+  /// [`with_key::SimpleDataReader::as_status_raw_fd`](crate::with_key::SimpleDataReader::as_status_raw_fd).
+  #[cfg(not(target_os = "windows"))]
+  pub fn as_status_raw_fd(&mut self) -> RawFd {
+    self.simple_data_reader.as_status_raw_fd()
+  }
+
+  /// Reception statistics for each currently matched DataWriter. This is a
+  /// RustDDS extension for diagnosing communication problems, e.g. which
+  /// remote writer is not delivering or where samples are being lost.
+  pub fn matched_writer_statistics(&self) -> ReadResult<Vec<WriterProxyStatistics>> {
+    self.simple_data_reader.matched_writer_statistics()
+  }
+
+  /// Receives large samples as [`FragmentStream`]s. See
+  /// [`SimpleDataReader::set_fragment_streaming`](crate::with_key::SimpleDataReader::set_fragment_streaming).
+  pub fn set_fragment_streaming(&self, min_size: Option<usize>) {
+    self.simple_data_reader.set_fragment_streaming(min_size);
+  }
+
+  /// The next streamed sample, if any. See
+  /// [`SimpleDataReader::set_fragment_streaming`](crate::with_key::SimpleDataReader::set_fragment_streaming).
+  pub fn take_fragment_stream(&self) -> Option<FragmentStream> {
+    self.simple_data_reader.take_fragment_stream()
+  }
+
+  /// Delivers each sample at most once, even across recreation of the
+  /// DataReader. See
+  /// [`SimpleDataReader::set_delivery_journal`](crate::with_key::SimpleDataReader::set_delivery_journal).
+  pub fn set_delivery_journal(&mut self, name: &str, retention: std::time::Duration) {
+    self
+      .simple_data_reader
+      .set_delivery_journal(name, retention);
+  }
+
+  /// Stops using the journal set with
+  /// [`set_delivery_journal`](Self::set_delivery_journal).
+  pub fn remove_delivery_journal(&mut self) {
+    self.simple_data_reader.remove_delivery_journal();
   }
 
   /// An async stream for reading the (bare) data samples.
@@ -736,6 +1168,104 @@ where
 
 // -------------------
 
+impl<D, DA> DataReader<D, DA>
+where
+  D: Keyed + Send + 'static,
+  DA: DeserializerAdapter<D> + 'static,
+{
+  /// Same as [`take`](Self::take), but the samples received since the
+  /// previous access are deserialized in parallel in the worker pool of the
+  /// DomainParticipant. The pool is set up with
+  /// [`DomainParticipantBuilder::deserialization_threads`](crate::DomainParticipantBuilder::deserialization_threads).
+  /// Without a pool, this is the same as `take`.
+  ///
+  /// This is useful for large samples that are expensive to deserialize,
+  /// e.g. images.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::*;
+  /// # use rustdds::with_key::DataReader;
+  /// # use rustdds::serialization::CDRDeserializerAdapter;
+  /// let domain_participant = DomainParticipantBuilder::new(0)
+  ///   .deserialization_threads(4)
+  ///   .build()
+  ///   .unwrap();
+  /// # let qos = QosPolicyBuilder::new().build();
+  /// # let subscriber = domain_participant.create_subscriber(&qos).unwrap();
+  /// #
+  /// # #[derive(Serialize, Deserialize, Debug)]
+  /// # struct Image { id: i32, pixels: Vec<u8> }
+  /// # impl Keyed for Image {
+  /// #   type K = i32;
+  /// #   fn key(&self) -> Self::K { self.id }
+  /// # }
+  /// #
+  /// let topic = domain_participant.create_topic("images".to_string(), "Image".to_string(), &qos, TopicKind::WithKey).unwrap();
+  /// let mut data_reader = subscriber.create_datareader::<Image, CDRDeserializerAdapter<_>>(&topic, None).unwrap();
+  ///
+  /// // Wait for data to arrive...
+  ///
+  /// if let Ok(images) = data_reader.take_offloaded(10, ReadCondition::not_read()) {
+  ///   for image in images.iter() {
+  ///     // do something
+  ///   }
+  /// }
+  /// ```
+  pub fn take_offloaded(
+    &mut self,
+    max_samples: usize,
+    read_condition: ReadCondition,
+  ) -> ReadResult<Vec<DataSample<D>>> {
+    // Clear notification buffer. This must be done first to avoid race conditions.
+    self.drain_read_notifications();
+
+    for dcc in self.simple_data_reader.try_take_all_offloaded()? {
+      self.add_to_local_datasample_cache(dcc);
+    }
+    self.take(max_samples, read_condition)
+  }
+}
+
+impl<D, DA> DataReader<D, DA>
+where
+  D: Keyed + 'static,
+  DA: DeserializerAdapter<D> + SeedDeserializerAdapter<D>,
+{
+  /// Takes up to `max_samples` samples received since the previous access,
+  /// deserializing each data value with a serde
+  /// [`DeserializeSeed`](serde::de::DeserializeSeed) made by `seed`, instead
+  /// of into `D`. The seed can e.g. deserialize into an arena or into buffers
+  /// kept from previous samples, so that a processing loop does not need to
+  /// allocate. Deserialized values cannot borrow from the received data.
+  ///
+  /// The samples bypass the sample cache of the DataReader, so there is no
+  /// [`SampleInfo`], and samples already moved to the cache by e.g.
+  /// [`read`](Self::read) are not returned here. Dispose samples are given
+  /// as keys.
+  ///
+  /// If a sample fails to deserialize, the samples before it are returned,
+  /// and the error is returned from the next call. See also
+  /// [`SimpleDataReader::try_take_one_with_deserializer`](crate::with_key::SimpleDataReader::try_take_one_with_deserializer).
+  pub fn take_with_deserializer<S, T>(
+    &mut self,
+    max_samples: usize,
+    seed: impl FnMut() -> S,
+  ) -> ReadResult<Vec<Sample<T, D::K>>>
+  where
+    S: for<'de> DeserializeSeed<'de, Value = T>,
+  {
+    // Clear notification buffer. This must be done first to avoid race conditions.
+    self.drain_read_notifications();
+
+    self
+      .simple_data_reader
+      .try_take_with_deserializer(max_samples, seed)
+  }
+}
+
 impl<D, DA> Evented for DataReader<D, DA>
 where
   D: Keyed,
@@ -815,6 +1345,24 @@ where
   }
 }
 
+/// For polling the DataReader outside mio, e.g. with poll(2), or from C code.
+/// The file descriptor is readable when new data may be available. It stays
+/// readable until data is read or taken, so after it becomes readable, read or
+/// take all available data before polling again.
+///
+/// The file descriptor is owned by the DataReader, and must not be closed or
+/// read from.
+#[cfg(not(target_os = "windows"))]
+impl<D, DA> AsRawFd for DataReader<D, DA>
+where
+  D: Keyed,
+  DA: DeserializerAdapter<D>,
+{
+  fn as_raw_fd(&self) -> RawFd {
+    self.simple_data_reader.as_raw_fd()
+  }
+}
+
 impl<'a, D, DA> StatusEvented<'a, DataReaderStatus, SimpleDataReaderEventStream<'a, D, DA>>
   for DataReader<D, DA>
 where
@@ -848,16 +1396,46 @@ where
   }
 }
 
-impl<D, DA> RTPSEntity for DataReader<D, DA>
+impl<D, DA> MutQosPolicy for DataReader<D, DA>
 where
   D: Keyed + 'static,
   DA: DeserializerAdapter<D>,
 {
-  fn guid(&self) -> GUID {
+  /// Change the QoS policies. Policies set in `new_qos` replace the current
+  /// ones, and the rest are kept.
+  ///
+  /// Changing a policy that cannot be changed after creation fails with
+  /// [`QosError::ImmutablePolicy`], which lists the changeable policies. The
+  /// new QoS is announced via Discovery, and matching with remote DataWriters
+  /// is re-evaluated.
+  fn set_qos(&mut self, new_qos: &QosPolicies) -> Result<(), QosError> {
+    self.simple_data_reader.set_qos(new_qos)?;
+    self
+      .datasample_cache
+      .set_qos(self.simple_data_reader.qos().clone());
+    Ok(())
+  }
+}
+
+impl<D, DA> RTPSEntity for DataReader<D, DA>
+where
+  D: Keyed + 'static,
+  DA: DeserializerAdapter<D>,
+{
+  fn guid(&self) -> GUID {
     self.simple_data_reader.guid()
   }
 }
 
+// Wakes up a thread blocked in DataReader::wait_for_instance
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+  fn wake(self: Arc<Self>) {
+    self.0.unpark();
+  }
+}
+
 // ----------------------------------------------
 // ----------------------------------------------
 
@@ -986,18 +1564,22 @@ where
 
 #[cfg(test)]
 mod tests {
-  use std::rc::Rc;
+  use std::{cell::RefCell, fmt, ops::Range, rc::Rc};
 
   use bytes::Bytes;
   use mio_extras::channel as mio_channel;
   use log::info;
   use byteorder::LittleEndian;
+  use serde::de::{self, Deserializer, SeqAccess, Visitor};
 
   use super::*;
   use crate::{
     dds::{
-      participant::DomainParticipant,
+      ddsdata::DDSData,
+      participant::{DomainParticipant, DomainParticipantBuilder},
+      sampleinfo::{SampleState, ViewState},
       topic::{TopicDescription, TopicKind},
+      with_key::datawriter::WriteOptions,
     },
     messages::submessages::{
       elements::serialized_payload::SerializedPayload, submessage_flag::*, submessages::Data,
@@ -1006,10 +1588,14 @@ mod tests {
     network::udp_sender::UDPSender,
     rtps::{
       message_receiver::*,
-      reader::{Reader, ReaderIngredients},
+      reader::{
+        test_util::{reliable_qos, TestReader},
+        Reader, ReaderIngredients,
+      },
     },
     serialization::{cdr_deserializer::CDRDeserializerAdapter, cdr_serializer::to_bytes},
     structure::{
+      cache_change::CacheChange,
       guid::{EntityId, EntityKind, GuidPrefix},
       sequence_number::SequenceNumber,
     },
@@ -1069,6 +1655,8 @@ mod tests {
       data_reader_command_receiver: reader_command_receiver,
       data_reader_waker,
       poll_event_sender: notification_event_sender,
+      readiness_queue: None,
+      fragment_streams: Arc::default(),
       security_plugins: None,
     };
 
@@ -1147,6 +1735,7 @@ mod tests {
 
     reader.handle_data_msg(data_msg, data_flags, &mr_state);
     reader.handle_data_msg(data_msg2, data_flags, &mr_state);
+    reader.commit_changes();
 
     // Test that reading does not consume data samples, i.e. they can be read
     // multiple times
@@ -1182,6 +1771,265 @@ mod tests {
     assert_eq!(result_vec2.unwrap().len(), 0);
   }
 
+  #[test]
+  fn take_offloaded_deserializes_in_pool() {
+    let dp = DomainParticipantBuilder::new(0)
+      .deserialization_threads(3)
+      .build()
+      .expect("Participant creation failed!");
+
+    let mut qos = QosPolicies::qos_none();
+    qos.history = Some(policy::History::KeepAll); // Just for testing
+
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic(
+        "dr take offloaded".to_string(),
+        "take_offloaded test".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+
+    let topic_cache =
+      dp.dds_cache()
+        .write()
+        .unwrap()
+        .add_new_topic(topic.name(), topic.get_type(), &topic.qos());
+
+    let reader_guid = GUID::new_with_prefix_and_id(dp.guid_prefix(), EntityId::default());
+    let mut reader =
+      TestReader::with_topic_cache(reader_guid, QosPolicies::qos_none(), topic_cache);
+
+    let mut datareader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+
+    let writer_guid = GUID {
+      prefix: GuidPrefix::new(&[1; 12]),
+      entity_id: EntityId::create_custom_entity_id(
+        [1; 3],
+        EntityKind::WRITER_WITH_KEY_USER_DEFINED,
+      ),
+    };
+    let mr_state = reader.add_writer(writer_guid, &QosPolicies::qos_none());
+
+    let test_data: Vec<RandomData> = (0..20)
+      .map(|a| RandomData {
+        a,
+        b: format!("sample {a}"),
+      })
+      .collect();
+    for (sn, data) in test_data.iter().enumerate() {
+      let data_msg = Data {
+        serialized_payload: Some(
+          SerializedPayload {
+            representation_identifier: RepresentationIdentifier::CDR_LE,
+            representation_options: [0, 0],
+            value: Bytes::from(to_bytes::<RandomData, LittleEndian>(data).unwrap()),
+          }
+          .into(),
+        ),
+        ..reader.data(writer_guid, sn as i64 + 1)
+      };
+      reader.receive(data_msg, &mr_state);
+    }
+
+    let taken: Vec<RandomData> = datareader
+      .take_offloaded(100, ReadCondition::any())
+      .unwrap()
+      .into_iter()
+      .map(|s| s.into_value().unwrap())
+      .collect();
+    assert_eq!(taken, test_data);
+    assert!(datareader
+      .take_offloaded(100, ReadCondition::any())
+      .unwrap()
+      .is_empty());
+  }
+
+  #[test]
+  fn take_with_deserializer_uses_seed() {
+    let dp = DomainParticipant::new(0).expect("Participant creation failed!");
+
+    let mut qos = QosPolicies::qos_none();
+    qos.history = Some(policy::History::KeepAll); // Just for testing
+
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic(
+        "dr take with deserializer".to_string(),
+        "take_with_deserializer test".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+
+    let topic_cache =
+      dp.dds_cache()
+        .write()
+        .unwrap()
+        .add_new_topic(topic.name(), topic.get_type(), &topic.qos());
+
+    let reader_guid = GUID::new_with_prefix_and_id(dp.guid_prefix(), EntityId::default());
+    let mut reader =
+      TestReader::with_topic_cache(reader_guid, QosPolicies::qos_none(), topic_cache);
+
+    let mut datareader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+
+    let writer_guid = GUID {
+      prefix: GuidPrefix::new(&[1; 12]),
+      entity_id: EntityId::create_custom_entity_id(
+        [1; 3],
+        EntityKind::WRITER_WITH_KEY_USER_DEFINED,
+      ),
+    };
+    let mr_state = reader.add_writer(writer_guid, &QosPolicies::qos_none());
+
+    let test_data: Vec<RandomData> = (0..5)
+      .map(|a| RandomData {
+        a,
+        b: format!("sample {a}"),
+      })
+      .collect();
+    for (sn, data) in test_data.iter().enumerate() {
+      let data_msg = Data {
+        serialized_payload: Some(
+          SerializedPayload {
+            representation_identifier: RepresentationIdentifier::CDR_LE,
+            representation_options: [0, 0],
+            value: Bytes::from(to_bytes::<RandomData, LittleEndian>(data).unwrap()),
+          }
+          .into(),
+        ),
+        ..reader.data(writer_guid, sn as i64 + 1)
+      };
+      reader.receive(data_msg, &mr_state);
+    }
+
+    let arena = RefCell::new(String::new());
+    let mut taken = datareader
+      .take_with_deserializer(3, || ArenaSeed(&arena))
+      .unwrap();
+    assert_eq!(taken.len(), 3);
+    taken.extend(
+      datareader
+        .take_with_deserializer(100, || ArenaSeed(&arena))
+        .unwrap(),
+    );
+    let arena = arena.into_inner();
+    let taken: Vec<RandomData> = taken
+      .into_iter()
+      .map(|s| {
+        let (a, b) = s.value().unwrap();
+        RandomData {
+          a,
+          b: arena[b].to_string(),
+        }
+      })
+      .collect();
+    assert_eq!(taken, test_data);
+  }
+
+  // Deserializes RandomData into (a, range of b in the arena)
+  struct ArenaSeed<'a>(&'a RefCell<String>);
+
+  impl<'de, 'a> DeserializeSeed<'de> for ArenaSeed<'a> {
+    type Value = (i64, Range<usize>);
+
+    fn deserialize<De: Deserializer<'de>>(
+      self,
+      deserializer: De,
+    ) -> Result<Self::Value, De::Error> {
+      deserializer.deserialize_tuple(2, self)
+    }
+  }
+
+  impl<'de, 'a> Visitor<'de> for ArenaSeed<'a> {
+    type Value = (i64, Range<usize>);
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+      formatter.write_str("RandomData")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+      let a = seq
+        .next_element()?
+        .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+      let b = seq
+        .next_element_seed(ArenaString(self.0))?
+        .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+      Ok((a, b))
+    }
+  }
+
+  struct ArenaString<'a>(&'a RefCell<String>);
+
+  impl<'de, 'a> DeserializeSeed<'de> for ArenaString<'a> {
+    type Value = Range<usize>;
+
+    fn deserialize<De: Deserializer<'de>>(
+      self,
+      deserializer: De,
+    ) -> Result<Self::Value, De::Error> {
+      deserializer.deserialize_str(self)
+    }
+  }
+
+  impl<'de, 'a> Visitor<'de> for ArenaString<'a> {
+    type Value = Range<usize>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+      formatter.write_str("a string")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+      let mut arena = self.0.borrow_mut();
+      let start = arena.len();
+      arena.push_str(v);
+      Ok(start..arena.len())
+    }
+  }
+
+  #[test]
+  fn matched_writer_statistics() {
+    let dp = DomainParticipant::new(0).expect("Participant creation failed!");
+    let qos = QosPolicies::qos_none();
+    let topic = dp
+      .create_topic(
+        "matched_writer_statistics_test".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let subscriber = dp.create_subscriber(&qos).unwrap();
+    let datareader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+    assert_eq!(datareader.matched_writer_statistics().unwrap(), vec![]);
+
+    // A local DataWriter is matched without network traffic
+    let publisher = dp.create_publisher(&qos).unwrap();
+    let datawriter = publisher
+      .create_datawriter_cdr::<RandomData>(&topic, None)
+      .unwrap();
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    loop {
+      let statistics = datareader.matched_writer_statistics().unwrap();
+      if statistics
+        .iter()
+        .any(|s| s.writer_guid == datawriter.guid())
+      {
+        break;
+      }
+      assert!(std::time::Instant::now() < deadline, "{statistics:?}");
+      std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+  }
+
   #[test]
   fn read_and_take_with_instance() {
     // Test the methods read_instance and take_instance of the DataReader
@@ -1234,6 +2082,8 @@ mod tests {
       data_reader_command_receiver: reader_command_receiver,
       data_reader_waker,
       poll_event_sender: notification_event_sender,
+      readiness_queue: None,
+      fragment_streams: Arc::default(),
       security_plugins: None,
     };
 
@@ -1358,6 +2208,7 @@ mod tests {
     reader.handle_data_msg(data_msg2, data_flags, &mr_state);
     reader.handle_data_msg(data_msg3, data_flags, &mr_state);
     reader.handle_data_msg(data_msg4, data_flags, &mr_state);
+    reader.commit_changes();
 
     // Check that calling read_instance with different keys and SelectByKey options
     // works as expected
@@ -1402,4 +2253,892 @@ mod tests {
     assert!(results.is_ok());
     assert!(results.unwrap().is_empty());
   }
+
+  fn test_writer_guid() -> GUID {
+    GUID {
+      prefix: GuidPrefix::new(&[1; 12]),
+      entity_id: EntityId::create_custom_entity_id(
+        [1; 3],
+        EntityKind::WRITER_WITH_KEY_USER_DEFINED,
+      ),
+    }
+  }
+
+  // A DataReader of a new KeepAll topic, and a Reader that feeds it samples
+  // from test_writer_guid()
+  fn test_datareader(
+    dp: &DomainParticipant,
+    topic_name: &str,
+  ) -> (
+    TestReader,
+    MessageReceiverState,
+    DataReader<RandomData, CDRDeserializerAdapter<RandomData>>,
+  ) {
+    let mut qos = QosPolicies::qos_none();
+    qos.history = Some(policy::History::KeepAll);
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic(
+        topic_name.to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let topic_cache =
+      dp.dds_cache()
+        .write()
+        .unwrap()
+        .add_new_topic(topic.name(), topic.get_type(), &topic.qos());
+
+    let reader_guid = GUID::new_with_prefix_and_id(dp.guid_prefix(), EntityId::default());
+    let mut reader =
+      TestReader::with_topic_cache(reader_guid, QosPolicies::qos_none(), topic_cache);
+    let mr_state = reader.add_writer(test_writer_guid(), &QosPolicies::qos_none());
+    let datareader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+    (reader, mr_state, datareader)
+  }
+
+  // DATA of `data` from test_writer_guid()
+  fn random_data_msg(reader: &TestReader, sequence_number: i64, data: &RandomData) -> Data {
+    Data {
+      serialized_payload: Some(
+        SerializedPayload {
+          representation_identifier: RepresentationIdentifier::CDR_LE,
+          representation_options: [0, 0],
+          value: Bytes::from(to_bytes::<RandomData, LittleEndian>(data).unwrap()),
+        }
+        .into(),
+      ),
+      ..reader.data(test_writer_guid(), sequence_number)
+    }
+  }
+
+  #[test]
+  fn read_state_is_remembered_between_calls() {
+    let dp = DomainParticipant::new(0).expect("Participant creation failed!");
+    let (mut reader, mr_state, mut datareader) = test_datareader(&dp, "dr read state");
+    for sn in 1..=2 {
+      let data = RandomData {
+        a: sn,
+        b: "read".to_string(),
+      };
+      let data_msg = random_data_msg(&reader, sn, &data);
+      reader.receive(data_msg, &mr_state);
+    }
+
+    let states = |datareader: &mut DataReader<_, _>| {
+      datareader
+        .read(100, ReadCondition::any())
+        .unwrap()
+        .iter()
+        .map(|s| (s.sample_info().sample_state(), s.sample_info().view_state()))
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(
+      states(&mut datareader),
+      vec![(SampleState::NotRead, ViewState::New); 2]
+    );
+    assert_eq!(
+      states(&mut datareader),
+      vec![(SampleState::Read, ViewState::NotNew); 2]
+    );
+    assert!(datareader.read_next_sample().unwrap().is_none());
+  }
+
+  #[test]
+  fn snapshot_has_latest_sample_of_each_instance() {
+    let dp = DomainParticipant::new(0).expect("Participant creation failed!");
+    let (mut reader, mr_state, mut datareader) = test_datareader(&dp, "dr snapshot");
+
+    let data_key1 = RandomData {
+      a: 1,
+      b: "1".to_string(),
+    };
+    let data_key2_1 = RandomData {
+      a: 2,
+      b: "1".to_string(),
+    };
+    let data_key2_2 = RandomData {
+      a: 2,
+      b: "2".to_string(),
+    };
+    let key1 = data_key1.key();
+    let key2 = data_key2_1.key();
+    for (sn, data) in [(1, &data_key1), (2, &data_key2_1), (3, &data_key2_2)] {
+      let data_msg = random_data_msg(&reader, sn, data);
+      reader.receive(data_msg, &mr_state);
+    }
+
+    let world = datareader.snapshot(ReadCondition::any()).unwrap();
+    assert_eq!(world.len(), 2);
+    assert_eq!(world[&key1].value().clone().unwrap(), &data_key1);
+    assert_eq!(world[&key2].value().clone().unwrap(), &data_key2_2);
+
+    // Nothing was marked read
+    assert_eq!(
+      datareader
+        .read(100, ReadCondition::not_read())
+        .unwrap()
+        .len(),
+      3
+    );
+    assert!(datareader
+      .snapshot(ReadCondition::not_read())
+      .unwrap()
+      .is_empty());
+  }
+
+  #[test]
+  fn reception_instant_follows_reception_order() {
+    let dp = DomainParticipant::new(0).expect("Participant creation failed!");
+    let (mut reader, mr_state, mut datareader) = test_datareader(&dp, "dr reception instant");
+
+    let before = std::time::Instant::now();
+    for sn in 1..=2 {
+      let data = RandomData {
+        a: sn,
+        b: "received".to_string(),
+      };
+      let data_msg = random_data_msg(&reader, sn, &data);
+      reader.receive(data_msg, &mr_state);
+    }
+    let after = std::time::Instant::now();
+
+    let samples = datareader.take(100, ReadCondition::any()).unwrap();
+    let info = |a: i64| {
+      samples
+        .iter()
+        .find(|s| s.key() == a)
+        .unwrap()
+        .sample_info()
+        .clone()
+    };
+    let (info1, info2) = (info(1), info(2));
+    // Later sample was received later, by both clocks
+    assert!(info1.reception_timestamp() < info2.reception_timestamp());
+    assert!(before <= info1.reception_instant());
+    assert!(info1.reception_instant() <= info2.reception_instant());
+    assert!(info2.reception_instant() <= after);
+  }
+
+  #[test]
+  fn wait_for_instance_skips_non_matching_samples() {
+    let dp = DomainParticipant::new(0).expect("Participant creation failed!");
+    let (mut reader, mr_state, mut datareader) = test_datareader(&dp, "dr wait for instance");
+
+    let data_key1 = RandomData {
+      a: 1,
+      b: "1".to_string(),
+    };
+    let data_key2_1 = RandomData {
+      a: 2,
+      b: "1".to_string(),
+    };
+    let data_key2_2 = RandomData {
+      a: 2,
+      b: "2".to_string(),
+    };
+    let key2 = data_key2_1.key();
+
+    // Nothing yet, so waiting times out
+    let waited =
+      datareader.wait_for_instance(&key2, |_| true, std::time::Duration::from_millis(10));
+    assert!(waited.unwrap().is_none());
+
+    // Two samples for key 2, and one for key 1. Only the second one of key 2
+    // matches.
+    for (sn, data) in [(1, &data_key2_1), (2, &data_key1), (3, &data_key2_2)] {
+      let data_msg = random_data_msg(&reader, sn, data);
+      reader.receive(data_msg, &mr_state);
+    }
+    let waited = datareader
+      .wait_for_instance(
+        &key2,
+        |s| matches!(s.value(), Sample::Value(d) if d.b == "2"),
+        std::time::Duration::from_millis(10),
+      )
+      .unwrap()
+      .unwrap();
+    assert_eq!(waited.into_value().unwrap(), data_key2_2);
+
+    // The non-matching sample was marked read, but is still there
+    let results = datareader
+      .take_instance(100, ReadCondition::any(), Some(key2), SelectByKey::This)
+      .unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].value().clone().unwrap(), data_key2_1);
+    assert_eq!(results[0].sample_info().sample_state(), SampleState::Read);
+    // Samples of other instances are untouched
+    let results = datareader.take(100, ReadCondition::not_read()).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].value().clone().unwrap(), data_key1);
+  }
+
+  #[test]
+  fn shared_subscription_round_robin() {
+    let dp = DomainParticipant::new(0).expect("Participant creation failed!");
+
+    let mut qos = QosPolicies::qos_none();
+    qos.history = Some(policy::History::KeepAll);
+
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic(
+        "dr shared".to_string(),
+        "shared subscription test".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+
+    let topic_cache =
+      dp.dds_cache()
+        .write()
+        .unwrap()
+        .add_new_topic(topic.name(), topic.get_type(), &topic.qos());
+
+    let reader_guid = GUID::new_with_prefix_and_id(dp.guid_prefix(), EntityId::default());
+    let mut reader =
+      TestReader::with_topic_cache(reader_guid, QosPolicies::qos_none(), topic_cache);
+
+    // Two DataReaders in a shared group, and one outside of it
+    let shared_qos = QosPolicies::builder()
+      .shared_subscription(policy::SharedSubscription {
+        group: "workers".to_string(),
+        distribution: policy::SampleDistribution::RoundRobin,
+      })
+      .build();
+    let mut worker1 = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(
+        &topic,
+        Some(shared_qos.clone()),
+      )
+      .unwrap();
+    let mut worker2 = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, Some(shared_qos))
+      .unwrap();
+    let mut monitor = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+
+    let writer_guid = GUID {
+      prefix: GuidPrefix::new(&[1; 12]),
+      entity_id: EntityId::create_custom_entity_id(
+        [1; 3],
+        EntityKind::WRITER_WITH_KEY_USER_DEFINED,
+      ),
+    };
+    let mr_state = reader.add_writer(writer_guid, &QosPolicies::qos_none());
+
+    for a in 1..=6 {
+      let data = RandomData {
+        a,
+        b: "work".to_string(),
+      };
+      let data_msg = Data {
+        serialized_payload: Some(
+          SerializedPayload {
+            representation_identifier: RepresentationIdentifier::CDR_LE,
+            representation_options: [0, 0],
+            value: Bytes::from(to_bytes::<RandomData, LittleEndian>(&data).unwrap()),
+          }
+          .into(),
+        ),
+        ..reader.data(writer_guid, a)
+      };
+      reader.receive(data_msg, &mr_state);
+    }
+
+    let take_keys = |dr: &mut DataReader<RandomData, CDRDeserializerAdapter<RandomData>>| {
+      dr.take(100, ReadCondition::any())
+        .unwrap()
+        .iter()
+        .map(|ds| ds.key())
+        .collect::<Vec<_>>()
+    };
+    let keys1 = take_keys(&mut worker1);
+    let keys2 = take_keys(&mut worker2);
+
+    // The workers share the samples evenly, the other reader gets all of them.
+    assert_eq!(keys1.len(), 3);
+    assert_eq!(keys2.len(), 3);
+    let mut all_keys = [keys1, keys2].concat();
+    all_keys.sort();
+    assert_eq!(all_keys, vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(take_keys(&mut monitor).len(), 6);
+  }
+
+  #[test]
+  fn take_between_stage_and_commit_does_not_skip_staged_samples() {
+    let dp = DomainParticipant::new(0).expect("Participant creation failed!");
+    let qos = reliable_qos();
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic(
+        "dr staged".to_string(),
+        "staged change test".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+
+    let topic_cache =
+      dp.dds_cache()
+        .write()
+        .unwrap()
+        .add_new_topic(topic.name(), topic.get_type(), &topic.qos());
+
+    let reader_guid = GUID::new_with_prefix_and_id(dp.guid_prefix(), EntityId::default());
+    let mut reader = TestReader::with_topic_cache(reader_guid, qos.clone(), topic_cache);
+    let mut datareader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+
+    let writer_guid = GUID {
+      prefix: GuidPrefix::new(&[1; 12]),
+      entity_id: EntityId::create_custom_entity_id(
+        [1; 3],
+        EntityKind::WRITER_WITH_KEY_USER_DEFINED,
+      ),
+    };
+    let mr_state = reader.add_writer(writer_guid, &qos);
+    let data_msg = |reader: &TestReader, a: i64| {
+      let data = RandomData {
+        a,
+        b: "staged".to_string(),
+      };
+      Data {
+        serialized_payload: Some(
+          SerializedPayload {
+            representation_identifier: RepresentationIdentifier::CDR_LE,
+            representation_options: [0, 0],
+            value: Bytes::from(to_bytes::<RandomData, LittleEndian>(&data).unwrap()),
+          }
+          .into(),
+        ),
+        ..reader.data(writer_guid, a)
+      }
+    };
+    let mut take_keys = || {
+      datareader
+        .take(100, ReadCondition::any())
+        .unwrap()
+        .iter()
+        .map(|ds| ds.key())
+        .collect::<Vec<_>>()
+    };
+
+    // SN 2 arrives first, so it has to wait for SN 1.
+    let sn2 = data_msg(&reader, 2);
+    reader.receive(sn2, &mr_state);
+    assert!(take_keys().is_empty());
+
+    // SN 1 is staged, but the rest of its message is still being processed.
+    let sn1 = data_msg(&reader, 1);
+    reader.stage(sn1, &mr_state);
+    assert!(take_keys().is_empty());
+
+    reader.commit();
+    let mut keys = take_keys();
+    keys.sort();
+    assert_eq!(keys, vec![1, 2]);
+  }
+
+  #[test]
+  fn staged_sample_is_not_skipped_by_best_effort_take() {
+    let dp = DomainParticipant::new(0).expect("Participant creation failed!");
+    let qos = QosPolicies::qos_none();
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic(
+        "dr staged best effort".to_string(),
+        "staged change test".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let topic_cache =
+      dp.dds_cache()
+        .write()
+        .unwrap()
+        .add_new_topic(topic.name(), topic.get_type(), &topic.qos());
+    let mut datareader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+
+    let writer_guid = GUID::dummy_test_guid(EntityKind::WRITER_WITH_KEY_USER_DEFINED);
+    let change = |a: i64| {
+      let data = RandomData {
+        a,
+        b: "staged".to_string(),
+      };
+      CacheChange::new(
+        writer_guid,
+        SequenceNumber::new(a),
+        WriteOptions::default(),
+        DDSData::new(SerializedPayload {
+          representation_identifier: RepresentationIdentifier::CDR_LE,
+          representation_options: [0, 0],
+          value: Bytes::from(to_bytes::<RandomData, LittleEndian>(&data).unwrap()),
+        }),
+      )
+    };
+    let mut take_keys = || {
+      datareader
+        .take(100, ReadCondition::any())
+        .unwrap()
+        .iter()
+        .map(|ds| ds.key())
+        .collect::<Vec<_>>()
+    };
+
+    // Sample 1 was received before sample 2 was committed, e.g. by a local
+    // DataWriter, but it is staged only after that.
+    let received_1 = Timestamp::now();
+    let received_2 = received_1 + crate::Duration::from_millis(1);
+    topic_cache
+      .lock()
+      .unwrap()
+      .add_change(&received_2, change(2));
+    topic_cache
+      .lock()
+      .unwrap()
+      .stage_change(&received_1, change(1));
+    assert_eq!(take_keys(), vec![2]);
+
+    topic_cache.lock().unwrap().commit_changes();
+    assert_eq!(take_keys(), vec![1]);
+  }
+
+  #[test]
+  fn time_ordered_delivery_across_writers() {
+    use crate::{
+      dds::with_key::{DataWriter, WriteOptionsBuilder},
+      serialization::CDRSerializerAdapter,
+    };
+
+    let dp = DomainParticipant::new(0).expect("Participant creation failed!");
+    let qos = QosPolicies::qos_none();
+    let publisher = dp.create_publisher(&qos).unwrap();
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic(
+        "dr time ordered".to_string(),
+        "time ordered delivery test".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+
+    let hold_back = crate::Duration::from_millis(300);
+    let reader_qos = QosPolicyBuilder::new()
+      .history(policy::History::KeepAll)
+      .time_ordered_delivery(policy::TimeOrderedDelivery { hold_back })
+      .build();
+    let mut reader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, Some(reader_qos))
+      .unwrap();
+    let writer1: DataWriter<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher.create_datawriter(&topic, None).unwrap();
+    let writer2: DataWriter<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher.create_datawriter(&topic, None).unwrap();
+
+    // Samples arrive out of source timestamp order
+    let t0 = Timestamp::now();
+    let write = |writer: &DataWriter<_, _>, a: i64, offset_ms: i64| {
+      let options = WriteOptionsBuilder::new()
+        .source_timestamp(t0 + crate::Duration::from_millis(offset_ms))
+        .build();
+      let data = RandomData {
+        a,
+        b: "ordered".to_string(),
+      };
+      writer.write_with_options(data, options).unwrap();
+    };
+    write(&writer1, 3, 30);
+    write(&writer2, 1, 10);
+    write(&writer1, 4, 40);
+    write(&writer2, 2, 20);
+
+    let started = std::time::Instant::now();
+    let mut received = Vec::new();
+    while received.len() < 4 && started.elapsed() < std::time::Duration::from_secs(5) {
+      std::thread::sleep(std::time::Duration::from_millis(20));
+      while let Some(sample) = reader.take_next_sample().unwrap() {
+        if received.is_empty() {
+          assert!(started.elapsed() >= std::time::Duration::from_millis(250));
+        }
+        received.push(sample.into_value().unwrap().a);
+      }
+    }
+    assert_eq!(received, vec![1, 2, 3, 4]);
+  }
+
+  #[test]
+  fn read_as_of_reconstructs_past_states() {
+    use crate::{
+      dds::{
+        ddsdata::DDSData,
+        key::Key,
+        with_key::{WriteOptions, WriteOptionsBuilder},
+      },
+      structure::cache_change::{CacheChange, ChangeKind},
+    };
+
+    let dp = DomainParticipant::new(0).expect("Participant creation failed!");
+    let qos = QosPolicyBuilder::new()
+      .history(policy::History::KeepAll)
+      .build();
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic(
+        "dr read as of".to_string(),
+        "read as of test".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let mut reader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+    let topic_cache = dp
+      .dds_cache()
+      .read()
+      .unwrap()
+      .get_existing_topic_cache(&topic.name())
+      .unwrap();
+
+    // Changes from a remote writer, stamped at t0 + offset_ms
+    let writer_guid = GUID::dummy_test_guid(EntityKind::WRITER_WITH_KEY_USER_DEFINED);
+    let t0 = Timestamp::now();
+    let at = |offset_ms: i64| t0 + crate::Duration::from_millis(offset_ms);
+    let options_at = |offset_ms: i64| -> WriteOptions {
+      WriteOptionsBuilder::new()
+        .source_timestamp(at(offset_ms))
+        .build()
+    };
+    let mut sn = 0;
+    let mut add = |data: DDSData, offset_ms: i64| {
+      sn += 1;
+      let cc = CacheChange::new(
+        writer_guid,
+        SequenceNumber::from(sn),
+        options_at(offset_ms),
+        data,
+      );
+      topic_cache.lock().unwrap().add_change(&at(offset_ms), cc);
+    };
+    let write = |a: i64, b: &str| {
+      let data = RandomData {
+        a,
+        b: b.to_string(),
+      };
+      DDSData::new(SerializedPayload {
+        representation_identifier: RepresentationIdentifier::CDR_LE,
+        representation_options: [0, 0],
+        value: Bytes::from(to_bytes::<RandomData, LittleEndian>(&data).unwrap()),
+      })
+    };
+    add(write(1, "first"), 10);
+    add(write(2, "only"), 20);
+    add(write(1, "second"), 30);
+    add(
+      DDSData::new_disposed_by_key_hash(ChangeKind::NotAliveDisposed, 2_i64.hash_key(false)),
+      40,
+    );
+
+    let state_at = |reader: &mut DataReader<RandomData, _>, offset_ms| {
+      let mut state = reader
+        .read_as_of(at(offset_ms))
+        .unwrap()
+        .into_iter()
+        .map(|(key, (_written, sample))| {
+          (
+            key,
+            sample
+              .value()
+              .map_or_else(|| "disposed".to_string(), |d| d.b),
+          )
+        })
+        .collect::<Vec<_>>();
+      state.sort();
+      state
+    };
+    assert!(state_at(&mut reader, 5).is_empty());
+    assert_eq!(state_at(&mut reader, 15), vec![(1, "first".to_string())]);
+    assert_eq!(
+      state_at(&mut reader, 20),
+      vec![(1, "first".to_string()), (2, "only".to_string())]
+    );
+    assert_eq!(
+      state_at(&mut reader, 35),
+      vec![(1, "second".to_string()), (2, "only".to_string())]
+    );
+    assert_eq!(
+      state_at(&mut reader, 45),
+      vec![(1, "second".to_string()), (2, "disposed".to_string())]
+    );
+
+    // Reading history does not consume anything
+    let mut taken = 0;
+    while reader.take_next_sample().unwrap().is_some() {
+      taken += 1;
+    }
+    assert_eq!(taken, 4);
+  }
+
+  #[test]
+  fn pending_wake_up_is_not_requested_again() {
+    use crate::dds::with_key::simpledatareader::ReaderCommand;
+
+    let dp = DomainParticipant::new(0).expect("Participant creation failed!");
+    // Disabled, so that the commands to the RTPS Reader can be inspected here
+    let qos = QosPolicyBuilder::new()
+      .entity_factory(policy::EntityFactory {
+        autoenable_created_entities: false,
+      })
+      .build();
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic(
+        "dr wake up".to_string(),
+        "wake up test".to_string(),
+        &QosPolicies::qos_none(),
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let reader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+    let ingredients = reader
+      .simple_data_reader
+      .reader_ingredients()
+      .take()
+      .unwrap();
+    let requested_delays = || {
+      let mut delays = Vec::new();
+      while let Ok(command) = ingredients.data_reader_command_receiver.try_recv() {
+        match command {
+          ReaderCommand::WakeUpAfter { delay } => delays.push(delay.as_secs()),
+          _ => panic!("Unexpected command"),
+        }
+      }
+      delays
+    };
+    let wake_up_after = |secs| {
+      reader
+        .simple_data_reader
+        .wake_up_after(std::time::Duration::from_secs(secs));
+    };
+
+    wake_up_after(10);
+    // Woken up before this anyway
+    wake_up_after(20);
+    // Replaces the pending one
+    wake_up_after(5);
+    assert_eq!(requested_delays(), vec![10, 5]);
+    // Already due
+    wake_up_after(0);
+    wake_up_after(0);
+    assert_eq!(requested_delays(), vec![0, 0]);
+  }
+
+  #[test]
+  fn disabled_endpoints_are_announced_on_enable() {
+    use crate::{
+      dds::{
+        result::{ReadError, WriteError},
+        with_key::DataWriter,
+      },
+      discovery::discovery_db::discovery_db_read,
+      serialization::CDRSerializerAdapter,
+    };
+
+    let dp = DomainParticipant::new(0).expect("Participant creation failed!");
+    let qos = QosPolicyBuilder::new()
+      .entity_factory(policy::EntityFactory {
+        autoenable_created_entities: false,
+      })
+      .build();
+    let publisher = dp.create_publisher(&qos).unwrap();
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic(
+        "dr entity factory".to_string(),
+        "entity factory test".to_string(),
+        &QosPolicies::qos_none(),
+        TopicKind::WithKey,
+      )
+      .unwrap();
+
+    let mut reader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+    let writer: DataWriter<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher.create_datawriter(&topic, None).unwrap();
+    let data = RandomData {
+      a: 1,
+      b: "enabled".to_string(),
+    };
+    let db_arc = dp.discovery_db();
+
+    // Disabled: not known to Discovery, and no data flows
+    assert!(!reader.is_enabled());
+    assert!(!writer.is_enabled());
+    {
+      let db = discovery_db_read(&db_arc);
+      assert!(db.get_local_topic_reader(reader.guid()).is_none());
+      assert!(db.get_local_topic_writer(writer.guid()).is_none());
+    }
+    assert!(matches!(
+      reader.take_next_sample(),
+      Err(ReadError::NotEnabled)
+    ));
+    assert!(matches!(
+      writer.write(data.clone(), None),
+      Err(WriteError::NotEnabled { .. })
+    ));
+
+    reader.enable().unwrap();
+    writer.enable().unwrap();
+    writer.enable().unwrap(); // no effect
+    assert!(reader.is_enabled());
+    assert!(writer.is_enabled());
+    {
+      let db = discovery_db_read(&db_arc);
+      assert!(db.get_local_topic_reader(reader.guid()).is_some());
+      assert!(db.get_local_topic_writer(writer.guid()).is_some());
+    }
+
+    // Now both have RTPS endpoints behind them
+    writer.write(data, None).unwrap();
+    assert!(reader.take_next_sample().is_ok());
+  }
+
+  #[test]
+  fn dispose_by_key_hash_of_unseen_instance() {
+    use crate::{
+      dds::{ddsdata::DDSData, key::Key, with_key::WriteOptions},
+      structure::cache_change::{CacheChange, ChangeKind},
+    };
+
+    let dp = DomainParticipant::new(0).expect("Participant creation failed!");
+    let qos = QosPolicies::qos_none();
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic(
+        "dr dispose key hash".to_string(),
+        "dispose key hash test".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let mut reader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+
+    // A remote writer disposes an instance that we have never seen alive. The
+    // key (i64) is short, so the key hash is the key itself.
+    let writer_guid = GUID::dummy_test_guid(EntityKind::WRITER_WITH_KEY_USER_DEFINED);
+    let dispose = CacheChange::new(
+      writer_guid,
+      SequenceNumber::from(1),
+      WriteOptions::default(),
+      DDSData::new_disposed_by_key_hash(ChangeKind::NotAliveDisposed, 7_i64.hash_key(false)),
+    );
+    dp.dds_cache()
+      .read()
+      .unwrap()
+      .get_existing_topic_cache(&topic.name())
+      .unwrap()
+      .lock()
+      .unwrap()
+      .add_change(&Timestamp::now(), dispose);
+
+    let sample = reader.take_next_sample().unwrap().unwrap();
+    assert_eq!(sample.into_value(), Sample::Dispose(7));
+  }
+
+  #[test]
+  fn delivery_journal_prevents_redelivery_to_recreated_reader() {
+    use crate::{
+      dds::{ddsdata::DDSData, with_key::WriteOptions},
+      structure::cache_change::CacheChange,
+    };
+
+    let dp = DomainParticipant::new(0).expect("Participant creation failed!");
+    let qos = QosPolicyBuilder::new()
+      .history(policy::History::KeepAll)
+      .build();
+    let sub = dp.create_subscriber(&qos).unwrap();
+    let topic = dp
+      .create_topic(
+        "dr delivery journal".to_string(),
+        "delivery journal test".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let topic_cache = dp
+      .dds_cache()
+      .read()
+      .unwrap()
+      .get_existing_topic_cache(&topic.name())
+      .unwrap();
+
+    // Samples from a remote writer, added directly to the topic cache
+    let writer_guid = GUID::dummy_test_guid(EntityKind::WRITER_WITH_KEY_USER_DEFINED);
+    let t0 = Timestamp::now();
+    let write = |a: i64| {
+      let data = RandomData {
+        a,
+        b: "journal".to_string(),
+      };
+      let cc = CacheChange::new(
+        writer_guid,
+        SequenceNumber::from(a),
+        WriteOptions::default(),
+        DDSData::new(SerializedPayload {
+          representation_identifier: RepresentationIdentifier::CDR_LE,
+          representation_options: [0, 0],
+          value: Bytes::from(to_bytes::<RandomData, LittleEndian>(&data).unwrap()),
+        }),
+      );
+      let received = t0 + crate::Duration::from_millis(a);
+      topic_cache.lock().unwrap().add_change(&received, cc);
+    };
+    let take_all = |reader: &mut DataReader<RandomData, CDRDeserializerAdapter<RandomData>>| {
+      let mut received = Vec::new();
+      while let Some(sample) = reader.take_next_sample().unwrap() {
+        received.push(sample.into_value().unwrap().a);
+      }
+      received
+    };
+    let retention = std::time::Duration::from_secs(60);
+
+    // Without a journal, a recreated DataReader would get the samples again
+    // from the topic cache, like this one does.
+    let mut plain_reader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+    let mut reader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+    reader.set_delivery_journal("journal test", retention);
+    write(1);
+    write(2);
+    assert_eq!(take_all(&mut reader), vec![1, 2]);
+    drop(reader);
+
+    let mut reader = sub
+      .create_datareader::<RandomData, CDRDeserializerAdapter<RandomData>>(&topic, None)
+      .unwrap();
+    reader.set_delivery_journal("journal test", retention);
+    write(3);
+    assert_eq!(take_all(&mut plain_reader), vec![1, 2, 3]);
+    assert_eq!(take_all(&mut reader), vec![3]);
+  }
 }
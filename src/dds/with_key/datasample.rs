@@ -1,7 +1,12 @@
+use std::time::Instant;
+
 use crate::{
   dds::{key::*, sampleinfo::*, with_key::datawriter::WriteOptions},
   structure::{
-    cache_change::CacheChange, guid::GUID, sequence_number::SequenceNumber, time::Timestamp,
+    cache_change::{CacheChange, ChangeKind},
+    guid::GUID,
+    sequence_number::SequenceNumber,
+    time::Timestamp,
   },
 };
 
@@ -124,6 +129,8 @@ pub struct DeserializedCacheChange<D: Keyed> {
   pub(crate) writer_guid: GUID,               // 8 bytes
   pub(crate) sequence_number: SequenceNumber, // 8 bytes
   pub(crate) write_options: WriteOptions,     // 16 bytes
+  pub(crate) reception_instant: Instant,      // monotonic pair of receive_instant
+  pub(crate) change_kind: ChangeKind,         // tells apart dispose and unregister
 
   // the data sample (or key) itself is stored here
   pub(crate) sample: Sample<D, D::K>, /* TODO: make this a Box<> for easier detaching an
@@ -137,6 +144,8 @@ impl<D: Keyed> DeserializedCacheChange<D> {
       writer_guid: cc.writer_guid,
       sequence_number: cc.sequence_number,
       write_options: cc.write_options.clone(),
+      reception_instant: cc.reception_instant,
+      change_kind: cc.data_value.change_kind(),
       sample: deserialized,
     }
   }
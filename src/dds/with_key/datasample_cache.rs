@@ -1,6 +1,7 @@
 use std::{
   collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
   ops::Bound,
+  time::Instant,
 };
 
 #[allow(unused_imports)]
@@ -8,13 +9,17 @@ use log::{debug, error, info, warn};
 
 use crate::{
   dds::{
-    key::Keyed,
+    key::{InstanceHandle, Keyed},
     qos::{policy, QosPolicies},
     readcondition::ReadCondition,
     sampleinfo::*,
+    statusevents::SampleRejectedStatusKind,
     with_key::datasample::{DataSample, DeserializedCacheChange, Sample},
   },
-  structure::{guid::GUID, sequence_number::SequenceNumber, time::Timestamp},
+  structure::{
+    cache_change::ChangeKind, duration::Duration, guid::GUID, sequence_number::SequenceNumber,
+    time::Timestamp,
+  },
   with_key::WriteOptions,
 };
 
@@ -32,13 +37,31 @@ pub struct DataSampleCache<D: Keyed> {
   datasamples: BTreeMap<Timestamp, SampleWithMetaData<D>>, /* ordered storage for deserialized
                                                             * samples */
   pub(crate) instance_map: BTreeMap<D::K, InstanceMetaData>, // ordered storage for instances
+  // instances ordered for InstanceLimit eviction: not alive first, then by last update
+  eviction_order: BTreeSet<(bool, u64, D::K)>,
+  update_count: u64,            // for InstanceMetaData.last_update
+  evicted_instances: Vec<D::K>, // removed due to InstanceLimit, not yet taken
 }
 
 pub(crate) struct InstanceMetaData {
+  instance_handle: InstanceHandle, // computed once, when the instance is created
   instance_samples: BTreeSet<Timestamp>, // which samples belong to this instance
-  instance_state: InstanceState,         // latest known alive/not_alive state for this instance
+  instance_state: InstanceState,   // latest known alive/not_alive state for this instance
   latest_generation_available: NotAliveGenerationCounts, // in this instance
   last_generation_accessed: NotAliveGenerationCounts, // in this instance
+  registered_writers: BTreeSet<GUID>, // writers that have written and not unregistered
+  not_alive_since: Option<Instant>, // when instance_state last changed from Alive
+  last_update: u64,                // update_count at latest sample, for InstanceLimit eviction
+}
+
+impl InstanceMetaData {
+  fn eviction_order<K: Clone>(&self, key: &K) -> (bool, u64, K) {
+    (
+      self.instance_state == InstanceState::Alive,
+      self.last_update,
+      key.clone(),
+    )
+  }
 }
 
 struct SampleWithMetaData<D: Keyed> {
@@ -49,6 +72,8 @@ struct SampleWithMetaData<D: Keyed> {
   sequence_number: SequenceNumber, // as sent by the Writer
   write_options: WriteOptions,     // as stamped by Writer
   sample_has_been_read: bool,      // sample_state
+  reception_timestamp: Timestamp,  // wall-clock reception time
+  reception_instant: Instant,      // monotonic reception time
 
   // the data sample (or key) itself is stored here
   sample: Sample<D, D::K>,
@@ -75,39 +100,62 @@ where
       qos,
       datasamples: BTreeMap::new(),
       instance_map: BTreeMap::new(),
+      eviction_order: BTreeSet::new(),
+      update_count: 0,
+      evicted_instances: Vec::new(),
     }
   }
 
+  // Returns Err, if the sample was rejected due to ResourceLimits.
   pub(crate) fn fill_from_deserialized_cache_change(
     &mut self,
     deserialized_cc: DeserializedCacheChange<D>,
-  ) {
+  ) -> Result<(), SampleRejectedStatusKind> {
     // TODO list.
 
     self.add_sample(
       deserialized_cc.sample,
+      deserialized_cc.change_kind,
       deserialized_cc.writer_guid,
       deserialized_cc.sequence_number,
       deserialized_cc.receive_instant,
+      deserialized_cc.reception_instant,
       deserialized_cc.write_options,
-    );
+    )
   }
 
+  #[allow(clippy::too_many_arguments)]
   fn add_sample(
     &mut self,
     new_sample: Sample<D, D::K>,
+    change_kind: ChangeKind,
     writer_guid: GUID,
     sequence_number: SequenceNumber,
     receive_timestamp: Timestamp,
+    reception_instant: Instant,
     write_options: WriteOptions,
-  ) {
+  ) -> Result<(), SampleRejectedStatusKind> {
     let instance_key = match &new_sample {
       Sample::Value(d) => d.key(),
       Sample::Dispose(k) => k.clone(),
     };
 
+    let is_unregister =
+      matches!(new_sample, Sample::Dispose(_)) && change_kind == ChangeKind::NotAliveUnregistered;
+
+    // Unregistering an instance we know nothing about has no effect.
+    if is_unregister && !self.instance_map.contains_key(&instance_key) {
+      return Ok(());
+    }
+
+    self.check_resource_limits(&instance_key)?;
+    if !self.instance_map.contains_key(&instance_key) {
+      self.make_room_for_new_instance()?;
+    }
+
     let new_instance_state = match new_sample {
       Sample::Value(_) => InstanceState::Alive,
+      Sample::Dispose(_) if is_unregister => InstanceState::NotAliveNoWriters,
       Sample::Dispose(_) => InstanceState::NotAliveDisposed,
     };
 
@@ -117,11 +165,15 @@ where
     } else {
       // not found, create new one.
       let imd = InstanceMetaData {
+        instance_handle: InstanceHandle::from_key(&instance_key),
         instance_samples: BTreeSet::new(),
         instance_state: new_instance_state,
         latest_generation_available: NotAliveGenerationCounts::zero(), /* this is new instance,
                                                                         * so start from zero */
         last_generation_accessed: NotAliveGenerationCounts::sub_zero(), // never accessed
+        registered_writers: BTreeSet::new(),
+        not_alive_since: None,
+        last_update: 0,
       };
       self.instance_map.insert(instance_key.clone(), imd);
       self
@@ -131,8 +183,30 @@ where
         .unwrap()
     };
 
+    // An unregister makes the instance NotAliveNoWriters only when the last
+    // writer unregisters an Alive instance. Otherwise the state does not change,
+    // and there is nothing to tell the application.
+    let new_instance_state = if is_unregister {
+      instance_metadata.registered_writers.remove(&writer_guid);
+      if instance_metadata.registered_writers.is_empty()
+        && instance_metadata.instance_state == InstanceState::Alive
+      {
+        new_instance_state
+      } else {
+        return Ok(());
+      }
+    } else {
+      instance_metadata.registered_writers.insert(writer_guid);
+      new_instance_state
+    };
+
     // update instance metadata
     instance_metadata.instance_samples.insert(receive_timestamp);
+    self
+      .eviction_order
+      .remove(&instance_metadata.eviction_order(&instance_key));
+    self.update_count += 1;
+    instance_metadata.last_update = self.update_count;
 
     match (instance_metadata.instance_state, new_instance_state) {
       (InstanceState::Alive, _) => (), // was Alive, does not change counts
@@ -157,7 +231,17 @@ where
 
       (InstanceState::NotAliveNoWriters, _) => (), // you can only die once
     }
+    instance_metadata.not_alive_since = match new_instance_state {
+      InstanceState::Alive => None,
+      _ if new_instance_state != instance_metadata.instance_state => Some(reception_instant),
+      _ => instance_metadata
+        .not_alive_since
+        .or(Some(reception_instant)),
+    };
     instance_metadata.instance_state = new_instance_state;
+    self
+      .eviction_order
+      .insert(instance_metadata.eviction_order(&instance_key));
 
     // insert new_sample to main table
     self
@@ -170,6 +254,8 @@ where
           sequence_number,
           write_options,
           sample_has_been_read: false,
+          reception_timestamp: receive_timestamp,
+          reception_instant,
           sample: new_sample,
         },
       )
@@ -221,8 +307,172 @@ where
       }
     }
 
-    // TODO: Implement other resource_limit settings than max_instances_per
-    // sample, i.e.
+    Ok(())
+  }
+
+  // Check if adding a sample to the given instance would exceed
+  // ResourceLimits. See DDS Spec v1.4 Section 2.2.3.19 RESOURCE_LIMITS.
+  //
+  // Negative limits (LENGTH_UNLIMITED) are not enforced.
+  fn check_resource_limits(&self, instance_key: &D::K) -> Result<(), SampleRejectedStatusKind> {
+    let limits = match self.qos.resource_limits {
+      Some(limits) => limits,
+      None => return Ok(()),
+    };
+    let limit = |l: i32| usize::try_from(l).ok();
+
+    let instance_sample_count = self
+      .instance_map
+      .get(instance_key)
+      .map(|imd| imd.instance_samples.len());
+
+    match (instance_sample_count, self.qos.history()) {
+      (None, _) => {
+        // This would be a new instance. Instances that are not alive and have no
+        // samples left are no longer counted.
+        if let Some(max_instances) = limit(limits.max_instances) {
+          let instance_count = self
+            .instance_map
+            .values()
+            .filter(|imd| {
+              imd.instance_state == InstanceState::Alive || !imd.instance_samples.is_empty()
+            })
+            .count();
+          if instance_count >= max_instances {
+            return Err(SampleRejectedStatusKind::ByInstancesLimit);
+          }
+        }
+      }
+      (Some(count), Some(policy::History::KeepAll)) => {
+        // With KeepLast, the oldest sample of the instance would be removed instead.
+        if limit(limits.max_samples_per_instance).is_some_and(|max| count >= max) {
+          return Err(SampleRejectedStatusKind::BySamplesPerInstanceLimit);
+        }
+      }
+      (Some(_), _) => (),
+    }
+
+    // With KeepLast, a full instance replaces its oldest sample, so the total
+    // does not grow.
+    let grows_total = match self.qos.history() {
+      Some(policy::History::KeepAll) => true,
+      Some(policy::History::KeepLast { depth }) => {
+        (instance_sample_count.unwrap_or(0) as i64) < i64::from(depth)
+      }
+      None => instance_sample_count.unwrap_or(0) < 1, // default is KeepLast(1)
+    };
+    if grows_total && limit(limits.max_samples).is_some_and(|max| self.datasamples.len() >= max) {
+      return Err(SampleRejectedStatusKind::BySamplesLimit);
+    }
+
+    Ok(())
+  }
+
+  // Make room for a new instance according to InstanceLimit, if needed, by
+  // evicting an existing instance together with its samples. Not-alive
+  // instances are evicted first, least recently updated first.
+  fn make_room_for_new_instance(&mut self) -> Result<(), SampleRejectedStatusKind> {
+    let policy::InstanceLimit {
+      max_instances,
+      eviction,
+    } = match self.qos.instance_limit() {
+      Some(il) => il,
+      None => return Ok(()),
+    };
+    if self.instance_map.len() < max_instances as usize {
+      return Ok(());
+    }
+
+    let victim = match eviction {
+      policy::InstanceEviction::Reject => None,
+      policy::InstanceEviction::NotAlive => {
+        self.eviction_order.first().filter(|(alive, ..)| !alive)
+      }
+      policy::InstanceEviction::LeastRecentlyUpdated => self.eviction_order.first(),
+    }
+    .map(|(_alive, _last_update, key)| key.clone());
+
+    match victim.and_then(|key| self.remove_instance(key)) {
+      Some((key, imd)) => {
+        debug!(
+          "Instance limit {} reached. Evicting instance {:?} ({:?}) with {} samples.",
+          max_instances,
+          imd.instance_handle,
+          imd.instance_state,
+          imd.instance_samples.len()
+        );
+        self.evicted_instances.push(key);
+        Ok(())
+      }
+      None => Err(SampleRejectedStatusKind::ByInstancesLimit),
+    }
+  }
+
+  // Remove an instance together with its samples
+  fn remove_instance(&mut self, key: D::K) -> Option<(D::K, InstanceMetaData)> {
+    let imd = self.instance_map.remove(&key)?;
+    for ts in &imd.instance_samples {
+      self.datasamples.remove(ts);
+    }
+    self.eviction_order.remove(&imd.eviction_order(&key));
+    Some((key, imd))
+  }
+
+  // Keys of instances evicted due to InstanceLimit since the previous call
+  pub fn take_evicted_instances(&mut self) -> Vec<D::K> {
+    std::mem::take(&mut self.evicted_instances)
+  }
+
+  pub fn set_qos(&mut self, qos: QosPolicies) {
+    self.qos = qos;
+  }
+
+  // Remove instances that have been NotAliveNoWriters or NotAliveDisposed for
+  // at least the delay given in ReaderDataLifecycle, together with all of their
+  // samples. See DDS Spec v1.4 Section 2.2.3.22 READER_DATA_LIFECYCLE.
+  //
+  // Returns the keys of the purged instances.
+  pub fn purge_not_alive_instances(&mut self, now: Instant) -> Vec<D::K> {
+    let purged: Vec<D::K> = self
+      .instance_map
+      .iter()
+      .filter(|(_key, imd)| {
+        self
+          .autopurge_deadline(imd)
+          .is_some_and(|deadline| deadline <= now)
+      })
+      .map(|(key, _imd)| key.clone())
+      .collect();
+
+    for key in &purged {
+      self.remove_instance(key.clone());
+    }
+    purged
+  }
+
+  // When purge_not_alive_instances() next has something to do, if ever.
+  pub fn next_autopurge(&self) -> Option<Instant> {
+    self
+      .instance_map
+      .values()
+      .filter_map(|imd| self.autopurge_deadline(imd))
+      .min()
+  }
+
+  fn autopurge_deadline(&self, imd: &InstanceMetaData) -> Option<Instant> {
+    let policy::ReaderDataLifecycle {
+      autopurge_nowriter_samples_delay,
+      autopurge_disposed_samples_delay,
+    } = self.qos.reader_data_lifecycle()?; // default is infinite delay
+    let delay = match imd.instance_state {
+      InstanceState::Alive => return None,
+      InstanceState::NotAliveNoWriters => autopurge_nowriter_samples_delay,
+      InstanceState::NotAliveDisposed => autopurge_disposed_samples_delay,
+    };
+    if delay == Duration::INFINITE {
+      return None;
+    }
+    imd.not_alive_since.map(|since| since + delay.to_std())
   }
 
   // Calling select_(instance)_keys_for access does not constitute access, i.e.
@@ -318,6 +568,7 @@ where
         ViewState::NotNew
       },
       instance_state: imd.instance_state,
+      instance_handle: imd.instance_handle,
       generation_counts: dswm.generation_counts,
       sample_rank: sample_rank as i32, // how many samples follow this one
       generation_rank: mrsic_generations - dswm.generation_counts.total(),
@@ -325,6 +576,8 @@ where
       write_options: dswm.write_options.clone(),
       publication_handle: dswm.writer_guid,
       sequence_number: dswm.sequence_number,
+      reception_timestamp: dswm.reception_timestamp,
+      reception_instant: dswm.reception_instant,
     }
   }
 
@@ -451,7 +704,8 @@ where
     // collect result
     for (index, (ts, key)) in keys.iter().enumerate() {
       let dswm = self.datasamples.remove(ts).unwrap();
-      let imd = self.instance_map.get(key).unwrap();
+      let imd = self.instance_map.get_mut(key).unwrap();
+      imd.instance_samples.remove(ts);
       let sample_info = Self::make_sample_info(&dswm, imd, len - index - 1, mrs_total, mrsic_total);
       // dwsm.sample_has_been_read = true; // no need to mark read, as the dswm is
       // about to be destroyed
@@ -514,6 +768,9 @@ where
 
     for (ts, key) in keys.iter() {
       let dswm = self.datasamples.remove(ts).unwrap();
+      if let Some(imd) = self.instance_map.get_mut(key) {
+        imd.instance_samples.remove(ts);
+      }
       // dwsm.sample_has_been_read = true; // no need to mark read, as the dswm is
       // about to be destroyed
       Self::record_instance_generation_viewed(
@@ -528,6 +785,87 @@ where
     result
   }
 
+  // Latest data value of each instance, if it matches `rc`. This does not
+  // constitute access: samples are not marked read and instances are not
+  // marked viewed.
+  pub fn snapshot(&self, rc: ReadCondition) -> HashMap<D::K, DataSample<&D>> {
+    let mrs_total = match self.datasamples.values().next_back() {
+      Some(dswm) => dswm.generation_counts.total(),
+      None => return HashMap::new(),
+    };
+
+    self
+      .instance_map
+      .iter()
+      .filter_map(|(key, imd)| {
+        // Skip over possible dispose samples to the latest value
+        let dswm = imd
+          .instance_samples
+          .iter()
+          .rev()
+          .filter_map(|ts| self.datasamples.get(ts))
+          .find(|dswm| matches!(dswm.sample, Sample::Value(_)))?;
+        if !self.sample_selector(&rc, imd, dswm) {
+          return None;
+        }
+        let sample_info = Self::make_sample_info(
+          dswm,
+          imd,
+          0,
+          mrs_total,
+          imd.latest_generation_available.total(),
+        );
+        Some((
+          key.clone(),
+          DataSample::new(sample_info, result_ok_as_ref_err_clone(&dswm.sample)),
+        ))
+      })
+      .collect()
+  }
+
+  // All known instances in key order, with the SampleInfo of the latest sample
+  // of each instance, if any samples are still in the cache. Like snapshot(),
+  // this does not constitute access.
+  pub fn instances(&self) -> Vec<(InstanceHandle, D::K, InstanceState, Option<SampleInfo>)> {
+    let mrs_total = self
+      .datasamples
+      .values()
+      .next_back()
+      .map(|dswm| dswm.generation_counts.total());
+
+    self
+      .instance_map
+      .iter()
+      .map(|(key, imd)| {
+        let latest_sample_info = imd
+          .instance_samples
+          .iter()
+          .next_back()
+          .and_then(|ts| self.datasamples.get(ts))
+          .zip(mrs_total)
+          .map(|(dswm, mrs_total)| {
+            Self::make_sample_info(
+              dswm,
+              imd,
+              0,
+              mrs_total,
+              imd.latest_generation_available.total(),
+            )
+          });
+        (
+          imd.instance_handle,
+          key.clone(),
+          imd.instance_state,
+          latest_sample_info,
+        )
+      })
+      .collect()
+  }
+
+  pub fn lookup_instance(&self, key: &D::K) -> Option<InstanceHandle> {
+    self.instance_map.get(key).map(|imd| imd.instance_handle)
+  }
+
   pub fn next_key(&self, key: &D::K) -> Option<D::K> {
     self
       .instance_map
@@ -548,13 +886,352 @@ pub(crate) fn result_ok_as_ref_err_clone<T, E: Clone>(r: &Sample<T, E>) -> Sampl
 
 #[cfg(test)]
 mod tests {
-  // use super::*;
-  // use crate::{
-  //   structure::{time::Timestamp},
-  // };
-  // use crate::dds::ddsdata::DDSData;
-  // use crate::dds::traits::key::Keyed;
-  // use crate::test::random_data::*;
+  use super::*;
+  use crate::{
+    structure::guid::{EntityId, GuidPrefix},
+    test::random_data::RandomData,
+  };
+
+  fn writer_guid(n: u8) -> GUID {
+    GUID::new(GuidPrefix::new(&[n; 12]), EntityId::UNKNOWN)
+  }
+
+  // Adds a sample with a unique reception timestamp
+  fn add(
+    dsc: &mut DataSampleCache<RandomData>,
+    sample: Sample<RandomData, i64>,
+    change_kind: ChangeKind,
+    writer: GUID,
+  ) {
+    try_add(dsc, sample, change_kind, writer).unwrap();
+  }
+
+  fn try_add(
+    dsc: &mut DataSampleCache<RandomData>,
+    sample: Sample<RandomData, i64>,
+    change_kind: ChangeKind,
+    writer: GUID,
+  ) -> Result<(), SampleRejectedStatusKind> {
+    let ts = *dsc
+      .datasamples
+      .keys()
+      .next_back()
+      .unwrap_or(&Timestamp::ZERO)
+      + Duration::from_millis(1);
+    dsc.add_sample(
+      sample,
+      change_kind,
+      writer,
+      SequenceNumber::default(),
+      ts,
+      Instant::now(),
+      WriteOptions::default(),
+    )
+  }
+
+  fn value(a: i64) -> Sample<RandomData, i64> {
+    Sample::Value(RandomData {
+      a,
+      b: "x".to_string(),
+    })
+  }
+
+  fn state(dsc: &DataSampleCache<RandomData>, key: i64) -> Option<InstanceState> {
+    dsc.instance_map.get(&key).map(|imd| imd.instance_state)
+  }
+
+  #[test]
+  fn dsc_reader_data_lifecycle() {
+    let qos = QosPolicies::builder()
+      .history(policy::History::KeepAll)
+      .reader_data_lifecycle(policy::ReaderDataLifecycle {
+        autopurge_nowriter_samples_delay: Duration::INFINITE,
+        autopurge_disposed_samples_delay: Duration::ZERO,
+      })
+      .build();
+    let mut dsc = DataSampleCache::<RandomData>::new(qos);
+    let (w1, w2) = (writer_guid(1), writer_guid(2));
+
+    add(&mut dsc, value(1), ChangeKind::Alive, w1);
+    add(&mut dsc, value(2), ChangeKind::Alive, w1);
+    add(&mut dsc, value(2), ChangeKind::Alive, w2);
+
+    // Unregistering an unknown instance does nothing
+    add(
+      &mut dsc,
+      Sample::Dispose(3),
+      ChangeKind::NotAliveUnregistered,
+      w1,
+    );
+    assert_eq!(state(&dsc, 3), None);
+
+    // Instance 2 stays alive until both writers have unregistered
+    add(
+      &mut dsc,
+      Sample::Dispose(2),
+      ChangeKind::NotAliveUnregistered,
+      w1,
+    );
+    assert_eq!(state(&dsc, 2), Some(InstanceState::Alive));
+    assert_eq!(dsc.datasamples.len(), 3);
+    add(
+      &mut dsc,
+      Sample::Dispose(2),
+      ChangeKind::NotAliveUnregistered,
+      w2,
+    );
+    assert_eq!(state(&dsc, 2), Some(InstanceState::NotAliveNoWriters));
+
+    add(
+      &mut dsc,
+      Sample::Dispose(1),
+      ChangeKind::NotAliveDisposed,
+      w1,
+    );
+    assert_eq!(state(&dsc, 1), Some(InstanceState::NotAliveDisposed));
+
+    // Disposed instance is purged with its samples, the other one is kept
+    assert_eq!(dsc.purge_not_alive_instances(Instant::now()), vec![1]);
+    assert_eq!(state(&dsc, 1), None);
+    assert_eq!(dsc.datasamples.len(), 3);
+
+    // Without the policy, nothing is purged
+    add(
+      &mut dsc,
+      Sample::Dispose(2),
+      ChangeKind::NotAliveDisposed,
+      w2,
+    );
+    dsc.set_qos(QosPolicies::qos_none());
+    assert!(dsc.purge_not_alive_instances(Instant::now()).is_empty());
+    assert_eq!(state(&dsc, 2), Some(InstanceState::NotAliveDisposed));
+    assert_eq!(dsc.next_autopurge(), None);
+  }
+
+  #[test]
+  fn dsc_next_autopurge() {
+    let delay = Duration::from_secs(10);
+    let qos = QosPolicies::builder()
+      .reader_data_lifecycle(policy::ReaderDataLifecycle {
+        autopurge_nowriter_samples_delay: Duration::INFINITE,
+        autopurge_disposed_samples_delay: delay,
+      })
+      .build();
+    let mut dsc = DataSampleCache::<RandomData>::new(qos);
+    let w1 = writer_guid(1);
+
+    add(&mut dsc, value(1), ChangeKind::Alive, w1);
+    add(&mut dsc, value(2), ChangeKind::Alive, w1);
+    assert_eq!(dsc.next_autopurge(), None);
+
+    // Infinite delay is never due
+    add(
+      &mut dsc,
+      Sample::Dispose(2),
+      ChangeKind::NotAliveUnregistered,
+      w1,
+    );
+    assert_eq!(dsc.next_autopurge(), None);
+
+    let before = Instant::now();
+    add(
+      &mut dsc,
+      Sample::Dispose(1),
+      ChangeKind::NotAliveDisposed,
+      w1,
+    );
+    let due = dsc.next_autopurge().unwrap();
+    assert!(due >= before + delay.to_std());
+    assert!(dsc
+      .purge_not_alive_instances(due - delay.to_std())
+      .is_empty());
+    assert_eq!(dsc.purge_not_alive_instances(due), vec![1]);
+    assert_eq!(dsc.next_autopurge(), None);
+  }
+
+  #[test]
+  fn dsc_instance_limit_eviction() {
+    let limit = |eviction| {
+      QosPolicies::builder()
+        .history(policy::History::KeepAll)
+        .instance_limit(policy::InstanceLimit {
+          max_instances: 2,
+          eviction,
+        })
+        .build()
+    };
+    let mut dsc = DataSampleCache::<RandomData>::new(limit(policy::InstanceEviction::NotAlive));
+    let w = writer_guid(1);
+
+    add(&mut dsc, value(1), ChangeKind::Alive, w);
+    add(&mut dsc, value(2), ChangeKind::Alive, w);
+
+    // Full, and all instances are alive
+    assert_eq!(
+      try_add(&mut dsc, value(3), ChangeKind::Alive, w),
+      Err(SampleRejectedStatusKind::ByInstancesLimit)
+    );
+
+    // A disposed instance is evicted with its samples
+    add(
+      &mut dsc,
+      Sample::Dispose(1),
+      ChangeKind::NotAliveDisposed,
+      w,
+    );
+    add(&mut dsc, value(3), ChangeKind::Alive, w);
+    assert_eq!(state(&dsc, 1), None);
+    assert_eq!(dsc.take_evicted_instances(), vec![1]);
+    assert_eq!(dsc.datasamples.len(), 2);
+
+    // Known instances are not affected by the limit
+    add(&mut dsc, value(2), ChangeKind::Alive, w);
+
+    // Instance 3 is now the least recently updated
+    dsc.set_qos(limit(policy::InstanceEviction::LeastRecentlyUpdated));
+    add(&mut dsc, value(4), ChangeKind::Alive, w);
+    assert_eq!(state(&dsc, 3), None);
+    assert_eq!(state(&dsc, 2), Some(InstanceState::Alive));
+    assert_eq!(dsc.take_evicted_instances(), vec![3]);
+
+    dsc.set_qos(limit(policy::InstanceEviction::Reject));
+    add(
+      &mut dsc,
+      Sample::Dispose(2),
+      ChangeKind::NotAliveDisposed,
+      w,
+    );
+    assert_eq!(
+      try_add(&mut dsc, value(5), ChangeKind::Alive, w),
+      Err(SampleRejectedStatusKind::ByInstancesLimit)
+    );
+    assert!(dsc.take_evicted_instances().is_empty());
+  }
+
+  fn states(samples: &[DataSample<&RandomData>]) -> Vec<(i64, SampleState, ViewState)> {
+    samples
+      .iter()
+      .map(|ds| {
+        (
+          ds.key(),
+          ds.sample_info().sample_state(),
+          ds.sample_info().view_state(),
+        )
+      })
+      .collect()
+  }
+
+  #[test]
+  fn dsc_read_and_take_states() {
+    let qos = QosPolicies::builder()
+      .history(policy::History::KeepAll)
+      .resource_limits(policy::ResourceLimits {
+        max_samples: -1,
+        max_instances: -1,
+        max_samples_per_instance: 2,
+      })
+      .build();
+    let mut dsc = DataSampleCache::<RandomData>::new(qos);
+    let w = writer_guid(1);
+
+    add(&mut dsc, value(1), ChangeKind::Alive, w);
+    add(&mut dsc, value(2), ChangeKind::Alive, w);
+
+    // Reading does not consume, but marks samples read and instances viewed
+    let keys = dsc.select_keys_for_access(ReadCondition::not_read());
+    assert_eq!(
+      states(&dsc.read_by_keys(&keys)),
+      vec![
+        (1, SampleState::NotRead, ViewState::New),
+        (2, SampleState::NotRead, ViewState::New)
+      ]
+    );
+    assert!(dsc
+      .select_keys_for_access(ReadCondition::not_read())
+      .is_empty());
+    let keys = dsc.select_keys_for_access(ReadCondition::any());
+    assert_eq!(
+      states(&dsc.read_by_keys(&keys)),
+      vec![
+        (1, SampleState::Read, ViewState::NotNew),
+        (2, SampleState::Read, ViewState::NotNew)
+      ]
+    );
+
+    // A new sample of a viewed instance is not read, but not new either
+    add(&mut dsc, value(1), ChangeKind::Alive, w);
+    let mut keys = dsc.select_keys_for_access(ReadCondition::not_read());
+    keys.truncate(1); // as in read_next_sample
+    assert_eq!(
+      states(&dsc.read_by_keys(&keys)),
+      vec![(1, SampleState::NotRead, ViewState::NotNew)]
+    );
+
+    // Taking consumes the samples, also from the instance
+    let keys = dsc.select_instance_keys_for_access(&1, ReadCondition::any());
+    assert_eq!(dsc.take_by_keys(&keys).len(), 2);
+    assert!(dsc.instance_map[&1].instance_samples.is_empty());
+    assert_eq!(dsc.select_keys_for_access(ReadCondition::any()).len(), 1);
+
+    // Taken samples no longer count against the instance limits, and the
+    // instance is still viewed
+    add(&mut dsc, value(1), ChangeKind::Alive, w);
+    add(&mut dsc, value(1), ChangeKind::Alive, w);
+    let keys = dsc.select_instance_keys_for_access(&1, ReadCondition::any());
+    assert_eq!(
+      states(&dsc.read_by_keys(&keys)),
+      vec![
+        (1, SampleState::NotRead, ViewState::NotNew),
+        (1, SampleState::NotRead, ViewState::NotNew)
+      ]
+    );
+  }
+
+  #[test]
+  fn dsc_instances_do_not_access_samples() {
+    let qos = QosPolicies::builder()
+      .history(policy::History::KeepAll)
+      .build();
+    let mut dsc = DataSampleCache::<RandomData>::new(qos);
+    let w = writer_guid(1);
+
+    add(&mut dsc, value(2), ChangeKind::Alive, w);
+    add(&mut dsc, value(1), ChangeKind::Alive, w);
+    add(&mut dsc, value(2), ChangeKind::Alive, w);
+    add(
+      &mut dsc,
+      Sample::Dispose(1),
+      ChangeKind::NotAliveDisposed,
+      w,
+    );
+
+    let instances = dsc.instances();
+    let keys: Vec<i64> = instances.iter().map(|(_, k, _, _)| *k).collect();
+    assert_eq!(keys, vec![1, 2]);
+    assert_eq!(instances[0].2, InstanceState::NotAliveDisposed);
+    assert_eq!(instances[1].2, InstanceState::Alive);
+    assert_eq!(dsc.lookup_instance(&1), Some(instances[0].0));
+    assert_eq!(dsc.lookup_instance(&2), Some(instances[1].0));
+    assert_ne!(instances[0].0, instances[1].0);
+    assert_eq!(dsc.lookup_instance(&3), None);
+
+    // The SampleInfo is that of the latest sample, and it matches what read
+    // returns
+    let latest = instances[1].3.clone().unwrap();
+    assert_eq!(latest.instance_handle(), instances[1].0);
+    assert_eq!(latest.sample_state(), SampleState::NotRead);
+    assert_eq!(latest.view_state(), ViewState::New);
+    assert_eq!(
+      dsc.select_keys_for_access(ReadCondition::not_read()).len(),
+      4
+    );
+
+    // Taking all samples leaves the instance without a SampleInfo
+    let keys = dsc.select_instance_keys_for_access(&2, ReadCondition::any());
+    dsc.take_by_keys(&keys);
+    assert_eq!(dsc.instances()[1].3, None);
+    assert_eq!(dsc.lookup_instance(&2), Some(instances[1].0));
+  }
 
   #[test]
   fn dsc_empty_qos() {
@@ -1,45 +1,60 @@
 use std::{
+  io,
   marker::PhantomData,
   pin::Pin,
   sync::{
-    atomic::{AtomicI64, Ordering},
-    Arc, Mutex,
+    atomic::{AtomicI64, AtomicU64, Ordering},
+    Arc, Mutex, MutexGuard,
   },
   task::{Context, Poll, Waker},
   time::{Duration, Instant},
 };
+#[cfg(not(target_os = "windows"))]
+use std::os::fd::RawFd;
 
+use bytes::{buf::UninitSlice, BufMut, Bytes, BytesMut};
 use futures::{Future, Stream};
 use mio_06::{self, Evented, Events, PollOpt, Ready, Token};
 use mio_extras::channel::{self as mio_channel, SendError, TrySendError};
+use speedy::Endianness;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
+#[cfg(feature = "opentelemetry")]
+use opentelemetry::trace::SpanContext;
 
 use crate::{
   dds::{
     adapters::with_key::SerializerAdapter,
     dds_entity::DDSEntity,
     ddsdata::DDSData,
+    delivery::{DeliveryListener, DeliveryListenerSlot},
     helpers::*,
+    key::{Key, KeyHash},
     pubsub::Publisher,
     qos::{
       policy::{Liveliness, Reliability},
-      HasQoSPolicy, QosPolicies,
+      HasQoSPolicy, MutQosPolicy, QosPolicies,
     },
-    result::{CreateResult, WriteError, WriteResult},
+    result::{CreateResult, QosError, WriteError, WriteResult},
     statusevents::*,
-    topic::Topic,
+    topic::{Topic, TopicKind},
   },
-  discovery::{discovery::DiscoveryCommand, sedp_messages::SubscriptionBuiltinTopicData},
-  messages::submessages::elements::serialized_payload::SerializedPayload,
-  rtps::writer::WriterCommand,
+  discovery::{
+    discovery::DiscoveryCommand, discovery_db::discovery_db_read,
+    sedp_messages::SubscriptionBuiltinTopicData,
+  },
+  messages::submessages::elements::{inline_qos::InlineQos, serialized_payload::SerializedPayload},
+  rtps::writer::{RoundTripTimes, WriterCommand, WriterIngredients},
   serialization::CDRSerializerAdapter,
+  stats::SerializationStats,
   structure::{
-    cache_change::ChangeKind, duration, entity::RTPSEntity, guid::GUID, rpc::SampleIdentity,
-    sequence_number::SequenceNumber, time::Timestamp,
+    cache_change::ChangeKind, duration, entity::RTPSEntity, guid::GUID, parameter_id::ParameterId,
+    rpc::SampleIdentity, sequence_number::SequenceNumber, time::Timestamp,
   },
-  Keyed, TopicDescription,
+  Keyed, RepresentationIdentifier, TopicDescription,
 };
+#[cfg(feature = "opentelemetry")]
+use crate::dds::trace_context;
 
 // TODO: Move the write options and the builder type to some lower-level module
 // to avoid circular dependencies.
@@ -48,6 +63,8 @@ pub struct WriteOptionsBuilder {
   related_sample_identity: Option<SampleIdentity>,
   source_timestamp: Option<Timestamp>,
   to_single_reader: Option<GUID>,
+  inline_qos: Vec<InlineQosParameter>,
+  rustdds_parameters: Vec<InlineQosParameter>,
 }
 
 impl WriteOptionsBuilder {
@@ -60,6 +77,8 @@ impl WriteOptionsBuilder {
       related_sample_identity: self.related_sample_identity,
       source_timestamp: self.source_timestamp,
       to_single_reader: self.to_single_reader,
+      inline_qos: self.inline_qos,
+      rustdds_parameters: self.rustdds_parameters,
     }
   }
 
@@ -89,6 +108,76 @@ impl WriteOptionsBuilder {
     self.to_single_reader = Some(reader);
     self
   }
+
+  /// Propagate the given OpenTelemetry span context with the sample. Without
+  /// this, the span context of the current OpenTelemetry `Context` is
+  /// propagated, if there is one.
+  #[cfg(feature = "opentelemetry")]
+  #[must_use]
+  pub fn trace_context(self, span_context: &SpanContext) -> Self {
+    self.rustdds_parameter(trace_context::to_inline_qos(span_context))
+  }
+
+  /// Attach an inline QoS parameter to the sample. Can be called repeatedly
+  /// to attach several parameters. They are sent in the DATA (or each
+  /// DATA_FRAG) submessage in addition to those RustDDS generates itself.
+  ///
+  /// Writing the sample fails with [`WriteError::BadParameter`], if the
+  /// parameter is not valid, see [`InlineQosParameter`].
+  #[must_use]
+  pub fn inline_qos_parameter(mut self, parameter: InlineQosParameter) -> Self {
+    self.inline_qos.push(parameter);
+    self
+  }
+
+  // A PID_RUSTDDS_* parameter, which RustDDS generates and interprets itself
+  #[must_use]
+  pub(crate) fn rustdds_parameter(mut self, parameter: InlineQosParameter) -> Self {
+    self.rustdds_parameters.push(parameter);
+    self
+  }
+}
+
+/// An inline QoS parameter carried with a single sample, for passing
+/// e.g. vendor-specific tags or trace context to remote DataReaders.
+///
+/// `value` is the already serialized parameter value. It is sent as is, padded
+/// to a multiple of 4 bytes. When received, `value` includes such padding.
+///
+/// The `parameter_id` must not be one that RustDDS generates or interprets
+/// itself, i.e. PID_PAD (0x0000), PID_SENTINEL (0x0001), PID_KEY_HASH
+/// (0x0070), PID_STATUS_INFO (0x0071), PID_RELATED_SAMPLE_IDENTITY (0x800f),
+/// or one of the RustDDS vendor-specific parameters (0x8b00 - 0x8bff). Custom
+/// parameters should use the vendor-specific range, i.e. have bit 0x8000 set.
+/// The padded `value` must fit in 65535 bytes.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct InlineQosParameter {
+  pub parameter_id: u16,
+  pub value: Vec<u8>,
+}
+
+impl InlineQosParameter {
+  // Largest value length that still fits the u16 length field after padding
+  const MAX_VALUE_LENGTH: usize = 0xfffc;
+
+  // Is this something the application may send? Returns the reason if not.
+  pub(crate) fn validate(&self) -> Result<(), String> {
+    let parameter_id = ParameterId::new(self.parameter_id);
+    if InlineQos::is_interpreted(parameter_id) || parameter_id.is_rustdds_specific() {
+      return Err(format!(
+        "Inline QoS parameter id {:#06x} is reserved",
+        self.parameter_id
+      ));
+    }
+    if self.value.len() > Self::MAX_VALUE_LENGTH {
+      return Err(format!(
+        "Inline QoS parameter {:#06x} is too long: {} bytes",
+        self.parameter_id,
+        self.value.len()
+      ));
+    }
+    Ok(())
+  }
 }
 
 /// Type to be used with write_with_options.
@@ -99,6 +188,8 @@ pub struct WriteOptions {
   source_timestamp: Option<Timestamp>,             // from DDS spec
   to_single_reader: Option<GUID>,                  /* try to send to one Reader only
                                                     * future extension room fo other fields. */
+  inline_qos: Vec<InlineQosParameter>, // application-defined inline QoS parameters
+  rustdds_parameters: Vec<InlineQosParameter>, // PID_RUSTDDS_* parameters
 }
 
 impl WriteOptions {
@@ -113,6 +204,46 @@ impl WriteOptions {
   pub fn to_single_reader(&self) -> Option<GUID> {
     self.to_single_reader
   }
+
+  pub fn inline_qos_parameters(&self) -> &[InlineQosParameter] {
+    &self.inline_qos
+  }
+
+  #[cfg(feature = "opentelemetry")]
+  pub fn trace_context(&self) -> Option<SpanContext> {
+    trace_context::from_inline_qos(&self.rustdds_parameters)
+  }
+
+  pub(crate) fn rustdds_parameters(&self) -> &[InlineQosParameter] {
+    &self.rustdds_parameters
+  }
+
+  pub(crate) fn with_rustdds_parameter(mut self, parameter: InlineQosParameter) -> Self {
+    self.rustdds_parameters.push(parameter);
+    self
+  }
+
+  // Checks the application-defined inline QoS parameters
+  pub(crate) fn validate(&self) -> Result<(), String> {
+    self
+      .inline_qos
+      .iter()
+      .try_for_each(InlineQosParameter::validate)
+  }
+
+  // Adds the span context of the current OpenTelemetry Context, unless a trace
+  // context was given explicitly.
+  #[cfg(feature = "opentelemetry")]
+  pub(crate) fn with_current_trace_context(mut self) -> Self {
+    if !trace_context::is_present(&self.rustdds_parameters) {
+      if let Some(span_context) = trace_context::current_span_context() {
+        self
+          .rustdds_parameters
+          .push(trace_context::to_inline_qos(&span_context));
+      }
+    }
+    self
+  }
 }
 
 impl From<Option<Timestamp>> for WriteOptions {
@@ -121,13 +252,69 @@ impl From<Option<Timestamp>> for WriteOptions {
       related_sample_identity: None,
       source_timestamp,
       to_single_reader: None,
+      inline_qos: Vec::new(),
+      rustdds_parameters: Vec::new(),
     }
   }
 }
 
+/// A buffer for serializing one sample, obtained from
+/// [`DataWriter::reserve_sample`]. Implements [`std::io::Write`] and
+/// [`BufMut`].
+pub struct SampleBuffer {
+  buffer: BytesMut,
+}
+
+impl SampleBuffer {
+  /// The data serialized so far
+  pub fn as_slice(&self) -> &[u8] {
+    &self.buffer
+  }
+
+  pub fn len(&self) -> usize {
+    self.buffer.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.buffer.is_empty()
+  }
+
+  /// Discard the contents, keeping the memory.
+  pub fn clear(&mut self) {
+    self.buffer.clear();
+  }
+}
+
+impl io::Write for SampleBuffer {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.buffer.extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+unsafe impl BufMut for SampleBuffer {
+  fn remaining_mut(&self) -> usize {
+    self.buffer.remaining_mut()
+  }
+
+  unsafe fn advance_mut(&mut self, cnt: usize) {
+    self.buffer.advance_mut(cnt);
+  }
+
+  fn chunk_mut(&mut self) -> &mut UninitSlice {
+    self.buffer.chunk_mut()
+  }
+}
+
 /// Simplified type for CDR encoding
 pub type DataWriterCdr<D> = DataWriter<D, CDRSerializerAdapter<D>>;
 
+type SampleHook<D> = Box<dyn Fn(&mut D) -> Result<(), String> + Send + Sync>;
+
 /// DDS DataWriter for keyed topics
 ///
 /// # Examples
@@ -168,6 +355,22 @@ pub struct DataWriter<D: Keyed, SA: SerializerAdapter<D> = CDRSerializerAdapter<
   discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
   status_receiver: StatusReceiver<DataWriterStatus>,
   available_sequence_number: AtomicI64,
+  // Best-effort samples not sent out within this time from write are dropped.
+  max_send_age: Option<duration::Duration>,
+  // Counter of such dropped samples, incremented by the RTPS Writer
+  stale_samples_dropped: Arc<AtomicU64>,
+  // Round trip times to the readers, measured by the RTPS Writer
+  round_trip_times: RoundTripTimes,
+  // Delivery confirmations, called by the RTPS Writer
+  delivery_listener: DeliveryListenerSlot,
+  // Inspects or modifies samples before they are serialized
+  sample_hook: Option<SampleHook<D>>,
+  // Memory for reserve_sample()
+  sample_pool: Mutex<BytesMut>,
+  serialization_stats: SerializationStats,
+  // Ingredients of the RTPS Writer, kept here until the DataWriter is enabled.
+  // `None` means enabled.
+  writer_ingredients: Mutex<Option<WriterIngredients>>,
 }
 
 impl<D, SA> Drop for DataWriter<D, SA>
@@ -176,6 +379,12 @@ where
   SA: SerializerAdapter<D>,
 {
   fn drop(&mut self) {
+    // A disabled DataWriter has no RTPS Writer and is unknown to Discovery.
+    if let Ok(Some(_)) = self.writer_ingredients.get_mut() {
+      self.my_publisher.release_entity_id(self.my_guid.entity_id);
+      return;
+    }
+
     // Tell Publisher to drop the corresponding RTPS Writer
     self.my_publisher.remove_writer(self.my_guid);
 
@@ -214,6 +423,9 @@ where
     cc_upload_waker: Arc<Mutex<Option<Waker>>>,
     discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
     status_receiver_rec: StatusChannelReceiver<DataWriterStatus>,
+    stale_samples_dropped: Arc<AtomicU64>,
+    delivery_listener: DeliveryListenerSlot,
+    writer_ingredients: WriterIngredients,
   ) -> CreateResult<Self> {
     if let Some(lv) = qos.liveliness {
       match lv {
@@ -225,6 +437,7 @@ where
         }
       }
     };
+    let serialization_stats = SerializationStats::for_writer(&topic.name(), guid);
     Ok(Self {
       data_phantom: PhantomData,
       ser_phantom: PhantomData,
@@ -237,9 +450,51 @@ where
       discovery_command,
       status_receiver: StatusReceiver::new(status_receiver_rec),
       available_sequence_number: AtomicI64::new(1), // valid numbering starts from 1
+      max_send_age: None,
+      stale_samples_dropped,
+      round_trip_times: Arc::clone(&writer_ingredients.round_trip_times),
+      delivery_listener,
+      sample_hook: None,
+      sample_pool: Mutex::new(BytesMut::new()),
+      serialization_stats,
+      writer_ingredients: Mutex::new(Some(writer_ingredients)),
     })
   }
 
+  /// Enables a DataWriter that was created disabled, because
+  /// [`EntityFactory`](crate::policy::EntityFactory) of its Publisher has
+  /// `autoenable_created_entities` set to `false`.
+  ///
+  /// Only then the DataWriter is announced via Discovery and can be matched
+  /// with DataReaders. Before that, writing fails with
+  /// [`WriteError::NotEnabled`]. Enabling an enabled DataWriter does nothing.
+  pub fn enable(&self) -> CreateResult<()> {
+    self.my_publisher.enable_datawriter(self)
+  }
+
+  /// Has the DataWriter been enabled? See [`enable`](Self::enable).
+  pub fn is_enabled(&self) -> bool {
+    self.writer_ingredients.lock().unwrap().is_none()
+  }
+
+  /// File descriptor for polling status changes outside mio, e.g. from C
+  /// code. It is readable when there may be status events, and stays readable
+  /// until [`try_recv_status`](StatusEvented::try_recv_status) is called. Call
+  /// it until it returns `None` before polling again.
+  ///
+  /// Like [`as_status_source`](StatusEvented::as_status_source), this enables
+  /// receiving status events from this writer.
+  #[cfg(not(target_os = "windows"))]
+  pub fn as_status_raw_fd(&mut self) -> RawFd {
+    self.status_receiver.as_status_raw_fd()
+  }
+
+  // The RTPS Writer ingredients, if the DataWriter is not yet enabled. The
+  // DataWriter is enabled once they have been taken out.
+  pub(crate) fn writer_ingredients(&self) -> MutexGuard<'_, Option<WriterIngredients>> {
+    self.writer_ingredients.lock().unwrap()
+  }
+
   fn next_sequence_number(&self) -> SequenceNumber {
     SequenceNumber::from(
       self
@@ -254,10 +509,118 @@ where
       .fetch_sub(1, Ordering::Relaxed);
   }
 
-  // This one function provides both get_matched_subscriptions and
-  // get_matched_subscription_data TODO: Maybe we could return references to the
-  // subscription data to avoid copying? But then what if the result set changes
-  // while the application processes it?
+  // Point in time after which a sample written now is no longer worth sending.
+  // Applies to BestEffort writers only: Reliable writers must deliver everything
+  // they have accepted.
+  fn send_deadline(&self) -> Option<Instant> {
+    if self.qos_policy.is_reliable() {
+      None
+    } else {
+      self
+        .max_send_age
+        .map(|age| Instant::now() + Duration::from(age))
+    }
+  }
+
+  // Key hash to send in inline QoS (PID_KEY_HASH). NO_KEY topics have no
+  // instances, so there is nothing to hash.
+  fn key_hash(&self, key: &D::K) -> Option<KeyHash> {
+    match self.my_topic.kind() {
+      TopicKind::WithKey => Some(key.hash_key(false)),
+      TopicKind::NoKey => None,
+    }
+  }
+
+  /// Sets the maximum time a sample may wait for sending after it has been
+  /// written. If the RTPS Writer does not get to send the sample within this
+  /// time, e.g. because of backpressure, the sample is dropped instead of
+  /// being delivered late. `None` (the default) means no limit.
+  ///
+  /// This is not part of the DDS specification. It has effect only on
+  /// BestEffort DataWriters, because Reliable DataWriters must deliver all
+  /// samples they have accepted. Dispose operations are never dropped.
+  pub fn set_max_send_age(&mut self, max_send_age: Option<duration::Duration>) {
+    self.max_send_age = max_send_age;
+  }
+
+  /// Maximum time (DDS Duration since write) a sample may wait for sending,
+  /// or `None` if there is no limit.
+  pub fn max_send_age(&self) -> Option<duration::Duration> {
+    self.max_send_age
+  }
+
+  /// Total number of samples dropped so far because they could not be sent
+  /// within [`max_send_age`](Self::set_max_send_age). Incremented once per
+  /// sample that the RTPS Writer finds past its deadline when about to send.
+  pub fn stale_samples_dropped_count(&self) -> u64 {
+    self.stale_samples_dropped.load(Ordering::Relaxed)
+  }
+
+  /// Sets a listener that is told of the fate of every sample written from
+  /// now on: either all matched reliable DataReaders have acknowledged it,
+  /// or it was dropped, e.g. because its Lifespan expired. The samples are
+  /// reported one at a time in sequence number order, so that the
+  /// application can e.g. hand them over to the next system exactly once, or
+  /// write them again. The sequence numbers are those in the
+  /// [`SampleIdentity`] given by
+  /// [`write_with_options`](Self::write_with_options).
+  ///
+  /// Only Reliable DataWriters get acknowledgements, and only while there is
+  /// some reliable DataReader matched. Samples still pending when the
+  /// DataWriter is deleted are reported as
+  /// [`DropReason::WriterDeleted`](crate::DropReason::WriterDeleted).
+  ///
+  /// The listener is called from a thread of its own, so it may block, e.g.
+  /// to write again. A new listener replaces the previous one, which still
+  /// gets the reports made before that.
+  ///
+  /// This is not part of the DDS specification.
+  pub fn set_delivery_listener<L>(&self, listener: L)
+  where
+    L: DeliveryListener + 'static,
+  {
+    let first = SequenceNumber::from(self.available_sequence_number.load(Ordering::Relaxed));
+    self.delivery_listener.set(Arc::new(listener), first);
+  }
+
+  /// Removes the listener set with
+  /// [`set_delivery_listener`](Self::set_delivery_listener).
+  pub fn remove_delivery_listener(&self) {
+    self.delivery_listener.clear();
+  }
+
+  /// Sets a function that is called with every sample written with
+  /// [`write`](Self::write) or its variants, before the sample is serialized
+  /// and inserted into the history cache. The function may modify the sample,
+  /// e.g. to redact fields, or veto it by returning an error. A vetoed sample
+  /// is not sent, and the write fails with [`WriteError::Vetoed`], which gives
+  /// back the sample and the reason.
+  ///
+  /// This allows enforcing data policies in one place instead of in every
+  /// publishing component. Samples given already serialized to
+  /// [`write_sample`](Self::write_sample) bypass the hook, as do dispose and
+  /// unregister operations. A new hook replaces the previous one.
+  ///
+  /// This is not part of the DDS specification.
+  pub fn set_sample_hook<F>(&mut self, hook: F)
+  where
+    F: Fn(&mut D) -> Result<(), String> + Send + Sync + 'static,
+  {
+    self.sample_hook = Some(Box::new(hook));
+  }
+
+  /// Removes the function set with [`set_sample_hook`](Self::set_sample_hook).
+  pub fn remove_sample_hook(&mut self) {
+    self.sample_hook = None;
+  }
+
+  // Runs the sample hook, if any.
+  fn apply_sample_hook(&self, mut data: D) -> WriteResult<D, D> {
+    match self.sample_hook.as_ref().map(|hook| hook(&mut data)) {
+      None | Some(Ok(())) => Ok(data),
+      Some(Err(reason)) => Err(WriteError::Vetoed { reason, data }),
+    }
+  }
 
   /// Manually refreshes liveliness if QoS allows it
   ///
@@ -348,7 +711,21 @@ where
     data: D,
     write_options: WriteOptions,
   ) -> WriteResult<SampleIdentity, D> {
+    self.write_with_encoding(data, SA::output_encoding(), write_options)
+  }
+
+  // Like write_with_options, but the serialized data is labelled with
+  // `encoding` instead of SA::output_encoding(). This is for forwarding
+  // payloads whose encoding is only known at run time.
+  pub(crate) fn write_with_encoding(
+    &self,
+    data: D,
+    encoding: RepresentationIdentifier,
+    write_options: WriteOptions,
+  ) -> WriteResult<SampleIdentity, D> {
+    let data = self.apply_sample_hook(data)?;
     // serialize
+    let started = Instant::now();
     let send_buffer = match SA::to_bytes(&data) {
       Ok(b) => b,
       Err(e) => {
@@ -358,16 +735,98 @@ where
         })
       }
     };
+    self
+      .serialization_stats
+      .sample_processed(send_buffer.len(), started.elapsed());
+
+    let ddsdata = DDSData::new(SerializedPayload::new_from_bytes(encoding, send_buffer))
+      .with_key_hash(self.key_hash(&data.key()));
+    self.upload(ddsdata, write_options, data)
+  }
+
+  /// Gets a buffer for serializing a sample directly, without intermediate
+  /// copies. Serialize the sample into the buffer, e.g. using
+  /// [`to_writer_endian`](crate::serialization::to_writer_endian), and send
+  /// it with [`write_sample`](Self::write_sample).
+  ///
+  /// The buffers come from a pool owned by this DataWriter. Memory is reused
+  /// once the RTPS Writer no longer needs the previously written samples, so
+  /// a steady stream of writes does not allocate.
+  ///
+  /// `capacity` is the expected serialized size. The buffer grows if
+  /// necessary.
+  pub fn reserve_sample(&self, capacity: usize) -> SampleBuffer {
+    let mut pool = self.sample_pool.lock().unwrap();
+    pool.reserve(capacity);
+    SampleBuffer {
+      buffer: std::mem::take(&mut *pool),
+    }
+  }
+
+  /// Writes a sample serialized into a buffer from
+  /// [`reserve_sample`](Self::reserve_sample). The serialized data must be in
+  /// the encoding of the SerializerAdapter `SA`, and `key` must be the key of
+  /// the serialized sample.
+  ///
+  /// Returns the identity of the written sample, which includes the sequence
+  /// number assigned to it. On failure, the serialized data is returned in
+  /// the error.
+  pub fn write_sample(
+    &self,
+    key: &D::K,
+    sample: SampleBuffer,
+    write_options: WriteOptions,
+  ) -> WriteResult<SampleIdentity, Bytes> {
+    let SampleBuffer { mut buffer } = sample;
+    let send_buffer = buffer.split().freeze();
+    // Return the remaining capacity to the pool
+    {
+      let mut pool = self.sample_pool.lock().unwrap();
+      if buffer.capacity() > pool.capacity() {
+        *pool = buffer;
+      }
+    }
 
     let ddsdata = DDSData::new(SerializedPayload::new_from_bytes(
       SA::output_encoding(),
-      send_buffer,
-    ));
+      send_buffer.clone(),
+    ))
+    .with_key_hash(self.key_hash(key));
+    self.upload(ddsdata, write_options, send_buffer)
+  }
+
+  // Writes a change as it was received from another DataWriter, e.g. for
+  // forwarding. Data, disposes, and unregisters all keep their key hash.
+  pub(crate) fn write_dds_data(
+    &self,
+    ddsdata: DDSData,
+    write_options: WriteOptions,
+  ) -> WriteResult<SampleIdentity, ()> {
+    self.upload(ddsdata, write_options, ())
+  }
+
+  // Hands serialized data over to the RTPS Writer. `data` is given back in the
+  // error, if this fails.
+  fn upload<T>(
+    &self,
+    ddsdata: DDSData,
+    write_options: WriteOptions,
+    data: T,
+  ) -> WriteResult<SampleIdentity, T> {
+    if !self.is_enabled() {
+      return Err(WriteError::NotEnabled { data });
+    }
+    if let Err(reason) = write_options.validate() {
+      return Err(WriteError::BadParameter { reason, data });
+    }
+    #[cfg(feature = "opentelemetry")]
+    let write_options = write_options.with_current_trace_context();
     let sequence_number = self.next_sequence_number();
     let writer_command = WriterCommand::DDSData {
       ddsdata,
       write_options,
       sequence_number,
+      send_deadline: self.send_deadline(),
     };
 
     let timeout = self.qos().reliable_max_blocking_time();
@@ -447,6 +906,9 @@ where
   /// data_writer.wait_for_acknowledgments(std::time::Duration::from_millis(100));
   /// ```
   pub fn wait_for_acknowledgments(&self, max_wait: Duration) -> WriteResult<bool, ()> {
+    if !self.is_enabled() {
+      return Err(WriteError::NotEnabled { data: () });
+    }
     match &self.qos_policy.reliability {
       None | Some(Reliability::BestEffort) => Ok(true),
       Some(Reliability::Reliable { .. }) => {
@@ -485,6 +947,75 @@ where
     } // match
   }
 
+  /// Sends out the samples that the
+  /// [`Batching`](crate::policy::Batching) QoS policy is holding back,
+  /// without waiting for the batch to fill up or its `max_delay` to expire.
+  ///
+  /// If Batching is not set, there is nothing to flush and the call succeeds
+  /// immediately.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use serde::{Serialize, Deserialize};
+  /// # use rustdds::*;
+  /// #
+  /// let domain_participant = DomainParticipant::new(0).unwrap();
+  /// let qos = QosPolicyBuilder::new()
+  ///   .batching(policy::Batching {
+  ///     max_samples: 10,
+  ///     max_delay: Duration::from_millis(50),
+  ///   })
+  ///   .build();
+  /// let publisher = domain_participant.create_publisher(&qos).unwrap();
+  ///
+  /// #[derive(Serialize, Deserialize, Debug)]
+  /// struct SomeType { a: i32 }
+  /// impl Keyed for SomeType {
+  ///   type K = i32;
+  ///
+  ///   fn key(&self) -> Self::K {
+  ///     self.a
+  ///   }
+  /// }
+  ///
+  /// let topic = domain_participant.create_topic("some_topic".to_string(), "SomeType".to_string(), &qos, TopicKind::WithKey).unwrap();
+  /// let data_writer = publisher.create_datawriter::<SomeType, CDRSerializerAdapter<_>>(&topic, None).unwrap();
+  ///
+  /// data_writer.write(SomeType { a: 1 }, None).unwrap();
+  /// data_writer.write(SomeType { a: 2 }, None).unwrap();
+  /// data_writer.flush().unwrap();
+  /// ```
+  pub fn flush(&self) -> WriteResult<(), ()> {
+    if !self.is_enabled() {
+      return Err(WriteError::NotEnabled { data: () });
+    }
+    if self.qos_policy.batching().is_none() {
+      return Ok(());
+    }
+    self
+      .cc_upload
+      .send(WriterCommand::Flush)
+      .map_err(|e| WriteError::Poisoned {
+        reason: format!("Cannot send flush command to Writer: {e}"),
+        data: (),
+      })
+  }
+
+  /// Sets the byte order of RTPS submessages sent by this DataWriter,
+  /// overriding
+  /// [`DomainParticipantBuilder::rtps_endianness`](crate::DomainParticipantBuilder::rtps_endianness).
+  /// Sample data encoding is not affected.
+  pub fn set_rtps_endianness(&self, endianness: Endianness) -> WriteResult<(), ()> {
+    self
+      .cc_upload
+      .send(WriterCommand::SetEndianness { endianness })
+      .map_err(|e| WriteError::Poisoned {
+        reason: format!("Cannot send endianness to Writer: {e}"),
+        data: (),
+      })
+  }
+
   /*
 
   /// Unimplemented. <b>Do not use</b>.
@@ -781,12 +1312,16 @@ where
     Ok(())
   }
 
-  /// Unimplemented. <b>Do not use</b>.
+  /// Gets the discovery data of the currently matched DataReaders. Use
+  /// [`SubscriptionBuiltinTopicData::instance_handle`] to get their instance
+  /// handles.
+  ///
+  /// The set of matched readers is maintained in the background thread, so
+  /// this call blocks until it replies.
   ///
   /// # Examples
   ///
-  /// ```no_run
-  // TODO: enable when available
+  /// ```
   /// # use serde::{Serialize, Deserialize};
   /// # use rustdds::*;
   /// # use rustdds::with_key::DataWriter;
@@ -815,8 +1350,60 @@ where
   /// for sub in data_writer.get_matched_subscriptions().iter() {
   ///   // do something
   /// }
+  /// ```
   pub fn get_matched_subscriptions(&self) -> Vec<SubscriptionBuiltinTopicData> {
-    todo!()
+    if !self.is_enabled() {
+      return vec![];
+    }
+    let (reply, reply_receiver) = std::sync::mpsc::sync_channel(1);
+    if let Err(e) = self
+      .cc_upload
+      .try_send(WriterCommand::GetMatchedReaders { reply })
+    {
+      error!(
+        "Cannot send matched readers query to Writer {:?}: {e:?}",
+        self.my_guid
+      );
+      return vec![];
+    }
+    let reader_guids = match reply_receiver.recv_timeout(Duration::from_secs(1)) {
+      Ok(guids) => guids,
+      Err(e) => {
+        error!(
+          "No matched readers reply from Writer {:?}: {e:?}",
+          self.my_guid
+        );
+        return vec![];
+      }
+    };
+
+    let db_arc = self.my_publisher.discovery_db();
+    let db = discovery_db_read(&db_arc);
+    reader_guids
+      .iter()
+      .filter_map(|guid| {
+        db.get_topic_reader(guid)
+          .or_else(|| db.get_local_topic_reader(*guid))
+          .map(|drd| drd.subscription_topic_data.clone())
+      })
+      .collect()
+  }
+
+  /// Gets the smoothed round trip times to the matched DataReaders, as
+  /// measured from HEARTBEAT to ACKNACK. Readers that have not yet been
+  /// measured are left out, and so are all readers of a BestEffort
+  /// DataWriter.
+  ///
+  /// The measurements can also adapt the timing of the DataWriter, see
+  /// [`AdaptiveTiming`](crate::policy::AdaptiveTiming).
+  pub fn round_trip_times(&self) -> Vec<(GUID, Duration)> {
+    self
+      .round_trip_times
+      .lock()
+      .unwrap()
+      .iter()
+      .map(|(guid, rtt)| (*guid, *rtt))
+      .collect()
   }
 
   /// Disposes data instance with specified key
@@ -872,21 +1459,65 @@ where
     key: &<D as Keyed>::K,
     source_timestamp: Option<Timestamp>,
   ) -> WriteResult<(), ()> {
+    self.write_instance_state_change(key, ChangeKind::NotAliveDisposed, source_timestamp)
+  }
+
+  /// Unregisters an instance, i.e. tells the DataReaders that this DataWriter
+  /// will no longer update it. When no DataWriter has the instance
+  /// registered, its instance state at the DataReaders becomes
+  /// `NotAliveNoWriters`.
+  ///
+  /// If the WriterDataLifecycle QoS policy has
+  /// `autodispose_unregistered_instances` set, which is the default, the
+  /// instance is disposed before unregistering.
+  ///
+  /// # Arguments
+  ///
+  /// * `key` - Key of the instance
+  /// * `source_timestamp` - DDS source timestamp (None uses now as time as
+  ///   specified in DDS spec)
+  pub fn unregister_instance(
+    &self,
+    key: &<D as Keyed>::K,
+    source_timestamp: Option<Timestamp>,
+  ) -> WriteResult<(), ()> {
+    let autodispose = self
+      .qos_policy
+      .writer_data_lifecycle()
+      .map_or(true, |wdl| wdl.autodispose_unregistered_instances);
+    if autodispose {
+      self.dispose(key, source_timestamp)?;
+    }
+    self.write_instance_state_change(key, ChangeKind::NotAliveUnregistered, source_timestamp)
+  }
+
+  // Send a key-only sample, which changes the instance state at the readers.
+  fn write_instance_state_change(
+    &self,
+    key: &<D as Keyed>::K,
+    change_kind: ChangeKind,
+    source_timestamp: Option<Timestamp>,
+  ) -> WriteResult<(), ()> {
+    if !self.is_enabled() {
+      return Err(WriteError::NotEnabled { data: () });
+    }
     let send_buffer = SA::key_to_bytes(key).map_err(|e| WriteError::Serialization {
       reason: format!("{e}"),
       data: (),
     })?; // serialize key
 
     let ddsdata = DDSData::new_disposed_by_key(
-      ChangeKind::NotAliveDisposed,
+      change_kind,
       SerializedPayload::new_from_bytes(SA::output_encoding(), send_buffer),
-    );
+    )
+    .with_key_hash(self.key_hash(key));
     self
       .cc_upload
       .send(WriterCommand::DDSData {
         ddsdata,
         write_options: WriteOptions::from(source_timestamp),
         sequence_number: self.next_sequence_number(),
+        send_deadline: None, // instance state changes are never stale
       })
       .map_err(|e| {
         self.undo_sequence_number();
@@ -944,6 +1575,39 @@ where
   }
 }
 
+impl<D, SA> MutQosPolicy for DataWriter<D, SA>
+where
+  D: Keyed,
+  SA: SerializerAdapter<D>,
+{
+  /// Change the QoS policies. Policies set in `new_qos` replace the current
+  /// ones, and the rest are kept.
+  ///
+  /// Changing a policy that cannot be changed after creation fails with
+  /// [`QosError::ImmutablePolicy`], which lists the changeable policies. The
+  /// new QoS is announced via Discovery, and matching with remote DataReaders
+  /// is re-evaluated.
+  fn set_qos(&mut self, new_qos: &QosPolicies) -> Result<(), QosError> {
+    let updated_qos = self.qos_policy.updated_by(new_qos)?;
+    if updated_qos != self.qos_policy {
+      if let Some(ingredients) = self.writer_ingredients.get_mut().unwrap() {
+        // Not enabled yet. The RTPS Writer will be created with the new QoS.
+        ingredients.qos_policies = updated_qos.clone();
+      } else {
+        // The RTPS Writer takes care of Discovery and matching.
+        self
+          .cc_upload
+          .send(WriterCommand::UpdateQos {
+            qos_policies: Box::new(updated_qos.clone()),
+          })
+          .unwrap_or_else(|e| error!("Cannot send QoS update to Writer: {e:?}"));
+      }
+      self.qos_policy = updated_qos;
+    }
+    Ok(())
+  }
+}
+
 impl<D, SA> DDSEntity for DataWriter<D, SA>
 where
   D: Keyed,
@@ -1166,7 +1830,15 @@ where
     write_options: WriteOptions,
   ) -> WriteResult<SampleIdentity, D> {
     // Construct a future for an async write operation and await for its completion
+    if !self.is_enabled() {
+      return Err(WriteError::NotEnabled { data });
+    }
+    if let Err(reason) = write_options.validate() {
+      return Err(WriteError::BadParameter { reason, data });
+    }
+    let data = self.apply_sample_hook(data)?;
 
+    let started = Instant::now();
     let send_buffer = match SA::to_bytes(&data) {
       Ok(s) => s,
       Err(e) => {
@@ -1176,16 +1848,23 @@ where
         })
       }
     };
+    self
+      .serialization_stats
+      .sample_processed(send_buffer.len(), started.elapsed());
 
     let dds_data = DDSData::new(SerializedPayload::new_from_bytes(
       SA::output_encoding(),
       send_buffer,
-    ));
+    ))
+    .with_key_hash(self.key_hash(&data.key()));
+    #[cfg(feature = "opentelemetry")]
+    let write_options = write_options.with_current_trace_context();
     let sequence_number = self.next_sequence_number();
     let writer_command = WriterCommand::DDSData {
       ddsdata: dds_data,
       write_options,
       sequence_number,
+      send_deadline: self.send_deadline(),
     };
 
     let timeout = self.qos().reliable_max_blocking_time();
@@ -1207,6 +1886,9 @@ where
   /// Like the synchronous version.
   /// But there is no timeout. Use asyncs to bring your own timeout.
   pub async fn async_wait_for_acknowledgments(&self) -> WriteResult<bool, ()> {
+    if !self.is_enabled() {
+      return Err(WriteError::NotEnabled { data: () });
+    }
     match &self.qos_policy.reliability {
       None | Some(Reliability::BestEffort) => Ok(true),
       Some(Reliability::Reliable { .. }) => {
@@ -1239,8 +1921,11 @@ mod tests {
     dds::{
       key::{Key, Keyed},
       participant::DomainParticipant,
+      qos::{policy, QosPolicyBuilder},
+    },
+    serialization::{
+      cdr_deserializer::deserialize_from_little_endian, cdr_serializer::CDRSerializerAdapter,
     },
-    serialization::cdr_serializer::CDRSerializerAdapter,
     structure::topic_kind::TopicKind,
     test::random_data::*,
   };
@@ -1286,6 +1971,104 @@ mod tests {
     // TODO: write also with timestamp
   }
 
+  #[test]
+  fn dw_rejects_reserved_inline_qos_parameters() {
+    let domain_participant = DomainParticipant::new(0).expect("Participant creation failed!");
+    let qos = QosPolicies::qos_none();
+    let publisher = domain_participant.create_publisher(&qos).unwrap();
+    let topic = domain_participant
+      .create_topic(
+        "dw inline qos".to_string(),
+        "inline qos test".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let data_writer: DataWriter<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher.create_datawriter(&topic, None).unwrap();
+    let data = RandomData {
+      a: 1,
+      b: "tagged".to_string(),
+    };
+    let write = |parameter_id: u16, length: usize| {
+      let options = WriteOptionsBuilder::new()
+        .inline_qos_parameter(InlineQosParameter {
+          parameter_id,
+          value: vec![0; length],
+        })
+        .build();
+      data_writer.write_with_options(data.clone(), options)
+    };
+
+    assert!(write(0x8123, 4).is_ok());
+    for reserved in [
+      ParameterId::PID_SENTINEL,
+      ParameterId::PID_KEY_HASH,
+      ParameterId::PID_STATUS_INFO,
+      ParameterId::PID_RELATED_SAMPLE_IDENTITY,
+      ParameterId::PID_RUSTDDS_TRACE_CONTEXT,
+    ] {
+      assert!(matches!(
+        write(reserved.value(), 4),
+        Err(WriteError::BadParameter { data: d, .. }) if d == data
+      ));
+    }
+    // The padded length must fit in 16 bits
+    assert!(write(0x8123, 0xfffc).is_ok());
+    assert!(matches!(
+      write(0x8123, 0xfffd),
+      Err(WriteError::BadParameter { .. })
+    ));
+  }
+
+  #[test]
+  fn dw_write_sample_test() {
+    let domain_participant = DomainParticipant::new(0).expect("Publisher creation failed!");
+    let qos = QosPolicies::qos_none();
+    let publisher = domain_participant
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let topic = domain_participant
+      .create_topic(
+        "Aasii".to_string(),
+        "Huh?".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .expect("Failed to create topic");
+
+    let data_writer: DataWriter<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher
+        .create_datawriter(&topic, None)
+        .expect("Failed to create datawriter");
+
+    let data = RandomData {
+      a: 4,
+      b: "Fobar".to_string(),
+    };
+
+    let mut sample = data_writer.reserve_sample(64);
+    crate::serialization::to_writer_endian(&mut sample, &data, RepresentationIdentifier::CDR_LE)
+      .unwrap();
+    assert_eq!(
+      sample.as_slice(),
+      crate::serialization::cdr_serializer::to_bytes::<_, LittleEndian>(&data).unwrap()
+    );
+    let first = data_writer
+      .write_sample(&data.key(), sample, WriteOptions::default())
+      .expect("Unable to write sample");
+
+    let second = data_writer
+      .write_with_options(data.clone(), WriteOptions::default())
+      .expect("Unable to write data");
+    assert_eq!(first.sequence_number, SequenceNumber::from(1));
+    assert_eq!(second.sequence_number, SequenceNumber::from(2));
+
+    // The pool has the remaining capacity
+    let sample = data_writer.reserve_sample(8);
+    assert!(sample.is_empty());
+  }
+
   #[test]
   fn dw_dispose_test() {
     let domain_participant = DomainParticipant::new(0).expect("Publisher creation failed!");
@@ -1361,4 +2144,75 @@ mod tests {
     assert!(res); // we should get "true" immediately, because we have
                   // no Reliable QoS
   }
+
+  #[test]
+  fn dw_sample_hook_vetoes_and_transforms() {
+    let domain_participant = DomainParticipant::new(0).expect("Participant creation failed!");
+    let qos = QosPolicyBuilder::new()
+      .entity_factory(policy::EntityFactory {
+        autoenable_created_entities: false,
+      })
+      .build();
+    let publisher = domain_participant
+      .create_publisher(&qos)
+      .expect("Failed to create publisher");
+    let topic = domain_participant
+      .create_topic(
+        "dw_sample_hook".to_string(),
+        "Huh?".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .expect("Failed to create topic");
+
+    let mut data_writer: DataWriter<RandomData, CDRSerializerAdapter<RandomData, LittleEndian>> =
+      publisher
+        .create_datawriter(&topic, None)
+        .expect("Failed to create datawriter");
+    // Enable by hand, so that the commands meant for the RTPS Writer come here
+    let ingredients = data_writer.writer_ingredients().take().unwrap();
+
+    data_writer.set_sample_hook(|sample: &mut RandomData| {
+      if sample.a < 0 {
+        return Err("negative".to_string());
+      }
+      sample.b = "redacted".to_string();
+      Ok(())
+    });
+
+    let vetoed = RandomData {
+      a: -1,
+      b: "secret".to_string(),
+    };
+    match data_writer.write_with_options(vetoed.clone(), WriteOptions::default()) {
+      Err(WriteError::Vetoed { reason, data }) => {
+        assert_eq!(reason, "negative");
+        assert_eq!(data, vetoed);
+      }
+      other => panic!("Expected a veto, got {other:?}"),
+    }
+
+    let accepted = data_writer
+      .write_with_options(
+        RandomData {
+          a: 1,
+          b: "secret".to_string(),
+        },
+        WriteOptions::default(),
+      )
+      .expect("Unable to write data");
+    // The vetoed sample did not use a sequence number
+    assert_eq!(accepted.sequence_number, SequenceNumber::from(1));
+
+    // Only the transformed sample was passed on
+    let sample = match ingredients.writer_command_receiver.try_recv() {
+      Ok(WriterCommand::DDSData { ddsdata, .. }) => {
+        deserialize_from_little_endian::<RandomData>(&ddsdata.data()).unwrap()
+      }
+      _ => panic!("Expected a sample"),
+    };
+    assert_eq!(sample.a, 1);
+    assert_eq!(sample.b, "redacted");
+    assert!(ingredients.writer_command_receiver.try_recv().is_err());
+  }
 }
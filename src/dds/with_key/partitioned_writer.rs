@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use crate::{
+  dds::{
+    adapters::with_key::SerializerAdapter,
+    key::{Key, Keyed},
+    pubsub::Publisher,
+    qos::QosPolicies,
+    result::{CreateError, CreateResult, WriteResult},
+    topic::Topic,
+    with_key::datawriter::{DataWriter, WriteOptions},
+  },
+  structure::{rpc::SampleIdentity, time::Timestamp},
+};
+
+/// A pool of DataWriters of the same Topic, which share the writes between
+/// them by instance key.
+///
+/// Each DataWriter is a separate RTPS Writer with its own sequence numbering,
+/// history and reliability state, so a very high-rate keyed Topic can be
+/// spread over several of them. This is transparent to DataReaders, which
+/// simply see several matched DataWriters.
+///
+/// All samples of an instance are written by the same DataWriter, so they
+/// stay in order. The DataWriter is chosen by rendezvous (highest random
+/// weight) hashing of the key hash, so the assignment is stable, and growing
+/// the pool would only move keys to the new DataWriters.
+///
+/// # Example
+///
+/// ```
+/// # use serde::{Serialize, Deserialize};
+/// use rustdds::{*, with_key::PartitionedWriter};
+///
+/// let domain_participant = DomainParticipant::new(0).unwrap();
+/// let qos = QosPolicyBuilder::new().build();
+/// let publisher = domain_participant.create_publisher(&qos).unwrap();
+///
+/// #[derive(Serialize, Deserialize, Debug)]
+/// struct SomeType { a: i32 }
+/// impl Keyed for SomeType {
+///   type K = i32;
+///
+///   fn key(&self) -> Self::K {
+///     self.a
+///   }
+/// }
+///
+/// let topic = domain_participant.create_topic("some_topic".to_string(), "SomeType".to_string(), &qos, TopicKind::WithKey).unwrap();
+/// let writer = PartitionedWriter::<SomeType, CDRSerializerAdapter<_>>::new(&publisher, &topic, None, 4).unwrap();
+///
+/// writer.write(SomeType { a: 1 }, None).unwrap();
+/// writer.dispose(&1, None).unwrap();
+/// ```
+pub struct PartitionedWriter<D: Keyed, SA: SerializerAdapter<D>> {
+  writers: Vec<DataWriter<D, SA>>,
+}
+
+impl<D, SA> PartitionedWriter<D, SA>
+where
+  D: Keyed,
+  SA: SerializerAdapter<D>,
+{
+  /// Creates `count` DataWriters for `topic` in `publisher`, all with the
+  /// same QoS.
+  pub fn new(
+    publisher: &Publisher,
+    topic: &Topic,
+    qos: Option<&QosPolicies>,
+    count: usize,
+  ) -> CreateResult<Self> {
+    if count == 0 {
+      return Err(CreateError::BadParameter {
+        reason: "PartitionedWriter needs at least one DataWriter".to_string(),
+      });
+    }
+    let writers = (0..count)
+      .map(|_| publisher.create_datawriter(topic, qos.cloned()))
+      .collect::<CreateResult<Vec<_>>>()?;
+    Ok(Self { writers })
+  }
+
+  /// The DataWriters of the pool
+  pub fn writers(&self) -> &[DataWriter<D, SA>] {
+    &self.writers
+  }
+
+  /// The DataWriter that writes the instance with `key`
+  pub fn writer_for(&self, key: &D::K) -> &DataWriter<D, SA> {
+    &self.writers[partition_of(key, self.writers.len())]
+  }
+
+  /// Writes a sample with the DataWriter of its instance. See
+  /// [`DataWriter::write`].
+  pub fn write(&self, data: D, source_timestamp: Option<Timestamp>) -> WriteResult<(), D> {
+    self.writer_for(&data.key()).write(data, source_timestamp)
+  }
+
+  /// See [`DataWriter::write_with_options`].
+  pub fn write_with_options(
+    &self,
+    data: D,
+    write_options: WriteOptions,
+  ) -> WriteResult<SampleIdentity, D> {
+    self
+      .writer_for(&data.key())
+      .write_with_options(data, write_options)
+  }
+
+  /// Disposes an instance with the DataWriter that has written it. See
+  /// [`DataWriter::dispose`].
+  pub fn dispose(&self, key: &D::K, source_timestamp: Option<Timestamp>) -> WriteResult<(), ()> {
+    self.writer_for(key).dispose(key, source_timestamp)
+  }
+
+  /// See [`DataWriter::unregister_instance`].
+  pub fn unregister_instance(
+    &self,
+    key: &D::K,
+    source_timestamp: Option<Timestamp>,
+  ) -> WriteResult<(), ()> {
+    self
+      .writer_for(key)
+      .unregister_instance(key, source_timestamp)
+  }
+
+  /// Waits until all DataWriters of the pool have their samples acknowledged.
+  /// `max_wait` applies to each DataWriter separately. See
+  /// [`DataWriter::wait_for_acknowledgments`].
+  pub fn wait_for_acknowledgments(&self, max_wait: Duration) -> WriteResult<bool, ()> {
+    let mut all_acked = true;
+    for writer in &self.writers {
+      all_acked &= writer.wait_for_acknowledgments(max_wait)?;
+    }
+    Ok(all_acked)
+  }
+}
+
+// Rendezvous hashing: the partition with the highest weight for the key wins.
+fn partition_of<K: Key>(key: &K, count: usize) -> usize {
+  let key_hash = key.hash_key(false).to_u128();
+  let key_hash = (key_hash as u64) ^ ((key_hash >> 64) as u64);
+  (0..count)
+    .max_by_key(|i| mix(key_hash ^ mix(*i as u64)))
+    .unwrap_or(0)
+}
+
+// SplitMix64 finalizer
+fn mix(mut x: u64) -> u64 {
+  x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+  x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+  x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+  x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn partitions_are_stable_and_spread() {
+    let mut counts = [0; 4];
+    for key in 0..1000_i64 {
+      let partition = partition_of(&key, 4);
+      assert_eq!(partition, partition_of(&key, 4));
+      counts[partition] += 1;
+    }
+    assert!(counts.iter().all(|c| *c > 150), "{counts:?}");
+  }
+
+  #[test]
+  fn growing_pool_moves_keys_only_to_new_partition() {
+    for key in 0..1000_i64 {
+      let before = partition_of(&key, 4);
+      let after = partition_of(&key, 5);
+      assert!(after == before || after == 4);
+    }
+  }
+}
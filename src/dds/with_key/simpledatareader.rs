@@ -1,15 +1,19 @@
 use std::{
   cmp::max,
-  collections::BTreeMap,
+  collections::{BTreeMap, BTreeSet},
   io,
   marker::PhantomData,
   pin::Pin,
-  sync::{Arc, Mutex, MutexGuard},
+  sync::{Arc, Mutex, MutexGuard, RwLock},
   task::{Context, Poll, Waker},
+  time::Instant,
 };
+#[cfg(not(target_os = "windows"))]
+use std::os::fd::{AsRawFd, RawFd};
 
+use bytes::Bytes;
 use futures::stream::{FusedStream, Stream};
-use serde::de::DeserializeOwned;
+use serde::de::{DeserializeOwned, DeserializeSeed};
 use mio_extras::channel as mio_channel;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
@@ -19,32 +23,61 @@ use mio_08;
 use crate::{
   dds::{
     adapters::with_key::*,
+    compression,
     ddsdata::*,
+    delivery_journal::{DeliveryJournal, DeliveryJournals},
+    deserialization_pool::DeserializationPool,
+    fragment_stream::{FragmentStream, FragmentStreams},
     key::*,
     pubsub::Subscriber,
     qos::*,
     result::*,
     statusevents::*,
     topic::{Topic, TopicDescription},
-    with_key::datasample::{DeserializedCacheChange, Sample},
+    with_key::{
+      datasample::{DeserializedCacheChange, Sample},
+      datawriter::InlineQosParameter,
+    },
   },
-  discovery::discovery::DiscoveryCommand,
+  discovery::{
+    discovery::DiscoveryCommand,
+    discovery_db::{discovery_db_read, DiscoveryDB},
+    sedp_messages::PublicationBuiltinTopicData,
+  },
+  messages::submessages::elements::serialized_payload::SerializedPayload,
   mio_source::PollEventSource,
+  read_error_poisoned,
+  rtps::{local_endpoints, reader::ReaderIngredients, rtps_writer_proxy::WriterProxyStatistics},
   serialization::CDRDeserializerAdapter,
+  stats::SerializationStats,
   structure::{
     cache_change::CacheChange,
     dds_cache::TopicCache,
+    duration::Duration,
     entity::RTPSEntity,
     guid::{EntityId, GUID},
     sequence_number::SequenceNumber,
     time::Timestamp,
   },
+  RepresentationIdentifier,
 };
 
 #[derive(Clone, Debug)]
 pub(crate) enum ReaderCommand {
   #[allow(dead_code)] // TODO: Implement this (resetting) feature
   ResetRequestedDeadlineStatus,
+  GetMatchedWriterStatistics {
+    reply: std::sync::mpsc::SyncSender<Vec<WriterProxyStatistics>>,
+  },
+  // DataReader QoS was changed. Only changeable policies differ.
+  UpdateQos {
+    qos_policy: Box<QosPolicies>,
+  },
+  // Notify the DataReader again after the delay, to release held-back samples
+  // or to purge expired instances.
+  WakeUpAfter {
+    delay: std::time::Duration,
+  },
 }
 
 // This is helper struct.
@@ -56,7 +89,8 @@ pub(crate) struct ReadState<K: Key> {
   /// hash_to_key_map is used for decoding received key hashes back to original
   /// key values. This is needed when we receive a dispose message via hash
   /// only.
-  hash_to_key_map: BTreeMap<KeyHash, K>, // TODO: garbage collect this somehow
+  /// Entries are removed when the DataReader purges the instance.
+  hash_to_key_map: BTreeMap<KeyHash, K>,
 }
 
 impl<K: Key> ReadState<K> {
@@ -68,6 +102,12 @@ impl<K: Key> ReadState<K> {
     }
   }
 
+  // Moves the read pointers past a cache change
+  fn advance(&mut self, timestamp: Timestamp, writer_guid: GUID, sequence_number: SequenceNumber) {
+    self.latest_instant = max(self.latest_instant, timestamp);
+    self.last_read_sn.insert(writer_guid, sequence_number);
+  }
+
   // This is a helper function so that borrow checker understands
   // that we are splitting one mutable borrow into two _disjoint_ mutable
   // borrows.
@@ -86,6 +126,87 @@ impl<K: Key> ReadState<K> {
   }
 }
 
+// Membership of a SharedSubscription group, as seen by one member.
+struct SharedGroupView {
+  distribution: policy::SampleDistribution,
+  member_count: usize,
+  my_index: usize,
+}
+
+impl SharedGroupView {
+  // All members must come to the same conclusion here, given the same
+  // membership, so this uses only data that every member receives.
+  // `key_hash` is called only for KeyAffinity.
+  fn owns(&self, sequence_number: SequenceNumber, key_hash: impl FnOnce() -> KeyHash) -> bool {
+    let slot = match self.distribution {
+      policy::SampleDistribution::RoundRobin => i64::from(sequence_number) as u128,
+      policy::SampleDistribution::KeyAffinity => key_hash().to_u128(),
+    };
+    slot % self.member_count as u128 == self.my_index as u128
+  }
+}
+
+// Received samples waiting for TimeOrderedDelivery, ordered by source
+// timestamp. Writer GUID and sequence number make the keys unique.
+struct HoldBackQueue<D: Keyed> {
+  samples: BTreeMap<(Timestamp, GUID, SequenceNumber), DeserializedCacheChange<D>>,
+  // receive_instant of the latest released sample
+  last_released: Timestamp,
+}
+
+impl<D: Keyed> HoldBackQueue<D> {
+  fn new() -> Self {
+    Self {
+      samples: BTreeMap::new(),
+      last_released: Timestamp::ZERO,
+    }
+  }
+
+  fn insert(&mut self, dcc: DeserializedCacheChange<D>) {
+    let source_timestamp = dcc
+      .write_options
+      .source_timestamp()
+      .unwrap_or(dcc.receive_instant);
+    self.samples.insert(
+      (source_timestamp, dcc.writer_guid, dcc.sequence_number),
+      dcc,
+    );
+  }
+
+  // Releases the earliest sample, if it has been held back long enough.
+  fn release(
+    &mut self,
+    now: Instant,
+    hold_back: std::time::Duration,
+  ) -> Option<DeserializedCacheChange<D>> {
+    let first = self.samples.first_entry()?;
+    if first.get().reception_instant + hold_back > now {
+      return None;
+    }
+    let mut dcc = first.remove();
+    // receive_instant is the key of the sample in the DataReader, so it must
+    // be unique and increase in delivery order.
+    if dcc.receive_instant <= self.last_released {
+      dcc.receive_instant = self.last_released + Duration::from_nanos(1);
+    }
+    self.last_released = dcc.receive_instant;
+    Some(dcc)
+  }
+
+  // How long until the earliest sample can be released
+  fn time_to_release(
+    &self,
+    now: Instant,
+    hold_back: std::time::Duration,
+  ) -> Option<std::time::Duration> {
+    self
+      .samples
+      .values()
+      .next()
+      .map(|dcc| (dcc.reception_instant + hold_back).saturating_duration_since(now))
+  }
+}
+
 /// SimpleDataReaders can only do "take" semantics and does not have
 /// any deduplication or other DataSampleCache functionality.
 pub struct SimpleDataReader<D: Keyed, DA: DeserializerAdapter<D> = CDRDeserializerAdapter<D>> {
@@ -100,18 +221,38 @@ pub struct SimpleDataReader<D: Keyed, DA: DeserializerAdapter<D> = CDRDeserializ
   topic_cache: Arc<Mutex<TopicCache>>,
 
   read_state: Mutex<ReadState<<D as Keyed>::K>>,
+  // Used only with TimeOrderedDelivery
+  hold_back_queue: Mutex<HoldBackQueue<D>>,
+  serialization_stats: SerializationStats,
 
   deserializer_type: PhantomData<DA>, // This is to provide use for DA
 
   discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
+  // Used to find the other members of a SharedSubscription group
+  discovery_db: Arc<RwLock<DiscoveryDB>>,
+  // Participant's worker threads for deserialization, if configured
+  deserialization_pool: Option<Arc<DeserializationPool>>,
   status_receiver: StatusReceiver<DataReaderStatus>,
+  // For statuses that are detected on this side, e.g. SampleRejected.
+  // These go to the same status channel as those from the RTPS Reader.
+  status_sender: StatusChannelSender<DataReaderStatus>,
 
-  #[allow(dead_code)] // TODO: This is currently unused, because we do not implement
-  // resetting deadline missed status. Remove attribute when it is supported.
+  // Commands and queries to the RTPS Reader
   reader_command: mio_channel::SyncSender<ReaderCommand>,
+  // When the wake-up last requested from the RTPS Reader is due
+  wake_up_due: Mutex<Option<Instant>>,
   data_reader_waker: Arc<Mutex<Option<Waker>>>,
 
   event_source: PollEventSource,
+
+  // Ingredients of the RTPS Reader, kept here until the DataReader is enabled.
+  // `None` means enabled.
+  reader_ingredients: Mutex<Option<ReaderIngredients>>,
+  // Shared with the RTPS Reader
+  fragment_streams: Arc<FragmentStreams>,
+  // Of the participant, and the one in use, if any
+  delivery_journals: Arc<DeliveryJournals>,
+  delivery_journal: Option<Arc<DeliveryJournal>>,
 }
 
 impl<D, DA> Drop for SimpleDataReader<D, DA>
@@ -120,6 +261,12 @@ where
   DA: DeserializerAdapter<D>,
 {
   fn drop(&mut self) {
+    // A disabled DataReader has no RTPS Reader and is unknown to Discovery.
+    if let Ok(Some(_)) = self.reader_ingredients.get_mut() {
+      self.my_subscriber.release_entity_id(self.my_guid.entity_id);
+      return;
+    }
+
     // Tell dp_event_loop
     self.my_subscriber.remove_reader(self.my_guid);
 
@@ -156,9 +303,11 @@ where
     topic_cache: Arc<Mutex<TopicCache>>,
     discovery_command: mio_channel::SyncSender<DiscoveryCommand>,
     status_channel_rec: StatusChannelReceiver<DataReaderStatus>,
+    status_sender: StatusChannelSender<DataReaderStatus>,
     reader_command: mio_channel::SyncSender<ReaderCommand>,
     data_reader_waker: Arc<Mutex<Option<Waker>>>,
     event_source: PollEventSource,
+    reader_ingredients: ReaderIngredients,
   ) -> CreateResult<Self> {
     let dp = match subscriber.participant() {
       Some(dp) => dp,
@@ -190,24 +339,77 @@ where
       notification_receiver,
       topic_cache,
       read_state: Mutex::new(ReadState::new()),
+      hold_back_queue: Mutex::new(HoldBackQueue::new()),
+      serialization_stats: SerializationStats::for_reader(&topic.name(), my_guid),
       my_topic: topic,
       deserializer_type: PhantomData,
       discovery_command,
+      discovery_db: dp.discovery_db(),
+      deserialization_pool: dp.deserialization_pool(),
       status_receiver: StatusReceiver::new(status_channel_rec),
+      status_sender,
       reader_command,
+      wake_up_due: Mutex::new(None),
       data_reader_waker,
       event_source,
+      fragment_streams: reader_ingredients.fragment_streams.clone(),
+      delivery_journals: dp.delivery_journals(),
+      delivery_journal: None,
+      reader_ingredients: Mutex::new(Some(reader_ingredients)),
     })
   }
+
+  /// Enables a DataReader that was created disabled, because
+  /// [`EntityFactory`](crate::policy::EntityFactory) of its Subscriber has
+  /// `autoenable_created_entities` set to `false`.
+  ///
+  /// Only then the DataReader is announced via Discovery and starts receiving
+  /// data. Before that, taking fails with [`ReadError::NotEnabled`]. This
+  /// allows setting up e.g. status listeners before any data arrives.
+  /// Enabling an enabled DataReader does nothing.
+  pub fn enable(&self) -> CreateResult<()> {
+    self.my_subscriber.enable_datareader(self)
+  }
+
+  /// Has the DataReader been enabled? See [`enable`](Self::enable).
+  pub fn is_enabled(&self) -> bool {
+    self.reader_ingredients.lock().unwrap().is_none()
+  }
+
+  // The RTPS Reader ingredients, if the DataReader is not yet enabled. The
+  // DataReader is enabled once they have been taken out.
+  pub(crate) fn reader_ingredients(&self) -> MutexGuard<'_, Option<ReaderIngredients>> {
+    self.reader_ingredients.lock().unwrap()
+  }
+
   pub fn set_waker(&self, w: Option<Waker>) {
     *self.data_reader_waker.lock().unwrap() = w;
   }
 
-  pub(crate) fn drain_read_notifications(&self) {
+  pub(crate) fn send_status_change(&self, change: DataReaderStatus) {
+    self
+      .status_sender
+      .try_send(change)
+      .unwrap_or_else(|e| debug!("Cannot send DataReader status: {e:?}"));
+  }
+
+  pub fn drain_read_notifications(&self) {
     while self.notification_receiver.try_recv().is_ok() {}
     self.event_source.drain();
   }
 
+  /// File descriptor for polling status changes outside mio. It is readable
+  /// when there may be status events, and stays readable until
+  /// [`try_recv_status`](StatusEvented::try_recv_status) is called. Call it
+  /// until it returns `None` before polling again.
+  ///
+  /// Like [`as_status_source`](StatusEvented::as_status_source), this enables
+  /// receiving status events from this reader.
+  #[cfg(not(target_os = "windows"))]
+  pub fn as_status_raw_fd(&mut self) -> RawFd {
+    self.status_receiver.as_status_raw_fd()
+  }
+
   fn try_take_undecoded<'a>(
     is_reliable: bool,
     topic_cache: &'a TopicCache,
@@ -217,58 +419,188 @@ where
     if is_reliable {
       topic_cache.get_changes_in_range_reliable(last_read_sn)
     } else {
-      topic_cache.get_changes_in_range_best_effort(latest_instant, Timestamp::now())
+      topic_cache.get_changes_in_range_best_effort(latest_instant)
     }
   }
 
+  // Remember which key a hash means, so that we can later decode
+  // DisposeByKeyHash.
+  //
+  // If the writer sent a key hash (PID_KEY_HASH), validate it against our own
+  // computation. A mismatch means that we and the remote implementation disagree
+  // on the key serialization. In that case, we also remember the remote hash,
+  // because that is what the writer will use for disposing.
   fn update_hash_to_key_map(
     hash_to_key_map: &mut BTreeMap<KeyHash, D::K>,
     deserialized: &Sample<D, D::K>,
+    received_key_hash: Option<KeyHash>,
   ) {
     let instance_key = match deserialized {
       Sample::Value(d) => d.key(),
       Sample::Dispose(k) => k.clone(),
     };
-    hash_to_key_map.insert(instance_key.hash_key(false), instance_key);
+    let computed_key_hash = instance_key.hash_key(false);
+    if let Some(received_key_hash) = received_key_hash {
+      if received_key_hash != computed_key_hash
+        && hash_to_key_map
+          .insert(received_key_hash, instance_key.clone())
+          .is_none()
+      {
+        warn!(
+          "Received key hash {:x?} does not match computed key hash {:x?}",
+          received_key_hash, computed_key_hash
+        );
+      }
+    }
+    hash_to_key_map.insert(computed_key_hash, instance_key);
+  }
+
+  // If the key type serializes to at most 16 bytes, the key hash is the
+  // big-endian CDR serialization of the key, padded with zeros. See RTPS Spec
+  // v2.5 Section 9.6.4.8 KeyHash. Then the key can be decoded from the hash.
+  fn key_from_key_hash(key_hash: KeyHash) -> Option<D::K> {
+    if D::K::cdr_encoding_max_size() > CdrEncodingMaxSize::Bytes(16) {
+      return None; // MD5 hash
+    }
+    DA::key_from_bytes(&key_hash.to_vec(), RepresentationIdentifier::CDR_BE)
+      .ok()
+      // The padding must be zeros, i.e. the key hashes back to the same value.
+      .filter(|key| key.hash_key(false) == key_hash)
+  }
+
+  // Samples of other SharedSubscription group members are skipped. This is
+  // decided from the undecoded change, so that they are not deserialized.
+  fn is_mine(
+    shared_group: Option<&SharedGroupView>,
+    cc: &CacheChange,
+    hash_to_key_map: &BTreeMap<KeyHash, D::K>,
+  ) -> bool {
+    shared_group.map_or(true, |g| {
+      g.owns(cc.sequence_number, || {
+        Self::change_key_hash(cc, hash_to_key_map)
+      })
+    })
+  }
+
+  // The key hash of a change for SharedSubscription KeyAffinity, as we compute
+  // it. The key is deserialized only if the writer did not send the key hash.
+  fn change_key_hash(cc: &CacheChange, hash_to_key_map: &BTreeMap<KeyHash, D::K>) -> KeyHash {
+    // A writer may compute key hashes differently from us
+    let our_key_hash = |key_hash: KeyHash| {
+      hash_to_key_map
+        .get(&key_hash)
+        .map_or(key_hash, |key| key.hash_key(false))
+    };
+    match cc.data_value {
+      DDSData::Data {
+        key_hash: Some(key_hash),
+        ..
+      }
+      | DDSData::DisposeByKey {
+        key_hash: Some(key_hash),
+        ..
+      }
+      | DDSData::DisposeByKeyHash { key_hash, .. } => our_key_hash(key_hash),
+      DDSData::Data {
+        ref serialized_payload,
+        key_hash: None,
+      } => Self::decode_value(Self::undecoded_value(serialized_payload, cc))
+        .map_or(KeyHash::zero(), |d| d.key().hash_key(false)),
+      DDSData::DisposeByKey {
+        ref key,
+        key_hash: None,
+        ..
+      } => DA::key_from_bytes(&key.value, key.representation_identifier)
+        .map_or(KeyHash::zero(), |k| k.hash_key(false)),
+    }
+  }
+
+  // The parts of a cache change that decode_value needs
+  fn undecoded_value(
+    serialized_payload: &SerializedPayload,
+    cc: &CacheChange,
+  ) -> (Bytes, RepresentationIdentifier, Option<InlineQosParameter>) {
+    (
+      serialized_payload.value.clone(),
+      serialized_payload.representation_identifier,
+      compression::parameter(cc.write_options.rustdds_parameters()),
+    )
+  }
+
+  // Decodes the data value of a sample. This does not touch the reader
+  // state, so it can run in any thread.
+  fn decode_value(
+    (payload, representation_id, compression): (
+      Bytes,
+      RepresentationIdentifier,
+      Option<InlineQosParameter>,
+    ),
+  ) -> Result<D, String> {
+    // A compressed payload is decompressed first
+    let payload = match compression {
+      Some(parameter) => compression::decompress(&payload, &parameter)?,
+      None => payload,
+    };
+    // what is our data serialization format (representation identifier) ?
+    if DA::supported_encodings().contains(&representation_id) {
+      DA::from_vec_bytes(std::slice::from_ref(&payload), representation_id)
+        .map_err(|e| format!("Failed to deserialize sample bytes: {e}, "))
+    } else {
+      Err(format!("Unknown representation id {representation_id:?}."))
+    }
   }
 
+  // Like decode_value, but also tells how long decoding took
+  fn timed_decode_value(
+    payload: (Bytes, RepresentationIdentifier, Option<InlineQosParameter>),
+  ) -> (Result<D, String>, std::time::Duration) {
+    let started = Instant::now();
+    let decoded = Self::decode_value(payload);
+    (decoded, started.elapsed())
+  }
+
+  fn record_deserialization(&self, cc: &CacheChange, elapsed: std::time::Duration) {
+    // Dispose messages carry only a key, so they are not interesting here.
+    if let DDSData::Data {
+      ref serialized_payload,
+      ..
+    } = cc.data_value
+    {
+      self
+        .serialization_stats
+        .sample_processed(serialized_payload.value.len(), elapsed);
+    }
+  }
+
+  // Deserializes a cache change. If the data value has already been decoded,
+  // e.g. in the deserialization pool, it is given in `decoded_value`.
   fn deserialize(
     timestamp: Timestamp,
     cc: &CacheChange,
     hash_to_key_map: &mut BTreeMap<KeyHash, D::K>,
+    decoded_value: Option<Result<D, String>>,
   ) -> ReadResult<DeserializedCacheChange<D>> {
     match cc.data_value {
       DDSData::Data {
         ref serialized_payload,
+        key_hash,
       } => {
-        // what is our data serialization format (representation identifier) ?
-        if let Some(recognized_rep_id) = DA::supported_encodings()
-          .iter()
-          .find(|r| **r == serialized_payload.representation_identifier)
-        {
-          match DA::from_bytes(&serialized_payload.value, *recognized_rep_id) {
-            // Data update, decoded ok
-            Ok(payload) => {
-              let p = Sample::Value(payload);
-              Self::update_hash_to_key_map(hash_to_key_map, &p);
-              Ok(DeserializedCacheChange::new(timestamp, cc, p))
-            }
-            Err(e) => Err(ReadError::Deserialization {
-              reason: format!("Failed to deserialize sample bytes: {e}, "),
-            }),
+        let decoded_value = decoded_value
+          .unwrap_or_else(|| Self::decode_value(Self::undecoded_value(serialized_payload, cc)));
+        match decoded_value {
+          // Data update, decoded ok
+          Ok(payload) => {
+            let p = Sample::Value(payload);
+            Self::update_hash_to_key_map(hash_to_key_map, &p, key_hash);
+            Ok(DeserializedCacheChange::new(timestamp, cc, p))
           }
-        } else {
-          Err(ReadError::Deserialization {
-            reason: format!(
-              "Unknown representation id {:?}.",
-              serialized_payload.representation_identifier
-            ),
-          })
+          Err(reason) => Err(ReadError::Deserialization { reason }),
         }
       }
 
       DDSData::DisposeByKey {
         key: ref serialized_key,
+        key_hash,
         ..
       } => {
         match DA::key_from_bytes(
@@ -277,7 +609,7 @@ where
         ) {
           Ok(key) => {
             let k = Sample::Dispose(key);
-            Self::update_hash_to_key_map(hash_to_key_map, &k);
+            Self::update_hash_to_key_map(hash_to_key_map, &k, key_hash);
             Ok(DeserializedCacheChange::new(timestamp, cc, k))
           }
           Err(e) => Err(ReadError::Deserialization {
@@ -295,6 +627,15 @@ where
             cc,
             Sample::Dispose(key.clone()),
           ))
+        } else if let Some(key) = Self::key_from_key_hash(key_hash) {
+          // We have not seen this instance before, but the key is short
+          // enough to be contained in the hash.
+          hash_to_key_map.insert(key_hash, key.clone());
+          Ok(DeserializedCacheChange::new(
+            timestamp,
+            cc,
+            Sample::Dispose(key),
+          ))
         } else {
           Err(ReadError::Deserialization {
             reason: format!("Tried to dispose with unknown key hash: {:x?}", key_hash),
@@ -307,6 +648,95 @@ where
   /// Note: Always remember to call .drain_read_notifications() just before
   /// calling this one. Otherwise, new notifications may not appear.
   pub fn try_take_one(&self) -> ReadResult<Option<DeserializedCacheChange<D>>> {
+    if !self.is_enabled() {
+      return Err(ReadError::NotEnabled);
+    }
+    self.try_take_one_in_group(self.shared_group_view().as_ref())
+  }
+
+  /// Takes all available samples, like repeated calls to `try_take_one`
+  /// would. If a sample fails to deserialize, the samples before it are
+  /// returned, and the error is returned from the next call.
+  ///
+  /// Note: Always remember to call .drain_read_notifications() just before
+  /// calling this one. Otherwise, new notifications may not appear.
+  pub(crate) fn try_take_all(&self) -> ReadResult<Vec<DeserializedCacheChange<D>>> {
+    if !self.is_enabled() {
+      return Err(ReadError::NotEnabled);
+    }
+    // The group membership is looked up once for the whole batch.
+    let shared_group = self.shared_group_view();
+    let mut dccs = Vec::new();
+    loop {
+      match self.try_take_one_in_group(shared_group.as_ref()) {
+        Ok(Some(dcc)) => dccs.push(dcc),
+        Ok(None) => return Ok(dccs),
+        Err(e) if dccs.is_empty() => return Err(e),
+        Err(_) => return Ok(dccs), // Error will come again on next call
+      }
+    }
+  }
+
+  // Like try_take_one, but with the SharedSubscription group membership
+  // already looked up
+  fn try_take_one_in_group(
+    &self,
+    shared_group: Option<&SharedGroupView>,
+  ) -> ReadResult<Option<DeserializedCacheChange<D>>> {
+    match self.qos_policy.time_ordered_delivery() {
+      None => self.try_take_one_in_arrival_order(shared_group),
+      Some(policy::TimeOrderedDelivery { hold_back }) => {
+        self.try_take_one_in_source_order(hold_back.to_std(), shared_group)
+      }
+    }
+  }
+
+  // Received samples are moved to the hold-back queue, and released from
+  // there in source timestamp order. If some samples must still wait, the
+  // RTPS Reader is asked to wake us up when the first one is due.
+  fn try_take_one_in_source_order(
+    &self,
+    hold_back: std::time::Duration,
+    shared_group: Option<&SharedGroupView>,
+  ) -> ReadResult<Option<DeserializedCacheChange<D>>> {
+    let mut hold_back_queue = self.hold_back_queue.lock().unwrap();
+    while let Some(dcc) = self.try_take_one_in_arrival_order(shared_group)? {
+      hold_back_queue.insert(dcc);
+    }
+    let now = Instant::now();
+    let released = hold_back_queue.release(now, hold_back);
+    if released.is_none() {
+      if let Some(delay) = hold_back_queue.time_to_release(now, hold_back) {
+        self.wake_up_after(delay);
+      }
+    }
+    Ok(released)
+  }
+
+  // Ask the RTPS Reader to notify us again after `delay`. The Reader keeps
+  // only the latest request, so a pending wake-up that comes no later is not
+  // requested again. Once it is due, we are notified and will ask again for
+  // whatever is left.
+  pub(crate) fn wake_up_after(&self, delay: std::time::Duration) {
+    let now = Instant::now();
+    let due = now + delay;
+    let mut wake_up_due = self.wake_up_due.lock().unwrap();
+    if wake_up_due.is_some_and(|pending| now < pending && pending <= due) {
+      return;
+    }
+    match self
+      .reader_command
+      .try_send(ReaderCommand::WakeUpAfter { delay })
+    {
+      Ok(()) => *wake_up_due = Some(due),
+      Err(e) => debug!("Cannot request wake-up from Reader: {e:?}"),
+    }
+  }
+
+  fn try_take_one_in_arrival_order(
+    &self,
+    shared_group: Option<&SharedGroupView>,
+  ) -> ReadResult<Option<DeserializedCacheChange<D>>> {
     let is_reliable = matches!(
       self.qos_policy.reliability(),
       Some(policy::Reliability::Reliable { .. })
@@ -315,43 +745,216 @@ where
     let topic_cache = self.acquire_the_topic_cache_guard();
 
     let mut read_state_ref = self.read_state.lock().unwrap();
-    let latest_instant = read_state_ref.latest_instant;
-    let (last_read_sn, hash_to_key_map) = read_state_ref.get_sn_map_and_hash_map();
-    let (timestamp, cc) = match Self::try_take_undecoded(
-      is_reliable,
-      &topic_cache,
-      latest_instant,
-      last_read_sn,
-    )
-    .next()
-    {
-      None => return Ok(None),
-      Some((ts, cc)) => (ts, cc),
-    };
+    loop {
+      let latest_instant = read_state_ref.latest_instant;
+      let (last_read_sn, hash_to_key_map) = read_state_ref.get_sn_map_and_hash_map();
+      let (timestamp, cc) =
+        match Self::try_take_undecoded(is_reliable, &topic_cache, latest_instant, last_read_sn)
+          .next()
+        {
+          None => return Ok(None),
+          Some((ts, cc)) => (ts, cc),
+        };
 
-    match Self::deserialize(timestamp, cc, hash_to_key_map) {
-      Ok(dcc) => {
+      // DataReaders share the topic cache with the DataWriters of the same
+      // participant, so IgnoreLocal cannot be left to matching alone.
+      if local_endpoints::match_ignored(self.my_guid, cc.writer_guid)
+        || self.was_delivered(cc)
+        || !Self::is_mine(shared_group, cc, hash_to_key_map)
+      {
+        let (writer_guid, sequence_number) = (cc.writer_guid, cc.sequence_number);
         read_state_ref.latest_instant = max(read_state_ref.latest_instant, timestamp);
         read_state_ref
           .last_read_sn
-          .insert(dcc.writer_guid, dcc.sequence_number);
-        Ok(Some(dcc))
+          .insert(writer_guid, sequence_number);
+        continue;
+      }
+
+      let started = Instant::now();
+      let deserialized = Self::deserialize(timestamp, cc, hash_to_key_map, None);
+      self.record_deserialization(cc, started.elapsed());
+      match deserialized {
+        Ok(dcc) => {
+          read_state_ref.latest_instant = max(read_state_ref.latest_instant, timestamp);
+          read_state_ref
+            .last_read_sn
+            .insert(dcc.writer_guid, dcc.sequence_number);
+          if self.deliver(dcc.writer_guid, dcc.sequence_number) {
+            return Ok(Some(dcc));
+          }
+        }
+        Err(ser_err) => return Err(self.deserialization_error(&ser_err)),
       }
-      Err(ser_err) => Err(ReadError::Deserialization {
-        reason: format!(
-          "{}, Topic = {}, Type = {:?}",
-          ser_err,
-          self.my_topic.name(),
-          self.my_topic.get_type()
-        ),
-      }),
     }
   }
 
+  // Takes at most `max_samples` changes without deserializing them, e.g. for
+  // forwarding. Compressed payloads are decompressed. Skipped changes are the
+  // same as in try_take_one.
+  pub(crate) fn try_take_undecoded_changes(
+    &self,
+    max_samples: usize,
+  ) -> ReadResult<Vec<CacheChange>> {
+    if !self.is_enabled() {
+      return Err(ReadError::NotEnabled);
+    }
+    let is_reliable = matches!(
+      self.qos_policy.reliability(),
+      Some(policy::Reliability::Reliable { .. })
+    );
+    let shared_group = self.shared_group_view();
+
+    let topic_cache = self.acquire_the_topic_cache_guard();
+    let mut read_state_ref = self.read_state.lock().unwrap();
+    let mut changes = Vec::new();
+    while changes.len() < max_samples {
+      let latest_instant = read_state_ref.latest_instant;
+      let (last_read_sn, _) = read_state_ref.get_sn_map_and_hash_map();
+      let (timestamp, mut cc) =
+        match Self::try_take_undecoded(is_reliable, &topic_cache, latest_instant, last_read_sn)
+          .next()
+        {
+          None => break,
+          Some((ts, cc)) => (ts, cc.clone()),
+        };
+      read_state_ref.latest_instant = max(read_state_ref.latest_instant, timestamp);
+      read_state_ref
+        .last_read_sn
+        .insert(cc.writer_guid, cc.sequence_number);
+
+      if local_endpoints::match_ignored(self.my_guid, cc.writer_guid)
+        || self.was_delivered(&cc)
+        || !Self::is_mine(shared_group.as_ref(), &cc, &read_state_ref.hash_to_key_map)
+        || !self.deliver(cc.writer_guid, cc.sequence_number)
+      {
+        continue;
+      }
+
+      if let DDSData::Data {
+        serialized_payload, ..
+      } = &mut cc.data_value
+      {
+        if let Some(parameter) = compression::parameter(cc.write_options.rustdds_parameters()) {
+          serialized_payload.value = compression::decompress(&serialized_payload.value, &parameter)
+            .map_err(|reason| self.deserialization_error(&ReadError::Deserialization { reason }))?;
+        }
+      }
+      changes.push(cc);
+    }
+    Ok(changes)
+  }
+
+  /// All changes retained in the topic cache with source timestamp at or
+  /// before `source_time`, in source timestamp order. Changes without a
+  /// source timestamp are ordered by reception time. This does not affect
+  /// what [`try_take_one`](Self::try_take_one) returns.
+  pub fn read_as_of(&self, source_time: Timestamp) -> ReadResult<Vec<DeserializedCacheChange<D>>> {
+    if !self.is_enabled() {
+      return Err(ReadError::NotEnabled);
+    }
+    let topic_cache = self.acquire_the_topic_cache_guard();
+    // A copy, so that reading history does not change the reader state
+    let mut hash_to_key_map = self.read_state.lock().unwrap().hash_to_key_map.clone();
+    topic_cache
+      .get_changes_as_of(source_time)
+      .filter(|(_, cc)| !local_endpoints::match_ignored(self.my_guid, cc.writer_guid))
+      .map(|(timestamp, cc)| {
+        Self::deserialize(timestamp, cc, &mut hash_to_key_map, None)
+          .map_err(|ser_err| self.deserialization_error(&ser_err))
+      })
+      .collect()
+  }
+
+  // Has the sample been delivered already, according to the delivery journal?
+  fn was_delivered(&self, cc: &CacheChange) -> bool {
+    self
+      .delivery_journal
+      .as_ref()
+      .is_some_and(|journal| journal.was_delivered(cc.writer_guid, cc.sequence_number))
+  }
+
+  // Records the delivery of the sample in the delivery journal, if any. False
+  // means that it has been delivered already.
+  fn deliver(&self, writer_guid: GUID, sequence_number: SequenceNumber) -> bool {
+    self.delivery_journal.as_ref().map_or(true, |journal| {
+      journal.deliver(writer_guid, sequence_number)
+    })
+  }
+
+  fn deserialization_error(&self, ser_err: &ReadError) -> ReadError {
+    ReadError::Deserialization {
+      reason: format!(
+        "{}, Topic = {}, Type = {:?}",
+        ser_err,
+        self.my_topic.name(),
+        self.my_topic.get_type()
+      ),
+    }
+  }
+
+  fn shared_group_view(&self) -> Option<SharedGroupView> {
+    let policy::SharedSubscription {
+      group,
+      distribution,
+    } = self.qos_policy.shared_subscription()?;
+    let mut members = discovery_db_read(&self.discovery_db)
+      .shared_subscription_members(&self.my_topic.name(), &group);
+    // We may not be in the database yet.
+    members.insert(self.my_guid);
+    let my_index = members.iter().position(|g| *g == self.my_guid)?;
+    Some(SharedGroupView {
+      distribution,
+      member_count: members.len(),
+      my_index,
+    })
+  }
+
   pub fn qos(&self) -> &QosPolicies {
     &self.qos_policy
   }
 
+  /// Change the QoS policies. Policies set in `new_qos` replace the current
+  /// ones, and the rest are kept.
+  ///
+  /// Changing a policy that cannot be changed after creation fails with
+  /// [`QosError::ImmutablePolicy`], which lists the changeable policies. The
+  /// new QoS is announced via Discovery, and matching with remote DataWriters
+  /// is re-evaluated.
+  pub fn set_qos(&mut self, new_qos: &QosPolicies) -> Result<(), QosError> {
+    let updated_qos = self.qos_policy.updated_by(new_qos)?;
+    if updated_qos != self.qos_policy {
+      if let Some(ingredients) = self.reader_ingredients.get_mut().unwrap() {
+        // Not enabled yet. The RTPS Reader will be created with the new QoS.
+        ingredients.qos_policy = updated_qos.clone();
+      } else {
+        // The RTPS Reader takes care of Discovery and matching.
+        self
+          .reader_command
+          .send(ReaderCommand::UpdateQos {
+            qos_policy: Box::new(updated_qos.clone()),
+          })
+          .unwrap_or_else(|e| error!("Cannot send QoS update to Reader: {e:?}"));
+      }
+      self.qos_policy = updated_qos;
+    }
+    Ok(())
+  }
+
+  // Instances purged from the DataReader no longer need their key hashes
+  // decoded.
+  pub(crate) fn forget_instances(&self, keys: &[D::K]) {
+    if keys.is_empty() {
+      return;
+    }
+    let keys: BTreeSet<&D::K> = keys.iter().collect();
+    self
+      .read_state
+      .lock()
+      .unwrap()
+      .hash_to_key_map
+      .retain(|_hash, key| !keys.contains(key));
+  }
+
   pub fn guid(&self) -> GUID {
     self.my_guid
   }
@@ -360,6 +963,105 @@ where
     &self.my_topic
   }
 
+  /// Reception statistics for each currently matched DataWriter. This is a
+  /// RustDDS extension for diagnosing communication problems.
+  ///
+  /// The statistics are maintained in the background thread, so this call
+  /// blocks until it replies.
+  pub fn matched_writer_statistics(&self) -> ReadResult<Vec<WriterProxyStatistics>> {
+    if !self.is_enabled() {
+      return Ok(vec![]); // Nothing is matched yet
+    }
+    let (reply, reply_receiver) = std::sync::mpsc::sync_channel(1);
+    self
+      .reader_command
+      .try_send(ReaderCommand::GetMatchedWriterStatistics { reply })
+      .or_else(|e| {
+        read_error_poisoned!(
+          "Cannot send statistics query to Reader {:?}: {e:?}",
+          self.my_guid
+        )
+      })?;
+    reply_receiver
+      .recv_timeout(std::time::Duration::from_secs(1))
+      .or_else(|e| {
+        read_error_poisoned!("No statistics reply from Reader {:?}: {e:?}", self.my_guid)
+      })
+  }
+
+  /// Receives samples of at least `min_size` bytes as
+  /// [`FragmentStream`]s, or stops doing so with `None`. This is a RustDDS
+  /// extension for very large samples, e.g. file transfer.
+  ///
+  /// A streamed sample is not assembled and does not go to the DataReader's
+  /// cache. Instead, the DataReader is notified as usual when the first
+  /// fragments arrive, and [`take_fragment_stream`](Self::take_fragment_stream)
+  /// then gives a stream, from which the serialized payload can be read while
+  /// the rest of the fragments are still being received. This saves both
+  /// memory and latency.
+  ///
+  /// Only samples that are sent in fragments can be streamed, so `min_size`
+  /// should be larger than the fragment size of the DataWriters. Compressed
+  /// samples are never streamed, because they must be decompressed as a
+  /// whole. At most 16 streams wait to be taken. Further samples are
+  /// assembled as usual, until the application takes some streams.
+  pub fn set_fragment_streaming(&self, min_size: Option<usize>) {
+    self.fragment_streams.set_min_size(min_size);
+  }
+
+  /// The next sample that has started to arrive, if any. See
+  /// [`set_fragment_streaming`](Self::set_fragment_streaming).
+  pub fn take_fragment_stream(&self) -> Option<FragmentStream> {
+    self.fragment_streams.take()
+  }
+
+  /// Makes this DataReader deliver each sample at most once, even if it
+  /// is received again by a later DataReader, e.g. from the TransientLocal
+  /// history of a DataWriter after the application has recreated the
+  /// DataReader. DataReaders of the same DomainParticipant that set the same
+  /// `name` share a journal of the (writer GUID, sequence number) pairs
+  /// that they have delivered, and skip samples already in it.
+  ///
+  /// The journal keeps the ranges of delivered sequence numbers of each
+  /// DataWriter, and forgets the oldest ranges if there are too many gaps
+  /// between them. Forgotten samples, and samples older than any delivered
+  /// one, are delivered again if received again. A DataWriter that has not
+  /// delivered anything in `retention` is forgotten entirely. The latest
+  /// `retention` given for a journal applies.
+  ///
+  /// Samples are recorded when they are taken from the topic cache, even if
+  /// the application then drops the DataReader without reading them. This is
+  /// not part of the DDS specification.
+  pub fn set_delivery_journal(&mut self, name: &str, retention: std::time::Duration) {
+    self.delivery_journal = Some(self.delivery_journals.journal(name, retention));
+  }
+
+  /// Stops using the journal set with
+  /// [`set_delivery_journal`](Self::set_delivery_journal). The journal itself
+  /// is kept for other DataReaders.
+  pub fn remove_delivery_journal(&mut self) {
+    self.delivery_journal = None;
+  }
+
+  pub(crate) fn matched_publications(&self) -> Vec<PublicationBuiltinTopicData> {
+    let writer_guids: Vec<GUID> = match self.matched_writer_statistics() {
+      Ok(statistics) => statistics.iter().map(|s| s.writer_guid).collect(),
+      Err(e) => {
+        error!("Cannot get matched writers: {e:?}");
+        return vec![];
+      }
+    };
+    let db = discovery_db_read(&self.discovery_db);
+    writer_guids
+      .iter()
+      .filter_map(|guid| {
+        db.get_topic_writer(guid)
+          .or_else(|| db.get_local_topic_writer(*guid))
+          .map(|dwd| dwd.publication_topic_data.clone())
+      })
+      .collect()
+  }
+
   pub fn as_async_stream(&self) -> SimpleDataReaderStream<D, DA> {
     SimpleDataReaderStream {
       simple_datareader: self,
@@ -377,6 +1079,284 @@ where
   }
 }
 
+impl<D, DA> SimpleDataReader<D, DA>
+where
+  D: Keyed + Send + 'static,
+  DA: DeserializerAdapter<D> + 'static,
+{
+  /// Takes all available samples, like repeated calls to `try_take_one` would,
+  /// but the data values are deserialized in the participant's
+  /// deserialization pool, if there is one. If a sample fails to deserialize,
+  /// the samples before it are returned, and the error is returned from the
+  /// next call.
+  ///
+  /// Note: Always remember to call .drain_read_notifications() just before
+  /// calling this one. Otherwise, new notifications may not appear.
+  pub fn try_take_all_offloaded(&self) -> ReadResult<Vec<DeserializedCacheChange<D>>> {
+    if !self.is_enabled() {
+      return Err(ReadError::NotEnabled);
+    }
+    // The hold-back queue is simpler to handle one sample at a time.
+    let pool = match self.deserialization_pool {
+      Some(ref pool) if self.qos_policy.time_ordered_delivery().is_none() => pool.clone(),
+      _ => return self.try_take_all(),
+    };
+
+    let is_reliable = matches!(
+      self.qos_policy.reliability(),
+      Some(policy::Reliability::Reliable { .. })
+    );
+    let shared_group = self.shared_group_view();
+
+    // Copy out the pending changes, so that no locks are held while decoding.
+    // The payloads are reference-counted, so this does not copy the data.
+    // Samples of other SharedSubscription group members are ignored, so that
+    // they are not decoded.
+    let (pending, ignored): (Vec<(Timestamp, CacheChange)>, Vec<bool>) = {
+      let topic_cache = self.acquire_the_topic_cache_guard();
+      let read_state_ref = self.read_state.lock().unwrap();
+      let pending: Vec<(Timestamp, CacheChange)> = Self::try_take_undecoded(
+        is_reliable,
+        &topic_cache,
+        read_state_ref.latest_instant,
+        &read_state_ref.last_read_sn,
+      )
+      .map(|(timestamp, cc)| (timestamp, cc.clone()))
+      .collect();
+      let ignored = pending
+        .iter()
+        .map(|(_, cc)| {
+          local_endpoints::match_ignored(self.my_guid, cc.writer_guid)
+            || self.was_delivered(cc)
+            || !Self::is_mine(shared_group.as_ref(), cc, &read_state_ref.hash_to_key_map)
+        })
+        .collect();
+      (pending, ignored)
+    };
+
+    // Decode data values in the pool. Dispose messages are cheap and need the
+    // hash_to_key_map, so they are decoded below.
+    let (indices, payloads): (Vec<usize>, Vec<_>) = pending
+      .iter()
+      .enumerate()
+      .filter(|(i, _)| !ignored[*i])
+      .filter_map(|(i, (_, cc))| match cc.data_value {
+        DDSData::Data {
+          ref serialized_payload,
+          ..
+        } => Some((i, Self::undecoded_value(serialized_payload, cc))),
+        _ => None,
+      })
+      .unzip();
+    let decoded = pool
+      .map(payloads, Self::timed_decode_value)
+      .ok_or_else(|| ReadError::Poisoned {
+        reason: "Deserialization panicked in the deserialization pool".to_string(),
+      })?;
+    let mut decoded_values: Vec<Option<_>> = pending.iter().map(|_| None).collect();
+    for (i, value) in indices.into_iter().zip(decoded) {
+      decoded_values[i] = Some(value);
+    }
+
+    let mut read_state_ref = self.read_state.lock().unwrap();
+    let mut dccs = Vec::with_capacity(pending.len());
+    for (((timestamp, cc), ignored), decoded_value) in
+      pending.into_iter().zip(ignored).zip(decoded_values)
+    {
+      // Taken by a concurrent call while we were decoding
+      let already_taken = if is_reliable {
+        read_state_ref
+          .last_read_sn
+          .get(&cc.writer_guid)
+          .is_some_and(|sn| *sn >= cc.sequence_number)
+      } else {
+        timestamp <= read_state_ref.latest_instant
+      };
+      if already_taken {
+        continue;
+      }
+      if ignored {
+        read_state_ref.latest_instant = max(read_state_ref.latest_instant, timestamp);
+        read_state_ref
+          .last_read_sn
+          .insert(cc.writer_guid, cc.sequence_number);
+        continue;
+      }
+      let decoded_value = decoded_value.map(|(decoded, elapsed)| {
+        self.record_deserialization(&cc, elapsed);
+        decoded
+      });
+      let (_, hash_to_key_map) = read_state_ref.get_sn_map_and_hash_map();
+      match Self::deserialize(timestamp, &cc, hash_to_key_map, decoded_value) {
+        Ok(dcc) => {
+          read_state_ref.latest_instant = max(read_state_ref.latest_instant, timestamp);
+          read_state_ref
+            .last_read_sn
+            .insert(dcc.writer_guid, dcc.sequence_number);
+          if self.deliver(dcc.writer_guid, dcc.sequence_number) {
+            dccs.push(dcc);
+          }
+        }
+        Err(ser_err) if dccs.is_empty() => return Err(self.deserialization_error(&ser_err)),
+        Err(_) => break, // Error will come again on next call
+      }
+    }
+    Ok(dccs)
+  }
+}
+
+impl<D, DA> SimpleDataReader<D, DA>
+where
+  D: Keyed + 'static,
+  DA: DeserializerAdapter<D> + SeedDeserializerAdapter<D>,
+{
+  /// Like [`try_take_one`](Self::try_take_one), but the data value is
+  /// deserialized with `seed` instead of into `D`. This allows deserializing
+  /// e.g. into an arena or into buffers reused from previous samples. Dispose
+  /// samples carry only the key, as usual.
+  ///
+  /// The DataReader remembers the keys of received instances, so that it can
+  /// report disposes that carry only a key hash. Values deserialized with a
+  /// seed are not seen by the DataReader, so their keys are not remembered.
+  ///
+  /// This is not available with
+  /// [`TimeOrderedDelivery`](policy::TimeOrderedDelivery), which holds back
+  /// already deserialized samples, and fails with
+  /// [`ReadError::UnsupportedQos`].
+  ///
+  /// Note: Always remember to call .drain_read_notifications() just before
+  /// calling this one. Otherwise, new notifications may not appear.
+  pub fn try_take_one_with_deserializer<S, T>(&self, seed: S) -> ReadResult<Option<Sample<T, D::K>>>
+  where
+    S: for<'de> DeserializeSeed<'de, Value = T>,
+  {
+    self.try_take_one_with_deserializer_in_group(seed, self.shared_group_view().as_ref())
+  }
+
+  // Takes at most `max_samples` samples, like repeated calls to
+  // try_take_one_with_deserializer would. If a sample fails to deserialize,
+  // the samples before it are returned, and the error is returned from the
+  // next call.
+  pub(crate) fn try_take_with_deserializer<S, T>(
+    &self,
+    max_samples: usize,
+    mut seed: impl FnMut() -> S,
+  ) -> ReadResult<Vec<Sample<T, D::K>>>
+  where
+    S: for<'de> DeserializeSeed<'de, Value = T>,
+  {
+    // The group membership is looked up once for the whole batch.
+    let shared_group = self.shared_group_view();
+    let mut samples = Vec::new();
+    while samples.len() < max_samples {
+      match self.try_take_one_with_deserializer_in_group(seed(), shared_group.as_ref()) {
+        Ok(Some(sample)) => samples.push(sample),
+        Ok(None) => break,
+        Err(e) if samples.is_empty() => return Err(e),
+        Err(_) => break, // Error will come again on next call
+      }
+    }
+    Ok(samples)
+  }
+
+  fn try_take_one_with_deserializer_in_group<S, T>(
+    &self,
+    seed: S,
+    shared_group: Option<&SharedGroupView>,
+  ) -> ReadResult<Option<Sample<T, D::K>>>
+  where
+    S: for<'de> DeserializeSeed<'de, Value = T>,
+  {
+    if !self.is_enabled() {
+      return Err(ReadError::NotEnabled);
+    }
+    if self.qos_policy.time_ordered_delivery().is_some() {
+      return Err(ReadError::UnsupportedQos {
+        reason: "TimeOrderedDelivery cannot be used with a custom deserializer".to_string(),
+      });
+    }
+    let is_reliable = matches!(
+      self.qos_policy.reliability(),
+      Some(policy::Reliability::Reliable { .. })
+    );
+    let topic_cache = self.acquire_the_topic_cache_guard();
+    let mut read_state_ref = self.read_state.lock().unwrap();
+    loop {
+      let latest_instant = read_state_ref.latest_instant;
+      let (last_read_sn, hash_to_key_map) = read_state_ref.get_sn_map_and_hash_map();
+      let Some((timestamp, cc)) =
+        Self::try_take_undecoded(is_reliable, &topic_cache, latest_instant, last_read_sn).next()
+      else {
+        return Ok(None);
+      };
+
+      let (writer_guid, sequence_number) = (cc.writer_guid, cc.sequence_number);
+      let dispose = match cc.data_value {
+        // Samples of other SharedSubscription group members are skipped
+        // before the seed is spent on them.
+        _ if local_endpoints::match_ignored(self.my_guid, cc.writer_guid)
+          || self.was_delivered(cc)
+          || !Self::is_mine(shared_group, cc, hash_to_key_map) =>
+        {
+          None
+        }
+        DDSData::Data {
+          ref serialized_payload,
+          ..
+        } => {
+          let started = Instant::now();
+          let value = Self::decode_value_with_seed(serialized_payload, cc, seed);
+          self.record_deserialization(cc, started.elapsed());
+          return match value {
+            Ok(value) => {
+              read_state_ref.advance(timestamp, writer_guid, sequence_number);
+              // Not delivered yet, as checked above
+              self.deliver(writer_guid, sequence_number);
+              Ok(Some(Sample::Value(value)))
+            }
+            Err(reason) => Err(self.deserialization_error(&ReadError::Deserialization { reason })),
+          };
+        }
+        _ => match Self::deserialize(timestamp, cc, hash_to_key_map, None) {
+          Ok(dcc) => Some(match dcc.sample {
+            Sample::Value(d) => d.key(),
+            Sample::Dispose(k) => k,
+          }),
+          Err(ser_err) => return Err(self.deserialization_error(&ser_err)),
+        },
+      };
+
+      read_state_ref.advance(timestamp, writer_guid, sequence_number);
+      if let Some(key) = dispose {
+        self.deliver(writer_guid, sequence_number);
+        return Ok(Some(Sample::Dispose(key)));
+      }
+    }
+  }
+
+  // Like decode_value, but with a DeserializeSeed
+  fn decode_value_with_seed<S, T>(
+    serialized_payload: &SerializedPayload,
+    cc: &CacheChange,
+    seed: S,
+  ) -> Result<T, String>
+  where
+    S: for<'de> DeserializeSeed<'de, Value = T>,
+  {
+    let (payload, representation_id, compression) = Self::undecoded_value(serialized_payload, cc);
+    let payload = match compression {
+      Some(parameter) => compression::decompress(&payload, &parameter)?,
+      None => payload,
+    };
+    if DA::supported_encodings().contains(&representation_id) {
+      DA::from_bytes_seed(&payload, representation_id, seed)
+        .map_err(|e| format!("Failed to deserialize sample bytes: {e}, "))
+    } else {
+      Err(format!("Unknown representation id {representation_id:?}."))
+    }
+  }
+}
+
 // This is  not part of DDS spec. We implement mio Evented so that the
 // application can asynchronously poll DataReader(s).
 impl<D, DA> Evented for SimpleDataReader<D, DA>
@@ -443,6 +1423,25 @@ where
   }
 }
 
+/// For polling the SimpleDataReader outside mio, e.g. with poll(2), or from C
+/// code. The file descriptor is readable when new data may be available. It
+/// stays readable until
+/// [`drain_read_notifications`](SimpleDataReader::drain_read_notifications)
+/// is called, so call that first and then take until there is no more data.
+///
+/// The file descriptor is owned by the SimpleDataReader, and must not be
+/// closed or read from.
+#[cfg(not(target_os = "windows"))]
+impl<D, DA> AsRawFd for SimpleDataReader<D, DA>
+where
+  D: Keyed,
+  DA: DeserializerAdapter<D>,
+{
+  fn as_raw_fd(&self) -> RawFd {
+    self.event_source.as_raw_fd()
+  }
+}
+
 impl<'a, D, DA> StatusEvented<'a, DataReaderStatus, SimpleDataReaderEventStream<'a, D, DA>>
   for SimpleDataReader<D, DA>
 where
@@ -3,12 +3,16 @@ pub(crate) mod content_filter_property;
 #[allow(clippy::module_inception)]
 pub(crate) mod discovery;
 pub(crate) mod discovery_db;
+pub(crate) mod discovery_filter;
+pub(crate) mod discovery_snapshot;
 
 #[cfg(feature = "security")]
 pub(crate) mod secure_discovery;
 
 pub(crate) mod sedp_messages;
 pub(crate) mod spdp_participant_data;
+pub(crate) mod static_discovery;
 
+pub use discovery_snapshot::*;
 pub use sedp_messages::*;
 pub use spdp_participant_data::*;
@@ -40,8 +40,15 @@ impl BuiltinEndpointSet {
   pub const PARTICIPANT_SECURE_READER: u32 = 1 << 27;
 
   // non-security again
-  pub const TOPICS_ANNOUNCER: u32 = 0x08000000;
-  pub const TOPICS_DETECTOR: u32 = 0x10000000;
+  // RTPS spec v2.5 Section 9.3.2 Table 9.4
+  pub const TOPICS_ANNOUNCER: u32 = 1 << 28;
+  pub const TOPICS_DETECTOR: u32 = 1 << 29;
+
+  // RustDDS-specific: the ping responder (request reader and reply writer)
+  // and requester (request writer and reply reader). Other vendors may use
+  // these bits for something else.
+  pub const RUSTDDS_PING_RESPONDER: u32 = 1 << 30;
+  pub const RUSTDDS_PING_REQUESTER: u32 = 1 << 31;
 
   pub fn from_u32(val: u32) -> Self {
     Self { value: val }
@@ -52,6 +59,87 @@ impl BuiltinEndpointSet {
   }
 }
 
+/// Which built-in Discovery endpoints a DomainParticipant creates, see
+/// [`DomainParticipantBuilder::builtin_endpoints`](crate::DomainParticipantBuilder::builtin_endpoints).
+///
+/// Leaving endpoints out saves memory, sockets, and network traffic on
+/// constrained devices, e.g. sensor nodes that only publish. Remote endpoints
+/// must then be configured with [`StaticDiscovery`](crate::StaticDiscovery),
+/// and remote participants must know ours in the same way.
+///
+/// The default creates all endpoints. These options cannot be used together
+/// with DDS Security.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BuiltinEndpointOptions {
+  /// Create the SEDP endpoints (DCPSPublication, DCPSSubscription and
+  /// DCPSTopic), which announce the local DataReaders and DataWriters and
+  /// discover the remote ones. Default is `true`.
+  pub sedp: bool,
+  /// Create the DCPSParticipantMessage endpoints, which carry the liveliness
+  /// of DataWriters with Automatic or ManualByParticipant liveliness. Without
+  /// them, such DataWriters cannot be created with a finite lease duration.
+  /// Default is `true`.
+  pub participant_message: bool,
+  /// Create the RustDDS-specific ping endpoints, which answer the round-trip
+  /// measurements of remote participants and allow
+  /// [`DomainParticipant::measure_rtt`](crate::DomainParticipant::measure_rtt).
+  /// Default is `false`.
+  pub ping: bool,
+  /// Publish only. No DataReaders can be created, SEDP,
+  /// DCPSParticipantMessage, and ping endpoints are left out regardless of
+  /// the fields above, and no multicast sockets are opened. The participant
+  /// still announces itself with SPDP. Default is `false`.
+  pub send_only: bool,
+}
+
+impl Default for BuiltinEndpointOptions {
+  fn default() -> Self {
+    Self {
+      sedp: true,
+      participant_message: true,
+      ping: false,
+      send_only: false,
+    }
+  }
+}
+
+impl BuiltinEndpointOptions {
+  pub(crate) fn sedp_enabled(&self) -> bool {
+    self.sedp && !self.send_only
+  }
+
+  pub(crate) fn participant_message_enabled(&self) -> bool {
+    self.participant_message && !self.send_only
+  }
+
+  pub(crate) fn ping_enabled(&self) -> bool {
+    self.ping && !self.send_only
+  }
+
+  // The endpoints to announce in SPDP, not counting DDS Security
+  pub(crate) fn endpoint_set(&self) -> u32 {
+    let mut endpoints =
+      BuiltinEndpointSet::PARTICIPANT_ANNOUNCER | BuiltinEndpointSet::PARTICIPANT_DETECTOR;
+    if self.sedp_enabled() {
+      endpoints |= BuiltinEndpointSet::PUBLICATIONS_ANNOUNCER
+        | BuiltinEndpointSet::PUBLICATIONS_DETECTOR
+        | BuiltinEndpointSet::SUBSCRIPTIONS_ANNOUNCER
+        | BuiltinEndpointSet::SUBSCRIPTIONS_DETECTOR
+        | BuiltinEndpointSet::TOPICS_ANNOUNCER
+        | BuiltinEndpointSet::TOPICS_DETECTOR;
+    }
+    if self.participant_message_enabled() {
+      endpoints |= BuiltinEndpointSet::PARTICIPANT_MESSAGE_DATA_WRITER
+        | BuiltinEndpointSet::PARTICIPANT_MESSAGE_DATA_READER;
+    }
+    if self.ping_enabled() {
+      endpoints |=
+        BuiltinEndpointSet::RUSTDDS_PING_RESPONDER | BuiltinEndpointSet::RUSTDDS_PING_REQUESTER;
+    }
+    endpoints
+  }
+}
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone, Readable, Writable)]
 pub struct BuiltinEndpointQos {
   value: u32,
@@ -64,3 +152,67 @@ impl BuiltinEndpointQos {
     self.value == Self::BEST_EFFORT_PARTICIPANT_MESSAGE_DATA_READER
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::rtps::constant::{
+    PING_BUILTIN_READERS_INIT_LIST, PING_BUILTIN_WRITERS_INIT_LIST,
+    STANDARD_BUILTIN_READERS_INIT_LIST, STANDARD_BUILTIN_WRITERS_INIT_LIST,
+  };
+
+  #[test]
+  fn builtin_endpoint_bits_are_distinct() {
+    let all = [
+      BuiltinEndpointSet::PARTICIPANT_ANNOUNCER,
+      BuiltinEndpointSet::PARTICIPANT_DETECTOR,
+      BuiltinEndpointSet::PUBLICATIONS_ANNOUNCER,
+      BuiltinEndpointSet::PUBLICATIONS_DETECTOR,
+      BuiltinEndpointSet::SUBSCRIPTIONS_ANNOUNCER,
+      BuiltinEndpointSet::SUBSCRIPTIONS_DETECTOR,
+      BuiltinEndpointSet::PARTICIPANT_MESSAGE_DATA_WRITER,
+      BuiltinEndpointSet::PARTICIPANT_MESSAGE_DATA_READER,
+      BuiltinEndpointSet::PUBLICATIONS_SECURE_WRITER,
+      BuiltinEndpointSet::PUBLICATIONS_SECURE_READER,
+      BuiltinEndpointSet::SUBSCRIPTIONS_SECURE_WRITER,
+      BuiltinEndpointSet::SUBSCRIPTIONS_SECURE_READER,
+      BuiltinEndpointSet::PARTICIPANT_MESSAGE_SECURE_WRITER,
+      BuiltinEndpointSet::PARTICIPANT_MESSAGE_SECURE_READER,
+      BuiltinEndpointSet::PARTICIPANT_STATELESS_MESSAGE_WRITER,
+      BuiltinEndpointSet::PARTICIPANT_STATELESS_MESSAGE_READER,
+      BuiltinEndpointSet::PARTICIPANT_VOLATILE_MESSAGE_SECURE_WRITER,
+      BuiltinEndpointSet::PARTICIPANT_VOLATILE_MESSAGE_SECURE_READER,
+      BuiltinEndpointSet::PARTICIPANT_SECURE_WRITER,
+      BuiltinEndpointSet::PARTICIPANT_SECURE_READER,
+      BuiltinEndpointSet::TOPICS_ANNOUNCER,
+      BuiltinEndpointSet::TOPICS_DETECTOR,
+      BuiltinEndpointSet::RUSTDDS_PING_RESPONDER,
+      BuiltinEndpointSet::RUSTDDS_PING_REQUESTER,
+    ];
+    let union = all.iter().fold(0, |acc, bit| {
+      assert_eq!(bit.count_ones(), 1);
+      assert_eq!(acc & bit, 0, "Bit {bit:#x} is defined twice");
+      acc | bit
+    });
+    assert_eq!(union.count_ones() as usize, all.len());
+
+    // Each remote endpoint in the init lists must be identified by its own bit
+    for list in [
+      STANDARD_BUILTIN_READERS_INIT_LIST,
+      STANDARD_BUILTIN_WRITERS_INIT_LIST,
+      PING_BUILTIN_READERS_INIT_LIST,
+      PING_BUILTIN_WRITERS_INIT_LIST,
+    ] {
+      list.iter().fold(0, |acc, (_, _, bit)| {
+        assert_eq!(acc & bit, 0, "Bit {bit:#x} is used twice");
+        acc | bit
+      });
+    }
+
+    let set = BuiltinEndpointSet::from_u32(
+      BuiltinEndpointSet::TOPICS_ANNOUNCER | BuiltinEndpointSet::PARTICIPANT_ANNOUNCER,
+    );
+    assert!(set.contains(BuiltinEndpointSet::TOPICS_ANNOUNCER));
+    assert!(!set.contains(BuiltinEndpointSet::PARTICIPANT_SECURE_READER));
+  }
+}
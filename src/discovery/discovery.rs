@@ -1,7 +1,8 @@
 use std::{
-  collections::HashMap,
+  collections::{BTreeSet, HashMap},
+  hash::Hash,
   sync::{Arc, RwLock},
-  time::Duration as StdDuration,
+  time::{Duration as StdDuration, Instant},
 };
 
 #[allow(unused_imports)]
@@ -22,17 +23,27 @@ use crate::{
     },
     readcondition::ReadCondition,
     result::{CreateError, CreateResult},
-    statusevents::{DomainParticipantStatusEvent, LostReason, StatusChannelSender},
+    statusevents::{
+      DomainParticipantStatusEvent, EndpointDescription, LostReason, ParticipantDescription,
+      StatusChannelSender,
+    },
   },
   discovery::{
+    builtin_endpoint::BuiltinEndpointOptions,
     discovery_db::{discovery_db_read, discovery_db_write, DiscoveredVia, DiscoveryDB},
+    discovery_filter::DiscoveryFilter,
     sedp_messages::{
       DiscoveredReaderData, DiscoveredTopicData, DiscoveredWriterData, Endpoint_GUID,
-      ParticipantMessageData, ParticipantMessageDataKind,
+      ParticipantMessageData, ParticipantMessageDataKind, PublicationBuiltinTopicData, WriterProxy,
     },
     spdp_participant_data::{Participant_GUID, SpdpDiscoveredParticipantData},
+    static_discovery::StaticDiscovery,
+  },
+  ping::PingResponder,
+  rtps::{
+    compatibility::{self, Feature},
+    constant::*,
   },
-  rtps::constant::*,
   serialization::{
     cdr_deserializer::CDRDeserializerAdapter, cdr_serializer::CDRSerializerAdapter,
     pl_cdr_adapters::*,
@@ -42,7 +53,6 @@ use crate::{
     entity::RTPSEntity,
     guid::{EntityId, GuidPrefix, GUID},
     locator::Locator,
-    time::Timestamp,
   },
   with_key::{DataReader, DataWriter, Sample},
   DomainParticipant,
@@ -74,6 +84,7 @@ pub enum DiscoveryCommand {
     guid: GUID,
   },
   ManualAssertLiveliness,
+  RefreshParticipantInfo,
   AssertTopicLiveliness {
     writer_guid: GUID,
     manual_assertion: bool,
@@ -92,14 +103,14 @@ pub enum DiscoveryCommand {
 }
 
 pub struct LivelinessState {
-  last_auto_update: Timestamp,
+  last_auto_update: Instant,
   manual_participant_liveness_refresh_requested: bool,
 }
 
 impl LivelinessState {
   pub fn new() -> Self {
     Self {
-      last_auto_update: Timestamp::now(),
+      last_auto_update: Instant::now(),
       manual_participant_liveness_refresh_requested: false,
     }
   }
@@ -193,9 +204,28 @@ pub(crate) struct Discovery {
 
   participant_status_sender: StatusChannelSender<DomainParticipantStatusEvent>,
 
+  // Application-defined admission control for remote participants and endpoints
+  discovery_filter: DiscoveryFilter,
+
+  // Remote participants and endpoints known without SEDP, and the GUIDs of
+  // the static endpoints added to the DiscoveryDB. These are not subject to
+  // the discovery filter.
+  static_discovery: StaticDiscovery,
+  static_endpoints: BTreeSet<GUID>,
+
   // TODO: Why is this a HashMap? Are there ever more than 2?
   self_locators: HashMap<Token, Vec<Locator>>,
 
+  // Unicast locators of other participants that have announced our GUID
+  // prefix. Each is reported only once.
+  guid_prefix_collisions: BTreeSet<Vec<Locator>>,
+
+  // What we have last published of our own endpoints and topics.
+  published_readers: Published<GUID, DiscoveredReaderData>,
+  // Without DiscoveredWriterData::last_updated, which is not published
+  published_writers: Published<GUID, (WriterProxy, PublicationBuiltinTopicData)>,
+  published_topics: Published<String, DiscoveredTopicData>,
+
   // DDS Subscriber and Publisher for Discovery
   // ...but these are not actually used after initialization
   // discovery_subscriber: Subscriber,
@@ -208,18 +238,24 @@ pub(crate) struct Discovery {
   dcps_participant: with_key::DiscoveryTopicPlCdr<SpdpDiscoveredParticipantData>,
   participant_cleanup_timer: Timer<()>, // garbage collection timer for dead remote participants
 
+  // The SEDP and DCPSParticipantMessage topics below are None, if they have
+  // been left out by BuiltinEndpointOptions.
+
   // Topic "DCPSSubscription" - announcing and detecting Readers
-  dcps_subscription: with_key::DiscoveryTopicPlCdr<DiscoveredReaderData>,
+  dcps_subscription: Option<with_key::DiscoveryTopicPlCdr<DiscoveredReaderData>>,
 
   // Topic "DCPSPublication" - announcing and detecting Writers
-  dcps_publication: with_key::DiscoveryTopicPlCdr<DiscoveredWriterData>,
+  dcps_publication: Option<with_key::DiscoveryTopicPlCdr<DiscoveredWriterData>>,
 
   // Topic "DCPSTopic" - announcing and detecting topics
-  dcps_topic: with_key::DiscoveryTopicPlCdr<DiscoveredTopicData>,
+  dcps_topic: Option<with_key::DiscoveryTopicPlCdr<DiscoveredTopicData>>,
   topic_cleanup_timer: Timer<()>,
 
   // DCPSParticipantMessage - used by participants to communicate liveness
-  dcps_participant_message: with_key::DiscoveryTopicCDR<ParticipantMessageData>,
+  dcps_participant_message: Option<with_key::DiscoveryTopicCDR<ParticipantMessageData>>,
+
+  // RustDDS-specific ping endpoints, if enabled by BuiltinEndpointOptions
+  ping_responder: Option<PingResponder>,
 
   // If security is enabled, this field contains a SecureDiscovery struct, an appendix
   // which is used for Secure functionality
@@ -259,11 +295,42 @@ pub(crate) struct Discovery {
   cached_secure_discovery_messages_resend_timer: Timer<()>,
 }
 
+// Our own endpoints or topics as last published to SEDP, by key. An
+// announcement is written only if it differs from the published one, so that
+// e.g. repeated AddLocalWriter commands do not cause SEDP traffic.
+struct Published<K, D> {
+  announcements: HashMap<K, D>,
+}
+
+impl<K, D> Default for Published<K, D> {
+  fn default() -> Self {
+    Self {
+      announcements: HashMap::new(),
+    }
+  }
+}
+
+impl<K: Hash + Eq, D: PartialEq> Published<K, D> {
+  fn is_unchanged(&self, key: &K, data: &D) -> bool {
+    self.announcements.get(key) == Some(data)
+  }
+
+  fn insert(&mut self, key: K, data: D) {
+    self.announcements.insert(key, data);
+  }
+
+  // After a dispose, the next announcement is published regardless.
+  fn remove(&mut self, key: &K) {
+    self.announcements.remove(key);
+  }
+}
+
 impl Discovery {
   const PARTICIPANT_CLEANUP_PERIOD: StdDuration = StdDuration::from_secs(2);
   const TOPIC_CLEANUP_PERIOD: StdDuration = StdDuration::from_secs(60); // timer for cleaning up inactive topics
   const SEND_PARTICIPANT_INFO_PERIOD: StdDuration = StdDuration::from_secs(2);
   const CHECK_PARTICIPANT_MESSAGES: StdDuration = StdDuration::from_secs(1);
+  const MIN_CHECK_PARTICIPANT_MESSAGES: StdDuration = StdDuration::from_millis(10);
   #[cfg(feature = "security")]
   const CACHED_SECURE_DISCOVERY_MESSAGE_RESEND_PERIOD: StdDuration = StdDuration::from_secs(1);
 
@@ -282,6 +349,23 @@ impl Discovery {
     history: Some(History::KeepLast { depth: 1 }),
     resource_limits: None,
     lifespan: None,
+    user_data: None,
+    topic_data: None,
+    group_data: None,
+    writer_data_lifecycle: None,
+    reader_data_lifecycle: None,
+    publish_mode: None,
+    history_replay: None,
+    shared_subscription: None,
+    ignore_local: None,
+    entity_factory: None,
+    time_ordered_delivery: None,
+    instance_limit: None,
+    batching: None,
+    adaptive_timing: None,
+    compression: None,
+    payload_checksum: None,
+    notification_coalescing: None,
     #[cfg(feature = "security")]
     property: None,
   };
@@ -297,6 +381,9 @@ impl Discovery {
     self_locators: HashMap<Token, Vec<Locator>>,
     participant_status_sender: StatusChannelSender<DomainParticipantStatusEvent>,
     security_plugins_opt: Option<SecurityPluginsHandle>,
+    discovery_filter: DiscoveryFilter,
+    static_discovery: StaticDiscovery,
+    builtin_endpoints: BuiltinEndpointOptions,
   ) -> CreateResult<Self> {
     // helper macro to handle initialization failures.
     macro_rules! try_construct {
@@ -447,49 +534,56 @@ impl Discovery {
 
     // Subscriptions: What are the Readers on the network and what are they
     // subscribing to?
-    let dcps_subscription = construct_topic_and_poll!(
-      PlCdr,
-      with_key,
-      builtin_topic_names::DCPS_SUBSCRIPTION,
-      builtin_topic_type_names::DCPS_SUBSCRIPTION,
-      DiscoveredReaderData,
-      None,  // QoS
-      false, // Regular stateful RTPS Reader & Writer
-      EntityId::SEDP_BUILTIN_SUBSCRIPTIONS_READER,
-      DISCOVERY_READER_DATA_TOKEN,
-      EntityId::SEDP_BUILTIN_SUBSCRIPTIONS_WRITER,
-      None, // No timer
-    );
+    let sedp = builtin_endpoints.sedp_enabled();
+    let dcps_subscription = sedp.then(|| {
+      construct_topic_and_poll!(
+        PlCdr,
+        with_key,
+        builtin_topic_names::DCPS_SUBSCRIPTION,
+        builtin_topic_type_names::DCPS_SUBSCRIPTION,
+        DiscoveredReaderData,
+        None,  // QoS
+        false, // Regular stateful RTPS Reader & Writer
+        EntityId::SEDP_BUILTIN_SUBSCRIPTIONS_READER,
+        DISCOVERY_READER_DATA_TOKEN,
+        EntityId::SEDP_BUILTIN_SUBSCRIPTIONS_WRITER,
+        None, // No timer
+      )
+    });
 
     // Publication : Who are the Writers here and elsewhere
-    let dcps_publication = construct_topic_and_poll!(
-      PlCdr,
-      with_key,
-      builtin_topic_names::DCPS_PUBLICATION,
-      builtin_topic_type_names::DCPS_PUBLICATION,
-      DiscoveredWriterData,
-      None,  // QoS,
-      false, // Regular stateful RTPS Reader & Writer
-      EntityId::SEDP_BUILTIN_PUBLICATIONS_READER,
-      DISCOVERY_WRITER_DATA_TOKEN,
-      EntityId::SEDP_BUILTIN_PUBLICATIONS_WRITER,
-      None, // No timer
-    );
+    let dcps_publication = sedp.then(|| {
+      construct_topic_and_poll!(
+        PlCdr,
+        with_key,
+        builtin_topic_names::DCPS_PUBLICATION,
+        builtin_topic_type_names::DCPS_PUBLICATION,
+        DiscoveredWriterData,
+        None,  // QoS,
+        false, // Regular stateful RTPS Reader & Writer
+        EntityId::SEDP_BUILTIN_PUBLICATIONS_READER,
+        DISCOVERY_WRITER_DATA_TOKEN,
+        EntityId::SEDP_BUILTIN_PUBLICATIONS_WRITER,
+        None, // No timer
+      )
+    });
 
     // Topic topic (not a typo)
-    let dcps_topic = construct_topic_and_poll!(
-      PlCdr,
-      with_key,
-      builtin_topic_names::DCPS_TOPIC,
-      builtin_topic_type_names::DCPS_TOPIC,
-      DiscoveredTopicData,
-      None,  // QoS,
-      false, // Regular stateful RTPS Reader & Writer
-      EntityId::SEDP_BUILTIN_TOPIC_READER,
-      DISCOVERY_TOPIC_DATA_TOKEN,
-      EntityId::SEDP_BUILTIN_TOPIC_WRITER,
-      None, // No timer
-    );
+    let dcps_topic = sedp.then(|| {
+      construct_topic_and_poll!(
+        PlCdr,
+        with_key,
+        builtin_topic_names::DCPS_TOPIC,
+        builtin_topic_type_names::DCPS_TOPIC,
+        DiscoveredTopicData,
+        None,  // QoS,
+        false, // Regular stateful RTPS Reader & Writer
+        EntityId::SEDP_BUILTIN_TOPIC_READER,
+        DISCOVERY_TOPIC_DATA_TOKEN,
+        EntityId::SEDP_BUILTIN_TOPIC_WRITER,
+        None, // No timer
+      )
+    });
 
     // create lease duration check timer
     let mut topic_cleanup_timer: Timer<()> = Timer::default();
@@ -505,22 +599,48 @@ impl Discovery {
     );
 
     // Participant Message Data 8.4.13
-    let dcps_participant_message = construct_topic_and_poll!(
-      CDR,
-      with_key,
-      builtin_topic_names::DCPS_PARTICIPANT_MESSAGE,
-      builtin_topic_type_names::DCPS_PARTICIPANT_MESSAGE,
-      ParticipantMessageData,
-      Some(Self::PARTICIPANT_MESSAGE_QOS),
-      false, // Regular stateful RTPS Reader & Writer
-      EntityId::P2P_BUILTIN_PARTICIPANT_MESSAGE_READER,
-      DISCOVERY_PARTICIPANT_MESSAGE_TOKEN,
-      EntityId::P2P_BUILTIN_PARTICIPANT_MESSAGE_WRITER,
-      Some((
-        Self::CHECK_PARTICIPANT_MESSAGES,
-        DISCOVERY_PARTICIPANT_MESSAGE_TIMER_TOKEN,
-      )),
-    );
+    let dcps_participant_message = builtin_endpoints.participant_message_enabled().then(|| {
+      construct_topic_and_poll!(
+        CDR,
+        with_key,
+        builtin_topic_names::DCPS_PARTICIPANT_MESSAGE,
+        builtin_topic_type_names::DCPS_PARTICIPANT_MESSAGE,
+        ParticipantMessageData,
+        Some(Self::PARTICIPANT_MESSAGE_QOS),
+        false, // Regular stateful RTPS Reader & Writer
+        EntityId::P2P_BUILTIN_PARTICIPANT_MESSAGE_READER,
+        DISCOVERY_PARTICIPANT_MESSAGE_TOKEN,
+        EntityId::P2P_BUILTIN_PARTICIPANT_MESSAGE_WRITER,
+        Some((
+          Self::CHECK_PARTICIPANT_MESSAGES,
+          DISCOVERY_PARTICIPANT_MESSAGE_TIMER_TOKEN,
+        )),
+      )
+    });
+
+    // Ping
+    let ping_responder = if builtin_endpoints.ping_enabled() {
+      let responder = try_construct!(
+        PingResponder::new(
+          &domain_participant,
+          &discovery_subscriber,
+          &discovery_publisher
+        ),
+        "Unable to create ping responder. {:?}"
+      );
+      try_construct!(
+        poll.register(
+          responder.request_reader(),
+          DISCOVERY_PING_REQUEST_TOKEN,
+          Ready::readable(),
+          PollOpt::edge(),
+        ),
+        "Unable to register ping responder. {:?}"
+      );
+      Some(responder)
+    } else {
+      None
+    };
 
     // DDS Security
 
@@ -665,7 +785,14 @@ impl Discovery {
       discovery_command_receiver,
       spdp_liveness_receiver,
       participant_status_sender,
+      discovery_filter,
+      static_discovery,
+      static_endpoints: BTreeSet::new(),
       self_locators,
+      guid_prefix_collisions: BTreeSet::new(),
+      published_readers: Published::default(),
+      published_writers: Published::default(),
+      published_topics: Published::default(),
 
       liveliness_state: LivelinessState::new(),
 
@@ -678,6 +805,7 @@ impl Discovery {
       dcps_topic,
       topic_cleanup_timer,      // SEDP
       dcps_participant_message, // liveliness messages
+      ping_responder,
 
       security_opt,
       #[cfg(feature = "security")]
@@ -699,6 +827,7 @@ impl Discovery {
 
   pub fn discovery_event_loop(&mut self) {
     self.initialize_participant();
+    self.add_static_participants();
 
     // send out info about non-built-in Writers and Readers that we have.
     self.write_writers_info();
@@ -746,17 +875,19 @@ impl Discovery {
                   self.write_topic_info(&topic_name);
                 }
                 DiscoveryCommand::RemoveLocalWriter { guid } => {
-                  if guid == self.dcps_publication.writer.guid() {
+                  if guid.entity_id == EntityId::SEDP_BUILTIN_PUBLICATIONS_WRITER {
                     continue;
                   }
                   self.send_endpoint_dispose_message(guid);
+                  self.published_writers.remove(&guid);
                   discovery_db_write(&self.discovery_db).remove_local_topic_writer(guid);
                 }
                 DiscoveryCommand::RemoveLocalReader { guid } => {
-                  if guid == self.dcps_subscription.writer.guid() {
+                  if guid.entity_id == EntityId::SEDP_BUILTIN_SUBSCRIPTIONS_WRITER {
                     continue;
                   }
                   self.send_endpoint_dispose_message(guid);
+                  self.published_readers.remove(&guid);
                   discovery_db_write(&self.discovery_db).remove_local_topic_reader(guid);
                 }
                 DiscoveryCommand::ManualAssertLiveliness => {
@@ -764,6 +895,11 @@ impl Discovery {
                     .liveliness_state
                     .manual_participant_liveness_refresh_requested = true;
                 }
+                DiscoveryCommand::RefreshParticipantInfo => {
+                  if let Some(dp) = self.domain_participant.clone().upgrade() {
+                    self.send_participant_info(&dp);
+                  }
+                }
                 DiscoveryCommand::AssertTopicLiveliness {
                   writer_guid,
                   manual_assertion,
@@ -852,10 +988,16 @@ impl Discovery {
           }
           DISCOVERY_PARTICIPANT_MESSAGE_TIMER_TOKEN => {
             self.write_participant_message();
-            self
-              .dcps_participant_message
-              .timer
-              .set_timeout(Self::CHECK_PARTICIPANT_MESSAGES, ());
+            let check_period = self.participant_message_check_period();
+            if let Some(dcps_participant_message) = self.dcps_participant_message.as_mut() {
+              dcps_participant_message.timer.set_timeout(check_period, ());
+            }
+          }
+          DISCOVERY_PING_REQUEST_TOKEN => {
+            let my_guid_prefix = self.domain_participant.guid().prefix;
+            if let Some(ping_responder) = self.ping_responder.as_mut() {
+              ping_responder.reply(my_guid_prefix);
+            }
           }
           SPDP_LIVENESS_TOKEN => {
             while let Ok(guid_prefix) = self.spdp_liveness_receiver.try_recv() {
@@ -927,6 +1069,55 @@ impl Discovery {
     });
   }
 
+  // Add the participants of static discovery that have a known GUID prefix,
+  // as if they had been discovered.
+  fn add_static_participants(&mut self) {
+    for participant_data in self.static_discovery.participant_data() {
+      let guid_prefix = participant_data.participant_guid.prefix;
+      let was_new = discovery_db_write(&self.discovery_db).update_participant(&participant_data);
+      self
+        .send_discovery_notification(DiscoveryNotificationType::ParticipantUpdated { guid_prefix });
+      if was_new {
+        let dpd = (&participant_data).into();
+        self.send_participant_status(DomainParticipantStatusEvent::ParticipantDiscovered { dpd });
+      }
+      self.add_static_endpoints(&participant_data);
+    }
+  }
+
+  // Add the static endpoints of a known participant, as if they had been
+  // announced via SEDP.
+  fn add_static_endpoints(&mut self, participant_data: &SpdpDiscoveredParticipantData) {
+    let (readers, writers) = self.static_discovery.endpoints_of(participant_data);
+    for drd in readers {
+      info!(
+        "Static reader {:?} topic={:?}",
+        drd.reader_proxy.remote_reader_guid,
+        drd.subscription_topic_data.topic_name()
+      );
+      self
+        .static_endpoints
+        .insert(drd.reader_proxy.remote_reader_guid);
+      let discovered_reader_data = discovery_db_write(&self.discovery_db).update_subscription(&drd);
+      self.send_discovery_notification(DiscoveryNotificationType::ReaderUpdated {
+        discovered_reader_data,
+      });
+    }
+    for dwd in writers {
+      info!(
+        "Static writer {:?} topic={:?}",
+        dwd.writer_proxy.remote_writer_guid, dwd.publication_topic_data.topic_name
+      );
+      self
+        .static_endpoints
+        .insert(dwd.writer_proxy.remote_writer_guid);
+      let discovered_writer_data = discovery_db_write(&self.discovery_db).update_publication(&dwd);
+      self.send_discovery_notification(DiscoveryNotificationType::WriterUpdated {
+        discovered_writer_data,
+      });
+    }
+  }
+
   pub fn handle_participant_reader(&mut self) {
     loop {
       let s = self.dcps_participant.reader.take_next_sample();
@@ -962,6 +1153,13 @@ impl Discovery {
                 self.process_discovered_participant_data(&participant_data);
               }
               // Sample::Dispose means that DomainParticipant was disposed
+              Sample::Dispose(participant_guid)
+                if participant_guid.0.prefix == self.domain_participant.guid().prefix =>
+              {
+                // We are not going anywhere. This can only come from another
+                // participant with our GUID prefix.
+                warn!("Ignoring disposal of our own participant GUID {participant_guid:?}");
+              }
               Sample::Dispose(participant_guid) => {
                 self.process_participant_dispose(participant_guid.0.prefix);
               }
@@ -984,12 +1182,36 @@ impl Discovery {
     &mut self,
     participant_data: &SpdpDiscoveredParticipantData,
   ) {
-    let was_new = discovery_db_write(&self.discovery_db).update_participant(participant_data);
+    if participant_data.participant_guid.prefix == self.domain_participant.guid().prefix
+      && !self.is_own_participant_data(participant_data)
+    {
+      self.report_guid_prefix_collision(participant_data);
+      return;
+    }
+    if !self.participant_admitted(participant_data) {
+      debug!(
+        "Discovery filter refused participant {:?}",
+        participant_data.participant_guid
+      );
+      return;
+    }
     let guid_prefix = participant_data.participant_guid.prefix;
+    let (was_new, user_data_changed) = {
+      let mut db = discovery_db_write(&self.discovery_db);
+      let user_data_changed = db
+        .find_participant_proxy(guid_prefix)
+        .is_some_and(|old| old.user_data != participant_data.user_data);
+      (db.update_participant(participant_data), user_data_changed)
+    };
     self.send_discovery_notification(DiscoveryNotificationType::ParticipantUpdated { guid_prefix });
+    if user_data_changed && participant_data.participant_guid != self.domain_participant.guid() {
+      let dpd = participant_data.into();
+      self.send_participant_status(DomainParticipantStatusEvent::ParticipantUpdated { dpd });
+    }
     if was_new {
       let dpd = participant_data.into();
       self.send_participant_status(DomainParticipantStatusEvent::ParticipantDiscovered { dpd });
+      self.add_static_endpoints(participant_data);
       // This may be a rediscovery of a previously seen participant that
       // was temporarily lost due to network outage. Check if we already know
       // what it has (readers, writers, topics).
@@ -1001,6 +1223,106 @@ impl Discovery {
     }
   }
 
+  // Our own participant announcements loop back to us. Data with our GUID
+  // prefix, but with other unicast locators, is from another participant that
+  // has the same GUID prefix.
+  fn is_own_participant_data(&self, participant_data: &SpdpDiscoveredParticipantData) -> bool {
+    let same_locators = |announced: &[Locator], token: Token| {
+      let own: BTreeSet<&Locator> = self
+        .self_locators
+        .get(&token)
+        .into_iter()
+        .flatten()
+        .collect();
+      announced.iter().collect::<BTreeSet<_>>() == own
+    };
+    same_locators(
+      &participant_data.metatraffic_unicast_locators,
+      DISCOVERY_LISTENER_TOKEN,
+    ) && same_locators(
+      &participant_data.default_unicast_locators,
+      USER_TRAFFIC_LISTENER_TOKEN,
+    )
+  }
+
+  fn report_guid_prefix_collision(&mut self, participant_data: &SpdpDiscoveredParticipantData) {
+    let remote_locators: Vec<Locator> = participant_data
+      .metatraffic_unicast_locators
+      .iter()
+      .chain(participant_data.default_unicast_locators.iter())
+      .copied()
+      .collect::<BTreeSet<_>>()
+      .into_iter()
+      .collect();
+    if self.guid_prefix_collisions.insert(remote_locators.clone()) {
+      error!(
+        "Another participant at {:?} announces our GUID prefix {:?}. Communication with both \
+         participants is unreliable. Ignoring its discovery data.",
+        remote_locators, participant_data.participant_guid.prefix
+      );
+      self.send_participant_status(DomainParticipantStatusEvent::GuidPrefixCollision {
+        remote: participant_data.into(),
+        remote_locators,
+      });
+    }
+  }
+
+  // Our own participant is always admitted, as discovery relies on seeing it.
+  fn participant_admitted(&self, participant_data: &SpdpDiscoveredParticipantData) -> bool {
+    participant_data.participant_guid.prefix == self.domain_participant.guid().prefix
+      || self.discovery_filter.admits_participant(participant_data)
+  }
+
+  // Policies in RustDDS-specific parameters mean something else, if the reader
+  // is from another implementation. If the participant is not known yet, this
+  // is checked again when the reader is reprocessed after its participant has
+  // been discovered.
+  fn ignore_foreign_extensions(&self, reader_data: &mut DiscoveredReaderData) {
+    let participant = discovery_db_read(&self.discovery_db)
+      .find_participant_proxy(reader_data.reader_proxy.remote_reader_guid.prefix)
+      .map(|p| (p.vendor_id, p.protocol_version));
+    if let Some((vendor_id, version)) = participant {
+      if !compatibility::has_feature(vendor_id, version, Feature::RustDdsParameters) {
+        reader_data
+          .subscription_topic_data
+          .clear_rustdds_extensions();
+      }
+    }
+  }
+
+  fn endpoint_admitted(&self, endpoint: &EndpointDescription) -> bool {
+    let prefix = endpoint.guid.prefix;
+    if prefix == self.domain_participant.guid().prefix {
+      return true;
+    }
+    // If admission depends on participant data, we must know the participant.
+    // Endpoints that arrive before their participant are processed again
+    // when the participant is discovered.
+    let participant: Option<ParticipantDescription> = discovery_db_read(&self.discovery_db)
+      .find_participant_proxy(prefix)
+      .map(|p| p.into());
+    if self.discovery_filter.has_participant_data_rules() && participant.is_none() {
+      return false;
+    }
+    if !self.discovery_filter.admits_endpoint(endpoint) {
+      return false;
+    }
+    match participant.map(|p| self.discovery_filter.authorize_endpoint(endpoint, &p)) {
+      Some(Err(reason)) => {
+        warn!(
+          "Endpoint authorizer vetoed {:?} on topic {:?}: {}",
+          endpoint.guid, endpoint.topic_name, reason
+        );
+        self.send_participant_status(DomainParticipantStatusEvent::RemoteEndpointRejected {
+          endpoint: endpoint.clone(),
+          reason,
+        });
+        false
+      }
+      _ => true,
+    }
+  }
+
   fn process_participant_dispose(&mut self, participant_guidp: GuidPrefix) {
     discovery_db_write(&self.discovery_db).remove_participant(participant_guidp, true); // true = actively removed
     self.send_discovery_notification(DiscoveryNotificationType::ParticipantLost {
@@ -1015,11 +1337,12 @@ impl Discovery {
   fn send_endpoint_dispose_message(&self, endpoint_guid: GUID) {
     let is_writer = endpoint_guid.entity_id.entity_kind.is_writer();
     if is_writer {
-      self
-        .dcps_publication
-        .writer
-        .dispose(&Endpoint_GUID(endpoint_guid), None)
-        .unwrap_or_else(|e| error!("Disposing local Writer: {e:?}"));
+      if let Some(dcps_publication) = &self.dcps_publication {
+        dcps_publication
+          .writer
+          .dispose(&Endpoint_GUID(endpoint_guid), None)
+          .unwrap_or_else(|e| error!("Disposing local Writer: {e:?}"));
+      }
       #[cfg(feature = "security")]
       self
         .dcps_publications_secure
@@ -1028,11 +1351,12 @@ impl Discovery {
         .unwrap_or_else(|e| error!("Disposing local Writer: {e:?}"));
     } else {
       // is reader
-      self
-        .dcps_subscription
-        .writer
-        .dispose(&Endpoint_GUID(endpoint_guid), None)
-        .unwrap_or_else(|e| error!("Disposing local Reader: {e:?}"));
+      if let Some(dcps_subscription) = &self.dcps_subscription {
+        dcps_subscription
+          .writer
+          .dispose(&Endpoint_GUID(endpoint_guid), None)
+          .unwrap_or_else(|e| error!("Disposing local Reader: {e:?}"));
+      }
       #[cfg(feature = "security")]
       self
         .dcps_subscriptions_secure
@@ -1068,8 +1392,11 @@ impl Discovery {
 
   // Check if there are messages about new Readers
   pub fn handle_subscription_reader(&mut self, read_history: Option<GuidPrefix>) {
+    let Some(dcps_subscription) = self.dcps_subscription.as_mut() else {
+      return;
+    };
     let drds: Vec<Sample<DiscoveredReaderData, GUID>> =
-      match self.dcps_subscription.reader.into_iterator() {
+      match dcps_subscription.reader.into_iterator() {
         Ok(ds) => ds
           .map(|d| d.map_dispose(|g| g.0)) // map_dispose removes Endpoint_GUID wrapper around GUID
           .filter(|d|
@@ -1103,7 +1430,22 @@ impl Discovery {
 
       if permission == NormalDiscoveryPermission::Allow {
         match d {
-          Sample::Value(d) => {
+          Sample::Value(d) if !self.endpoint_admitted(&(&d).into()) => {
+            let reader_guid = d.reader_proxy.remote_reader_guid;
+            debug!("Discovery filter refused reader {:?}", reader_guid);
+            // An update may make a previously admitted reader refused, e.g. by
+            // changing its QoS. Then it must be unmatched. Static readers are
+            // not subject to the filter.
+            if !self.static_endpoints.contains(&reader_guid)
+              && discovery_db_write(&self.discovery_db).remove_topic_reader(reader_guid)
+            {
+              info!("Updated reader {reader_guid:?} is no longer admitted. Unmatching.");
+              self
+                .send_discovery_notification(DiscoveryNotificationType::ReaderLost { reader_guid });
+            }
+          }
+          Sample::Value(mut d) => {
+            self.ignore_foreign_extensions(&mut d);
             let drd = discovery_db_write(&self.discovery_db).update_subscription(&d);
             debug!(
               "handle_subscription_reader - send_discovery_notification ReaderUpdated  {:?}",
@@ -1137,8 +1479,11 @@ impl Discovery {
   }
 
   pub fn handle_publication_reader(&mut self, read_history: Option<GuidPrefix>) {
+    let Some(dcps_publication) = self.dcps_publication.as_mut() else {
+      return;
+    };
     let dwds: Vec<Sample<DiscoveredWriterData, GUID>> =
-      match self.dcps_publication.reader.into_iterator() {
+      match dcps_publication.reader.into_iterator() {
         // a lot of cloning here, but we must copy the data out of the
         // reader before we can use self again, as .read() returns references to within
         // a reader and thus self
@@ -1176,6 +1521,18 @@ impl Discovery {
 
       if permission == NormalDiscoveryPermission::Allow {
         match d {
+          Sample::Value(dwd) if !self.endpoint_admitted(&(&dwd).into()) => {
+            let writer_guid = dwd.writer_proxy.remote_writer_guid;
+            debug!("Discovery filter refused writer {:?}", writer_guid);
+            // Same as with readers above
+            if !self.static_endpoints.contains(&writer_guid)
+              && discovery_db_write(&self.discovery_db).remove_topic_writer(writer_guid)
+            {
+              info!("Updated writer {writer_guid:?} is no longer admitted. Unmatching.");
+              self
+                .send_discovery_notification(DiscoveryNotificationType::WriterLost { writer_guid });
+            }
+          }
           Sample::Value(dwd) => {
             trace!("handle_publication_reader discovered {:?}", &dwd);
             let discovered_writer_data =
@@ -1208,25 +1565,25 @@ impl Discovery {
   // DomainParticipants timing out and then coming back. The read_history was
   // supposed to help in recovering from that.
   pub fn handle_topic_reader(&mut self, _read_history: Option<GuidPrefix>) {
-    let ts: Vec<Sample<(DiscoveredTopicData, GUID), GUID>> = match self
-      .dcps_topic
-      .reader
-      .take(usize::MAX, ReadCondition::any())
-    {
-      Ok(ds) => ds
-        .iter()
-        .map(|d| {
-          d.value
-            .clone()
-            .map_value(|o| (o, d.sample_info.writer_guid()))
-            .map_dispose(|g| g.0)
-        })
-        .collect(),
-      Err(e) => {
-        error!("handle_topic_reader: {e:?}");
-        return;
-      }
+    let Some(dcps_topic) = self.dcps_topic.as_mut() else {
+      return;
     };
+    let ts: Vec<Sample<(DiscoveredTopicData, GUID), GUID>> =
+      match dcps_topic.reader.take(usize::MAX, ReadCondition::any()) {
+        Ok(ds) => ds
+          .iter()
+          .map(|d| {
+            d.value
+              .clone()
+              .map_value(|o| (o, d.sample_info.writer_guid()))
+              .map_dispose(|g| g.0)
+          })
+          .collect(),
+        Err(e) => {
+          error!("handle_topic_reader: {e:?}");
+          return;
+        }
+      };
 
     for t in ts {
       #[cfg(not(feature = "security"))]
@@ -1290,8 +1647,10 @@ impl Discovery {
   // and manual (by by application, via DDS API call) liveness
   pub fn handle_participant_message_reader(&mut self) {
     // First read from nonsecure reader
-    let mut samples = match self
-      .dcps_participant_message
+    let Some(dcps_participant_message) = self.dcps_participant_message.as_mut() else {
+      return;
+    };
+    let mut samples = match dcps_participant_message
       .reader
       .take(usize::MAX, ReadCondition::any())
     {
@@ -1354,7 +1713,32 @@ impl Discovery {
       });
   }
 
+  // How often to check if liveliness messages need to be sent. Messages are
+  // sent when half of the lease duration has elapsed, so checking at a quarter
+  // of the shortest lease keeps remote participants from timing out our
+  // writers.
+  fn participant_message_check_period(&self) -> StdDuration {
+    discovery_db_read(&self.discovery_db)
+      .get_all_local_topic_writers()
+      .filter_map(|p| match p.publication_topic_data.liveliness {
+        Some(Liveliness::Automatic { lease_duration })
+        | Some(Liveliness::ManualByParticipant { lease_duration }) => Some(lease_duration),
+        _ => None,
+      })
+      .min()
+      .map_or(Self::CHECK_PARTICIPANT_MESSAGES, |lease_duration| {
+        (lease_duration / 4).to_std().clamp(
+          Self::MIN_CHECK_PARTICIPANT_MESSAGES,
+          Self::CHECK_PARTICIPANT_MESSAGES,
+        )
+      })
+  }
+
   pub fn write_participant_message(&mut self) {
+    let Some(dcps_participant_message) = &self.dcps_participant_message else {
+      return;
+    };
+
     // Inspect if we need to send liveness messages
     // See 8.4.13.5 "Implementing Writer Liveliness Protocol .." in the RPTS spec
 
@@ -1372,14 +1756,16 @@ impl Discovery {
       })
       .min();
 
-    let timenow = Timestamp::now();
+    // Monotonic time, so that a wall clock step does not stop or flood the
+    // automatic liveliness updates.
+    let timenow = Instant::now();
 
     let mut messages_to_be_sent: Vec<ParticipantMessageData> = vec![];
 
     // Send Automatic liveness update if needed
     if let Some(min_auto_duration) = min_automatic_lease_duration_opt {
       let time_since_last_auto_update =
-        timenow.duration_since(self.liveliness_state.last_auto_update);
+        Duration::from(timenow.saturating_duration_since(self.liveliness_state.last_auto_update));
       trace!(
         "time_since_last_auto_update: {:?}, min_auto_duration {:?}",
         time_since_last_auto_update,
@@ -1399,12 +1785,11 @@ impl Discovery {
     }
 
     // Send ManualByParticipant liveliness update if someone has requested us to do
-    // so.
-    // Note: According to the RTPS spec (8.7.2.2.3 LIVELINESS) the interval at which
-    // we check if we need to send a manual liveness update should depend on the
-    // lease durations of writers with ManualByParticipant liveness QoS.
-    // Now we just check this at the same interval as with Automatic liveness.
-    // So TODO if needed: comply with the spec.
+    // so. The participant, or one of its writers with ManualByParticipant
+    // liveliness, has been asserted since the previous update.
+    // According to the RTPS spec (8.7.2.2.3 LIVELINESS) the interval at which
+    // we check this depends on the lease durations of writers with
+    // ManualByParticipant liveness QoS, see participant_message_check_period().
     if self
       .liveliness_state
       .manual_participant_liveness_refresh_requested
@@ -1421,18 +1806,18 @@ impl Discovery {
       let msg_kind = msg.kind;
 
       #[cfg(not(feature = "security"))]
-      let write_result = self.dcps_participant_message.writer.write(msg, None);
+      let write_result = dcps_participant_message.writer.write(msg, None);
 
       #[cfg(feature = "security")]
       let write_result = if let Some(security) = self.security_opt.as_ref() {
         security.write_liveness_message(
           &self.dcps_participant_message_secure.writer,
-          &self.dcps_participant_message.writer,
+          &dcps_participant_message.writer,
           msg,
         )
       } else {
         // No security enabled
-        self.dcps_participant_message.writer.write(msg, None)
+        dcps_participant_message.writer.write(msg, None)
       };
 
       match write_result {
@@ -1689,9 +2074,14 @@ impl Discovery {
     discovery_db_write(&self.discovery_db).topic_cleanup();
   }
 
-  pub fn write_single_reader_info(&self, guid: GUID) {
-    let db = discovery_db_read(&self.discovery_db);
-    if let Some(reader_data) = db.get_local_topic_reader(guid) {
+  pub fn write_single_reader_info(&mut self, guid: GUID) {
+    let Some(dcps_subscription) = &self.dcps_subscription else {
+      return; // Not announcing without SEDP
+    };
+    let reader_data_opt = discovery_db_read(&self.discovery_db)
+      .get_local_topic_reader(guid)
+      .cloned();
+    if let Some(reader_data) = reader_data_opt {
       if !reader_data
         .reader_proxy
         .remote_reader_guid
@@ -1702,6 +2092,10 @@ impl Discovery {
         // Only readers of user-defined topics are published to discovery
         return;
       }
+      if self.published_readers.is_unchanged(&guid, &reader_data) {
+        debug!("Reader {guid:?} is unchanged since last published. Not publishing again.");
+        return;
+      }
 
       #[cfg(not(feature = "security"))]
       let do_nonsecure_write = true;
@@ -1709,27 +2103,25 @@ impl Discovery {
       #[cfg(feature = "security")]
       let do_nonsecure_write = if let Some(security) = self.security_opt.as_ref() {
         security.write_single_reader_info(
-          &self.dcps_subscription.writer,
+          &dcps_subscription.writer,
           &self.dcps_subscriptions_secure.writer,
-          reader_data,
+          &reader_data,
         );
+        self.published_readers.insert(guid, reader_data.clone());
         false
       } else {
         true // No security configured
       };
 
       if do_nonsecure_write {
-        match self
-          .dcps_subscription
-          .writer
-          .write(reader_data.clone(), None)
-        {
+        match dcps_subscription.writer.write(reader_data.clone(), None) {
           Ok(()) => {
             debug!(
               "Published DCPSSubscription data on topic {}, reader guid {:?}",
               reader_data.subscription_topic_data.topic_name(),
               guid
             );
+            self.published_readers.insert(guid, reader_data);
           }
           Err(e) => {
             error!(
@@ -1746,9 +2138,8 @@ impl Discovery {
     }
   }
 
-  pub fn write_readers_info(&self) {
-    let db = discovery_db_read(&self.discovery_db);
-    let local_user_reader_guids = db
+  pub fn write_readers_info(&mut self) {
+    let local_user_reader_guids: Vec<GUID> = discovery_db_read(&self.discovery_db)
       .get_all_local_topic_readers()
       .filter(|p| {
         p.reader_proxy
@@ -1757,16 +2148,22 @@ impl Discovery {
           .kind()
           .is_user_defined()
       })
-      .map(|drd| drd.reader_proxy.remote_reader_guid);
+      .map(|drd| drd.reader_proxy.remote_reader_guid)
+      .collect();
 
     for guid in local_user_reader_guids {
       self.write_single_reader_info(guid);
     }
   }
 
-  pub fn write_single_writer_info(&self, guid: GUID) {
-    let db = discovery_db_read(&self.discovery_db);
-    if let Some(writer_data) = db.get_local_topic_writer(guid) {
+  pub fn write_single_writer_info(&mut self, guid: GUID) {
+    let Some(dcps_publication) = &self.dcps_publication else {
+      return; // Not announcing without SEDP
+    };
+    let writer_data_opt = discovery_db_read(&self.discovery_db)
+      .get_local_topic_writer(guid)
+      .cloned();
+    if let Some(writer_data) = writer_data_opt {
       if !writer_data
         .writer_proxy
         .remote_writer_guid
@@ -1777,6 +2174,14 @@ impl Discovery {
         // Only writers of user-defined topics are published to discovery
         return;
       }
+      let published_data = (
+        writer_data.writer_proxy.clone(),
+        writer_data.publication_topic_data.clone(),
+      );
+      if self.published_writers.is_unchanged(&guid, &published_data) {
+        debug!("Writer {guid:?} is unchanged since last published. Not publishing again.");
+        return;
+      }
 
       #[cfg(not(feature = "security"))]
       let do_nonsecure_write = true;
@@ -1784,27 +2189,25 @@ impl Discovery {
       #[cfg(feature = "security")]
       let do_nonsecure_write = if let Some(security) = self.security_opt.as_ref() {
         security.write_single_writer_info(
-          &self.dcps_publication.writer,
+          &dcps_publication.writer,
           &self.dcps_publications_secure.writer,
-          writer_data,
+          &writer_data,
         );
+        self.published_writers.insert(guid, published_data.clone());
         false
       } else {
         true // No security configured
       };
 
       if do_nonsecure_write {
-        match self
-          .dcps_publication
-          .writer
-          .write(writer_data.clone(), None)
-        {
+        match dcps_publication.writer.write(writer_data.clone(), None) {
           Ok(()) => {
             debug!(
               "Published DCPSPublication data on topic {}, writer guid {:?}",
               writer_data.publication_topic_data.topic_name(),
               guid
             );
+            self.published_writers.insert(guid, published_data);
           }
           Err(e) => {
             error!(
@@ -1821,9 +2224,8 @@ impl Discovery {
     }
   }
 
-  pub fn write_writers_info(&self) {
-    let db: std::sync::RwLockReadGuard<'_, DiscoveryDB> = discovery_db_read(&self.discovery_db);
-    let local_user_writer_guids = db
+  pub fn write_writers_info(&mut self) {
+    let local_user_writer_guids: Vec<GUID> = discovery_db_read(&self.discovery_db)
       .get_all_local_topic_writers()
       .filter(|p| {
         p.writer_proxy
@@ -1832,20 +2234,23 @@ impl Discovery {
           .kind()
           .is_user_defined()
       })
-      .map(|drd| drd.writer_proxy.remote_writer_guid);
+      .map(|drd| drd.writer_proxy.remote_writer_guid)
+      .collect();
 
     for guid in local_user_writer_guids {
       self.write_single_writer_info(guid);
     }
   }
 
-  pub fn write_topic_info(&self, topic_name: &str) {
-    let db = discovery_db_read(&self.discovery_db);
+  pub fn write_topic_info(&mut self, topic_name: &str) {
+    let Some(dcps_topic) = &self.dcps_topic else {
+      return; // Not announcing without SEDP
+    };
     // We might have multiple topics with the same name (but different Qos etc..),
     // and the following call gets just one of them. Should we publish all of
     // them or is this enough?
-    let topic_data = match db.get_topic(topic_name) {
-      Some(data) => data,
+    let topic_data = match discovery_db_read(&self.discovery_db).get_topic(topic_name) {
+      Some(data) => data.clone(),
       None => {
         warn!("Did not find topic data with topic name {topic_name}");
         return;
@@ -1857,10 +2262,20 @@ impl Discovery {
     if !is_user_defined {
       return;
     }
+    if self
+      .published_topics
+      .is_unchanged(&topic_name.to_string(), &topic_data)
+    {
+      debug!("Topic {topic_name} is unchanged since last published. Not publishing again.");
+      return;
+    }
 
-    match self.dcps_topic.writer.write(topic_data.clone(), None) {
+    match dcps_topic.writer.write(topic_data.clone(), None) {
       Ok(()) => {
         debug!("Published topic {topic_name} to DCPSTopic");
+        self
+          .published_topics
+          .insert(topic_name.to_string(), topic_data);
       }
       Err(e) => {
         error!("Failed to publish topic {topic_name} to DCPSTopic: {e}");
@@ -1990,21 +2405,49 @@ mod tests {
   use crate::{
     dds::{adapters::no_key::DeserializerAdapter, participant::DomainParticipant},
     discovery::sedp_messages::TopicBuiltinTopicData,
-    messages::submessages::submessages::{InterpreterSubmessage, WriterSubmessage},
+    messages::{
+      submessages::submessages::{InterpreterSubmessage, WriterSubmessage},
+      vendor_id::VendorId,
+    },
     network::{constant::*, udp_listener::UDPListener, udp_sender::UDPSender},
     rtps::submessage::*,
     serialization::cdr_deserializer::CDRDeserializerAdapter,
-    structure::{entity::RTPSEntity, locator::Locator},
+    structure::{entity::RTPSEntity, locator::Locator, sequence_number::SequenceNumber},
     test::{
       shape_type::ShapeType,
       test_data::{
-        create_cdr_pl_rtps_data_message, spdp_participant_msg_mod, spdp_publication_msg,
-        spdp_subscription_msg,
+        create_cdr_pl_rtps_data_message, publication_builtin_topic_data, spdp_participant_data,
+        spdp_participant_msg_mod, spdp_publication_msg, spdp_subscription_msg, writer_proxy_data,
       },
     },
     RepresentationIdentifier,
   };
 
+  #[test]
+  fn unchanged_announcements_are_not_published_again() {
+    let mut published = Published::default();
+    let writer = (
+      writer_proxy_data().unwrap(),
+      publication_builtin_topic_data().unwrap(),
+    );
+    let guid = writer.0.remote_writer_guid;
+    assert!(!published.is_unchanged(&guid, &writer));
+    published.insert(guid, writer.clone());
+    assert!(published.is_unchanged(&guid, &writer));
+
+    // Changed locators are published
+    let mut moved = writer.clone();
+    moved.0.unicast_locator_list = vec![Locator::from(SocketAddr::new(
+      "127.0.0.1".parse().unwrap(),
+      7999,
+    ))];
+    assert!(!published.is_unchanged(&guid, &moved));
+
+    // After the writer has been disposed, it is published again
+    published.remove(&guid);
+    assert!(!published.is_unchanged(&guid, &writer));
+  }
+
   #[test]
   fn discovery_participant_data_test() {
     let poll = Poll::new().unwrap();
@@ -2042,6 +2485,68 @@ mod tests {
     // actual message might be good idea
   }
 
+  #[test]
+  fn discovery_detects_guid_prefix_collision() {
+    use crate::{dds::statusevents::StatusEvented, messages::header::Header};
+
+    let participant = DomainParticipant::new(0).expect("participant creation");
+    let status_listener = participant.status_listener();
+
+    // Participant data with our GUID prefix, but somebody else's locators
+    let mut impostor = spdp_participant_data().unwrap();
+    impostor.participant_guid = participant.guid();
+    let impostor_locator = Locator::from(SocketAddr::new("127.0.0.1".parse().unwrap(), 7999));
+    impostor.metatraffic_unicast_locators = vec![impostor_locator];
+    impostor.default_unicast_locators = vec![impostor_locator];
+    impostor.vendor_id = VendorId::VENDOR_UNKNOWN;
+
+    let mut message = create_cdr_pl_rtps_data_message(
+      &impostor,
+      EntityId::SPDP_BUILTIN_PARTICIPANT_READER,
+      EntityId::SPDP_BUILTIN_PARTICIPANT_WRITER,
+    );
+    message.set_header(Header::new(participant.guid().prefix));
+    for submsg in &mut message.submessages {
+      if let SubmessageBody::Writer(WriterSubmessage::Data(data, _)) = &mut submsg.body {
+        // Past our own announcements, which come from the same writer GUID
+        data.writer_sn = SequenceNumber::new(1_000_000);
+      }
+    }
+    let udp_sender = UDPSender::new_with_random_port().expect("failed to create UDPSender");
+    let addresses = vec![SocketAddr::new(
+      "127.0.0.1".parse().unwrap(),
+      spdp_well_known_unicast_port(0, participant.participant_id()),
+    )];
+    let msg_data = message
+      .write_to_vec_with_ctx(Endianness::LittleEndian)
+      .unwrap();
+
+    let mut collision = None;
+    for _ in 0..50 {
+      udp_sender.send_to_all(&msg_data, &addresses);
+      std::thread::sleep(StdDuration::from_millis(100));
+      while let Some(event) = status_listener.try_recv_status() {
+        if let DomainParticipantStatusEvent::GuidPrefixCollision {
+          remote_locators, ..
+        } = event
+        {
+          assert!(collision.is_none(), "collision reported twice");
+          collision = Some(remote_locators);
+        }
+      }
+      if collision.is_some() {
+        break;
+      }
+    }
+    assert_eq!(collision, Some(vec![impostor_locator]));
+
+    // Our own participant data was not replaced
+    let own = participant
+      .discovered_participant_data(participant.guid().into())
+      .unwrap();
+    assert_eq!(own.vendor_id, VendorId::THIS_IMPLEMENTATION);
+  }
+
   #[test]
   fn discovery_reader_data_test() {
     use crate::{serialization::pl_cdr_adapters::PlCdrSerialize, TopicKind};
@@ -2233,6 +2738,7 @@ mod tests {
         history: None,
         resource_limits: None,
         ownership: None,
+        topic_data: None,
       },
     );
 
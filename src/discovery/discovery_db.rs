@@ -1,6 +1,10 @@
 use std::{
-  collections::BTreeMap,
-  sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+  collections::{BTreeMap, BTreeSet},
+  net::{IpAddr, SocketAddr},
+  sync::{
+    atomic::{AtomicI32, Ordering},
+    Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak,
+  },
   time::Instant,
 };
 
@@ -11,9 +15,9 @@ use log::{debug, error, info, trace, warn};
 use crate::{
   dds::{
     participant::DomainParticipant,
-    qos::HasQoSPolicy,
+    qos::{HasQoSPolicy, QosPolicies},
     statusevents::{DomainParticipantStatusEvent, LostReason, StatusChannelSender},
-    topic::{Topic, TopicDescription},
+    topic::{Topic, TopicData, TopicDescription},
   },
   rtps::{
     reader::ReaderIngredients, rtps_reader_proxy::RtpsReaderProxy,
@@ -23,9 +27,11 @@ use crate::{
     duration::Duration,
     entity::RTPSEntity,
     guid::{EntityId, GuidPrefix, GUID},
+    locator::Locator,
   },
 };
 use super::{
+  discovery_snapshot::{DiscoverySnapshot, EndpointSnapshot, ParticipantSnapshot},
   sedp_messages::{
     topics_inconsistent, DiscoveredReaderData, DiscoveredTopicData, DiscoveredWriterData,
     ParticipantMessageData, ReaderProxy, SubscriptionBuiltinTopicData, TopicBuiltinTopicData,
@@ -75,6 +81,11 @@ pub(crate) struct DiscoveryDB {
   // Inner key is topic data sender.
   topics: BTreeMap<String, BTreeMap<GUID, (DiscoveredVia, DiscoveredTopicData)>>,
 
+  // InconsistentTopic counters of local Topics by topic name, with the type
+  // name of each local Topic. The Topics own the counters, so dead entries are
+  // pruned as we go.
+  inconsistent_topic_counters: BTreeMap<String, Vec<(String, Weak<AtomicI32>)>>,
+
   // sender for notifying (potential) waiters in participant.find_topic() call
   topic_updated_sender: mio_extras::channel::SyncSender<()>,
 
@@ -138,6 +149,7 @@ impl DiscoveryDB {
       external_topic_readers_attic: BTreeMap::new(),
       external_topic_writers_attic: BTreeMap::new(),
       topics: BTreeMap::new(),
+      inconsistent_topic_counters: BTreeMap::new(),
       topic_updated_sender,
       participant_status_sender,
     }
@@ -261,6 +273,92 @@ impl DiscoveryDB {
     self.participant_proxies.get(&guid_prefix)
   }
 
+  // Participants that have announced a unicast locator with the given IP
+  // address.
+  pub fn participants_at_address(&self, address: IpAddr) -> Vec<GuidPrefix> {
+    self
+      .participant_proxies
+      .iter()
+      .filter(|(_, p)| {
+        p.metatraffic_unicast_locators
+          .iter()
+          .chain(p.default_unicast_locators.iter())
+          .filter(|l| l.is_udp())
+          .any(|l| SocketAddr::from(*l).ip() == address)
+      })
+      .map(|(guid_prefix, _)| *guid_prefix)
+      .collect()
+  }
+
+  // Copy of the database contents for export to applications.
+  pub fn snapshot(&self) -> DiscoverySnapshot {
+    let inow = Instant::now();
+
+    let participants = self
+      .participant_proxies
+      .iter()
+      .map(|(guid_prefix, sp)| {
+        let lease_duration = sp
+          .lease_duration
+          .unwrap_or(DEFAULT_PARTICIPANT_LEASE_DURATION);
+        let last_life_sign_age = self
+          .participant_last_life_signs
+          .get(guid_prefix)
+          .map(|&last_life| Duration::from_std(inow.duration_since(last_life)));
+        ParticipantSnapshot::new(
+          sp,
+          last_life_sign_age,
+          lease_duration + PARTICIPANT_LEASE_DURATION_TOLERANCE,
+        )
+      })
+      .collect();
+
+    let readers = self
+      .local_topic_readers
+      .values()
+      .map(|drd| EndpointSnapshot::from_reader(drd, true))
+      .chain(
+        self
+          .external_topic_readers
+          .iter()
+          // Our own endpoints are discovered also via the network.
+          .filter(|(guid, _)| !self.local_topic_readers.contains_key(guid))
+          .map(|(_, drd)| EndpointSnapshot::from_reader(drd, false)),
+      )
+      .collect();
+
+    let writers = self
+      .local_topic_writers
+      .values()
+      .map(|dwd| EndpointSnapshot::from_writer(dwd, true))
+      .chain(
+        self
+          .external_topic_writers
+          .iter()
+          // Our own endpoints are discovered also via the network.
+          .filter(|(guid, _)| !self.local_topic_writers.contains_key(guid))
+          .map(|(_, dwd)| EndpointSnapshot::from_writer(dwd, false)),
+      )
+      .collect();
+
+    let topics = self
+      .topics
+      .iter()
+      .filter(|(name, _)| !name.starts_with("DCPS"))
+      .filter_map(|(_, definitions)| definitions.values().next())
+      .map(|(_, dtd)| TopicData::from(&dtd.topic_data))
+      .collect();
+
+    DiscoverySnapshot {
+      taken_at: Utc::now(),
+      local_participant: self.my_guid,
+      participants,
+      readers,
+      writers,
+      topics,
+    }
+  }
+
   fn remove_topic_reader_with_prefix(&mut self, guid_prefix: GuidPrefix) {
     // TODO: Implement this using .drain_filter() in BTreeMap once it lands in
     // stable.
@@ -274,17 +372,16 @@ impl DiscoveryDB {
     }
   }
 
-  pub fn remove_topic_reader(&mut self, guid: GUID) {
+  // Returns true if the reader was known.
+  pub fn remove_topic_reader(&mut self, guid: GUID) -> bool {
     info!("remove_topic_reader {:?}", guid);
-    self.external_topic_readers.remove(&guid);
+    self.external_topic_readers.remove(&guid).is_some()
   }
 
-  #[cfg(feature = "security")]
   pub fn get_topic_reader(&self, guid: &GUID) -> Option<&DiscoveredReaderData> {
     self.external_topic_readers.get(guid)
   }
 
-  #[cfg(feature = "security")]
   pub fn get_topic_writer(&self, guid: &GUID) -> Option<&DiscoveredWriterData> {
     self.external_topic_writers.get(guid)
   }
@@ -302,8 +399,9 @@ impl DiscoveryDB {
     }
   }
 
-  pub fn remove_topic_writer(&mut self, guid: GUID) {
-    self.external_topic_writers.remove(&guid);
+  // Returns true if the writer was known.
+  pub fn remove_topic_writer(&mut self, guid: GUID) -> bool {
+    self.external_topic_writers.remove(&guid).is_some()
   }
 
   // Delete participant proxies, if we have not heard of them within
@@ -402,6 +500,11 @@ impl DiscoveryDB {
     for dt in &dead_topics {
       self.topics.remove(dt);
     }
+    // forget counters of local Topics that have been dropped
+    self.inconsistent_topic_counters.retain(|_name, counters| {
+      counters.retain(|(_type_name, counter)| counter.strong_count() > 0);
+      !counters.is_empty()
+    });
   }
 
   pub fn update_local_topic_writer(&mut self, writer: DiscoveredWriterData) {
@@ -410,6 +513,15 @@ impl DiscoveryDB {
       .insert(writer.writer_proxy.remote_writer_guid, writer);
   }
 
+  // QoS of a local writer was changed. Returns false if the writer is unknown.
+  pub fn update_local_topic_writer_qos(&mut self, guid: GUID, qos: &QosPolicies) -> bool {
+    self
+      .local_topic_writers
+      .get_mut(&guid)
+      .map(|dwd| dwd.publication_topic_data.set_qos(qos))
+      .is_some()
+  }
+
   pub fn remove_local_topic_writer(&mut self, guid: GUID) {
     self.local_topic_writers.remove(&guid);
   }
@@ -430,30 +542,6 @@ impl DiscoveryDB {
     let guid = data.reader_proxy.remote_reader_guid;
 
     self.external_topic_readers.insert(guid, data.clone());
-
-    // fill in the default locators from participant, in case DRD did not provide
-    // any
-    let default_locator_lists = self
-      .find_participant_proxy(guid.prefix)
-      .map(|pp| {
-        debug!("Added participant locators to Reader {:?}", guid);
-        (
-          pp.default_unicast_locators.clone(),
-          pp.default_multicast_locators.clone(),
-        )
-      })
-      .unwrap_or_else(|| {
-        if guid.prefix != GuidPrefix::UNKNOWN {
-          // This is normal, since we might not know about the participant yet.
-          debug!(
-            "No remote participant known for {:?}\nSearched with {:?} in {:?}",
-            data,
-            guid.prefix,
-            self.participant_proxies.keys()
-          );
-        }
-        (Vec::default(), Vec::default())
-      });
     debug!("External reader: {:?}", data);
 
     // Now the topic update:
@@ -469,11 +557,16 @@ impl DiscoveryDB {
     // from that record and modify by QoS given in the DRD.
 
     // Return DiscoveredReaderData with possibly updated locators.
+    self.reader_with_default_locators(data)
+  }
+
+  // Fill in the default locators from participant, in case DiscoveredReaderData
+  // did not provide any.
+  fn reader_with_default_locators(&self, data: &DiscoveredReaderData) -> DiscoveredReaderData {
+    let (unicast, multicast) = self.default_locators(data.reader_proxy.remote_reader_guid, data);
     DiscoveredReaderData {
       reader_proxy: ReaderProxy::from(RtpsReaderProxy::from_discovered_reader_data(
-        data,
-        &default_locator_lists.0,
-        &default_locator_lists.1,
+        data, &unicast, &multicast,
       )),
       ..data.clone()
     }
@@ -486,13 +579,40 @@ impl DiscoveryDB {
     self
       .external_topic_writers
       .insert(data.writer_proxy.remote_writer_guid, data.clone());
+    debug!("External writer: {:?}", data);
+
+    // Now the topic update:
+    let dtd = data.publication_topic_data.to_topic_data();
+    self.update_topic_data(
+      &DiscoveredTopicData::new(Utc::now(), dtd),
+      guid,
+      DiscoveredVia::Publication,
+    );
 
-    // fill in the default locators from participant, in case DRD did not provide
-    // any
-    let default_locator_lists = self
+    self.writer_with_default_locators(data)
+  }
+
+  // Fill in the default locators from participant, in case DiscoveredWriterData
+  // did not provide any.
+  fn writer_with_default_locators(&self, data: &DiscoveredWriterData) -> DiscoveredWriterData {
+    let (unicast, multicast) = self.default_locators(data.writer_proxy.remote_writer_guid, data);
+    DiscoveredWriterData {
+      writer_proxy: WriterProxy::from(RtpsWriterProxy::from_discovered_writer_data(
+        data, &unicast, &multicast,
+      )),
+      ..data.clone()
+    }
+  }
+
+  fn default_locators(
+    &self,
+    guid: GUID,
+    data: &impl std::fmt::Debug,
+  ) -> (Vec<Locator>, Vec<Locator>) {
+    self
       .find_participant_proxy(guid.prefix)
       .map(|pp| {
-        debug!("Added participant locators to Reader {:?}", guid);
+        debug!("Added participant locators to {:?}", guid);
         (
           pp.default_unicast_locators.clone(),
           pp.default_multicast_locators.clone(),
@@ -509,26 +629,7 @@ impl DiscoveryDB {
           );
         }
         (Vec::default(), Vec::default())
-      });
-
-    debug!("External writer: {:?}", data);
-
-    // Now the topic update:
-    let dtd = data.publication_topic_data.to_topic_data();
-    self.update_topic_data(
-      &DiscoveredTopicData::new(Utc::now(), dtd),
-      guid,
-      DiscoveredVia::Publication,
-    );
-
-    DiscoveredWriterData {
-      writer_proxy: WriterProxy::from(RtpsWriterProxy::from_discovered_writer_data(
-        data,
-        &default_locator_lists.0,
-        &default_locator_lists.1,
-      )),
-      ..data.clone()
-    }
+      })
   }
 
   // This is for local participant updating the topic table
@@ -545,6 +646,32 @@ impl DiscoveryDB {
     self.update_topic_data(&topic_data, self.my_guid, DiscoveredVia::SelfDefined);
   }
 
+  // QoS of a local Topic was changed. Our own record of the topic is replaced.
+  // Returns false if the topic has not been recorded, i.e. there are no local
+  // readers or writers on it yet.
+  pub fn update_local_topic_qos(&mut self, topic: &Topic) -> bool {
+    let my_guid = self.my_guid;
+    match self
+      .topics
+      .get_mut(&topic.name())
+      .and_then(|t| t.get_mut(&my_guid))
+    {
+      Some((_via, dtd)) => {
+        *dtd = DiscoveredTopicData::new(
+          Utc::now(),
+          TopicBuiltinTopicData::new(
+            None,
+            topic.name(),
+            topic.get_type().name().to_owned(),
+            &topic.qos(),
+          ),
+        );
+        true
+      }
+      None => false,
+    }
+  }
+
   // Topic update sends notifications, in case someone was waiting to find a
   // topic. Return value indicates whether the topic (name) was new to us. This
   // is used to add
@@ -559,6 +686,27 @@ impl DiscoveryDB {
     let mut notify = false;
     let mut inconsistency_event_to_send = None;
 
+    if updater.prefix != self.my_guid.prefix {
+      let previous_type_name = self
+        .topics
+        .get(&topic_name)
+        .and_then(|t| t.get(&updater))
+        .map(|(_via, old_dtd)| &old_dtd.topic_data.type_name);
+      if previous_type_name != Some(&dtd.topic_data.type_name) {
+        if let Some(counters) = self.inconsistent_topic_counters.get_mut(&topic_name) {
+          counters.retain(|(type_name, counter)| match counter.upgrade() {
+            Some(counter) => {
+              if *type_name != dtd.topic_data.type_name {
+                counter.fetch_add(1, Ordering::Relaxed);
+              }
+              true
+            }
+            None => false,
+          });
+        }
+      }
+    }
+
     if let Some(t) = self.topics.get_mut(&dtd.topic_data.name) {
       if let Some(old_dtd) = t.get_mut(&updater) {
         // already have it from the same source, do some checking(?) and merging
@@ -652,7 +800,7 @@ impl DiscoveryDB {
       Some(domain_participant.guid()),
       topic.name(),
       topic.get_type().name().to_string(),
-      &topic.qos(),
+      &reader.qos_policy,
       sec_info_opt,
     );
 
@@ -670,6 +818,15 @@ impl DiscoveryDB {
       .insert(reader_guid, discovered_reader_data);
   }
 
+  // QoS of a local reader was changed. Returns false if the reader is unknown.
+  pub fn update_local_topic_reader_qos(&mut self, guid: GUID, qos: &QosPolicies) -> bool {
+    self
+      .local_topic_readers
+      .get_mut(&guid)
+      .map(|drd| drd.subscription_topic_data.set_qos(qos))
+      .is_some()
+  }
+
   pub fn remove_local_topic_reader(&mut self, guid: GUID) {
     self.local_topic_readers.remove(&guid);
   }
@@ -690,6 +847,23 @@ impl DiscoveryDB {
     self.local_topic_writers.values()
   }
 
+  // Local and remote readers in a SharedSubscription group on a topic.
+  pub fn shared_subscription_members(&self, topic_name: &str, group: &str) -> BTreeSet<GUID> {
+    self
+      .local_topic_readers
+      .iter()
+      .chain(self.external_topic_readers.iter())
+      .filter(|(_guid, drd)| {
+        let sbtd = &drd.subscription_topic_data;
+        sbtd.topic_name() == topic_name
+          && sbtd
+            .shared_subscription()
+            .is_some_and(|ss| ss.group == group)
+      })
+      .map(|(guid, _drd)| *guid)
+      .collect()
+  }
+
   // Note:
   // If multiple participants announce the same topic, this will
   // return duplicates, one per announcing participant.
@@ -714,6 +888,26 @@ impl DiscoveryDB {
       .and_then(|m| m.values().next().map(|t| &t.1))
   }
 
+  // Start counting remote definitions of the topic with another type name for
+  // a new local Topic. Definitions already known are counted right away.
+  pub fn register_local_topic(&mut self, topic_name: &str, type_name: &str) -> Arc<AtomicI32> {
+    let known_count = self.topics.get(topic_name).map_or(0, |t| {
+      t.iter()
+        .filter(|(source, (_via, dtd))| {
+          source.prefix != self.my_guid.prefix && dtd.topic_data.type_name != type_name
+        })
+        .count()
+    });
+    let counter = Arc::new(AtomicI32::new(known_count.try_into().unwrap_or(i32::MAX)));
+    let counters = self
+      .inconsistent_topic_counters
+      .entry(topic_name.to_string())
+      .or_default();
+    counters.retain(|(_type_name, counter)| counter.strong_count() > 0);
+    counters.push((type_name.to_string(), Arc::downgrade(&counter)));
+    counter
+  }
+
   pub fn writers_on_topic_and_participant(
     &self,
     topic_name: &str,
@@ -747,6 +941,28 @@ impl DiscoveryDB {
       .collect()
   }
 
+  // Remote readers on a topic, with default locators filled in from the
+  // participant as in update_subscription.
+  pub fn readers_on_topic(&self, topic_name: &str) -> Vec<DiscoveredReaderData> {
+    self
+      .external_topic_readers
+      .values()
+      .filter(|drd| drd.subscription_topic_data.topic_name() == topic_name)
+      .map(|drd| self.reader_with_default_locators(drd))
+      .collect()
+  }
+
+  // Remote writers on a topic, with default locators filled in from the
+  // participant as in update_publication.
+  pub fn writers_on_topic(&self, topic_name: &str) -> Vec<DiscoveredWriterData> {
+    self
+      .external_topic_writers
+      .values()
+      .filter(|dwd| dwd.publication_topic_data.topic_name == topic_name)
+      .map(|dwd| self.writer_with_default_locators(dwd))
+      .collect()
+  }
+
   // // TODO: return iterator somehow?
   #[cfg(test)] // used only for testing
   pub fn get_local_topic_readers<'a, T: TopicDescription>(
@@ -762,12 +978,28 @@ impl DiscoveryDB {
       .collect()
   }
 
+  // Process a liveliness assertion from the ParticipantMessage topic.
   pub fn update_lease_duration(&mut self, data: &ParticipantMessageData) {
-    let now = Instant::now();
     let prefix = data.guid;
+    if prefix == self.my_guid.prefix {
+      return; // our own message looped back
+    }
+    if data.kind.is_vendor_specific() {
+      trace!("Ignoring vendor-specific ParticipantMessageData {data:?}");
+      return;
+    }
+    // Any message is a sign of life from the participant.
+    self.participant_is_alive(prefix);
+
+    let now = Instant::now();
     self
       .external_topic_writers
       .range_mut(prefix.range())
+      .filter(|(_guid, p)| {
+        data
+          .kind
+          .asserts_liveliness_of(p.publication_topic_data.liveliness)
+      })
       .for_each(|(_guid, p)| p.last_updated = now);
   }
 
@@ -799,17 +1031,21 @@ mod tests {
   use super::*;
   use crate::{
     dds::{
-      qos::QosPolicies,
+      qos::{policy::Liveliness, QosPolicies},
       statusevents::{sync_status_channel, DataReaderStatus},
       topic::TopicKind,
       with_key::simpledatareader::ReaderCommand,
     },
+    discovery::sedp_messages::ParticipantMessageDataKind,
     mio_source,
     serialization::cdr_serializer::CDRSerializerAdapter,
     structure::guid::*,
     test::{
       random_data::RandomData,
-      test_data::{reader_proxy_data, spdp_participant_data, subscription_builtin_topic_data},
+      test_data::{
+        publication_builtin_topic_data, reader_proxy_data, spdp_participant_data,
+        subscription_builtin_topic_data, writer_proxy_data,
+      },
     },
   };
 
@@ -840,6 +1076,63 @@ mod tests {
     // TODO: more operations tests
   }
 
+  #[test]
+  fn discdb_participant_message_liveliness() {
+    let (discovery_db_event_sender, _discovery_db_event_receiver) =
+      mio_channel::sync_channel::<()>(4);
+    let (status_sender, _status_receiver) = sync_status_channel(16).unwrap();
+    let mut discoverydb = DiscoveryDB::new(
+      GUID::new_participant_guid(),
+      discovery_db_event_sender,
+      status_sender,
+    );
+
+    let lease_duration = Duration::from_secs(1);
+    let mut writer_guids = Vec::new();
+    for (key, liveliness) in [
+      (1, Liveliness::Automatic { lease_duration }),
+      (2, Liveliness::ManualByParticipant { lease_duration }),
+    ] {
+      let mut publication_topic_data = publication_builtin_topic_data().unwrap();
+      publication_topic_data.key.entity_id.entity_key = [0, 0, key];
+      publication_topic_data.liveliness = Some(liveliness);
+      let mut writer_proxy = writer_proxy_data().unwrap();
+      writer_proxy.remote_writer_guid = publication_topic_data.key;
+      writer_guids.push(publication_topic_data.key);
+      discoverydb.update_publication(&DiscoveredWriterData {
+        last_updated: Instant::now(),
+        writer_proxy,
+        publication_topic_data,
+      });
+    }
+    let last_updated = |db: &DiscoveryDB, guid: &GUID| db.external_topic_writers[guid].last_updated;
+    let (automatic_writer, manual_writer) = (writer_guids[0], writer_guids[1]);
+    let automatic_before = last_updated(&discoverydb, &automatic_writer);
+    let manual_before = last_updated(&discoverydb, &manual_writer);
+
+    std::thread::sleep(StdDuration::from_millis(10));
+    discoverydb.update_lease_duration(&ParticipantMessageData {
+      guid: automatic_writer.prefix,
+      kind: ParticipantMessageDataKind::AUTOMATIC_LIVELINESS_UPDATE,
+      data: Vec::new(),
+    });
+    let automatic_after = last_updated(&discoverydb, &automatic_writer);
+    assert!(automatic_after > automatic_before);
+    assert_eq!(last_updated(&discoverydb, &manual_writer), manual_before);
+
+    std::thread::sleep(StdDuration::from_millis(10));
+    discoverydb.update_lease_duration(&ParticipantMessageData {
+      guid: manual_writer.prefix,
+      kind: ParticipantMessageDataKind::MANUAL_LIVELINESS_UPDATE,
+      data: Vec::new(),
+    });
+    assert_eq!(
+      last_updated(&discoverydb, &automatic_writer),
+      automatic_after
+    );
+    assert!(last_updated(&discoverydb, &manual_writer) > manual_before);
+  }
+
   #[test]
   fn discdb_writer_proxies() {
     let (discovery_db_event_sender, _discovery_db_event_receiver) =
@@ -994,6 +1287,8 @@ mod tests {
       data_reader_command_receiver: reader_command_receiver1,
       data_reader_waker: data_reader_waker1,
       poll_event_sender: notification_event_sender1,
+      readiness_queue: None,
+      fragment_streams: Arc::default(),
       security_plugins: None,
     };
 
@@ -1031,6 +1326,8 @@ mod tests {
       data_reader_command_receiver: reader_command_receiver2,
       data_reader_waker: data_reader_waker2,
       poll_event_sender: notification_event_sender2,
+      readiness_queue: None,
+      fragment_streams: Arc::default(),
       security_plugins: None,
     };
 
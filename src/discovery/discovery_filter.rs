@@ -0,0 +1,290 @@
+use std::{collections::BTreeSet, fmt, sync::Arc};
+
+use crate::{
+  dds::statusevents::{EndpointDescription, ParticipantDescription},
+  discovery::SpdpDiscoveredParticipantData,
+  messages::vendor_id::VendorId,
+  structure::guid::GuidPrefix,
+};
+
+type ParticipantPredicate = Arc<dyn Fn(&ParticipantDescription) -> bool + Send + Sync>;
+type EndpointPredicate = Arc<dyn Fn(&EndpointDescription) -> bool + Send + Sync>;
+type EndpointAuthorizer =
+  Arc<dyn Fn(&EndpointDescription, &ParticipantDescription) -> Result<(), String> + Send + Sync>;
+
+/// Application-level admission control for discovered remote
+/// DomainParticipants, DataReaders, and DataWriters.
+///
+/// The filter is evaluated when discovery data is received, before any
+/// proxies are created. Anything refused is ignored, as if it had never been
+/// discovered: no matching, no status events, and no traffic. Endpoints of a
+/// refused participant are refused as well.
+///
+/// Note that this is not a security mechanism, because discovery data is not
+/// authenticated. Use DDS Security for that.
+///
+/// Use
+/// [`DomainParticipantBuilder::discovery_filter`](crate::DomainParticipantBuilder::discovery_filter)
+/// to apply this. The default filter admits everything.
+///
+/// # Example
+///
+/// ```
+/// use rustdds::*;
+///
+/// // Talk only to RustDDS and Cyclone DDS participants named "robot-..."
+/// let filter = DiscoveryFilter::new()
+///   .allow_only_vendors([VendorId::ATOSTEK, VendorId { vendor_id: [0x01, 0x10] }])
+///   .participant_filter(|p| {
+///     p.entity_name
+///       .as_ref()
+///       .map_or(false, |name| name.starts_with("robot-"))
+///   });
+/// let participant = DomainParticipantBuilder::new(0)
+///   .discovery_filter(filter)
+///   .build();
+/// ```
+#[derive(Clone, Default)]
+pub struct DiscoveryFilter {
+  allowed_guid_prefixes: Option<BTreeSet<GuidPrefix>>,
+  denied_guid_prefixes: BTreeSet<GuidPrefix>,
+  allowed_vendors: Option<Vec<VendorId>>,
+  denied_vendors: Vec<VendorId>,
+  participant_predicate: Option<ParticipantPredicate>,
+  endpoint_predicate: Option<EndpointPredicate>,
+  endpoint_authorizer: Option<EndpointAuthorizer>,
+}
+
+impl DiscoveryFilter {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Admit only participants with these GUID prefixes. Can be called several
+  /// times to extend the set.
+  pub fn allow_only_participants(mut self, prefixes: impl IntoIterator<Item = GuidPrefix>) -> Self {
+    self
+      .allowed_guid_prefixes
+      .get_or_insert_with(BTreeSet::new)
+      .extend(prefixes);
+    self
+  }
+
+  /// Refuse the participant with this GUID prefix.
+  pub fn deny_participant(mut self, prefix: GuidPrefix) -> Self {
+    self.denied_guid_prefixes.insert(prefix);
+    self
+  }
+
+  /// Admit only participants from these DDS implementations. Can be called
+  /// several times to extend the set.
+  pub fn allow_only_vendors(mut self, vendors: impl IntoIterator<Item = VendorId>) -> Self {
+    self
+      .allowed_vendors
+      .get_or_insert_with(Vec::new)
+      .extend(vendors);
+    self
+  }
+
+  /// Refuse participants from this DDS implementation.
+  pub fn deny_vendor(mut self, vendor: VendorId) -> Self {
+    self.denied_vendors.push(vendor);
+    self
+  }
+
+  /// Custom rule for participants, e.g. based on entity name or properties.
+  /// Return `true` to admit. This is evaluated only if the other rules admit
+  /// the participant. Replaces a previously set participant filter.
+  ///
+  /// The function is called from the discovery thread, so it should return
+  /// quickly.
+  pub fn participant_filter(
+    mut self,
+    predicate: impl Fn(&ParticipantDescription) -> bool + Send + Sync + 'static,
+  ) -> Self {
+    self.participant_predicate = Some(Arc::new(predicate));
+    self
+  }
+
+  /// Custom rule for remote DataReaders and DataWriters, e.g. based on topic
+  /// name. Return `true` to admit. Replaces a previously set endpoint
+  /// filter.
+  ///
+  /// The function is called from the discovery thread, so it should return
+  /// quickly.
+  pub fn endpoint_filter(
+    mut self,
+    predicate: impl Fn(&EndpointDescription) -> bool + Send + Sync + 'static,
+  ) -> Self {
+    self.endpoint_predicate = Some(Arc::new(predicate));
+    self
+  }
+
+  /// Application check of remote DataReaders and DataWriters against the
+  /// discovery data of the endpoint and its participant, e.g. verifying a
+  /// signed deployment token carried in
+  /// [`UserData`](crate::policy::UserData). Return `Err` with a reason to
+  /// veto matching. Replaces a previously set authorizer.
+  ///
+  /// This is evaluated after the other rules have admitted the endpoint, and
+  /// only when its participant has been discovered. Vetoed endpoints are
+  /// reported with
+  /// [`RemoteEndpointRejected`](crate::dds::statusevents::DomainParticipantStatusEvent::RemoteEndpointRejected),
+  /// and otherwise handled like refused ones.
+  ///
+  /// This gives lightweight authorization, but no authentication: discovery
+  /// data is not protected, so a token can be copied by anyone who sees it.
+  /// The function is called from the discovery thread, so it should return
+  /// quickly.
+  pub fn endpoint_authorizer(
+    mut self,
+    authorizer: impl Fn(&EndpointDescription, &ParticipantDescription) -> Result<(), String>
+      + Send
+      + Sync
+      + 'static,
+  ) -> Self {
+    self.endpoint_authorizer = Some(Arc::new(authorizer));
+    self
+  }
+
+  pub(crate) fn admits_guid_prefix(&self, prefix: GuidPrefix) -> bool {
+    !self.denied_guid_prefixes.contains(&prefix)
+      && self
+        .allowed_guid_prefixes
+        .as_ref()
+        .map_or(true, |allowed| allowed.contains(&prefix))
+  }
+
+  // Are there rules that need the participant data, i.e. cannot be decided
+  // from the GUID prefix alone?
+  pub(crate) fn has_participant_data_rules(&self) -> bool {
+    self.allowed_vendors.is_some()
+      || !self.denied_vendors.is_empty()
+      || self.participant_predicate.is_some()
+      || self.endpoint_authorizer.is_some()
+  }
+
+  pub(crate) fn admits_participant(&self, data: &SpdpDiscoveredParticipantData) -> bool {
+    let vendor = data.vendor_id;
+    self.admits_guid_prefix(data.participant_guid.prefix)
+      && !self.denied_vendors.contains(&vendor)
+      && self
+        .allowed_vendors
+        .as_ref()
+        .map_or(true, |allowed| allowed.contains(&vendor))
+      && self
+        .participant_predicate
+        .as_ref()
+        .map_or(true, |predicate| predicate(&data.into()))
+  }
+
+  pub(crate) fn admits_endpoint(&self, endpoint: &EndpointDescription) -> bool {
+    self.admits_guid_prefix(endpoint.guid.prefix)
+      && self
+        .endpoint_predicate
+        .as_ref()
+        .map_or(true, |predicate| predicate(endpoint))
+  }
+
+  pub(crate) fn authorize_endpoint(
+    &self,
+    endpoint: &EndpointDescription,
+    participant: &ParticipantDescription,
+  ) -> Result<(), String> {
+    self
+      .endpoint_authorizer
+      .as_ref()
+      .map_or(Ok(()), |authorizer| authorizer(endpoint, participant))
+  }
+}
+
+impl fmt::Debug for DiscoveryFilter {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("DiscoveryFilter")
+      .field("allowed_guid_prefixes", &self.allowed_guid_prefixes)
+      .field("denied_guid_prefixes", &self.denied_guid_prefixes)
+      .field("allowed_vendors", &self.allowed_vendors)
+      .field("denied_vendors", &self.denied_vendors)
+      .field("participant_filter", &self.participant_predicate.is_some())
+      .field("endpoint_filter", &self.endpoint_predicate.is_some())
+      .field("endpoint_authorizer", &self.endpoint_authorizer.is_some())
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    dds::qos::{policy, QosPolicyBuilder},
+    structure::guid::{EntityId, GUID},
+    test::test_data::spdp_participant_data,
+  };
+
+  #[test]
+  fn discovery_filter_rules() {
+    let data = spdp_participant_data().unwrap();
+    let prefix = data.participant_guid.prefix;
+    let other_prefix = GuidPrefix::new(b"FakeTestGUID");
+
+    assert!(DiscoveryFilter::new().admits_participant(&data));
+    assert!(!DiscoveryFilter::new().has_participant_data_rules());
+
+    assert!(!DiscoveryFilter::new()
+      .deny_participant(prefix)
+      .admits_participant(&data));
+    assert!(!DiscoveryFilter::new()
+      .allow_only_participants([other_prefix])
+      .admits_participant(&data));
+
+    assert!(DiscoveryFilter::new()
+      .allow_only_vendors([data.vendor_id])
+      .admits_participant(&data));
+    assert!(!DiscoveryFilter::new()
+      .deny_vendor(data.vendor_id)
+      .admits_participant(&data));
+
+    let name_filter = DiscoveryFilter::new().participant_filter(|p| p.entity_name.is_some());
+    assert!(name_filter.has_participant_data_rules());
+    assert_eq!(
+      name_filter.admits_participant(&data),
+      data.entity_name.is_some()
+    );
+  }
+
+  #[test]
+  fn endpoint_authorizer_checks_tokens() {
+    let participant: ParticipantDescription = (&spdp_participant_data().unwrap()).into();
+    let endpoint_with_token = |token: &[u8]| EndpointDescription {
+      updated_time: chrono::Utc::now(),
+      guid: GUID::new(participant.guid.prefix, EntityId::UNKNOWN),
+      topic_name: "some_topic".to_string(),
+      type_name: "SomeType".to_string(),
+      qos: QosPolicyBuilder::new()
+        .user_data(policy::UserData {
+          value: token.to_vec(),
+        })
+        .build(),
+    };
+
+    let filter =
+      DiscoveryFilter::new().endpoint_authorizer(|endpoint, _participant| {
+        match endpoint.qos.user_data() {
+          Some(user_data) if user_data.value == b"signed" => Ok(()),
+          _ => Err("missing deployment token".to_string()),
+        }
+      });
+    assert!(filter.has_participant_data_rules());
+    assert_eq!(
+      filter.authorize_endpoint(&endpoint_with_token(b"signed"), &participant),
+      Ok(())
+    );
+    assert!(filter
+      .authorize_endpoint(&endpoint_with_token(b"forged"), &participant)
+      .is_err());
+    // Without an authorizer, everything is authorized.
+    assert!(DiscoveryFilter::new()
+      .authorize_endpoint(&endpoint_with_token(b"forged"), &participant)
+      .is_ok());
+  }
+}
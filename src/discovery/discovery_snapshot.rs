@@ -0,0 +1,132 @@
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::{
+  dds::{
+    qos::{policy::UserData, QosPolicies},
+    topic::TopicData,
+  },
+  messages::{protocol_version::ProtocolVersion, vendor_id::VendorId},
+  structure::{duration::Duration, guid::GUID, locator::Locator},
+};
+use super::{
+  sedp_messages::{DiscoveredReaderData, DiscoveredWriterData},
+  spdp_participant_data::SpdpDiscoveredParticipantData,
+};
+
+/// Contents of the discovery database of a DomainParticipant at some point in
+/// time, obtained from [`DomainParticipant::discovery_snapshot`].
+///
+/// This is meant for debugging and monitoring tools. All the parts implement
+/// `serde::Serialize`, so the snapshot can be exported e.g. as JSON. Changes
+/// after the snapshot are available as
+/// [`DomainParticipantStatusEvent`]s from
+/// [`DomainParticipant::status_listener`], which are also serializable.
+///
+/// [`DomainParticipant::discovery_snapshot`]: crate::DomainParticipant::discovery_snapshot
+/// [`DomainParticipant::status_listener`]: crate::DomainParticipant::status_listener
+/// [`DomainParticipantStatusEvent`]: crate::dds::statusevents::DomainParticipantStatusEvent
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoverySnapshot {
+  pub taken_at: chrono::DateTime<Utc>,
+  /// GUID of the DomainParticipant that owns the database
+  pub local_participant: GUID,
+  /// Known participants, including the local one, once it has discovered
+  /// itself.
+  pub participants: Vec<ParticipantSnapshot>,
+  /// Local and remote DataReaders, including the built-in discovery readers
+  pub readers: Vec<EndpointSnapshot>,
+  /// Local and remote DataWriters, including the built-in discovery writers
+  pub writers: Vec<EndpointSnapshot>,
+  /// User-defined Topics. Built-in discovery topics are not included. If
+  /// several participants have announced the same Topic, only one of the
+  /// definitions is included.
+  pub topics: Vec<TopicData>,
+}
+
+/// A participant in a [`DiscoverySnapshot`]
+#[derive(Debug, Clone, Serialize)]
+pub struct ParticipantSnapshot {
+  pub guid: GUID,
+  pub entity_name: Option<String>,
+  pub user_data: Option<UserData>,
+  pub vendor_id: VendorId,
+  pub protocol_version: ProtocolVersion,
+  /// When the participant data was last updated via discovery
+  pub updated_time: chrono::DateTime<Utc>,
+  /// Lease duration announced by the participant
+  pub lease_duration: Option<Duration>,
+  /// Time since the last sign of life, i.e. any message, from the participant
+  pub last_life_sign_age: Option<Duration>,
+  /// Is the participant considered alive, i.e. its lease has not expired.
+  /// Expired participants stay in the database until the next periodic
+  /// cleanup.
+  pub alive: bool,
+  pub metatraffic_unicast_locators: Vec<Locator>,
+  pub metatraffic_multicast_locators: Vec<Locator>,
+  pub default_unicast_locators: Vec<Locator>,
+  pub default_multicast_locators: Vec<Locator>,
+}
+
+impl ParticipantSnapshot {
+  pub(crate) fn new(
+    dpd: &SpdpDiscoveredParticipantData,
+    last_life_sign_age: Option<Duration>,
+    lease_duration: Duration,
+  ) -> Self {
+    Self {
+      guid: dpd.participant_guid,
+      entity_name: dpd.entity_name.clone(),
+      user_data: dpd.user_data.clone(),
+      vendor_id: dpd.vendor_id,
+      protocol_version: dpd.protocol_version,
+      updated_time: dpd.updated_time,
+      lease_duration: dpd.lease_duration,
+      last_life_sign_age,
+      alive: last_life_sign_age.is_some_and(|age| age <= lease_duration),
+      metatraffic_unicast_locators: dpd.metatraffic_unicast_locators.clone(),
+      metatraffic_multicast_locators: dpd.metatraffic_multicast_locators.clone(),
+      default_unicast_locators: dpd.default_unicast_locators.clone(),
+      default_multicast_locators: dpd.default_multicast_locators.clone(),
+    }
+  }
+}
+
+/// A DataReader or DataWriter in a [`DiscoverySnapshot`]
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointSnapshot {
+  pub guid: GUID,
+  /// Does the endpoint belong to the local participant
+  pub local: bool,
+  pub topic_name: String,
+  pub type_name: String,
+  pub qos: QosPolicies,
+  pub unicast_locators: Vec<Locator>,
+  pub multicast_locators: Vec<Locator>,
+}
+
+impl EndpointSnapshot {
+  pub(crate) fn from_reader(drd: &DiscoveredReaderData, local: bool) -> Self {
+    Self {
+      guid: drd.reader_proxy.remote_reader_guid,
+      local,
+      topic_name: drd.subscription_topic_data.topic_name().clone(),
+      type_name: drd.subscription_topic_data.type_name().clone(),
+      qos: drd.subscription_topic_data.qos(),
+      unicast_locators: drd.reader_proxy.unicast_locator_list.clone(),
+      multicast_locators: drd.reader_proxy.multicast_locator_list.clone(),
+    }
+  }
+
+  pub(crate) fn from_writer(dwd: &DiscoveredWriterData, local: bool) -> Self {
+    Self {
+      guid: dwd.writer_proxy.remote_writer_guid,
+      local,
+      topic_name: dwd.publication_topic_data.topic_name.clone(),
+      type_name: dwd.publication_topic_data.type_name.clone(),
+      qos: dwd.publication_topic_data.qos(),
+      unicast_locators: dwd.writer_proxy.unicast_locator_list.clone(),
+      multicast_locators: dwd.writer_proxy.multicast_locator_list.clone(),
+    }
+  }
+}
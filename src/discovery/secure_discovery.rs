@@ -6,6 +6,7 @@ use std::{
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 use mio_extras::channel as mio_channel;
+use serde::Serialize;
 
 use crate::{
   dds::{
@@ -52,7 +53,7 @@ use super::{
 };
 
 // Enum for authentication status of a remote participant
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize)]
 pub enum AuthenticationStatus {
   Authenticated,
   Authenticating, // In the process of being authenticated
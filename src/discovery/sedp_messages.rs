@@ -11,11 +11,13 @@ use cdr_encoding_size::*;
 use crate::{
   dds::{
     adapters::with_key::SerializerAdapter,
+    key::InstanceHandle,
     participant::DomainParticipant,
     qos::{
       policy::{
-        Deadline, DestinationOrder, Durability, History, LatencyBudget, Lifespan, Liveliness,
-        Ownership, Presentation, Reliability, ResourceLimits, TimeBasedFilter,
+        Compression, Deadline, DestinationOrder, Durability, GroupData, History, LatencyBudget,
+        Lifespan, Liveliness, Ownership, Presentation, Reliability, ResourceLimits,
+        SharedSubscription, TimeBasedFilter, TopicData, UserData,
       },
       HasQoSPolicy, QosPolicies,
     },
@@ -27,8 +29,10 @@ use crate::{
     parameter::Parameter,
     parameter_list::{ParameterList, ParameterListable},
   },
-  network::{constant::user_traffic_unicast_port, util::get_local_unicast_locators},
-  rtps::{rtps_reader_proxy::RtpsReaderProxy, rtps_writer_proxy::RtpsWriterProxy},
+  rtps::{
+    constant::USER_TRAFFIC_LISTENER_TOKEN, rtps_reader_proxy::RtpsReaderProxy,
+    rtps_writer_proxy::RtpsWriterProxy,
+  },
   serialization::{
     pl_cdr_adapters::{
       PlCdrDeserialize, PlCdrDeserializeError, PlCdrSerialize, PlCdrSerializeError,
@@ -162,14 +166,17 @@ pub struct SubscriptionBuiltinTopicData {
   reliability: Option<Reliability>,
   ownership: Option<Ownership>,
   destination_order: Option<DestinationOrder>,
-  // pub user_data: Option<UserData>,
+  user_data: Option<UserData>,
   time_based_filter: Option<TimeBasedFilter>,
   presentation: Option<Presentation>,
   // pub partition: Option<Partition>,
-  // pub topic_data: Option<TopicData>,
-  // pub group_data: Option<GroupData>,
+  topic_data: Option<TopicData>,
+  group_data: Option<GroupData>,
   // pub durability_service: Option<DurabilityService>,
   lifespan: Option<Lifespan>,
+  // RustDDS extensions
+  shared_subscription: Option<SharedSubscription>,
+  compression: Option<Compression>,
 
   // From spec Remote Procedure Call over DDS:
   service_instance_name: Option<String>,
@@ -206,9 +213,14 @@ impl SubscriptionBuiltinTopicData {
       reliability: None,
       ownership: None,
       destination_order: None,
+      user_data: None,
       time_based_filter: None,
       presentation: None,
+      topic_data: None,
+      group_data: None,
       lifespan: None,
+      shared_subscription: None,
+      compression: None,
       // DDS-RPC
       // TODO: these are not implemented
       service_instance_name: None,  // Note: Not implemented
@@ -228,6 +240,12 @@ impl SubscriptionBuiltinTopicData {
     self.key
   }
 
+  /// Instance handle of the DataReader. This is derived from [`Self::key`],
+  /// like the handle of any instance keyed by GUID.
+  pub fn instance_handle(&self) -> InstanceHandle {
+    InstanceHandle::from(self.key)
+  }
+
   pub fn participant_key(&self) -> &Option<GUID> {
     &self.participant_key
   }
@@ -245,6 +263,16 @@ impl SubscriptionBuiltinTopicData {
     &self.security_info
   }
 
+  pub fn shared_subscription(&self) -> Option<&SharedSubscription> {
+    self.shared_subscription.as_ref()
+  }
+
+  // Forgets the policies that are sent in RustDDS-specific parameters
+  pub(crate) fn clear_rustdds_extensions(&mut self) {
+    self.shared_subscription = None;
+    self.compression = None;
+  }
+
   pub fn set_qos(&mut self, qos: &QosPolicies) {
     self.durability = qos.durability;
     self.deadline = qos.deadline;
@@ -253,9 +281,14 @@ impl SubscriptionBuiltinTopicData {
     self.reliability = qos.reliability;
     self.ownership = qos.ownership;
     self.destination_order = qos.destination_order;
+    self.user_data = qos.user_data();
     self.time_based_filter = qos.time_based_filter;
     self.presentation = qos.presentation;
+    self.topic_data = qos.topic_data();
+    self.group_data = qos.group_data();
     self.lifespan = qos.lifespan;
+    self.shared_subscription = qos.shared_subscription();
+    self.compression = qos.compression();
     // history does not exist
     // resource_limits does not exist
   }
@@ -274,6 +307,23 @@ impl SubscriptionBuiltinTopicData {
       history: None, // SubscriptionBuiltinTopicData does not contain History QoS
       resource_limits: None, // nor Resource Limits, see Figure 8.30 in RTPS spec 2.5
       lifespan: self.lifespan,
+      user_data: self.user_data.clone(),
+      topic_data: self.topic_data.clone(),
+      group_data: self.group_data.clone(),
+      writer_data_lifecycle: None,
+      reader_data_lifecycle: None,
+      publish_mode: None,
+      history_replay: None,
+      shared_subscription: self.shared_subscription.clone(),
+      ignore_local: None,
+      entity_factory: None,
+      time_ordered_delivery: None,
+      instance_limit: None,
+      batching: None,
+      adaptive_timing: None,
+      compression: self.compression,
+      payload_checksum: None,
+      notification_coalescing: None,
 
       #[cfg(feature = "security")]
       property: None, // TODO: no property QoS?
@@ -467,6 +517,11 @@ impl ParameterListable for DiscoveredReaderData {
           time_based_filter: _,
           presentation: _,
           lifespan: _,
+          user_data: _,
+          topic_data: _,
+          group_data: _,
+          shared_subscription: _,
+          compression: _,
 
           service_instance_name,
           related_datawriter_key,
@@ -621,6 +676,9 @@ pub struct PublicationBuiltinTopicData {
   pub ownership: Option<Ownership>,
   pub destination_order: Option<DestinationOrder>,
   pub presentation: Option<Presentation>,
+  pub user_data: Option<UserData>,
+  pub topic_data: Option<TopicData>,
+  pub group_data: Option<GroupData>,
 
   // From Remote Procedure Call over DDS:
   pub service_instance_name: Option<String>,
@@ -659,6 +717,9 @@ impl PublicationBuiltinTopicData {
       ownership: None,
       destination_order: None,
       presentation: None,
+      user_data: None,
+      topic_data: None,
+      group_data: None,
 
       service_instance_name: None,  // TODO: These are not supported/used
       related_datareader_key: None, // TODO
@@ -682,6 +743,12 @@ impl PublicationBuiltinTopicData {
     s
   }
 
+  /// Instance handle of the DataWriter. This is derived from `key`, like the
+  /// handle of any instance keyed by GUID.
+  pub fn instance_handle(&self) -> InstanceHandle {
+    InstanceHandle::from(self.key)
+  }
+
   pub fn set_qos(&mut self, qos: &QosPolicies) {
     self.durability = qos.durability;
     self.deadline = qos.deadline;
@@ -693,6 +760,9 @@ impl PublicationBuiltinTopicData {
     self.ownership = qos.ownership;
     self.destination_order = qos.destination_order;
     self.presentation = qos.presentation;
+    self.user_data = qos.user_data();
+    self.topic_data = qos.topic_data();
+    self.group_data = qos.group_data();
   }
 
   pub fn qos(&self) -> QosPolicies {
@@ -709,6 +779,23 @@ impl PublicationBuiltinTopicData {
       history: None,         // PublicationBuiltinTopicData does not contain History QoS
       resource_limits: None, // nor Resource Limits, see Figure 8.30 in RTPS spec 2.5
       lifespan: self.lifespan,
+      user_data: self.user_data.clone(),
+      topic_data: self.topic_data.clone(),
+      group_data: self.group_data.clone(),
+      writer_data_lifecycle: None,
+      reader_data_lifecycle: None,
+      publish_mode: None,
+      history_replay: None,
+      shared_subscription: None,
+      ignore_local: None,
+      entity_factory: None,
+      time_ordered_delivery: None,
+      instance_limit: None,
+      batching: None,
+      adaptive_timing: None,
+      compression: None,
+      payload_checksum: None,
+      notification_coalescing: None,
       #[cfg(feature = "security")]
       property: None, // TODO: no property Qos?
     }
@@ -764,8 +851,10 @@ impl DiscoveredWriterData {
     dp: &DomainParticipant,
     security_info: Option<EndpointSecurityInfo>,
   ) -> Self {
-    let unicast_port = user_traffic_unicast_port(dp.domain_id(), dp.participant_id());
-    let unicast_addresses = get_local_unicast_locators(unicast_port);
+    let unicast_addresses = dp
+      .self_locators()
+      .remove(&USER_TRAFFIC_LISTENER_TOKEN)
+      .unwrap_or_default();
     // TODO: Why empty vector below? No multicast?
     let writer_proxy = WriterProxy::new(writer.guid(), vec![], unicast_addresses);
     let publication_topic_data = PublicationBuiltinTopicData::new_with_qos(
@@ -911,6 +1000,9 @@ impl ParameterListable for DiscoveredWriterData {
           time_based_filter: _,
           presentation: _,
           lifespan: _,
+          user_data: _,
+          topic_data: _,
+          group_data: _,
 
           service_instance_name,
           related_datareader_key,
@@ -1019,6 +1111,7 @@ pub struct TopicBuiltinTopicData {
   pub history: Option<History>,
   pub resource_limits: Option<ResourceLimits>,
   pub ownership: Option<Ownership>,
+  pub topic_data: Option<TopicData>,
 }
 
 impl TopicBuiltinTopicData {
@@ -1038,6 +1131,7 @@ impl TopicBuiltinTopicData {
       history: qos.history(),
       resource_limits: qos.resource_limits(),
       ownership: qos.ownership(),
+      topic_data: qos.topic_data(),
     }
   }
 }
@@ -1057,6 +1151,23 @@ impl HasQoSPolicy for TopicBuiltinTopicData {
       history: self.history,
       resource_limits: self.resource_limits,
       lifespan: self.lifespan,
+      user_data: None,
+      topic_data: self.topic_data.clone(),
+      group_data: None,
+      writer_data_lifecycle: None,
+      reader_data_lifecycle: None,
+      publish_mode: None,
+      history_replay: None,
+      shared_subscription: None,
+      ignore_local: None,
+      entity_factory: None,
+      time_ordered_delivery: None,
+      instance_limit: None,
+      batching: None,
+      adaptive_timing: None,
+      compression: None,
+      payload_checksum: None,
+      notification_coalescing: None,
       #[cfg(feature = "security")]
       property: None, // TODO: no property Qos?
     }
@@ -1181,6 +1292,7 @@ impl PlCdrSerialize for DiscoveredTopicData {
           presentation: _,
           lifespan: _,
           resource_limits: _,
+          topic_data: _,
         },
     } = self;
 
@@ -1238,6 +1350,24 @@ impl ParticipantMessageDataKind {
   pub const MANUAL_LIVELINESS_UPDATE: Self = Self {
     value: [0x00, 0x00, 0x00, 0x02],
   };
+
+  // RTPS spec 9.6.2.1: Kinds with the most significant bit set are reserved
+  // for vendor-specific use.
+  const VENDOR_SPECIFIC_KIND_FLAG: u8 = 0x80;
+
+  pub fn is_vendor_specific(&self) -> bool {
+    self.value[0] & Self::VENDOR_SPECIFIC_KIND_FLAG != 0
+  }
+
+  // Does a message of this kind assert the liveliness of a writer with the
+  // given LIVELINESS QoS? Missing QoS means the default, i.e. Automatic.
+  pub fn asserts_liveliness_of(&self, liveliness: Option<Liveliness>) -> bool {
+    match liveliness {
+      None | Some(Liveliness::Automatic { .. }) => *self == Self::AUTOMATIC_LIVELINESS_UPDATE,
+      Some(Liveliness::ManualByParticipant { .. }) => *self == Self::MANUAL_LIVELINESS_UPDATE,
+      Some(Liveliness::ManualByTopic { .. }) => false,
+    }
+  }
 }
 
 // =======================================================================
@@ -1285,7 +1415,8 @@ mod tests {
   use crate::{
     dds::adapters::no_key::DeserializerAdapter,
     rtps::Message,
-    serialization::pl_cdr_adapters::*,
+    serialization::{pl_cdr_adapters::*, CDRDeserializerAdapter, CDRSerializerAdapter},
+    structure::duration::Duration,
     test::test_data::{
       content_filter_data, publication_builtin_topic_data, reader_proxy_data,
       subscription_builtin_topic_data, topic_data, writer_proxy_data,
@@ -1463,19 +1594,75 @@ mod tests {
     assert_eq!(sdata, sdata2);
   }
 
-  // TODO: somehow get some actual bytes of ParticipantMessageData
-  // #[test]
-  // fn td_participant_message_data_ser_deser() {
-  //   let mut pmd_file = File::open("participant_message_data.bin").unwrap();
-  //   let mut buffer: [u8; 1024] = [0; 1024];
-  //   let _len = pmd_file.read(&mut buffer).unwrap();
-
-  //   let rpi = CDRDeserializerAdapter::<ParticipantMessageData>::from_bytes(
-  //     &buffer,
-  //     RepresentationIdentifier::CDR_LE,
-  //   )
-  //   .unwrap();
-
-  //   let _data2 = to_bytes::<ParticipantMessageData,
-  // LittleEndian>(&rpi).unwrap(); }
+  #[test]
+  fn td_participant_message_data_ser_deser() {
+    let pmd = ParticipantMessageData {
+      guid: GuidPrefix::new(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]),
+      kind: ParticipantMessageDataKind::MANUAL_LIVELINESS_UPDATE,
+      data: vec![],
+    };
+    // RTPS spec 9.6.2.1: participantGuidPrefix, kind, and data as a
+    // sequence<octet>
+    let expected = [
+      0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, // prefix
+      0x00, 0x00, 0x00, 0x02, // kind
+      0x00, 0x00, 0x00, 0x00, // data length
+    ];
+
+    let sdata =
+      CDRSerializerAdapter::<ParticipantMessageData, LittleEndian>::to_bytes(&pmd).unwrap();
+    assert_eq!(sdata, Bytes::copy_from_slice(&expected));
+    let pmd2: ParticipantMessageData =
+      CDRDeserializerAdapter::from_bytes(&sdata, RepresentationIdentifier::CDR_LE).unwrap();
+    assert_eq!(pmd, pmd2);
+  }
+
+  #[test]
+  fn td_participant_message_data_kind() {
+    let automatic = ParticipantMessageDataKind::AUTOMATIC_LIVELINESS_UPDATE;
+    let manual = ParticipantMessageDataKind::MANUAL_LIVELINESS_UPDATE;
+    let lease_duration = Duration::from_secs(1);
+
+    assert!(automatic.asserts_liveliness_of(None));
+    assert!(automatic.asserts_liveliness_of(Some(Liveliness::Automatic { lease_duration })));
+    assert!(
+      !automatic.asserts_liveliness_of(Some(Liveliness::ManualByParticipant { lease_duration }))
+    );
+    assert!(!manual.asserts_liveliness_of(None));
+    assert!(manual.asserts_liveliness_of(Some(Liveliness::ManualByParticipant { lease_duration })));
+    assert!(!manual.asserts_liveliness_of(Some(Liveliness::ManualByTopic { lease_duration })));
+
+    assert!(!manual.is_vendor_specific());
+    let vendor_kind = ParticipantMessageDataKind {
+      value: [0x80, 0x00, 0x00, 0x01],
+    };
+    assert!(vendor_kind.is_vendor_specific());
+    assert!(!vendor_kind.asserts_liveliness_of(None));
+  }
+
+  #[test]
+  fn td_instance_handles_match_builtin_topic_keys() {
+    use crate::{
+      dds::key::InstanceHandle, discovery::spdp_participant_data::Participant_GUID,
+      structure::guid::EntityId,
+    };
+
+    let reader_data = DiscoveredReaderData::default("t".to_string(), "T".to_string());
+    let reader_guid = reader_data.reader_proxy.remote_reader_guid;
+    // A sample of a built-in topic gets its handle from its Key, i.e. GUID.
+    assert_eq!(
+      InstanceHandle::from_key(&reader_data.key()),
+      reader_data.subscription_topic_data.instance_handle()
+    );
+    assert_eq!(
+      GUID::from(reader_data.subscription_topic_data.instance_handle()),
+      reader_guid
+    );
+
+    let participant_guid = reader_guid.from_prefix(EntityId::PARTICIPANT);
+    assert_eq!(
+      InstanceHandle::from_key(&Participant_GUID(participant_guid)),
+      InstanceHandle::from(participant_guid)
+    );
+  }
 }
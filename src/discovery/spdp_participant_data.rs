@@ -10,7 +10,11 @@ use bytes::Bytes;
 use cdr_encoding_size::CdrEncodingSize;
 
 use crate::{
-  dds::{participant::DomainParticipant, qos::QosPolicies},
+  dds::{
+    key::InstanceHandle,
+    participant::DomainParticipant,
+    qos::{self, QosPolicies},
+  },
   messages::{
     protocol_version::ProtocolVersion,
     submessages::elements::{
@@ -33,11 +37,8 @@ use crate::{
 };
 use super::builtin_endpoint::{BuiltinEndpointQos, BuiltinEndpointSet};
 #[cfg(feature = "security")]
-use crate::{
-  dds::qos,
-  security::{
-    access_control::PermissionsToken, authentication::IdentityToken, ParticipantSecurityInfo,
-  },
+use crate::security::{
+  access_control::PermissionsToken, authentication::IdentityToken, ParticipantSecurityInfo,
 };
 #[cfg(feature = "security")]
 use super::secure_discovery::SecureDiscovery;
@@ -64,6 +65,7 @@ pub struct SpdpDiscoveredParticipantData {
   pub manual_liveliness_count: i32,
   pub builtin_endpoint_qos: Option<BuiltinEndpointQos>,
   pub entity_name: Option<String>,
+  pub user_data: Option<qos::policy::UserData>,
 
   // security
   #[cfg(feature = "security")]
@@ -80,6 +82,12 @@ pub struct SpdpDiscoveredParticipantData {
 }
 
 impl SpdpDiscoveredParticipantData {
+  /// Instance handle of the participant. This is derived from
+  /// `participant_guid`, like the handle of any instance keyed by GUID.
+  pub fn instance_handle(&self) -> InstanceHandle {
+    InstanceHandle::from(self.participant_guid)
+  }
+
   #[cfg(feature = "security")]
   pub(crate) fn supports_security(&self) -> bool {
     // TODO: Is this logic correct? Or maybe we could come up with a more accurate
@@ -178,16 +186,7 @@ impl SpdpDiscoveredParticipantData {
       .unwrap_or_default();
 
     #[allow(unused_mut)] // only security feature mutates this
-    let mut builtin_endpoints = BuiltinEndpointSet::PARTICIPANT_ANNOUNCER
-      | BuiltinEndpointSet::PARTICIPANT_DETECTOR
-      | BuiltinEndpointSet::PUBLICATIONS_ANNOUNCER
-      | BuiltinEndpointSet::PUBLICATIONS_DETECTOR
-      | BuiltinEndpointSet::SUBSCRIPTIONS_ANNOUNCER
-      | BuiltinEndpointSet::SUBSCRIPTIONS_DETECTOR
-      | BuiltinEndpointSet::PARTICIPANT_MESSAGE_DATA_WRITER
-      | BuiltinEndpointSet::PARTICIPANT_MESSAGE_DATA_READER
-      | BuiltinEndpointSet::TOPICS_ANNOUNCER
-      | BuiltinEndpointSet::TOPICS_DETECTOR;
+    let mut builtin_endpoints = participant.builtin_endpoint_options().endpoint_set();
 
     // Security-related items initially None
     #[cfg(feature = "security")]
@@ -241,6 +240,7 @@ impl SpdpDiscoveredParticipantData {
       manual_liveliness_count: 0,
       builtin_endpoint_qos: None,
       entity_name: None,
+      user_data: participant.qos().user_data(),
 
       // DDS Security
       #[cfg(feature = "security")]
@@ -332,6 +332,9 @@ impl PlCdrDeserialize for SpdpDiscoveredParticipantData {
       get_option_from_pl_map::< _ , StringWithNul>(&pl_map, ctx, ParameterId::PID_ENTITY_NAME, "entity name")?
       .map( String::from );
 
+    let user_data: Option<qos::policy::UserData> =
+      get_option_from_pl_map(&pl_map, ctx, ParameterId::PID_USER_DATA, "user data")?;
+
     // DDS security
     #[cfg(feature = "security")]
     let identity_token: Option<IdentityToken> = get_option_from_pl_map(
@@ -378,6 +381,7 @@ impl PlCdrDeserialize for SpdpDiscoveredParticipantData {
       manual_liveliness_count,
       builtin_endpoint_qos,
       entity_name,
+      user_data,
       #[cfg(feature = "security")]
       identity_token,
       #[cfg(feature = "security")]
@@ -424,6 +428,7 @@ impl ParameterListable for SpdpDiscoveredParticipantData {
       manual_liveliness_count,
       builtin_endpoint_qos,
       entity_name,
+      user_data,
 
       // DDS security
       #[cfg(feature = "security")]
@@ -508,6 +513,7 @@ impl ParameterListable for SpdpDiscoveredParticipantData {
     // and does not follow CDR encoding.
     let entity_name_n: Option<StringWithNul> = entity_name.clone().map(|e| e.into());
     emit_option!(PID_ENTITY_NAME, &entity_name_n, StringWithNul);
+    emit_option!(PID_USER_DATA, user_data, qos::policy::UserData);
 
     #[cfg(feature = "security")] // DDS security
     {
@@ -645,6 +651,36 @@ mod tests {
     }
   }
 
+  #[test]
+  fn pdata_user_data() {
+    let data = spdp_participant_data_raw();
+    let rtpsmsg = Message::read_from_buffer(&data).unwrap();
+
+    for submsg in &rtpsmsg.submessages() {
+      if let SubmessageBody::Writer(WriterSubmessage::Data(d, _)) = &submsg.body {
+        let mut participant_data: SpdpDiscoveredParticipantData =
+          PlCdrDeserializerAdapter::from_bytes(
+            &d.unwrap_serialized_payload_value(),
+            RepresentationIdentifier::PL_CDR_LE,
+          )
+          .unwrap();
+        assert_eq!(participant_data.user_data, None);
+
+        participant_data.user_data = Some(qos::policy::UserData {
+          value: b"site=lab".to_vec(),
+        });
+        let sdata = participant_data
+          .to_pl_cdr_bytes(RepresentationIdentifier::PL_CDR_LE)
+          .unwrap();
+        let mut participant_data_2: SpdpDiscoveredParticipantData =
+          PlCdrDeserializerAdapter::from_bytes(&sdata, RepresentationIdentifier::PL_CDR_LE)
+            .unwrap();
+        participant_data_2.updated_time = participant_data.updated_time;
+        assert_eq!(participant_data_2, participant_data);
+      }
+    }
+  }
+
   #[test]
   fn deserialize_evil_spdp_fuzz() {
     use hex_literal::hex;
@@ -0,0 +1,710 @@
+// Static endpoint discovery, see DomainParticipantBuilder::static_discovery().
+//
+// The application describes remote participants and their DataReaders and
+// DataWriters, and Discovery puts them into the DiscoveryDB as if they had
+// been announced via SEDP. A participant with a known GUID prefix is added at
+// start, and needs no SPDP either. A participant given only by name gets its
+// endpoints when it is discovered via SPDP with that entity name, like in
+// the STATIC discovery of Fast DDS.
+
+use std::net::SocketAddr;
+
+use chrono::Utc;
+
+use crate::{
+  dds::qos::QosPolicies,
+  discovery::{
+    builtin_endpoint::BuiltinEndpointSet,
+    sedp_messages::{
+      DiscoveredReaderData, DiscoveredWriterData, PublicationBuiltinTopicData, ReaderProxy,
+      SubscriptionBuiltinTopicData, WriterProxy,
+    },
+    spdp_participant_data::SpdpDiscoveredParticipantData,
+  },
+  messages::{protocol_version::ProtocolVersion, vendor_id::VendorId},
+  structure::{
+    duration::Duration,
+    guid::{EntityId, GuidPrefix, GUID},
+    locator::Locator,
+  },
+};
+
+/// Remote DomainParticipants and their DataReaders and DataWriters, which are
+/// matched without SEDP. This is for peers that do not implement SEDP, e.g.
+/// small embedded devices.
+///
+/// Use
+/// [`DomainParticipantBuilder::static_discovery`](crate::DomainParticipantBuilder::static_discovery)
+/// to apply this. Static endpoints are admitted regardless of the
+/// [`DiscoveryFilter`](crate::DiscoveryFilter). If a static endpoint is also
+/// announced via SEDP, the announcement replaces the static description.
+///
+/// Static discovery is not available together with DDS Security.
+#[derive(Debug, Clone, Default)]
+pub struct StaticDiscovery {
+  pub participants: Vec<StaticParticipant>,
+}
+
+/// A remote DomainParticipant in [`StaticDiscovery`].
+///
+/// If `guid_prefix` is given, the participant is known from the start, and
+/// never times out. Otherwise its endpoints are added when a participant
+/// with the entity name `name` is discovered via SPDP.
+#[derive(Debug, Clone, Default)]
+pub struct StaticParticipant {
+  pub name: Option<String>,
+  pub guid_prefix: Option<GuidPrefix>,
+  /// Default unicast addresses of the endpoints of the participant
+  pub unicast_locators: Vec<SocketAddr>,
+  /// Default multicast addresses of the endpoints of the participant
+  pub multicast_locators: Vec<SocketAddr>,
+  pub readers: Vec<StaticEndpoint>,
+  pub writers: Vec<StaticEndpoint>,
+}
+
+/// A DataReader or DataWriter of a [`StaticParticipant`].
+#[derive(Debug, Clone)]
+pub struct StaticEndpoint {
+  /// The GUID of the endpoint consists of the GUID prefix of the participant
+  /// and this.
+  pub entity_id: EntityId,
+  pub topic_name: String,
+  pub type_name: String,
+  pub qos: QosPolicies,
+  /// Ignored for DataWriters
+  pub expects_inline_qos: bool,
+  /// If empty, the addresses of the participant are used.
+  pub unicast_locators: Vec<SocketAddr>,
+  pub multicast_locators: Vec<SocketAddr>,
+}
+
+fn locators(addresses: &[SocketAddr]) -> Vec<Locator> {
+  addresses.iter().copied().map(Locator::from).collect()
+}
+
+impl StaticDiscovery {
+  pub fn is_empty(&self) -> bool {
+    self.participants.is_empty()
+  }
+
+  // Participant data of the participants with a known GUID prefix
+  pub(crate) fn participant_data(&self) -> Vec<SpdpDiscoveredParticipantData> {
+    self
+      .participants
+      .iter()
+      .filter_map(|p| p.guid_prefix.map(|prefix| p.participant_data(prefix)))
+      .collect()
+  }
+
+  // The static endpoints of a known participant, whether it is known
+  // statically or via SPDP
+  pub(crate) fn endpoints_of(
+    &self,
+    participant: &SpdpDiscoveredParticipantData,
+  ) -> (Vec<DiscoveredReaderData>, Vec<DiscoveredWriterData>) {
+    let prefix = participant.participant_guid.prefix;
+    let described = |p: &&StaticParticipant| match p.guid_prefix {
+      Some(static_prefix) => static_prefix == prefix,
+      None => p.name.is_some() && p.name == participant.entity_name,
+    };
+    let mut readers = Vec::new();
+    let mut writers = Vec::new();
+    for p in self.participants.iter().filter(described) {
+      readers.extend(p.readers.iter().map(|r| r.reader_data(prefix)));
+      writers.extend(p.writers.iter().map(|w| w.writer_data(prefix)));
+    }
+    (readers, writers)
+  }
+}
+
+impl StaticParticipant {
+  fn participant_data(&self, prefix: GuidPrefix) -> SpdpDiscoveredParticipantData {
+    SpdpDiscoveredParticipantData {
+      updated_time: Utc::now(),
+      protocol_version: ProtocolVersion::THIS_IMPLEMENTATION,
+      vendor_id: VendorId::VENDOR_UNKNOWN,
+      expects_inline_qos: false,
+      participant_guid: GUID::new(prefix, EntityId::PARTICIPANT),
+      metatraffic_unicast_locators: vec![],
+      metatraffic_multicast_locators: vec![],
+      default_unicast_locators: locators(&self.unicast_locators),
+      default_multicast_locators: locators(&self.multicast_locators),
+      // No SPDP or SEDP
+      available_builtin_endpoints: BuiltinEndpointSet::from_u32(0),
+      lease_duration: Some(Duration::INFINITE),
+      manual_liveliness_count: 0,
+      builtin_endpoint_qos: None,
+      entity_name: self.name.clone(),
+      user_data: None,
+      #[cfg(feature = "security")]
+      identity_token: None,
+      #[cfg(feature = "security")]
+      permissions_token: None,
+      #[cfg(feature = "security")]
+      property: None,
+      #[cfg(feature = "security")]
+      security_info: None,
+    }
+  }
+}
+
+impl StaticEndpoint {
+  fn reader_data(&self, prefix: GuidPrefix) -> DiscoveredReaderData {
+    let guid = GUID::new(prefix, self.entity_id);
+    DiscoveredReaderData {
+      reader_proxy: ReaderProxy::new(
+        guid,
+        self.expects_inline_qos,
+        locators(&self.unicast_locators),
+        locators(&self.multicast_locators),
+      ),
+      subscription_topic_data: SubscriptionBuiltinTopicData::new(
+        guid,
+        Some(GUID::new(prefix, EntityId::PARTICIPANT)),
+        self.topic_name.clone(),
+        self.type_name.clone(),
+        &self.qos,
+        None,
+      ),
+      content_filter: None,
+    }
+  }
+
+  fn writer_data(&self, prefix: GuidPrefix) -> DiscoveredWriterData {
+    let guid = GUID::new(prefix, self.entity_id);
+    DiscoveredWriterData {
+      last_updated: std::time::Instant::now(),
+      writer_proxy: WriterProxy::new(
+        guid,
+        locators(&self.multicast_locators),
+        locators(&self.unicast_locators),
+      ),
+      publication_topic_data: PublicationBuiltinTopicData::new_with_qos(
+        guid,
+        Some(GUID::new(prefix, EntityId::PARTICIPANT)),
+        self.topic_name.clone(),
+        self.type_name.clone(),
+        &self.qos,
+        None,
+      ),
+    }
+  }
+}
+
+/// Error in a [`StaticDiscovery`] XML or TOML document
+#[cfg(feature = "static_discovery")]
+#[derive(Debug, thiserror::Error)]
+pub enum StaticDiscoveryError {
+  #[error("Cannot parse XML: {0}")]
+  Xml(#[from] serde_xml_rs::Error),
+  #[error("Cannot parse TOML: {0}")]
+  Toml(#[from] toml::de::Error),
+  #[error("Invalid static discovery description: {0}")]
+  Invalid(String),
+}
+
+#[cfg(feature = "static_discovery")]
+impl StaticDiscovery {
+  /// Reads the description from XML in the format of the STATIC discovery of
+  /// Fast DDS, with elements `<staticdiscovery>`, `<participant>`, `<reader>`
+  /// and `<writer>`.
+  ///
+  /// As an extension, a `<participant>` may have a `<guidPrefix>`, e.g.
+  /// `01.0f.00.00.00.00.00.00.00.00.00.01`, and default `<unicastLocator>`
+  /// and `<multicastLocator>` elements. The endpoint elements `<userId>`,
+  /// `<entityID>`, `<expectsInlineQos>`, `<topicName>`, `<topicDataType>`,
+  /// `<topicKind>`, `<reliabilityQos>`, `<durabilityQos>`, `<ownershipQos>`,
+  /// `<unicastLocator>` and `<multicastLocator>` are understood.
+  ///
+  /// The entity key of an endpoint is the 24 lowest bits of `<entityID>`, or
+  /// of `<userId>`, if there is no `<entityID>`. The entity kind is
+  /// user-defined, with or without key according to `<topicKind>`. As in
+  /// Fast DDS, DataReaders are best effort and DataWriters reliable by
+  /// default.
+  pub fn from_xml(xml: &str) -> Result<Self, StaticDiscoveryError> {
+    Self::from_document(&serde_xml_rs::from_str(xml)?)
+  }
+
+  /// Reads the description from TOML, with the same names as in
+  /// [`from_xml`](Self::from_xml). Participants are an array of tables
+  /// `[[participant]]`, their endpoints `[[participant.reader]]` and
+  /// `[[participant.writer]]`, and locators arrays of inline tables, e.g.
+  /// `unicastLocator = [{ address = "192.168.1.10", port = 7411 }]`.
+  pub fn from_toml(toml: &str) -> Result<Self, StaticDiscoveryError> {
+    Self::from_document(&toml::from_str(toml)?)
+  }
+
+  fn from_document(document: &document::StaticDiscovery) -> Result<Self, StaticDiscoveryError> {
+    let participants = document
+      .participants
+      .iter()
+      .map(StaticParticipant::from_document)
+      .collect::<Result<_, _>>()?;
+    Ok(Self { participants })
+  }
+}
+
+#[cfg(feature = "static_discovery")]
+impl StaticParticipant {
+  fn from_document(xp: &document::Participant) -> Result<Self, StaticDiscoveryError> {
+    let guid_prefix = xp
+      .guid_prefix
+      .as_ref()
+      .map(|prefix| {
+        prefix
+          .parse()
+          .map_err(|e| StaticDiscoveryError::Invalid(format!("Bad guidPrefix {prefix:?}: {e}")))
+      })
+      .transpose()?;
+    if guid_prefix.is_none() && xp.name.is_none() {
+      return Err(StaticDiscoveryError::Invalid(
+        "A participant must have a name or a guidPrefix".to_string(),
+      ));
+    }
+    let endpoints = |xes: &[document::Endpoint], is_reader| {
+      xes
+        .iter()
+        .map(|xe| StaticEndpoint::from_document(xe, is_reader))
+        .collect::<Result<Vec<_>, _>>()
+    };
+    Ok(Self {
+      name: xp.name.clone(),
+      guid_prefix,
+      unicast_locators: document::addresses(&xp.unicast_locators)?,
+      multicast_locators: document::addresses(&xp.multicast_locators)?,
+      readers: endpoints(&xp.readers, true)?,
+      writers: endpoints(&xp.writers, false)?,
+    })
+  }
+}
+
+#[cfg(feature = "static_discovery")]
+impl StaticEndpoint {
+  fn from_document(xe: &document::Endpoint, is_reader: bool) -> Result<Self, StaticDiscoveryError> {
+    use crate::{
+      dds::qos::policy::{Durability, Ownership, Reliability},
+      structure::guid::EntityKind,
+    };
+
+    let invalid = |what: &str, value: &str| {
+      StaticDiscoveryError::Invalid(format!(
+        "Endpoint on topic {:?}: bad {what} {value:?}",
+        xe.topic_name
+      ))
+    };
+
+    let id = xe.entity_id.or(xe.user_id).ok_or_else(|| {
+      StaticDiscoveryError::Invalid(format!(
+        "Endpoint on topic {:?} has no entityID or userId",
+        xe.topic_name
+      ))
+    })?;
+    let with_key = match xe.topic_kind.as_deref() {
+      None | Some("NO_KEY") => false,
+      Some("WITH_KEY") => true,
+      Some(other) => return Err(invalid("topicKind", other)),
+    };
+    let entity_kind = match (is_reader, with_key) {
+      (true, true) => EntityKind::READER_WITH_KEY_USER_DEFINED,
+      (true, false) => EntityKind::READER_NO_KEY_USER_DEFINED,
+      (false, true) => EntityKind::WRITER_WITH_KEY_USER_DEFINED,
+      (false, false) => EntityKind::WRITER_NO_KEY_USER_DEFINED,
+    };
+    let [_, key @ ..] = id.to_be_bytes();
+
+    let reliability = match xe.reliability_qos.as_deref() {
+      None if is_reader => Reliability::BestEffort,
+      None => Reliability::Reliable {
+        max_blocking_time: Duration::from_millis(100),
+      },
+      Some("BEST_EFFORT_RELIABILITY_QOS") => Reliability::BestEffort,
+      Some("RELIABLE_RELIABILITY_QOS") => Reliability::Reliable {
+        max_blocking_time: Duration::from_millis(100),
+      },
+      Some(other) => return Err(invalid("reliabilityQos", other)),
+    };
+    let durability = match xe.durability_qos.as_deref() {
+      None | Some("VOLATILE_DURABILITY_QOS") => Durability::Volatile,
+      Some("TRANSIENT_LOCAL_DURABILITY_QOS") => Durability::TransientLocal,
+      Some("TRANSIENT_DURABILITY_QOS") => Durability::Transient,
+      Some("PERSISTENT_DURABILITY_QOS") => Durability::Persistent,
+      Some(other) => return Err(invalid("durabilityQos", other)),
+    };
+    let ownership = match &xe.ownership_qos {
+      None => Ownership::Shared,
+      Some(xo) => match xo.kind.as_str() {
+        "SHARED_OWNERSHIP_QOS" => Ownership::Shared,
+        "EXCLUSIVE_OWNERSHIP_QOS" => Ownership::Exclusive {
+          strength: xo.strength.unwrap_or(0),
+        },
+        other => return Err(invalid("ownershipQos kind", other)),
+      },
+    };
+
+    Ok(Self {
+      entity_id: EntityId::new(key, entity_kind),
+      topic_name: xe.topic_name.clone(),
+      type_name: xe.topic_data_type.clone(),
+      qos: QosPolicies::builder()
+        .reliability(reliability)
+        .durability(durability)
+        .ownership(ownership)
+        .build(),
+      expects_inline_qos: xe.expects_inline_qos.unwrap_or(false),
+      unicast_locators: document::addresses(&xe.unicast_locators)?,
+      multicast_locators: document::addresses(&xe.multicast_locators)?,
+    })
+  }
+}
+
+// The XML or TOML document as it is written
+#[cfg(feature = "static_discovery")]
+mod document {
+  use std::net::{IpAddr, SocketAddr};
+
+  use serde::Deserialize;
+
+  use super::StaticDiscoveryError;
+
+  #[derive(Debug, Deserialize)]
+  pub struct StaticDiscovery {
+    #[serde(rename = "participant", default)]
+    pub participants: Vec<Participant>,
+  }
+
+  #[derive(Debug, Deserialize)]
+  #[serde(rename_all = "camelCase")]
+  pub struct Participant {
+    pub name: Option<String>,
+    pub guid_prefix: Option<String>,
+    #[serde(rename = "unicastLocator", default)]
+    pub unicast_locators: Vec<Locator>,
+    #[serde(rename = "multicastLocator", default)]
+    pub multicast_locators: Vec<Locator>,
+    #[serde(rename = "reader", default)]
+    pub readers: Vec<Endpoint>,
+    #[serde(rename = "writer", default)]
+    pub writers: Vec<Endpoint>,
+  }
+
+  #[derive(Debug, Deserialize)]
+  #[serde(rename_all = "camelCase")]
+  pub struct Endpoint {
+    pub user_id: Option<u32>,
+    #[serde(rename = "entityID")]
+    pub entity_id: Option<u32>,
+    pub expects_inline_qos: Option<bool>,
+    pub topic_name: String,
+    pub topic_data_type: String,
+    pub topic_kind: Option<String>,
+    pub reliability_qos: Option<String>,
+    pub durability_qos: Option<String>,
+    pub ownership_qos: Option<OwnershipQos>,
+    #[serde(rename = "unicastLocator", default)]
+    pub unicast_locators: Vec<Locator>,
+    #[serde(rename = "multicastLocator", default)]
+    pub multicast_locators: Vec<Locator>,
+  }
+
+  #[derive(Debug, Deserialize)]
+  pub struct OwnershipQos {
+    pub kind: String,
+    pub strength: Option<i32>,
+  }
+
+  #[derive(Debug, Deserialize)]
+  pub struct Locator {
+    pub address: String,
+    pub port: u16,
+  }
+
+  pub fn addresses(locators: &[Locator]) -> Result<Vec<SocketAddr>, StaticDiscoveryError> {
+    locators
+      .iter()
+      .map(|l| {
+        l.address
+          .parse::<IpAddr>()
+          .map(|ip| SocketAddr::new(ip, l.port))
+          .map_err(|e| StaticDiscoveryError::Invalid(format!("Bad address {:?}: {e}", l.address)))
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    thread,
+    time::{Duration as StdDuration, Instant},
+  };
+
+  use super::*;
+  use crate::{
+    rtps::constant::USER_TRAFFIC_LISTENER_TOKEN, structure::entity::RTPSEntity,
+    test::random_data::RandomData, with_key::Sample, CDRDeserializerAdapter, CDRSerializerAdapter,
+    DiscoveryFilter, DomainParticipant, DomainParticipantBuilder, TopicKind,
+  };
+
+  #[test]
+  fn static_writer_is_matched_without_sedp() {
+    let qos = QosPolicies::builder()
+      .reliable(Duration::from_millis(100))
+      .build();
+    let writer_participant = DomainParticipant::new(0).unwrap();
+    let publisher = writer_participant.create_publisher(&qos).unwrap();
+    let topic = writer_participant
+      .create_topic(
+        "static_discovery".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let writer = publisher
+      .create_datawriter_with_entity_key::<RandomData, CDRSerializerAdapter<_>>(
+        [0x00, 0x00, 0x42],
+        &topic,
+        None,
+      )
+      .unwrap();
+    let writer_guid = writer.guid();
+    let unicast_locators = writer_participant
+      .self_locators()
+      .remove(&USER_TRAFFIC_LISTENER_TOKEN)
+      .unwrap_or_default()
+      .into_iter()
+      .map(SocketAddr::from)
+      .collect();
+
+    // The SEDP announcement of the writer is refused, so only the static
+    // description can match it.
+    let static_discovery = StaticDiscovery {
+      participants: vec![StaticParticipant {
+        guid_prefix: Some(writer_guid.prefix),
+        unicast_locators,
+        writers: vec![StaticEndpoint {
+          entity_id: writer_guid.entity_id,
+          topic_name: "static_discovery".to_string(),
+          type_name: "RandomData".to_string(),
+          qos: qos.clone(),
+          expects_inline_qos: false,
+          unicast_locators: vec![],
+          multicast_locators: vec![],
+        }],
+        ..Default::default()
+      }],
+    };
+    let reader_participant = DomainParticipantBuilder::new(0)
+      .discovery_filter(DiscoveryFilter::new().endpoint_filter(move |e| e.guid != writer_guid))
+      .static_discovery(static_discovery)
+      .build()
+      .unwrap();
+    let subscriber = reader_participant.create_subscriber(&qos).unwrap();
+    let topic = reader_participant
+      .create_topic(
+        "static_discovery".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let mut reader = subscriber
+      .create_datareader::<RandomData, CDRDeserializerAdapter<_>>(&topic, None)
+      .unwrap();
+
+    let deadline = Instant::now() + StdDuration::from_secs(5);
+    while writer.get_matched_subscriptions().is_empty() && Instant::now() < deadline {
+      thread::sleep(StdDuration::from_millis(50));
+    }
+    let data = RandomData {
+      a: 1,
+      b: "static".to_string(),
+    };
+    writer.write(data.clone(), None).unwrap();
+
+    let mut received = None;
+    while received.is_none() && Instant::now() < deadline {
+      thread::sleep(StdDuration::from_millis(50));
+      received = reader.take_next_sample().unwrap();
+    }
+    assert_eq!(received.map(|s| s.into_value()), Some(Sample::Value(data)));
+  }
+
+  #[test]
+  fn reader_created_later_is_matched_with_static_writer() {
+    use crate::structure::guid::EntityKind;
+
+    // The writer participant does not exist, so nothing is announced via
+    // SPDP, SEDP, or DCPSTopic. Only the static description can match.
+    let writer_guid = GUID::new(
+      GuidPrefix::from([1, 0xf, 0x98, 0x76, 0, 0, 0, 0, 0, 0, 0, 1]),
+      EntityId::new([0, 0, 1], EntityKind::WRITER_WITH_KEY_USER_DEFINED),
+    );
+    let qos = QosPolicies::builder()
+      .reliable(Duration::from_millis(100))
+      .build();
+    let static_discovery = StaticDiscovery {
+      participants: vec![StaticParticipant {
+        guid_prefix: Some(writer_guid.prefix),
+        unicast_locators: vec!["127.0.0.1:9".parse().unwrap()],
+        writers: vec![StaticEndpoint {
+          entity_id: writer_guid.entity_id,
+          topic_name: "static_discovery_later_reader".to_string(),
+          type_name: "RandomData".to_string(),
+          qos: qos.clone(),
+          expects_inline_qos: false,
+          unicast_locators: vec![],
+          multicast_locators: vec![],
+        }],
+        ..Default::default()
+      }],
+    };
+    let participant = DomainParticipantBuilder::new(0)
+      .static_discovery(static_discovery)
+      .build()
+      .unwrap();
+
+    let subscriber = participant.create_subscriber(&qos).unwrap();
+    let topic = participant
+      .create_topic(
+        "static_discovery_later_reader".to_string(),
+        "RandomData".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+
+    // The first DataReader is matched when either it or the static writer is
+    // added, whichever comes later. The static writer is added only once, when
+    // Discovery starts, so the second DataReader must be matched with the
+    // already known writer.
+    let deadline = Instant::now() + StdDuration::from_secs(5);
+    for _ in 0..2 {
+      let reader = subscriber
+        .create_datareader::<RandomData, CDRDeserializerAdapter<_>>(&topic, None)
+        .unwrap();
+      while !reader
+        .get_matched_publications()
+        .any(|p| p.key == writer_guid)
+      {
+        assert!(Instant::now() < deadline, "Static writer was not matched");
+        thread::sleep(StdDuration::from_millis(10));
+      }
+    }
+  }
+
+  #[cfg(feature = "static_discovery")]
+  #[test]
+  fn fast_dds_static_xml() {
+    use crate::{
+      dds::qos::policy::{Durability, Reliability},
+      structure::guid::EntityKind,
+    };
+
+    let xml = r#"
+      <staticdiscovery>
+        <participant>
+          <name>sensor</name>
+          <guidPrefix>01.0f.00.00.00.00.00.00.00.00.00.01</guidPrefix>
+          <unicastLocator address="192.168.1.10" port="7411"/>
+          <writer>
+            <userId>1</userId>
+            <entityID>2</entityID>
+            <topicName>Temperature</topicName>
+            <topicDataType>Reading</topicDataType>
+            <topicKind>WITH_KEY</topicKind>
+            <durabilityQos>TRANSIENT_LOCAL_DURABILITY_QOS</durabilityQos>
+          </writer>
+          <reader>
+            <userId>3</userId>
+            <topicName>Command</topicName>
+            <topicDataType>Command</topicDataType>
+            <unicastLocator address="192.168.1.10" port="7412"/>
+          </reader>
+        </participant>
+        <participant>
+          <name>display</name>
+        </participant>
+      </staticdiscovery>"#;
+    let sd = StaticDiscovery::from_xml(xml).unwrap();
+    assert_eq!(sd.participants.len(), 2);
+    let sensor = &sd.participants[0];
+    assert_eq!(
+      sensor.guid_prefix,
+      Some(GuidPrefix::from([1, 0xf, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]))
+    );
+    assert_eq!(
+      sensor.unicast_locators,
+      vec!["192.168.1.10:7411".parse::<SocketAddr>().unwrap()]
+    );
+
+    let writer = &sensor.writers[0];
+    assert_eq!(
+      writer.entity_id,
+      EntityId::new([0, 0, 2], EntityKind::WRITER_WITH_KEY_USER_DEFINED)
+    );
+    assert!(matches!(
+      writer.qos.reliability,
+      Some(Reliability::Reliable { .. })
+    ));
+    assert_eq!(writer.qos.durability, Some(Durability::TransientLocal));
+
+    let reader = &sensor.readers[0];
+    assert_eq!(
+      reader.entity_id,
+      EntityId::new([0, 0, 3], EntityKind::READER_NO_KEY_USER_DEFINED)
+    );
+    assert_eq!(reader.qos.reliability, Some(Reliability::BestEffort));
+    assert_eq!(reader.unicast_locators.len(), 1);
+
+    // Participants need a name or a GUID prefix
+    assert!(StaticDiscovery::from_xml(
+      "<staticdiscovery><participant><reader><userId>1</userId><topicName>T</\
+       topicName><topicDataType>T</topicDataType></reader></participant></staticdiscovery>"
+    )
+    .is_err());
+  }
+
+  #[cfg(feature = "static_discovery")]
+  #[test]
+  fn static_toml() {
+    use crate::structure::guid::EntityKind;
+
+    let toml = r#"
+      [[participant]]
+      name = "sensor"
+      guidPrefix = "01.0f.00.00.00.00.00.00.00.00.00.01"
+      unicastLocator = [{ address = "192.168.1.10", port = 7411 }]
+
+      [[participant.writer]]
+      entityID = 2
+      topicName = "Temperature"
+      topicDataType = "Reading"
+      topicKind = "WITH_KEY"
+
+      [[participant]]
+      name = "display"
+    "#;
+    let sd = StaticDiscovery::from_toml(toml).unwrap();
+    assert_eq!(sd.participants.len(), 2);
+    let sensor = &sd.participants[0];
+    assert_eq!(
+      sensor.unicast_locators,
+      vec!["192.168.1.10:7411".parse::<SocketAddr>().unwrap()]
+    );
+    assert_eq!(
+      sensor.writers[0].entity_id,
+      EntityId::new([0, 0, 2], EntityKind::WRITER_WITH_KEY_USER_DEFINED)
+    );
+    assert!(sd.participants[1].writers.is_empty());
+
+    assert!(matches!(
+      StaticDiscovery::from_toml("[[participant]]\nguidPrefix = \"bad\""),
+      Err(StaticDiscoveryError::Invalid(_))
+    ));
+    assert!(matches!(
+      StaticDiscovery::from_toml("participant = 1"),
+      Err(StaticDiscoveryError::Toml(_))
+    ));
+  }
+}
@@ -0,0 +1,499 @@
+//! Dynamic data: data types described at run time, and CDR (de)serialization
+//! of values of such types.
+//!
+//! This is for tools and language bindings that do not have the data types
+//! compiled in. A [`DynamicType`] describes the type like an IDL definition
+//! would, and [`DynamicValue`]s are converted to and from CDR payloads
+//! according to it, e.g. for a
+//! [`RawSample`](crate::gateway::RawSample).
+//!
+//! Supported types are the primitive types, strings, sequences, arrays, and
+//! structures. Enumerations, unions, and optional members are not supported.
+//!
+//! # Example
+//!
+//! ```
+//! use rustdds::{dynamic_data::*, RepresentationIdentifier};
+//!
+//! // struct ShapeType { string color; long x; long y; long shapesize; };
+//! let shape_type = DynamicType::Struct(vec![
+//!   ("color".to_string(), DynamicType::String),
+//!   ("x".to_string(), DynamicType::Int32),
+//!   ("y".to_string(), DynamicType::Int32),
+//!   ("shapesize".to_string(), DynamicType::Int32),
+//! ]);
+//! let value = DynamicValue::Struct(
+//!   [
+//!     ("color".to_string(), DynamicValue::String("RED".to_string())),
+//!     ("x".to_string(), DynamicValue::Int(10)),
+//!     ("y".to_string(), DynamicValue::Int(20)),
+//!     ("shapesize".to_string(), DynamicValue::Int(30)),
+//!   ]
+//!   .into(),
+//! );
+//! let payload = shape_type
+//!   .serialize_value(&value, RepresentationIdentifier::CDR_LE)
+//!   .unwrap();
+//! let decoded = shape_type
+//!   .deserialize_value(&payload, RepresentationIdentifier::CDR_LE)
+//!   .unwrap();
+//! assert_eq!(decoded, value);
+//! ```
+
+use std::{collections::BTreeMap, fmt};
+
+use bytes::Bytes;
+use byteorder::{BigEndian, LittleEndian};
+use serde::{
+  de::{self, DeserializeSeed, Deserializer, SeqAccess, Visitor},
+  ser::{self, SerializeSeq, SerializeTuple, Serializer},
+  Serialize,
+};
+
+use crate::{
+  serialization::{to_writer_endian, CdrDeserializer, Error, Result},
+  RepresentationIdentifier,
+};
+
+/// Description of a data type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DynamicType {
+  Boolean,
+  Int8,
+  UInt8,
+  Int16,
+  UInt16,
+  Int32,
+  UInt32,
+  Int64,
+  UInt64,
+  Float32,
+  Float64,
+  String,
+  /// Variable-length sequence of elements
+  Sequence(Box<DynamicType>),
+  /// Fixed-length array of elements
+  Array(Box<DynamicType>, usize),
+  /// Structure members in declaration order
+  Struct(Vec<(String, DynamicType)>),
+}
+
+/// A value of a [`DynamicType`]
+///
+/// Integers of all widths are represented as `Int` or `UInt`. Either one is
+/// accepted for any integer type, if the value fits. Deserialized values of
+/// signed types are `Int` and of unsigned types `UInt`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DynamicValue {
+  Boolean(bool),
+  Int(i64),
+  UInt(u64),
+  Float(f64),
+  String(String),
+  /// Value of a sequence or an array
+  Sequence(Vec<DynamicValue>),
+  /// Structure members by name
+  Struct(BTreeMap<String, DynamicValue>),
+}
+
+impl DynamicType {
+  /// Serialize `value` as CDR. The encoding must be `CDR_LE` or `CDR_BE`.
+  pub fn serialize_value(
+    &self,
+    value: &DynamicValue,
+    encoding: RepresentationIdentifier,
+  ) -> Result<Bytes> {
+    check_encoding(encoding)?;
+    let mut buffer = Vec::new();
+    to_writer_endian(&mut buffer, &TypedValue(self, value), encoding)
+      .map_err(|e| Error::Message(e.to_string()))?;
+    Ok(Bytes::from(buffer))
+  }
+
+  /// Deserialize a CDR payload. Bytes after the value are ignored, as they
+  /// may be padding.
+  pub fn deserialize_value(
+    &self,
+    payload: &[u8],
+    encoding: RepresentationIdentifier,
+  ) -> Result<DynamicValue> {
+    check_encoding(encoding)?;
+    let value = if encoding == RepresentationIdentifier::CDR_LE {
+      TypeSeed(self).deserialize(&mut CdrDeserializer::<LittleEndian>::new(payload))
+    } else {
+      TypeSeed(self).deserialize(&mut CdrDeserializer::<BigEndian>::new(payload))
+    };
+    value.map_err(|e| Error::Message(e.to_string()))
+  }
+}
+
+fn check_encoding(encoding: RepresentationIdentifier) -> Result<()> {
+  if encoding == RepresentationIdentifier::CDR_LE || encoding == RepresentationIdentifier::CDR_BE {
+    Ok(())
+  } else {
+    Err(Error::Message(format!(
+      "Dynamic data does not support encoding {encoding:?}"
+    )))
+  }
+}
+
+// Serialization
+
+struct TypedValue<'a>(&'a DynamicType, &'a DynamicValue);
+
+impl TypedValue<'_> {
+  fn integer<T, E>(&self) -> std::result::Result<T, E>
+  where
+    T: TryFrom<i64> + TryFrom<u64>,
+    E: ser::Error,
+  {
+    match self.1 {
+      DynamicValue::Int(i) => T::try_from(*i).ok(),
+      DynamicValue::UInt(u) => T::try_from(*u).ok(),
+      _ => None,
+    }
+    .ok_or_else(|| self.mismatch())
+  }
+
+  fn float<E: ser::Error>(&self) -> std::result::Result<f64, E> {
+    match self.1 {
+      DynamicValue::Float(f) => Ok(*f),
+      DynamicValue::Int(i) => Ok(*i as f64),
+      DynamicValue::UInt(u) => Ok(*u as f64),
+      _ => Err(self.mismatch()),
+    }
+  }
+
+  fn mismatch<E: ser::Error>(&self) -> E {
+    E::custom(format!("{:?} is not a valid {:?}", self.1, self.0))
+  }
+}
+
+impl Serialize for TypedValue<'_> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    match (self.0, self.1) {
+      (DynamicType::Boolean, DynamicValue::Boolean(b)) => serializer.serialize_bool(*b),
+      (DynamicType::Int8, _) => serializer.serialize_i8(self.integer()?),
+      (DynamicType::UInt8, _) => serializer.serialize_u8(self.integer()?),
+      (DynamicType::Int16, _) => serializer.serialize_i16(self.integer()?),
+      (DynamicType::UInt16, _) => serializer.serialize_u16(self.integer()?),
+      (DynamicType::Int32, _) => serializer.serialize_i32(self.integer()?),
+      (DynamicType::UInt32, _) => serializer.serialize_u32(self.integer()?),
+      (DynamicType::Int64, _) => serializer.serialize_i64(self.integer()?),
+      (DynamicType::UInt64, _) => serializer.serialize_u64(self.integer()?),
+      (DynamicType::Float32, _) => serializer.serialize_f32(self.float()? as f32),
+      (DynamicType::Float64, _) => serializer.serialize_f64(self.float()?),
+      (DynamicType::String, DynamicValue::String(s)) => serializer.serialize_str(s),
+      (DynamicType::Sequence(element_type), DynamicValue::Sequence(elements)) => {
+        let mut seq = serializer.serialize_seq(Some(elements.len()))?;
+        for element in elements {
+          seq.serialize_element(&TypedValue(element_type, element))?;
+        }
+        seq.end()
+      }
+      (DynamicType::Array(element_type, len), DynamicValue::Sequence(elements)) => {
+        if elements.len() != *len {
+          return Err(ser::Error::custom(format!(
+            "Array has {} elements, expected {len}",
+            elements.len()
+          )));
+        }
+        let mut tuple = serializer.serialize_tuple(*len)?;
+        for element in elements {
+          tuple.serialize_element(&TypedValue(element_type, element))?;
+        }
+        tuple.end()
+      }
+      (DynamicType::Struct(members), DynamicValue::Struct(values)) => {
+        let mut tuple = serializer.serialize_tuple(members.len())?;
+        for (name, member_type) in members {
+          let value = values
+            .get(name)
+            .ok_or_else(|| ser::Error::custom(format!("Missing struct member {name:?}")))?;
+          tuple.serialize_element(&TypedValue(member_type, value))?;
+        }
+        tuple.end()
+      }
+      _ => Err(self.mismatch()),
+    }
+  }
+}
+
+// Deserialization
+
+#[derive(Clone, Copy)]
+struct TypeSeed<'a>(&'a DynamicType);
+
+impl<'de> DeserializeSeed<'de> for TypeSeed<'_> {
+  type Value = DynamicValue;
+
+  fn deserialize<D: Deserializer<'de>>(
+    self,
+    deserializer: D,
+  ) -> std::result::Result<DynamicValue, D::Error> {
+    match self.0 {
+      DynamicType::Boolean => deserializer.deserialize_bool(self),
+      DynamicType::Int8 => deserializer.deserialize_i8(self),
+      DynamicType::UInt8 => deserializer.deserialize_u8(self),
+      DynamicType::Int16 => deserializer.deserialize_i16(self),
+      DynamicType::UInt16 => deserializer.deserialize_u16(self),
+      DynamicType::Int32 => deserializer.deserialize_i32(self),
+      DynamicType::UInt32 => deserializer.deserialize_u32(self),
+      DynamicType::Int64 => deserializer.deserialize_i64(self),
+      DynamicType::UInt64 => deserializer.deserialize_u64(self),
+      DynamicType::Float32 => deserializer.deserialize_f32(self),
+      DynamicType::Float64 => deserializer.deserialize_f64(self),
+      DynamicType::String => deserializer.deserialize_str(self),
+      DynamicType::Sequence(_) => deserializer.deserialize_seq(self),
+      DynamicType::Array(_, len) => deserializer.deserialize_tuple(*len, self),
+      DynamicType::Struct(members) => deserializer.deserialize_tuple(members.len(), self),
+    }
+  }
+}
+
+impl<'de> Visitor<'de> for TypeSeed<'_> {
+  type Value = DynamicValue;
+
+  fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    write!(formatter, "a value of {:?}", self.0)
+  }
+
+  fn visit_bool<E: de::Error>(self, v: bool) -> std::result::Result<DynamicValue, E> {
+    Ok(DynamicValue::Boolean(v))
+  }
+
+  fn visit_i64<E: de::Error>(self, v: i64) -> std::result::Result<DynamicValue, E> {
+    Ok(DynamicValue::Int(v))
+  }
+
+  fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<DynamicValue, E> {
+    Ok(DynamicValue::UInt(v))
+  }
+
+  fn visit_f64<E: de::Error>(self, v: f64) -> std::result::Result<DynamicValue, E> {
+    Ok(DynamicValue::Float(v))
+  }
+
+  fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<DynamicValue, E> {
+    Ok(DynamicValue::String(v.to_string()))
+  }
+
+  fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<DynamicValue, A::Error> {
+    let missing = || de::Error::custom(format!("Payload ends within {:?}", self.0));
+    match self.0 {
+      DynamicType::Sequence(element_type) => {
+        // The element count comes from the wire, so it is not used to
+        // preallocate.
+        let mut elements = Vec::new();
+        while let Some(element) = seq.next_element_seed(TypeSeed(element_type))? {
+          elements.push(element);
+        }
+        Ok(DynamicValue::Sequence(elements))
+      }
+      DynamicType::Array(element_type, len) => {
+        let mut elements = Vec::with_capacity(*len);
+        for _ in 0..*len {
+          elements.push(
+            seq
+              .next_element_seed(TypeSeed(element_type))?
+              .ok_or_else(missing)?,
+          );
+        }
+        Ok(DynamicValue::Sequence(elements))
+      }
+      DynamicType::Struct(members) => {
+        let mut values = BTreeMap::new();
+        for (name, member_type) in members {
+          let value = seq
+            .next_element_seed(TypeSeed(member_type))?
+            .ok_or_else(missing)?;
+          values.insert(name.clone(), value);
+        }
+        Ok(DynamicValue::Struct(values))
+      }
+      _ => Err(de::Error::invalid_type(de::Unexpected::Seq, &self)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde::Deserialize;
+
+  use super::*;
+  use crate::serialization::cdr_serializer::to_bytes;
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  struct Inner {
+    flag: bool,
+    small: u8,
+    values: Vec<f32>,
+  }
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  struct Outer {
+    name: String,
+    id: i16,
+    inner: Inner,
+    position: [f64; 3],
+    counter: u64,
+    tags: Vec<String>,
+  }
+
+  fn outer_type() -> DynamicType {
+    DynamicType::Struct(vec![
+      ("name".to_string(), DynamicType::String),
+      ("id".to_string(), DynamicType::Int16),
+      (
+        "inner".to_string(),
+        DynamicType::Struct(vec![
+          ("flag".to_string(), DynamicType::Boolean),
+          ("small".to_string(), DynamicType::UInt8),
+          (
+            "values".to_string(),
+            DynamicType::Sequence(Box::new(DynamicType::Float32)),
+          ),
+        ]),
+      ),
+      (
+        "position".to_string(),
+        DynamicType::Array(Box::new(DynamicType::Float64), 3),
+      ),
+      ("counter".to_string(), DynamicType::UInt64),
+      (
+        "tags".to_string(),
+        DynamicType::Sequence(Box::new(DynamicType::String)),
+      ),
+    ])
+  }
+
+  fn outer_value() -> DynamicValue {
+    let inner = DynamicValue::Struct(
+      [
+        ("flag".to_string(), DynamicValue::Boolean(true)),
+        ("small".to_string(), DynamicValue::Int(7)),
+        (
+          "values".to_string(),
+          DynamicValue::Sequence(vec![DynamicValue::Float(0.5), DynamicValue::Float(-2.0)]),
+        ),
+      ]
+      .into(),
+    );
+    DynamicValue::Struct(
+      [
+        ("name".to_string(), DynamicValue::String("abc".to_string())),
+        ("id".to_string(), DynamicValue::Int(-3)),
+        ("inner".to_string(), inner),
+        (
+          "position".to_string(),
+          DynamicValue::Sequence(vec![
+            DynamicValue::Float(1.0),
+            DynamicValue::Float(2.0),
+            DynamicValue::Float(3.0),
+          ]),
+        ),
+        ("counter".to_string(), DynamicValue::UInt(1 << 40)),
+        (
+          "tags".to_string(),
+          DynamicValue::Sequence(vec![DynamicValue::String("x".to_string())]),
+        ),
+      ]
+      .into(),
+    )
+  }
+
+  fn outer_struct() -> Outer {
+    Outer {
+      name: "abc".to_string(),
+      id: -3,
+      inner: Inner {
+        flag: true,
+        small: 7,
+        values: vec![0.5, -2.0],
+      },
+      position: [1.0, 2.0, 3.0],
+      counter: 1 << 40,
+      tags: vec!["x".to_string()],
+    }
+  }
+
+  #[test]
+  fn serializes_like_static_types() {
+    let payload_le = outer_type()
+      .serialize_value(&outer_value(), RepresentationIdentifier::CDR_LE)
+      .unwrap();
+    assert_eq!(
+      payload_le,
+      to_bytes::<_, LittleEndian>(&outer_struct()).unwrap()
+    );
+    let payload_be = outer_type()
+      .serialize_value(&outer_value(), RepresentationIdentifier::CDR_BE)
+      .unwrap();
+    assert_eq!(
+      payload_be,
+      to_bytes::<_, BigEndian>(&outer_struct()).unwrap()
+    );
+  }
+
+  #[test]
+  fn deserializes_static_types() {
+    for encoding in [
+      RepresentationIdentifier::CDR_LE,
+      RepresentationIdentifier::CDR_BE,
+    ] {
+      let mut payload = Vec::new();
+      to_writer_endian(&mut payload, &outer_struct(), encoding).unwrap();
+      let value = outer_type().deserialize_value(&payload, encoding).unwrap();
+      // Unsigned values come back as UInt
+      let mut expected = outer_value();
+      if let DynamicValue::Struct(members) = &mut expected {
+        if let Some(DynamicValue::Struct(inner)) = members.get_mut("inner") {
+          inner.insert("small".to_string(), DynamicValue::UInt(7));
+        }
+      }
+      assert_eq!(value, expected);
+    }
+  }
+
+  #[test]
+  fn rejects_mismatching_values() {
+    let encoding = RepresentationIdentifier::CDR_LE;
+    assert!(DynamicType::UInt8
+      .serialize_value(&DynamicValue::Int(256), encoding)
+      .is_err());
+    assert!(DynamicType::Int32
+      .serialize_value(&DynamicValue::String("1".to_string()), encoding)
+      .is_err());
+    assert!(DynamicType::Array(Box::new(DynamicType::Int32), 2)
+      .serialize_value(
+        &DynamicValue::Sequence(vec![DynamicValue::Int(1)]),
+        encoding
+      )
+      .is_err());
+    assert!(
+      DynamicType::Struct(vec![("a".to_string(), DynamicType::Int32)])
+        .serialize_value(&DynamicValue::Struct(BTreeMap::new()), encoding)
+        .is_err()
+    );
+    assert!(DynamicType::Int32
+      .serialize_value(&DynamicValue::Int(1), RepresentationIdentifier::PL_CDR_LE)
+      .is_err());
+  }
+
+  #[test]
+  fn rejects_truncated_payloads() {
+    let payload = outer_type()
+      .serialize_value(&outer_value(), RepresentationIdentifier::CDR_LE)
+      .unwrap();
+    for len in [0, 5, payload.len() / 2, payload.len() - 1] {
+      assert!(outer_type()
+        .deserialize_value(&payload[..len], RepresentationIdentifier::CDR_LE)
+        .is_err());
+    }
+    // A sequence length from the wire does not cause a big allocation.
+    let huge_sequence = u32::MAX.to_le_bytes();
+    assert!(DynamicType::Sequence(Box::new(DynamicType::UInt64))
+      .deserialize_value(&huge_sequence, RepresentationIdentifier::CDR_LE)
+      .is_err());
+  }
+}
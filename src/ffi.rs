@@ -0,0 +1,811 @@
+//! C language interface to the core publish/subscribe API.
+//!
+//! The data types of the Rust API are generic over the sample type and its
+//! (de)serializer, so they cannot be bound from C directly. This module works
+//! on serialized payloads instead: DataWriters take already serialized
+//! samples, and DataReaders give out samples as they were received, together
+//! with their encoding, see [`RawSample`]. Serialization is left to the
+//! application, e.g. code generated from IDL.
+//!
+//! Topics are accessed as if they were NO_KEY Topics, like in the
+//! [gateway](crate::gateway). Samples of keyed Topics can be written and
+//! taken, but instances cannot be disposed or unregistered.
+//!
+//! This module is available only with the feature "ffi". To build a shared
+//! library for C applications, run
+//!
+//! ```text
+//! cargo rustc --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! The function declarations are in `include/rustdds.h`.
+//!
+//! Objects are created with `rustdds_*_new` and must be released with the
+//! matching `rustdds_*_delete`. Functions that return an object return NULL
+//! on failure. Other functions return `RUSTDDS_OK` or another of the
+//! `RUSTDDS_*` result codes. Objects of one participant may be used from
+//! several threads, but each object from only one thread at a time.
+//!
+//! The library does not run callbacks from threads of its own. Status
+//! callbacks are called from [`rustdds_reader_process_status`] and
+//! [`rustdds_writer_process_status`]. On non-Windows platforms the
+//! application can wait for data and status changes on file descriptors, e.g.
+//! using `poll(2)` or libevent, see [`rustdds_reader_fd`].
+
+use std::{
+  ffi::CStr,
+  os::raw::{c_char, c_int, c_void},
+  panic::{catch_unwind, AssertUnwindSafe},
+  ptr, slice,
+};
+#[cfg(not(target_os = "windows"))]
+use std::os::fd::AsRawFd;
+
+use bytes::Bytes;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::{
+  dds::{
+    no_key::{datareader::DataReader, datawriter::DataWriter},
+    participant::DomainParticipant,
+    pubsub::{Publisher, Subscriber},
+    qos::{policy, QosPolicies, QosPolicyBuilder},
+    result::WriteError,
+    statusevents::{DataReaderStatus, DataWriterStatus, StatusEvented},
+    topic::{Topic, TopicKind},
+    with_key::datawriter::WriteOptions,
+  },
+  gateway::{RawSample, RawSampleAdapter},
+  structure::{duration::Duration, time::Timestamp},
+  RepresentationIdentifier,
+};
+
+/// Success
+pub const RUSTDDS_OK: c_int = 0;
+/// [`rustdds_reader_take`] found no sample to take.
+pub const RUSTDDS_NO_DATA: c_int = 1;
+/// Operation failed
+pub const RUSTDDS_ERROR: c_int = -1;
+/// NULL pointer or otherwise invalid argument
+pub const RUSTDDS_BAD_PARAMETER: c_int = -2;
+/// A reliable write could not complete within the max blocking time.
+pub const RUSTDDS_TIMEOUT: c_int = -3;
+
+/// A DomainParticipant, with a Publisher and a Subscriber for creating
+/// DataWriters and DataReaders.
+pub struct RustddsParticipant {
+  participant: DomainParticipant,
+  publisher: Publisher,
+  subscriber: Subscriber,
+}
+
+pub struct RustddsTopic {
+  topic: Topic,
+}
+
+/// Callback for status changes. `status` is valid only during the call.
+pub type RustddsStatusCallback =
+  Option<unsafe extern "C" fn(status: *const RustddsStatus, user_data: *mut c_void)>;
+
+struct StatusListener {
+  callback: RustddsStatusCallback,
+  user_data: *mut c_void,
+}
+
+impl StatusListener {
+  fn notify(&self, status: &RustddsStatus) {
+    if let Some(callback) = self.callback {
+      unsafe { callback(status, self.user_data) }
+    }
+  }
+}
+
+pub struct RustddsWriter {
+  writer: DataWriter<RawSample, RawSampleAdapter>,
+  listener: Option<StatusListener>,
+}
+
+pub struct RustddsReader {
+  reader: DataReader<RawSample, RawSampleAdapter>,
+  listener: Option<StatusListener>,
+}
+
+/// The most commonly needed QoS policies. Pass NULL instead to use the Topic
+/// QoS.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RustddsQos {
+  /// Reliable instead of BestEffort
+  pub reliable: bool,
+  /// TransientLocal instead of Volatile durability
+  pub transient_local: bool,
+  /// KeepLast depth. Zero or negative means KeepAll.
+  pub history_depth: i32,
+}
+
+impl RustddsQos {
+  fn to_qos_policies(self) -> QosPolicies {
+    let mut builder = QosPolicyBuilder::new();
+    builder = if self.reliable {
+      builder.reliable(Duration::from_millis(100))
+    } else {
+      builder.best_effort()
+    };
+    builder = builder.durability(if self.transient_local {
+      policy::Durability::TransientLocal
+    } else {
+      policy::Durability::Volatile
+    });
+    builder = builder.history(if self.history_depth > 0 {
+      policy::History::KeepLast {
+        depth: self.history_depth,
+      }
+    } else {
+      policy::History::KeepAll
+    });
+    builder.build()
+  }
+}
+
+/// A sample taken from a DataReader. The payload is owned by the library,
+/// and must be released with [`rustdds_sample_release`].
+#[repr(C)]
+#[derive(Debug)]
+pub struct RustddsSample {
+  /// Serialized data, without the encapsulation header
+  pub data: *mut u8,
+  pub len: usize,
+  /// Encoding of `data`, i.e. the RTPS representation identifier, e.g.
+  /// 0x0001 for little-endian CDR
+  pub encoding: u16,
+  /// Source timestamp in nanoseconds since the Unix epoch, or -1 if the
+  /// writer did not send one
+  pub source_timestamp_ns: i64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RustddsStatusKind {
+  SampleRejected,
+  LivelinessChanged,
+  RequestedDeadlineMissed,
+  RequestedIncompatibleQos,
+  SampleLost,
+  SubscriptionMatched,
+  LivelinessLost,
+  OfferedDeadlineMissed,
+  OfferedIncompatibleQos,
+  PublicationMatched,
+}
+
+/// A status change of a DataReader or DataWriter.
+///
+/// For `SubscriptionMatched` and `PublicationMatched`, `current_count` is the
+/// number of currently matched endpoints. For `LivelinessChanged`,
+/// `total_count` counts writers that became alive, and `current_count`
+/// writers that became not alive. For other kinds, `current_count` is zero.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RustddsStatus {
+  pub kind: RustddsStatusKind,
+  pub total_count: i32,
+  pub total_count_change: i32,
+  pub current_count: i32,
+  pub current_count_change: i32,
+}
+
+impl RustddsStatus {
+  fn new(kind: RustddsStatusKind, total: (i32, i32), current: (i32, i32)) -> Self {
+    Self {
+      kind,
+      total_count: total.0,
+      total_count_change: total.1,
+      current_count: current.0,
+      current_count_change: current.1,
+    }
+  }
+}
+
+impl From<&DataReaderStatus> for RustddsStatus {
+  fn from(status: &DataReaderStatus) -> Self {
+    use RustddsStatusKind as K;
+    match status {
+      DataReaderStatus::SampleRejected { count, .. } => Self::new(
+        K::SampleRejected,
+        (count.count(), count.count_change()),
+        (0, 0),
+      ),
+      DataReaderStatus::LivelinessChanged {
+        alive_total,
+        not_alive_total,
+      } => Self::new(
+        K::LivelinessChanged,
+        (alive_total.count(), alive_total.count_change()),
+        (not_alive_total.count(), not_alive_total.count_change()),
+      ),
+      DataReaderStatus::RequestedDeadlineMissed { count } => Self::new(
+        K::RequestedDeadlineMissed,
+        (count.count(), count.count_change()),
+        (0, 0),
+      ),
+      DataReaderStatus::RequestedIncompatibleQos { count, .. } => Self::new(
+        K::RequestedIncompatibleQos,
+        (count.count(), count.count_change()),
+        (0, 0),
+      ),
+      DataReaderStatus::SampleLost { count } => {
+        Self::new(K::SampleLost, (count.count(), count.count_change()), (0, 0))
+      }
+      DataReaderStatus::SubscriptionMatched { total, current, .. } => Self::new(
+        K::SubscriptionMatched,
+        (total.count(), total.count_change()),
+        (current.count(), current.count_change()),
+      ),
+    }
+  }
+}
+
+impl From<&DataWriterStatus> for RustddsStatus {
+  fn from(status: &DataWriterStatus) -> Self {
+    use RustddsStatusKind as K;
+    match status {
+      DataWriterStatus::LivelinessLost { count } => Self::new(
+        K::LivelinessLost,
+        (count.count(), count.count_change()),
+        (0, 0),
+      ),
+      DataWriterStatus::OfferedDeadlineMissed { count } => Self::new(
+        K::OfferedDeadlineMissed,
+        (count.count(), count.count_change()),
+        (0, 0),
+      ),
+      DataWriterStatus::OfferedIncompatibleQos { count, .. } => Self::new(
+        K::OfferedIncompatibleQos,
+        (count.count(), count.count_change()),
+        (0, 0),
+      ),
+      DataWriterStatus::PublicationMatched { total, current, .. } => Self::new(
+        K::PublicationMatched,
+        (total.count(), total.count_change()),
+        (current.count(), current.count_change()),
+      ),
+    }
+  }
+}
+
+// Panics must not unwind into C code.
+fn guarded<R>(on_panic: R, f: impl FnOnce() -> R) -> R {
+  catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| {
+    error!("Panic in RustDDS C interface");
+    on_panic
+  })
+}
+
+unsafe fn string_arg(s: *const c_char) -> Option<String> {
+  if s.is_null() {
+    return None;
+  }
+  CStr::from_ptr(s).to_str().ok().map(String::from)
+}
+
+unsafe fn qos_arg(qos: *const RustddsQos) -> Option<QosPolicies> {
+  qos.as_ref().map(|q| q.to_qos_policies())
+}
+
+/// Create a DomainParticipant in domain `domain_id`.
+#[no_mangle]
+pub extern "C" fn rustdds_participant_new(domain_id: u16) -> *mut RustddsParticipant {
+  guarded(ptr::null_mut(), || {
+    let participant = match DomainParticipant::new(domain_id) {
+      Ok(p) => p,
+      Err(e) => {
+        error!("rustdds_participant_new: {e:?}");
+        return ptr::null_mut();
+      }
+    };
+    let qos = QosPolicies::qos_none();
+    match (
+      participant.create_publisher(&qos),
+      participant.create_subscriber(&qos),
+    ) {
+      (Ok(publisher), Ok(subscriber)) => Box::into_raw(Box::new(RustddsParticipant {
+        participant,
+        publisher,
+        subscriber,
+      })),
+      (Err(e), _) | (_, Err(e)) => {
+        error!("rustdds_participant_new: {e:?}");
+        ptr::null_mut()
+      }
+    }
+  })
+}
+
+/// # Safety
+///
+/// `participant` must be NULL or a pointer returned by
+/// [`rustdds_participant_new`], that has not been deleted.
+#[no_mangle]
+pub unsafe extern "C" fn rustdds_participant_delete(participant: *mut RustddsParticipant) {
+  if !participant.is_null() {
+    guarded((), || drop(Box::from_raw(participant)));
+  }
+}
+
+/// Find or create a Topic. `name` and `type_name` are NUL-terminated UTF-8
+/// strings. `qos` may be NULL for default QoS.
+///
+/// # Safety
+///
+/// `participant` must be a valid participant, and the strings valid C
+/// strings.
+#[no_mangle]
+pub unsafe extern "C" fn rustdds_topic_new(
+  participant: *const RustddsParticipant,
+  name: *const c_char,
+  type_name: *const c_char,
+  qos: *const RustddsQos,
+) -> *mut RustddsTopic {
+  let (participant, name, type_name) = match (
+    participant.as_ref(),
+    string_arg(name),
+    string_arg(type_name),
+  ) {
+    (Some(p), Some(n), Some(t)) => (p, n, t),
+    _ => return ptr::null_mut(),
+  };
+  let qos = qos_arg(qos).unwrap_or_else(QosPolicies::qos_none);
+  guarded(ptr::null_mut(), || {
+    match participant
+      .participant
+      .create_topic(name, type_name, &qos, TopicKind::NoKey)
+    {
+      Ok(topic) => Box::into_raw(Box::new(RustddsTopic { topic })),
+      Err(e) => {
+        error!("rustdds_topic_new: {e:?}");
+        ptr::null_mut()
+      }
+    }
+  })
+}
+
+/// # Safety
+///
+/// `topic` must be NULL or a pointer returned by [`rustdds_topic_new`], that
+/// has not been deleted.
+#[no_mangle]
+pub unsafe extern "C" fn rustdds_topic_delete(topic: *mut RustddsTopic) {
+  if !topic.is_null() {
+    guarded((), || drop(Box::from_raw(topic)));
+  }
+}
+
+/// Create a DataWriter. `qos` may be NULL to use the Topic QoS.
+///
+/// # Safety
+///
+/// `participant` and `topic` must be valid objects.
+#[no_mangle]
+pub unsafe extern "C" fn rustdds_writer_new(
+  participant: *const RustddsParticipant,
+  topic: *const RustddsTopic,
+  qos: *const RustddsQos,
+) -> *mut RustddsWriter {
+  let (participant, topic) = match (participant.as_ref(), topic.as_ref()) {
+    (Some(p), Some(t)) => (p, t),
+    _ => return ptr::null_mut(),
+  };
+  let qos = qos_arg(qos);
+  guarded(ptr::null_mut(), || {
+    match participant
+      .publisher
+      .create_datawriter_no_key::<RawSample, RawSampleAdapter>(&topic.topic, qos)
+    {
+      Ok(writer) => Box::into_raw(Box::new(RustddsWriter {
+        writer,
+        listener: None,
+      })),
+      Err(e) => {
+        error!("rustdds_writer_new: {e:?}");
+        ptr::null_mut()
+      }
+    }
+  })
+}
+
+/// # Safety
+///
+/// `writer` must be NULL or a pointer returned by [`rustdds_writer_new`],
+/// that has not been deleted.
+#[no_mangle]
+pub unsafe extern "C" fn rustdds_writer_delete(writer: *mut RustddsWriter) {
+  if !writer.is_null() {
+    guarded((), || drop(Box::from_raw(writer)));
+  }
+}
+
+/// Write a serialized sample. `data` is the payload without the
+/// encapsulation header, and `encoding` its representation identifier, e.g.
+/// 0x0001 for little-endian CDR.
+/// The source timestamp is the current time.
+///
+/// # Safety
+///
+/// `writer` must be a valid DataWriter, and `data` must point to `len`
+/// readable bytes. `data` may be NULL if `len` is zero.
+#[no_mangle]
+pub unsafe extern "C" fn rustdds_writer_write(
+  writer: *const RustddsWriter,
+  data: *const u8,
+  len: usize,
+  encoding: u16,
+) -> c_int {
+  let writer = match writer.as_ref() {
+    Some(w) => w,
+    None => return RUSTDDS_BAD_PARAMETER,
+  };
+  let value = match (data.is_null(), len) {
+    (_, 0) => Bytes::new(),
+    (true, _) => return RUSTDDS_BAD_PARAMETER,
+    (false, _) => Bytes::copy_from_slice(slice::from_raw_parts(data, len)),
+  };
+  let encoding = match RepresentationIdentifier::from_bytes(&encoding.to_be_bytes()) {
+    Ok(e) => e,
+    Err(_) => return RUSTDDS_BAD_PARAMETER,
+  };
+  guarded(RUSTDDS_ERROR, || {
+    match writer.writer.write_with_encoding(
+      RawSample { encoding, value },
+      encoding,
+      WriteOptions::from(Some(Timestamp::now())),
+    ) {
+      Ok(_) => RUSTDDS_OK,
+      Err(WriteError::WouldBlock { .. }) => RUSTDDS_TIMEOUT,
+      Err(e) => {
+        debug!("rustdds_writer_write: {e:?}");
+        RUSTDDS_ERROR
+      }
+    }
+  })
+}
+
+/// Create a DataReader. `qos` may be NULL to use the Topic QoS.
+///
+/// # Safety
+///
+/// `participant` and `topic` must be valid objects.
+#[no_mangle]
+pub unsafe extern "C" fn rustdds_reader_new(
+  participant: *const RustddsParticipant,
+  topic: *const RustddsTopic,
+  qos: *const RustddsQos,
+) -> *mut RustddsReader {
+  let (participant, topic) = match (participant.as_ref(), topic.as_ref()) {
+    (Some(p), Some(t)) => (p, t),
+    _ => return ptr::null_mut(),
+  };
+  let qos = qos_arg(qos);
+  guarded(ptr::null_mut(), || {
+    match participant
+      .subscriber
+      .create_datareader_no_key::<RawSample, RawSampleAdapter>(&topic.topic, qos)
+    {
+      Ok(reader) => Box::into_raw(Box::new(RustddsReader {
+        reader,
+        listener: None,
+      })),
+      Err(e) => {
+        error!("rustdds_reader_new: {e:?}");
+        ptr::null_mut()
+      }
+    }
+  })
+}
+
+/// # Safety
+///
+/// `reader` must be NULL or a pointer returned by [`rustdds_reader_new`],
+/// that has not been deleted.
+#[no_mangle]
+pub unsafe extern "C" fn rustdds_reader_delete(reader: *mut RustddsReader) {
+  if !reader.is_null() {
+    guarded((), || drop(Box::from_raw(reader)));
+  }
+}
+
+/// Take the next available sample. Returns `RUSTDDS_OK` and fills in
+/// `sample`, or `RUSTDDS_NO_DATA` if there is nothing to take. A filled-in
+/// sample must be released with [`rustdds_sample_release`].
+///
+/// # Safety
+///
+/// `reader` must be a valid DataReader and `sample` writable.
+#[no_mangle]
+pub unsafe extern "C" fn rustdds_reader_take(
+  reader: *mut RustddsReader,
+  sample: *mut RustddsSample,
+) -> c_int {
+  let (reader, sample) = match (reader.as_mut(), sample.as_mut()) {
+    (Some(r), Some(s)) => (r, s),
+    _ => return RUSTDDS_BAD_PARAMETER,
+  };
+  guarded(RUSTDDS_ERROR, || match reader.reader.take_next_sample() {
+    Ok(Some(ds)) => {
+      let source_timestamp_ns = ds
+        .sample_info()
+        .source_timestamp()
+        .map_or(-1, |ts| ts.duration_since(Timestamp::ZERO).to_nanoseconds());
+      let raw = ds.into_value();
+      let data: Box<[u8]> = raw.value.to_vec().into_boxed_slice();
+      *sample = RustddsSample {
+        len: data.len(),
+        data: Box::into_raw(data).cast::<u8>(),
+        encoding: u16::from_be_bytes(raw.encoding.to_bytes()),
+        source_timestamp_ns,
+      };
+      RUSTDDS_OK
+    }
+    Ok(None) => RUSTDDS_NO_DATA,
+    Err(e) => {
+      debug!("rustdds_reader_take: {e:?}");
+      RUSTDDS_ERROR
+    }
+  })
+}
+
+/// Release the payload of a sample filled in by [`rustdds_reader_take`].
+///
+/// # Safety
+///
+/// `sample` must have been filled in by [`rustdds_reader_take`], and not
+/// released before.
+#[no_mangle]
+pub unsafe extern "C" fn rustdds_sample_release(sample: *mut RustddsSample) {
+  if let Some(sample) = sample.as_mut() {
+    if !sample.data.is_null() {
+      drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+        sample.data,
+        sample.len,
+      )));
+    }
+    sample.data = ptr::null_mut();
+    sample.len = 0;
+  }
+}
+
+/// File descriptor that becomes readable when the DataReader may have new
+/// data. Take all available samples before waiting on it again. The
+/// descriptor is owned by the DataReader, and must not be closed or read.
+///
+/// # Safety
+///
+/// `reader` must be a valid DataReader.
+#[cfg(not(target_os = "windows"))]
+#[no_mangle]
+pub unsafe extern "C" fn rustdds_reader_fd(reader: *const RustddsReader) -> c_int {
+  match reader.as_ref() {
+    Some(r) => r.reader.as_raw_fd(),
+    None => RUSTDDS_BAD_PARAMETER,
+  }
+}
+
+/// File descriptor that becomes readable when the DataReader may have status
+/// changes. Call [`rustdds_reader_process_status`] before waiting on it
+/// again.
+///
+/// # Safety
+///
+/// `reader` must be a valid DataReader.
+#[cfg(not(target_os = "windows"))]
+#[no_mangle]
+pub unsafe extern "C" fn rustdds_reader_status_fd(reader: *mut RustddsReader) -> c_int {
+  match reader.as_mut() {
+    Some(r) => r.reader.as_status_raw_fd(),
+    None => RUSTDDS_BAD_PARAMETER,
+  }
+}
+
+/// Set the status callback of a DataReader, replacing any previous one.
+/// `user_data` is passed to the callback as is. The callback is called from
+/// [`rustdds_reader_process_status`].
+///
+/// # Safety
+///
+/// `reader` must be a valid DataReader. `callback` must be safe to call with
+/// `user_data` for as long as it is set.
+#[no_mangle]
+pub unsafe extern "C" fn rustdds_reader_set_status_callback(
+  reader: *mut RustddsReader,
+  callback: RustddsStatusCallback,
+  user_data: *mut c_void,
+) -> c_int {
+  let reader = match reader.as_mut() {
+    Some(r) => r,
+    None => return RUSTDDS_BAD_PARAMETER,
+  };
+  // Status events are queued only after they are subscribed to.
+  reader.reader.as_status_source();
+  reader.listener = Some(StatusListener {
+    callback,
+    user_data,
+  });
+  RUSTDDS_OK
+}
+
+/// Call the status callback for each pending status change. Returns the
+/// number of status changes, or a negative result code.
+///
+/// # Safety
+///
+/// `reader` must be a valid DataReader.
+#[no_mangle]
+pub unsafe extern "C" fn rustdds_reader_process_status(reader: *mut RustddsReader) -> c_int {
+  let reader = match reader.as_mut() {
+    Some(r) => r,
+    None => return RUSTDDS_BAD_PARAMETER,
+  };
+  guarded(RUSTDDS_ERROR, || {
+    let mut count = 0;
+    while let Some(status) = reader.reader.try_recv_status() {
+      if let Some(listener) = &reader.listener {
+        listener.notify(&RustddsStatus::from(&status));
+      }
+      count += 1;
+    }
+    count
+  })
+}
+
+/// File descriptor that becomes readable when the DataWriter may have status
+/// changes. Call [`rustdds_writer_process_status`] before waiting on it
+/// again.
+///
+/// # Safety
+///
+/// `writer` must be a valid DataWriter.
+#[cfg(not(target_os = "windows"))]
+#[no_mangle]
+pub unsafe extern "C" fn rustdds_writer_status_fd(writer: *mut RustddsWriter) -> c_int {
+  match writer.as_mut() {
+    Some(w) => w.writer.as_status_raw_fd(),
+    None => RUSTDDS_BAD_PARAMETER,
+  }
+}
+
+/// Set the status callback of a DataWriter. See
+/// [`rustdds_reader_set_status_callback`].
+///
+/// # Safety
+///
+/// `writer` must be a valid DataWriter. `callback` must be safe to call with
+/// `user_data` for as long as it is set.
+#[no_mangle]
+pub unsafe extern "C" fn rustdds_writer_set_status_callback(
+  writer: *mut RustddsWriter,
+  callback: RustddsStatusCallback,
+  user_data: *mut c_void,
+) -> c_int {
+  let writer = match writer.as_mut() {
+    Some(w) => w,
+    None => return RUSTDDS_BAD_PARAMETER,
+  };
+  writer.writer.as_status_source();
+  writer.listener = Some(StatusListener {
+    callback,
+    user_data,
+  });
+  RUSTDDS_OK
+}
+
+/// Call the status callback for each pending status change of a DataWriter.
+/// Returns the number of status changes, or a negative result code.
+///
+/// # Safety
+///
+/// `writer` must be a valid DataWriter.
+#[no_mangle]
+pub unsafe extern "C" fn rustdds_writer_process_status(writer: *mut RustddsWriter) -> c_int {
+  let writer = match writer.as_mut() {
+    Some(w) => w,
+    None => return RUSTDDS_BAD_PARAMETER,
+  };
+  guarded(RUSTDDS_ERROR, || {
+    let mut count = 0;
+    while let Some(status) = writer.writer.try_recv_status() {
+      if let Some(listener) = &writer.listener {
+        listener.notify(&RustddsStatus::from(&status));
+      }
+      count += 1;
+    }
+    count
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    ffi::CString,
+    sync::atomic::{AtomicI32, Ordering},
+    thread,
+    time::{Duration as StdDuration, Instant},
+  };
+
+  use super::*;
+
+  unsafe extern "C" fn count_matches(status: *const RustddsStatus, user_data: *mut c_void) {
+    let status = &*status;
+    if status.kind == RustddsStatusKind::PublicationMatched {
+      let current = &*(user_data as *const AtomicI32);
+      current.store(status.current_count, Ordering::SeqCst);
+    }
+  }
+
+  #[test]
+  fn write_and_take_through_c_interface() {
+    let name = CString::new("ffi_test_topic").unwrap();
+    let type_name = CString::new("ffi_test_type").unwrap();
+    let qos = RustddsQos {
+      reliable: true,
+      transient_local: false,
+      history_depth: 10,
+    };
+    let matched = AtomicI32::new(0);
+
+    unsafe {
+      let participant = rustdds_participant_new(0);
+      assert!(!participant.is_null());
+      let topic = rustdds_topic_new(participant, name.as_ptr(), type_name.as_ptr(), &qos);
+      assert!(!topic.is_null());
+      let writer = rustdds_writer_new(participant, topic, ptr::null());
+      let reader = rustdds_reader_new(participant, topic, &qos);
+      assert!(!writer.is_null() && !reader.is_null());
+      assert_eq!(
+        rustdds_writer_set_status_callback(
+          writer,
+          Some(count_matches),
+          &matched as *const AtomicI32 as *mut c_void
+        ),
+        RUSTDDS_OK
+      );
+
+      let payload = [1u8, 2, 3, 4];
+      assert_eq!(
+        rustdds_writer_write(writer, payload.as_ptr(), payload.len(), 0x0001),
+        RUSTDDS_OK
+      );
+      assert_eq!(
+        rustdds_writer_write(writer, ptr::null(), 1, 0x0001),
+        RUSTDDS_BAD_PARAMETER
+      );
+
+      let mut sample = RustddsSample {
+        data: ptr::null_mut(),
+        len: 0,
+        encoding: 0,
+        source_timestamp_ns: 0,
+      };
+      let started = Instant::now();
+      let mut result = RUSTDDS_NO_DATA;
+      while result == RUSTDDS_NO_DATA && started.elapsed() < StdDuration::from_secs(5) {
+        thread::sleep(StdDuration::from_millis(20));
+        result = rustdds_reader_take(reader, &mut sample);
+      }
+      assert_eq!(result, RUSTDDS_OK);
+      assert_eq!(slice::from_raw_parts(sample.data, sample.len), &payload);
+      assert_eq!(sample.encoding, 0x0001);
+      assert!(sample.source_timestamp_ns > 0);
+      rustdds_sample_release(&mut sample);
+      assert!(sample.data.is_null());
+      assert_eq!(rustdds_reader_take(reader, &mut sample), RUSTDDS_NO_DATA);
+
+      assert!(rustdds_writer_process_status(writer) > 0);
+      assert_eq!(matched.load(Ordering::SeqCst), 1);
+
+      rustdds_reader_delete(reader);
+      rustdds_writer_delete(writer);
+      rustdds_topic_delete(topic);
+      rustdds_participant_delete(participant);
+    }
+  }
+}
@@ -0,0 +1,754 @@
+//! Chunked, resumable file transfer over DDS.
+//!
+//! A [`FileSender`] offers a file on a metadata Topic and then writes its
+//! contents in chunks on a chunk Topic. A [`FileReceiver`] writes the chunks of
+//! each offered file into its directory, and moves the file into place when
+//! all chunks have been received and the checksum of the file matches.
+//!
+//! Features:
+//! * Flow control: the sender waits for the receivers to acknowledge each
+//!   window of chunks before writing more, so that a slow receiver is not
+//!   overrun and the file is never held in memory as a whole.
+//! * Integrity checking: every chunk and the complete file carry a CRC-32C.
+//!   Corrupted chunks are discarded, and they remain missing.
+//! * Resuming: the transfer id is derived from the name, size and checksum of
+//!   the file, so a receiver recognizes the same file offered again, and keeps
+//!   the chunks it already has. The missing chunks, see
+//!   [`TransferProgress::missing_chunks`], can be sent again with
+//!   [`FileSender::send_chunks`].
+//!
+//! Chunks are typically larger than a UDP datagram, so RTPS sends them in
+//! fragments.
+//!
+//! Limitations: a receiver keeps the progress of transfers in memory only, so
+//! a restarted receiver starts over. Receivers that match the sender late get
+//! the offer, but not the chunks sent before that.
+//!
+//! The receiver does not run a thread of its own. Call
+//! [`FileReceiver::process`] whenever there may be new data, e.g.
+//! periodically or when the readers are signaled.
+//!
+//! # Example
+//!
+//! ```
+//! use rustdds::{file_transfer::*, *};
+//!
+//! let participant = DomainParticipant::new(0).unwrap();
+//! let mut receiver = FileReceiver::new(&participant, std::env::temp_dir()).unwrap();
+//! let sender = FileSender::new(&participant).unwrap().chunk_size(16 * 1024);
+//!
+//! // sender.send_file("some/file.bin").unwrap();
+//!
+//! // in application main loop:
+//! for result in receiver.process() {
+//!   match result {
+//!     Ok(file) => println!("Received {:?}", file.path),
+//!     Err(e) => println!("File transfer failed: {e}"),
+//!   }
+//! }
+//! ```
+
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  fs::{self, File, OpenOptions},
+  io::{self, Read, Seek, SeekFrom, Write},
+  mem,
+  path::{Path, PathBuf},
+  time::Duration as StdDuration,
+};
+
+use bit_vec::BitVec;
+use serde::{Deserialize, Serialize};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::{
+  dds::{
+    no_key::{datareader::DataReader, datawriter::DataWriter},
+    participant::DomainParticipant,
+    qos::{policy, QosPolicies, QosPolicyBuilder},
+    result::{CreateResult, ReadError, WriteError},
+    topic::{Topic, TopicKind},
+  },
+  serialization::{CDRDeserializerAdapter, CDRSerializerAdapter},
+};
+
+/// Topic for file offers
+pub const FILE_METADATA_TOPIC_NAME: &str = "rustdds_file_metadata";
+/// Topic for file contents
+pub const FILE_CHUNK_TOPIC_NAME: &str = "rustdds_file_chunk";
+const FILE_METADATA_TYPE_NAME: &str = "rustdds::FileMetadata";
+const FILE_CHUNK_TYPE_NAME: &str = "rustdds::FileChunk";
+
+/// Default size of a chunk in bytes
+pub const DEFAULT_CHUNK_SIZE: u32 = 64 * 1024;
+/// Default number of chunks written before waiting for acknowledgments
+pub const DEFAULT_WINDOW: usize = 16;
+/// Default maximum size of a received file in bytes
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 1 << 30;
+
+// Chunks of files whose metadata has not arrived yet are kept, up to this
+// many.
+const MAX_EARLY_CHUNKS: usize = 64;
+
+/// Description of an offered file
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FileMetadata {
+  pub transfer_id: u64,
+  /// Name of the file without directories
+  pub file_name: String,
+  /// Size in bytes
+  pub size: u64,
+  pub chunk_size: u32,
+  /// CRC-32C of the file contents
+  pub crc32c: u32,
+}
+
+impl FileMetadata {
+  pub fn chunk_count(&self) -> u32 {
+    // Chunk count is checked to fit when the file is offered, and when the
+    // offer is received.
+    chunk_count(self.size, self.chunk_size) as u32
+  }
+
+  fn chunk_range(&self, index: u32) -> (u64, usize) {
+    let offset = u64::from(index) * u64::from(self.chunk_size);
+    let len = self
+      .size
+      .saturating_sub(offset)
+      .min(u64::from(self.chunk_size));
+    (offset, len as usize)
+  }
+}
+
+fn chunk_count(size: u64, chunk_size: u32) -> u64 {
+  let chunk_size = u64::from(chunk_size.max(1));
+  size / chunk_size + u64::from(size % chunk_size != 0) // rounding up
+}
+
+// The same file always gets the same id, so that an offer can be resumed.
+fn transfer_id(file_name: &str, size: u64, crc32c: u32) -> u64 {
+  let name_and_size =
+    crc32c::crc32c_append(crc32c::crc32c(file_name.as_bytes()), &size.to_le_bytes());
+  (u64::from(name_and_size) << 32) | u64::from(crc32c)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+struct FileChunk {
+  transfer_id: u64,
+  index: u32,
+  crc32c: u32,
+  data: Vec<u8>,
+}
+
+type MetadataReader = DataReader<FileMetadata, CDRDeserializerAdapter<FileMetadata>>;
+type MetadataWriter = DataWriter<FileMetadata, CDRSerializerAdapter<FileMetadata>>;
+type ChunkReader = DataReader<FileChunk, CDRDeserializerAdapter<FileChunk>>;
+type ChunkWriter = DataWriter<FileChunk, CDRSerializerAdapter<FileChunk>>;
+
+/// Failures of sending or receiving files
+#[derive(Debug, thiserror::Error)]
+pub enum FileTransferError {
+  #[error("File I/O error: {0}")]
+  Io(#[from] io::Error),
+
+  #[error("Cannot send: {0}")]
+  Write(#[from] WriteError<()>),
+
+  #[error("Cannot receive: {0}")]
+  Read(#[from] ReadError),
+
+  /// The receivers did not acknowledge the sent chunks in time.
+  #[error("Chunks were not acknowledged within {0:?}")]
+  AckTimeout(StdDuration),
+
+  /// An offered file name contains directories, or is otherwise unsafe to
+  /// use. The file is not received.
+  #[error("Unacceptable file name {0:?}")]
+  BadFileName(String),
+
+  /// An offered file is larger than the receiver accepts, see
+  /// [`FileReceiver::max_file_size`]. The file is not received.
+  #[error("Offered file {file_name:?} of {size} bytes is too large")]
+  TooLarge { file_name: String, size: u64 },
+
+  /// An offered file cannot be split into chunks as described, e.g. the chunk
+  /// size is zero. The file is not received.
+  #[error("Offered file {file_name:?} has an invalid chunk size {chunk_size}")]
+  BadChunkSize { file_name: String, chunk_size: u32 },
+
+  /// The received file does not match its checksum. Its chunks are all
+  /// missing again.
+  #[error("Received file {file_name:?} does not match its checksum")]
+  Corrupted { file_name: String },
+}
+
+// Metadata is kept for late joining receivers, chunks are not.
+fn metadata_qos() -> QosPolicies {
+  QosPolicyBuilder::new()
+    .reliability(policy::Reliability::Reliable {
+      max_blocking_time: crate::Duration::from_secs(1),
+    })
+    .durability(policy::Durability::TransientLocal)
+    .history(policy::History::KeepLast { depth: 16 })
+    .build()
+}
+
+fn chunk_qos() -> QosPolicies {
+  QosPolicyBuilder::new()
+    .reliability(policy::Reliability::Reliable {
+      max_blocking_time: crate::Duration::from_secs(1),
+    })
+    .durability(policy::Durability::Volatile)
+    .history(policy::History::KeepAll)
+    .build()
+}
+
+fn topic(
+  participant: &DomainParticipant,
+  name: &str,
+  type_name: &str,
+  qos: &QosPolicies,
+) -> CreateResult<Topic> {
+  participant.create_topic(
+    name.to_string(),
+    type_name.to_string(),
+    qos,
+    TopicKind::NoKey,
+  )
+}
+
+/// Sends files to [`FileReceiver`]s. See the
+/// [module documentation](crate::file_transfer).
+pub struct FileSender {
+  metadata_writer: MetadataWriter,
+  chunk_writer: ChunkWriter,
+  chunk_size: u32,
+  window: usize,
+  ack_timeout: StdDuration,
+}
+
+impl FileSender {
+  pub fn new(participant: &DomainParticipant) -> CreateResult<Self> {
+    let publisher = participant.create_publisher(&QosPolicies::qos_none())?;
+    let metadata_topic = topic(
+      participant,
+      FILE_METADATA_TOPIC_NAME,
+      FILE_METADATA_TYPE_NAME,
+      &metadata_qos(),
+    )?;
+    let chunk_topic = topic(
+      participant,
+      FILE_CHUNK_TOPIC_NAME,
+      FILE_CHUNK_TYPE_NAME,
+      &chunk_qos(),
+    )?;
+    Ok(Self {
+      metadata_writer: publisher.create_datawriter_no_key(&metadata_topic, None)?,
+      chunk_writer: publisher.create_datawriter_no_key(&chunk_topic, None)?,
+      chunk_size: DEFAULT_CHUNK_SIZE,
+      window: DEFAULT_WINDOW,
+      ack_timeout: StdDuration::from_secs(10),
+    })
+  }
+
+  /// Size of the chunks of files offered from now on, in bytes. Default is
+  /// [`DEFAULT_CHUNK_SIZE`].
+  #[must_use]
+  pub fn chunk_size(mut self, chunk_size: u32) -> Self {
+    self.chunk_size = chunk_size.max(1);
+    self
+  }
+
+  /// Number of chunks written before waiting for the receivers to
+  /// acknowledge them. Default is [`DEFAULT_WINDOW`].
+  #[must_use]
+  pub fn window(mut self, chunks: usize) -> Self {
+    self.window = chunks.max(1);
+    self
+  }
+
+  /// How long to wait for the acknowledgment of a window of chunks before
+  /// giving up. Default is 10 seconds.
+  #[must_use]
+  pub fn ack_timeout(mut self, timeout: StdDuration) -> Self {
+    self.ack_timeout = timeout;
+    self
+  }
+
+  /// Offers the file at `path` and sends all of it.
+  pub fn send_file(&self, path: impl AsRef<Path>) -> Result<FileMetadata, FileTransferError> {
+    let metadata = self.offer_file(&path)?;
+    self.send_chunks(path, &metadata, 0..metadata.chunk_count())?;
+    Ok(metadata)
+  }
+
+  /// Offers the file at `path` to the receivers without sending the
+  /// contents.
+  pub fn offer_file(&self, path: impl AsRef<Path>) -> Result<FileMetadata, FileTransferError> {
+    let path = path.as_ref();
+    let file_name = path
+      .file_name()
+      .map(|name| name.to_string_lossy().into_owned())
+      .ok_or_else(|| FileTransferError::BadFileName(path.display().to_string()))?;
+    let (size, crc32c) = checksum(&mut File::open(path)?)?;
+    let metadata = FileMetadata {
+      transfer_id: transfer_id(&file_name, size, crc32c),
+      file_name,
+      size,
+      chunk_size: self.chunk_size,
+      crc32c,
+    };
+    if chunk_count(size, self.chunk_size) > u64::from(u32::MAX) {
+      return Err(FileTransferError::Io(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "Too many chunks, use a larger chunk size",
+      )));
+    }
+    debug!("Offering file {metadata:?}");
+    self
+      .metadata_writer
+      .write(metadata.clone(), None)
+      .map_err(WriteError::forget_data)?;
+    Ok(metadata)
+  }
+
+  /// Sends the listed chunks of an offered file, e.g. the missing ones to
+  /// resume a transfer.
+  pub fn send_chunks(
+    &self,
+    path: impl AsRef<Path>,
+    metadata: &FileMetadata,
+    chunks: impl IntoIterator<Item = u32>,
+  ) -> Result<(), FileTransferError> {
+    let mut file = File::open(path)?;
+    let mut unacknowledged = 0;
+    for index in chunks {
+      if index >= metadata.chunk_count() {
+        continue;
+      }
+      let (offset, len) = metadata.chunk_range(index);
+      let mut data = vec![0; len];
+      file.seek(SeekFrom::Start(offset))?;
+      file.read_exact(&mut data)?;
+      let chunk = FileChunk {
+        transfer_id: metadata.transfer_id,
+        index,
+        crc32c: crc32c::crc32c(&data),
+        data,
+      };
+      self
+        .chunk_writer
+        .write(chunk, None)
+        .map_err(WriteError::forget_data)?;
+      unacknowledged += 1;
+      if unacknowledged >= self.window {
+        self.wait_for_acknowledgments()?;
+        unacknowledged = 0;
+      }
+    }
+    self.wait_for_acknowledgments()
+  }
+
+  fn wait_for_acknowledgments(&self) -> Result<(), FileTransferError> {
+    if self
+      .chunk_writer
+      .wait_for_acknowledgments(self.ack_timeout)
+      .map_err(WriteError::forget_data)?
+    {
+      Ok(())
+    } else {
+      Err(FileTransferError::AckTimeout(self.ack_timeout))
+    }
+  }
+}
+
+// Size and CRC-32C of the contents
+fn checksum(file: &mut File) -> io::Result<(u64, u32)> {
+  let mut buffer = vec![0; 64 * 1024];
+  let (mut size, mut crc32c) = (0, 0);
+  loop {
+    match file.read(&mut buffer)? {
+      0 => return Ok((size, crc32c)),
+      n => {
+        size += n as u64;
+        crc32c = crc32c::crc32c_append(crc32c, &buffer[..n]);
+      }
+    }
+  }
+}
+
+// Accepts only plain file names, so that a sender cannot write outside the
+// receiver's directory.
+fn safe_file_name(file_name: &str) -> Option<&str> {
+  let name = Path::new(file_name).file_name()?.to_str()?;
+  (name == file_name && !name.starts_with('.')).then_some(name)
+}
+
+/// A file received completely by a [`FileReceiver`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReceivedFile {
+  pub metadata: FileMetadata,
+  pub path: PathBuf,
+}
+
+/// State of a file being received
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransferProgress {
+  pub metadata: FileMetadata,
+  /// Indices of the chunks not yet received
+  pub missing_chunks: Vec<u32>,
+}
+
+struct IncomingTransfer {
+  metadata: FileMetadata,
+  // The file is written here, and renamed when complete.
+  part_path: PathBuf,
+  path: PathBuf,
+  file: File,
+  received: BitVec,
+}
+
+impl IncomingTransfer {
+  fn progress(&self) -> TransferProgress {
+    TransferProgress {
+      metadata: self.metadata.clone(),
+      missing_chunks: (0..self.metadata.chunk_count())
+        .filter(|i| !self.received[*i as usize])
+        .collect(),
+    }
+  }
+}
+
+/// Receives files from [`FileSender`]s into a directory. See the
+/// [module documentation](crate::file_transfer).
+pub struct FileReceiver {
+  metadata_reader: MetadataReader,
+  chunk_reader: ChunkReader,
+  directory: PathBuf,
+  max_file_size: u64,
+  transfers: BTreeMap<u64, IncomingTransfer>,
+  // Transfers already done. Offering these again does nothing.
+  completed: BTreeSet<u64>,
+  early_chunks: Vec<FileChunk>,
+}
+
+impl FileReceiver {
+  pub fn new(participant: &DomainParticipant, directory: impl Into<PathBuf>) -> CreateResult<Self> {
+    let subscriber = participant.create_subscriber(&QosPolicies::qos_none())?;
+    let metadata_topic = topic(
+      participant,
+      FILE_METADATA_TOPIC_NAME,
+      FILE_METADATA_TYPE_NAME,
+      &metadata_qos(),
+    )?;
+    let chunk_topic = topic(
+      participant,
+      FILE_CHUNK_TOPIC_NAME,
+      FILE_CHUNK_TYPE_NAME,
+      &chunk_qos(),
+    )?;
+    Ok(Self {
+      metadata_reader: subscriber.create_datareader_no_key(&metadata_topic, None)?,
+      chunk_reader: subscriber.create_datareader_no_key(&chunk_topic, None)?,
+      directory: directory.into(),
+      max_file_size: DEFAULT_MAX_FILE_SIZE,
+      transfers: BTreeMap::new(),
+      completed: BTreeSet::new(),
+      early_chunks: Vec::new(),
+    })
+  }
+
+  /// Largest file that is accepted, in bytes. Larger offered files fail with
+  /// [`FileTransferError::TooLarge`]. Default is [`DEFAULT_MAX_FILE_SIZE`].
+  #[must_use]
+  pub fn max_file_size(mut self, size: u64) -> Self {
+    self.max_file_size = size;
+    self
+  }
+
+  /// Handles the offers and chunks received so far. Gives the files that
+  /// were completed, and the failures.
+  pub fn process(&mut self) -> Vec<Result<ReceivedFile, FileTransferError>> {
+    let mut results = Vec::new();
+    loop {
+      match self.metadata_reader.take_next_sample() {
+        Ok(Some(sample)) => {
+          if let Err(e) = self.offered(sample.into_value(), &mut results) {
+            results.push(Err(e));
+          }
+        }
+        Ok(None) => break,
+        Err(e) => {
+          results.push(Err(e.into()));
+          break;
+        }
+      }
+    }
+    for chunk in mem::take(&mut self.early_chunks) {
+      self.chunk_received(chunk, &mut results);
+    }
+    loop {
+      match self.chunk_reader.take_next_sample() {
+        Ok(Some(sample)) => self.chunk_received(sample.into_value(), &mut results),
+        Ok(None) => break,
+        Err(e) => {
+          results.push(Err(e.into()));
+          break;
+        }
+      }
+    }
+    results
+  }
+
+  /// Progress of an incomplete transfer
+  pub fn progress(&self, transfer_id: u64) -> Option<TransferProgress> {
+    self
+      .transfers
+      .get(&transfer_id)
+      .map(IncomingTransfer::progress)
+  }
+
+  /// Progress of all incomplete transfers
+  pub fn transfers(&self) -> Vec<TransferProgress> {
+    self
+      .transfers
+      .values()
+      .map(IncomingTransfer::progress)
+      .collect()
+  }
+
+  fn offered(
+    &mut self,
+    metadata: FileMetadata,
+    results: &mut Vec<Result<ReceivedFile, FileTransferError>>,
+  ) -> Result<(), FileTransferError> {
+    let transfer_id = metadata.transfer_id;
+    if self.transfers.contains_key(&transfer_id) || self.completed.contains(&transfer_id) {
+      debug!("File {:?} offered again", metadata.file_name);
+      return Ok(());
+    }
+    let file_name = safe_file_name(&metadata.file_name)
+      .ok_or_else(|| FileTransferError::BadFileName(metadata.file_name.clone()))?;
+    // The offer comes from anyone on the network. Check it before allocating
+    // anything for it.
+    if metadata.size > self.max_file_size {
+      return Err(FileTransferError::TooLarge {
+        file_name: metadata.file_name,
+        size: metadata.size,
+      });
+    }
+    if metadata.chunk_size == 0
+      || chunk_count(metadata.size, metadata.chunk_size) > u64::from(u32::MAX)
+    {
+      return Err(FileTransferError::BadChunkSize {
+        file_name: metadata.file_name,
+        chunk_size: metadata.chunk_size,
+      });
+    }
+    let path = self.directory.join(file_name);
+    let part_path = self.directory.join(format!("{file_name}.part"));
+    let file = OpenOptions::new()
+      .read(true)
+      .write(true)
+      .create(true)
+      .truncate(true)
+      .open(&part_path)?;
+    file.set_len(metadata.size)?;
+    debug!("Receiving file {metadata:?} to {part_path:?}");
+    self.transfers.insert(
+      transfer_id,
+      IncomingTransfer {
+        received: BitVec::from_elem(metadata.chunk_count() as usize, false),
+        metadata,
+        part_path,
+        path,
+        file,
+      },
+    );
+    // An empty file has no chunks to wait for.
+    self.complete_if_received(transfer_id, results);
+    Ok(())
+  }
+
+  fn chunk_received(
+    &mut self,
+    chunk: FileChunk,
+    results: &mut Vec<Result<ReceivedFile, FileTransferError>>,
+  ) {
+    let transfer_id = chunk.transfer_id;
+    let Some(transfer) = self.transfers.get_mut(&transfer_id) else {
+      if !self.completed.contains(&transfer_id) && self.early_chunks.len() < MAX_EARLY_CHUNKS {
+        self.early_chunks.push(chunk);
+      }
+      return;
+    };
+    if transfer.received.get(chunk.index as usize) != Some(false) {
+      return; // already have it, or out of range
+    }
+    let (offset, len) = transfer.metadata.chunk_range(chunk.index);
+    if chunk.data.len() != len || crc32c::crc32c(&chunk.data) != chunk.crc32c {
+      warn!(
+        "Discarding corrupted chunk {} of file {:?}",
+        chunk.index, transfer.metadata.file_name
+      );
+      return;
+    }
+    let written = transfer
+      .file
+      .seek(SeekFrom::Start(offset))
+      .and_then(|_| transfer.file.write_all(&chunk.data));
+    match written {
+      Ok(()) => transfer.received.set(chunk.index as usize, true),
+      Err(e) => results.push(Err(e.into())),
+    }
+    self.complete_if_received(transfer_id, results);
+  }
+
+  fn complete_if_received(
+    &mut self,
+    transfer_id: u64,
+    results: &mut Vec<Result<ReceivedFile, FileTransferError>>,
+  ) {
+    let Some(transfer) = self.transfers.get_mut(&transfer_id) else {
+      return;
+    };
+    if !transfer.received.all() {
+      return;
+    }
+    let verified = transfer
+      .file
+      .seek(SeekFrom::Start(0))
+      .and_then(|_| checksum(&mut transfer.file));
+    match verified {
+      Ok((_, crc32c)) if crc32c == transfer.metadata.crc32c => {}
+      Ok(_) => {
+        transfer.received.clear();
+        results.push(Err(FileTransferError::Corrupted {
+          file_name: transfer.metadata.file_name.clone(),
+        }));
+        return;
+      }
+      Err(e) => {
+        results.push(Err(e.into()));
+        return;
+      }
+    }
+    let Some(transfer) = self.transfers.remove(&transfer_id) else {
+      return;
+    };
+    drop(transfer.file);
+    match fs::rename(&transfer.part_path, &transfer.path) {
+      Ok(()) => {
+        info!("Received file {:?}", transfer.path);
+        self.completed.insert(transfer_id);
+        results.push(Ok(ReceivedFile {
+          metadata: transfer.metadata,
+          path: transfer.path,
+        }));
+      }
+      Err(e) => results.push(Err(e.into())),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Instant;
+
+  use super::*;
+
+  #[test]
+  fn file_names_are_sanitized() {
+    assert_eq!(safe_file_name("data.bin"), Some("data.bin"));
+    assert_eq!(safe_file_name("../data.bin"), None);
+    assert_eq!(safe_file_name("/etc/passwd"), None);
+    assert_eq!(safe_file_name("dir/data.bin"), None);
+    assert_eq!(safe_file_name(".."), None);
+    assert_eq!(safe_file_name(""), None);
+  }
+
+  #[test]
+  fn receiver_rejects_unacceptable_offers() {
+    let directory =
+      std::env::temp_dir().join(format!("rustdds_file_offers_{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let participant = DomainParticipant::new(0).unwrap();
+    let mut receiver = FileReceiver::new(&participant, &directory)
+      .unwrap()
+      .max_file_size(1 << 40);
+    let offer = |size, chunk_size| FileMetadata {
+      transfer_id: 1,
+      file_name: "data.bin".to_string(),
+      size,
+      chunk_size,
+      crc32c: 0,
+    };
+    let mut results = Vec::new();
+
+    let result = receiver.offered(offer((1 << 40) + 1, 1 << 20), &mut results);
+    assert!(matches!(result, Err(FileTransferError::TooLarge { .. })));
+    let result = receiver.offered(offer(1 << 20, 0), &mut results);
+    assert!(matches!(
+      result,
+      Err(FileTransferError::BadChunkSize { .. })
+    ));
+    // Chunk count would not fit in u32
+    let result = receiver.offered(offer(1 << 40, 1), &mut results);
+    assert!(matches!(
+      result,
+      Err(FileTransferError::BadChunkSize { .. })
+    ));
+    assert!(receiver.transfers().is_empty());
+    assert!(!directory.join("data.bin.part").exists());
+
+    fs::remove_dir_all(&directory).unwrap();
+  }
+
+  #[test]
+  fn transfer_file_to_self() {
+    let directory =
+      std::env::temp_dir().join(format!("rustdds_file_transfer_{}", std::process::id()));
+    let receive_directory = directory.join("received");
+    fs::create_dir_all(&receive_directory).unwrap();
+    let source = directory.join("data.bin");
+    let contents: Vec<u8> = (0..100_000_u32).map(|i| (i * 7 % 251) as u8).collect();
+    fs::write(&source, &contents).unwrap();
+
+    let participant = DomainParticipant::new(0).unwrap();
+    let mut receiver = FileReceiver::new(&participant, &receive_directory).unwrap();
+    let sender = FileSender::new(&participant)
+      .unwrap()
+      .chunk_size(8 * 1024)
+      .window(4);
+
+    // Chunks sent before the endpoints matched are lost, but the offer is
+    // not. Resume until done.
+    let metadata = sender.send_file(&source).unwrap();
+    assert_eq!(metadata.chunk_count(), 13);
+    let deadline = Instant::now() + StdDuration::from_secs(10);
+    let received = loop {
+      assert!(Instant::now() < deadline, "File was not received");
+      std::thread::sleep(StdDuration::from_millis(200));
+      let mut results = receiver.process();
+      if let Some(result) = results.pop() {
+        break result.unwrap();
+      }
+      if let Some(progress) = receiver.progress(metadata.transfer_id) {
+        sender
+          .send_chunks(&source, &metadata, progress.missing_chunks)
+          .unwrap();
+      }
+    };
+    assert_eq!(received.metadata, metadata);
+    assert_eq!(fs::read(&received.path).unwrap(), contents);
+    assert!(receiver.transfers().is_empty());
+
+    // The same file again is not received twice.
+    sender.send_file(&source).unwrap();
+    std::thread::sleep(StdDuration::from_millis(200));
+    assert!(receiver.process().is_empty());
+
+    fs::remove_dir_all(&directory).unwrap();
+  }
+}
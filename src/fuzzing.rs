@@ -0,0 +1,191 @@
+//! Deserialization entry points for fuzz testing.
+//!
+//! These functions run the same parsing code that is applied to data received
+//! from the network, but without any side effects: no DomainParticipant,
+//! sockets, or threads are involved. They take plain byte slices, so that they
+//! can be called directly from e.g. `cargo fuzz` harnesses. All of them must
+//! return an error, rather than panic, on malformed input. A panic is a bug.
+//!
+//! This module is available only with the feature "fuzzing".
+//!
+//! A fuzz corpus can be replayed as a regular test by setting the environment
+//! variable `RUSTDDS_FUZZ_CORPUS` to a directory that has subdirectories named
+//! after the functions below, e.g. `message`, `submessage`, or `data`, and
+//! running `cargo test --features fuzzing replay_fuzz_corpus`.
+
+use std::{fs, io, path::Path};
+
+use bytes::Bytes;
+use enumflags2::BitFlags;
+use speedy::Readable;
+
+use crate::{
+  messages::submessages::{
+    elements::parameter_list::ParameterList,
+    submessage_flag::{endianness_flag, DATAFRAG_Flags, DATA_Flags},
+    submessages::{
+      AckNack, Data, DataFrag, Gap, Heartbeat, HeartbeatFrag, InfoDestination, InfoReply,
+      InfoSource, NackFrag,
+    },
+  },
+  rtps::{Message, Submessage},
+  structure::time::Timestamp,
+};
+
+/// Deserialize a complete RTPS message, i.e. UDP datagram payload, including
+/// the RTPS header. Returns the number of submessages parsed.
+pub fn message(bytes: &[u8]) -> io::Result<usize> {
+  Message::read_from_buffer_with_skipped(&Bytes::copy_from_slice(bytes))
+    .map(|(m, _skipped)| m.submessages.len())
+}
+
+/// Deserialize a single submessage, starting from the submessage header.
+/// Returns `false` if the submessage was valid, but skipped, e.g. PAD or an
+/// unknown kind.
+pub fn submessage(bytes: &[u8]) -> io::Result<bool> {
+  let mut buffer = Bytes::copy_from_slice(bytes);
+  Submessage::read_from_buffer(&mut buffer).map(|s| s.is_some())
+}
+
+/// Deserialize a ParameterList, e.g. inline QoS.
+pub fn parameter_list(bytes: &[u8], little_endian: bool) -> io::Result<()> {
+  let endianness = if little_endian {
+    speedy::Endianness::LittleEndian
+  } else {
+    speedy::Endianness::BigEndian
+  };
+  ParameterList::read_from_buffer_with_ctx(endianness, bytes)
+    .map(|_| ())
+    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+// The submessage-specific functions below take the submessage contents after
+// the submessage header, and the flags byte from the header. Flag bit 0 selects
+// endianness.
+
+pub fn data(bytes: &[u8], flags: u8) -> io::Result<()> {
+  Data::deserialize_data(
+    &Bytes::copy_from_slice(bytes),
+    BitFlags::<DATA_Flags>::from_bits_truncate(flags),
+  )
+  .map(|_| ())
+}
+
+pub fn data_frag(bytes: &[u8], flags: u8) -> io::Result<()> {
+  DataFrag::deserialize(
+    &Bytes::copy_from_slice(bytes),
+    BitFlags::<DATAFRAG_Flags>::from_bits_truncate(flags),
+  )
+  .map(|_| ())
+}
+
+macro_rules! speedy_submessage_entry_point {
+  ($fn_name:ident, $type:ty) => {
+    pub fn $fn_name(bytes: &[u8], flags: u8) -> io::Result<()> {
+      <$type>::read_from_buffer_with_ctx(endianness_flag(flags), bytes)
+        .map(|_| ())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+  };
+}
+
+speedy_submessage_entry_point!(gap, Gap);
+speedy_submessage_entry_point!(heartbeat, Heartbeat);
+speedy_submessage_entry_point!(heartbeat_frag, HeartbeatFrag);
+speedy_submessage_entry_point!(acknack, AckNack);
+speedy_submessage_entry_point!(nack_frag, NackFrag);
+speedy_submessage_entry_point!(info_destination, InfoDestination);
+speedy_submessage_entry_point!(info_source, InfoSource);
+speedy_submessage_entry_point!(info_reply, InfoReply);
+speedy_submessage_entry_point!(info_timestamp, Timestamp);
+
+/// Run `f` on the contents of every file in directory `dir`. Returns the number
+/// of files processed.
+pub fn replay_corpus_dir(dir: &Path, mut f: impl FnMut(&[u8])) -> io::Result<usize> {
+  let mut count = 0;
+  for entry in fs::read_dir(dir)? {
+    let path = entry?.path();
+    if path.is_file() {
+      f(&fs::read(&path)?);
+      count += 1;
+    }
+  }
+  Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+
+  use log::info;
+
+  use super::*;
+
+  // Corpus directory name and the entry point that consumes its inputs
+  type FuzzTarget = (&'static str, Box<dyn Fn(&[u8])>);
+
+  // For submessage-specific corpora, the first byte of each input is used as
+  // flags, like a cargo-fuzz harness would do.
+  fn with_flags(input: &[u8], f: impl Fn(&[u8], u8) -> io::Result<()>) {
+    if let Some((flags, rest)) = input.split_first() {
+      let _ = f(rest, *flags);
+    }
+  }
+
+  #[test]
+  fn replay_fuzz_corpus() {
+    let corpus = match std::env::var("RUSTDDS_FUZZ_CORPUS") {
+      Ok(dir) => PathBuf::from(dir),
+      Err(_) => return, // Nothing to replay
+    };
+
+    let targets: Vec<FuzzTarget> = vec![
+      ("message", Box::new(|b| drop(message(b)))),
+      ("submessage", Box::new(|b| drop(submessage(b)))),
+      (
+        "parameter_list",
+        Box::new(|b| drop(parameter_list(b, true))),
+      ),
+      ("data", Box::new(|b| with_flags(b, data))),
+      ("data_frag", Box::new(|b| with_flags(b, data_frag))),
+      ("gap", Box::new(|b| with_flags(b, gap))),
+      ("heartbeat", Box::new(|b| with_flags(b, heartbeat))),
+      (
+        "heartbeat_frag",
+        Box::new(|b| with_flags(b, heartbeat_frag)),
+      ),
+      ("acknack", Box::new(|b| with_flags(b, acknack))),
+      ("nack_frag", Box::new(|b| with_flags(b, nack_frag))),
+      (
+        "info_destination",
+        Box::new(|b| with_flags(b, info_destination)),
+      ),
+      ("info_source", Box::new(|b| with_flags(b, info_source))),
+      ("info_reply", Box::new(|b| with_flags(b, info_reply))),
+      (
+        "info_timestamp",
+        Box::new(|b| with_flags(b, info_timestamp)),
+      ),
+    ];
+
+    for (name, f) in targets {
+      let dir = corpus.join(name);
+      if dir.is_dir() {
+        let count = replay_corpus_dir(&dir, f).unwrap();
+        info!("Replayed {count} inputs for {name}");
+      }
+    }
+  }
+
+  #[test]
+  fn fuzz_entry_points_reject_garbage() {
+    // Too short to have even a header
+    assert!(message(b"RTPS").is_err());
+    assert!(submessage(&[0x15, 0x01]).is_err());
+    // Header claims more content than there is
+    assert!(submessage(&[0x15, 0x01, 0xff, 0x00, 0x00]).is_err());
+    assert!(heartbeat(&[0x00; 5], 0x01).is_err());
+    // Missing sentinel
+    assert!(parameter_list(&[0x70, 0x00, 0x04, 0x00], true).is_err());
+  }
+}
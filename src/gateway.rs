@@ -0,0 +1,681 @@
+//! Topic gateway: route selected Topics between two DomainParticipants.
+//!
+//! The two participants can be in different domains, or configured with
+//! different transports or security settings. The gateway creates a
+//! DataReader on the source side and a DataWriter on the destination side for
+//! each routed Topic, and copies samples across without deserializing them.
+//! The serialized payload and its encoding are forwarded as they are, so the
+//! gateway does not need to know the data types.
+//!
+//! Features:
+//! * QoS mapping: the reader and writer of a route can have different QoS, e.g.
+//!   to convert a best-effort source into a reliable destination.
+//! * Loop prevention: samples written by the gateway's own DataWriters are
+//!   never forwarded again, so routing a Topic in both directions is safe.
+//! * Rate limiting: a route can be limited to a maximum number of samples per
+//!   second. Excess samples are dropped.
+//!
+//! The endpoints of a route have the [`TopicKind`] of the route. On keyed
+//! Topics, the key hashes, disposes, and unregisters are forwarded, too.
+//!
+//! Limitations: loops formed by several gateways are not detected.
+//!
+//! The gateway does not run a thread of its own. Call
+//! [`TopicGateway::process`] whenever there may be new data, e.g.
+//! periodically or when the readers are signaled.
+//!
+//! # Example
+//!
+//! ```
+//! use rustdds::{gateway::*, *};
+//!
+//! let domain_0 = DomainParticipant::new(0).unwrap();
+//! let domain_1 = DomainParticipant::new(1).unwrap();
+//! let mut gateway = TopicGateway::new(&domain_0, &domain_1).unwrap();
+//!
+//! let qos = QosPolicyBuilder::new().build();
+//! let route =
+//!   GatewayRoute::new("Square", "ShapeType", TopicKind::WithKey, RouteDirection::AToB, qos)
+//!     .max_samples_per_second(100);
+//! gateway.add_route(&route).unwrap();
+//!
+//! // in application main loop:
+//! let forwarded = gateway.process();
+//! ```
+
+use std::{collections::BTreeSet, convert::Infallible, time::Instant};
+
+use bytes::{Bytes, BytesMut};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::{
+  dds::{
+    adapters::no_key::{DeserializerAdapter, SerializerAdapter},
+    no_key::wrappers::{DAWrapper, NoKeyWrapper, SAWrapper},
+    participant::DomainParticipant,
+    pubsub::{Publisher, Subscriber},
+    qos::QosPolicies,
+    result::CreateResult,
+    topic::{Topic, TopicKind},
+    with_key::{
+      datawriter::{DataWriter, WriteOptionsBuilder},
+      simpledatareader::SimpleDataReader,
+    },
+  },
+  structure::{entity::RTPSEntity, guid::GUID},
+  RepresentationIdentifier,
+};
+
+/// A serialized sample as it was received, without deserialization.
+#[derive(Debug, Clone)]
+pub struct RawSample {
+  pub encoding: RepresentationIdentifier,
+  pub value: Bytes,
+}
+
+/// Adapter for reading and writing [`RawSample`]s. Accepts every encoding.
+///
+/// The payload is not copied, so this also suits NO_KEY Topics of large
+/// binary blobs, e.g. video frames. Writing only takes a reference to the
+/// application buffer: fragments are slices of it, and they are sent with
+/// scatter/gather I/O. On the reader side, a sample that arrived in one
+/// datagram is handed out as a slice of the receive buffer, and a fragmented
+/// sample as its reassembly buffer.
+///
+/// [`DataWriter::write`] labels the payload CDR_LE, whatever the `encoding` of
+/// the sample.
+///
+/// # Example
+///
+/// ```
+/// use bytes::Bytes;
+/// use rustdds::{gateway::*, *};
+///
+/// let domain_participant = DomainParticipant::new(0).unwrap();
+/// let qos = QosPolicyBuilder::new().build();
+/// let publisher = domain_participant.create_publisher(&qos).unwrap();
+/// let topic = domain_participant
+///   .create_topic(
+///     "video".to_string(),
+///     "Frame".to_string(),
+///     &qos,
+///     TopicKind::NoKey,
+///   )
+///   .unwrap();
+/// let writer = publisher
+///   .create_datawriter_no_key::<RawSample, RawSampleAdapter>(&topic, None)
+///   .unwrap();
+///
+/// let frame = RawSample {
+///   encoding: RepresentationIdentifier::CDR_LE,
+///   value: Bytes::from(vec![0u8; 1_000_000]),
+/// };
+/// writer.write(frame, None).unwrap();
+/// ```
+pub struct RawSampleAdapter {}
+
+const ALL_ENCODINGS: [RepresentationIdentifier; 11] = [
+  RepresentationIdentifier::CDR_BE,
+  RepresentationIdentifier::CDR_LE,
+  RepresentationIdentifier::PL_CDR_BE,
+  RepresentationIdentifier::PL_CDR_LE,
+  RepresentationIdentifier::CDR2_BE,
+  RepresentationIdentifier::CDR2_LE,
+  RepresentationIdentifier::PL_CDR2_BE,
+  RepresentationIdentifier::PL_CDR2_LE,
+  RepresentationIdentifier::D_CDR_BE,
+  RepresentationIdentifier::D_CDR_LE,
+  RepresentationIdentifier::XML,
+];
+
+impl DeserializerAdapter<RawSample> for RawSampleAdapter {
+  type Error = Infallible;
+
+  fn supported_encodings() -> &'static [RepresentationIdentifier] {
+    &ALL_ENCODINGS
+  }
+
+  fn from_bytes(
+    input_bytes: &[u8],
+    encoding: RepresentationIdentifier,
+  ) -> Result<RawSample, Self::Error> {
+    Ok(RawSample {
+      encoding,
+      value: Bytes::copy_from_slice(input_bytes),
+    })
+  }
+
+  fn from_vec_bytes(
+    input_vec_bytes: &[Bytes],
+    encoding: RepresentationIdentifier,
+  ) -> Result<RawSample, Self::Error> {
+    let value = match input_vec_bytes {
+      [single] => single.clone(), // reference count only
+      _ => {
+        let total_len = input_vec_bytes.iter().map(Bytes::len).sum();
+        let mut payload = BytesMut::with_capacity(total_len);
+        for b in input_vec_bytes {
+          payload.extend_from_slice(b);
+        }
+        payload.freeze()
+      }
+    };
+    Ok(RawSample { encoding, value })
+  }
+}
+
+impl SerializerAdapter<RawSample> for RawSampleAdapter {
+  type Error = Infallible;
+
+  // Only a default. The gateway writes each sample with its original
+  // encoding.
+  fn output_encoding() -> RepresentationIdentifier {
+    RepresentationIdentifier::CDR_LE
+  }
+
+  fn to_bytes(value: &RawSample) -> Result<Bytes, Self::Error> {
+    Ok(value.value.clone())
+  }
+}
+
+/// Which way samples flow on a route. "A" and "B" are the first and second
+/// participant given to [`TopicGateway::new`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteDirection {
+  AToB,
+  BToA,
+  Both,
+}
+
+/// Configuration of one routed Topic.
+#[derive(Debug, Clone)]
+pub struct GatewayRoute {
+  topic_name: String,
+  type_name: String,
+  topic_kind: TopicKind,
+  direction: RouteDirection,
+  topic_qos: QosPolicies,
+  reader_qos: Option<QosPolicies>,
+  writer_qos: Option<QosPolicies>,
+  max_samples_per_second: Option<u32>,
+}
+
+impl GatewayRoute {
+  /// Route Topic `topic_name`. The Topic is created on both participants with
+  /// `topic_qos`, which is also the default QoS of the reader and writer.
+  pub fn new(
+    topic_name: &str,
+    type_name: &str,
+    topic_kind: TopicKind,
+    direction: RouteDirection,
+    topic_qos: QosPolicies,
+  ) -> Self {
+    Self {
+      topic_name: topic_name.to_string(),
+      type_name: type_name.to_string(),
+      topic_kind,
+      direction,
+      topic_qos,
+      reader_qos: None,
+      writer_qos: None,
+      max_samples_per_second: None,
+    }
+  }
+
+  /// QoS of the DataReader on the source side. Modifies the Topic QoS.
+  pub fn reader_qos(mut self, qos: QosPolicies) -> Self {
+    self.reader_qos = Some(qos);
+    self
+  }
+
+  /// QoS of the DataWriter on the destination side. Modifies the Topic QoS.
+  pub fn writer_qos(mut self, qos: QosPolicies) -> Self {
+    self.writer_qos = Some(qos);
+    self
+  }
+
+  /// Forward at most this many samples per second in each direction. Samples
+  /// exceeding the limit are dropped. Short bursts up to one second worth of
+  /// samples are allowed.
+  pub fn max_samples_per_second(mut self, rate: u32) -> Self {
+    self.max_samples_per_second = Some(rate);
+    self
+  }
+
+  pub fn topic_name(&self) -> &str {
+    &self.topic_name
+  }
+
+  pub fn direction(&self) -> RouteDirection {
+    self.direction
+  }
+}
+
+/// Per-direction counters of a route.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RouteStatistics {
+  /// Samples written to the destination.
+  pub forwarded: u64,
+  /// Samples dropped by the rate limit.
+  pub rate_limited: u64,
+  /// Samples that were the gateway's own output, i.e. would have looped.
+  pub loops_prevented: u64,
+  /// Samples that the destination DataWriter did not accept.
+  pub write_failed: u64,
+}
+
+// Token bucket
+struct RateLimiter {
+  rate: f64,
+  tokens: f64,
+  last_refill: Instant,
+}
+
+impl RateLimiter {
+  fn new(samples_per_second: u32) -> Self {
+    let rate = f64::from(samples_per_second);
+    Self {
+      rate,
+      tokens: rate,
+      last_refill: Instant::now(),
+    }
+  }
+
+  fn try_acquire(&mut self, now: Instant) -> bool {
+    let elapsed = now.saturating_duration_since(self.last_refill);
+    self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate).min(self.rate);
+    self.last_refill = now;
+    if self.tokens >= 1.0 {
+      self.tokens -= 1.0;
+      true
+    } else {
+      false
+    }
+  }
+}
+
+// Samples forwarded per route and direction in one call of process()
+const PROCESS_BATCH_SIZE: usize = 256;
+
+// One direction of a route
+struct Pipe {
+  topic_name: String,
+  direction: RouteDirection, // AToB or BToA
+  reader: SimpleDataReader<NoKeyWrapper<RawSample>, DAWrapper<RawSampleAdapter>>,
+  writer: DataWriter<NoKeyWrapper<RawSample>, SAWrapper<RawSampleAdapter>>,
+  rate_limiter: Option<RateLimiter>,
+  statistics: RouteStatistics,
+}
+
+struct Side {
+  participant: DomainParticipant,
+  publisher: Publisher,
+  subscriber: Subscriber,
+}
+
+impl Side {
+  fn new(participant: &DomainParticipant) -> CreateResult<Self> {
+    let qos = QosPolicies::qos_none();
+    Ok(Self {
+      participant: participant.clone(),
+      publisher: participant.create_publisher(&qos)?,
+      subscriber: participant.create_subscriber(&qos)?,
+    })
+  }
+
+  fn topic(&self, route: &GatewayRoute) -> CreateResult<Topic> {
+    self.participant.create_topic(
+      route.topic_name.clone(),
+      route.type_name.clone(),
+      &route.topic_qos,
+      route.topic_kind,
+    )
+  }
+}
+
+/// Routes Topics between two DomainParticipants. See the
+/// [module documentation](crate::gateway).
+pub struct TopicGateway {
+  a: Side,
+  b: Side,
+  pipes: Vec<Pipe>,
+  own_writers: BTreeSet<GUID>,
+}
+
+impl TopicGateway {
+  pub fn new(
+    participant_a: &DomainParticipant,
+    participant_b: &DomainParticipant,
+  ) -> CreateResult<Self> {
+    Ok(Self {
+      a: Side::new(participant_a)?,
+      b: Side::new(participant_b)?,
+      pipes: Vec::new(),
+      own_writers: BTreeSet::new(),
+    })
+  }
+
+  /// Start routing a Topic. Creates the necessary Topics, DataReaders, and
+  /// DataWriters.
+  pub fn add_route(&mut self, route: &GatewayRoute) -> CreateResult<()> {
+    if matches!(route.direction, RouteDirection::AToB | RouteDirection::Both) {
+      let pipe = Self::create_pipe(&self.a, &self.b, RouteDirection::AToB, route)?;
+      self.own_writers.insert(pipe.writer.guid());
+      self.pipes.push(pipe);
+    }
+    if matches!(route.direction, RouteDirection::BToA | RouteDirection::Both) {
+      let pipe = Self::create_pipe(&self.b, &self.a, RouteDirection::BToA, route)?;
+      self.own_writers.insert(pipe.writer.guid());
+      self.pipes.push(pipe);
+    }
+    Ok(())
+  }
+
+  fn create_pipe(
+    source: &Side,
+    destination: &Side,
+    direction: RouteDirection,
+    route: &GatewayRoute,
+  ) -> CreateResult<Pipe> {
+    let reader_qos = route
+      .reader_qos
+      .as_ref()
+      .map_or_else(|| route.topic_qos.clone(), |q| route.topic_qos.modify_by(q));
+    let writer_qos = route
+      .writer_qos
+      .as_ref()
+      .map_or_else(|| route.topic_qos.clone(), |q| route.topic_qos.modify_by(q));
+
+    let reader = source
+      .subscriber
+      .create_raw_simple_datareader::<RawSample, RawSampleAdapter>(
+        &source.topic(route)?,
+        Some(reader_qos),
+      )?;
+    let writer = destination
+      .publisher
+      .create_raw_datawriter::<RawSample, RawSampleAdapter>(
+        &destination.topic(route)?,
+        Some(writer_qos),
+      )?;
+
+    Ok(Pipe {
+      topic_name: route.topic_name.clone(),
+      direction,
+      reader,
+      writer,
+      rate_limiter: route.max_samples_per_second.map(RateLimiter::new),
+      statistics: RouteStatistics::default(),
+    })
+  }
+
+  /// Forward samples that are currently available. Returns the number of
+  /// samples forwarded.
+  ///
+  /// At most 256 samples are taken per route and direction, so that one call
+  /// does not take arbitrarily long. Samples left over are taken by the next
+  /// call.
+  pub fn process(&mut self) -> usize {
+    let mut forwarded = 0;
+    for pipe in self.pipes.iter_mut() {
+      pipe.reader.drain_read_notifications();
+      let changes = match pipe.reader.try_take_undecoded_changes(PROCESS_BATCH_SIZE) {
+        Ok(changes) => changes,
+        Err(e) => {
+          warn!("Gateway cannot read topic {}: {e:?}", pipe.topic_name);
+          continue;
+        }
+      };
+      let now = Instant::now();
+      for cc in changes {
+        if self.own_writers.contains(&cc.writer_guid) {
+          pipe.statistics.loops_prevented += 1;
+          continue;
+        }
+        if let Some(limiter) = pipe.rate_limiter.as_mut() {
+          if !limiter.try_acquire(now) {
+            pipe.statistics.rate_limited += 1;
+            continue;
+          }
+        }
+        let mut options = WriteOptionsBuilder::new();
+        if let Some(ts) = cc.write_options.source_timestamp() {
+          options = options.source_timestamp(ts);
+        }
+        match pipe.writer.write_dds_data(cc.data_value, options.build()) {
+          Ok(_) => {
+            pipe.statistics.forwarded += 1;
+            forwarded += 1;
+          }
+          Err(e) => {
+            debug!("Gateway failed to forward on {}: {e:?}", pipe.topic_name);
+            pipe.statistics.write_failed += 1;
+          }
+        }
+      }
+    }
+    forwarded
+  }
+
+  /// Statistics of each routed Topic and direction.
+  pub fn statistics(&self) -> Vec<(String, RouteDirection, RouteStatistics)> {
+    self
+      .pipes
+      .iter()
+      .map(|p| (p.topic_name.clone(), p.direction, p.statistics))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use super::*;
+
+  #[test]
+  fn rate_limiter_refills() {
+    let mut limiter = RateLimiter::new(2);
+    let start = Instant::now();
+    assert!(limiter.try_acquire(start));
+    assert!(limiter.try_acquire(start));
+    assert!(!limiter.try_acquire(start));
+    assert!(limiter.try_acquire(start + Duration::from_millis(500)));
+    assert!(!limiter.try_acquire(start + Duration::from_millis(600)));
+    // burst is capped to one second worth of samples
+    let later = start + Duration::from_secs(10);
+    assert!(limiter.try_acquire(later));
+    assert!(limiter.try_acquire(later));
+    assert!(!limiter.try_acquire(later));
+  }
+
+  #[test]
+  fn raw_sample_keeps_encoding() {
+    let raw =
+      RawSampleAdapter::from_bytes(&[1, 2, 3], RepresentationIdentifier::PL_CDR_BE).unwrap();
+    assert_eq!(raw.encoding, RepresentationIdentifier::PL_CDR_BE);
+    assert_eq!(
+      RawSampleAdapter::to_bytes(&raw).unwrap().as_ref(),
+      &[1, 2, 3]
+    );
+  }
+
+  #[test]
+  fn raw_samples_are_not_copied() {
+    let frame = Bytes::from(vec![7u8; 100_000]);
+    let serialized = RawSampleAdapter::to_bytes(&RawSample {
+      encoding: RepresentationIdentifier::CDR_LE,
+      value: frame.clone(),
+    })
+    .unwrap();
+    assert_eq!(serialized.as_ptr(), frame.as_ptr());
+
+    let received =
+      RawSampleAdapter::from_vec_bytes(&[serialized.slice(10..)], RepresentationIdentifier::CDR_LE)
+        .unwrap();
+    assert_eq!(received.value.as_ptr(), frame[10..].as_ptr());
+
+    let joined = RawSampleAdapter::from_vec_bytes(
+      &[frame.slice(..10), frame.slice(10..)],
+      RepresentationIdentifier::CDR_LE,
+    )
+    .unwrap();
+    assert_eq!(joined.value, frame);
+  }
+
+  #[test]
+  fn raw_sample_frame_round_trip() {
+    use std::collections::BTreeMap;
+
+    use crate::{
+      messages::submessages::{
+        elements::serialized_payload::SerializedPayload, submessages::WriterSubmessage,
+      },
+      rtps::{
+        reader::test_util::TestReader,
+        writer::test_util::{reliable_qos, writer_submessages, TestWriter},
+      },
+      structure::entity::RTPSEntity,
+    };
+
+    // The frame goes from an RTPS Writer to an RTPS Reader in DATA_FRAGs
+    let mut writer = TestWriter::new(reliable_qos().build());
+    let mut reader = TestReader::new(reliable_qos().build());
+    writer.add_reader(reader.guid(), 7520);
+    let mr_state = reader.add_writer(writer.guid(), &reliable_qos().build());
+
+    let frame = Bytes::from(
+      (0..200_000u32)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<u8>>(),
+    );
+    let serialized = RawSampleAdapter::to_bytes(&RawSample {
+      encoding: RepresentationIdentifier::CDR_LE,
+      value: frame.clone(),
+    })
+    .unwrap();
+    let sent = writer.sent_by(|writer| {
+      writer.write(
+        1,
+        SerializedPayload::new_from_bytes(RepresentationIdentifier::CDR_LE, serialized),
+      );
+    });
+    let mut fragments = 0;
+    for submessage in writer_submessages(&sent) {
+      if let WriterSubmessage::DataFrag(datafrag, flags) = submessage {
+        reader.handle_datafrag_msg(&datafrag, flags, &mr_state);
+        fragments += 1;
+      }
+    }
+    reader.commit_changes();
+    assert!(fragments > 1);
+
+    let received: Vec<Bytes> = reader
+      .topic_cache_handle
+      .lock()
+      .unwrap()
+      .get_changes_in_range_reliable(&BTreeMap::new())
+      .map(|(_, cc)| cc.data_value.data())
+      .collect();
+    assert_eq!(received.len(), 1);
+    let sample =
+      RawSampleAdapter::from_vec_bytes(&received, RepresentationIdentifier::CDR_LE).unwrap();
+    assert_eq!(sample.value, frame);
+  }
+
+  #[test]
+  fn keyed_route_forwards_data_and_disposes() {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+      policy::{History, Reliability},
+      with_key::Sample,
+      Keyed, QosPolicyBuilder,
+    };
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct Shape {
+      color: String,
+      x: i32,
+    }
+
+    impl Keyed for Shape {
+      type K = String;
+      fn key(&self) -> String {
+        self.color.clone()
+      }
+    }
+
+    let domain_a = DomainParticipant::new(0).unwrap();
+    let domain_b = DomainParticipant::new(1).unwrap();
+    let qos = QosPolicyBuilder::new()
+      .reliability(Reliability::Reliable {
+        max_blocking_time: crate::Duration::from_millis(100),
+      })
+      .history(History::KeepAll)
+      .build();
+    let mut gateway = TopicGateway::new(&domain_a, &domain_b).unwrap();
+    let route = GatewayRoute::new(
+      "gateway_keyed_test",
+      "Shape",
+      TopicKind::WithKey,
+      RouteDirection::AToB,
+      qos.clone(),
+    );
+    gateway.add_route(&route).unwrap();
+
+    let topic_a = domain_a
+      .create_topic(
+        "gateway_keyed_test".to_string(),
+        "Shape".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let topic_b = domain_b
+      .create_topic(
+        "gateway_keyed_test".to_string(),
+        "Shape".to_string(),
+        &qos,
+        TopicKind::WithKey,
+      )
+      .unwrap();
+    let writer = domain_a
+      .create_publisher(&qos)
+      .unwrap()
+      .create_datawriter_cdr::<Shape>(&topic_a, None)
+      .unwrap();
+    let mut reader = domain_b
+      .create_subscriber(&qos)
+      .unwrap()
+      .create_datareader_cdr::<Shape>(&topic_b, None)
+      .unwrap();
+
+    let red = Shape {
+      color: "RED".to_string(),
+      x: 1,
+    };
+    writer.write(red.clone(), None).unwrap();
+    writer.dispose(&"RED".to_string(), None).unwrap();
+
+    // Both participants are in this process, so no network is involved.
+    let mut received = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while received.len() < 2 {
+      assert!(Instant::now() < deadline, "Received only {received:?}");
+      gateway.process();
+      while let Some(sample) = reader.take_next_sample().unwrap() {
+        received.push(sample.into_value());
+      }
+      std::thread::yield_now();
+    }
+    assert_eq!(
+      received,
+      vec![Sample::Value(red), Sample::Dispose("RED".to_string())]
+    );
+    let statistics = gateway.statistics();
+    assert_eq!(statistics.len(), 1);
+    assert_eq!(statistics[0].2.forwarded, 2);
+  }
+}
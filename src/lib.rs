@@ -89,6 +89,69 @@
 //!
 //! See exampe `async_shapes_demo`.
 //!
+//! # Metrics
+//!
+//! With feature `metrics`, RustDDS reports statistics via the
+//! [metrics](https://docs.rs/metrics) crate facade, so that any exporter
+//! (e.g. Prometheus) can collect them. Install the recorder before creating
+//! the `DomainParticipant`, because metric handles are registered when
+//! DataReaders and DataWriters are created.
+//!
+//! Per-endpoint metrics, labeled with `topic` and `guid`:
+//! * `rustdds_writer_samples_written_total`
+//! * `rustdds_writer_stale_samples_dropped_total`
+//! * `rustdds_writer_acknacks_received_total`
+//! * `rustdds_writer_repair_samples_sent_total`
+//! * `rustdds_writer_matched_readers` (gauge)
+//! * `rustdds_reader_samples_received_total`
+//! * `rustdds_reader_samples_lost_total`
+//! * `rustdds_reader_heartbeats_received_total`
+//! * `rustdds_reader_matched_writers` (gauge)
+//! * `rustdds_writer_payload_bytes`, `rustdds_writer_serialization_seconds`
+//!   (histograms of serialized sample sizes and serialization times)
+//! * `rustdds_reader_payload_bytes`, `rustdds_reader_deserialization_seconds`
+//!   (histograms of received sample sizes and deserialization times)
+//!
+//! The gauges of an endpoint are set to zero when the endpoint is deleted. The
+//! `metrics` facade cannot remove them, but exporters can, e.g. with an idle
+//! timeout.
+//!
+//! Transport metrics:
+//! * `rustdds_udp_datagrams_sent_total`, `rustdds_udp_bytes_sent_total`
+//! * `rustdds_udp_send_errors_total`
+//! * `rustdds_udp_datagrams_received_total`, `rustdds_udp_bytes_received_total`
+//! * `rustdds_messages_rejected_total`, labeled with `reason`
+//! * `rustdds_submessages_skipped_total`, labeled with `kind`
+//!   (`vendor_specific` or `unknown`)
+//!
+//! # Trace context propagation
+//!
+//! With feature `opentelemetry`, a DataWriter sends the span context of the
+//! current [OpenTelemetry](https://docs.rs/opentelemetry) `Context` with each
+//! sample, as a W3C traceparent in a vendor-specific inline QoS parameter. The
+//! span context can also be given explicitly with
+//! `WriteOptionsBuilder::trace_context`. On the receiving side, it is
+//! available from `SampleInfo::trace_context`.
+//!
+//! # Tracing
+//!
+//! With feature `tracing`, the event loop of each DomainParticipant runs in a
+//! `participant` span with fields `domain_id` and `guid`, and every RTPS
+//! Writer and Reader has a `writer` or `reader` span within it, with fields
+//! `topic` and `guid`. Written, received and resent samples, ACKNACKs,
+//! HEARTBEATs, lost samples and (un)matching are reported as
+//! [`tracing`](https://docs.rs/tracing) events in the span of the endpoint,
+//! with the remote GUID and sequence numbers as fields. The per-sample events
+//! can be thinned with
+//! [`DomainParticipantBuilder::trace_sampling`](DomainParticipantBuilder::trace_sampling).
+//!
+//! # Payload compression
+//!
+//! With feature `compression`, the [`Compression`](policy::Compression) QoS
+//! policy makes DataWriters compress large serialized samples with zlib or
+//! zstd, when all matched DataReaders have announced that they accept it.
+//! DataReaders decompress them transparently.
+//!
 //! # Usage Example
 //!
 //! ```
@@ -186,41 +249,86 @@ pub(crate) mod structure;
 mod test;
 
 mod mio_source;
+mod stats;
+mod trace;
 
 // Public modules
 pub mod dds; // this is public, but not advertised
 
+#[cfg(any(test, feature = "fuzzing"))]
+pub mod fuzzing;
+
+#[cfg(any(test, feature = "ffi"))]
+pub mod ffi;
+
+#[cfg(feature = "python")]
+pub mod python;
+
+pub mod dynamic_data;
+pub mod file_transfer;
+pub mod gateway;
+pub mod ping;
+pub mod relay;
 #[deprecated(since = "0.8.5", note = "Use crate ros2-client instead.")]
 pub mod ros2;
+/// RTPS wire format core, which does not need the standard library
+pub use rustdds_rtps_core as rtps_core;
 /// Helpers for (De)serialization and definitions of (De)serializer adapters
 pub mod serialization;
 
 // Re-exports from crate root to simplify usage
 #[doc(inline)]
 pub use dds::{
-  key::{Key, Keyed},
+  delivery::{DeliveryListener, DropReason},
+  fragment_stream::FragmentStream,
+  key::{InstanceHandle, Key, Keyed},
   participant::{DomainParticipant, DomainParticipantBuilder},
   pubsub::{Publisher, Subscriber},
   qos,
   qos::{policy, QosPolicies, QosPolicyBuilder},
   readcondition::ReadCondition,
+  reader_multiplexer::AnySample,
   sampleinfo::{InstanceState, NotAliveGenerationCounts, SampleInfo, SampleState, ViewState},
   statusevents::StatusEvented,
   topic::{Topic, TopicDescription, TopicKind},
+  type_support::{CdrTypeSupport, RegisteredType, TypeSupport},
   typedesc::TypeDesc,
-  with_key::{datareader::SelectByKey, WriteOptions, WriteOptionsBuilder},
+  with_key::{datareader::SelectByKey, InlineQosParameter, WriteOptions, WriteOptionsBuilder},
 };
 /// Needed to specify serialized data representation in case it is other than
 /// CDR.
 pub use serialization::representation_identifier::RepresentationIdentifier;
+/// Byte order of RTPS messages, see
+/// [`DomainParticipantBuilder::rtps_endianness`].
+pub use speedy::Endianness;
 #[doc(inline)]
 pub use serialization::{
   CDRDeserializerAdapter, CDRSerializerAdapter, CdrDeserializer, CdrSerializer,
 };
+pub use discovery::{
+  builtin_endpoint::BuiltinEndpointOptions,
+  discovery_filter::DiscoveryFilter,
+  discovery_snapshot::{DiscoverySnapshot, EndpointSnapshot, ParticipantSnapshot},
+  static_discovery::{StaticDiscovery, StaticEndpoint, StaticParticipant},
+};
+#[cfg(feature = "static_discovery")]
+pub use discovery::static_discovery::StaticDiscoveryError;
+pub use messages::vendor_id::VendorId;
+pub use network::{rate_limit::SourceRateLimit, socket_options::SocketOptions};
+pub use rtps::{
+  event_scheduler::EventLoopWeights,
+  message_receiver::{MessageReceiverLimits, MessageReceiverRejections, MessageReceiverStatistics},
+  rtps_writer_proxy::WriterProxyStatistics,
+  writer::WriterCacheCleaning,
+};
 pub use structure::{
-  duration::Duration, entity::RTPSEntity, guid::GUID, sequence_number::SequenceNumber,
+  duration::Duration,
+  entity::RTPSEntity,
+  guid::{EntityId, EntityKind, GuidParseError, GuidPrefix, GUID},
+  sequence_number::SequenceNumber,
   time::Timestamp,
 };
+pub use trace::TraceSampling;
 // re-export from a helper crate
 /// Helper trait to compute the CDR-serialized size of data
 pub use cdr_encoding_size::CdrEncodingSize;
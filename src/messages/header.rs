@@ -1,4 +1,7 @@
+use std::io;
+
 use speedy::{Readable, Writable};
+use rustdds_rtps_core as rtps_core;
 
 use crate::{
   messages::{
@@ -25,6 +28,29 @@ impl Header {
       guid_prefix: guid,
     }
   }
+
+  /// Decodes the header at the start of a message. Fails if the message is
+  /// too short, or it does not start with "RTPS".
+  pub fn decode(bytes: &[u8]) -> io::Result<Self> {
+    rtps_core::Header::decode(bytes)
+      .map(|(header, _submessages)| Self::from(header))
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+  }
+}
+
+impl From<rtps_core::Header> for Header {
+  fn from(header: rtps_core::Header) -> Self {
+    let [major, minor] = header.protocol_version;
+    Self {
+      // rtps_core checks this when decoding
+      protocol_id: ProtocolId::PROTOCOL_RTPS,
+      protocol_version: ProtocolVersion { major, minor },
+      vendor_id: VendorId {
+        vendor_id: header.vendor_id,
+      },
+      guid_prefix: GuidPrefix::from(header.guid_prefix),
+    }
+  }
 }
 
 impl Validity for Header {
@@ -64,6 +90,21 @@ mod tests {
     assert!(header.valid());
   }
 
+  #[test]
+  fn header_decode_matches_speedy() {
+    let header = Header::new(GuidPrefix::from([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]));
+    let bytes = header.write_to_vec().unwrap();
+
+    assert_eq!(Header::decode(&bytes).unwrap(), header);
+    let (core_header, _) = rtps_core::Header::decode(&bytes).unwrap();
+    assert_eq!(core_header.encode().as_slice(), bytes.as_slice());
+
+    let mut not_rtps = bytes.clone();
+    not_rtps[0] = b'X';
+    assert!(Header::decode(&not_rtps).is_err());
+    assert!(Header::decode(&bytes[..19]).is_err());
+  }
+
   serialization_test!( type = Header,
   {
       header_with_unknown_guid_prefix,
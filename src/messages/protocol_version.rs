@@ -1,10 +1,11 @@
 use std::fmt;
 
 use speedy::{Readable, Writable};
+use serde::Serialize;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace};
 
-#[derive(PartialOrd, PartialEq, Ord, Eq, Readable, Writable, Clone, Copy)]
+#[derive(PartialOrd, PartialEq, Ord, Eq, Readable, Writable, Clone, Copy, Serialize)]
 pub struct ProtocolVersion {
   pub major: u8,
   pub minor: u8,
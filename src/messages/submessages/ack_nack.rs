@@ -1,9 +1,10 @@
-use std::mem::size_of;
+use std::{io, mem::size_of};
 
 use enumflags2::BitFlags;
 use speedy::{Readable, Writable};
 #[allow(unused_imports)]
 use log::error;
+use rustdds_rtps_core as rtps_core;
 
 use crate::{
   messages::submessages::submessages::SubmessageHeader,
@@ -50,6 +51,13 @@ pub struct AckNack {
 }
 
 impl AckNack {
+  /// Decodes the submessage content.
+  pub fn decode(flags: BitFlags<ACKNACK_Flags>, content: &[u8]) -> io::Result<Self> {
+    rtps_core::AckNack::decode(flags.bits(), content)
+      .map(Self::from)
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+  }
+
   pub fn create_submessage(self, flags: BitFlags<ACKNACK_Flags>) -> Submessage {
     Submessage {
       header: SubmessageHeader {
@@ -67,6 +75,17 @@ impl AckNack {
   }
 }
 
+impl From<rtps_core::AckNack> for AckNack {
+  fn from(acknack: rtps_core::AckNack) -> Self {
+    Self {
+      reader_id: EntityId::from(acknack.reader_id),
+      writer_id: EntityId::from(acknack.writer_id),
+      reader_sn_state: SequenceNumberSet::from(acknack.reader_sn_state),
+      count: acknack.count,
+    }
+  }
+}
+
 impl HasEntityIds for AckNack {
   fn receiver_entity_id(&self) -> EntityId {
     self.writer_id
@@ -78,8 +97,39 @@ impl HasEntityIds for AckNack {
 
 #[cfg(test)]
 mod tests {
+  use std::collections::BTreeSet;
+
+  use speedy::Endianness;
+
   use super::*;
-  use crate::structure::sequence_number::SequenceNumber;
+  use crate::{
+    messages::submessages::submessage_flag::FromEndianness,
+    structure::sequence_number::SequenceNumber,
+  };
+
+  #[test]
+  fn core_codec_matches() {
+    let missing: BTreeSet<SequenceNumber> =
+      [3, 4, 40].into_iter().map(SequenceNumber::from).collect();
+    let acknack = AckNack {
+      reader_id: EntityId::SEDP_BUILTIN_PUBLICATIONS_READER,
+      writer_id: EntityId::SEDP_BUILTIN_PUBLICATIONS_WRITER,
+      reader_sn_state: SequenceNumberSet::from_base_and_set(SequenceNumber::from(3), &missing),
+      count: 5,
+    };
+    for endianness in [Endianness::LittleEndian, Endianness::BigEndian] {
+      let flags = BitFlags::<ACKNACK_Flags>::from_endianness(endianness);
+      let bytes = acknack.write_to_vec_with_ctx(endianness).unwrap();
+      assert_eq!(AckNack::decode(flags, &bytes).unwrap(), acknack);
+
+      let core_acknack = rtps_core::AckNack::decode(flags.bits(), &bytes).unwrap();
+      let mut core_bytes = vec![0; core_acknack.encoded_len()];
+      core_acknack
+        .encode(endianness == Endianness::LittleEndian, &mut core_bytes)
+        .unwrap();
+      assert_eq!(core_bytes, bytes);
+    }
+  }
 
   serialization_test!( type = AckNack,
   {
@@ -1,8 +1,9 @@
 use std::io;
 
 use bytes::Bytes;
-use speedy::{Context, Error, Readable, Writable, Writer};
+use speedy::{Context, Readable, Writable, Writer};
 use enumflags2::BitFlags;
+use rustdds_rtps_core as rtps_core;
 
 use crate::{
   messages::submessages::{elements::parameter_list::ParameterList, submessages::*},
@@ -55,88 +56,25 @@ impl Data {
   /// and expect_payload, which are told on submessage header flags.
 
   pub fn deserialize_data(buffer: &Bytes, flags: BitFlags<DATA_Flags>) -> io::Result<Self> {
-    let mut cursor = io::Cursor::new(&buffer);
-    let endianness = endianness_flag(flags.bits());
-    let map_speedy_err = |p: Error| io::Error::new(io::ErrorKind::Other, p);
-
-    let _extra_flags =
-      u16::read_from_stream_unbuffered_with_ctx(endianness, &mut cursor).map_err(map_speedy_err)?;
-    let octets_to_inline_qos =
-      u16::read_from_stream_unbuffered_with_ctx(endianness, &mut cursor).map_err(map_speedy_err)?;
-    let reader_id = EntityId::read_from_stream_unbuffered_with_ctx(endianness, &mut cursor)
-      .map_err(map_speedy_err)?;
-    let writer_id = EntityId::read_from_stream_unbuffered_with_ctx(endianness, &mut cursor)
-      .map_err(map_speedy_err)?;
-    let sequence_number =
-      SequenceNumber::read_from_stream_unbuffered_with_ctx(endianness, &mut cursor)
-        .map_err(map_speedy_err)?;
-
-    let expect_qos = flags.contains(DATA_Flags::InlineQos);
-    let expect_data = flags.contains(DATA_Flags::Data) || flags.contains(DATA_Flags::Key);
-
-    // size of DATA-specific header above is
-    // extraFlags (2) + octetsToInlineQos (2) + readerId (4) + writerId (4) +
-    // writerSN (8) = 20 bytes
-    // of which 16 bytes is after octetsToInlineQos field.
-    let rtps_v23_data_header_size: u16 = 16;
-    // ... and octets_to_inline_qos must be at least this much, or otherwise inline
-    // Qos (or in case it is absent, the following SerializedPayload) would
-    // overlap with the rtps_v23_data_header fields (readerId, writerId, and
-    // writerSN).
-    if octets_to_inline_qos < rtps_v23_data_header_size {
-      return Err(io::Error::new(
-        io::ErrorKind::InvalidData,
-        format!("DATA submessage has invalid octets_to_inline_qos={octets_to_inline_qos}."),
-      ));
-    }
-
-    // We need to check to avoid subtract overflow
-    // https://github.com/jhelovuo/RustDDS/issues/277
-    if octets_to_inline_qos > rtps_v23_data_header_size {
-      let extra_octets = octets_to_inline_qos - rtps_v23_data_header_size;
-      // There may be some extra data between writerSN and inlineQos, if the header is
-      // extended in future versions. But as of RTPS v2.3 , extra_octets should be
-      // always zero.
-
-      // Nevertheless, skip over that extra data, if we are told such exists.
-      cursor.set_position(cursor.position() + u64::from(extra_octets));
-
-      if cursor.position() > buffer.len().try_into().unwrap() {
-        // octets_to_inline_qos told us to skip past the end of the message.
-        // This is a malformed message.
-        return Err(io::Error::new(
-          io::ErrorKind::InvalidData,
-          format!(
-            "DATA submessage octets_to_inline_qos points to byte {}, but message len={}.",
-            cursor.position(),
-            buffer.len()
-          ),
-        ));
-      }
-    }
-
-    // read the inline Qos
-    let parameter_list = if expect_qos {
-      Some(
-        ParameterList::read_from_stream_unbuffered_with_ctx(endianness, &mut cursor)
-          .map_err(map_speedy_err)?,
-      )
-    } else {
-      None
-    };
-
-    let serialized_payload = if expect_data {
-      Some(buffer.clone().split_off(cursor.position() as usize))
-    } else {
-      None
-    };
+    let data = rtps_core::Data::decode(flags.bits(), buffer)
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("DATA submessage: {e}")))?;
+    let inline_qos = data
+      .inline_qos
+      .map(|qos| {
+        ParameterList::read_from_buffer_with_ctx(endianness_flag(flags.bits()), qos)
+          .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+      })
+      .transpose()?;
 
     Ok(Self {
-      reader_id,
-      writer_id,
-      writer_sn: sequence_number,
-      inline_qos: parameter_list,
-      serialized_payload,
+      reader_id: EntityId::from(data.reader_id),
+      writer_id: EntityId::from(data.writer_id),
+      writer_sn: SequenceNumber::new(data.writer_sn),
+      inline_qos,
+      // The payload is shared with the received message, not copied.
+      serialized_payload: data
+        .serialized_payload
+        .map(|payload| buffer.slice_ref(payload)),
     })
   }
 
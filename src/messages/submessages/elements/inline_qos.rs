@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 use log::{debug, error, info, trace, warn};
 
 use crate::{
-  dds::key::KeyHash,
+  dds::{key::KeyHash, with_key::datawriter::InlineQosParameter},
   messages::submessages::elements::{parameter_list::ParameterList, RepresentationIdentifier},
   serialization::{pl_cdr_adapters::PlCdrDeserializeError, speedy_pl_cdr_helpers::*},
   structure::{cache_change::ChangeKind, parameter_id::ParameterId, rpc::SampleIdentity},
@@ -81,6 +81,47 @@ impl InlineQos {
       None => None,
     })
   }
+
+  // Is the parameter interpreted by the above, or otherwise part of the
+  // parameter list structure?
+  pub fn is_interpreted(parameter_id: ParameterId) -> bool {
+    [
+      ParameterId::PID_PAD,
+      ParameterId::PID_SENTINEL,
+      ParameterId::PID_KEY_HASH,
+      ParameterId::PID_STATUS_INFO,
+      ParameterId::PID_RELATED_SAMPLE_IDENTITY,
+    ]
+    .contains(&parameter_id)
+  }
+
+  // The parameters that are not interpreted by RustDDS, to be passed to the
+  // application
+  pub fn custom_parameters(params: &ParameterList) -> Vec<InlineQosParameter> {
+    Self::parameters_where(params, |id| {
+      !Self::is_interpreted(id) && !id.is_rustdds_specific()
+    })
+  }
+
+  // The PID_RUSTDDS_* parameters
+  pub fn rustdds_parameters(params: &ParameterList) -> Vec<InlineQosParameter> {
+    Self::parameters_where(params, ParameterId::is_rustdds_specific)
+  }
+
+  fn parameters_where(
+    params: &ParameterList,
+    predicate: impl Fn(ParameterId) -> bool,
+  ) -> Vec<InlineQosParameter> {
+    params
+      .parameters
+      .iter()
+      .filter(|p| predicate(p.parameter_id))
+      .map(|p| InlineQosParameter {
+        parameter_id: p.parameter_id.value(),
+        value: p.value.clone(),
+      })
+      .collect()
+  }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -106,8 +106,15 @@ impl<C: Context> Writable<C> for Parameter {
     let length = self.value.len();
     let pad = if length % 4 != 0 { 4 - (length % 4) } else { 0 };
 
+    let padded_length = u16::try_from(length + pad).map_err(|_| {
+      speedy::Error::custom(format!(
+        "Parameter {:?} is too long: {} bytes",
+        self.parameter_id, length
+      ))
+    })?;
+
     writer.write_value(&self.parameter_id)?;
-    writer.write_u16((length + pad) as u16)?;
+    writer.write_u16(padded_length)?;
     writer.write_bytes(&self.value)?;
 
     for _ in 0..pad {
@@ -122,6 +129,14 @@ impl<C: Context> Writable<C> for Parameter {
 mod tests {
   use super::*;
 
+  #[test]
+  fn too_long_parameter_is_not_written() {
+    let parameter = Parameter::new(ParameterId::PID_USER_DATA, vec![0; 0xfffd]);
+    assert!(parameter.write_to_vec().is_err());
+    let parameter = Parameter::new(ParameterId::PID_USER_DATA, vec![0; 0xfffc]);
+    assert_eq!(parameter.write_to_vec().unwrap().len(), 4 + 0xfffc);
+  }
+
   serialization_test!( type = Parameter,
   {
       pid_protocol_version,
@@ -1,6 +1,9 @@
+use std::io;
+
 use enumflags2::BitFlags;
 use log::error;
 use speedy::{Readable, Writable};
+use rustdds_rtps_core as rtps_core;
 
 use crate::{
   messages::submessages::submessages::SubmessageHeader,
@@ -42,6 +45,13 @@ pub struct Gap {
 }
 
 impl Gap {
+  /// Decodes the submessage content.
+  pub fn decode(flags: BitFlags<GAP_Flags>, content: &[u8]) -> io::Result<Self> {
+    rtps_core::Gap::decode(flags.bits(), content)
+      .map(Self::from)
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+  }
+
   pub fn create_submessage(self, flags: BitFlags<GAP_Flags>) -> Option<Submessage> {
     let submessage_len = match self.write_to_vec() {
       Ok(bytes) => bytes.len() as u16,
@@ -63,6 +73,17 @@ impl Gap {
   }
 }
 
+impl From<rtps_core::Gap> for Gap {
+  fn from(gap: rtps_core::Gap) -> Self {
+    Self {
+      reader_id: EntityId::from(gap.reader_id),
+      writer_id: EntityId::from(gap.writer_id),
+      gap_start: SequenceNumber::new(gap.gap_start),
+      gap_list: SequenceNumberSet::from(gap.gap_list),
+    }
+  }
+}
+
 impl HasEntityIds for Gap {
   fn receiver_entity_id(&self) -> EntityId {
     self.reader_id
@@ -74,7 +95,36 @@ impl HasEntityIds for Gap {
 
 #[cfg(test)]
 mod tests {
+  use std::collections::BTreeSet;
+
+  use speedy::Endianness;
+
   use super::*;
+  use crate::messages::submessages::submessage_flag::FromEndianness;
+
+  #[test]
+  fn core_codec_matches() {
+    let irrelevant: BTreeSet<SequenceNumber> =
+      [12, 13, 70].into_iter().map(SequenceNumber::from).collect();
+    let gap = Gap {
+      reader_id: EntityId::SEDP_BUILTIN_PUBLICATIONS_READER,
+      writer_id: EntityId::SEDP_BUILTIN_PUBLICATIONS_WRITER,
+      gap_start: SequenceNumber::from(10),
+      gap_list: SequenceNumberSet::from_base_and_set(SequenceNumber::from(12), &irrelevant),
+    };
+    for endianness in [Endianness::LittleEndian, Endianness::BigEndian] {
+      let flags = BitFlags::<GAP_Flags>::from_endianness(endianness);
+      let bytes = gap.write_to_vec_with_ctx(endianness).unwrap();
+      assert_eq!(Gap::decode(flags, &bytes).unwrap(), gap);
+
+      let core_gap = rtps_core::Gap::decode(flags.bits(), &bytes).unwrap();
+      let mut core_bytes = vec![0; core_gap.encoded_len()];
+      core_gap
+        .encode(endianness == Endianness::LittleEndian, &mut core_bytes)
+        .unwrap();
+      assert_eq!(core_bytes, bytes);
+    }
+  }
 
   serialization_test!( type = Gap,
   {
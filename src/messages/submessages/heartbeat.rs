@@ -1,6 +1,9 @@
+use std::io;
+
 use enumflags2::BitFlags;
 use log::error;
 use speedy::{Readable, Writable};
+use rustdds_rtps_core as rtps_core;
 
 use crate::{
   messages::submessages::submessages::SubmessageHeader,
@@ -49,6 +52,13 @@ pub struct Heartbeat {
 }
 
 impl Heartbeat {
+  /// Decodes the submessage content.
+  pub fn decode(flags: BitFlags<HEARTBEAT_Flags>, content: &[u8]) -> io::Result<Self> {
+    rtps_core::Heartbeat::decode(flags.bits(), content)
+      .map(Self::from)
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+  }
+
   pub fn create_submessage(self, flags: BitFlags<HEARTBEAT_Flags>) -> Option<Submessage> {
     let submessage_len = match self.write_to_vec() {
       Ok(bytes) => bytes.len() as u16,
@@ -70,6 +80,18 @@ impl Heartbeat {
   }
 }
 
+impl From<rtps_core::Heartbeat> for Heartbeat {
+  fn from(heartbeat: rtps_core::Heartbeat) -> Self {
+    Self {
+      reader_id: EntityId::from(heartbeat.reader_id),
+      writer_id: EntityId::from(heartbeat.writer_id),
+      first_sn: SequenceNumber::new(heartbeat.first_sn),
+      last_sn: SequenceNumber::new(heartbeat.last_sn),
+      count: heartbeat.count,
+    }
+  }
+}
+
 impl HasEntityIds for Heartbeat {
   fn receiver_entity_id(&self) -> EntityId {
     self.reader_id
@@ -81,7 +103,34 @@ impl HasEntityIds for Heartbeat {
 
 #[cfg(test)]
 mod tests {
+  use speedy::Endianness;
+
   use super::*;
+  use crate::messages::submessages::submessage_flag::FromEndianness;
+
+  #[test]
+  fn core_codec_matches() {
+    let heartbeat = Heartbeat {
+      reader_id: EntityId::SEDP_BUILTIN_PUBLICATIONS_READER,
+      writer_id: EntityId::SEDP_BUILTIN_PUBLICATIONS_WRITER,
+      first_sn: SequenceNumber::from(1),
+      last_sn: SequenceNumber::new((1 << 32) + 7),
+      count: -2,
+    };
+    for endianness in [Endianness::LittleEndian, Endianness::BigEndian] {
+      let flags = BitFlags::<HEARTBEAT_Flags>::from_endianness(endianness);
+      let bytes = heartbeat.write_to_vec_with_ctx(endianness).unwrap();
+      assert_eq!(Heartbeat::decode(flags, &bytes).unwrap(), heartbeat);
+
+      let core_heartbeat = rtps_core::Heartbeat::decode(flags.bits(), &bytes).unwrap();
+      let mut core_bytes = [0; rtps_core::Heartbeat::ENCODED_LEN];
+      core_heartbeat
+        .encode(endianness == Endianness::LittleEndian, &mut core_bytes)
+        .unwrap();
+      assert_eq!(core_bytes.as_slice(), bytes.as_slice());
+    }
+    assert!(Heartbeat::decode(BitFlags::empty(), &[0; 27]).is_err());
+  }
 
   serialization_test!( type = Heartbeat,
   {
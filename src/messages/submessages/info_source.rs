@@ -1,20 +1,17 @@
 use speedy::{Readable, Writable};
-#[cfg(feature = "security")]
 use enumflags2::BitFlags;
 
+use super::{
+  submessage::InterpreterSubmessage, submessage_flag::INFOSOURCE_Flags,
+  submessage_kind::SubmessageKind, submessages::SubmessageHeader,
+};
 use crate::{
   messages::{
     header::Header, protocol_id::ProtocolId, protocol_version::ProtocolVersion, vendor_id::VendorId,
   },
+  rtps::{Submessage, SubmessageBody},
   structure::guid::GuidPrefix,
 };
-#[cfg(feature = "security")]
-use super::{
-  submessage::InterpreterSubmessage, submessage_flag::INFOSOURCE_Flags,
-  submessage_kind::SubmessageKind, submessages::SubmessageHeader,
-};
-#[cfg(feature = "security")]
-use crate::rtps::{Submessage, SubmessageBody};
 
 /// This message modifies the logical source of the Submessages
 /// that follow.
@@ -36,7 +33,6 @@ pub struct InfoSource {
 }
 
 impl InfoSource {
-  #[cfg(feature = "security")] // currently otherwise unused, clippy warns about this
   pub fn len_serialized(&self) -> usize {
     std::mem::size_of::<u32>()
       + std::mem::size_of::<ProtocolVersion>()
@@ -44,7 +40,6 @@ impl InfoSource {
       + std::mem::size_of::<GuidPrefix>()
   }
 
-  #[cfg(feature = "security")] // currently otherwise unused
   pub fn create_submessage(self, flags: BitFlags<INFOSOURCE_Flags>) -> Submessage {
     Submessage {
       header: SubmessageHeader {
@@ -120,7 +120,7 @@ impl<C: Context> Writable<C> for InterpreterSubmessage {
   }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum AckSubmessage {
   AckNack(AckNack),
   #[allow(dead_code)] // Functionality not yet implemented
@@ -134,6 +134,29 @@ impl AckSubmessage {
       AckSubmessage::NackFrag(a) => a.writer_id,
     }
   }
+
+  pub fn reader_id(&self) -> EntityId {
+    match self {
+      AckSubmessage::AckNack(a) => a.reader_id,
+      AckSubmessage::NackFrag(a) => a.reader_id,
+    }
+  }
+
+  // Copy of this submessage with the (possibly UNKNOWN) entity ids replaced
+  pub fn addressed_to(&self, writer_id: EntityId, reader_id: EntityId) -> Self {
+    let mut addressed = self.clone();
+    match &mut addressed {
+      AckSubmessage::AckNack(a) => {
+        a.writer_id = writer_id;
+        a.reader_id = reader_id;
+      }
+      AckSubmessage::NackFrag(a) => {
+        a.writer_id = writer_id;
+        a.reader_id = reader_id;
+      }
+    }
+    addressed
+  }
 }
 
 pub trait HasEntityIds {
@@ -1,4 +1,7 @@
+use std::io;
+
 use speedy::{Context, Endianness, Readable, Reader, Writable, Writer};
+use rustdds_rtps_core as rtps_core;
 
 use crate::messages::submessages::{submessage_flag::*, submessage_kind::SubmessageKind};
 
@@ -9,6 +12,25 @@ pub struct SubmessageHeader {
   pub content_length: u16, // Note that 0 is a special value, see spec 9.4.5.1.3
 }
 
+impl SubmessageHeader {
+  /// Decodes the submessage header at the start of `bytes`.
+  pub fn decode(bytes: &[u8]) -> io::Result<Self> {
+    rtps_core::SubmessageHeader::decode(bytes)
+      .map(|(header, _content)| Self::from(header))
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+  }
+}
+
+impl From<rtps_core::SubmessageHeader> for SubmessageHeader {
+  fn from(header: rtps_core::SubmessageHeader) -> Self {
+    Self {
+      kind: SubmessageKind::from(header.kind),
+      flags: header.flags,
+      content_length: header.content_length,
+    }
+  }
+}
+
 impl<'a, C: Context> Readable<'a, C> for SubmessageHeader {
   #[inline]
   fn read_from<R: Reader<'a, C>>(reader: &mut R) -> Result<Self, C::Error> {
@@ -124,4 +146,21 @@ mod tests {
       be = [0x08, 0x01, 0x07, 0x00]
       //TODO: Where is the flags value 0x03 from? RTPS 2.3 spec 9.4.5.5 shows only Endianness bit is legal.
   });
+
+  #[test]
+  fn submessage_header_decode_matches_speedy() {
+    for flags in [0x00, 0x01, 0x03] {
+      let header = SubmessageHeader {
+        kind: SubmessageKind::ACKNACK,
+        flags,
+        content_length: 0x1234,
+      };
+      let bytes = header.write_to_vec().unwrap();
+
+      assert_eq!(SubmessageHeader::decode(&bytes).unwrap(), header);
+      let (core_header, _) = rtps_core::SubmessageHeader::decode(&bytes).unwrap();
+      assert_eq!(core_header.encode().as_slice(), bytes.as_slice());
+    }
+    assert!(SubmessageHeader::decode(&[0x06, 0x01, 0x2A]).is_err());
+  }
 }
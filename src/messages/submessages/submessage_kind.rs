@@ -13,6 +13,12 @@ impl From<SubmessageKind> for u8 {
   }
 }
 
+impl From<u8> for SubmessageKind {
+  fn from(value: u8) -> Self {
+    Self { value }
+  }
+}
+
 impl SubmessageKind {
   pub const PAD: Self = Self { value: 0x01 };
   pub const ACKNACK: Self = Self { value: 0x06 };
@@ -32,6 +38,12 @@ impl SubmessageKind {
   pub const SEC_POSTFIX: Self = Self { value: 0x32 }; // Section 7.3.7.7.2 of the Security specification (v. 1.1)
   pub const SRTPS_PREFIX: Self = Self { value: 0x33 }; // Section 7.3.7.8.2 of the Security specification (v. 1.1)
   pub const SRTPS_POSTFIX: Self = Self { value: 0x34 }; // Section 7.3.7.9.2 of the Security specification (v. 1.1)
+
+  /// Kinds 0x80 - 0xFF are vendor-specific. See RTPS spec v2.5 Section
+  /// 9.4.5.1.1.
+  pub fn is_vendor_specific(&self) -> bool {
+    self.value >= 0x80
+  }
 }
 
 impl Debug for SubmessageKind {
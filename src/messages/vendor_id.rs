@@ -1,8 +1,9 @@
 use std::fmt;
 
 use speedy::{Context, Readable, Reader, Writable, Writer};
+use serde::Serialize;
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(PartialEq, Eq, Clone, Copy, Serialize)]
 pub struct VendorId {
   pub vendor_id: [u8; 2],
 }
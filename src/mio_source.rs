@@ -4,7 +4,7 @@ use std::{
   sync::{Arc, Mutex},
 };
 #[cfg(not(target_os = "windows"))]
-use std::os::fd::OwnedFd;
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
 #[cfg(target_os = "windows")]
 use std::{thread::sleep, time::Duration};
 
@@ -136,6 +136,16 @@ impl PollEventSource {
   }
 }
 
+// The receiving socket is readable whenever there are undrained events. This
+// allows polling PollEventSource also in event loops other than mio, e.g.
+// using poll(2) or libevent.
+#[cfg(not(target_os = "windows"))]
+impl AsRawFd for PollEventSource {
+  fn as_raw_fd(&self) -> RawFd {
+    self.rec_mio_socket.lock().unwrap().as_raw_fd()
+  }
+}
+
 impl event::Source for PollEventSource {
   fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
     self
@@ -162,3 +172,58 @@ impl event::Source for PollEventSource {
     self.rec_mio_socket.lock().unwrap().deregister(registry)
   }
 }
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod tests {
+  use std::time::Duration;
+
+  use mio_06::{unix::EventedFd, Events, Poll, PollOpt, Ready, Token};
+
+  use super::*;
+  use crate::dds::statusevents::sync_status_channel;
+
+  // Readiness as seen by a poller outside mio-0.8
+  fn readable(fd: RawFd) -> bool {
+    let poll = Poll::new().unwrap();
+    poll
+      .register(
+        &EventedFd(&fd),
+        Token(0),
+        Ready::readable(),
+        PollOpt::level(),
+      )
+      .unwrap();
+    let mut events = Events::with_capacity(1);
+    poll.poll(&mut events, Some(Duration::ZERO)).unwrap();
+    !events.is_empty()
+  }
+
+  #[test]
+  fn raw_fd_is_readable_until_drained() {
+    let (source, sender) = make_poll_channel().unwrap();
+    let fd = source.as_raw_fd();
+    assert!(!readable(fd));
+
+    sender.send();
+    sender.send();
+    assert!(readable(fd));
+    assert!(readable(fd)); // level-triggered
+
+    source.drain();
+    assert!(!readable(fd));
+  }
+
+  #[test]
+  fn status_raw_fd_is_readable_until_received() {
+    let (status_sender, status_receiver) = sync_status_channel::<u32>(4).unwrap();
+    let fd = status_receiver.as_raw_fd();
+    assert!(!readable(fd));
+
+    status_sender.try_send(1).unwrap();
+    status_sender.try_send(2).unwrap();
+    assert!(readable(fd));
+
+    while status_receiver.try_recv().is_ok() {}
+    assert!(!readable(fd));
+  }
+}
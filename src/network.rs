@@ -1,4 +1,8 @@
 pub mod constant;
+#[cfg(all(target_os = "linux", feature = "io_uring"))]
+pub(crate) mod io_uring;
+pub mod rate_limit;
+pub mod socket_options;
 pub mod udp_listener;
 pub mod udp_sender;
 pub mod util;
@@ -0,0 +1,510 @@
+// A minimal io_uring interface for batched UDP sending and receiving.
+//
+// Only what RustDDS needs is implemented: submitting a batch of sendmsg
+// operations, possibly on different sockets, or recvmsg operations, and
+// waiting until all of them have completed, with as few system calls as
+// possible. This is used instead of per-datagram system calls and recvmmsg
+// when feature "io_uring" is enabled. Creating a ring may fail, e.g. on old
+// kernels or where seccomp forbids io_uring, and then callers fall back to
+// plain system calls.
+//
+// The structures and constants are from linux/io_uring.h.
+
+use std::{
+  io, mem,
+  os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+  ptr,
+  sync::atomic::{AtomicU32, Ordering},
+};
+
+use log::error;
+
+const IORING_OFF_SQ_RING: libc::off_t = 0;
+const IORING_OFF_CQ_RING: libc::off_t = 0x800_0000;
+const IORING_OFF_SQES: libc::off_t = 0x1000_0000;
+
+const IORING_ENTER_GETEVENTS: libc::c_uint = 1;
+
+const IORING_OP_SENDMSG: u8 = 9;
+const IORING_OP_RECVMSG: u8 = 10;
+
+// The next operation starts only after this one has succeeded
+const IOSQE_IO_LINK: u8 = 1 << 2;
+
+#[repr(C)]
+#[derive(Default)]
+struct SqRingOffsets {
+  head: u32,
+  tail: u32,
+  ring_mask: u32,
+  ring_entries: u32,
+  flags: u32,
+  dropped: u32,
+  array: u32,
+  resv1: u32,
+  user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqRingOffsets {
+  head: u32,
+  tail: u32,
+  ring_mask: u32,
+  ring_entries: u32,
+  overflow: u32,
+  cqes: u32,
+  flags: u32,
+  resv1: u32,
+  user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+  sq_entries: u32,
+  cq_entries: u32,
+  flags: u32,
+  sq_thread_cpu: u32,
+  sq_thread_idle: u32,
+  features: u32,
+  wq_fd: u32,
+  resv: [u32; 3],
+  sq_off: SqRingOffsets,
+  cq_off: CqRingOffsets,
+}
+
+// Submission queue entry
+#[repr(C)]
+#[derive(Default)]
+struct Sqe {
+  opcode: u8,
+  flags: u8,
+  ioprio: u16,
+  fd: i32,
+  off: u64,
+  addr: u64,
+  len: u32,
+  msg_flags: u32,
+  user_data: u64,
+  buf_index: u16,
+  personality: u16,
+  splice_fd_in: i32,
+  addr3: u64,
+  pad: u64,
+}
+static_assertions::const_assert_eq!(mem::size_of::<Sqe>(), 64);
+static_assertions::const_assert_eq!(mem::size_of::<Params>(), 120);
+
+// Completion queue entry
+#[repr(C)]
+struct Cqe {
+  user_data: u64,
+  res: i32,
+  flags: u32,
+}
+
+// A memory mapped part of a ring
+struct Mmap {
+  addr: *mut libc::c_void,
+  len: usize,
+}
+
+impl Mmap {
+  fn new(fd: RawFd, len: usize, offset: libc::off_t) -> io::Result<Self> {
+    // SAFETY: Maps memory from the kernel. Nothing is overwritten.
+    let addr = unsafe {
+      libc::mmap(
+        ptr::null_mut(),
+        len,
+        libc::PROT_READ | libc::PROT_WRITE,
+        libc::MAP_SHARED | libc::MAP_POPULATE,
+        fd,
+        offset,
+      )
+    };
+    if addr == libc::MAP_FAILED {
+      Err(io::Error::last_os_error())
+    } else {
+      Ok(Self { addr, len })
+    }
+  }
+
+  // Pointer to a field at the offset the kernel gave
+  fn at<T>(&self, offset: u32) -> *mut T {
+    // SAFETY: The kernel-provided offsets are within the mapping.
+    unsafe { self.addr.cast::<u8>().add(offset as usize).cast() }
+  }
+
+  // A ring head or tail index, which the kernel also accesses
+  fn atomic_at(&self, offset: u32) -> &AtomicU32 {
+    // SAFETY: The offset is a kernel-provided offset of an aligned u32 within
+    // the mapping, which lives as long as self.
+    unsafe { &*self.at::<AtomicU32>(offset) }
+  }
+}
+
+impl Drop for Mmap {
+  fn drop(&mut self) {
+    // SAFETY: addr and len are from a successful mmap.
+    unsafe {
+      libc::munmap(self.addr, self.len);
+    }
+  }
+}
+
+pub(crate) struct IoUring {
+  sq_ring: Mmap,
+  cq_ring: Mmap,
+  sqes: Mmap,
+  sq_entries: u32,
+  sq_off: SqRingOffsets,
+  cq_off: CqRingOffsets,
+  // Declared last, so that it is closed after the mappings are gone
+  fd: OwnedFd,
+}
+
+// SAFETY: The ring memory is only accessed through &mut self.
+unsafe impl Send for IoUring {}
+
+impl std::fmt::Debug for IoUring {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("IoUring")
+      .field("fd", &self.fd)
+      .field("sq_entries", &self.sq_entries)
+      .finish()
+  }
+}
+
+impl IoUring {
+  // The kernel rounds entries up to a power of two.
+  pub(crate) fn new(entries: u32) -> io::Result<Self> {
+    let mut params = Params::default();
+    // SAFETY: params is a valid io_uring_params structure.
+    let fd = unsafe {
+      libc::syscall(
+        libc::SYS_io_uring_setup,
+        entries,
+        &mut params as *mut Params,
+      )
+    };
+    if fd < 0 {
+      return Err(io::Error::last_os_error());
+    }
+    // SAFETY: The kernel just gave us this file descriptor.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd as RawFd) };
+
+    let raw_fd = fd.as_raw_fd();
+    let sq_ring = Mmap::new(
+      raw_fd,
+      params.sq_off.array as usize + params.sq_entries as usize * mem::size_of::<u32>(),
+      IORING_OFF_SQ_RING,
+    )?;
+    let cq_ring = Mmap::new(
+      raw_fd,
+      params.cq_off.cqes as usize + params.cq_entries as usize * mem::size_of::<Cqe>(),
+      IORING_OFF_CQ_RING,
+    )?;
+    let sqes = Mmap::new(
+      raw_fd,
+      params.sq_entries as usize * mem::size_of::<Sqe>(),
+      IORING_OFF_SQES,
+    )?;
+
+    Ok(Self {
+      sq_ring,
+      cq_ring,
+      sqes,
+      sq_entries: params.sq_entries,
+      sq_off: params.sq_off,
+      cq_off: params.cq_off,
+      fd,
+    })
+  }
+
+  // Submits the operations, and waits until they have all completed. Returns
+  // the result of each operation, in the same order. At most sq_entries
+  // operations at a time. If submitting fails, the operations not yet
+  // submitted are withdrawn, and the error is returned once the submitted ones
+  // have completed.
+  //
+  // Unsafe, because the kernel accesses the memory that the operations point
+  // to. It must be valid for the operation until this returns.
+  unsafe fn submit_and_wait(&mut self, operations: Vec<Sqe>) -> io::Result<Vec<i32>> {
+    let count = operations.len();
+    assert!(count <= self.sq_entries as usize);
+
+    // Fill in the submission queue
+    let sq_mask = unsafe { *self.sq_ring.at::<u32>(self.sq_off.ring_mask) };
+    let sq_array = self.sq_ring.at::<u32>(self.sq_off.array);
+    let sq_tail = self.sq_ring.atomic_at(self.sq_off.tail);
+    let mut tail = sq_tail.load(Ordering::Relaxed); // only we write tail
+    for (index, mut sqe) in operations.into_iter().enumerate() {
+      sqe.user_data = index as u64;
+      let slot = tail & sq_mask;
+      // SAFETY: slot is within the submission queue, and the kernel has
+      // consumed the previous entry in it, because we always wait for all
+      // completions.
+      unsafe {
+        ptr::write(self.sqes.at::<Sqe>(0).add(slot as usize), sqe);
+        ptr::write(sq_array.add(slot as usize), slot);
+      }
+      tail = tail.wrapping_add(1);
+    }
+    sq_tail.store(tail, Ordering::Release);
+
+    let cq_mask = unsafe { *self.cq_ring.at::<u32>(self.cq_off.ring_mask) };
+    let cqes = self.cq_ring.at::<Cqe>(self.cq_off.cqes);
+    let cq_head = self.cq_ring.atomic_at(self.cq_off.head);
+    let cq_tail = self.cq_ring.atomic_at(self.cq_off.tail);
+
+    let mut results = vec![0; count];
+    let mut count = count;
+    let mut to_submit = count;
+    let mut completed = 0;
+    let mut error = None;
+    while completed < count {
+      // SAFETY: Plain system call
+      let ret = unsafe {
+        libc::syscall(
+          libc::SYS_io_uring_enter,
+          self.fd.as_raw_fd(),
+          to_submit as libc::c_uint,
+          (count - completed) as libc::c_uint,
+          IORING_ENTER_GETEVENTS,
+          ptr::null::<libc::sigset_t>(),
+          0usize,
+        )
+      };
+      if ret < 0 {
+        let e = io::Error::last_os_error();
+        if matches!(
+          e.raw_os_error(),
+          Some(libc::EINTR) | Some(libc::EAGAIN) | Some(libc::EBUSY)
+        ) {
+          std::thread::yield_now();
+          continue;
+        }
+        if to_submit > 0 {
+          // The kernel has not consumed these, so we may take them back.
+          tail = tail.wrapping_sub(to_submit as u32);
+          sq_tail.store(tail, Ordering::Release);
+          count -= to_submit;
+          to_submit = 0;
+          error = Some(e);
+        } else {
+          // Only waiting failed, which does not happen with a valid ring. The
+          // submitted operations point to caller memory, so we cannot return
+          // before they have completed.
+          error!("io_uring_enter failed while waiting: {e:?}");
+          std::thread::yield_now();
+        }
+        continue;
+      }
+      to_submit -= ret as usize;
+
+      let mut head = cq_head.load(Ordering::Relaxed); // only we write head
+      let cq_end = cq_tail.load(Ordering::Acquire);
+      while head != cq_end {
+        // SAFETY: The kernel has written the entries between head and tail.
+        let cqe = unsafe { &*cqes.add((head & cq_mask) as usize) };
+        if let Some(result) = results.get_mut(cqe.user_data as usize) {
+          *result = cqe.res;
+        }
+        completed += 1;
+        head = head.wrapping_add(1);
+      }
+      cq_head.store(head, Ordering::Release);
+    }
+    match error {
+      Some(e) => Err(e),
+      None => Ok(results),
+    }
+  }
+
+  // How many operations can be submitted at once
+  pub(crate) fn capacity(&self) -> usize {
+    self.sq_entries as usize
+  }
+
+  /// Sends each message with `sendmsg` on the corresponding socket. Returns
+  /// the number of bytes sent for each message, or the error.
+  ///
+  /// # Safety
+  ///
+  /// The message headers and everything they point to, i.e. the addresses,
+  /// iovecs, and buffers, must be valid for `sendmsg`.
+  pub(crate) unsafe fn send_messages(
+    &mut self,
+    messages: &[(RawFd, libc::msghdr)],
+  ) -> io::Result<Vec<io::Result<usize>>> {
+    let mut results = Vec::with_capacity(messages.len());
+    for chunk in messages.chunks(self.capacity()) {
+      let operations = chunk
+        .iter()
+        .map(|(fd, header)| Sqe {
+          opcode: IORING_OP_SENDMSG,
+          fd: *fd,
+          addr: header as *const libc::msghdr as u64,
+          len: 1,
+          msg_flags: libc::MSG_DONTWAIT as u32,
+          ..Sqe::default()
+        })
+        .collect();
+      // SAFETY: The headers are valid, as required from our caller.
+      let chunk_results = unsafe { self.submit_and_wait(operations)? };
+      results.extend(chunk_results.into_iter().map(result_to_io_result));
+    }
+    Ok(results)
+  }
+
+  /// Receives datagrams from the socket with `recvmsg`, into the headers in
+  /// turn, until the socket is empty. Sets `msg_len` of each filled header,
+  /// and returns how many were filled, like `recvmmsg`. Returns
+  /// `WouldBlock` if there was nothing to receive.
+  ///
+  /// # Safety
+  ///
+  /// The message headers and everything they point to, i.e. the address
+  /// buffers, iovecs, and receive buffers, must be valid for `recvmsg`.
+  pub(crate) unsafe fn receive_messages(
+    &mut self,
+    fd: RawFd,
+    headers: &mut [libc::mmsghdr],
+  ) -> io::Result<usize> {
+    let count = headers.len().min(self.capacity());
+    // Linked, so that they are received in order, and the rest are cancelled
+    // when the socket is empty.
+    let operations = headers[..count]
+      .iter_mut()
+      .enumerate()
+      .map(|(index, header)| Sqe {
+        opcode: IORING_OP_RECVMSG,
+        flags: if index + 1 < count { IOSQE_IO_LINK } else { 0 },
+        fd,
+        addr: &mut header.msg_hdr as *mut libc::msghdr as u64,
+        len: 1,
+        msg_flags: libc::MSG_DONTWAIT as u32,
+        ..Sqe::default()
+      })
+      .collect();
+
+    // SAFETY: The headers are valid, as required from our caller.
+    let results = unsafe { self.submit_and_wait(operations)? };
+    let mut received = 0;
+    for result in results {
+      match result_to_io_result(result) {
+        Ok(nbytes) => {
+          headers[received].msg_len = nbytes as libc::c_uint;
+          received += 1;
+        }
+        Err(e) if received == 0 => return Err(e),
+        Err(_) => break, // Socket is empty, and the rest were cancelled
+      }
+    }
+    Ok(received)
+  }
+}
+
+fn result_to_io_result(res: i32) -> io::Result<usize> {
+  if res < 0 {
+    Err(io::Error::from_raw_os_error(-res))
+  } else {
+    Ok(res as usize)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::net::UdpSocket;
+
+  use super::*;
+
+  fn header(iovec: &mut libc::iovec, address: &mut libc::sockaddr_storage) -> libc::msghdr {
+    // SAFETY: All-zero msghdr is valid.
+    let mut header: libc::msghdr = unsafe { mem::zeroed() };
+    header.msg_name = (address as *mut libc::sockaddr_storage).cast();
+    header.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    header.msg_iov = iovec;
+    header.msg_iovlen = 1;
+    header
+  }
+
+  #[test]
+  fn io_uring_send_and_receive() {
+    let mut ring = match IoUring::new(4) {
+      Ok(ring) => ring,
+      // Not an error: RustDDS falls back to plain system calls.
+      Err(_e) => return,
+    };
+
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    receiver.set_nonblocking(true).unwrap();
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let destination = socket2::SockAddr::from(receiver.local_addr().unwrap());
+
+    // Send 6 datagrams, which takes two rounds
+    let mut payloads: Vec<Vec<u8>> = (1..=6u8).map(|n| vec![n; n as usize]).collect();
+    let mut iovecs: Vec<libc::iovec> = payloads
+      .iter_mut()
+      .map(|p| libc::iovec {
+        iov_base: p.as_mut_ptr().cast(),
+        iov_len: p.len(),
+      })
+      .collect();
+    let messages: Vec<(RawFd, libc::msghdr)> = iovecs
+      .iter_mut()
+      .map(|iovec| {
+        // SAFETY: All-zero msghdr is valid.
+        let mut header: libc::msghdr = unsafe { mem::zeroed() };
+        header.msg_name = destination.as_ptr() as *mut libc::c_void;
+        header.msg_namelen = destination.len();
+        header.msg_iov = iovec;
+        header.msg_iovlen = 1;
+        (sender.as_raw_fd(), header)
+      })
+      .collect();
+    // SAFETY: The headers point to destination, iovecs, and payloads, which
+    // are all alive here.
+    let sent = unsafe { ring.send_messages(&messages) }.unwrap();
+    let sent: Vec<usize> = sent.into_iter().map(|r| r.unwrap()).collect();
+    assert_eq!(sent, vec![1, 2, 3, 4, 5, 6]);
+
+    // Receive them in order, at most 4 at a time. Loopback delivery is
+    // synchronous, so they are all waiting already.
+    let mut buffers = vec![[0u8; 16]; 4];
+    let mut addresses: Vec<libc::sockaddr_storage> = vec![unsafe { mem::zeroed() }; 4];
+    let mut received = Vec::new();
+    loop {
+      let mut iovecs: Vec<libc::iovec> = buffers
+        .iter_mut()
+        .map(|b| libc::iovec {
+          iov_base: b.as_mut_ptr().cast(),
+          iov_len: b.len(),
+        })
+        .collect();
+      let mut headers: Vec<libc::mmsghdr> = iovecs
+        .iter_mut()
+        .zip(addresses.iter_mut())
+        .map(|(iovec, address)| libc::mmsghdr {
+          msg_hdr: header(iovec, address),
+          msg_len: 0,
+        })
+        .collect();
+      // SAFETY: The headers point to iovecs, addresses, and buffers, which are
+      // all alive here.
+      match unsafe { ring.receive_messages(receiver.as_raw_fd(), &mut headers) } {
+        Ok(count) => {
+          for (header, buffer) in headers.iter().zip(&buffers).take(count) {
+            received.push(buffer[..header.msg_len as usize].to_vec());
+          }
+        }
+        Err(e) => {
+          assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+          break;
+        }
+      }
+    }
+    assert_eq!(received, payloads);
+  }
+}
@@ -0,0 +1,263 @@
+// Rate limiting of received messages per source address, see
+// MessageReceiverLimits::source_rate_limit.
+//
+// Each source IP address has a token bucket for messages and another for
+// bytes. A bucket holds at most one second worth of its rate, so short bursts
+// pass. A source that exceeds either rate is blocked, i.e. all of its messages
+// are dropped, for a while. The limits apply per address, not per port, so
+// that a peer cannot get around them by sending from several sockets.
+//
+// The number of tracked sources is bounded, so that spoofed source addresses
+// cannot exhaust memory. When a new source arrives and the table is full, the
+// sources that are idle are forgotten, but at most once per SWEEP_INTERVAL,
+// because that goes through the whole table. If none are idle, the least
+// recently updated source is forgotten.
+
+use std::{
+  collections::{BTreeSet, HashMap},
+  net::IpAddr,
+  time::{Duration, Instant},
+};
+
+// How many sources are tracked at most
+const MAX_TRACKED_SOURCES: usize = 4096;
+// How often idle sources are looked for at most
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Limits on the rate of messages received from a single source IP address,
+/// see
+/// [`MessageReceiverLimits::source_rate_limit`](crate::MessageReceiverLimits::source_rate_limit).
+///
+/// Short bursts of up to one second worth of either rate are allowed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourceRateLimit {
+  /// Messages (UDP datagrams) per second
+  pub max_messages_per_second: u32,
+  /// Bytes per second
+  pub max_bytes_per_second: u64,
+  /// How long a source is blocked after exceeding either limit. All messages
+  /// from a blocked source are dropped. Zero drops only the messages that
+  /// exceed the limits.
+  pub block_duration: Duration,
+}
+
+impl Default for SourceRateLimit {
+  fn default() -> Self {
+    Self {
+      max_messages_per_second: 10_000,
+      max_bytes_per_second: 64 * 1024 * 1024,
+      block_duration: Duration::from_secs(10),
+    }
+  }
+}
+
+// What to do with a received message
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Admission {
+  Admit,
+  // The source exceeded its limits, and is blocked from now on
+  Block,
+  Drop,
+}
+
+#[derive(Debug)]
+struct SourceState {
+  // Tokens in the buckets
+  messages: f64,
+  bytes: f64,
+  updated: Instant,
+  blocked_until: Option<Instant>,
+}
+
+impl SourceState {
+  // Bucket contents at `now`
+  fn refilled(&self, limit: &SourceRateLimit, now: Instant) -> (f64, f64) {
+    let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+    let max_messages = f64::from(limit.max_messages_per_second);
+    let max_bytes = limit.max_bytes_per_second as f64;
+    (
+      (self.messages + elapsed * max_messages).min(max_messages),
+      (self.bytes + elapsed * max_bytes).min(max_bytes),
+    )
+  }
+
+  fn is_idle(&self, limit: &SourceRateLimit, now: Instant) -> bool {
+    let (messages, bytes) = self.refilled(limit, now);
+    self.blocked_until.map_or(true, |until| until <= now)
+      && messages >= f64::from(limit.max_messages_per_second)
+      && bytes >= limit.max_bytes_per_second as f64
+  }
+}
+
+#[derive(Debug)]
+pub(crate) struct SourceRateLimiter {
+  limit: SourceRateLimit,
+  sources: HashMap<IpAddr, SourceState>,
+  // The tracked sources by SourceState::updated, least recently updated first
+  by_update: BTreeSet<(Instant, IpAddr)>,
+  last_sweep: Option<Instant>,
+}
+
+impl SourceRateLimiter {
+  pub fn new(limit: SourceRateLimit) -> Self {
+    Self {
+      limit,
+      sources: HashMap::new(),
+      by_update: BTreeSet::new(),
+      last_sweep: None,
+    }
+  }
+
+  pub fn block_duration(&self) -> Duration {
+    self.limit.block_duration
+  }
+
+  pub fn admit(&mut self, source: IpAddr, bytes: usize, now: Instant) -> Admission {
+    let limit = self.limit;
+    if self.sources.len() >= MAX_TRACKED_SOURCES && !self.sources.contains_key(&source) {
+      self.make_room(now);
+    }
+    let state = self.sources.entry(source).or_insert_with(|| SourceState {
+      messages: f64::from(limit.max_messages_per_second),
+      bytes: limit.max_bytes_per_second as f64,
+      updated: now,
+      blocked_until: None,
+    });
+    self.by_update.remove(&(state.updated, source));
+    (state.messages, state.bytes) = state.refilled(&limit, now);
+    state.updated = now;
+    self.by_update.insert((now, source));
+
+    if let Some(until) = state.blocked_until {
+      if now < until {
+        return Admission::Drop;
+      }
+      state.blocked_until = None;
+    }
+
+    if state.messages < 1.0 || state.bytes < bytes as f64 {
+      if limit.block_duration.is_zero() {
+        return Admission::Drop;
+      }
+      state.blocked_until = Some(now + limit.block_duration);
+      return Admission::Block;
+    }
+    state.messages -= 1.0;
+    state.bytes -= bytes as f64;
+    Admission::Admit
+  }
+
+  // Forgets the idle sources, if they were not looked for recently, and if
+  // that is not enough, the least recently updated source.
+  fn make_room(&mut self, now: Instant) {
+    let swept_recently = self
+      .last_sweep
+      .is_some_and(|last| now.saturating_duration_since(last) < SWEEP_INTERVAL);
+    if !swept_recently {
+      self.last_sweep = Some(now);
+      let limit = self.limit;
+      let by_update = &mut self.by_update;
+      self.sources.retain(|source, state| {
+        let idle = state.is_idle(&limit, now);
+        if idle {
+          by_update.remove(&(state.updated, *source));
+        }
+        !idle
+      });
+    }
+    if self.sources.len() >= MAX_TRACKED_SOURCES {
+      if let Some((_, source)) = self.by_update.pop_first() {
+        self.sources.remove(&source);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn sources_are_limited_and_blocked() {
+    let mut limiter = SourceRateLimiter::new(SourceRateLimit {
+      max_messages_per_second: 10,
+      max_bytes_per_second: 1000,
+      block_duration: Duration::from_secs(5),
+    });
+    let flooder: IpAddr = "192.0.2.1".parse().unwrap();
+    let other: IpAddr = "192.0.2.2".parse().unwrap();
+    let start = Instant::now();
+
+    // The burst allowance
+    for _ in 0..10 {
+      assert_eq!(limiter.admit(flooder, 10, start), Admission::Admit);
+    }
+    assert_eq!(limiter.admit(flooder, 10, start), Admission::Block);
+    // Blocked even after the rate would allow it again
+    let later = start + Duration::from_secs(1);
+    assert_eq!(limiter.admit(flooder, 10, later), Admission::Drop);
+    // Others are not affected
+    assert_eq!(limiter.admit(other, 10, later), Admission::Admit);
+    // Too many bytes is blocked the same way
+    assert_eq!(limiter.admit(other, 1000, later), Admission::Block);
+
+    let unblocked = start + Duration::from_secs(5);
+    assert_eq!(limiter.admit(flooder, 10, unblocked), Admission::Admit);
+
+    // Without blocking, only the excess is dropped
+    let mut limiter = SourceRateLimiter::new(SourceRateLimit {
+      block_duration: Duration::ZERO,
+      ..SourceRateLimit::default()
+    });
+    assert_eq!(limiter.admit(flooder, 100_000_000, start), Admission::Drop);
+    assert_eq!(limiter.admit(flooder, 10, start), Admission::Admit);
+  }
+
+  #[test]
+  fn idle_sources_are_forgotten() {
+    let mut limiter = SourceRateLimiter::new(SourceRateLimit::default());
+    let start = Instant::now();
+    for i in 0..MAX_TRACKED_SOURCES as u32 {
+      limiter.admit(IpAddr::from(i.to_be_bytes()), 10, start);
+    }
+    assert_eq!(limiter.sources.len(), MAX_TRACKED_SOURCES);
+    let later = start + Duration::from_secs(1);
+    limiter.admit("192.0.2.1".parse().unwrap(), 10, later);
+    assert_eq!(limiter.sources.len(), 1);
+    assert_eq!(limiter.by_update.len(), 1);
+  }
+
+  #[test]
+  fn least_recently_updated_source_is_forgotten() {
+    let mut limiter = SourceRateLimiter::new(SourceRateLimit {
+      max_messages_per_second: 1,
+      ..SourceRateLimit::default()
+    });
+    let start = Instant::now();
+    let source = |i: u32| IpAddr::from(i.to_be_bytes());
+    for i in 0..MAX_TRACKED_SOURCES as u32 {
+      let now = start + Duration::from_micros(i.into());
+      assert_eq!(limiter.admit(source(i), 10, now), Admission::Admit);
+    }
+
+    // None are idle, so the first one is forgotten
+    let now = start + Duration::from_millis(10);
+    let new = "192.0.2.1".parse().unwrap();
+    assert_eq!(limiter.admit(new, 10, now), Admission::Admit);
+    assert_eq!(limiter.sources.len(), MAX_TRACKED_SOURCES);
+    assert!(!limiter.sources.contains_key(&source(0)));
+    assert_eq!(limiter.last_sweep, Some(now));
+    // The new source is tracked and limited
+    assert_eq!(limiter.admit(new, 10, now), Admission::Block);
+
+    // Then the next one, without looking for idle sources again
+    let next = start + Duration::from_millis(20);
+    assert_eq!(
+      limiter.admit("192.0.2.2".parse().unwrap(), 10, next),
+      Admission::Admit
+    );
+    assert!(!limiter.sources.contains_key(&source(1)));
+    assert_eq!(limiter.last_sweep, Some(now));
+    assert_eq!(limiter.by_update.len(), MAX_TRACKED_SOURCES);
+  }
+}
@@ -0,0 +1,205 @@
+use std::{
+  io,
+  net::{IpAddr, Ipv4Addr},
+};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use socket2::Socket;
+
+use crate::{network::util::get_local_multicast_ip_addrs, structure::locator::Locator};
+
+/// Tuning options for the UDP sockets of a DomainParticipant.
+///
+/// All options are `None` by default, which means that operating system
+/// defaults (or RustDDS built-in behaviour) are used. Use
+/// [`DomainParticipantBuilder::socket_options`](crate::DomainParticipantBuilder::socket_options)
+/// to apply these.
+///
+/// Note that the operating system may silently limit buffer sizes. E.g. on
+/// Linux the upper limits are set by `net.core.rmem_max` and
+/// `net.core.wmem_max`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SocketOptions {
+  /// Size of the kernel receive buffer (`SO_RCVBUF`) of listening sockets.
+  pub receive_buffer_size: Option<usize>,
+  /// Size of the kernel send buffer (`SO_SNDBUF`) of sending sockets.
+  pub send_buffer_size: Option<usize>,
+  /// Time-to-live of sent multicast packets. The operating system default is
+  /// usually 1, i.e. multicast stays in the local network segment.
+  pub multicast_ttl: Option<u32>,
+  /// Should sent multicast packets loop back to the sending host. Default is
+  /// `true`, so that DomainParticipants on the same host can hear each other.
+  pub multicast_loop: Option<bool>,
+  /// Set `SO_REUSEPORT` also on unicast listening sockets, so that several
+  /// processes can bind to the same port. Multicast listening sockets always
+  /// have this set. The unicast discovery socket never has it, because its
+  /// port selects the participant id. No effect on Windows, Solaris, or
+  /// illumos.
+  pub reuse_port: Option<bool>,
+  /// Network interfaces (by IPv4 address) to use for multicast. If `None`,
+  /// all non-loopback interfaces are used.
+  pub multicast_interfaces: Option<Vec<Ipv4Addr>>,
+  /// How many datagrams a listening socket may read with one system call
+  /// (`recvmmsg`), e.g. 8 for high message rates. Each call needs 64 KiB of
+  /// receive buffer per datagram. Default is 1, i.e. one datagram at a time.
+  /// Only has an effect on Linux, elsewhere datagrams are always read one at
+  /// a time.
+  pub receive_batch_size: Option<usize>,
+  /// Read each listening socket in a dedicated thread, instead of the event
+  /// loop thread. This lets the kernel socket buffer drain while the event
+  /// loop is busy processing messages, which helps keep up with high message
+  /// rates. Default is `false`.
+  pub receive_threads: Option<bool>,
+  /// Redundant network interfaces (by IPv4 address), e.g. one on each of two
+  /// independent LANs. Every message is then transmitted over each of them:
+  /// the participant announces unicast locators only on these interfaces, so
+  /// that remote participants send to all of them, and multicast is sent and
+  /// received on each of them, unless `multicast_interfaces` is also given.
+  /// Receivers discard the extra copies by writer GUID and sequence number, so
+  /// traffic continues without interruption if one network fails.
+  ///
+  /// Unicast destinations are routed by the operating system, so the
+  /// networks should be in different IP subnets.
+  pub redundant_interfaces: Option<Vec<Ipv4Addr>>,
+}
+
+impl SocketOptions {
+  // Interfaces for joining multicast groups and sending multicast
+  pub(crate) fn multicast_ip_addrs(&self) -> io::Result<Vec<IpAddr>> {
+    match self
+      .multicast_interfaces
+      .as_ref()
+      .or(self.redundant_interfaces.as_ref())
+    {
+      Some(ifs) => Ok(ifs.iter().map(|a| IpAddr::V4(*a)).collect()),
+      None => get_local_multicast_ip_addrs(),
+    }
+  }
+
+  // Locators to announce for a listening socket. With redundant interfaces,
+  // unicast locators on other interfaces are left out.
+  pub(crate) fn announced_locators(&self, locators: Vec<Locator>) -> Vec<Locator> {
+    match self.redundant_interfaces {
+      Some(ref ifs) => locators
+        .into_iter()
+        .filter(|loc| match loc {
+          Locator::UdpV4(addr) => addr.ip().is_multicast() || ifs.contains(addr.ip()),
+          _ => false,
+        })
+        .collect(),
+      None => locators,
+    }
+  }
+
+  pub(crate) fn multicast_loop(&self) -> bool {
+    self.multicast_loop.unwrap_or(true)
+  }
+
+  pub(crate) fn receive_batch_size(&self) -> usize {
+    self.receive_batch_size.unwrap_or(1)
+  }
+
+  pub(crate) fn receive_threads(&self) -> bool {
+    self.receive_threads.unwrap_or(false)
+  }
+
+  // Options for listening sockets. This must be called before bind.
+  pub(crate) fn apply_to_listener(&self, socket: &Socket) {
+    if let Some(size) = self.receive_buffer_size {
+      socket
+        .set_recv_buffer_size(size)
+        .unwrap_or_else(|e| warn!("Cannot set receive buffer size to {size}: {e:?}"));
+    }
+
+    #[cfg(not(any(target_os = "solaris", target_os = "illumos", windows)))]
+    {
+      if self.reuse_port == Some(true) {
+        socket
+          .set_reuse_port(true)
+          .unwrap_or_else(|e| warn!("Cannot set SO_REUSEPORT: {e:?}"));
+      }
+    }
+  }
+
+  // Options for sending sockets, unicast and multicast
+  pub(crate) fn apply_to_sender(&self, socket: &Socket) {
+    if let Some(size) = self.send_buffer_size {
+      socket
+        .set_send_buffer_size(size)
+        .unwrap_or_else(|e| warn!("Cannot set send buffer size to {size}: {e:?}"));
+    }
+    if let Some(ttl) = self.multicast_ttl {
+      socket
+        .set_multicast_ttl_v4(ttl)
+        .unwrap_or_else(|e| warn!("Cannot set multicast TTL to {ttl}: {e:?}"));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::net::SocketAddr;
+
+  use socket2::{Domain, Protocol, Type};
+
+  use super::*;
+
+  #[test]
+  fn socket_options_set_buffer_sizes() {
+    let options = SocketOptions {
+      receive_buffer_size: Some(64 * 1024),
+      send_buffer_size: Some(64 * 1024),
+      ..SocketOptions::default()
+    };
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)).unwrap();
+    options.apply_to_listener(&socket);
+    options.apply_to_sender(&socket);
+    // Operating systems may round the size up (Linux doubles it), but not below
+    // what was asked, unless limited by system configuration.
+    assert!(socket.recv_buffer_size().unwrap() >= 64 * 1024);
+    assert!(socket.send_buffer_size().unwrap() >= 64 * 1024);
+  }
+
+  #[test]
+  fn socket_options_multicast_interfaces() {
+    let options = SocketOptions {
+      multicast_interfaces: Some(vec![Ipv4Addr::new(192, 168, 1, 10)]),
+      ..SocketOptions::default()
+    };
+    assert_eq!(
+      options.multicast_ip_addrs().unwrap(),
+      vec![IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10))]
+    );
+    assert!(options.multicast_loop());
+  }
+
+  #[test]
+  fn socket_options_redundant_interfaces() {
+    let lan_a = Ipv4Addr::new(10, 0, 1, 5);
+    let lan_b = Ipv4Addr::new(10, 0, 2, 5);
+    let options = SocketOptions {
+      redundant_interfaces: Some(vec![lan_a, lan_b]),
+      ..SocketOptions::default()
+    };
+    assert_eq!(
+      options.multicast_ip_addrs().unwrap(),
+      vec![IpAddr::V4(lan_a), IpAddr::V4(lan_b)]
+    );
+
+    let locators = vec![
+      Locator::from(SocketAddr::from((lan_a, 7410))),
+      Locator::from(SocketAddr::from((Ipv4Addr::new(192, 168, 1, 10), 7410))),
+      Locator::from(SocketAddr::from((lan_b, 7410))),
+      Locator::from(SocketAddr::from((Ipv4Addr::new(239, 255, 0, 1), 7400))),
+    ];
+    assert_eq!(
+      options.announced_locators(locators.clone()),
+      vec![locators[0], locators[2], locators[3]]
+    );
+    assert_eq!(
+      SocketOptions::default().announced_locators(locators.clone()),
+      locators
+    );
+  }
+}
@@ -1,16 +1,28 @@
 use std::{
+  collections::VecDeque,
   io,
   net::{IpAddr, Ipv4Addr, SocketAddr},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  thread::{self, JoinHandle},
+  time::Duration,
 };
 
 use log::{debug, error, info, trace, warn};
 use socket2::{Domain, Protocol, SockAddr, Socket, Type};
 use bytes::{Bytes, BytesMut};
+use mio_extras::channel as mio_channel;
 
+#[cfg(all(target_os = "linux", feature = "io_uring"))]
+use crate::network::io_uring::IoUring;
 use crate::{
-  network::util::{
-    get_local_multicast_ip_addrs, get_local_multicast_locators, get_local_unicast_locators,
+  network::{
+    socket_options::SocketOptions,
+    util::{get_local_multicast_locators, get_local_unicast_locators},
   },
+  stats,
   structure::locator::Locator,
 };
 
@@ -18,16 +30,36 @@ const MAX_MESSAGE_SIZE: usize = 64 * 1024; // This is max we can get from UDP.
 const MESSAGE_BUFFER_ALLOCATION_CHUNK: usize = 256 * 1024; // must be >= MAX_MESSAGE_SIZE
 static_assertions::const_assert!(MESSAGE_BUFFER_ALLOCATION_CHUNK > MAX_MESSAGE_SIZE);
 
+// How long a receive thread waits for datagrams before checking if it should
+// stop
+const RECEIVE_THREAD_POLL_INTERVAL: Duration = Duration::from_millis(100);
+// How many batches of datagrams a receive thread may have queued for the event
+// loop. When the queue is full, the thread stops reading, and the datagrams
+// queue up in the socket instead.
+const RECEIVE_THREAD_QUEUE_LENGTH: usize = 64;
+
 /// Listens to messages coming to specified host port combination.
 /// Only messages from added listen addressed are read when get_all_messages is
 /// called.
 #[derive(Debug)]
 pub struct UDPListener {
+  // Used for poll registration and sending
   socket: mio_06::net::UdpSocket,
-  receive_buffer: BytesMut,
+  receiving: Receiving,
   multicast_group: Option<Ipv4Addr>,
 }
 
+#[derive(Debug)]
+enum Receiving {
+  // The event loop reads the socket
+  Direct {
+    socket: std::net::UdpSocket, // a clone of the mio socket
+    reader: DatagramReader,
+  },
+  // A dedicated thread reads the socket and forwards the datagrams
+  Thread(ReceiveThread),
+}
+
 impl Drop for UDPListener {
   fn drop(&mut self) {
     if let Some(mcg) = self.multicast_group {
@@ -46,8 +78,10 @@ impl UDPListener {
     host: &str,
     port: u16,
     reuse_addr: bool,
-  ) -> io::Result<mio_06::net::UdpSocket> {
+    options: &SocketOptions,
+  ) -> io::Result<std::net::UdpSocket> {
     let raw_socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    options.apply_to_listener(&raw_socket);
 
     // We set ReuseAddr so that other DomainParticipants on this host can
     // bind to the same multicast address and port.
@@ -81,15 +115,39 @@ impl UDPListener {
     std_socket
       .set_nonblocking(true)
       .expect("Failed to set std socket to non blocking.");
-
-    let mio_socket =
-      mio_06::net::UdpSocket::from_socket(std_socket).expect("Unable to create mio socket");
     info!(
       "UDPListener: new socket with address {:?}",
-      mio_socket.local_addr()
+      std_socket.local_addr()
     );
 
-    Ok(mio_socket)
+    Ok(std_socket)
+  }
+
+  // Sets up receiving from the socket according to options
+  fn new_with_socket(
+    std_socket: std::net::UdpSocket,
+    multicast_group: Option<Ipv4Addr>,
+    options: &SocketOptions,
+  ) -> io::Result<Self> {
+    let receive_socket = std_socket.try_clone()?;
+    let socket =
+      mio_06::net::UdpSocket::from_socket(std_socket).expect("Unable to create mio socket");
+    let reader = DatagramReader::new(options.receive_batch_size());
+    #[cfg(all(target_os = "linux", feature = "io_uring"))]
+    let reader = reader.with_io_uring();
+    let receiving = if options.receive_threads() {
+      Receiving::Thread(ReceiveThread::start(receive_socket, reader)?)
+    } else {
+      Receiving::Direct {
+        socket: receive_socket,
+        reader,
+      }
+    };
+    Ok(Self {
+      socket,
+      receiving,
+      multicast_group,
+    })
   }
 
   pub fn to_locator_address(&self) -> io::Result<Vec<Locator>> {
@@ -101,17 +159,31 @@ impl UDPListener {
     }
   }
 
+  #[cfg(test)]
   pub fn new_unicast(host: &str, port: u16) -> io::Result<Self> {
-    let mio_socket = Self::new_listening_socket(host, port, false)?;
+    Self::new_unicast_with_options(host, port, &SocketOptions::default())
+  }
 
-    Ok(Self {
-      socket: mio_socket,
-      receive_buffer: BytesMut::with_capacity(MESSAGE_BUFFER_ALLOCATION_CHUNK),
-      multicast_group: None,
-    })
+  pub fn new_unicast_with_options(
+    host: &str,
+    port: u16,
+    options: &SocketOptions,
+  ) -> io::Result<Self> {
+    let std_socket = Self::new_listening_socket(host, port, false, options)?;
+    Self::new_with_socket(std_socket, None, options)
   }
 
+  #[cfg(test)]
   pub fn new_multicast(host: &str, port: u16, multicast_group: Ipv4Addr) -> io::Result<Self> {
+    Self::new_multicast_with_options(host, port, multicast_group, &SocketOptions::default())
+  }
+
+  pub fn new_multicast_with_options(
+    host: &str,
+    port: u16,
+    multicast_group: Ipv4Addr,
+    options: &SocketOptions,
+  ) -> io::Result<Self> {
     if !multicast_group.is_multicast() {
       return io::Result::Err(io::Error::new(
         io::ErrorKind::Other,
@@ -119,11 +191,11 @@ impl UDPListener {
       ));
     }
 
-    let mio_socket = Self::new_listening_socket(host, port, true)?;
+    let std_socket = Self::new_listening_socket(host, port, true, options)?;
 
-    for multicast_if_ipaddr in get_local_multicast_ip_addrs()? {
+    for multicast_if_ipaddr in options.multicast_ip_addrs()? {
       match multicast_if_ipaddr {
-        IpAddr::V4(a) => mio_socket
+        IpAddr::V4(a) => std_socket
           .join_multicast_v4(&multicast_group, &a)
           .unwrap_or_else(|e| {
             warn!(
@@ -135,17 +207,32 @@ impl UDPListener {
       }
     }
 
-    Ok(Self {
-      socket: mio_socket,
-      receive_buffer: BytesMut::with_capacity(MESSAGE_BUFFER_ALLOCATION_CHUNK),
-      multicast_group: Some(multicast_group),
-    })
+    Self::new_with_socket(std_socket, Some(multicast_group), options)
   }
 
+  #[cfg(test)]
   pub fn mio_socket(&mut self) -> &mut mio_06::net::UdpSocket {
     &mut self.socket
   }
 
+  /// Register to a poll for notification of received messages. This is
+  /// either the socket itself, or the queue from its receive thread.
+  pub fn register(&mut self, poll: &mio_06::Poll, token: mio_06::Token) -> io::Result<()> {
+    let ready = mio_06::Ready::readable();
+    let edge = mio_06::PollOpt::edge();
+    match &self.receiving {
+      Receiving::Direct { .. } => poll.register(&self.socket, token, ready, edge),
+      Receiving::Thread(thread) => poll.register(&thread.datagrams, token, ready, edge),
+    }
+  }
+
+  /// Send a datagram from the listening socket. Replies to such datagrams
+  /// come back to this listener, also through NATs and firewalls that only
+  /// let in replies to outgoing traffic.
+  pub fn send_to(&self, buffer: &[u8], address: &SocketAddr) -> io::Result<usize> {
+    self.socket.send_to(buffer, address)
+  }
+
   #[cfg(test)]
   pub fn port(&self) -> u16 {
     match self.socket.local_addr() {
@@ -173,64 +260,33 @@ impl UDPListener {
   }
 
   /// Get all messages waiting in the socket.
+  #[cfg(test)]
   pub fn messages(&mut self) -> Vec<Bytes> {
-    let mut messages = Vec::with_capacity(4);
-
-    loop {
-      // Loop invariant. Note that capacity() may be large, but .len() == 0.
-      assert_eq!(self.receive_buffer.len(), 0);
+    self
+      .messages_with_source(usize::MAX)
+      .into_iter()
+      .map(|(message, _source)| message)
+      .collect()
+  }
 
-      // Ensure that receive buffer has enough capacity for a message
-      if self.receive_buffer.capacity() < MAX_MESSAGE_SIZE {
-        self.receive_buffer = BytesMut::with_capacity(MESSAGE_BUFFER_ALLOCATION_CHUNK);
-        debug!("ensure_receive_buffer_capacity - reallocated receive_buffer");
-      }
-      unsafe {
-        // This is safe, because we just checked that there is enough capacity,
-        // or allocated more.
-        // We do not read undefined data, because the recv()
-        // will overwrite this space and truncate the rest away.
-        self.receive_buffer.set_len(MAX_MESSAGE_SIZE);
-      }
-      trace!(
-        "ensure_receive_buffer_capacity - {} bytes left",
-        self.receive_buffer.capacity()
-      );
-      let nbytes = match self.socket.recv(&mut self.receive_buffer) {
-        Ok(n) => n,
-        Err(e) => {
-          self.receive_buffer.clear(); // since nothing was received
-          if e.kind() == io::ErrorKind::WouldBlock {
-            // This is the normal case.
-          } else {
-            warn!("socket recv() error: {e:?}");
+  /// Get messages waiting in the socket, together with the address each one
+  /// was sent from. At most `max` messages are received, so if `max` are
+  /// returned, there may be more waiting.
+  pub fn messages_with_source(&mut self, max: usize) -> Vec<(Bytes, SocketAddr)> {
+    match &mut self.receiving {
+      Receiving::Direct { socket, reader } => {
+        let mut messages = Vec::with_capacity(4);
+        while messages.len() < max {
+          let before = messages.len();
+          reader.read(socket, max - messages.len(), &mut messages);
+          if messages.len() == before {
+            break; // socket is empty
           }
-          // In any case, we stop trying and return.
-          return messages;
         }
-      };
-      // Something was received.
-
-      // Now, append some extra data to align the buffer end, so the next piece will
-      // be aligned also. This assumes that the initial buffer was aligned to begin
-      // with. This is because RTPS data is optimized to align to 4-byte boundaries.
-      let unalign = self.receive_buffer.len() % 4;
-      if unalign != 0 {
-        self
-          .receive_buffer
-          .extend_from_slice(&[0xCC, 0xCC, 0xCC, 0xCC][..(4 - unalign)]);
-        // Funny value 0xCC encourages a fast crash in case these bytes
-        // are ever accessed, as they should not.
+        messages
       }
-
-      // Now split away the used portion.
-      let mut message = self.receive_buffer.split_to(self.receive_buffer.len());
-      message.truncate(nbytes); // discard (hide) padding
-      messages.push(Bytes::from(message)); // freeze bytes and push
-    } // loop
-
-    // unreachable!(); // But why does this cause a warning? (rustc 1.66.0)
-    // Answer: https://github.com/rust-lang/rust/issues/46500
+      Receiving::Thread(thread) => thread.messages(max),
+    }
   }
 
   #[cfg(test)] // normally done in .drop()
@@ -247,6 +303,391 @@ impl UDPListener {
   }
 }
 
+// Reads datagrams from a socket into a shared receive buffer, so that each
+// message is a slice of a larger allocation. On Linux, up to batch_size
+// datagrams are read with a single recvmmsg call directly into the receive
+// buffer. With feature "io_uring", a batch of recvmsg operations is submitted
+// through io_uring instead, if available.
+#[derive(Debug)]
+struct DatagramReader {
+  receive_buffer: BytesMut,
+  #[cfg(target_os = "linux")]
+  batch: Option<BatchHeaders>,
+  // None if io_uring is not available, or has failed
+  #[cfg(all(target_os = "linux", feature = "io_uring"))]
+  ring: Option<IoUring>,
+}
+
+impl DatagramReader {
+  #[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+  fn new(batch_size: usize) -> Self {
+    Self {
+      receive_buffer: BytesMut::with_capacity(MESSAGE_BUFFER_ALLOCATION_CHUNK),
+      #[cfg(target_os = "linux")]
+      batch: (batch_size > 1).then(|| BatchHeaders::new(batch_size)),
+      #[cfg(all(target_os = "linux", feature = "io_uring"))]
+      ring: None,
+    }
+  }
+
+  // Use io_uring for batches, if it is available
+  #[cfg(all(target_os = "linux", feature = "io_uring"))]
+  fn with_io_uring(mut self) -> Self {
+    let Some(batch) = self.batch.as_ref() else {
+      return self; // no batches
+    };
+    match IoUring::new(batch.headers.len() as u32) {
+      Ok(ring) => self.ring = Some(ring),
+      Err(e) => info!("UDPListener: io_uring not available, not using it: {e:?}"),
+    }
+    self
+  }
+
+  // Reads at most `max` datagrams with one system call, if the platform
+  // allows, and appends them to `messages`. Reads nothing if there is nothing
+  // to read.
+  fn read(
+    &mut self,
+    socket: &std::net::UdpSocket,
+    max: usize,
+    messages: &mut Vec<(Bytes, SocketAddr)>,
+  ) {
+    #[cfg(target_os = "linux")]
+    if self.batch.is_some() && max > 1 {
+      self.read_batch(socket, max, messages);
+      return;
+    }
+
+    self.read_one(socket, messages);
+  }
+
+  fn read_one(&mut self, socket: &std::net::UdpSocket, messages: &mut Vec<(Bytes, SocketAddr)>) {
+    // Loop invariant. Note that capacity() may be large, but .len() == 0.
+    assert_eq!(self.receive_buffer.len(), 0);
+
+    self.ensure_receive_buffer_capacity(MAX_MESSAGE_SIZE);
+    unsafe {
+      // This is safe, because we just checked that there is enough capacity,
+      // or allocated more.
+      // We do not read undefined data, because the recv()
+      // will overwrite this space and truncate the rest away.
+      self.receive_buffer.set_len(MAX_MESSAGE_SIZE);
+    }
+    let (nbytes, source) = match socket.recv_from(&mut self.receive_buffer) {
+      Ok(received) => received,
+      Err(e) => {
+        self.receive_buffer.clear(); // since nothing was received
+        log_receive_error(&e);
+        return;
+      }
+    };
+    self.receive_buffer.truncate(nbytes);
+    messages.push((split_message(&mut self.receive_buffer), source));
+  }
+
+  #[cfg(target_os = "linux")]
+  fn read_batch(
+    &mut self,
+    socket: &std::net::UdpSocket,
+    max: usize,
+    messages: &mut Vec<(Bytes, SocketAddr)>,
+  ) {
+    assert_eq!(self.receive_buffer.len(), 0);
+
+    let Some(batch) = self.batch.as_mut() else {
+      return;
+    };
+    let count = max.min(batch.headers.len());
+    // Each datagram gets a maximum size slot in the unused part of the
+    // receive buffer. The gaps between received datagrams are closed below.
+    if self.receive_buffer.capacity() < count * MAX_MESSAGE_SIZE {
+      self.receive_buffer =
+        BytesMut::with_capacity(MESSAGE_BUFFER_ALLOCATION_CHUNK.max(count * MAX_MESSAGE_SIZE));
+    }
+    let slots = self.receive_buffer.as_mut_ptr();
+    batch.prepare(slots, count);
+
+    #[cfg(feature = "io_uring")]
+    let received = match self.ring.as_mut() {
+      // SAFETY: The headers point to the batch addresses and iovecs, and the
+      // iovecs to the receive buffer capacity. They all live until the end of
+      // this function.
+      Some(ring) => match unsafe {
+        ring.receive_messages(
+          std::os::fd::AsRawFd::as_raw_fd(socket),
+          &mut batch.headers[..count],
+        )
+      } {
+        Err(e) if e.kind() != io::ErrorKind::WouldBlock => {
+          warn!("read_batch: io_uring failed: {e:?}. Using recvmmsg from now on.");
+          self.ring = None;
+          recvmmsg(socket, &mut batch.headers[..count])
+        }
+        received => received,
+      },
+      None => recvmmsg(socket, &mut batch.headers[..count]),
+    };
+    #[cfg(not(feature = "io_uring"))]
+    let received = recvmmsg(socket, &mut batch.headers[..count]);
+    let received = match received {
+      Ok(received) => received,
+      Err(e) => {
+        log_receive_error(&e);
+        return;
+      }
+    };
+
+    // How far the start of the receive buffer has moved from `slots`
+    let mut consumed = 0;
+    for (i, (header, address)) in batch
+      .headers
+      .iter()
+      .zip(&batch.addresses)
+      .take(received)
+      .enumerate()
+    {
+      let nbytes = header.msg_len as usize;
+      let slot_offset = i * MAX_MESSAGE_SIZE - consumed;
+      // SAFETY: The kernel has written nbytes to slot i, which is in the
+      // capacity of the receive buffer, after the part already split away.
+      // ptr::copy allows overlapping memory.
+      unsafe {
+        let start = self.receive_buffer.as_mut_ptr();
+        if slot_offset > 0 {
+          std::ptr::copy(start.add(slot_offset), start, nbytes);
+        }
+        self.receive_buffer.set_len(nbytes);
+      }
+      let message = split_message(&mut self.receive_buffer);
+      consumed += (nbytes + 3) & !3; // with the alignment padding
+                                     // SAFETY: The kernel wrote a valid address of msg_namelen bytes.
+      let source = unsafe { SockAddr::new(*address, header.msg_hdr.msg_namelen) }.as_socket();
+      // Not an IP address should not happen with UDP sockets.
+      if let Some(source) = source {
+        messages.push((message, source));
+      }
+    }
+  }
+
+  fn ensure_receive_buffer_capacity(&mut self, min_capacity: usize) {
+    if self.receive_buffer.capacity() < min_capacity {
+      self.receive_buffer = BytesMut::with_capacity(MESSAGE_BUFFER_ALLOCATION_CHUNK);
+      debug!("ensure_receive_buffer_capacity - reallocated receive_buffer");
+    }
+    trace!(
+      "ensure_receive_buffer_capacity - {} bytes left",
+      self.receive_buffer.capacity()
+    );
+  }
+}
+
+// Splits the received datagram away from the receive buffer
+fn split_message(receive_buffer: &mut BytesMut) -> Bytes {
+  let nbytes = receive_buffer.len();
+  stats::udp_datagram_received(nbytes);
+
+  // Now, append some extra data to align the buffer end, so the next piece will
+  // be aligned also. This assumes that the initial buffer was aligned to begin
+  // with. This is because RTPS data is optimized to align to 4-byte boundaries.
+  let unalign = nbytes % 4;
+  if unalign != 0 {
+    receive_buffer.extend_from_slice(&[0xCC, 0xCC, 0xCC, 0xCC][..(4 - unalign)]);
+    // Funny value 0xCC encourages a fast crash in case these bytes
+    // are ever accessed, as they should not.
+  }
+
+  // Now split away the used portion.
+  let mut message = receive_buffer.split_to(receive_buffer.len());
+  message.truncate(nbytes); // discard (hide) padding
+  Bytes::from(message) // freeze bytes
+}
+
+// Arguments for recvmmsg, allocated once per socket. The pointers in them are
+// set by prepare() before each call.
+#[cfg(target_os = "linux")]
+struct BatchHeaders {
+  addresses: Vec<libc::sockaddr_storage>,
+  iovecs: Vec<libc::iovec>,
+  headers: Vec<libc::mmsghdr>,
+}
+
+// SAFETY: The pointers are used only during a read by the owning
+// DatagramReader, after prepare() has pointed them to memory it owns.
+#[cfg(target_os = "linux")]
+unsafe impl Send for BatchHeaders {}
+
+#[cfg(target_os = "linux")]
+impl BatchHeaders {
+  fn new(batch_size: usize) -> Self {
+    use std::mem;
+
+    // SAFETY: All-zero sockaddr_storage, iovec, and mmsghdr are valid values.
+    Self {
+      addresses: vec![unsafe { mem::zeroed() }; batch_size],
+      iovecs: vec![unsafe { mem::zeroed() }; batch_size],
+      headers: vec![unsafe { mem::zeroed() }; batch_size],
+    }
+  }
+
+  // Points the first `count` headers to consecutive slots of MAX_MESSAGE_SIZE
+  // bytes starting from `slots`.
+  fn prepare(&mut self, slots: *mut u8, count: usize) {
+    for (i, ((header, iovec), address)) in self
+      .headers
+      .iter_mut()
+      .zip(self.iovecs.iter_mut())
+      .zip(self.addresses.iter_mut())
+      .take(count)
+      .enumerate()
+    {
+      *iovec = libc::iovec {
+        iov_base: slots.wrapping_add(i * MAX_MESSAGE_SIZE).cast(),
+        iov_len: MAX_MESSAGE_SIZE,
+      };
+      header.msg_hdr.msg_name = (address as *mut libc::sockaddr_storage).cast();
+      header.msg_hdr.msg_namelen = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+      header.msg_hdr.msg_iov = iovec;
+      header.msg_hdr.msg_iovlen = 1;
+      header.msg_len = 0;
+    }
+  }
+}
+
+#[cfg(target_os = "linux")]
+impl std::fmt::Debug for BatchHeaders {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("BatchHeaders")
+      .field("batch_size", &self.headers.len())
+      .finish()
+  }
+}
+
+// Receives into the headers, and returns how many were filled. The headers
+// must point to valid buffers.
+#[cfg(target_os = "linux")]
+fn recvmmsg(socket: &std::net::UdpSocket, headers: &mut [libc::mmsghdr]) -> io::Result<usize> {
+  use std::{os::fd::AsRawFd, ptr};
+
+  // MSG_WAITFORONE: If the socket is blocking, wait only for the first
+  // datagram.
+  // SAFETY: The caller has set up the headers.
+  let received = unsafe {
+    libc::recvmmsg(
+      socket.as_raw_fd(),
+      headers.as_mut_ptr(),
+      headers.len() as _,
+      libc::MSG_WAITFORONE as _,
+      ptr::null_mut(),
+    )
+  };
+  if received < 0 {
+    Err(io::Error::last_os_error())
+  } else {
+    Ok(received as usize)
+  }
+}
+
+fn log_receive_error(e: &io::Error) {
+  match e.kind() {
+    io::ErrorKind::WouldBlock => {} // This is the normal case
+    _ => warn!("socket recv() error: {e:?}"),
+  }
+}
+
+// A thread that keeps reading a socket, and queues the received datagrams for
+// the event loop.
+struct ReceiveThread {
+  datagrams: mio_channel::Receiver<Vec<(Bytes, SocketAddr)>>,
+  // Received from the queue, but not yet given to the event loop
+  pending: VecDeque<(Bytes, SocketAddr)>,
+  stop: Arc<AtomicBool>,
+  join_handle: Option<JoinHandle<()>>,
+}
+
+impl ReceiveThread {
+  fn start(socket: std::net::UdpSocket, mut reader: DatagramReader) -> io::Result<Self> {
+    // The socket stays non-blocking, because it shares the file description
+    // with the socket used for sending. The thread waits in its own poll, and
+    // wakes up periodically to check if it should stop.
+    let mut poll_socket = mio_08::net::UdpSocket::from_std(socket.try_clone()?);
+    let poll = mio_08::Poll::new()?;
+    poll.registry().register(
+      &mut poll_socket,
+      mio_08::Token(0),
+      mio_08::Interest::READABLE,
+    )?;
+
+    let (sender, datagrams) = mio_channel::sync_channel(RECEIVE_THREAD_QUEUE_LENGTH);
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = stop.clone();
+    let name = format!("RustDDS UDP receive {:?}", socket.local_addr()?);
+    let join_handle = thread::Builder::new().name(name).spawn(move || {
+      let mut poll = poll;
+      let _poll_socket = poll_socket; // keep registered
+      let mut events = mio_08::Events::with_capacity(1);
+      while !thread_stop.load(Ordering::Relaxed) {
+        if let Err(e) = poll.poll(&mut events, Some(RECEIVE_THREAD_POLL_INTERVAL)) {
+          if e.kind() != io::ErrorKind::Interrupted {
+            error!("UDP receive thread poll failed: {e:?}");
+            break;
+          }
+        }
+        // Polling is edge-triggered, so read until the socket is empty.
+        loop {
+          let mut messages = Vec::new();
+          reader.read(&socket, usize::MAX, &mut messages);
+          if messages.is_empty() {
+            break;
+          }
+          if sender.send(messages).is_err() {
+            return; // Event loop is gone
+          }
+        }
+      }
+    })?;
+
+    Ok(Self {
+      datagrams,
+      pending: VecDeque::new(),
+      stop,
+      join_handle: Some(join_handle),
+    })
+  }
+
+  fn messages(&mut self, max: usize) -> Vec<(Bytes, SocketAddr)> {
+    while self.pending.len() < max {
+      match self.datagrams.try_recv() {
+        Ok(batch) => self.pending.extend(batch),
+        Err(_) => break, // Empty, or thread is gone
+      }
+    }
+    let count = max.min(self.pending.len());
+    self.pending.drain(..count).collect()
+  }
+}
+
+impl std::fmt::Debug for ReceiveThread {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("ReceiveThread")
+      .field("pending", &self.pending.len())
+      .field("stop", &self.stop)
+      .finish()
+  }
+}
+
+impl Drop for ReceiveThread {
+  fn drop(&mut self) {
+    self.stop.store(true, Ordering::Relaxed);
+    // Unblock the thread, if it is waiting for queue space
+    while self.datagrams.try_recv().is_ok() {}
+    if let Some(join_handle) = self.join_handle.take() {
+      join_handle
+        .join()
+        .unwrap_or_else(|e| error!("UDP receive thread panicked: {e:?}"));
+    }
+  }
+}
+
 #[cfg(test)]
 mod tests {
   // use std::os::unix::io::AsRawFd;
@@ -273,6 +714,78 @@ mod tests {
     assert_eq!(rec_data, data);
   }
 
+  #[test]
+  fn udpl_send_to_and_source_address() {
+    let mut listener_a = UDPListener::new_unicast("127.0.0.1", 10003).unwrap();
+    let listener_b = UDPListener::new_unicast("127.0.0.1", 10004).unwrap();
+
+    let data: Vec<u8> = vec![1, 3, 5, 7];
+    let address_a = SocketAddr::new("127.0.0.1".parse().unwrap(), 10003);
+    listener_b.send_to(&data, &address_a).unwrap();
+
+    thread::sleep(time::Duration::from_millis(100));
+
+    let messages = listener_a.messages_with_source(usize::MAX);
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].0.as_ref(), &data[..]);
+    assert_eq!(messages[0].1.port(), listener_b.port());
+  }
+
+  // Sends datagrams of different lengths, and checks that they are received
+  // intact, in order, at most `max` per call, and with the padding hidden.
+  fn send_and_receive_batch(options: &SocketOptions) {
+    // Port 0 lets the OS choose a free port
+    let mut listener = UDPListener::new_unicast_with_options("127.0.0.1", 0, options).unwrap();
+    let poll = mio_06::Poll::new().unwrap();
+    listener.register(&poll, mio_06::Token(0)).unwrap();
+    let sender = UDPSender::new_with_random_port().unwrap();
+    let address = vec![SocketAddr::new(
+      "127.0.0.1".parse().unwrap(),
+      listener.port(),
+    )];
+    let datagrams: Vec<Vec<u8>> = (1..=20u8).map(|n| vec![n; n as usize * 3]).collect();
+    for datagram in &datagrams {
+      sender.send_to_all(datagram, &address);
+    }
+
+    let mut events = mio_06::Events::with_capacity(1);
+    let mut received = Vec::new();
+    while received.len() < datagrams.len() {
+      let batch = listener.messages_with_source(5);
+      assert!(batch.len() <= 5);
+      if batch.is_empty() {
+        // Wait for more. Polling is edge-triggered, so this is only done
+        // when everything so far has been read.
+        poll
+          .poll(&mut events, Some(time::Duration::from_secs(5)))
+          .unwrap();
+        assert!(!events.is_empty(), "Timed out waiting for datagrams");
+      }
+      received.extend(batch.into_iter().map(|(m, _)| m.to_vec()));
+    }
+    assert_eq!(received, datagrams);
+    assert!(listener.messages_with_source(usize::MAX).is_empty());
+  }
+
+  #[test]
+  fn udpl_batch_receive() {
+    let options = SocketOptions {
+      receive_batch_size: Some(4),
+      ..SocketOptions::default()
+    };
+    send_and_receive_batch(&options);
+  }
+
+  #[test]
+  fn udpl_receive_thread() {
+    let options = SocketOptions {
+      receive_batch_size: Some(4),
+      receive_threads: Some(true),
+      ..SocketOptions::default()
+    };
+    send_and_receive_batch(&options);
+  }
+
   #[test]
   fn udpl_multicast_address() {
     let listener =
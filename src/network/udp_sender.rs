@@ -1,17 +1,31 @@
 use std::{
-  io,
+  io::{self, IoSlice},
   net::{IpAddr, SocketAddr},
 };
 #[cfg(test)]
-use std::net::Ipv4Addr;
+use std::{
+  net::Ipv4Addr,
+  time::{SystemTime, UNIX_EPOCH},
+};
+#[cfg(any(test, all(target_os = "linux", feature = "io_uring")))]
+use std::sync::Mutex;
 
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
-use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use socket2::{Domain, Protocol, SockAddr, SockRef, Socket, Type};
+use bytes::Bytes;
 #[cfg(windows)]
 use local_ip_address::list_afinet_netifas;
 
-use crate::{network::util::get_local_multicast_ip_addrs, structure::locator::Locator};
+#[cfg(all(target_os = "linux", feature = "io_uring"))]
+use crate::network::io_uring::IoUring;
+use crate::{network::socket_options::SocketOptions, stats, structure::locator::Locator};
+#[cfg(test)]
+use crate::test::pcap::Datagram;
+
+// Submission queue size for sending with io_uring
+#[cfg(all(target_os = "linux", feature = "io_uring"))]
+const SEND_RING_ENTRIES: u32 = 64;
 
 // We need one multicast sender socket per interface
 
@@ -19,14 +33,31 @@ use crate::{network::util::get_local_multicast_ip_addrs, structure::locator::Loc
 pub struct UDPSender {
   unicast_socket: mio_08::net::UdpSocket,
   multicast_sockets: Vec<mio_08::net::UdpSocket>,
+  // Copies of sent datagrams, when capturing
+  #[cfg(test)]
+  capture: Mutex<Option<Vec<Datagram>>>,
+  // For sending to several destinations with one system call. None, if
+  // io_uring is not available.
+  #[cfg(all(target_os = "linux", feature = "io_uring"))]
+  ring: Option<Mutex<IoUring>>,
 }
 
 impl UDPSender {
+  #[cfg(test)]
   pub fn new(sender_port: u16) -> io::Result<Self> {
+    Self::new_with_options(sender_port, &SocketOptions::default())
+  }
+
+  pub fn new_with_options(sender_port: u16, options: &SocketOptions) -> io::Result<Self> {
     #[cfg(not(windows))]
     let unicast_socket = {
+      // Go through socket2 to be able to set buffer sizes.
+      let raw_socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+      options.apply_to_sender(&raw_socket);
       let saddr: SocketAddr = SocketAddr::new("0.0.0.0".parse().unwrap(), sender_port);
-      mio_08::net::UdpSocket::bind(saddr)?
+      raw_socket.bind(&SockAddr::from(saddr))?;
+      raw_socket.set_nonblocking(true)?;
+      mio_08::net::UdpSocket::from_std(std::net::UdpSocket::from(raw_socket))
     };
 
     #[cfg(windows)]
@@ -34,6 +65,7 @@ impl UDPSender {
       // for windows users, bind to valid addresses only
       let raw_socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
       raw_socket.set_reuse_address(true)?;
+      options.apply_to_sender(&raw_socket);
       // get a list of all detected network interfaces, and try binding to their ip
       // addresses one by one.
       let network_interfaces = list_afinet_netifas().unwrap();
@@ -52,15 +84,17 @@ impl UDPSender {
 
     // We set multicasting loop on so that we can hear other DomainParticipant
     // instances running on the same host.
+    let multicast_loop = options.multicast_loop();
     unicast_socket
-      .set_multicast_loop_v4(true)
+      .set_multicast_loop_v4(multicast_loop)
       .unwrap_or_else(|e| {
-        error!("Cannot set multicast loop on: {e:?}");
+        error!("Cannot set multicast loop {multicast_loop}: {e:?}");
       });
 
     let mut multicast_sockets = Vec::with_capacity(1);
-    for multicast_if_ipaddr in get_local_multicast_ip_addrs()? {
+    for multicast_if_ipaddr in options.multicast_ip_addrs()? {
       let raw_socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+      options.apply_to_sender(&raw_socket);
       // beef: specify output interface
       info!(
         "UDPSender: Multicast sender on interface {:?}",
@@ -78,15 +112,24 @@ impl UDPSender {
       }
 
       let mc_socket = std::net::UdpSocket::from(raw_socket);
-      mc_socket.set_multicast_loop_v4(true).unwrap_or_else(|e| {
-        error!("Cannot set multicast loop on: {e:?}");
-      });
+      mc_socket
+        .set_multicast_loop_v4(multicast_loop)
+        .unwrap_or_else(|e| {
+          error!("Cannot set multicast loop {multicast_loop}: {e:?}");
+        });
       multicast_sockets.push(mio_08::net::UdpSocket::from_std(mc_socket));
     } // end for
 
     let sender = Self {
       unicast_socket,
       multicast_sockets,
+      #[cfg(test)]
+      capture: Mutex::new(None),
+      #[cfg(all(target_os = "linux", feature = "io_uring"))]
+      ring: IoUring::new(SEND_RING_ENTRIES)
+        .map(Mutex::new)
+        .map_err(|e| info!("UDPSender: io_uring not available, not using it: {e:?}"))
+        .ok(),
     };
     info!("UDPSender::new() --> {:?}", sender);
     Ok(sender)
@@ -98,59 +141,213 @@ impl UDPSender {
   }
 
   pub fn send_to_locator_list(&self, buffer: &[u8], ll: &[Locator]) {
+    #[cfg(all(target_os = "linux", feature = "io_uring"))]
+    if let Some(ring) = &self.ring {
+      let destinations: Vec<_> = ll
+        .iter()
+        .flat_map(|loc| self.destinations(buffer.len(), loc))
+        .collect();
+      if destinations.len() > 1 {
+        self.send_with_ring(ring, &[buffer], &destinations);
+        return;
+      }
+    }
+
     for loc in ll {
       self.send_to_locator(buffer, loc);
     }
   }
 
+  /// Send a datagram that is given in pieces, e.g. from
+  /// `Message::write_to_chunks`. The pieces are gathered by the operating
+  /// system, so they are not copied together first.
+  pub fn send_chunks_to_locator_list(&self, chunks: &[Bytes], ll: &[Locator]) {
+    if let [buffer] = chunks {
+      return self.send_to_locator_list(buffer, ll);
+    }
+    let chunks: Vec<&[u8]> = chunks.iter().map(|c| c.as_ref()).collect();
+    let len = chunks.iter().map(|c| c.len()).sum();
+    let destinations: Vec<_> = ll
+      .iter()
+      .flat_map(|loc| self.destinations(len, loc))
+      .collect();
+
+    #[cfg(all(target_os = "linux", feature = "io_uring"))]
+    if let Some(ring) = &self.ring {
+      self.send_with_ring(ring, &chunks, &destinations);
+      return;
+    }
+
+    let slices: Vec<IoSlice> = chunks.iter().map(|c| IoSlice::new(c)).collect();
+    for (socket, addr) in destinations {
+      #[cfg(test)]
+      self.capture(&chunks, socket, &addr);
+      let result = send_to_vectored(socket, &slices, &addr);
+      self.log_send_result(len, &addr, result);
+    }
+  }
+
+  // Start recording sent datagrams, e.g. to write them to a pcap file
+  #[cfg(test)]
+  pub fn start_capture(&self) {
+    *self.capture.lock().unwrap() = Some(Vec::new());
+  }
+
+  // Datagrams sent since start_capture()
+  #[cfg(test)]
+  pub fn take_capture(&self) -> Vec<Datagram> {
+    self.capture.lock().unwrap().take().unwrap_or_default()
+  }
+
+  #[cfg(test)]
+  fn capture(&self, chunks: &[&[u8]], socket: &mio_08::net::UdpSocket, addr: &SocketAddr) {
+    if let Some(capture) = self.capture.lock().unwrap().as_mut() {
+      capture.push(Datagram {
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap(),
+        source: socket
+          .local_addr()
+          .unwrap_or_else(|_| SocketAddr::from(([0, 0, 0, 0], 0))),
+        destination: *addr,
+        payload: Bytes::from(chunks.concat()),
+      });
+    }
+  }
+
   fn send_to_udp_socket(&self, buffer: &[u8], socket: &mio_08::net::UdpSocket, addr: &SocketAddr) {
-    match socket.send_to(buffer, *addr) {
+    #[cfg(test)]
+    self.capture(&[buffer], socket, addr);
+    self.log_send_result(buffer.len(), addr, socket.send_to(buffer, *addr));
+  }
+
+  fn log_send_result(&self, len: usize, addr: &SocketAddr, result: io::Result<usize>) {
+    match result {
       Ok(bytes_sent) => {
-        if bytes_sent == buffer.len() { // ok
+        stats::udp_datagram_sent(bytes_sent);
+        if bytes_sent == len { // ok
         } else {
           error!(
             "send_to_udp_socket - send_to tried {} bytes, sent only {}",
-            buffer.len(),
-            bytes_sent
+            len, bytes_sent
           );
         }
       }
       Err(e) => {
+        stats::udp_send_failed();
         warn!(
           "send_to_udp_socket - send_to {} : {:?} len={}",
-          addr,
-          e,
-          buffer.len()
+          addr, e, len
         );
       }
     }
   }
 
   pub fn send_to_locator(&self, buffer: &[u8], locator: &Locator) {
-    if buffer.len() > 1500 {
-      warn!("send_to_locator: Message size = {}", buffer.len());
+    for (socket, socket_address) in self.destinations(buffer.len(), locator) {
+      self.send_to_udp_socket(buffer, socket, &socket_address);
     }
-    let send = |socket_address: SocketAddr| {
+  }
+
+  // The sockets to send from and addresses to send to, for a locator
+  fn destinations(
+    &self,
+    message_len: usize,
+    locator: &Locator,
+  ) -> Vec<(&mio_08::net::UdpSocket, SocketAddr)> {
+    if message_len > 1500 {
+      warn!("send_to_locator: Message size = {}", message_len);
+    }
+    let destinations = |socket_address: SocketAddr| {
       if socket_address.ip().is_multicast() {
-        for socket in &self.multicast_sockets {
-          self.send_to_udp_socket(buffer, socket, &socket_address);
-        }
+        self
+          .multicast_sockets
+          .iter()
+          .map(|socket| (socket, socket_address))
+          .collect()
       } else {
-        self.send_to_udp_socket(buffer, &self.unicast_socket, &socket_address);
+        vec![(&self.unicast_socket, socket_address)]
       }
     };
 
     match locator {
-      Locator::UdpV4(socket_address) => send(SocketAddr::from(*socket_address)),
-      Locator::UdpV6(socket_address) => send(SocketAddr::from(*socket_address)),
+      Locator::UdpV4(socket_address) => destinations(SocketAddr::from(*socket_address)),
+      Locator::UdpV6(socket_address) => destinations(SocketAddr::from(*socket_address)),
       Locator::Invalid | Locator::Reserved => {
         error!("send_to_locator: Cannot send to {:?}", locator);
+        vec![]
       }
       Locator::Other { kind, .. } =>
       // This is normal, as other implementations can define their own kinds.
       // We get those from Discovery.
       {
         trace!("send_to_locator: Unknown LocatorKind: {:?}", kind);
+        vec![]
+      }
+    }
+  }
+
+  // Send the same datagram, gathered from chunks, to several destinations
+  // with io_uring
+  #[cfg(all(target_os = "linux", feature = "io_uring"))]
+  fn send_with_ring(
+    &self,
+    ring: &Mutex<IoUring>,
+    chunks: &[&[u8]],
+    destinations: &[(&mio_08::net::UdpSocket, SocketAddr)],
+  ) {
+    use std::{mem, os::fd::AsRawFd};
+
+    let len = chunks.iter().map(|c| c.len()).sum();
+    let addresses: Vec<SockAddr> = destinations
+      .iter()
+      .map(|(_socket, addr)| SockAddr::from(*addr))
+      .collect();
+    // sendmsg does not modify the iovecs or the buffers
+    let mut iovecs: Vec<libc::iovec> = chunks
+      .iter()
+      .map(|chunk| libc::iovec {
+        iov_base: chunk.as_ptr() as *mut libc::c_void,
+        iov_len: chunk.len(),
+      })
+      .collect();
+    let messages: Vec<(std::os::fd::RawFd, libc::msghdr)> = destinations
+      .iter()
+      .zip(&addresses)
+      .map(|((socket, _addr), address)| {
+        // SAFETY: All-zero msghdr is valid.
+        let mut header: libc::msghdr = unsafe { mem::zeroed() };
+        header.msg_name = address.as_ptr() as *mut libc::c_void;
+        header.msg_namelen = address.len();
+        header.msg_iov = iovecs.as_mut_ptr();
+        header.msg_iovlen = iovecs.len();
+        (socket.as_raw_fd(), header)
+      })
+      .collect();
+
+    #[cfg(test)]
+    for (socket, addr) in destinations {
+      self.capture(chunks, socket, addr);
+    }
+    // SAFETY: The headers point to addresses and iovecs, and the iovecs to
+    // chunks, which all live until the end of this function.
+    let results = unsafe {
+      ring
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .send_messages(&messages)
+    };
+    match results {
+      Ok(results) => {
+        for ((_socket, addr), result) in destinations.iter().zip(results) {
+          self.log_send_result(len, addr, result);
+        }
+      }
+      Err(e) => {
+        warn!("send_with_ring: io_uring failed: {e:?}. Sending one by one.");
+        let slices: Vec<IoSlice> = chunks.iter().map(|c| IoSlice::new(c)).collect();
+        for (socket, addr) in destinations {
+          let result = send_to_vectored(socket, &slices, addr);
+          self.log_send_result(len, addr, result);
+        }
       }
     }
   }
@@ -182,6 +379,26 @@ impl UDPSender {
   }
 }
 
+// Scatter/gather send. The mio socket does not offer this, so go through
+// socket2.
+fn send_to_vectored(
+  socket: &mio_08::net::UdpSocket,
+  slices: &[IoSlice],
+  addr: &SocketAddr,
+) -> io::Result<usize> {
+  // SAFETY: The socket outlives the borrow.
+  #[cfg(unix)]
+  let borrowed =
+    unsafe { std::os::fd::BorrowedFd::borrow_raw(std::os::fd::AsRawFd::as_raw_fd(socket)) };
+  #[cfg(windows)]
+  let borrowed = unsafe {
+    std::os::windows::io::BorrowedSocket::borrow_raw(
+      std::os::windows::io::AsRawSocket::as_raw_socket(socket),
+    )
+  };
+  SockRef::from(&borrowed).send_to_vectored(slices, &SockAddr::from(*addr))
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -226,4 +443,27 @@ mod tests {
     assert_eq!(rec_data_2.len(), 6);
     assert_eq!(rec_data_2, data);
   }
+
+  #[test]
+  fn udps_send_to_locator_list() {
+    let mut listener_1 = UDPListener::new_unicast("127.0.0.1", 10401).unwrap();
+    let mut listener_2 = UDPListener::new_unicast("127.0.0.1", 10402).unwrap();
+    let sender = UDPSender::new_with_random_port().unwrap();
+
+    let data: Vec<u8> = vec![9, 8, 7];
+    let locators = vec![
+      Locator::from(SocketAddr::new("127.0.0.1".parse().unwrap(), 10401)),
+      Locator::from(SocketAddr::new("127.0.0.1".parse().unwrap(), 10402)),
+      Locator::Invalid,
+    ];
+    sender.send_to_locator_list(&data, &locators);
+
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    assert_eq!(
+      listener_1.messages(),
+      vec![bytes::Bytes::from(data.clone())]
+    );
+    assert_eq!(listener_2.messages(), vec![bytes::Bytes::from(data)]);
+  }
 }
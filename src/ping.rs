@@ -0,0 +1,368 @@
+//! Round-trip latency measurement between DomainParticipants.
+//!
+//! A DomainParticipant built with the
+//! [`ping`](crate::BuiltinEndpointOptions::ping) built-in endpoints answers
+//! ping requests addressed to it, and can measure the round-trip time to
+//! other such participants with
+//! [`DomainParticipant::measure_rtt`](crate::DomainParticipant::measure_rtt).
+//! The endpoints are RustDDS-specific built-in endpoints. Like the Discovery
+//! endpoints, they are matched using the participant announcements, without
+//! SEDP, and only with RustDDS participants.
+//!
+//! Replies are sent by the Discovery thread, so they are sent promptly
+//! regardless of what the application is doing. Round-trip times are
+//! measured from the moment a request is written to the moment the reply is
+//! received by RTPS, so application wakeup latency is not included.
+//!
+//! The endpoints are best-effort: lost requests or replies are counted as
+//! lost, not retransmitted. Pings sent before the remote participant has
+//! been discovered are also lost.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//! use rustdds::*;
+//!
+//! let participant = DomainParticipantBuilder::new(0)
+//!   .builtin_endpoints(BuiltinEndpointOptions {
+//!     ping: true,
+//!     ..Default::default()
+//!   })
+//!   .build()
+//!   .unwrap();
+//!
+//! // GuidPrefix of some discovered remote participant
+//! let remote = participant.guid().prefix;
+//! let statistics = participant
+//!   .measure_rtt(remote, 5, Duration::from_millis(200))
+//!   .unwrap();
+//! if let Some(mean) = statistics.mean() {
+//!   println!("RTT {mean:?} jitter {:?}", statistics.jitter());
+//! }
+//! ```
+
+use std::time::{Duration as StdDuration, Instant};
+
+use mio_06::{Events, Poll, PollOpt, Ready, Token};
+use serde::{Deserialize, Serialize};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::{
+  dds::{
+    no_key::{datareader::DataReader, datawriter::DataWriter},
+    participant::{DomainParticipant, DomainParticipantWeak},
+    pubsub::{Publisher, Subscriber},
+    qos::{policy, QosPolicies, QosPolicyBuilder},
+    result::CreateResult,
+    topic::{Topic, TopicKind},
+  },
+  rtps::constant::{builtin_topic_names, builtin_topic_type_names},
+  serialization::{CDRDeserializerAdapter, CDRSerializerAdapter},
+  structure::{
+    entity::RTPSEntity,
+    guid::{EntityId, GuidPrefix},
+  },
+};
+
+const PING_REPLY_TOKEN: Token = Token(0);
+
+// Requests and replies have the same contents. A reply is the request echoed
+// back.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct PingMessage {
+  origin: GuidPrefix, // participant measuring the round trip
+  target: GuidPrefix, // participant expected to reply
+  sequence_number: u64,
+}
+
+type PingReader = DataReader<PingMessage, CDRDeserializerAdapter<PingMessage>>;
+type PingWriter = DataWriter<PingMessage, CDRSerializerAdapter<PingMessage>>;
+
+/// Results of [`DomainParticipant::measure_rtt`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RttStatistics {
+  /// Number of ping requests sent
+  pub sent: usize,
+  /// Round-trip time of each received reply, in sending order
+  pub round_trip_times: Vec<StdDuration>,
+}
+
+impl RttStatistics {
+  pub fn received(&self) -> usize {
+    self.round_trip_times.len()
+  }
+
+  /// Number of pings that received no reply within the timeout
+  pub fn lost(&self) -> usize {
+    self.sent.saturating_sub(self.received())
+  }
+
+  pub fn min(&self) -> Option<StdDuration> {
+    self.round_trip_times.iter().min().copied()
+  }
+
+  pub fn max(&self) -> Option<StdDuration> {
+    self.round_trip_times.iter().max().copied()
+  }
+
+  pub fn mean(&self) -> Option<StdDuration> {
+    let n = u32::try_from(self.received()).ok().filter(|n| *n > 0)?;
+    Some(self.round_trip_times.iter().sum::<StdDuration>() / n)
+  }
+
+  /// Mean absolute difference of consecutive round-trip times. Requires at
+  /// least two replies.
+  pub fn jitter(&self) -> Option<StdDuration> {
+    let n = u32::try_from(self.received().checked_sub(1)?)
+      .ok()
+      .filter(|n| *n > 0)?;
+    let total: StdDuration = self
+      .round_trip_times
+      .windows(2)
+      .map(|w| w[0].max(w[1]) - w[0].min(w[1]))
+      .sum();
+    Some(total / n)
+  }
+}
+
+// Pings are best-effort, so that lost pings show up as lost instead of as
+// increased round-trip time.
+fn ping_qos() -> QosPolicies {
+  QosPolicyBuilder::new()
+    .reliability(policy::Reliability::BestEffort)
+    .durability(policy::Durability::Volatile)
+    .history(policy::History::KeepLast { depth: 16 })
+    .build()
+}
+
+// The request and reply Topics
+fn ping_topics(participant: &DomainParticipantWeak) -> CreateResult<(Topic, Topic)> {
+  let topic = |name: &str| {
+    participant.create_topic(
+      name.to_string(),
+      builtin_topic_type_names::DCPS_PING.to_string(),
+      &ping_qos(),
+      TopicKind::NoKey,
+    )
+  };
+  Ok((
+    topic(builtin_topic_names::DCPS_PING_REQUEST)?,
+    topic(builtin_topic_names::DCPS_PING_REPLY)?,
+  ))
+}
+
+// Answers the ping requests addressed to our participant. Run by Discovery.
+pub(crate) struct PingResponder {
+  request_reader: PingReader,
+  reply_writer: PingWriter,
+}
+
+impl PingResponder {
+  pub(crate) fn new(
+    participant: &DomainParticipantWeak,
+    subscriber: &Subscriber,
+    publisher: &Publisher,
+  ) -> CreateResult<Self> {
+    let (request_topic, reply_topic) = ping_topics(participant)?;
+    Ok(Self {
+      request_reader: subscriber.create_datareader_with_entity_id_no_key(
+        &request_topic,
+        EntityId::RUSTDDS_PING_REQUEST_READER,
+        Some(ping_qos()),
+        false,
+      )?,
+      reply_writer: publisher.create_datawriter_with_entity_id_no_key(
+        EntityId::RUSTDDS_PING_REPLY_WRITER,
+        &reply_topic,
+        Some(ping_qos()),
+        false,
+      )?,
+    })
+  }
+
+  pub(crate) fn request_reader(&self) -> &PingReader {
+    &self.request_reader
+  }
+
+  pub(crate) fn reply(&mut self, my_guid_prefix: GuidPrefix) {
+    loop {
+      let request = match self.request_reader.take_next_sample() {
+        Ok(Some(sample)) => *sample.value(),
+        Ok(None) => return,
+        Err(e) => {
+          warn!("Ping responder cannot read requests: {e:?}");
+          return;
+        }
+      };
+      if request.target != my_guid_prefix {
+        continue;
+      }
+      trace!("Replying to ping {request:?}");
+      self
+        .reply_writer
+        .write(request, None)
+        .unwrap_or_else(|e| debug!("Cannot reply to ping: {e:?}"));
+    }
+  }
+}
+
+// Sends pings and waits for the replies. Owned by the DomainParticipant.
+pub(crate) struct PingRequester {
+  my_guid_prefix: GuidPrefix,
+  writer: PingWriter,
+  reader: PingReader,
+  poll: Poll,
+  next_sequence_number: u64,
+}
+
+impl PingRequester {
+  pub(crate) fn new(participant: &DomainParticipant) -> CreateResult<Self> {
+    let (request_topic, reply_topic) = ping_topics(&participant.weak_clone())?;
+    let publisher = participant.create_publisher(&ping_qos())?;
+    let subscriber = participant.create_subscriber(&ping_qos())?;
+    let writer = publisher.create_datawriter_with_entity_id_no_key(
+      EntityId::RUSTDDS_PING_REQUEST_WRITER,
+      &request_topic,
+      Some(ping_qos()),
+      false,
+    )?;
+    let reader = subscriber.create_datareader_with_entity_id_no_key(
+      &reply_topic,
+      EntityId::RUSTDDS_PING_REPLY_READER,
+      Some(ping_qos()),
+      false,
+    )?;
+    let poll = Poll::new()?;
+    poll.register(
+      &reader,
+      PING_REPLY_TOKEN,
+      Ready::readable(),
+      PollOpt::edge(),
+    )?;
+    Ok(Self {
+      my_guid_prefix: participant.guid().prefix,
+      writer,
+      reader,
+      poll,
+      next_sequence_number: 0,
+    })
+  }
+
+  // Sends `count` pings one at a time. Each waits for its reply at most
+  // `timeout`.
+  pub(crate) fn measure_rtt(
+    &mut self,
+    remote_participant: GuidPrefix,
+    count: usize,
+    timeout: StdDuration,
+  ) -> RttStatistics {
+    let mut statistics = RttStatistics::default();
+    let mut events = Events::with_capacity(4);
+
+    for _ in 0..count {
+      let request = PingMessage {
+        origin: self.my_guid_prefix,
+        target: remote_participant,
+        sequence_number: self.next_sequence_number,
+      };
+      self.next_sequence_number += 1;
+
+      let sent_at = Instant::now();
+      if let Err(e) = self.writer.write(request, None) {
+        warn!("Cannot send ping: {e:?}");
+        continue;
+      }
+      statistics.sent += 1;
+      let deadline = sent_at + timeout;
+
+      'wait_reply: loop {
+        // Replies to earlier, timed out, pings and to other participants are
+        // discarded here.
+        while let Ok(Some(reply)) = self.reader.take_next_sample() {
+          if *reply.value() == request {
+            let received_at = reply.sample_info().reception_instant();
+            let rtt = received_at.saturating_duration_since(sent_at);
+            statistics.round_trip_times.push(rtt);
+            break 'wait_reply;
+          }
+        }
+        let now = Instant::now();
+        if now >= deadline {
+          debug!("Ping {request:?} timed out");
+          break;
+        }
+        if let Err(e) = self.poll.poll(&mut events, Some(deadline - now)) {
+          error!("Ping requester poll failed: {e:?}");
+          return statistics;
+        }
+      }
+    }
+    statistics
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ms(millis: u64) -> StdDuration {
+    StdDuration::from_millis(millis)
+  }
+
+  #[test]
+  fn rtt_statistics() {
+    let empty = RttStatistics {
+      sent: 2,
+      round_trip_times: vec![],
+    };
+    assert_eq!(empty.lost(), 2);
+    assert_eq!(empty.mean(), None);
+    assert_eq!(empty.jitter(), None);
+
+    let statistics = RttStatistics {
+      sent: 4,
+      round_trip_times: vec![ms(10), ms(14), ms(12)],
+    };
+    assert_eq!(statistics.received(), 3);
+    assert_eq!(statistics.lost(), 1);
+    assert_eq!(statistics.min(), Some(ms(10)));
+    assert_eq!(statistics.max(), Some(ms(14)));
+    assert_eq!(statistics.mean(), Some(ms(12)));
+    assert_eq!(statistics.jitter(), Some(ms(3)));
+  }
+
+  #[test]
+  fn ping_self() {
+    use crate::{dds::participant::DomainParticipantBuilder, BuiltinEndpointOptions};
+
+    let participant = DomainParticipantBuilder::new(0)
+      .builtin_endpoints(BuiltinEndpointOptions {
+        ping: true,
+        ..Default::default()
+      })
+      .build()
+      .unwrap();
+    let me = participant.guid().prefix;
+
+    let statistics = participant.measure_rtt(me, 10, ms(500)).unwrap();
+    assert_eq!(statistics.sent, 10);
+    assert!(statistics.received() > 0);
+    assert!(statistics.max().unwrap() < ms(500));
+
+    // Nobody answers to unknown participants
+    let statistics = participant
+      .measure_rtt(GuidPrefix::UNKNOWN, 2, ms(50))
+      .unwrap();
+    assert_eq!(statistics.sent, 2);
+    assert_eq!(statistics.lost(), 2);
+  }
+
+  #[test]
+  fn measure_rtt_requires_ping_endpoints() {
+    let participant = DomainParticipant::new(0).unwrap();
+    let me = participant.guid().prefix;
+    assert!(participant.measure_rtt(me, 1, ms(10)).is_err());
+  }
+}
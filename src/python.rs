@@ -0,0 +1,615 @@
+//! Python bindings, for scripting tools and tests.
+//!
+//! Topics can be given a data type, described with Python values:
+//!
+//! * a primitive type name: `"bool"`, `"int8"`, `"uint8"`, `"int16"`,
+//!   `"uint16"`, `"int32"`, `"uint32"`, `"int64"`, `"uint64"`, `"float32"`,
+//!   `"float64"` or `"string"`
+//! * a list of one element type, for a sequence: `["float32"]`
+//! * a tuple of an element type and a length, for an array: `("float64", 3)`
+//! * a dict from member names to types, for a structure
+//!
+//! The data is then handled as [dynamic data](crate::dynamic_data):
+//! `DataWriter.write()` takes Python values of the type, i.e. `bool`, `int`,
+//! `float`, `str`, lists and dicts, and `Sample.value` gives them out. Without
+//! a data type, samples are handled as serialized payloads, like in the
+//! [C interface](crate::ffi): `DataWriter.write()` takes `bytes`, and
+//! `Sample.data` gives out the payload as received. `Sample.data` is
+//! available for typed Topics, too.
+//!
+//! This module is available only with the feature "python". To build the
+//! extension module, run
+//!
+//! ```text
+//! cargo rustc --release --features python,pyo3/extension-module --crate-type cdylib
+//! cp target/release/librustdds.so rustdds.so
+//! ```
+//!
+//! or use `maturin` with the same features. Example:
+//!
+//! ```text
+//! import rustdds, time
+//! dp = rustdds.Participant(0)
+//! shape_type = {"color": "string", "x": "int32", "y": "int32", "shapesize": "int32"}
+//! topic = dp.create_topic("Square", "ShapeType", data_type=shape_type)
+//! reader = dp.create_reader(topic, rustdds.Qos(reliable=True))
+//! writer = dp.create_writer(topic, rustdds.Qos(reliable=True))
+//! time.sleep(1) # wait for the reader and writer to match
+//! writer.write({"color": "RED", "x": 10, "y": 20, "shapesize": 30})
+//! sample = reader.take(timeout=1.0)
+//! print(sample.value, sample.encoding)
+//! ```
+
+use std::{
+  collections::BTreeMap,
+  sync::Arc,
+  thread,
+  time::{Duration as StdDuration, Instant},
+};
+#[cfg(not(target_os = "windows"))]
+use std::os::fd::AsRawFd;
+
+use bytes::Bytes;
+use pyo3::{
+  exceptions::{PyRuntimeError, PyTypeError, PyValueError},
+  prelude::*,
+  types::{PyBytes, PyDict, PyList, PyString, PyTuple},
+  IntoPyObjectExt,
+};
+
+use crate::{
+  dds::{
+    no_key::{datareader::DataReader, datawriter::DataWriter},
+    participant::DomainParticipant,
+    pubsub::{Publisher, Subscriber},
+    qos::{policy, QosPolicies, QosPolicyBuilder},
+    topic::{Topic, TopicDescription, TopicKind},
+    with_key::datawriter::WriteOptions,
+  },
+  dynamic_data::{DynamicType, DynamicValue},
+  gateway::{RawSample, RawSampleAdapter},
+  structure::{duration::Duration, entity::RTPSEntity, time::Timestamp},
+  RepresentationIdentifier,
+};
+
+fn runtime_error(e: impl std::fmt::Debug) -> PyErr {
+  PyRuntimeError::new_err(format!("{e:?}"))
+}
+
+fn value_error(e: impl std::fmt::Display) -> PyErr {
+  PyValueError::new_err(e.to_string())
+}
+
+/// Convert a data type description, see the module documentation.
+fn data_type_from_py(spec: &Bound<'_, PyAny>) -> PyResult<DynamicType> {
+  if let Ok(name) = spec.downcast::<PyString>() {
+    return match name.to_str()? {
+      "bool" => Ok(DynamicType::Boolean),
+      "int8" => Ok(DynamicType::Int8),
+      "uint8" => Ok(DynamicType::UInt8),
+      "int16" => Ok(DynamicType::Int16),
+      "uint16" => Ok(DynamicType::UInt16),
+      "int32" => Ok(DynamicType::Int32),
+      "uint32" => Ok(DynamicType::UInt32),
+      "int64" => Ok(DynamicType::Int64),
+      "uint64" => Ok(DynamicType::UInt64),
+      "float32" => Ok(DynamicType::Float32),
+      "float64" => Ok(DynamicType::Float64),
+      "string" => Ok(DynamicType::String),
+      other => Err(PyValueError::new_err(format!(
+        "Unknown type name {other:?}"
+      ))),
+    };
+  }
+  if let Ok(list) = spec.downcast::<PyList>() {
+    if list.len() == 1 {
+      let element_type = data_type_from_py(&list.get_item(0)?)?;
+      return Ok(DynamicType::Sequence(Box::new(element_type)));
+    }
+  } else if let Ok(tuple) = spec.downcast::<PyTuple>() {
+    if tuple.len() == 2 {
+      let element_type = data_type_from_py(&tuple.get_item(0)?)?;
+      return Ok(DynamicType::Array(
+        Box::new(element_type),
+        tuple.get_item(1)?.extract()?,
+      ));
+    }
+  } else if let Ok(dict) = spec.downcast::<PyDict>() {
+    return dict
+      .iter()
+      .map(|(name, member_type)| Ok((name.extract()?, data_type_from_py(&member_type)?)))
+      .collect::<PyResult<_>>()
+      .map(DynamicType::Struct);
+  }
+  Err(PyTypeError::new_err(format!(
+    "Invalid data type description: {spec}"
+  )))
+}
+
+fn value_from_py(data_type: &DynamicType, value: &Bound<'_, PyAny>) -> PyResult<DynamicValue> {
+  Ok(match data_type {
+    DynamicType::Boolean => DynamicValue::Boolean(value.extract()?),
+    DynamicType::Int8 | DynamicType::Int16 | DynamicType::Int32 | DynamicType::Int64 => {
+      DynamicValue::Int(value.extract()?)
+    }
+    DynamicType::UInt8 | DynamicType::UInt16 | DynamicType::UInt32 | DynamicType::UInt64 => {
+      DynamicValue::UInt(value.extract()?)
+    }
+    DynamicType::Float32 | DynamicType::Float64 => DynamicValue::Float(value.extract()?),
+    DynamicType::String => DynamicValue::String(value.extract()?),
+    DynamicType::Sequence(element_type) | DynamicType::Array(element_type, _) => {
+      DynamicValue::Sequence(
+        value
+          .try_iter()?
+          .map(|element| value_from_py(element_type, &element?))
+          .collect::<PyResult<_>>()?,
+      )
+    }
+    DynamicType::Struct(members) => {
+      let mut values = BTreeMap::new();
+      for (name, member_type) in members {
+        let member = value
+          .get_item(name)
+          .map_err(|_| PyValueError::new_err(format!("Missing struct member {name:?}")))?;
+        values.insert(name.clone(), value_from_py(member_type, &member)?);
+      }
+      DynamicValue::Struct(values)
+    }
+  })
+}
+
+fn value_to_py(
+  py: Python<'_>,
+  data_type: &DynamicType,
+  value: &DynamicValue,
+) -> PyResult<PyObject> {
+  match (data_type, value) {
+    (_, DynamicValue::Boolean(b)) => b.into_py_any(py),
+    (_, DynamicValue::Int(i)) => i.into_py_any(py),
+    (_, DynamicValue::UInt(u)) => u.into_py_any(py),
+    (_, DynamicValue::Float(f)) => f.into_py_any(py),
+    (_, DynamicValue::String(s)) => s.into_py_any(py),
+    (
+      DynamicType::Sequence(element_type) | DynamicType::Array(element_type, _),
+      DynamicValue::Sequence(elements),
+    ) => elements
+      .iter()
+      .map(|element| value_to_py(py, element_type, element))
+      .collect::<PyResult<Vec<_>>>()?
+      .into_py_any(py),
+    (DynamicType::Struct(members), DynamicValue::Struct(values)) => {
+      // Members in declaration order
+      let dict = PyDict::new(py);
+      for (name, member_type) in members {
+        if let Some(value) = values.get(name) {
+          dict.set_item(name, value_to_py(py, member_type, value)?)?;
+        }
+      }
+      dict.into_py_any(py)
+    }
+    _ => Err(PyValueError::new_err(format!(
+      "{value:?} is not a valid {data_type:?}"
+    ))),
+  }
+}
+
+/// The most commonly needed QoS policies.
+#[pyclass(name = "Qos")]
+#[derive(Clone, Copy)]
+pub struct PyQos {
+  #[pyo3(get, set)]
+  reliable: bool,
+  #[pyo3(get, set)]
+  transient_local: bool,
+  /// KeepLast depth. Zero means KeepAll.
+  #[pyo3(get, set)]
+  history_depth: i32,
+}
+
+#[pymethods]
+impl PyQos {
+  #[new]
+  #[pyo3(signature = (reliable = false, transient_local = false, history_depth = 1))]
+  fn new(reliable: bool, transient_local: bool, history_depth: i32) -> Self {
+    Self {
+      reliable,
+      transient_local,
+      history_depth,
+    }
+  }
+}
+
+impl PyQos {
+  fn to_qos_policies(self) -> QosPolicies {
+    let builder = QosPolicyBuilder::new()
+      .durability(if self.transient_local {
+        policy::Durability::TransientLocal
+      } else {
+        policy::Durability::Volatile
+      })
+      .history(if self.history_depth > 0 {
+        policy::History::KeepLast {
+          depth: self.history_depth,
+        }
+      } else {
+        policy::History::KeepAll
+      });
+    if self.reliable {
+      builder.reliable(Duration::from_millis(100)).build()
+    } else {
+      builder.best_effort().build()
+    }
+  }
+}
+
+#[pyclass(name = "Participant", unsendable)]
+pub struct PyParticipant {
+  participant: DomainParticipant,
+  publisher: Publisher,
+  subscriber: Subscriber,
+}
+
+#[pymethods]
+impl PyParticipant {
+  #[new]
+  #[pyo3(signature = (domain_id = 0))]
+  fn new(domain_id: u16) -> PyResult<Self> {
+    let participant = DomainParticipant::new(domain_id).map_err(runtime_error)?;
+    let qos = QosPolicies::qos_none();
+    Ok(Self {
+      publisher: participant.create_publisher(&qos).map_err(runtime_error)?,
+      subscriber: participant.create_subscriber(&qos).map_err(runtime_error)?,
+      participant,
+    })
+  }
+
+  #[getter]
+  fn domain_id(&self) -> u16 {
+    self.participant.domain_id()
+  }
+
+  #[getter]
+  fn guid(&self) -> String {
+    format!("{:?}", self.participant.guid())
+  }
+
+  /// Find or create a Topic. Keyed Topics are accessed without instance
+  /// lifecycle, see the C interface. With `data_type`, samples are written
+  /// and read as Python values of that type, see the module documentation.
+  #[pyo3(signature = (name, type_name, qos = None, data_type = None))]
+  fn create_topic(
+    &self,
+    name: &str,
+    type_name: &str,
+    qos: Option<PyQos>,
+    data_type: Option<&Bound<'_, PyAny>>,
+  ) -> PyResult<PyTopic> {
+    let data_type = data_type.map(data_type_from_py).transpose()?.map(Arc::new);
+    let qos = qos.map_or_else(QosPolicies::qos_none, PyQos::to_qos_policies);
+    self
+      .participant
+      .create_topic(
+        name.to_string(),
+        type_name.to_string(),
+        &qos,
+        TopicKind::NoKey,
+      )
+      .map(|topic| PyTopic { topic, data_type })
+      .map_err(runtime_error)
+  }
+
+  /// Create a DataWriter. Without `qos`, the Topic QoS is used.
+  #[pyo3(signature = (topic, qos = None))]
+  fn create_writer(&self, topic: &PyTopic, qos: Option<PyQos>) -> PyResult<PyDataWriter> {
+    self
+      .publisher
+      .create_datawriter_no_key::<RawSample, RawSampleAdapter>(
+        &topic.topic,
+        qos.map(PyQos::to_qos_policies),
+      )
+      .map(|writer| PyDataWriter {
+        writer,
+        data_type: topic.data_type.clone(),
+      })
+      .map_err(runtime_error)
+  }
+
+  /// Create a DataReader. Without `qos`, the Topic QoS is used.
+  #[pyo3(signature = (topic, qos = None))]
+  fn create_reader(&self, topic: &PyTopic, qos: Option<PyQos>) -> PyResult<PyDataReader> {
+    self
+      .subscriber
+      .create_datareader_no_key::<RawSample, RawSampleAdapter>(
+        &topic.topic,
+        qos.map(PyQos::to_qos_policies),
+      )
+      .map(|reader| PyDataReader {
+        reader,
+        data_type: topic.data_type.clone(),
+      })
+      .map_err(runtime_error)
+  }
+}
+
+#[pyclass(name = "Topic", unsendable)]
+pub struct PyTopic {
+  topic: Topic,
+  data_type: Option<Arc<DynamicType>>,
+}
+
+#[pymethods]
+impl PyTopic {
+  #[getter]
+  fn name(&self) -> String {
+    self.topic.name()
+  }
+
+  #[getter]
+  fn type_name(&self) -> String {
+    self.topic.get_type().name().to_string()
+  }
+}
+
+#[pyclass(name = "DataWriter", unsendable)]
+pub struct PyDataWriter {
+  writer: DataWriter<RawSample, RawSampleAdapter>,
+  data_type: Option<Arc<DynamicType>>,
+}
+
+#[pymethods]
+impl PyDataWriter {
+  /// Write a sample: a value of the Topic data type, or a serialized payload
+  /// if the Topic has no data type. `encoding` is the RTPS representation
+  /// identifier, by default 0x0001, i.e. little-endian CDR. Values are
+  /// serialized as little- or big-endian CDR.
+  #[pyo3(signature = (data, encoding = 0x0001))]
+  fn write(&self, data: &Bound<'_, PyAny>, encoding: u16) -> PyResult<()> {
+    let encoding =
+      RepresentationIdentifier::from_bytes(&encoding.to_be_bytes()).map_err(runtime_error)?;
+    let value = match &self.data_type {
+      Some(data_type) => data_type
+        .serialize_value(&value_from_py(data_type, data)?, encoding)
+        .map_err(value_error)?,
+      None => Bytes::copy_from_slice(data.extract()?),
+    };
+    self
+      .writer
+      .write_with_encoding(
+        RawSample { encoding, value },
+        encoding,
+        WriteOptions::from(Some(Timestamp::now())),
+      )
+      .map(|_| ())
+      .map_err(runtime_error)
+  }
+
+  #[getter]
+  fn guid(&self) -> String {
+    format!("{:?}", self.writer.guid())
+  }
+}
+
+/// A received sample
+#[pyclass(name = "Sample", frozen)]
+pub struct PySample {
+  data: Bytes,
+  #[pyo3(get)]
+  encoding: u16,
+  /// Seconds since the Unix epoch, or None
+  #[pyo3(get)]
+  source_timestamp: Option<f64>,
+  #[pyo3(get)]
+  writer_guid: String,
+  data_type: Option<Arc<DynamicType>>,
+}
+
+#[pymethods]
+impl PySample {
+  #[getter]
+  fn data<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+    PyBytes::new(py, &self.data)
+  }
+
+  /// The sample as a Python value of the Topic data type, or None if the
+  /// Topic has no data type.
+  #[getter]
+  fn value(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+    let Some(data_type) = &self.data_type else {
+      return Ok(None);
+    };
+    let encoding =
+      RepresentationIdentifier::from_bytes(&self.encoding.to_be_bytes()).map_err(runtime_error)?;
+    let value = data_type
+      .deserialize_value(&self.data, encoding)
+      .map_err(value_error)?;
+    value_to_py(py, data_type, &value).map(Some)
+  }
+}
+
+#[pyclass(name = "DataReader", unsendable)]
+pub struct PyDataReader {
+  reader: DataReader<RawSample, RawSampleAdapter>,
+  data_type: Option<Arc<DynamicType>>,
+}
+
+#[pymethods]
+impl PyDataReader {
+  /// Take the next sample. If there is none, wait up to `timeout` seconds for
+  /// one. Returns None if there is still no sample.
+  #[pyo3(signature = (timeout = 0.0))]
+  fn take(&mut self, py: Python<'_>, timeout: f64) -> PyResult<Option<PySample>> {
+    let timeout = StdDuration::try_from_secs_f64(timeout).map_err(value_error)?;
+    let deadline = Instant::now()
+      .checked_add(timeout)
+      .ok_or_else(|| PyValueError::new_err("timeout is too long"))?;
+    loop {
+      if let Some(ds) = self.reader.take_next_sample().map_err(runtime_error)? {
+        let source_timestamp = ds
+          .sample_info()
+          .source_timestamp()
+          .map(|ts| ts.duration_since(Timestamp::ZERO).to_nanoseconds() as f64 / 1e9);
+        let writer_guid = format!("{:?}", ds.sample_info().writer_guid());
+        let raw = ds.into_value();
+        return Ok(Some(PySample {
+          data: raw.value,
+          encoding: u16::from_be_bytes(raw.encoding.to_bytes()),
+          source_timestamp,
+          writer_guid,
+          data_type: self.data_type.clone(),
+        }));
+      }
+      if Instant::now() >= deadline {
+        return Ok(None);
+      }
+      py.allow_threads(|| thread::sleep(StdDuration::from_millis(10)));
+      py.check_signals()?;
+    }
+  }
+
+  /// Take all available samples without waiting.
+  fn take_all(&mut self, py: Python<'_>) -> PyResult<Vec<PySample>> {
+    let mut samples = Vec::new();
+    while let Some(sample) = self.take(py, 0.0)? {
+      samples.push(sample);
+    }
+    Ok(samples)
+  }
+
+  /// File descriptor for `select()`. It is readable when new data may be
+  /// available, until all samples are taken.
+  #[cfg(not(target_os = "windows"))]
+  fn fileno(&self) -> i32 {
+    self.reader.as_raw_fd()
+  }
+
+  #[getter]
+  fn guid(&self) -> String {
+    format!("{:?}", self.reader.guid())
+  }
+}
+
+/// The Python module `rustdds`
+#[pymodule]
+fn rustdds(m: &Bound<'_, PyModule>) -> PyResult<()> {
+  m.add_class::<PyQos>()?;
+  m.add_class::<PyParticipant>()?;
+  m.add_class::<PyTopic>()?;
+  m.add_class::<PyDataWriter>()?;
+  m.add_class::<PyDataReader>()?;
+  m.add_class::<PySample>()?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use pyo3::ffi::c_str;
+
+  use super::*;
+
+  fn with_python<R>(f: impl FnOnce(Python<'_>) -> R) -> R {
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(f)
+  }
+
+  #[test]
+  fn values_follow_data_type_description() {
+    with_python(|py| {
+      let spec = py
+        .eval(
+          c_str!(
+            "{'name': 'string', 'id': 'int16', 'flags': ['bool'], 'position': ('float64', 3), \
+             'inner': {'count': 'uint32'}}"
+          ),
+          None,
+          None,
+        )
+        .unwrap();
+      let data_type = data_type_from_py(&spec).unwrap();
+      assert_eq!(
+        data_type,
+        DynamicType::Struct(vec![
+          ("name".to_string(), DynamicType::String),
+          ("id".to_string(), DynamicType::Int16),
+          (
+            "flags".to_string(),
+            DynamicType::Sequence(Box::new(DynamicType::Boolean))
+          ),
+          (
+            "position".to_string(),
+            DynamicType::Array(Box::new(DynamicType::Float64), 3)
+          ),
+          (
+            "inner".to_string(),
+            DynamicType::Struct(vec![("count".to_string(), DynamicType::UInt32)])
+          ),
+        ])
+      );
+
+      let value = py
+        .eval(
+          c_str!(
+            "{'name': 'abc', 'id': -3, 'flags': [True, False], 'position': [1.0, 2.0, 3.5], \
+             'inner': {'count': 7}}"
+          ),
+          None,
+          None,
+        )
+        .unwrap();
+      let encoding = RepresentationIdentifier::CDR_LE;
+      let payload = data_type
+        .serialize_value(&value_from_py(&data_type, &value).unwrap(), encoding)
+        .unwrap();
+      let decoded = data_type.deserialize_value(&payload, encoding).unwrap();
+      let decoded = value_to_py(py, &data_type, &decoded).unwrap();
+      assert!(decoded.bind(py).eq(&value).unwrap());
+    });
+  }
+
+  #[test]
+  fn invalid_data_types_and_values_are_rejected() {
+    with_python(|py| {
+      for spec in [
+        c_str!("'int128'"),
+        c_str!("['int8', 'int8']"),
+        c_str!("('int8',)"),
+        c_str!("3"),
+      ] {
+        let spec = py.eval(spec, None, None).unwrap();
+        assert!(data_type_from_py(&spec).is_err());
+      }
+
+      let data_type = DynamicType::Struct(vec![("x".to_string(), DynamicType::UInt8)]);
+      for value in [
+        c_str!("{'x': 256}"),
+        c_str!("{'x': -1}"),
+        c_str!("{'y': 1}"),
+        c_str!("{'x': 'a'}"),
+      ] {
+        let value = py.eval(value, None, None).unwrap();
+        let result = value_from_py(&data_type, &value).and_then(|value| {
+          data_type
+            .serialize_value(&value, RepresentationIdentifier::CDR_LE)
+            .map_err(value_error)
+        });
+        assert!(result.is_err());
+      }
+    });
+  }
+
+  #[test]
+  fn take_rejects_invalid_timeouts() {
+    with_python(|py| {
+      let participant = PyParticipant::new(0).unwrap();
+      let topic = participant
+        .create_topic("python_timeout_test", "Bytes", None, None)
+        .unwrap();
+      let mut reader = participant.create_reader(&topic, None).unwrap();
+
+      assert!(reader.take(py, 0.0).unwrap().is_none());
+      for timeout in [-1.0, f64::NAN, f64::INFINITY, 1e300] {
+        let result = reader.take(py, timeout);
+        assert!(matches!(result, Err(e) if e.is_instance_of::<PyValueError>(py)));
+      }
+    });
+  }
+}
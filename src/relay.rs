@@ -0,0 +1,421 @@
+//! Helpers for relaying RTPS messages between networks.
+//!
+//! A relay (or bridge, or router) receives RTPS messages from one network and
+//! sends them on to another. The RTPS header of a forwarded message must
+//! identify the relay, because that is who actually sent the datagram.
+//! Receivers still need to know who the message originally came from: the
+//! GuidPrefix of the original sender determines the GUIDs of the Writers and
+//! Readers whose submessages are in the message. RTPS spec v2.5 Section 8.3.7.9
+//! provides the INFO_SRC submessage for this. It overrides the source
+//! information from the header for the submessages that follow it.
+//!
+//! [`forward_message`] rewrites the header to identify the relay, and inserts
+//! an INFO_SRC that carries the original header contents. The submessages
+//! themselves are copied unchanged, so also submessages that RustDDS does not
+//! understand are forwarded. A message that already starts with INFO_SRC,
+//! e.g. because it has passed through another relay, gets only its header
+//! rewritten.
+//!
+//! RustDDS processes received INFO_SRC submessages, so a DomainParticipant can
+//! communicate with remote participants over a relay, as long as discovery
+//! data also passes through the relay.
+//!
+//! Messages protected with DDS Security RTPS message protection (SRTPS_PREFIX)
+//! cannot be modified, because the header is covered by the protection. These
+//! must be forwarded as they are.
+//!
+//! When the same data can reach a bridge over several paths, [`SeenSamples`]
+//! tells which samples have already been forwarded.
+
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  io,
+};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use enumflags2::BitFlags;
+use speedy::{Endianness, Readable, Writable};
+
+use crate::{
+  messages::{
+    header::Header,
+    submessages::{
+      info_source::InfoSource,
+      submessage_flag::{endianness_flag, INFOSOURCE_Flags},
+      submessage_kind::SubmessageKind,
+    },
+    validity_trait::Validity,
+  },
+  structure::{
+    guid::{GuidPrefix, GUID},
+    sequence_number::SequenceNumber,
+  },
+};
+
+const RTPS_HEADER_SIZE: usize = 20;
+const SUBMESSAGE_HEADER_SIZE: usize = 4;
+
+/// Rewrite the RTPS message `msg_bytes` (UDP datagram payload) for
+/// forwarding by the relay participant `relay_guid_prefix`. The original
+/// sender is preserved in an INFO_SRC submessage.
+///
+/// Returns an error if `msg_bytes` does not start with a valid RTPS header, or
+/// if the message has RTPS message protection.
+pub fn forward_message(msg_bytes: &[u8], relay_guid_prefix: GuidPrefix) -> io::Result<Bytes> {
+  let header = read_header(msg_bytes)?;
+  let submessages = &msg_bytes[RTPS_HEADER_SIZE..];
+
+  let first_kind = submessages.first().copied();
+  if first_kind == Some(u8::from(SubmessageKind::SRTPS_PREFIX)) {
+    return Err(io::Error::new(
+      io::ErrorKind::Unsupported,
+      "Cannot rewrite a message that has RTPS message protection",
+    ));
+  }
+
+  let mut forwarded = BytesMut::with_capacity(msg_bytes.len() + 24);
+  forwarded.put_slice(&write_bytes(&Header::new(relay_guid_prefix))?);
+  if first_kind != Some(u8::from(SubmessageKind::INFO_SRC)) {
+    let info_source = InfoSource::from(header).create_submessage(
+      BitFlags::<INFOSOURCE_Flags>::from_flag(INFOSOURCE_Flags::Endianness),
+    );
+    forwarded.put_slice(&write_bytes(&info_source)?);
+  }
+  forwarded.put_slice(submessages);
+  Ok(forwarded.freeze())
+}
+
+/// The GuidPrefix of the participant that originally sent the RTPS message
+/// `msg_bytes`, even if it has been forwarded by relays. A relay can use this
+/// to avoid forwarding a message back to where it came from.
+///
+/// This looks only at the header and a possible INFO_SRC at the start of the
+/// message.
+pub fn message_origin(msg_bytes: &[u8]) -> io::Result<GuidPrefix> {
+  let header = read_header(msg_bytes)?;
+  let submessages = &msg_bytes[RTPS_HEADER_SIZE..];
+
+  match submessages {
+    [kind, flags, ..] if *kind == u8::from(SubmessageKind::INFO_SRC) => {
+      let content = submessages
+        .get(SUBMESSAGE_HEADER_SIZE..)
+        .unwrap_or_default();
+      InfoSource::read_from_buffer_with_ctx(endianness_flag(*flags), content)
+        .map(|info_source| info_source.guid_prefix)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+    _ => Ok(header.guid_prefix),
+  }
+}
+
+/// Receives the watermarks of a [`SeenSamples`] as they advance, so that they
+/// can be stored and given back to [`SeenSamples::restore`] after a restart.
+/// Without this, a restarted bridge would forward again samples that it had
+/// already forwarded before the restart.
+pub trait WatermarkPersistence {
+  /// All samples of `writer` up to and including `watermark` have been seen.
+  /// Called whenever the watermark advances.
+  fn store(&mut self, writer: GUID, watermark: SequenceNumber);
+
+  /// Samples of `writer` are no longer tracked, see
+  /// [`SeenSamples::forget_writer`].
+  fn remove(&mut self, _writer: GUID) {}
+}
+
+/// Tracks which samples (writer GUID and sequence number) have been seen, so
+/// that a bridge receiving the same data over several paths, e.g. redundant
+/// networks, can forward each sample only once.
+///
+/// For each writer, this remembers a high watermark, below which all samples
+/// have been seen, and the samples seen above it. Memory use is bounded by a
+/// window: if a sample arrives more than `window` sequence numbers above the
+/// watermark, the missing samples are considered lost, and the watermark
+/// advances. A lost sample that arrives later counts as seen.
+///
+/// The sample identity is available from received samples as
+/// [`SampleInfo::sample_identity`](crate::SampleInfo::sample_identity).
+///
+/// # Example
+///
+/// ```
+/// use rustdds::{relay::SeenSamples, EntityId, GuidPrefix, SequenceNumber, GUID};
+///
+/// let mut seen = SeenSamples::new();
+/// let writer = GUID::new(GuidPrefix::new(b"Writer"), EntityId::UNKNOWN);
+///
+/// assert!(seen.mark_seen(writer, SequenceNumber::new(1))); // first path
+/// assert!(!seen.mark_seen(writer, SequenceNumber::new(1))); // second path
+/// assert!(seen.has_seen(writer, SequenceNumber::new(1)));
+/// assert_eq!(seen.watermark(writer), Some(SequenceNumber::new(1)));
+/// ```
+pub struct SeenSamples {
+  writers: BTreeMap<GUID, WriterWatermark>,
+  window: i64,
+  persistence: Option<Box<dyn WatermarkPersistence + Send>>,
+}
+
+#[derive(Debug)]
+struct WriterWatermark {
+  // All samples up to and including this have been seen (or are lost)
+  watermark: SequenceNumber,
+  // Seen samples above the watermark
+  seen_above: BTreeSet<SequenceNumber>,
+}
+
+impl SeenSamples {
+  /// Default window, see [`SeenSamples`]
+  pub const DEFAULT_WINDOW: i64 = 1024;
+
+  pub fn new() -> Self {
+    Self {
+      writers: BTreeMap::new(),
+      window: Self::DEFAULT_WINDOW,
+      persistence: None,
+    }
+  }
+
+  /// Continue from watermarks stored by a [`WatermarkPersistence`], which
+  /// also receives the watermarks from now on.
+  pub fn restore(
+    watermarks: impl IntoIterator<Item = (GUID, SequenceNumber)>,
+    persistence: Box<dyn WatermarkPersistence + Send>,
+  ) -> Self {
+    Self {
+      writers: watermarks
+        .into_iter()
+        .map(|(writer, watermark)| {
+          (
+            writer,
+            WriterWatermark {
+              watermark,
+              seen_above: BTreeSet::new(),
+            },
+          )
+        })
+        .collect(),
+      window: Self::DEFAULT_WINDOW,
+      persistence: Some(persistence),
+    }
+  }
+
+  /// Set how far above the watermark samples are tracked individually.
+  /// Minimum is 1.
+  pub fn window(mut self, window: i64) -> Self {
+    self.window = window.max(1);
+    self
+  }
+
+  /// Has the sample been seen, or is it below the watermark?
+  pub fn has_seen(&self, writer: GUID, sequence_number: SequenceNumber) -> bool {
+    self
+      .writers
+      .get(&writer)
+      .is_some_and(|w| sequence_number <= w.watermark || w.seen_above.contains(&sequence_number))
+  }
+
+  /// Record the sample as seen. Returns `true` if it was not seen before,
+  /// i.e. it should be forwarded.
+  pub fn mark_seen(&mut self, writer: GUID, sequence_number: SequenceNumber) -> bool {
+    let w = self
+      .writers
+      .entry(writer)
+      .or_insert_with(|| WriterWatermark {
+        watermark: SequenceNumber::zero(),
+        seen_above: BTreeSet::new(),
+      });
+    if sequence_number <= w.watermark || !w.seen_above.insert(sequence_number) {
+      return false;
+    }
+
+    let old_watermark = w.watermark;
+    // Give up on samples that have fallen out of the window
+    let window_start = sequence_number - SequenceNumber::new(self.window);
+    if window_start > w.watermark {
+      w.watermark = window_start;
+      w.seen_above = w.seen_above.split_off(&window_start.plus_1());
+    }
+    // Advance over contiguous seen samples
+    while w.seen_above.remove(&w.watermark.plus_1()) {
+      w.watermark = w.watermark.plus_1();
+    }
+
+    if w.watermark != old_watermark {
+      if let Some(persistence) = self.persistence.as_mut() {
+        persistence.store(writer, w.watermark);
+      }
+    }
+    true
+  }
+
+  /// The high watermark of a writer: all its samples up to and including
+  /// this have been seen. `None` if nothing has been seen from the writer.
+  pub fn watermark(&self, writer: GUID) -> Option<SequenceNumber> {
+    self.writers.get(&writer).map(|w| w.watermark)
+  }
+
+  /// Watermarks of all tracked writers
+  pub fn watermarks(&self) -> impl Iterator<Item = (GUID, SequenceNumber)> + '_ {
+    self.writers.iter().map(|(guid, w)| (*guid, w.watermark))
+  }
+
+  /// Stop tracking a writer, e.g. when it has been lost.
+  pub fn forget_writer(&mut self, writer: GUID) {
+    if self.writers.remove(&writer).is_some() {
+      if let Some(persistence) = self.persistence.as_mut() {
+        persistence.remove(writer);
+      }
+    }
+  }
+}
+
+impl Default for SeenSamples {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl std::fmt::Debug for SeenSamples {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("SeenSamples")
+      .field("writers", &self.writers)
+      .field("window", &self.window)
+      .field("persistence", &self.persistence.is_some())
+      .finish()
+  }
+}
+
+fn read_header(msg_bytes: &[u8]) -> io::Result<Header> {
+  let header = msg_bytes
+    .get(..RTPS_HEADER_SIZE)
+    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Too short for RTPS header"))
+    .and_then(|bytes| {
+      Header::read_from_buffer(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    })?;
+  if header.valid() {
+    Ok(header)
+  } else {
+    Err(io::Error::new(
+      io::ErrorKind::InvalidData,
+      "Not a valid RTPS header",
+    ))
+  }
+}
+
+fn write_bytes(value: &impl Writable<Endianness>) -> io::Result<Vec<u8>> {
+  value
+    .write_to_vec_with_ctx(Endianness::LittleEndian)
+    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{rtps::Message, structure::guid::EntityId};
+
+  // INFO_DST, ACKNACK
+  const ACKNACK_MESSAGE: [u8; 64] = [
+    0x52, 0x54, 0x50, 0x53, 0x02, 0x03, 0x01, 0x0f, 0x01, 0x0f, 0x99, 0x06, 0x78, 0x34, 0x00, 0x00,
+    0x01, 0x00, 0x00, 0x00, 0x0e, 0x01, 0x0c, 0x00, 0x01, 0x03, 0x00, 0x0c, 0x29, 0x2d, 0x31, 0xa2,
+    0x28, 0x20, 0x02, 0x08, 0x06, 0x03, 0x18, 0x00, 0x00, 0x00, 0x04, 0xc7, 0x00, 0x00, 0x04, 0xc2,
+    0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00,
+  ];
+
+  #[test]
+  fn forward_preserves_origin() {
+    let origin = GuidPrefix::new(&ACKNACK_MESSAGE[8..20]);
+    let relay1 = GuidPrefix::new(b"FirstRelay..");
+    let relay2 = GuidPrefix::new(b"SecondRelay.");
+
+    let once = forward_message(&ACKNACK_MESSAGE, relay1).unwrap();
+    assert_eq!(once.len(), ACKNACK_MESSAGE.len() + 24);
+    assert_eq!(message_origin(&once).unwrap(), origin);
+    assert_eq!(&once[44..], &ACKNACK_MESSAGE[20..]);
+
+    let parsed = Message::read_from_buffer(&once).unwrap();
+    assert_eq!(parsed.header.guid_prefix, relay1);
+    assert_eq!(parsed.submessages.len(), 3);
+
+    // Second hop does not add another INFO_SRC
+    let twice = forward_message(&once, relay2).unwrap();
+    assert_eq!(twice.len(), once.len());
+    assert_eq!(message_origin(&twice).unwrap(), origin);
+    assert_eq!(
+      Message::read_from_buffer(&twice)
+        .unwrap()
+        .header
+        .guid_prefix,
+      relay2
+    );
+  }
+
+  #[test]
+  fn forward_rejects_invalid() {
+    let relay = GuidPrefix::new(b"Relay");
+    assert!(forward_message(&ACKNACK_MESSAGE[..10], relay).is_err());
+
+    let mut not_rtps = ACKNACK_MESSAGE;
+    not_rtps[0] = b'X';
+    assert!(forward_message(&not_rtps, relay).is_err());
+    assert!(message_origin(&not_rtps).is_err());
+
+    let mut protected = ACKNACK_MESSAGE;
+    protected[20] = u8::from(SubmessageKind::SRTPS_PREFIX);
+    assert_eq!(
+      forward_message(&protected, relay).unwrap_err().kind(),
+      io::ErrorKind::Unsupported
+    );
+  }
+
+  #[derive(Clone, Default)]
+  struct SharedStore(std::sync::Arc<std::sync::Mutex<BTreeMap<GUID, SequenceNumber>>>);
+
+  impl WatermarkPersistence for SharedStore {
+    fn store(&mut self, writer: GUID, watermark: SequenceNumber) {
+      self.0.lock().unwrap().insert(writer, watermark);
+    }
+    fn remove(&mut self, writer: GUID) {
+      self.0.lock().unwrap().remove(&writer);
+    }
+  }
+
+  fn sn(n: i64) -> SequenceNumber {
+    SequenceNumber::new(n)
+  }
+
+  #[test]
+  fn seen_samples_deduplicate_out_of_order_paths() {
+    let writer = GUID::new(GuidPrefix::new(b"Writer"), EntityId::UNKNOWN);
+    let store = SharedStore::default();
+    let mut seen = SeenSamples::restore(vec![], Box::new(store.clone())).window(10);
+
+    // Path A delivers 1, 3, 4, path B delivers 2, 1, 3
+    assert!(seen.mark_seen(writer, sn(1)));
+    assert!(seen.mark_seen(writer, sn(3)));
+    assert!(seen.mark_seen(writer, sn(4)));
+    assert_eq!(seen.watermark(writer), Some(sn(1)));
+    assert!(!seen.has_seen(writer, sn(2)));
+    assert!(seen.mark_seen(writer, sn(2)));
+    assert!(!seen.mark_seen(writer, sn(1)));
+    assert!(!seen.mark_seen(writer, sn(3)));
+    assert_eq!(seen.watermark(writer), Some(sn(4)));
+    assert_eq!(store.0.lock().unwrap().get(&writer), Some(&sn(4)));
+
+    // 5 is lost on both paths. It falls out of the window.
+    assert!(seen.mark_seen(writer, sn(6)));
+    assert_eq!(seen.watermark(writer), Some(sn(4)));
+    assert!(seen.mark_seen(writer, sn(20)));
+    assert_eq!(seen.watermark(writer), Some(sn(10)));
+    assert!(seen.has_seen(writer, sn(5)));
+    assert!(!seen.has_seen(writer, sn(11)));
+
+    // Restart
+    let stored = store.0.lock().unwrap().clone();
+    let mut restarted = SeenSamples::restore(stored, Box::new(store.clone()));
+    assert!(!restarted.mark_seen(writer, sn(3)));
+    assert!(restarted.mark_seen(writer, sn(11)));
+
+    restarted.forget_writer(writer);
+    assert_eq!(restarted.watermark(writer), None);
+    assert!(store.0.lock().unwrap().is_empty());
+  }
+}
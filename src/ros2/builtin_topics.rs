@@ -33,6 +33,23 @@ impl ROSDiscoveryTopic {
     lifespan: Some(Lifespan {
       duration: Duration::INFINITE,
     }),
+    user_data: None,
+    topic_data: None,
+    group_data: None,
+    writer_data_lifecycle: None,
+    reader_data_lifecycle: None,
+    publish_mode: None,
+    history_replay: None,
+    shared_subscription: None,
+    ignore_local: None,
+    entity_factory: None,
+    time_ordered_delivery: None,
+    instance_limit: None,
+    batching: None,
+    adaptive_timing: None,
+    compression: None,
+    payload_checksum: None,
+    notification_coalescing: None,
     #[cfg(feature = "security")]
     property: None,
   };
@@ -71,6 +88,23 @@ impl ParameterEventsTopic {
     history: Some(History::KeepLast { depth: 1 }),
     resource_limits: None,
     lifespan: None,
+    user_data: None,
+    topic_data: None,
+    group_data: None,
+    writer_data_lifecycle: None,
+    reader_data_lifecycle: None,
+    publish_mode: None,
+    history_replay: None,
+    shared_subscription: None,
+    ignore_local: None,
+    entity_factory: None,
+    time_ordered_delivery: None,
+    instance_limit: None,
+    batching: None,
+    adaptive_timing: None,
+    compression: None,
+    payload_checksum: None,
+    notification_coalescing: None,
     #[cfg(feature = "security")]
     property: None,
   };
@@ -115,6 +149,23 @@ impl RosOutTopic {
     lifespan: Some(Lifespan {
       duration: Duration::from_secs(10),
     }),
+    user_data: None,
+    topic_data: None,
+    group_data: None,
+    writer_data_lifecycle: None,
+    reader_data_lifecycle: None,
+    publish_mode: None,
+    history_replay: None,
+    shared_subscription: None,
+    ignore_local: None,
+    entity_factory: None,
+    time_ordered_delivery: None,
+    instance_limit: None,
+    batching: None,
+    adaptive_timing: None,
+    compression: None,
+    payload_checksum: None,
+    notification_coalescing: None,
     #[cfg(feature = "security")]
     property: None,
   };
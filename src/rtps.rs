@@ -1,8 +1,11 @@
 #[allow(dead_code)] // We allow this, since extra constants are not too harmful.
 pub(crate) mod constant;
 
+pub(crate) mod compatibility;
 pub(crate) mod dp_event_loop;
+pub(crate) mod event_scheduler;
 pub(crate) mod fragment_assembler;
+pub(crate) mod local_endpoints;
 pub(crate) mod message_receiver;
 pub(crate) mod reader;
 pub(crate) mod rtps_reader_proxy;
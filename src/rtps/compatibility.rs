@@ -0,0 +1,76 @@
+// Registry of the features and quirks of other DDS implementations.
+//
+// Behavior that depends on the implementation of a remote participant is
+// decided by looking up its vendor id and RTPS protocol version here, rather
+// than by vendor checks scattered around the code. An interoperability fix
+// then consists of a new Feature, an entry in REGISTRY, and a call to
+// features() at the affected code path.
+
+use std::ops::RangeInclusive;
+
+use enumflags2::{bitflags, make_bitflags, BitFlags};
+
+use crate::messages::{protocol_version::ProtocolVersion, vendor_id::VendorId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+#[bitflags]
+pub(crate) enum Feature {
+  // Understands the RustDDS vendor-specific parameters (PID_RUSTDDS_*).
+  // Parameter ids in the vendor-specific range mean different things for
+  // different vendors, so these must be ignored when sent by anyone else.
+  RustDdsParameters,
+}
+
+struct Entry {
+  vendor: VendorId,
+  versions: RangeInclusive<ProtocolVersion>,
+  features: BitFlags<Feature>,
+}
+
+const ALL_VERSIONS: RangeInclusive<ProtocolVersion> = ProtocolVersion::PROTOCOLVERSION_1_0
+  ..=ProtocolVersion {
+    major: 255,
+    minor: 255,
+  };
+
+const REGISTRY: &[Entry] = &[Entry {
+  vendor: VendorId::ATOSTEK,
+  versions: ALL_VERSIONS,
+  features: make_bitflags!(Feature::{RustDdsParameters}),
+}];
+
+// The features of a participant, as identified by the vendor id and protocol
+// version in its messages or discovery data.
+pub(crate) fn features(vendor: VendorId, version: ProtocolVersion) -> BitFlags<Feature> {
+  REGISTRY
+    .iter()
+    .filter(|entry| entry.vendor == vendor && entry.versions.contains(&version))
+    .fold(BitFlags::empty(), |features, entry| {
+      features | entry.features
+    })
+}
+
+pub(crate) fn has_feature(vendor: VendorId, version: ProtocolVersion, feature: Feature) -> bool {
+  features(vendor, version).contains(feature)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn features_by_vendor_and_version() {
+    let v2_4 = ProtocolVersion::PROTOCOLVERSION_2_4;
+    assert!(has_feature(
+      VendorId::THIS_IMPLEMENTATION,
+      v2_4,
+      Feature::RustDdsParameters
+    ));
+    let eprosima = VendorId {
+      vendor_id: [0x01, 0x0F],
+    };
+    assert!(features(eprosima, ProtocolVersion::PROTOCOLVERSION_2_3).is_empty());
+    assert!(features(VendorId::VENDOR_UNKNOWN, v2_4).is_empty());
+  }
+}
@@ -17,6 +17,10 @@ pub const PREEMPTIVE_ACKNACK_PERIOD: Duration = Duration::from_secs(5);
 pub const NACK_RESPONSE_DELAY: Duration = Duration::from_millis(200);
 pub const NACK_SUPPRESSION_DURATION: Duration = Duration::from_millis(0);
 
+// SEDP announcements that are written in a row are packed into RTPS messages
+// of at most this many payload bytes.
+pub const SEDP_BATCH_MAX_BYTES: usize = 8 * 1024;
+
 // Helper list for initializing remote standard (non-secure) built-in readers
 pub const STANDARD_BUILTIN_READERS_INIT_LIST: &[(EntityId, EntityId, u32)] = &[
   (
@@ -56,7 +60,7 @@ pub const STANDARD_BUILTIN_WRITERS_INIT_LIST: &[(EntityId, EntityId, u32)] = &[
   (
     EntityId::SEDP_BUILTIN_SUBSCRIPTIONS_WRITER, // SEDP ...
     EntityId::SEDP_BUILTIN_SUBSCRIPTIONS_READER,
-    BuiltinEndpointSet::PUBLICATIONS_ANNOUNCER,
+    BuiltinEndpointSet::SUBSCRIPTIONS_ANNOUNCER,
   ),
   (
     EntityId::SEDP_BUILTIN_PUBLICATIONS_WRITER,
@@ -75,6 +79,34 @@ pub const STANDARD_BUILTIN_WRITERS_INIT_LIST: &[(EntityId, EntityId, u32)] = &[
   ),
 ];
 
+// Helper lists for initializing the remote RustDDS-specific ping endpoints.
+// These are used only with RustDDS participants.
+pub const PING_BUILTIN_READERS_INIT_LIST: &[(EntityId, EntityId, u32)] = &[
+  (
+    EntityId::RUSTDDS_PING_REQUEST_WRITER,
+    EntityId::RUSTDDS_PING_REQUEST_READER,
+    BuiltinEndpointSet::RUSTDDS_PING_RESPONDER,
+  ),
+  (
+    EntityId::RUSTDDS_PING_REPLY_WRITER,
+    EntityId::RUSTDDS_PING_REPLY_READER,
+    BuiltinEndpointSet::RUSTDDS_PING_REQUESTER,
+  ),
+];
+
+pub const PING_BUILTIN_WRITERS_INIT_LIST: &[(EntityId, EntityId, u32)] = &[
+  (
+    EntityId::RUSTDDS_PING_REQUEST_WRITER,
+    EntityId::RUSTDDS_PING_REQUEST_READER,
+    BuiltinEndpointSet::RUSTDDS_PING_REQUESTER,
+  ),
+  (
+    EntityId::RUSTDDS_PING_REPLY_WRITER,
+    EntityId::RUSTDDS_PING_REPLY_READER,
+    BuiltinEndpointSet::RUSTDDS_PING_RESPONDER,
+  ),
+];
+
 // Helper list for initializing the authentication topic built-in reader
 #[cfg(feature = "security")]
 pub const AUTHENTICATION_BUILTIN_READERS_INIT_LIST: &[(EntityId, EntityId, u32)] = &[(
@@ -232,6 +264,7 @@ pub const DISCOVERY_TOPIC_DATA_TOKEN: Token = Token(37 + PTB);
 pub const DISCOVERY_TOPIC_CLEANUP_TOKEN: Token = Token(38 + PTB);
 pub const DISCOVERY_PARTICIPANT_MESSAGE_TOKEN: Token = Token(40 + PTB);
 pub const DISCOVERY_PARTICIPANT_MESSAGE_TIMER_TOKEN: Token = Token(41 + PTB);
+pub const DISCOVERY_PING_REQUEST_TOKEN: Token = Token(42 + PTB);
 
 pub const DPEV_ACKNACK_TIMER_TOKEN: Token = Token(45 + PTB);
 
@@ -291,6 +324,9 @@ pub mod builtin_topic_names {
   pub const DCPS_TOPIC: &str = "DCPSTopic";
   // DDS-RTPS 2.5: 8.4.13.4
   pub const DCPS_PARTICIPANT_MESSAGE: &str = "DCPSParticipantMessage";
+  // RustDDS-specific
+  pub const DCPS_PING_REQUEST: &str = "DCPSRustDDSPingRequest";
+  pub const DCPS_PING_REPLY: &str = "DCPSRustDDSPingReply";
 
   // DDS-SECURITY 1.1: 7.4
   pub const DCPS_PARTICIPANT_SECURE: &str = "DCPSParticipantSecure";
@@ -309,6 +345,7 @@ pub mod builtin_topic_type_names {
   pub const DCPS_TOPIC: &str = "DiscoveredTopicData";
 
   pub const DCPS_PARTICIPANT_MESSAGE: &str = "ParticipantMessageData";
+  pub const DCPS_PING: &str = "RustDDSPingMessage";
 
   pub const DCPS_PARTICIPANT_SECURE: &str = "ParticipantBuiltinTopicDataSecure";
   pub const DCPS_PUBLICATIONS_SECURE: &str = "PublicationBuiltinTopicDataSecure";
@@ -1,7 +1,8 @@
 use std::{
   collections::HashMap,
+  net::SocketAddr,
   rc::Rc,
-  sync::{Arc, RwLock},
+  sync::{Arc, Mutex, RwLock},
   time::{Duration, Instant},
 };
 
@@ -16,24 +17,30 @@ use crate::{
   },
   discovery::{
     discovery::DiscoveryCommand,
-    discovery_db::{discovery_db_read, DiscoveryDB},
+    discovery_db::{discovery_db_read, discovery_db_write, DiscoveryDB},
     sedp_messages::{DiscoveredReaderData, DiscoveredWriterData},
   },
-  messages::submessages::submessages::AckSubmessage,
-  network::{udp_listener::UDPListener, udp_sender::UDPSender},
+  messages::{submessages::submessages::AckSubmessage, vendor_id::VendorId},
+  network::{socket_options::SocketOptions, udp_listener::UDPListener, udp_sender::UDPSender},
   qos::HasQoSPolicy,
   rtps::{
     constant::*,
-    message_receiver::MessageReceiver,
+    event_scheduler::{EventClass, EventLoopWeights, EventScheduler, Progress},
+    message_receiver::{
+      is_rtps_ping, rtps_ping_message, MessageReceiver, MessageReceiverLimits,
+      MessageReceiverStatistics,
+    },
     reader::{Reader, ReaderIngredients},
     rtps_reader_proxy::RtpsReaderProxy,
     rtps_writer_proxy::RtpsWriterProxy,
-    writer::{Writer, WriterIngredients},
+    writer::{Writer, WriterCacheCleaning, WriterIngredients},
   },
   structure::{
     entity::RTPSEntity,
     guid::{EntityId, GuidPrefix, TokenDecode, GUID},
+    locator::Locator,
   },
+  trace::TraceSampling,
 };
 #[cfg(feature = "security")]
 use crate::{
@@ -53,6 +60,47 @@ pub struct DomainInfo {
 pub(crate) enum EventLoopCommand {
   Stop,
   PrepareStop,
+  SendRtpsPings,
+}
+
+// How to deal with RTPS ping (DDSPING) messages
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RtpsPingOptions {
+  // Where to send pings on EventLoopCommand::SendRtpsPings
+  pub peers: Vec<SocketAddr>,
+  // Whether to answer received pings with our SPDP announcement
+  pub reply_with_spdp: bool,
+}
+
+// Limits the SPDP replies to RTPS pings, so that a stream of (possibly
+// spoofed) pings cannot make us send announcements at the same rate.
+// Each source address gets at most one reply per REPLY_INTERVAL, and at most
+// MAX_SOURCES sources are remembered at a time.
+#[derive(Default)]
+struct RtpsPingReplyLimiter {
+  last_reply: HashMap<SocketAddr, Instant>,
+}
+
+impl RtpsPingReplyLimiter {
+  const REPLY_INTERVAL: Duration = Duration::from_secs(1);
+  const MAX_SOURCES: usize = 256;
+
+  fn allow_reply(&mut self, source: SocketAddr, now: Instant) -> bool {
+    if let Some(last) = self.last_reply.get(&source) {
+      if now.saturating_duration_since(*last) < Self::REPLY_INTERVAL {
+        return false;
+      }
+    } else if self.last_reply.len() >= Self::MAX_SOURCES {
+      self
+        .last_reply
+        .retain(|_, last| now.saturating_duration_since(*last) < Self::REPLY_INTERVAL);
+      if self.last_reply.len() >= Self::MAX_SOURCES {
+        return false;
+      }
+    }
+    self.last_reply.insert(source, now);
+    true
+  }
 }
 
 pub struct DPEventLoop {
@@ -80,11 +128,15 @@ pub struct DPEventLoop {
 
   writers: HashMap<EntityId, Writer>,
   udp_sender: Rc<UDPSender>,
+  rtps_ping_options: RtpsPingOptions,
+  rtps_ping_reply_limiter: RtpsPingReplyLimiter,
+  event_loop_weights: EventLoopWeights,
+  writer_cache_cleaning: WriterCacheCleaning,
+  trace_sampling: TraceSampling,
 
   participant_status_sender: StatusChannelSender<DomainParticipantStatusEvent>,
 
   discovery_update_notification_receiver: mio_channel::Receiver<DiscoveryNotificationType>,
-  #[cfg(feature = "security")]
   discovery_command_sender: mio_channel::SyncSender<DiscoveryCommand>,
 }
 
@@ -102,26 +154,25 @@ impl DPEventLoop {
     remove_writer_receiver: TokenReceiverPair<GUID>,
     stop_poll_receiver: mio_channel::Receiver<EventLoopCommand>,
     discovery_update_notification_receiver: mio_channel::Receiver<DiscoveryNotificationType>,
-    _discovery_command_sender: mio_channel::SyncSender<DiscoveryCommand>,
+    discovery_command_sender: mio_channel::SyncSender<DiscoveryCommand>,
     spdp_liveness_sender: mio_channel::SyncSender<GuidPrefix>,
     participant_status_sender: StatusChannelSender<DomainParticipantStatusEvent>,
     security_plugins_opt: Option<SecurityPluginsHandle>,
+    socket_options: SocketOptions,
+    message_receiver_limits: MessageReceiverLimits,
+    message_receiver_statistics: Arc<Mutex<MessageReceiverStatistics>>,
+    rtps_ping_options: RtpsPingOptions,
+    event_loop_weights: EventLoopWeights,
+    writer_cache_cleaning: WriterCacheCleaning,
+    trace_sampling: TraceSampling,
   ) -> Self {
-    #[cfg(not(feature = "security"))]
-    let _dummy = _discovery_command_sender;
-
     let poll = Poll::new().expect("Unable to create new poll.");
     let (acknack_sender, acknack_receiver) =
       mio_channel::sync_channel::<(GuidPrefix, AckSubmessage)>(100);
     let mut udp_listeners = udp_listeners;
     for (token, listener) in &mut udp_listeners {
-      poll
-        .register(
-          listener.mio_socket(),
-          *token,
-          Ready::readable(),
-          PollOpt::edge(),
-        )
+      listener
+        .register(&poll, *token)
         .expect("Failed to register listener.");
     }
 
@@ -188,7 +239,8 @@ impl DPEventLoop {
       .expect("Failed to register reader update notification.");
 
     // port number 0 means OS chooses an available port number.
-    let udp_sender = UDPSender::new(0).expect("UDPSender construction fail"); // TODO
+    let udp_sender =
+      UDPSender::new_with_options(0, &socket_options).expect("UDPSender construction fail"); // TODO
 
     #[cfg(not(feature = "security"))]
     let security_plugins_opt = security_plugins_opt.and(None); // make sure it is None an consume value
@@ -199,11 +251,18 @@ impl DPEventLoop {
       discovery_db,
       udp_listeners,
       udp_sender: Rc::new(udp_sender),
+      rtps_ping_options,
+      rtps_ping_reply_limiter: RtpsPingReplyLimiter::default(),
+      event_loop_weights,
+      writer_cache_cleaning,
+      trace_sampling,
       message_receiver: MessageReceiver::new(
         participant_guid_prefix,
         acknack_sender,
         spdp_liveness_sender,
         security_plugins_opt.clone(),
+        message_receiver_limits,
+        message_receiver_statistics,
       ),
       #[cfg(feature = "security")]
       security_plugins_opt,
@@ -216,8 +275,7 @@ impl DPEventLoop {
       ack_nack_receiver: acknack_receiver,
       discovery_update_notification_receiver,
       participant_status_sender,
-      #[cfg(feature = "security")]
-      discovery_command_sender: _discovery_command_sender,
+      discovery_command_sender,
     }
   }
 
@@ -236,14 +294,22 @@ impl DPEventLoop {
       )
       .unwrap();
     let mut poll_alive = Instant::now();
+    let mut scheduler = EventScheduler::new(self.event_loop_weights);
     let mut ev_wrapper = self;
     let mut preparing_to_stop = false;
 
     // loop starts here
     loop {
+      // If there is work left over from the previous round, only check for new
+      // events, so that they get their turn in the next round.
+      let timeout = if scheduler.has_pending() {
+        Duration::ZERO
+      } else {
+        Duration::from_millis(2000)
+      };
       ev_wrapper
         .poll
-        .poll(&mut events, Some(Duration::from_millis(2000)))
+        .poll(&mut events, Some(timeout))
         .expect("Failed in waiting of poll.");
 
       // liveness watchdog
@@ -253,14 +319,24 @@ impl DPEventLoop {
         poll_alive = now;
       }
 
-      if events.is_empty() {
+      if events.is_empty() && !scheduler.has_pending() {
         debug!("dp_event_loop idling.");
-      } else {
-        for event in events.iter() {
-          match EntityId::from_token(event.token()) {
-            TokenDecode::FixedToken(fixed_token) => match fixed_token {
-              STOP_POLL_TOKEN => {
-                use std::sync::mpsc::TryRecvError;
+      }
+      for event in events.iter() {
+        // Received data, Discovery results, ACKNACKs and timers are processed
+        // below, in turns. Commands are processed right away.
+        if let Some(class) = Self::event_class(event.token()) {
+          scheduler.schedule(class, event.token());
+          continue;
+        }
+        match EntityId::from_token(event.token()) {
+          TokenDecode::FixedToken(fixed_token) => match fixed_token {
+            STOP_POLL_TOKEN => {
+              use std::sync::mpsc::TryRecvError;
+              // Several commands may be queued, but edge-triggered polling
+              // signals them only once, so drain the channel.
+              let mut received_command = false;
+              loop {
                 match ev_wrapper.stop_poll_receiver.try_recv() {
                   Ok(EventLoopCommand::Stop) => {
                     info!("Stopping dp_event_loop");
@@ -269,150 +345,306 @@ impl DPEventLoop {
                   Ok(EventLoopCommand::PrepareStop) => {
                     info!("dp_event_loop preparing to stop.");
                     preparing_to_stop = true;
+                    received_command = true;
+                  }
+                  Ok(EventLoopCommand::SendRtpsPings) => {
+                    ev_wrapper.send_rtps_pings();
+                    received_command = true;
                   }
                   Err(TryRecvError::Empty) => {
-                    warn!("Spurious wake-up from dp_event_loop command channel. Very fishy.");
+                    if !received_command {
+                      warn!("Spurious wake-up from dp_event_loop command channel. Very fishy.");
+                    }
+                    break;
                   }
                   Err(TryRecvError::Disconnected) => {
                     error!(
                       "Application thread has exited abnormally. Stopping RustDDS event loop."
                     );
+                    break;
                   }
                 }
               }
-              DISCOVERY_LISTENER_TOKEN
-              | DISCOVERY_MUL_LISTENER_TOKEN
-              | USER_TRAFFIC_LISTENER_TOKEN
-              | USER_TRAFFIC_MUL_LISTENER_TOKEN => {
-                let udp_messages = ev_wrapper
-                  .udp_listeners
-                  .get_mut(&event.token())
-                  .map_or_else(
-                    || {
-                      error!("No listener with token {:?}", &event.token());
-                      vec![]
-                    },
-                    UDPListener::messages,
-                  );
-                for packet in udp_messages {
-                  ev_wrapper.message_receiver.handle_received_packet(&packet);
-                }
-              }
-              ADD_READER_TOKEN | REMOVE_READER_TOKEN => {
-                ev_wrapper.handle_reader_action(&event);
-              }
-              ADD_WRITER_TOKEN | REMOVE_WRITER_TOKEN => {
-                ev_wrapper.handle_writer_action(&event);
+            }
+            ADD_READER_TOKEN | REMOVE_READER_TOKEN => {
+              ev_wrapper.handle_reader_action(&event);
+            }
+            ADD_WRITER_TOKEN | REMOVE_WRITER_TOKEN => {
+              ev_wrapper.handle_writer_action(&event);
+            }
+
+            fixed_unknown => {
+              error!(
+                "Unknown event.token {:?} = 0x{:x?} , decoded as {:?}",
+                event.token(),
+                event.token().0,
+                fixed_unknown
+              );
+            }
+          },
+
+          // Commands/actions
+          TokenDecode::Entity(eid) => {
+            if eid.kind().is_reader() {
+              ev_wrapper.message_receiver.reader_mut(eid).map_or_else(
+                || {
+                  if !preparing_to_stop {
+                    error!("Event for unknown reader {eid:?}");
+                  }
+                },
+                Reader::process_command,
+              );
+              if ev_wrapper
+                .message_receiver
+                .reader_mut(eid)
+                .is_some_and(Reader::take_qos_updated)
+              {
+                ev_wrapper.local_reader_qos_updated(eid);
               }
-              ACKNACK_MESSAGE_TO_LOCAL_WRITER_TOKEN => {
-                ev_wrapper.handle_writer_acknack_action(&event);
+            } else if eid.kind().is_writer() {
+              let qos_updated = match ev_wrapper.writers.get_mut(&eid) {
+                None => {
+                  if !preparing_to_stop {
+                    error!("Event for unknown writer {eid:?}");
+                  };
+                  false
+                }
+                Some(writer) => {
+                  // Writer will record data to DDSCache and send it out. The
+                  // DDSCache wakes up local DataReaders of the topic.
+                  writer.process_writer_command();
+                  writer.take_qos_updated()
+                }
+              };
+              if qos_updated {
+                ev_wrapper.local_writer_qos_updated(eid);
               }
-              DISCOVERY_UPDATE_NOTIFICATION_TOKEN => {
-                while let Ok(dnt) = ev_wrapper.discovery_update_notification_receiver.try_recv() {
-                  use DiscoveryNotificationType::*;
-                  match dnt {
-                    WriterUpdated {
-                      discovered_writer_data,
-                    } => ev_wrapper.remote_writer_discovered(&discovered_writer_data),
+            } else {
+              error!("Entity Event for unknown EntityKind {eid:?}");
+            }
+          }
 
-                    WriterLost { writer_guid } => ev_wrapper.remote_writer_lost(writer_guid),
+          // Timed actions are scheduled above.
+          TokenDecode::AltEntity(eid) => {
+            error!("Unexpected AltEntity Event {eid:?}");
+          }
+        }
+      } // for
 
-                    ReaderUpdated {
-                      discovered_reader_data,
-                    } => ev_wrapper.remote_reader_discovered(&discovered_reader_data),
+      scheduler.run_round(|token, budget| {
+        ev_wrapper.handle_scheduled_event(token, budget, &mut acknack_timer)
+      });
+    } // loop
+  } // fn
 
-                    ReaderLost { reader_guid } => ev_wrapper.remote_reader_lost(reader_guid),
+  fn event_class(token: Token) -> Option<EventClass> {
+    match EntityId::from_token(token) {
+      TokenDecode::FixedToken(fixed_token) => match fixed_token {
+        USER_TRAFFIC_LISTENER_TOKEN | USER_TRAFFIC_MUL_LISTENER_TOKEN => Some(EventClass::UserData),
+        ACKNACK_MESSAGE_TO_LOCAL_WRITER_TOKEN => Some(EventClass::AckNack),
+        DPEV_ACKNACK_TIMER_TOKEN => Some(EventClass::Timers),
+        DISCOVERY_LISTENER_TOKEN
+        | DISCOVERY_MUL_LISTENER_TOKEN
+        | DISCOVERY_UPDATE_NOTIFICATION_TOKEN => Some(EventClass::Discovery),
+        _ => None,
+      },
+      TokenDecode::Entity(_) => None,
+      TokenDecode::AltEntity(_) => Some(EventClass::Timers),
+    }
+  }
 
-                    ParticipantUpdated { guid_prefix } => {
-                      ev_wrapper.update_participant(guid_prefix);
-                    }
+  // Process at most `budget` work items from the source identified by `token`.
+  fn handle_scheduled_event(
+    &mut self,
+    token: Token,
+    budget: usize,
+    acknack_timer: &mut mio_extras::timer::Timer<()>,
+  ) -> Progress {
+    match EntityId::from_token(token) {
+      TokenDecode::FixedToken(fixed_token) => match fixed_token {
+        DISCOVERY_LISTENER_TOKEN
+        | DISCOVERY_MUL_LISTENER_TOKEN
+        | USER_TRAFFIC_LISTENER_TOKEN
+        | USER_TRAFFIC_MUL_LISTENER_TOKEN => {
+          let udp_messages = self.udp_listeners.get_mut(&token).map_or_else(
+            || {
+              error!("No listener with token {:?}", &token);
+              vec![]
+            },
+            |listener| listener.messages_with_source(budget),
+          );
+          let used = udp_messages.len();
+          for (packet, source) in udp_messages {
+            if !self.message_receiver.admit_from(source, packet.len()) {
+              continue;
+            }
+            if is_rtps_ping(&packet) {
+              self.handle_rtps_ping(source);
+            } else if token == USER_TRAFFIC_MUL_LISTENER_TOKEN
+              || token == DISCOVERY_MUL_LISTENER_TOKEN
+            {
+              self
+                .message_receiver
+                .handle_received_multicast_packet(&packet);
+            } else {
+              self.message_receiver.handle_received_packet(&packet);
+            }
+          }
+          Progress {
+            used,
+            more: used >= budget,
+          }
+        }
+        ACKNACK_MESSAGE_TO_LOCAL_WRITER_TOKEN => {
+          let used = self.handle_writer_acknacks(budget);
+          Progress {
+            used,
+            more: used >= budget,
+          }
+        }
+        DISCOVERY_UPDATE_NOTIFICATION_TOKEN => {
+          let used = self.handle_discovery_notifications(budget);
+          Progress {
+            used,
+            more: used >= budget,
+          }
+        }
+        DPEV_ACKNACK_TIMER_TOKEN => {
+          self.message_receiver.send_preemptive_acknacks();
+          acknack_timer.set_timeout(PREEMPTIVE_ACKNACK_PERIOD, ());
+          Progress {
+            used: 1,
+            more: false,
+          }
+        }
+        fixed_unknown => {
+          error!("Unexpected scheduled event {token:?}, decoded as {fixed_unknown:?}");
+          Progress {
+            used: 1,
+            more: false,
+          }
+        }
+      },
+
+      // Timed Actions
+      TokenDecode::AltEntity(eid) => {
+        if eid.kind().is_reader() {
+          self.handle_reader_timed_event(eid);
+        } else if eid.kind().is_writer() {
+          self.handle_writer_timed_event(eid);
+        } else {
+          error!("AltEntity Event for unknown EntityKind {eid:?}");
+        }
+        Progress {
+          used: 1,
+          more: false,
+        }
+      }
 
-                    ParticipantLost { guid_prefix } => {
-                      ev_wrapper.remote_participant_lost(guid_prefix);
-                    }
+      TokenDecode::Entity(eid) => {
+        error!("Unexpected scheduled Entity Event {eid:?}");
+        Progress {
+          used: 1,
+          more: false,
+        }
+      }
+    }
+  }
 
-                    AssertTopicLiveliness {
-                      writer_guid,
-                      manual_assertion,
-                    } => {
-                      ev_wrapper
-                        .writers
-                        .get_mut(&writer_guid.entity_id)
-                        .map(|w| w.handle_heartbeat_tick(manual_assertion));
-                    }
+  // Returns the number of notifications processed
+  fn handle_discovery_notifications(&mut self, max: usize) -> usize {
+    let mut count = 0;
+    while count < max {
+      let dnt = match self.discovery_update_notification_receiver.try_recv() {
+        Ok(dnt) => dnt,
+        Err(_) => break,
+      };
+      count += 1;
+      use DiscoveryNotificationType::*;
+      match dnt {
+        WriterUpdated {
+          discovered_writer_data,
+        } => self.remote_writer_discovered(&discovered_writer_data, None),
 
-                    #[cfg(feature = "security")]
-                    ParticipantAuthenticationStatusChanged { guid_prefix } => {
-                      ev_wrapper.on_remote_participant_authentication_status_changed(guid_prefix);
-                    }
-                  }
-                }
-              }
-              DPEV_ACKNACK_TIMER_TOKEN => {
-                ev_wrapper.message_receiver.send_preemptive_acknacks();
-                acknack_timer.set_timeout(PREEMPTIVE_ACKNACK_PERIOD, ());
-              }
+        WriterLost { writer_guid } => self.remote_writer_lost(writer_guid),
 
-              fixed_unknown => {
-                error!(
-                  "Unknown event.token {:?} = 0x{:x?} , decoded as {:?}",
-                  event.token(),
-                  event.token().0,
-                  fixed_unknown
-                );
-              }
-            },
+        ReaderUpdated {
+          discovered_reader_data,
+        } => self.remote_reader_discovered(&discovered_reader_data, None),
 
-            // Commands/actions
-            TokenDecode::Entity(eid) => {
-              if eid.kind().is_reader() {
-                ev_wrapper.message_receiver.reader_mut(eid).map_or_else(
-                  || {
-                    if !preparing_to_stop {
-                      error!("Event for unknown reader {eid:?}");
-                    }
-                  },
-                  Reader::process_command,
-                );
-              } else if eid.kind().is_writer() {
-                let local_readers = match ev_wrapper.writers.get_mut(&eid) {
-                  None => {
-                    if !preparing_to_stop {
-                      error!("Event for unknown writer {eid:?}");
-                    };
-                    vec![]
-                  }
-                  Some(writer) => {
-                    // Writer will record data to DDSCache and send it out.
-                    writer.process_writer_command();
-                    writer.local_readers()
-                  }
-                };
-                // Notify local (same participant) readers that new data is available in the
-                // cache.
-                ev_wrapper
-                  .message_receiver
-                  .notify_data_to_readers(local_readers);
-              } else {
-                error!("Entity Event for unknown EntityKind {eid:?}");
-              }
-            }
+        ReaderLost { reader_guid } => self.remote_reader_lost(reader_guid),
 
-            // Timed Actions
-            TokenDecode::AltEntity(eid) => {
-              if eid.kind().is_reader() {
-                ev_wrapper.handle_reader_timed_event(eid);
-              } else if eid.kind().is_writer() {
-                ev_wrapper.handle_writer_timed_event(eid);
-              } else {
-                error!("AltEntity Event for unknown EntityKind {eid:?}");
-              }
-            }
-          }
-        } // for
-      } // if
-    } // loop
-  } // fn
+        ParticipantUpdated { guid_prefix } => {
+          self.update_participant(guid_prefix);
+        }
+
+        ParticipantLost { guid_prefix } => {
+          self.remote_participant_lost(guid_prefix);
+        }
+
+        AssertTopicLiveliness {
+          writer_guid,
+          manual_assertion,
+        } => {
+          self
+            .writers
+            .get_mut(&writer_guid.entity_id)
+            .map(|w| w.handle_heartbeat_tick(manual_assertion));
+        }
+
+        #[cfg(feature = "security")]
+        ParticipantAuthenticationStatusChanged { guid_prefix } => {
+          self.on_remote_participant_authentication_status_changed(guid_prefix);
+        }
+      }
+    }
+    count
+  }
+
+  // A ping does not identify the participant that sent it, only the source
+  // address. If exactly one known remote participant has that address, the
+  // ping is a sign of life from it.
+  fn handle_rtps_ping(&mut self, source: SocketAddr) {
+    debug!("Received RTPS PING from {source}");
+    let my_guid_prefix = self.domain_info.domain_participant_guid.prefix;
+    let candidates: Vec<GuidPrefix> = discovery_db_read(&self.discovery_db)
+      .participants_at_address(source.ip())
+      .into_iter()
+      .filter(|guid_prefix| *guid_prefix != my_guid_prefix)
+      .collect();
+    if let [guid_prefix] = candidates[..] {
+      discovery_db_write(&self.discovery_db).participant_is_alive(guid_prefix);
+    } else {
+      trace!("RTPS PING from {source} matches participants {candidates:?}");
+    }
+
+    if self.rtps_ping_options.reply_with_spdp
+      && self
+        .rtps_ping_reply_limiter
+        .allow_reply(source, Instant::now())
+    {
+      match self.writers.get(&EntityId::SPDP_BUILTIN_PARTICIPANT_WRITER) {
+        Some(spdp_writer) => spdp_writer.send_latest_change_to(&Locator::from(source)),
+        None => warn!("No SPDP writer to reply to RTPS PING"),
+      }
+    }
+  }
+
+  // Pings are sent from the unicast listening sockets, so that NATs and
+  // firewalls on the way let in traffic from the peers to those sockets.
+  fn send_rtps_pings(&self) {
+    let ping = rtps_ping_message();
+    for token in [DISCOVERY_LISTENER_TOKEN, USER_TRAFFIC_LISTENER_TOKEN] {
+      if let Some(listener) = self.udp_listeners.get(&token) {
+        for peer in &self.rtps_ping_options.peers {
+          listener
+            .send_to(&ping, peer)
+            .map(|_| ())
+            .unwrap_or_else(|e| warn!("Cannot send RTPS PING to {peer}: {e:?}"));
+        }
+      }
+    }
+  }
 
   #[cfg(feature = "security")] // Currently used only with security.
                                // Just remove attribute if used also without.
@@ -427,6 +659,11 @@ impl DPEventLoop {
     match event.token() {
       ADD_READER_TOKEN => {
         trace!("add reader(s)");
+        // A new Reader may reuse the EntityId of a removed one, so removals
+        // that were requested earlier must be done first.
+        while let Ok(old_reader_guid) = self.remove_reader_receiver.receiver.try_recv() {
+          self.remove_local_reader(old_reader_guid);
+        }
         while let Ok(new_reader_ing) = self.add_reader_receiver.receiver.try_recv() {
           self.add_local_reader(new_reader_ing);
         }
@@ -443,6 +680,10 @@ impl DPEventLoop {
   fn handle_writer_action(&mut self, event: &Event) {
     match event.token() {
       ADD_WRITER_TOKEN => {
+        // See ADD_READER_TOKEN
+        while let Ok(writer_guid) = self.remove_writer_receiver.receiver.try_recv() {
+          self.remove_local_writer(&writer_guid);
+        }
         while let Ok(new_writer_ingredients) = self.add_writer_receiver.receiver.try_recv() {
           self.add_local_writer(new_writer_ingredients);
         }
@@ -475,8 +716,19 @@ impl DPEventLoop {
     }
   }
 
-  fn handle_writer_acknack_action(&mut self, _event: &Event) {
-    while let Ok((acknack_sender_prefix, acknack_submessage)) = self.ack_nack_receiver.try_recv() {
+  // Returns the number of ACKNACKs processed
+  fn handle_writer_acknacks(&mut self, max: usize) -> usize {
+    let mut count = 0;
+    while count < max {
+      let (acknack_sender_prefix, acknack_submessage) = match self.ack_nack_receiver.try_recv() {
+        Ok(acknack) => acknack,
+        Err(_) => break,
+      };
+      count += 1;
+      if acknack_submessage.writer_id() == EntityId::UNKNOWN {
+        self.handle_unaddressed_acknack(acknack_sender_prefix, &acknack_submessage);
+        continue;
+      }
       let writer_guid = GUID::new_with_prefix_and_id(
         self.domain_info.domain_participant_guid.prefix,
         acknack_submessage.writer_id(),
@@ -497,9 +749,31 @@ impl DPEventLoop {
           "Couldn't handle acknack/nackfrag! Did not find local RTPS writer with GUID: {:x?}",
           writer_guid
         );
-        continue;
       }
     }
+    count
+  }
+
+  // An ACKNACK (or NACK_FRAG) with writer id UNKNOWN is meant for the writers
+  // that have the sending reader matched.
+  fn handle_unaddressed_acknack(&mut self, sender_prefix: GuidPrefix, ack: &AckSubmessage) {
+    let reader_id = ack.reader_id();
+    let mut handled = false;
+    for writer in self.writers.values_mut() {
+      if writer.is_reliable() && writer.accepts_unaddressed_ack(sender_prefix, reader_id) {
+        writer.handle_ack_nack(
+          sender_prefix,
+          &ack.addressed_to(writer.entity_id(), reader_id),
+        );
+        handled = true;
+      }
+    }
+    if !handled {
+      debug!(
+        "No local writer matched to the reader of unaddressed {:?} from {:?}",
+        ack, sender_prefix
+      );
+    }
   }
 
   fn update_participant(&mut self, participant_guid_prefix: GuidPrefix) {
@@ -522,13 +796,18 @@ impl DPEventLoop {
     // Select which builtin endpoints of the remote participant are updated to local
     // readers & writers
     #[cfg(not(feature = "security"))]
-    let (readers_init_list, writers_init_list) = (
+    let (mut readers_init_list, mut writers_init_list) = (
       STANDARD_BUILTIN_READERS_INIT_LIST.to_vec(),
       STANDARD_BUILTIN_WRITERS_INIT_LIST.to_vec(),
     );
+    #[cfg(not(feature = "security"))]
+    let ping_allowed = true;
 
+    // The ping endpoints are never used with security.
     #[cfg(feature = "security")]
-    let (readers_init_list, writers_init_list) = match &self.security_plugins_opt {
+    let ping_allowed = self.security_plugins_opt.is_none();
+    #[cfg(feature = "security")]
+    let (mut readers_init_list, mut writers_init_list) = match &self.security_plugins_opt {
       None => {
         // No security enabled, just the standard endpoints
         let readers_init_list = STANDARD_BUILTIN_READERS_INIT_LIST.to_vec();
@@ -569,6 +848,12 @@ impl DPEventLoop {
       }
     };
 
+    // The ping endpoint bits are RustDDS-specific.
+    if ping_allowed && discovered_participant.vendor_id == VendorId::THIS_IMPLEMENTATION {
+      readers_init_list.extend_from_slice(PING_BUILTIN_READERS_INIT_LIST);
+      writers_init_list.extend_from_slice(PING_BUILTIN_WRITERS_INIT_LIST);
+    }
+
     // Update local writers
     for (writer_eid, reader_eid, endpoint) in &readers_init_list {
       if let Some(writer) = self.writers.get_mut(writer_eid) {
@@ -598,6 +883,10 @@ impl DPEventLoop {
             "update_discovery writer - endpoint {:?} - {:?}",
             endpoint, discovered_participant.participant_guid
           );
+        } else {
+          // The remote does not (or no longer) have this endpoint. Do not keep
+          // sending to it.
+          writer.reader_lost(GUID::new(participant_guid_prefix, *reader_eid));
         }
       }
     }
@@ -622,6 +911,8 @@ impl DPEventLoop {
             "update_discovery_reader - endpoint {:?} - {:?}",
             *endpoint, discovered_participant.participant_guid
           );
+        } else {
+          reader.remove_writer_proxy(GUID::new(participant_guid_prefix, *writer_eid));
         }
       }
     } // for
@@ -658,9 +949,26 @@ impl DPEventLoop {
     }
   }
 
-  fn remote_reader_discovered(&mut self, remote_reader: &DiscoveredReaderData) {
-    for writer in self.writers.values_mut() {
-      if remote_reader.subscription_topic_data.topic_name() == writer.topic_name() {
+  // Match the remote reader with local writers on the same topic. If
+  // `local_writer` is given, only that writer is considered.
+  //
+  // This is called also when a known remote reader is re-announced. Then the
+  // existing reader proxies are updated in place, or removed if the reader no
+  // longer matches.
+  fn remote_reader_discovered(
+    &mut self,
+    remote_reader: &DiscoveredReaderData,
+    local_writer: Option<EntityId>,
+  ) {
+    let remote_reader_guid = remote_reader.reader_proxy.remote_reader_guid;
+    for (writer_eid, writer) in self.writers.iter_mut() {
+      if local_writer.is_some_and(|eid| eid != *writer_eid) {
+        continue;
+      }
+      if remote_reader.subscription_topic_data.topic_name() != writer.topic_name() {
+        // Does nothing, unless the reader was matched before on another topic.
+        writer.reader_lost(remote_reader_guid);
+      } else {
         #[cfg(not(feature = "security"))]
         let match_to_reader = true;
         #[cfg(feature = "security")]
@@ -722,6 +1030,8 @@ impl DPEventLoop {
             &RtpsReaderProxy::from_discovered_reader_data(remote_reader, &[], &[]),
             &requested_qos,
           );
+        } else {
+          writer.reader_lost(remote_reader_guid);
         }
       }
     }
@@ -733,10 +1043,25 @@ impl DPEventLoop {
     }
   }
 
-  fn remote_writer_discovered(&mut self, remote_writer: &DiscoveredWriterData) {
+  // Match the remote writer with local readers on the same topic. If
+  // `local_reader` is given, only that reader is considered.
+  //
+  // As with readers above, a re-announced remote writer updates the existing
+  // writer proxies in place, or removes them.
+  fn remote_writer_discovered(
+    &mut self,
+    remote_writer: &DiscoveredWriterData,
+    local_reader: Option<EntityId>,
+  ) {
+    let remote_writer_guid = remote_writer.writer_proxy.remote_writer_guid;
     // update writer proxies in local readers
-    for reader in self.message_receiver.available_readers.values_mut() {
-      if &remote_writer.publication_topic_data.topic_name == reader.topic_name() {
+    for (reader_eid, reader) in self.message_receiver.available_readers.iter_mut() {
+      if local_reader.is_some_and(|eid| eid != *reader_eid) {
+        continue;
+      }
+      if &remote_writer.publication_topic_data.topic_name != reader.topic_name() {
+        reader.remove_writer_proxy(remote_writer_guid);
+      } else {
         #[cfg(not(feature = "security"))]
         let match_to_writer = true;
         #[cfg(feature = "security")]
@@ -797,6 +1122,8 @@ impl DPEventLoop {
             RtpsWriterProxy::from_discovered_writer_data(remote_writer, &[], &[]),
             &offered_qos,
           );
+        } else {
+          reader.remove_writer_proxy(remote_writer_guid);
         }
       }
     }
@@ -808,8 +1135,62 @@ impl DPEventLoop {
     }
   }
 
+  // The QoS of a local writer was changed. Update it to Discovery, so that it
+  // is re-announced, and re-evaluate matching with the known remote readers.
+  fn local_writer_qos_updated(&mut self, writer_eid: EntityId) {
+    let (writer_guid, topic_name, qos) = match self.writers.get(&writer_eid) {
+      Some(writer) => (writer.guid(), writer.topic_name().clone(), writer.qos()),
+      None => return,
+    };
+    let remote_readers = {
+      let mut db = discovery_db_write(&self.discovery_db);
+      if !db.update_local_topic_writer_qos(writer_guid, &qos) {
+        warn!("QoS updated for writer {writer_guid:?}, but Discovery DB does not have it.");
+      }
+      db.readers_on_topic(&topic_name)
+    };
+    for remote_reader in &remote_readers {
+      self.remote_reader_discovered(remote_reader, Some(writer_eid));
+    }
+    // Adding an already known writer re-publishes it.
+    self
+      .discovery_command_sender
+      .try_send(DiscoveryCommand::AddLocalWriter { guid: writer_guid })
+      .unwrap_or_else(|e| error!("Cannot re-announce writer {writer_guid:?}: {e:?}"));
+  }
+
+  // The QoS of a local reader was changed. Update it to Discovery, so that it
+  // is re-announced, and re-evaluate matching with the known remote writers.
+  fn local_reader_qos_updated(&mut self, reader_eid: EntityId) {
+    let (reader_guid, topic_name, qos) = match self.message_receiver.reader_mut(reader_eid) {
+      Some(reader) => (reader.guid(), reader.topic_name().clone(), reader.qos()),
+      None => return,
+    };
+    let remote_writers = {
+      let mut db = discovery_db_write(&self.discovery_db);
+      if !db.update_local_topic_reader_qos(reader_guid, &qos) {
+        warn!("QoS updated for reader {reader_guid:?}, but Discovery DB does not have it.");
+      }
+      db.writers_on_topic(&topic_name)
+    };
+    for remote_writer in &remote_writers {
+      self.remote_writer_discovered(remote_writer, Some(reader_eid));
+    }
+    // Adding an already known reader re-publishes it.
+    self
+      .discovery_command_sender
+      .try_send(DiscoveryCommand::AddLocalReader { guid: reader_guid })
+      .unwrap_or_else(|e| error!("Cannot re-announce reader {reader_guid:?}: {e:?}"));
+  }
+
   fn add_local_reader(&mut self, reader_ing: ReaderIngredients) {
-    let timer = mio_extras::timer::Builder::default().num_slots(8).build();
+    // A fine tick, so that coalesced notifications are flushed on time. The
+    // timer wakes up only when a timeout is due, so this costs nothing while
+    // the Reader is idle.
+    let timer = mio_extras::timer::Builder::default()
+      .tick_duration(Duration::from_millis(1))
+      .num_slots(8)
+      .build();
     self
       .poll
       .register(
@@ -826,6 +1207,7 @@ impl DPEventLoop {
       timer,
       self.participant_status_sender.clone(),
     );
+    new_reader.set_trace_sampling(self.trace_sampling);
 
     // Non-timed action polling
     self
@@ -837,10 +1219,29 @@ impl DPEventLoop {
         PollOpt::edge(),
       )
       .expect("Reader command channel registration failed!!!");
+    // Flush requests are timer business
+    self
+      .poll
+      .register(
+        &new_reader.notification_flush_requests,
+        new_reader.guid().entity_id.as_alt_token(),
+        Ready::readable(),
+        PollOpt::edge(),
+      )
+      .expect("Reader notification flush channel registration failed!");
 
     new_reader.set_requested_deadline_check_timer();
     trace!("Add reader: {:?}", new_reader);
+    let reader_eid = new_reader.guid().entity_id;
+    let topic_name = new_reader.topic_name().clone();
     self.message_receiver.add_reader(new_reader);
+
+    // Match with the remote writers that are already known. Static writers,
+    // for example, are not announced again later.
+    let remote_writers = discovery_db_read(&self.discovery_db).writers_on_topic(&topic_name);
+    for remote_writer in &remote_writers {
+      self.remote_writer_discovered(remote_writer, Some(reader_eid));
+    }
   }
 
   fn remove_local_reader(&mut self, reader_guid: GUID) {
@@ -849,6 +1250,10 @@ impl DPEventLoop {
         .poll
         .deregister(&old_reader.timed_event_timer)
         .unwrap_or_else(|e| error!("Cannot deregister Reader timed_event_timer: {e:?}"));
+      self
+        .poll
+        .deregister(&old_reader.notification_flush_requests)
+        .unwrap_or_else(|e| error!("Cannot deregister Reader notification flush: {e:?}"));
       self
         .poll
         .deregister(&old_reader.data_reader_command_receiver)
@@ -883,12 +1288,14 @@ impl DPEventLoop {
       )
       .expect("Writer heartbeat timer channel registration failed!!");
 
-    let new_writer = Writer::new(
+    let mut new_writer = Writer::new(
       writer_ing,
       self.udp_sender.clone(),
       timer,
       self.participant_status_sender.clone(),
+      self.writer_cache_cleaning,
     );
+    new_writer.set_trace_sampling(self.trace_sampling);
 
     self
       .poll
@@ -900,7 +1307,15 @@ impl DPEventLoop {
       )
       .expect("Writer command channel registration failed!!");
 
-    self.writers.insert(new_writer.guid().entity_id, new_writer);
+    let writer_eid = new_writer.guid().entity_id;
+    let topic_name = new_writer.topic_name().clone();
+    self.writers.insert(writer_eid, new_writer);
+
+    // Match with the remote readers that are already known, as with readers
+    let remote_readers = discovery_db_read(&self.discovery_db).readers_on_topic(&topic_name);
+    for remote_reader in &remote_readers {
+      self.remote_reader_discovered(remote_reader, Some(writer_eid));
+    }
   }
 
   fn remove_local_writer(&mut self, writer_guid: &GUID) {
@@ -1041,6 +1456,38 @@ mod tests {
     structure::dds_cache::DDSCache,
   };
 
+  #[test]
+  fn rtps_ping_replies_are_rate_limited_per_source() {
+    let mut limiter = RtpsPingReplyLimiter::default();
+    let a: SocketAddr = "192.0.2.1:7400".parse().unwrap();
+    let b: SocketAddr = "192.0.2.2:7400".parse().unwrap();
+    let t0 = Instant::now();
+
+    assert!(limiter.allow_reply(a, t0));
+    assert!(!limiter.allow_reply(a, t0 + Duration::from_millis(500)));
+    // Other sources have their own budget
+    assert!(limiter.allow_reply(b, t0 + Duration::from_millis(500)));
+    assert!(limiter.allow_reply(a, t0 + RtpsPingReplyLimiter::REPLY_INTERVAL));
+  }
+
+  #[test]
+  fn rtps_ping_reply_sources_are_bounded() {
+    let mut limiter = RtpsPingReplyLimiter::default();
+    let t0 = Instant::now();
+    let source = |port: usize| SocketAddr::from(([192, 0, 2, 1], 10000 + port as u16));
+
+    for port in 0..RtpsPingReplyLimiter::MAX_SOURCES {
+      assert!(limiter.allow_reply(source(port), t0));
+    }
+    // Full of recent sources: new ones are not answered
+    let newcomer = source(RtpsPingReplyLimiter::MAX_SOURCES);
+    assert!(!limiter.allow_reply(newcomer, t0));
+    // ...until the old entries have expired
+    let later = t0 + RtpsPingReplyLimiter::REPLY_INTERVAL;
+    assert!(limiter.allow_reply(newcomer, later));
+    assert_eq!(limiter.last_reply.len(), 1);
+  }
+
   //#[test]
   // TODO: Investigate why this fails in the github CI pipeline
   // Then re-enable this test.
@@ -1114,6 +1561,13 @@ mod tests {
         spdp_liveness_sender,
         participant_status_sender,
         None,
+        SocketOptions::default(),
+        MessageReceiverLimits::default(),
+        Arc::default(),
+        RtpsPingOptions::default(),
+        EventLoopWeights::default(),
+        WriterCacheCleaning::default(),
+        TraceSampling::default(),
       );
       dp_event_loop
         .poll
@@ -1163,6 +1617,8 @@ mod tests {
         data_reader_command_receiver: reader_command_receiver,
         data_reader_waker: data_reader_waker.clone(),
         poll_event_sender: notification_event_sender,
+        readiness_queue: None,
+        fragment_streams: Arc::default(),
         security_plugins: None,
       };
 
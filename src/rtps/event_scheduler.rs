@@ -0,0 +1,189 @@
+// Scheduling of work in the DomainParticipant event loop.
+//
+// Polling tells which sources (sockets, channels, timers) have work. Instead
+// of draining each source completely as it is polled, sources are queued by
+// event class, and served in rounds. In each round, each class may process at
+// most its weight in work items. A source that still has work left goes to
+// the back of its queue. This way a burst of one class, e.g. a wave of
+// Discovery data, cannot delay the other classes by more than one round.
+
+use std::collections::VecDeque;
+
+use mio_06::Token;
+
+/// How many work items of each class the DomainParticipant event loop
+/// processes in turn, when several classes have work pending. A work item is
+/// a received UDP datagram, a Discovery notification, an ACKNACK forwarded to
+/// a local writer, or a timer event of a reader or writer.
+///
+/// Larger weight gives a class a larger share of the event loop under load.
+/// When only one class has work, it is processed as fast as possible
+/// regardless of weights. Weights less than 1 are treated as 1.
+///
+/// Use
+/// [`DomainParticipantBuilder::event_loop_weights`](crate::DomainParticipantBuilder::event_loop_weights)
+/// to change these.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EventLoopWeights {
+  /// Datagrams received on the user data sockets
+  pub user_data: usize,
+  /// ACKNACKs from remote readers to local writers
+  pub acknack: usize,
+  /// Timer events of local readers and writers, e.g. HEARTBEAT sending
+  pub timers: usize,
+  /// Datagrams received on the Discovery sockets, and Discovery results
+  /// (matching of remote readers and writers)
+  pub discovery: usize,
+}
+
+impl Default for EventLoopWeights {
+  fn default() -> Self {
+    Self {
+      user_data: 64,
+      acknack: 64,
+      timers: 32,
+      discovery: 16,
+    }
+  }
+}
+
+// In the order in which they are served in a round
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum EventClass {
+  UserData,
+  AckNack,
+  Timers,
+  Discovery,
+}
+
+const EVENT_CLASSES: [EventClass; 4] = [
+  EventClass::UserData,
+  EventClass::AckNack,
+  EventClass::Timers,
+  EventClass::Discovery,
+];
+
+// Result of serving one source
+pub(crate) struct Progress {
+  // Number of work items processed
+  pub used: usize,
+  // Did the source have more work than it was allowed to process?
+  pub more: bool,
+}
+
+pub(crate) struct EventScheduler {
+  weights: EventLoopWeights,
+  queues: [VecDeque<Token>; 4],
+}
+
+impl EventScheduler {
+  pub fn new(weights: EventLoopWeights) -> Self {
+    Self {
+      weights,
+      queues: Default::default(),
+    }
+  }
+
+  fn weight(&self, class: EventClass) -> usize {
+    let w = match class {
+      EventClass::UserData => self.weights.user_data,
+      EventClass::AckNack => self.weights.acknack,
+      EventClass::Timers => self.weights.timers,
+      EventClass::Discovery => self.weights.discovery,
+    };
+    w.max(1)
+  }
+
+  // Note that a source has work
+  pub fn schedule(&mut self, class: EventClass, token: Token) {
+    let queue = &mut self.queues[class as usize];
+    if !queue.contains(&token) {
+      queue.push_back(token);
+    }
+  }
+
+  pub fn has_pending(&self) -> bool {
+    self.queues.iter().any(|q| !q.is_empty())
+  }
+
+  // Serve each class once, up to its weight. `serve` is given a source and
+  // the number of work items it may process.
+  pub fn run_round<F>(&mut self, mut serve: F)
+  where
+    F: FnMut(Token, usize) -> Progress,
+  {
+    for class in EVENT_CLASSES {
+      let mut budget = self.weight(class);
+      // Each queued source is visited at most once per round.
+      let mut visits = self.queues[class as usize].len();
+      while budget > 0 && visits > 0 {
+        let token = match self.queues[class as usize].pop_front() {
+          Some(token) => token,
+          None => break,
+        };
+        visits -= 1;
+        let Progress { used, more } = serve(token, budget);
+        budget = budget.saturating_sub(used.max(1));
+        if more {
+          self.queues[class as usize].push_back(token);
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeMap;
+
+  use super::*;
+
+  #[test]
+  fn discovery_storm_does_not_starve_user_data() {
+    let weights = EventLoopWeights {
+      user_data: 4,
+      acknack: 1,
+      timers: 1,
+      discovery: 2,
+    };
+    let mut scheduler = EventScheduler::new(weights);
+    let discovery = Token(1);
+    let user_data = Token(2);
+
+    // Work items waiting in each source
+    let mut backlog: BTreeMap<Token, usize> = [(discovery, 100), (user_data, 10)].into();
+    scheduler.schedule(EventClass::Discovery, discovery);
+    scheduler.schedule(EventClass::UserData, user_data);
+    scheduler.schedule(EventClass::UserData, user_data); // no duplicates
+
+    let mut served = Vec::new();
+    let mut serve = |token: Token, budget: usize| {
+      let left = backlog.get_mut(&token).unwrap();
+      let used = budget.min(*left);
+      *left -= used;
+      served.push((token, used));
+      Progress {
+        used,
+        more: *left > 0,
+      }
+    };
+
+    scheduler.run_round(&mut serve);
+    scheduler.run_round(&mut serve);
+    scheduler.run_round(&mut serve);
+    assert_eq!(
+      served,
+      vec![
+        (user_data, 4),
+        (discovery, 2),
+        (user_data, 4),
+        (discovery, 2),
+        (user_data, 2),
+        (discovery, 2),
+      ]
+    );
+    // User data is done, Discovery continues.
+    assert!(scheduler.has_pending());
+    assert_eq!(scheduler.queues[EventClass::UserData as usize].len(), 0);
+  }
+}
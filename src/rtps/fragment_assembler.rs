@@ -1,13 +1,13 @@
-use std::{collections::BTreeMap, convert::TryInto, fmt, iter};
+use std::{collections::BTreeMap, convert::TryInto, fmt, iter, time::Instant};
 
 use bit_vec::BitVec;
 use enumflags2::BitFlags;
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 
 use crate::{
-  dds::ddsdata::DDSData,
+  dds::{ddsdata::DDSData, fragment_stream::FragmentStreamWriter},
   messages::submessages::{
     elements::serialized_payload::SerializedPayload,
     submessages::{DATAFRAG_Flags, DataFrag},
@@ -28,7 +28,9 @@ struct AssemblyBuffer {
   #[allow(dead_code)] // TODO: Purpose is to use this later for e.g.
   // garbage collection, in case some buffer is not completed within reasonable time.
   created_time: Timestamp,
-  modified_time: Timestamp,
+  // For garbage collection of buffers that are not completed within
+  // reasonable time
+  modified_time: Instant,
 }
 
 impl AssemblyBuffer {
@@ -51,14 +53,12 @@ impl AssemblyBuffer {
 
     let fragment_count = usize::from(datafrag.total_number_of_fragments());
 
-    let now = Timestamp::now();
-
     Self {
       buffer_bytes,
       fragment_count,
       received_bitmap: BitVec::from_elem(fragment_count, false),
-      created_time: now,
-      modified_time: now,
+      created_time: Timestamp::now(),
+      modified_time: Instant::now(),
     }
   }
 
@@ -132,7 +132,7 @@ impl AssemblyBuffer {
     for f in 0..frags_in_submessage {
       self.received_bitmap.set(start_frag_from_0 + f, true);
     }
-    self.modified_time = Timestamp::now();
+    self.modified_time = Instant::now();
   }
 
   pub fn is_complete(&self) -> bool {
@@ -140,11 +140,91 @@ impl AssemblyBuffer {
   }
 }
 
+// This is for passing a single object to a FragmentStream. Only the fragments
+// that arrive out of order are buffered, until the preceding ones arrive.
+struct StreamingBuffer {
+  stream: FragmentStreamWriter,
+  data_size: usize,
+  fragment_count: usize,
+  received_bitmap: BitVec,
+  // Bytes before this have been passed to the stream
+  streamed_before: usize,
+  // Received data after a gap, by starting byte
+  out_of_order: BTreeMap<usize, Bytes>,
+  modified_time: Instant,
+}
+
+impl StreamingBuffer {
+  fn new(datafrag: &DataFrag, stream: FragmentStreamWriter) -> Self {
+    let fragment_count = usize::from(datafrag.total_number_of_fragments());
+    Self {
+      stream,
+      data_size: datafrag.data_size as usize,
+      fragment_count,
+      received_bitmap: BitVec::from_elem(fragment_count, false),
+      streamed_before: 0,
+      out_of_order: BTreeMap::new(),
+      modified_time: Instant::now(),
+    }
+  }
+
+  fn insert_frags(&mut self, datafrag: &DataFrag, frag_size: u16) {
+    let frag_size = usize::from(frag_size);
+    let start_frag_from_0 = u32::from(datafrag.fragment_starting_num) as usize - 1;
+    let frags_in_submessage = usize::from(datafrag.fragments_in_submessage);
+    let from_byte = start_frag_from_0 * frag_size;
+    let to_before_byte = (from_byte
+      + (frags_in_submessage * frag_size).min(datafrag.serialized_payload.len()))
+    .min(self.data_size);
+    if from_byte >= to_before_byte
+      || (start_frag_from_0..start_frag_from_0 + frags_in_submessage)
+        .all(|f| self.received_bitmap.get(f).unwrap_or(true))
+    {
+      return; // nothing new
+    }
+    for f in start_frag_from_0..(start_frag_from_0 + frags_in_submessage).min(self.fragment_count) {
+      self.received_bitmap.set(f, true);
+    }
+    self.modified_time = Instant::now();
+    let data = datafrag
+      .serialized_payload
+      .slice(..to_before_byte - from_byte);
+    if from_byte > self.streamed_before {
+      self.out_of_order.insert(from_byte, data);
+      return;
+    }
+    self.stream_from(from_byte, &data);
+    while let Some(entry) = self.out_of_order.first_entry() {
+      if *entry.key() > self.streamed_before {
+        break;
+      }
+      let (from_byte, data) = entry.remove_entry();
+      self.stream_from(from_byte, &data);
+    }
+  }
+
+  // Streams the part of `data` that has not been streamed yet
+  fn stream_from(&mut self, from_byte: usize, data: &Bytes) {
+    let to_before_byte = from_byte + data.len();
+    if to_before_byte > self.streamed_before {
+      self
+        .stream
+        .push(data.slice(self.streamed_before - from_byte..));
+      self.streamed_before = to_before_byte;
+    }
+  }
+
+  fn is_complete(&self) -> bool {
+    self.received_bitmap.all()
+  }
+}
+
 // Assembles fragments from a single (remote) Writer
 // So there is only one sequence of SNs
 pub(crate) struct FragmentAssembler {
   fragment_size: u16, // number of bytes per fragment. Each writer must select one constant value.
   assembly_buffers: BTreeMap<SequenceNumber, AssemblyBuffer>,
+  streaming_buffers: BTreeMap<SequenceNumber, StreamingBuffer>,
 }
 
 impl fmt::Debug for FragmentAssembler {
@@ -161,6 +241,7 @@ impl FragmentAssembler {
     Self {
       fragment_size,
       assembly_buffers: BTreeMap::new(),
+      streaming_buffers: BTreeMap::new(),
     }
   }
 
@@ -209,6 +290,84 @@ impl FragmentAssembler {
     }
   }
 
+  // Like new_datafrag(), but passes the data on to a FragmentStream from
+  // `start_stream` instead of assembling it. Returns the stream when all
+  // fragments have been received, so that the caller can finish it.
+  pub fn new_streamed_datafrag(
+    &mut self,
+    datafrag: &DataFrag,
+    start_stream: impl FnOnce() -> FragmentStreamWriter,
+  ) -> Option<FragmentStreamWriter> {
+    let writer_sn = datafrag.writer_sn;
+    let frag_size = self.fragment_size;
+
+    let streaming_buffer = self
+      .streaming_buffers
+      .entry(writer_sn)
+      .or_insert_with(|| StreamingBuffer::new(datafrag, start_stream()));
+
+    streaming_buffer.insert_frags(datafrag, frag_size);
+
+    if streaming_buffer.is_complete() {
+      debug!("new_streamed_datafrag: COMPLETED {writer_sn:?}");
+      self
+        .streaming_buffers
+        .remove(&writer_sn)
+        .map(|buffer| buffer.stream)
+    } else {
+      None
+    }
+  }
+
+  // Is the sample being assembled, as opposed to streamed?
+  pub fn is_assembling(&self, sn: SequenceNumber) -> bool {
+    self.assembly_buffers.contains_key(&sn)
+  }
+
+  pub fn is_streaming(&self, sn: SequenceNumber) -> bool {
+    self.streaming_buffers.contains_key(&sn)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.assembly_buffers.is_empty() && self.streaming_buffers.is_empty()
+  }
+
+  // Discards the partially received samples that `is_discarded`, e.g.
+  // because the writer announced in a GAP that it no longer has them.
+  pub fn discard(&mut self, is_discarded: impl Fn(SequenceNumber) -> bool, reason: &str) {
+    self.assembly_buffers.retain(|sn, _| !is_discarded(*sn));
+    let (discarded, kept) = std::mem::take(&mut self.streaming_buffers)
+      .into_iter()
+      .partition(|(sn, _)| is_discarded(*sn));
+    self.streaming_buffers = kept;
+    Self::fail_streams(discarded, reason);
+  }
+
+  // Discards the partially received samples that have not received any
+  // fragments since `stale_before`.
+  pub fn garbage_collect(&mut self, stale_before: Instant) {
+    self
+      .assembly_buffers
+      .retain(|_, ab| ab.modified_time >= stale_before);
+    let (kept, stale) = std::mem::take(&mut self.streaming_buffers)
+      .into_iter()
+      .partition(|(_, sb)| sb.modified_time >= stale_before);
+    self.streaming_buffers = kept;
+    Self::fail_streams(stale, "Fragments of the sample stopped arriving");
+  }
+
+  // Discards everything, when the writer is gone
+  pub fn abandon(self, reason: &str) {
+    Self::fail_streams(self.streaming_buffers, reason);
+  }
+
+  fn fail_streams(streaming_buffers: BTreeMap<SequenceNumber, StreamingBuffer>, reason: &str) {
+    for (sn, buffer) in streaming_buffers {
+      debug!("Failing the stream of {sn:?}: {reason}");
+      buffer.stream.fail(reason.to_string());
+    }
+  }
+
   // pub fn partially_received_sequence_numbers_iterator(&self) -> Box<dyn
   // Iterator<Item=SequenceNumber>> {   // Since we should only know about SNs
   // via DATAFRAG messages   // and AssemblyBuffers are removed immediately on
@@ -217,7 +376,7 @@ impl FragmentAssembler {
   // }
 
   pub fn is_partially_received(&self, sn: SequenceNumber) -> bool {
-    self.assembly_buffers.contains_key(&sn)
+    self.assembly_buffers.contains_key(&sn) || self.streaming_buffers.contains_key(&sn)
     // assembly buffers map contains a key (SN) if and only if we have some
     // frags but not all
   }
@@ -226,11 +385,21 @@ impl FragmentAssembler {
     &self,
     seq: SequenceNumber,
   ) -> Box<dyn '_ + Iterator<Item = FragmentNumber>> {
-    match self.assembly_buffers.get(&seq) {
+    let received = self
+      .assembly_buffers
+      .get(&seq)
+      .map(|ab| (ab.fragment_count, &ab.received_bitmap))
+      .or_else(|| {
+        self
+          .streaming_buffers
+          .get(&seq)
+          .map(|sb| (sb.fragment_count, &sb.received_bitmap))
+      });
+    match received {
       None => Box::new(iter::empty()),
-      Some(ab) => {
-        let iter = (0..ab.fragment_count)
-          .filter(move |f| !ab.received_bitmap.get(*f).unwrap_or(true))
+      Some((fragment_count, received_bitmap)) => {
+        let iter = (0..fragment_count)
+          .filter(move |f| !received_bitmap.get(*f).unwrap_or(true))
           .map(|f| FragmentNumber::new((f + 1).try_into().unwrap()));
         Box::new(iter)
       }
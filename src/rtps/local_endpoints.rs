@@ -0,0 +1,120 @@
+// Process-wide registry of the local DomainParticipants and of the local
+// endpoints that have the IgnoreLocal QoS policy. Matching consults this, so
+// that a Writer does not match a Reader that ignores it, and vice versa, even
+// when they are in different DomainParticipants of this process.
+
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  sync::Mutex,
+};
+
+use crate::{
+  dds::qos::policy::IgnoreLocal,
+  structure::guid::{GuidPrefix, GUID},
+};
+
+static PARTICIPANTS: Mutex<BTreeSet<GuidPrefix>> = Mutex::new(BTreeSet::new());
+
+// Only endpoints with something to ignore are recorded
+static IGNORING_ENDPOINTS: Mutex<BTreeMap<GUID, IgnoreLocal>> = Mutex::new(BTreeMap::new());
+
+pub(crate) fn add_participant(prefix: GuidPrefix) {
+  PARTICIPANTS
+    .lock()
+    .unwrap_or_else(|e| e.into_inner())
+    .insert(prefix);
+}
+
+pub(crate) fn remove_participant(prefix: GuidPrefix) {
+  PARTICIPANTS
+    .lock()
+    .unwrap_or_else(|e| e.into_inner())
+    .remove(&prefix);
+}
+
+pub(crate) fn add_endpoint(guid: GUID, ignore_local: Option<IgnoreLocal>) {
+  match ignore_local {
+    None | Some(IgnoreLocal::Nothing) => (),
+    Some(ignore_local) => {
+      IGNORING_ENDPOINTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(guid, ignore_local);
+    }
+  }
+}
+
+pub(crate) fn remove_endpoint(guid: GUID) {
+  IGNORING_ENDPOINTS
+    .lock()
+    .unwrap_or_else(|e| e.into_inner())
+    .remove(&guid);
+}
+
+// Does either of the endpoints ignore the other?
+pub(crate) fn match_ignored(a: GUID, b: GUID) -> bool {
+  let ignoring = IGNORING_ENDPOINTS.lock().unwrap_or_else(|e| e.into_inner());
+  if ignoring.is_empty() {
+    return false;
+  }
+  let ignores = |this: GUID, other: GUID| match ignoring.get(&this) {
+    None | Some(IgnoreLocal::Nothing) => false,
+    Some(IgnoreLocal::Participant) => this.prefix == other.prefix,
+    Some(IgnoreLocal::Process) => {
+      this.prefix == other.prefix
+        || PARTICIPANTS
+          .lock()
+          .unwrap_or_else(|e| e.into_inner())
+          .contains(&other.prefix)
+    }
+  };
+  ignores(a, b) || ignores(b, a)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::structure::guid::{EntityId, EntityKind};
+
+  #[test]
+  fn ignore_local_scopes() {
+    let prefix_a = GuidPrefix::new(&[0xA1; 12]);
+    let prefix_b = GuidPrefix::new(&[0xB1; 12]);
+    let remote = GuidPrefix::new(&[0xC1; 12]);
+    add_participant(prefix_a);
+    add_participant(prefix_b);
+
+    let writer = GUID::new(
+      prefix_a,
+      EntityId::new([1, 0, 0], EntityKind::WRITER_WITH_KEY_USER_DEFINED),
+    );
+    let same_participant = GUID::new(
+      prefix_a,
+      EntityId::new([2, 0, 0], EntityKind::READER_WITH_KEY_USER_DEFINED),
+    );
+    let same_process = GUID::new(
+      prefix_b,
+      EntityId::new([3, 0, 0], EntityKind::READER_WITH_KEY_USER_DEFINED),
+    );
+    let remote_reader = GUID::new(
+      remote,
+      EntityId::new([4, 0, 0], EntityKind::READER_WITH_KEY_USER_DEFINED),
+    );
+
+    add_endpoint(writer, Some(IgnoreLocal::Participant));
+    assert!(match_ignored(writer, same_participant));
+    assert!(match_ignored(same_participant, writer));
+    assert!(!match_ignored(writer, same_process));
+    assert!(!match_ignored(writer, remote_reader));
+
+    add_endpoint(same_process, Some(IgnoreLocal::Process));
+    assert!(match_ignored(writer, same_process));
+    assert!(!match_ignored(remote_reader, same_process));
+
+    remove_endpoint(writer);
+    remove_endpoint(same_process);
+    remove_participant(prefix_a);
+    remove_participant(prefix_b);
+    assert!(!match_ignored(writer, same_participant));
+  }
+}
@@ -2,9 +2,10 @@ use std::{cmp::min, collections::BTreeSet, convert::TryInto, io};
 
 #[allow(unused_imports)]
 use log::{debug, error, trace, warn};
-use speedy::{Context, Endianness, Readable, Writable, Writer};
+use speedy::{Context, Endianness, Writable, Writer};
 use enumflags2::BitFlags;
 use bytes::Bytes;
+use rustdds_rtps_core as rtps_core;
 
 use crate::{
   dds::ddsdata::DDSData,
@@ -21,7 +22,7 @@ use crate::{
   },
   rtps::{writer::Writer as RtpsWriter, Submessage, SubmessageBody},
   structure::{
-    cache_change::CacheChange,
+    cache_change::{CacheChange, ChangeKind},
     entity::RTPSEntity,
     guid::{EntityId, GuidPrefix, GUID},
     parameter_id::ParameterId,
@@ -37,6 +38,10 @@ use crate::{
 #[cfg(not(feature = "security"))]
 use crate::no_security::SecurityPluginsHandle;
 
+// Smaller payloads are copied to the message buffer, as that is cheaper than
+// another piece to gather.
+pub(crate) const SCATTER_GATHER_MIN_PAYLOAD: usize = 1024;
+
 #[derive(Debug, Clone)]
 pub struct Message {
   pub header: Header,
@@ -58,24 +63,67 @@ impl Message {
     self.header = header;
   }
 
+  /// Serializes the message in pieces, which together make up the same bytes
+  /// as `write_to_vec_with_ctx`. Large DATA and DATA_FRAG payloads are not
+  /// copied, but are pieces of their own, referring to the original buffers.
+  /// The pieces are meant to be sent with scatter/gather I/O. Submessages that
+  /// carry their serialized bytes are copied, not serialized again.
+  pub fn write_to_chunks(&self, endianness: Endianness) -> Result<Vec<Bytes>, speedy::Error> {
+    let mut chunks = Vec::new();
+    let mut buffer = self.header.write_to_vec_with_ctx(endianness)?;
+    for submessage in &self.submessages {
+      match (&submessage.original_bytes, submessage.payload()) {
+        (Some(bytes), _) => buffer.extend_from_slice(bytes),
+        (None, Some(payload)) if payload.len() >= SCATTER_GATHER_MIN_PAYLOAD => {
+          // The submessage header still tells the length with the payload.
+          buffer.extend(
+            submessage
+              .without_payload()
+              .write_to_vec_with_ctx(endianness)?,
+          );
+          chunks.push(Bytes::from(std::mem::take(&mut buffer)));
+          chunks.push(payload.clone());
+        }
+        _ => buffer.extend(submessage.write_to_vec_with_ctx(endianness)?),
+      }
+    }
+    if !buffer.is_empty() {
+      chunks.push(Bytes::from(buffer));
+    }
+    Ok(chunks)
+  }
+
+  #[cfg(test)]
+  pub fn read_from_buffer(buffer: &Bytes) -> io::Result<Self> {
+    Self::read_from_buffer_with_skipped(buffer).map(|(message, _skipped)| message)
+  }
+
   // We implement this instead of Speedy trait Readable, because
   // we need to run-time decide which endianness we input. Speedy requires the
   // top level to fix that. And there seems to be no reasonable way to change
   // endianness. TODO: The error type should be something better
-  pub fn read_from_buffer(buffer: &Bytes) -> io::Result<Self> {
-    // The Header deserializes the same
-    let rtps_header =
-      Header::read_from_buffer(buffer).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+  //
+  // Also returns the kinds of the submessages that were skipped, because they
+  // are unknown or vendor-specific. Skipping uses the length in the submessage
+  // header, so the following submessages are still read.
+  pub(crate) fn read_from_buffer_with_skipped(
+    buffer: &Bytes,
+  ) -> io::Result<(Self, Vec<SubmessageKind>)> {
+    let rtps_header = Header::decode(buffer)?;
     let mut message = Self::new(rtps_header);
-    let mut submessages_left: Bytes = buffer.slice(20..); // header is 20 bytes
-                                                          // submessage loop
+    let mut skipped = Vec::new();
+    let mut submessages_left: Bytes = buffer.slice(rtps_core::HEADER_LEN..);
+    // submessage loop
     while !submessages_left.is_empty() {
-      if let Some(submessage) = Submessage::read_from_buffer(&mut submessages_left)? {
-        message.submessages.push(submessage);
+      let kind = SubmessageKind::from(submessages_left[0]);
+      match Submessage::read_from_buffer(&mut submessages_left)? {
+        Some(submessage) => message.submessages.push(submessage),
+        None if kind != SubmessageKind::PAD => skipped.push(kind),
+        None => (), // PAD has no content
       }
     } // loop
 
-    Ok(message)
+    Ok((message, skipped))
   }
 }
 
@@ -171,14 +219,24 @@ impl MessageBuilder {
     self
   }
 
-  pub fn data_msg(
-    mut self,
+  /// Adds an already constructed submessage, e.g. one from
+  /// [`Self::data_submessage`].
+  pub fn submessage(mut self, submessage: Submessage) -> Self {
+    self.submessages.push(submessage);
+    self
+  }
+
+  /// Constructs a DATA submessage without adding it to a message. This is
+  /// useful when the same submessage is sent in several messages.
+  ///
+  /// Returns `None` if the payload cannot be encoded.
+  pub fn data_submessage(
     cache_change: &CacheChange,
     reader_entity_id: EntityId, // The entity id to be included in the submessage
     writer_guid: GUID,
     endianness: Endianness,
     security_plugins: Option<&SecurityPluginsHandle>,
-  ) -> Self {
+  ) -> Option<Submessage> {
     #[cfg(not(feature = "security"))]
     // Parameter not used
     let _ = security_plugins;
@@ -189,7 +247,23 @@ impl MessageBuilder {
 
     // Check if we are disposing by key hash
     match cache_change.data_value {
-      DDSData::Data { .. } | DDSData::DisposeByKey { .. } => (), // no
+      DDSData::Data { key_hash, .. } | DDSData::DisposeByKey { key_hash, .. } => {
+        // no, but send key hash along with data or key, if we have it
+        if let Some(key_hash) = key_hash {
+          param_list.push(Parameter {
+            parameter_id: ParameterId::PID_KEY_HASH,
+            value: key_hash.to_vec(),
+          });
+        }
+        // A key without data may be either a dispose or an unregister.
+        if let DDSData::DisposeByKey { change_kind, .. } = cache_change.data_value {
+          param_list.push(Parameter::create_pid_status_info_parameter(
+            /* disposed */ change_kind == ChangeKind::NotAliveDisposed,
+            /* unregistered */ change_kind == ChangeKind::NotAliveUnregistered,
+            /* filtered */ false,
+          ));
+        }
+      }
       DDSData::DisposeByKeyHash { key_hash, .. } => {
         // yes, insert to inline QoS
         // insert key hash
@@ -215,9 +289,23 @@ impl MessageBuilder {
       });
     }
 
+    // Application-defined and RustDDS specific inline QoS parameters
+    for p in cache_change
+      .write_options
+      .inline_qos_parameters()
+      .iter()
+      .chain(cache_change.write_options.rustdds_parameters())
+    {
+      param_list.push(Parameter::new(
+        ParameterId::new(p.parameter_id),
+        p.value.clone(),
+      ));
+    }
+
     let serialized_payload = match cache_change.data_value {
       DDSData::Data {
         ref serialized_payload,
+        ..
       } => Some(serialized_payload.clone()), // contents is Bytes
       DDSData::DisposeByKey { ref key, .. } => Some(key.clone()),
       DDSData::DisposeByKeyHash { .. } => None,
@@ -254,7 +342,7 @@ impl MessageBuilder {
       Ok(encoded_payload) => encoded_payload,
       Err(e) => {
         error!("{e:?}");
-        return self;
+        return None;
       }
     }; // end security
 
@@ -287,7 +375,7 @@ impl MessageBuilder {
         BitFlags::<DATA_Flags>::empty()
       });
 
-    self.submessages.push(Submessage {
+    Some(Submessage {
       header: SubmessageHeader {
         kind: SubmessageKind::DATA,
         flags: flags.bits(),
@@ -295,8 +383,7 @@ impl MessageBuilder {
       },
       body: SubmessageBody::Writer(WriterSubmessage::Data(data_message, flags)),
       original_bytes: None,
-    });
-    self
+    })
   }
 
   // This whole MessageBuilder structure should be refactored into something more
@@ -323,7 +410,15 @@ impl MessageBuilder {
 
     // Check if we are disposing by key hash
     match cache_change.data_value {
-      DDSData::Data { .. } | DDSData::DisposeByKey { .. } => (), // no => ok
+      DDSData::Data { key_hash, .. } | DDSData::DisposeByKey { key_hash, .. } => {
+        // no => ok. Send key hash in every fragment, as they may arrive in any order.
+        if let Some(key_hash) = key_hash {
+          param_list.parameters.push(Parameter {
+            parameter_id: ParameterId::PID_KEY_HASH,
+            value: key_hash.to_vec(),
+          });
+        }
+      }
       DDSData::DisposeByKeyHash { .. } => {
         error!(
           "data_frag_msg: Called with DDSData::DisposeByKeyHash. This is not legit! Discarding."
@@ -345,6 +440,20 @@ impl MessageBuilder {
       });
     }
 
+    // Application-defined and RustDDS specific inline QoS parameters, also in
+    // every fragment
+    for p in cache_change
+      .write_options
+      .inline_qos_parameters()
+      .iter()
+      .chain(cache_change.write_options.rustdds_parameters())
+    {
+      param_list.push(Parameter::new(
+        ParameterId::new(p.parameter_id),
+        p.value.clone(),
+      ));
+    }
+
     let have_inline_qos = !param_list.is_empty(); // we need this later also
 
     // fragments are numbered starting from 1, not 0.
@@ -354,17 +463,18 @@ impl MessageBuilder {
       sample_size.try_into().unwrap(),
     );
 
-    let serialized_payload = Vec::from(
-      cache_change
-        .data_value
-        .bytes_slice(from_byte, up_to_before_byte),
-    );
+    // This is a slice of the original payload buffer, not a copy, except for the
+    // first fragment, which includes the payload header.
+    let serialized_payload = cache_change
+      .data_value
+      .bytes_slice(from_byte, up_to_before_byte);
 
     #[cfg(not(feature = "security"))]
     let encoded_payload = serialized_payload;
 
     #[cfg(feature = "security")]
     let encoded_payload = {
+      let serialized_payload = Vec::from(serialized_payload);
       let encode_result = match security_plugins.map(SecurityPluginsHandle::get_plugins) {
         Some(security_plugins) => {
           security_plugins
@@ -383,7 +493,7 @@ impl MessageBuilder {
       };
 
       match encode_result {
-        Ok(encoded_payload) => encoded_payload,
+        Ok(encoded_payload) => Bytes::from(encoded_payload),
         Err(e) => {
           error!("{e:?}");
           return self;
@@ -404,7 +514,7 @@ impl MessageBuilder {
       } else {
         None
       },
-      serialized_payload: Bytes::from(encoded_payload),
+      serialized_payload: encoded_payload,
     };
 
     let flags: BitFlags<DATAFRAG_Flags> =
@@ -474,14 +584,14 @@ impl MessageBuilder {
     set_liveliness_flag: bool,
   ) -> Self {
     let first = writer.first_change_sequence_number;
-    let last = writer.last_change_sequence_number;
+    let last = writer.announced_sequence_number();
 
     let heartbeat = Heartbeat {
       reader_id: reader_entity_id,
       writer_id: writer.entity_id(),
       first_sn: first,
       last_sn: last,
-      count: writer.heartbeat_message_counter,
+      count: writer.next_heartbeat_count(),
     };
 
     let mut flags = BitFlags::<HEARTBEAT_Flags>::from_endianness(writer.endianness);
@@ -752,4 +862,57 @@ mod tests {
     info!("read_from_buffer() --> {rtps:?}");
     // if we get here without panic, the test passes
   }
+
+  #[test]
+  fn chunks_refer_to_payload_and_join_to_whole_message() {
+    use crate::{
+      dds::with_key::datawriter::WriteOptions,
+      messages::submessages::elements::serialized_payload::SerializedPayload,
+      structure::guid::EntityKind, RepresentationIdentifier,
+    };
+
+    let frame = Bytes::from((0..10_000u32).map(|i| i as u8).collect::<Vec<u8>>());
+    let writer_guid = GUID::dummy_test_guid(EntityKind::WRITER_NO_KEY_USER_DEFINED);
+    let cache_change = CacheChange::new(
+      writer_guid,
+      SequenceNumber::new(1),
+      WriteOptions::default(),
+      DDSData::new(SerializedPayload::new_from_bytes(
+        RepresentationIdentifier::CDR_LE,
+        frame.clone(),
+      )),
+    );
+    let sample_size = frame.len() as u32 + 4; // with payload header
+    let fragment_size = 4000;
+
+    for fragment_number in 1..=3 {
+      let message = MessageBuilder::new()
+        .ts_msg(Endianness::LittleEndian, Some(Timestamp::now()))
+        .data_frag_msg(
+          &cache_change,
+          EntityId::UNKNOWN,
+          writer_guid,
+          FragmentNumber::new(fragment_number),
+          fragment_size,
+          sample_size,
+          Endianness::LittleEndian,
+          None,
+        )
+        .add_header_and_build(writer_guid.prefix);
+      let chunks = message.write_to_chunks(Endianness::LittleEndian).unwrap();
+
+      assert_eq!(chunks.len(), 2);
+      assert_eq!(
+        chunks.concat(),
+        message
+          .write_to_vec_with_ctx(Endianness::LittleEndian)
+          .unwrap()
+      );
+      if fragment_number > 1 {
+        // Not a copy. The first fragment has the payload header prepended.
+        let from = (fragment_number as usize - 1) * usize::from(fragment_size) - 4;
+        assert_eq!(chunks[1].as_ptr(), frame[from..].as_ptr());
+      }
+    }
+  }
 }
@@ -1,26 +1,38 @@
-use std::collections::{btree_map::Entry, BTreeMap};
+use std::{
+  collections::{btree_map::Entry, BTreeMap},
+  net::SocketAddr,
+  sync::{Arc, Mutex, MutexGuard},
+  time::Instant,
+};
 
 use enumflags2::BitFlags;
 use mio_extras::{channel as mio_channel, channel::TrySendError};
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 use bytes::Bytes;
+use rustdds_rtps_core as rtps_core;
 
 use crate::{
+  dds::compression,
   messages::{
     protocol_version::ProtocolVersion,
     submessages::submessages::{WriterSubmessage, *},
     vendor_id::VendorId,
   },
+  network::rate_limit::{Admission, SourceRateLimit, SourceRateLimiter},
   rtps::{reader::Reader, Message, Submessage, SubmessageBody},
+  stats,
   structure::{
     entity::RTPSEntity,
     guid::{EntityId, GuidPrefix, GUID},
     locator::Locator,
+    sequence_number::SequenceNumber,
     time::Timestamp,
   },
 };
 #[cfg(feature = "security")]
+use crate::security_warn;
+#[cfg(feature = "security")]
 use crate::security::{
   cryptographic::{DecodeOutcome, DecodedSubmessage},
   security_plugins::SecurityPluginsHandle,
@@ -31,10 +43,107 @@ use crate::messages::submessages::{secure_postfix::SecurePostfix, secure_prefix:
 use crate::no_security::SecurityPluginsHandle;
 #[cfg(test)]
 use crate::dds::ddsdata::DDSData;
-#[cfg(test)]
-use crate::structure::sequence_number::SequenceNumber;
 
-const RTPS_MESSAGE_HEADER_SIZE: usize = 20;
+const RTPS_MESSAGE_HEADER_SIZE: usize = rtps_core::HEADER_LEN;
+
+// RTPS ping messages are not in the RTPS spec, but at least RTI Connext sends
+// them to probe for peers. A ping is "RTPS", protocol version, vendor id, one
+// byte, and "DDSPING". It is shorter than an RTPS header.
+const RTPS_PING_SIZE: usize = 16;
+
+pub(crate) fn is_rtps_ping(msg_bytes: &[u8]) -> bool {
+  msg_bytes.len() >= RTPS_PING_SIZE
+    && msg_bytes.len() < RTPS_MESSAGE_HEADER_SIZE
+    && msg_bytes[0..4] == b"RTPS"[..]
+    && msg_bytes[9..16] == b"DDSPING"[..]
+}
+
+pub(crate) fn rtps_ping_message() -> [u8; RTPS_PING_SIZE] {
+  let mut ping = [0; RTPS_PING_SIZE];
+  let version = ProtocolVersion::THIS_IMPLEMENTATION;
+  ping[0..4].copy_from_slice(b"RTPS");
+  ping[4..6].copy_from_slice(&[version.major, version.minor]);
+  ping[6..8].copy_from_slice(&VendorId::THIS_IMPLEMENTATION.as_bytes());
+  ping[9..16].copy_from_slice(b"DDSPING");
+  ping
+}
+
+/// Upper bounds on what the RTPS message receiver is willing to process from a
+/// single incoming message (UDP datagram), or from a single source address.
+/// Messages or submessages exceeding these are rejected as early as possible,
+/// in order to protect against pathological or malicious packets and peers.
+///
+/// Use
+/// [`DomainParticipantBuilder::message_receiver_limits`](crate::DomainParticipantBuilder::message_receiver_limits)
+/// to change these.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MessageReceiverLimits {
+  /// Maximum size of a message in bytes. Larger messages are rejected before
+  /// parsing.
+  pub max_message_size: usize,
+  /// Maximum number of submessages in a message. Messages with more
+  /// submessages are rejected before parsing.
+  pub max_submessages: usize,
+  /// Maximum number of inline QoS parameters in a DATA or DATA_FRAG
+  /// submessage. Submessages with more parameters are dropped.
+  pub max_inline_qos_parameters: usize,
+  /// Maximum size of a sample in bytes after decompression. DATA and
+  /// DATA_FRAG submessages of compressed samples that claim a larger
+  /// uncompressed size are dropped, so that decompression cannot exhaust
+  /// memory. See [`Compression`](crate::policy::Compression).
+  pub max_sample_size: usize,
+  /// Limits on the rate of messages from a single source IP address. These
+  /// are checked first, before the message is parsed at all. `None`, the
+  /// default, means no limits.
+  pub source_rate_limit: Option<SourceRateLimit>,
+}
+
+impl Default for MessageReceiverLimits {
+  fn default() -> Self {
+    Self {
+      max_message_size: 64 * 1024, // This is max we can get from UDP.
+      max_submessages: 1024,
+      max_inline_qos_parameters: 64,
+      max_sample_size: 64 * 1024 * 1024,
+      source_rate_limit: None,
+    }
+  }
+}
+
+/// Counts of messages and submessages rejected due to [`MessageReceiverLimits`]
+/// or invalid content
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MessageReceiverRejections {
+  pub oversize_messages: u64,
+  pub too_many_submessages: u64,
+  pub too_many_inline_qos_parameters: u64,
+  /// Submessages of compressed samples larger than
+  /// [`MessageReceiverLimits::max_sample_size`] when decompressed
+  pub oversize_samples: u64,
+  /// Submessages with sequence numbers that are invalid according to the
+  /// RTPS spec, e.g. zero, negative or `SEQUENCENUMBER_UNKNOWN`, or
+  /// unreasonably large. See [`SequenceNumber::is_valid`].
+  pub invalid_sequence_numbers: u64,
+  /// Messages dropped because their source exceeded
+  /// [`MessageReceiverLimits::source_rate_limit`] or was blocked for it
+  pub rate_limited_messages: u64,
+  /// How many times a source has been blocked for exceeding
+  /// [`MessageReceiverLimits::source_rate_limit`]
+  pub blocked_sources: u64,
+}
+
+/// Statistics of the RTPS message receiver, from
+/// [`DomainParticipant::message_receiver_statistics`](crate::DomainParticipant::message_receiver_statistics).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MessageReceiverStatistics {
+  /// Messages and submessages rejected due to [`MessageReceiverLimits`] or
+  /// invalid content
+  pub rejections: MessageReceiverRejections,
+  /// Submessages skipped because their kind (submessageId) is unknown or
+  /// vendor-specific (0x80 - 0xFF), counted by kind. The rest of the message
+  /// is processed normally.
+  pub skipped_submessages: BTreeMap<u8, u64>,
+}
 
 // Secure submessage receiving state machine:
 //
@@ -72,9 +181,15 @@ pub struct SecureWrapping {
 #[derive(Debug, Clone)]
 pub struct MessageReceiverState {
   pub source_guid_prefix: GuidPrefix,
+  // Identify the implementation of the sender, see compatibility::features()
+  pub source_vendor_id: VendorId,
+  pub source_version: ProtocolVersion,
   pub unicast_reply_locator_list: Vec<Locator>,
   pub multicast_reply_locator_list: Vec<Locator>,
   pub source_timestamp: Option<Timestamp>,
+  // The message was sent to a multicast address, so other Readers have likely
+  // received it too.
+  pub received_via_multicast: bool,
   #[cfg(feature = "security")]
   pub secure_rtps_wrapped: Option<SecureWrapping>,
 }
@@ -83,9 +198,12 @@ impl Default for MessageReceiverState {
   fn default() -> Self {
     Self {
       source_guid_prefix: GuidPrefix::default(),
+      source_vendor_id: VendorId::VENDOR_UNKNOWN,
+      source_version: ProtocolVersion::THIS_IMPLEMENTATION,
       unicast_reply_locator_list: Vec::default(),
       multicast_reply_locator_list: Vec::default(),
       source_timestamp: Some(Timestamp::INVALID),
+      received_via_multicast: false,
       #[cfg(feature = "security")]
       secure_rtps_wrapped: None,
     }
@@ -118,6 +236,12 @@ pub(crate) struct MessageReceiver {
   pub unicast_reply_locator_list: Vec<Locator>,
   pub multicast_reply_locator_list: Vec<Locator>,
   pub source_timestamp: Option<Timestamp>,
+  received_via_multicast: bool,
+
+  limits: MessageReceiverLimits,
+  source_rate_limiter: Option<SourceRateLimiter>,
+  // Shared with DomainParticipant, which reports these to the application
+  statistics: Arc<Mutex<MessageReceiverStatistics>>,
 
   submessage_count: usize, // Used in tests only?
   secure_receiver_state: Option<SecureReceiverState>,
@@ -135,6 +259,8 @@ impl MessageReceiver {
     acknack_sender: mio_channel::SyncSender<(GuidPrefix, AckSubmessage)>,
     spdp_liveness_sender: mio_channel::SyncSender<GuidPrefix>,
     security_plugins: Option<SecurityPluginsHandle>,
+    limits: MessageReceiverLimits,
+    statistics: Arc<Mutex<MessageReceiverStatistics>>,
   ) -> Self {
     Self {
       available_readers: BTreeMap::new(),
@@ -150,6 +276,11 @@ impl MessageReceiver {
       unicast_reply_locator_list: vec![Locator::Invalid],
       multicast_reply_locator_list: vec![Locator::Invalid],
       source_timestamp: None,
+      received_via_multicast: false,
+
+      source_rate_limiter: limits.source_rate_limit.map(SourceRateLimiter::new),
+      limits,
+      statistics,
 
       submessage_count: 0,
       secure_receiver_state: None,
@@ -183,9 +314,12 @@ impl MessageReceiver {
   fn clone_partial_message_receiver_state(&self) -> MessageReceiverState {
     MessageReceiverState {
       source_guid_prefix: self.source_guid_prefix,
+      source_vendor_id: self.source_vendor_id,
+      source_version: self.source_version,
       unicast_reply_locator_list: self.unicast_reply_locator_list.clone(),
       multicast_reply_locator_list: self.multicast_reply_locator_list.clone(),
       source_timestamp: self.source_timestamp,
+      received_via_multicast: self.received_via_multicast,
       #[cfg(feature = "security")]
       secure_rtps_wrapped: self.secure_rtps_wrapped.clone(),
     }
@@ -209,17 +343,54 @@ impl MessageReceiver {
     self.available_readers.get_mut(&reader_id)
   }
 
+  fn statistics(&self) -> MutexGuard<'_, MessageReceiverStatistics> {
+    self.statistics.lock().unwrap_or_else(|e| e.into_inner())
+  }
+
+  // Applies MessageReceiverLimits::source_rate_limit to a message from
+  // `source`. Returns false, if the message is to be dropped unparsed.
+  pub fn admit_from(&mut self, source: SocketAddr, msg_len: usize) -> bool {
+    let Some(limiter) = self.source_rate_limiter.as_mut() else {
+      return true;
+    };
+    let admission = limiter.admit(source.ip(), msg_len, Instant::now());
+    let block_duration = limiter.block_duration();
+    if admission == Admission::Admit {
+      return true;
+    }
+    let blocked = {
+      let mut statistics = self.statistics();
+      statistics.rejections.rate_limited_messages += 1;
+      if admission == Admission::Block {
+        statistics.rejections.blocked_sources += 1;
+      }
+      statistics.rejections.blocked_sources
+    };
+    stats::message_rejected("rate_limited_messages");
+    if admission == Admission::Block {
+      warn!(
+        "Source {} exceeded its rate limit. Blocking it for {:?}. Blocked so far: {}",
+        source.ip(),
+        block_duration,
+        blocked
+      );
+    }
+    false
+  }
+
+  // Like handle_received_packet, but the packet came to a multicast address.
+  pub fn handle_received_multicast_packet(&mut self, msg_bytes: &Bytes) {
+    self.received_via_multicast = true;
+    self.handle_received_packet(msg_bytes);
+    self.received_via_multicast = false;
+  }
+
   pub fn handle_received_packet(&mut self, msg_bytes: &Bytes) {
-    // Check for RTPS ping message. At least RTI implementation sends these.
-    // What should we do with them? The spec does not say.
+    // RTPS ping messages are handled by the event loop, because responding
+    // requires the source address.
     if msg_bytes.len() < RTPS_MESSAGE_HEADER_SIZE {
-      if msg_bytes.len() >= 16
-        && msg_bytes[0..4] == b"RTPS"[..]
-        && msg_bytes[9..16] == b"DDSPING"[..]
-      {
-        // TODO: Add some sensible ping message handling here.
-        info!("Received RTPS PING. Do not know how to respond.");
-        debug!("Data was {:?}", &msg_bytes);
+      if is_rtps_ping(msg_bytes) {
+        debug!("Received RTPS PING. Ignoring.");
       } else {
         warn!("Message is shorter than RTPS header. Cannot deserialize.");
         debug!("Data was {:?}", &msg_bytes);
@@ -227,10 +398,55 @@ impl MessageReceiver {
       return;
     }
 
+    // Check limits before doing any actual parsing work.
+    if msg_bytes.len() > self.limits.max_message_size {
+      let rejected = {
+        let mut statistics = self.statistics();
+        statistics.rejections.oversize_messages += 1;
+        statistics.rejections.oversize_messages
+      };
+      stats::message_rejected("oversize_messages");
+      warn!(
+        "Rejecting message of {} bytes, limit is {}. Rejected so far: {}",
+        msg_bytes.len(),
+        self.limits.max_message_size,
+        rejected
+      );
+      return;
+    }
+    if submessage_count_exceeds(
+      &msg_bytes[RTPS_MESSAGE_HEADER_SIZE..],
+      self.limits.max_submessages,
+    ) {
+      let rejected = {
+        let mut statistics = self.statistics();
+        statistics.rejections.too_many_submessages += 1;
+        statistics.rejections.too_many_submessages
+      };
+      stats::message_rejected("too_many_submessages");
+      warn!(
+        "Rejecting message with more than {} submessages. Rejected so far: {}",
+        self.limits.max_submessages, rejected
+      );
+      return;
+    }
+
     // call Speedy reader
     // Bytes .clone() is cheap, so no worries
-    let rtps_message = match Message::read_from_buffer(msg_bytes) {
-      Ok(m) => m,
+    let rtps_message = match Message::read_from_buffer_with_skipped(msg_bytes) {
+      Ok((m, skipped)) => {
+        if !skipped.is_empty() {
+          let mut statistics = self.statistics();
+          for kind in skipped {
+            *statistics
+              .skipped_submessages
+              .entry(u8::from(kind))
+              .or_default() += 1;
+            stats::submessage_skipped(kind.is_vendor_specific());
+          }
+        }
+        m
+      }
       Err(speedy_err) => {
         warn!("RTPS deserialize error {:?}", speedy_err);
         debug!("Data was {:?}", msg_bytes);
@@ -319,6 +535,10 @@ impl MessageReceiver {
       self.handle_submessage(submessage);
       self.submessage_count += 1;
     }
+    // Publish the received changes to DataReaders, all of the message at once
+    for reader in self.available_readers.values_mut() {
+      reader.commit_changes();
+    }
   }
 
   fn handle_submessage(&mut self, submessage: Submessage) {
@@ -330,6 +550,11 @@ impl MessageReceiver {
         match submessage.body {
           SubmessageBody::Interpreter(m) => self.handle_interpreter_submessage(m),
           SubmessageBody::Writer(submessage) => {
+            if !self.inline_qos_within_limits(&submessage)
+              || !self.writer_sequence_numbers_valid(&submessage)
+            {
+              return;
+            }
             let security_plugins_clone = self.security_plugins.clone();
             let receiver_entity_id = submessage.receiver_entity_id();
 
@@ -413,6 +638,9 @@ impl MessageReceiver {
           }
 
           SubmessageBody::Reader(submessage) => {
+            if !self.reader_sequence_numbers_valid(&submessage) {
+              return;
+            }
             #[cfg(not(feature = "security"))]
             {
               self.handle_reader_submessage(submessage);
@@ -518,6 +746,142 @@ impl MessageReceiver {
     } // match secure_submessage_state
   } // fn
 
+  // Check inline QoS size of DATA and DATA_FRAG, and the decompressed size of
+  // compressed samples. Counts a rejection if a limit is exceeded.
+  fn inline_qos_within_limits(&mut self, submessage: &WriterSubmessage) -> bool {
+    let inline_qos = match submessage {
+      WriterSubmessage::Data(data, _) => data.inline_qos.as_ref(),
+      WriterSubmessage::DataFrag(datafrag, _) => datafrag.inline_qos.as_ref(),
+      _ => None,
+    };
+    match inline_qos {
+      Some(pl) if pl.parameters.len() > self.limits.max_inline_qos_parameters => {
+        let rejected = {
+          let mut statistics = self.statistics();
+          statistics.rejections.too_many_inline_qos_parameters += 1;
+          statistics.rejections.too_many_inline_qos_parameters
+        };
+        stats::message_rejected("too_many_inline_qos_parameters");
+        warn!(
+          "Dropping {:?} with {} inline QoS parameters, limit is {}. Rejected so far: {}",
+          submessage.sender_entity_id(),
+          pl.parameters.len(),
+          self.limits.max_inline_qos_parameters,
+          rejected
+        );
+        false
+      }
+      Some(pl) => match compression::uncompressed_len(pl) {
+        Some(len) if len > self.limits.max_sample_size => {
+          let rejected = {
+            let mut statistics = self.statistics();
+            statistics.rejections.oversize_samples += 1;
+            statistics.rejections.oversize_samples
+          };
+          stats::message_rejected("oversize_samples");
+          warn!(
+            "Dropping {:?} with a compressed sample of {} bytes, limit is {}. Rejected so far: {}",
+            submessage.sender_entity_id(),
+            len,
+            self.limits.max_sample_size,
+            rejected
+          );
+          false
+        }
+        _ => true,
+      },
+      None => true,
+    }
+  }
+
+  // Check sequence numbers in a writer submessage according to the validity
+  // rules of RTPS spec v2.5 Section 8.3.7. Readers rely on this, e.g. a
+  // HEARTBEAT with a huge lastSN or an unknown firstSN must not get through.
+  // Counts a rejection if the submessage is invalid.
+  fn writer_sequence_numbers_valid(&mut self, submessage: &WriterSubmessage) -> bool {
+    let invalid = match submessage {
+      WriterSubmessage::Data(data, _) => {
+        (!data.writer_sn.is_valid()).then(|| format!("DATA writerSN={:?}", data.writer_sn))
+      }
+      WriterSubmessage::DataFrag(datafrag, _) => (!datafrag.writer_sn.is_valid())
+        .then(|| format!("DATA_FRAG writerSN={:?}", datafrag.writer_sn)),
+      WriterSubmessage::Gap(gap, _) => {
+        (!gap.gap_start.is_valid() || !gap.gap_list.base().is_valid()).then(|| {
+          format!(
+            "GAP gapStart={:?} gapList.base={:?}",
+            gap.gap_start,
+            gap.gap_list.base()
+          )
+        })
+      }
+      // lastSN = firstSN - 1 is valid, and means that nothing is available.
+      WriterSubmessage::Heartbeat(heartbeat, _) => (!heartbeat.first_sn.is_valid()
+        || heartbeat.last_sn < SequenceNumber::zero()
+        || heartbeat.last_sn > SequenceNumber::MAX_ACCEPTED
+        || heartbeat.last_sn.plus_1() < heartbeat.first_sn)
+        .then(|| {
+          format!(
+            "HEARTBEAT firstSN={:?} lastSN={:?}",
+            heartbeat.first_sn, heartbeat.last_sn
+          )
+        }),
+      WriterSubmessage::HeartbeatFrag(heartbeatfrag, _) => (!heartbeatfrag.writer_sn.is_valid())
+        .then(|| format!("HEARTBEAT_FRAG writerSN={:?}", heartbeatfrag.writer_sn)),
+    };
+    match invalid {
+      Some(description) => {
+        self.reject_invalid_sequence_numbers(&description);
+        false
+      }
+      None => true,
+    }
+  }
+
+  // Same as above, for ACKNACK and NACK_FRAG
+  fn reader_sequence_numbers_valid(&mut self, submessage: &ReaderSubmessage) -> bool {
+    let invalid = match submessage {
+      ReaderSubmessage::AckNack(acknack, _) => {
+        (!acknack.reader_sn_state.base().is_valid()).then(|| {
+          format!(
+            "ACKNACK readerSNState.base={:?}",
+            acknack.reader_sn_state.base()
+          )
+        })
+      }
+      ReaderSubmessage::NackFrag(nackfrag, _) => (!nackfrag.writer_sn.is_valid())
+        .then(|| format!("NACK_FRAG writerSN={:?}", nackfrag.writer_sn)),
+    };
+    match invalid {
+      Some(description) => {
+        self.reject_invalid_sequence_numbers(&description);
+        false
+      }
+      None => true,
+    }
+  }
+
+  fn reject_invalid_sequence_numbers(&self, description: &str) {
+    let rejected = {
+      let mut statistics = self.statistics();
+      statistics.rejections.invalid_sequence_numbers += 1;
+      statistics.rejections.invalid_sequence_numbers
+    };
+    stats::message_rejected("invalid_sequence_numbers");
+    // A well-behaving peer never sends these, so this is a potential attack.
+    #[cfg(feature = "security")]
+    security_warn!(
+      "Dropping {} from {:?}: invalid sequence numbers. Rejected so far: {}",
+      description,
+      self.source_guid_prefix,
+      rejected
+    );
+    #[cfg(not(feature = "security"))]
+    warn!(
+      "Dropping {} from {:?}: invalid sequence numbers. Rejected so far: {}",
+      description, self.source_guid_prefix, rejected
+    );
+  }
+
   fn handle_writer_submessage(
     &mut self,
     target_reader_entity_id: EntityId,
@@ -825,8 +1189,18 @@ impl MessageReceiver {
         }
       }
 
-      ReaderSubmessage::NackFrag(_, _) => {
-        // TODO: Implement NackFrag handling
+      ReaderSubmessage::NackFrag(nackfrag, _) => {
+        // Same as AckNack above
+        match self
+          .acknack_sender
+          .try_send((self.source_guid_prefix, AckSubmessage::NackFrag(nackfrag)))
+        {
+          Ok(_) => (),
+          Err(TrySendError::Full(_)) => {
+            info!("AckNack pipe full. Looks like I am very busy. Discarding NackFrag.");
+          }
+          Err(e) => warn!("AckNack pipe fail: {:?}", e),
+        }
       }
     }
   }
@@ -863,6 +1237,9 @@ impl MessageReceiver {
             decoded_writer_submessage,
             approved_receiving_datareader_crypto_handles,
           ))) => {
+            if !self.writer_sequence_numbers_valid(&decoded_writer_submessage) {
+              return;
+            }
             let receiver_entity_id = decoded_writer_submessage.receiver_entity_id();
 
             // If the receiver entity ID is unknown, we try to find the correct id based on
@@ -915,6 +1292,9 @@ impl MessageReceiver {
             decoded_reader_submessage,
             approved_receiving_datawriter_crypto_handles,
           ))) => {
+            if !self.reader_sequence_numbers_valid(&decoded_reader_submessage) {
+              return;
+            }
             let receiver_entity_id = decoded_reader_submessage.receiver_entity_id();
             let receiver_guid = GUID {
               prefix: self.dest_guid_prefix,
@@ -973,11 +1353,12 @@ impl MessageReceiver {
         self.source_version = info_src.protocol_version;
         self.source_vendor_id = info_src.vendor_id;
 
-        // TODO: Why are the following set on InfoSource?
-        self.unicast_reply_locator_list.clear(); // Or invalid?
-        self.multicast_reply_locator_list.clear(); // Or invalid?
-        self.source_timestamp = None; // TODO: Why does InfoSource set timestamp
-                                      // to None?
+        // RTPS spec v2.5 Section 8.3.7.9.4: Reply locators and timestamp from
+        // earlier submessages belong to the previous source, so they are
+        // reset. Empty reply locator list means "use locators from discovery".
+        self.unicast_reply_locator_list.clear();
+        self.multicast_reply_locator_list.clear();
+        self.source_timestamp = None;
       }
       InterpreterSubmessage::InfoReply(info_reply, flags) => {
         self.unicast_reply_locator_list = info_reply.unicast_locator_list;
@@ -1009,15 +1390,6 @@ impl MessageReceiver {
     }
   }
 
-  pub fn notify_data_to_readers(&mut self, readers: Vec<EntityId>) {
-    for eid in readers {
-      self
-        .available_readers
-        .get_mut(&eid)
-        .map(Reader::notify_cache_change);
-    }
-  }
-
   // sends 0 seqnum acknacks for those writer that haven't had any action
   pub fn send_preemptive_acknacks(&mut self) {
     for reader in self.available_readers.values_mut() {
@@ -1042,6 +1414,11 @@ impl MessageReceiver {
     )
   }
 
+  #[cfg(test)]
+  pub(crate) fn submessage_count(&self) -> usize {
+    self.submessage_count
+  }
+
   #[cfg(test)]
   fn get_reader_history_cache_start_and_end_seq_num(
     &self,
@@ -1061,6 +1438,14 @@ impl MessageReceiver {
 // ------------------------------------------------------------------------------------------------------
 // ------------------------------------------------------------------------------------------------------
 
+// Walks through the submessage headers to see if there are more than limit
+// submessages. This does not parse submessage contents, so it is cheap, and it
+// stops as soon as the limit is exceeded. Malformed lengths are left for the
+// actual parser to complain about.
+fn submessage_count_exceeds(submessages: &[u8], limit: usize) -> bool {
+  rtps_core::submessages(submessages).nth(limit).is_some()
+}
+
 #[cfg(test)]
 mod tests {
   use std::{
@@ -1084,7 +1469,10 @@ mod tests {
     mio_source,
     network::udp_sender::UDPSender,
     rtps::reader::ReaderIngredients,
-    serialization::cdr_deserializer::deserialize_from_little_endian,
+    serialization::{
+      cdr_deserializer::{deserialize_from_big_endian, deserialize_from_little_endian},
+      representation_identifier::RepresentationIdentifier,
+    },
     structure::{dds_cache::DDSCache, guid::EntityKind},
   };
   use super::*;
@@ -1129,6 +1517,8 @@ mod tests {
       acknack_sender,
       spdp_liveness_sender,
       None,
+      MessageReceiverLimits::default(),
+      Arc::default(),
     );
 
     // Create a reader to process the message
@@ -1168,6 +1558,8 @@ mod tests {
       data_reader_command_receiver: reader_command_receiver,
       data_reader_waker: data_reader_waker.clone(),
       poll_event_sender: notification_event_sender,
+      readiness_queue: None,
+      fragment_streams: Arc::default(),
       security_plugins: None,
     };
 
@@ -1227,6 +1619,167 @@ mod tests {
     assert_eq!(deserialized_shape_type.color, "RED");
   }
 
+  #[test]
+  fn test_big_endian_shapes_demo_message_deserialization() {
+    // The same INFO_DST, INFO_TS, DATA & HEARTBEAT as above, but as sent by a
+    // big-endian implementation: submessage bodies are big-endian, and the
+    // payload is CDR_BE.
+    let udp_bits1 = Bytes::from_static(&[
+      0x52, 0x54, 0x50, 0x53, 0x02, 0x03, 0x01, 0x0f, 0x01, 0x0f, 0x99, 0x06, 0x78, 0x34, 0x00,
+      0x00, 0x01, 0x00, 0x00, 0x00, 0x0e, 0x00, 0x00, 0x0c, 0x01, 0x03, 0x00, 0x0c, 0x29, 0x2d,
+      0x31, 0xa2, 0x28, 0x20, 0x02, 0x08, 0x09, 0x00, 0x00, 0x08, 0x5e, 0xf3, 0x15, 0x1a, 0x13,
+      0xfb, 0xcc, 0x00, 0x15, 0x04, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00,
+      0x00, 0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x5b, 0x00, 0x00, 0x00,
+      0x00, 0x00, 0x00, 0x00, 0x04, 0x52, 0x45, 0x44, 0x00, 0x00, 0x00, 0x00, 0x69, 0x00, 0x00,
+      0x00, 0x17, 0x00, 0x00, 0x00, 0x1e, 0x07, 0x00, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x00,
+      0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x5b, 0x00, 0x00, 0x00, 0x00,
+      0x00, 0x00, 0x00, 0x5b, 0x00, 0x00, 0x00, 0x1f,
+    ]);
+
+    let sample = receive_shapes_demo_sample(&udp_bits1);
+    match &sample {
+      DDSData::Data {
+        serialized_payload, ..
+      } => assert_eq!(
+        serialized_payload.representation_identifier,
+        RepresentationIdentifier::CDR_BE
+      ),
+      other => panic!("Expected Data, got {other:?}"),
+    }
+    let shape: ShapeType = deserialize_from_big_endian(&sample.data()).unwrap();
+    assert_eq!(
+      shape,
+      ShapeType {
+        color: "RED".to_string(),
+        x: 0x69,
+        y: 0x17,
+        size: 0x1e,
+      }
+    );
+  }
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+  struct ShapeType {
+    color: String,
+    x: i32,
+    y: i32,
+    size: i32,
+  }
+
+  // Feeds a Shapes demo message to a reader, and returns the received sample.
+  fn receive_shapes_demo_sample(udp_bits1: &Bytes) -> DDSData {
+    // The message bytes contain the following guid prefix as the message target.
+    let target_gui_prefix = GuidPrefix::new(&[
+      0x01, 0x03, 0x00, 0x0c, 0x29, 0x2d, 0x31, 0xa2, 0x28, 0x20, 0x02, 0x08,
+    ]);
+
+    // The message bytes contain the following guid as the message source
+    let remote_writer_guid = GUID::new(
+      GuidPrefix::new(&[
+        0x01, 0x0f, 0x99, 0x06, 0x78, 0x34, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+      ]),
+      EntityId::create_custom_entity_id([0, 0, 1], EntityKind::WRITER_WITH_KEY_USER_DEFINED),
+    );
+
+    // Create a message receiver
+    let (acknack_sender, _acknack_receiver) =
+      mio_channel::sync_channel::<(GuidPrefix, AckSubmessage)>(10);
+    let (spdp_liveness_sender, _spdp_liveness_receiver) = mio_channel::sync_channel(8);
+    let mut message_receiver = MessageReceiver::new(
+      target_gui_prefix,
+      acknack_sender,
+      spdp_liveness_sender,
+      None,
+      MessageReceiverLimits::default(),
+      Arc::default(),
+    );
+
+    // Create a reader to process the message
+    let entity =
+      EntityId::create_custom_entity_id([0, 0, 0], EntityKind::READER_WITH_KEY_USER_DEFINED);
+    let reader_guid = GUID::new_with_prefix_and_id(target_gui_prefix, entity);
+
+    let (notification_sender, _notification_receiver) = mio_channel::sync_channel::<()>(100);
+    let (_notification_event_source, notification_event_sender) =
+      mio_source::make_poll_channel().unwrap();
+    let data_reader_waker = Arc::new(Mutex::new(None));
+
+    let (status_sender, _status_receiver) = sync_status_channel::<DataReaderStatus>(4).unwrap();
+    let (participant_status_sender, _participant_status_receiver) =
+      sync_status_channel(16).unwrap();
+
+    let (_reader_command_sender, reader_command_receiver) =
+      mio_channel::sync_channel::<ReaderCommand>(10);
+
+    let qos_policy = QosPolicies::qos_none();
+
+    let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+
+    let topic_cache_handle = dds_cache.write().unwrap().add_new_topic(
+      "test".to_string(),
+      TypeDesc::new("test".to_string()),
+      &qos_policy,
+    );
+    let reader_ing = ReaderIngredients {
+      guid: reader_guid,
+      notification_sender,
+      status_sender,
+      topic_name: "test".to_string(),
+      topic_cache_handle: topic_cache_handle.clone(),
+      like_stateless: false,
+      qos_policy,
+      data_reader_command_receiver: reader_command_receiver,
+      data_reader_waker: data_reader_waker.clone(),
+      poll_event_sender: notification_event_sender,
+      readiness_queue: None,
+      fragment_streams: Arc::default(),
+      security_plugins: None,
+    };
+
+    let mut new_reader = Reader::new(
+      reader_ing,
+      Rc::new(UDPSender::new_with_random_port().unwrap()),
+      mio_extras::timer::Builder::default().build(),
+      participant_status_sender,
+    );
+
+    // Add info of the writer to the reader
+    new_reader.matched_writer_add(
+      remote_writer_guid,
+      EntityId::UNKNOWN,
+      vec![],
+      vec![],
+      &QosPolicies::qos_none(),
+    );
+
+    // Add reader to message reader and process the bytes message
+    message_receiver.add_reader(new_reader);
+
+    message_receiver.handle_received_packet(udp_bits1);
+
+    // Verify the message reader has recorded the right amount of submessages
+    assert_eq!(message_receiver.submessage_count, 4);
+
+    // This is not correct way to read history cache values but it serves as a test
+    let sequence_numbers =
+      message_receiver.get_reader_history_cache_start_and_end_seq_num(reader_guid.entity_id);
+    info!(
+      "history change sequence number range: {:?}",
+      sequence_numbers
+    );
+
+    // Get the DDSData (serialized) from the topic cache / history cache
+    let a = message_receiver
+      .get_reader_and_history_cache_change(
+        reader_guid.entity_id,
+        *sequence_numbers.first().unwrap(),
+      )
+      .expect("No data in topic cache");
+    info!("reader history cache DATA: {:?}", a.data());
+
+    a
+  }
+
   #[test]
   fn mr_test_submsg_count() {
     // Udp packet with INFO_DST, INFO_TS, DATA, HEARTBEAT
@@ -1254,8 +1807,14 @@ mod tests {
     let (acknack_sender, _acknack_receiver) =
       mio_channel::sync_channel::<(GuidPrefix, AckSubmessage)>(10);
     let (spdp_liveness_sender, _spdp_liveness_receiver) = mio_channel::sync_channel(8);
-    let mut message_receiver =
-      MessageReceiver::new(guid_new.prefix, acknack_sender, spdp_liveness_sender, None);
+    let mut message_receiver = MessageReceiver::new(
+      guid_new.prefix,
+      acknack_sender,
+      spdp_liveness_sender,
+      None,
+      MessageReceiverLimits::default(),
+      Arc::default(),
+    );
 
     message_receiver.handle_received_packet(&udp_bits1);
     assert_eq!(message_receiver.submessage_count, 4);
@@ -1264,6 +1823,278 @@ mod tests {
     assert_eq!(message_receiver.submessage_count, 2);
   }
 
+  #[test]
+  fn mr_test_limits() {
+    // Udp packet with INFO_DST, ACKNACK
+    let udp_bits = Bytes::from_static(&[
+      0x52, 0x54, 0x50, 0x53, 0x02, 0x03, 0x01, 0x0f, 0x01, 0x0f, 0x99, 0x06, 0x78, 0x34, 0x00,
+      0x00, 0x01, 0x00, 0x00, 0x00, 0x0e, 0x01, 0x0c, 0x00, 0x01, 0x03, 0x00, 0x0c, 0x29, 0x2d,
+      0x31, 0xa2, 0x28, 0x20, 0x02, 0x08, 0x06, 0x03, 0x18, 0x00, 0x00, 0x00, 0x04, 0xc7, 0x00,
+      0x00, 0x04, 0xc2, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+      0x03, 0x00, 0x00, 0x00,
+    ]);
+    assert!(!submessage_count_exceeds(&udp_bits[20..], 2));
+    assert!(submessage_count_exceeds(&udp_bits[20..], 1));
+
+    let guid_new = GUID::default();
+    let (acknack_sender, _acknack_receiver) =
+      mio_channel::sync_channel::<(GuidPrefix, AckSubmessage)>(10);
+    let (spdp_liveness_sender, _spdp_liveness_receiver) = mio_channel::sync_channel(8);
+    let statistics = Arc::new(Mutex::new(MessageReceiverStatistics::default()));
+    let mut message_receiver = MessageReceiver::new(
+      guid_new.prefix,
+      acknack_sender,
+      spdp_liveness_sender,
+      None,
+      MessageReceiverLimits {
+        max_message_size: 60,
+        max_submessages: 1,
+        ..MessageReceiverLimits::default()
+      },
+      statistics.clone(),
+    );
+
+    // Too large
+    message_receiver.handle_received_packet(&udp_bits);
+    assert_eq!(message_receiver.submessage_count, 0);
+    assert_eq!(statistics.lock().unwrap().rejections.oversize_messages, 1);
+
+    // Too many submessages
+    message_receiver.limits.max_message_size = 1000;
+    message_receiver.handle_received_packet(&udp_bits);
+    assert_eq!(message_receiver.submessage_count, 0);
+    assert_eq!(
+      statistics.lock().unwrap().rejections.too_many_submessages,
+      1
+    );
+
+    // Within limits
+    message_receiver.limits.max_submessages = 2;
+    message_receiver.handle_received_packet(&udp_bits);
+    assert_eq!(message_receiver.submessage_count, 2);
+  }
+
+  #[test]
+  fn mr_test_compressed_sample_size_limit() {
+    use crate::{
+      messages::submessages::elements::{parameter::Parameter, parameter_list::ParameterList},
+      structure::parameter_id::ParameterId,
+    };
+
+    let (acknack_sender, _acknack_receiver) =
+      mio_channel::sync_channel::<(GuidPrefix, AckSubmessage)>(10);
+    let (spdp_liveness_sender, _spdp_liveness_receiver) = mio_channel::sync_channel(8);
+    let statistics = Arc::new(Mutex::new(MessageReceiverStatistics::default()));
+    let mut message_receiver = MessageReceiver::new(
+      GuidPrefix::default(),
+      acknack_sender,
+      spdp_liveness_sender,
+      None,
+      MessageReceiverLimits {
+        max_sample_size: 1000,
+        ..MessageReceiverLimits::default()
+      },
+      statistics.clone(),
+    );
+    // zstd compressed sample of `len` bytes
+    let data = |len: u32| {
+      let mut value = 1_u32.to_be_bytes().to_vec();
+      value.extend_from_slice(&len.to_be_bytes());
+      WriterSubmessage::Data(
+        Data {
+          reader_id: EntityId::UNKNOWN,
+          writer_id: EntityId::UNKNOWN,
+          writer_sn: SequenceNumber::new(1),
+          inline_qos: Some(ParameterList {
+            parameters: vec![Parameter {
+              parameter_id: ParameterId::PID_RUSTDDS_COMPRESSED_PAYLOAD,
+              value,
+            }],
+          }),
+          serialized_payload: None,
+        },
+        BitFlags::from_flag(DATA_Flags::Endianness),
+      )
+    };
+
+    assert!(message_receiver.inline_qos_within_limits(&data(1000)));
+    assert!(!message_receiver.inline_qos_within_limits(&data(u32::MAX)));
+    assert_eq!(statistics.lock().unwrap().rejections.oversize_samples, 1);
+  }
+
+  #[test]
+  fn mr_test_skip_unknown_submessages() {
+    // Udp packet with vendor-specific submessage, INFO_DST, unknown standard
+    // submessage, ACKNACK
+    let udp_bits = Bytes::from_static(&[
+      0x52, 0x54, 0x50, 0x53, 0x02, 0x03, 0x01, 0x0f, 0x01, 0x0f, 0x99, 0x06, 0x78, 0x34, 0x00,
+      0x00, 0x01, 0x00, 0x00, 0x00, 0x80, 0x01, 0x08, 0x00, 0xde, 0xad, 0xbe, 0xef, 0xde, 0xad,
+      0xbe, 0xef, 0x0e, 0x01, 0x0c, 0x00, 0x01, 0x03, 0x00, 0x0c, 0x29, 0x2d, 0x31, 0xa2, 0x28,
+      0x20, 0x02, 0x08, 0x7f, 0x01, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x06, 0x03, 0x18, 0x00,
+      0x00, 0x00, 0x04, 0xc7, 0x00, 0x00, 0x04, 0xc2, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00,
+      0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00,
+    ]);
+    let destination = GuidPrefix::new(&udp_bits[36..48]);
+
+    let (acknack_sender, acknack_receiver) =
+      mio_channel::sync_channel::<(GuidPrefix, AckSubmessage)>(10);
+    let (spdp_liveness_sender, _spdp_liveness_receiver) = mio_channel::sync_channel(8);
+    let statistics = Arc::new(Mutex::new(MessageReceiverStatistics::default()));
+    let mut message_receiver = MessageReceiver::new(
+      destination,
+      acknack_sender,
+      spdp_liveness_sender,
+      None,
+      MessageReceiverLimits::default(),
+      statistics.clone(),
+    );
+
+    message_receiver.handle_received_packet(&udp_bits);
+    assert_eq!(message_receiver.submessage_count, 2);
+    assert!(acknack_receiver.try_recv().is_ok());
+    assert_eq!(
+      statistics.lock().unwrap().skipped_submessages,
+      BTreeMap::from([(0x7f, 1), (0x80, 1)])
+    );
+  }
+
+  #[test]
+  fn mr_test_invalid_sequence_numbers() {
+    // Udp packet with INFO_DST, ACKNACK
+    let mut udp_bits = [
+      0x52, 0x54, 0x50, 0x53, 0x02, 0x03, 0x01, 0x0f, 0x01, 0x0f, 0x99, 0x06, 0x78, 0x34, 0x00,
+      0x00, 0x01, 0x00, 0x00, 0x00, 0x0e, 0x01, 0x0c, 0x00, 0x01, 0x03, 0x00, 0x0c, 0x29, 0x2d,
+      0x31, 0xa2, 0x28, 0x20, 0x02, 0x08, 0x06, 0x03, 0x18, 0x00, 0x00, 0x00, 0x04, 0xc7, 0x00,
+      0x00, 0x04, 0xc2, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+      0x03, 0x00, 0x00, 0x00,
+    ];
+    let destination = GuidPrefix::new(&udp_bits[24..36]);
+
+    let (acknack_sender, acknack_receiver) =
+      mio_channel::sync_channel::<(GuidPrefix, AckSubmessage)>(10);
+    let (spdp_liveness_sender, _spdp_liveness_receiver) = mio_channel::sync_channel(8);
+    let statistics = Arc::new(Mutex::new(MessageReceiverStatistics::default()));
+    let mut message_receiver = MessageReceiver::new(
+      destination,
+      acknack_sender,
+      spdp_liveness_sender,
+      None,
+      MessageReceiverLimits::default(),
+      statistics.clone(),
+    );
+    let invalid_count = || {
+      statistics
+        .lock()
+        .unwrap()
+        .rejections
+        .invalid_sequence_numbers
+    };
+
+    message_receiver.handle_received_packet(&Bytes::copy_from_slice(&udp_bits));
+    assert!(acknack_receiver.try_recv().is_ok());
+    assert_eq!(invalid_count(), 0);
+
+    // readerSNState.base = SEQUENCENUMBER_UNKNOWN
+    udp_bits[48..52].copy_from_slice(&[0xff; 4]);
+    message_receiver.handle_received_packet(&Bytes::copy_from_slice(&udp_bits));
+    assert!(acknack_receiver.try_recv().is_err());
+    assert_eq!(invalid_count(), 1);
+
+    let heartbeat = |first_sn: SequenceNumber, last_sn: SequenceNumber| {
+      Heartbeat {
+        reader_id: EntityId::UNKNOWN,
+        writer_id: EntityId::SEDP_BUILTIN_PUBLICATIONS_WRITER,
+        first_sn,
+        last_sn,
+        count: 1,
+      }
+      .create_submessage(BitFlags::from_flag(HEARTBEAT_Flags::Endianness))
+      .unwrap()
+    };
+    let message = Message {
+      header: Header::new(GuidPrefix::UNKNOWN),
+      submessages: vec![
+        // Valid: nothing available, or a range
+        heartbeat(SequenceNumber::new(1), SequenceNumber::zero()),
+        heartbeat(SequenceNumber::new(5), SequenceNumber::new(10)),
+        // Invalid
+        heartbeat(SequenceNumber::zero(), SequenceNumber::new(10)),
+        heartbeat(
+          SequenceNumber::SEQUENCENUMBER_UNKNOWN,
+          SequenceNumber::new(10),
+        ),
+        heartbeat(SequenceNumber::new(5), SequenceNumber::new(3)),
+        heartbeat(SequenceNumber::new(1), SequenceNumber::new(i64::MAX)),
+      ],
+    };
+    message_receiver.handle_received_packet(&Bytes::from(message.write_to_vec().unwrap()));
+    assert_eq!(invalid_count(), 5);
+  }
+
+  #[test]
+  fn mr_test_relayed_message() {
+    // Udp packet with INFO_DST, ACKNACK
+    let udp_bits = [
+      0x52, 0x54, 0x50, 0x53, 0x02, 0x03, 0x01, 0x0f, 0x01, 0x0f, 0x99, 0x06, 0x78, 0x34, 0x00,
+      0x00, 0x01, 0x00, 0x00, 0x00, 0x0e, 0x01, 0x0c, 0x00, 0x01, 0x03, 0x00, 0x0c, 0x29, 0x2d,
+      0x31, 0xa2, 0x28, 0x20, 0x02, 0x08, 0x06, 0x03, 0x18, 0x00, 0x00, 0x00, 0x04, 0xc7, 0x00,
+      0x00, 0x04, 0xc2, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+      0x03, 0x00, 0x00, 0x00,
+    ];
+    let origin = GuidPrefix::new(&udp_bits[8..20]);
+    let destination = GuidPrefix::new(&udp_bits[24..36]);
+    let relayed =
+      crate::relay::forward_message(&udp_bits, GuidPrefix::new(b"RelayGuidPre")).unwrap();
+
+    let (acknack_sender, acknack_receiver) =
+      mio_channel::sync_channel::<(GuidPrefix, AckSubmessage)>(10);
+    let (spdp_liveness_sender, _spdp_liveness_receiver) = mio_channel::sync_channel(8);
+    let mut message_receiver = MessageReceiver::new(
+      destination,
+      acknack_sender,
+      spdp_liveness_sender,
+      None,
+      MessageReceiverLimits::default(),
+      Arc::default(),
+    );
+
+    message_receiver.handle_received_packet(&relayed);
+    assert_eq!(message_receiver.submessage_count, 3);
+    // The ACKNACK is attributed to the original sender, not the relay
+    let (acknack_source, _acknack) = acknack_receiver.try_recv().unwrap();
+    assert_eq!(acknack_source, origin);
+  }
+
+  #[test]
+  fn mr_test_rtps_ping() {
+    let ping = rtps_ping_message();
+    assert!(is_rtps_ping(&ping));
+    assert_eq!(&ping[0..4], b"RTPS");
+    assert_eq!(&ping[9..16], b"DDSPING");
+    assert!(!is_rtps_ping(&ping[..15]));
+
+    // Version and vendor do not matter
+    let mut other_ping = ping;
+    other_ping[4..8].copy_from_slice(&[2, 1, 1, 1]);
+    assert!(is_rtps_ping(&other_ping));
+
+    // Pings are not RTPS messages, and are ignored by the MessageReceiver
+    let (acknack_sender, _acknack_receiver) =
+      mio_channel::sync_channel::<(GuidPrefix, AckSubmessage)>(10);
+    let (spdp_liveness_sender, spdp_liveness_receiver) = mio_channel::sync_channel(8);
+    let mut message_receiver = MessageReceiver::new(
+      GuidPrefix::default(),
+      acknack_sender,
+      spdp_liveness_sender,
+      None,
+      MessageReceiverLimits::default(),
+      Arc::default(),
+    );
+    message_receiver.handle_received_packet(&Bytes::copy_from_slice(&ping));
+    assert_eq!(message_receiver.submessage_count, 0);
+    assert!(spdp_liveness_receiver.try_recv().is_err());
+  }
+
   #[test]
   fn mr_test_header() {
     let guid_new = GUID::default();
@@ -4,19 +4,26 @@ use std::{
   rc::Rc,
   sync::{Arc, Mutex, MutexGuard},
   task::Waker,
-  time::Duration as StdDuration,
+  time::{Duration as StdDuration, Instant},
 };
 
 use mio_06::Token;
-use mio_extras::{channel as mio_channel, timer::Timer};
+use mio_extras::{
+  channel as mio_channel,
+  timer::{Timeout, Timer},
+};
 use log::{debug, error, info, trace, warn};
+use rand::Rng;
 use enumflags2::BitFlags;
 use speedy::{Endianness, Writable};
 
 use crate::{
   dds::{
+    checksum, compression,
     ddsdata::DDSData,
+    fragment_stream::FragmentStreams,
     qos::{policy, HasQoSPolicy, QosPolicies},
+    reader_multiplexer::ReadinessQueue,
     statusevents::{
       CountWithChange, DataReaderStatus, DomainParticipantStatusEvent, StatusChannelSender,
     },
@@ -40,18 +47,25 @@ use crate::{
   mio_source,
   network::udp_sender::UDPSender,
   rtps::{
-    fragment_assembler::FragmentAssembler, message_receiver::MessageReceiverState,
-    rtps_writer_proxy::RtpsWriterProxy, Message,
+    compatibility::{self, Feature},
+    fragment_assembler::FragmentAssembler,
+    local_endpoints,
+    message_receiver::MessageReceiverState,
+    rtps_writer_proxy::RtpsWriterProxy,
+    Message,
   },
+  stats::ReaderStats,
   structure::{
     cache_change::{CacheChange, ChangeKind},
-    dds_cache::TopicCache,
+    dds_cache::{ChangeNotifier, TopicCache},
+    duration::Duration,
     entity::RTPSEntity,
     guid::{EntityId, GuidPrefix, GUID},
     locator::Locator,
     sequence_number::{FragmentNumber, FragmentNumberSet, SequenceNumber, SequenceNumberSet},
     time::Timestamp,
   },
+  trace::{EndpointTrace, TraceSampling},
 };
 #[cfg(feature = "security")]
 use super::Submessage;
@@ -60,9 +74,29 @@ use crate::security::{security_plugins::SecurityPluginsHandle, SecurityResult};
 #[cfg(not(feature = "security"))]
 use crate::no_security::SecurityPluginsHandle;
 
+// A partially received sample is discarded, if no fragments of it have
+// arrived for this long. The check is done at most once per interval.
+const FRAGMENT_TIMEOUT: StdDuration = StdDuration::from_secs(10);
+const FRAGMENT_GC_INTERVAL: StdDuration = StdDuration::from_secs(1);
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub(crate) enum TimedEvent {
   DeadlineMissedCheck,
+  // The DataReader holds back samples for TimeOrderedDelivery
+  HoldBackRelease,
+  // Time to respond to a multicast HEARTBEAT from this writer
+  HeartbeatResponse { writer_guid: GUID },
+  // Wake-up of the DataReader, if NotificationCoalescing still holds it back
+  NotificationFlush,
+}
+
+// A response to a multicast HEARTBEAT, waiting for its random delay to pass
+#[derive(Debug)]
+struct PendingHeartbeatResponse {
+  first_sn: SequenceNumber,
+  last_sn: SequenceNumber,
+  final_flag_set: bool,
+  reply_locators: Vec<Locator>,
 }
 
 // Some pieces necessary to construct a reader.
@@ -79,6 +113,10 @@ pub(crate) struct ReaderIngredients {
   pub data_reader_command_receiver: mio_channel::Receiver<ReaderCommand>,
   pub(crate) data_reader_waker: Arc<Mutex<Option<Waker>>>,
   pub(crate) poll_event_sender: mio_source::PollEventSender,
+  // Subscriber::take_any() readiness
+  pub(crate) readiness_queue: Option<Arc<ReadinessQueue>>,
+  // Streaming reception of large samples, shared with the DataReader
+  pub(crate) fragment_streams: Arc<FragmentStreams>,
 
   pub(crate) security_plugins: Option<SecurityPluginsHandle>,
 }
@@ -101,8 +139,6 @@ impl fmt::Debug for ReaderIngredients {
 }
 
 pub(crate) struct Reader {
-  // Should the instant be sent?
-  notification_sender: mio_channel::SyncSender<()>,
   status_sender: StatusChannelSender<DataReaderStatus>,
   udp_sender: Rc<UDPSender>,
 
@@ -122,10 +158,15 @@ pub(crate) struct Reader {
 
   topic_name: String,
   qos_policy: QosPolicies,
+  // Set when the QoS has been updated. The event loop then re-evaluates
+  // matching with remote writers.
+  qos_updated: bool,
 
   my_guid: GUID,
 
+  // Maximum delay of responses to multicast HEARTBEATs
   heartbeat_response_delay: StdDuration,
+  pending_heartbeat_responses: BTreeMap<GUID, PendingHeartbeatResponse>,
 
   // TODO: Implement (use) this
   #[allow(dead_code)]
@@ -134,16 +175,30 @@ pub(crate) struct Reader {
   received_heartbeat_count: i32,
 
   fragment_assemblers: BTreeMap<GUID, FragmentAssembler>,
+  // When fragment_assemblers were last garbage collected
+  fragments_collected: Instant,
+  fragment_streams: Arc<FragmentStreams>,
+  // Received changes are staged in the topic cache, and published to the
+  // DataReader by commit_changes() after the whole RTPS message is handled.
+  changes_staged: bool,
   matched_writers: BTreeMap<GUID, RtpsWriterProxy>,
   writer_match_count_total: i32, // total count, never decreases
 
   requested_deadline_missed_count: i32,
   offered_incompatible_qos_count: i32,
+  sample_lost_count: i32,
+
+  stats: ReaderStats,
+  trace: EndpointTrace,
 
   pub(crate) timed_event_timer: Timer<TimedEvent>,
+  // The pending HoldBackRelease, if any. There is at most one.
+  hold_back_release: Option<Timeout>,
+  // The topic cache asks for a NotificationFlush here, when it starts holding
+  // back a notification. There is at most one NotificationFlush pending.
+  pub(crate) notification_flush_requests: mio_channel::Receiver<()>,
+  notification_flush: Option<Timeout>,
   pub(crate) data_reader_command_receiver: mio_channel::Receiver<ReaderCommand>,
-  data_reader_waker: Arc<Mutex<Option<Waker>>>,
-  poll_event_sender: mio_source::PollEventSender,
 
   participant_status_sender: StatusChannelSender<DomainParticipantStatusEvent>,
 
@@ -167,13 +222,34 @@ impl Reader {
       );
     }
 
+    // The topic cache wakes up our DataReader when there are new changes,
+    // whoever added them.
+    let (notification_flush_request_sender, notification_flush_requests) =
+      mio_channel::sync_channel(1);
+    i.topic_cache_handle.lock().unwrap().add_subscriber(
+      i.guid,
+      ChangeNotifier::new(
+        i.data_reader_waker,
+        i.poll_event_sender,
+        i.notification_sender,
+      )
+      .with_coalescing(
+        i.qos_policy.notification_coalescing(),
+        notification_flush_request_sender,
+      )
+      .with_readiness_queue(i.guid, i.readiness_queue),
+    );
+
     // If reader should be stateless, only BestEffort QoS is supported
     if i.like_stateless && i.qos_policy.is_reliable() {
       panic!("Attempted to create a stateless Reader with other than BestEffort reliability");
     }
 
+    let stats = ReaderStats::new(&i.topic_name, i.guid);
+    let trace = EndpointTrace::reader(&i.topic_name, i.guid);
+    local_endpoints::add_endpoint(i.guid, i.qos_policy.ignore_local());
+
     Self {
-      notification_sender: i.notification_sender,
       status_sender: i.status_sender,
       udp_sender,
       like_stateless: i.like_stateless,
@@ -184,23 +260,32 @@ impl Reader {
       topic_cache: i.topic_cache_handle,
       topic_name: i.topic_name,
       qos_policy: i.qos_policy,
+      qos_updated: false,
 
       #[cfg(test)]
       seqnum_instant_map: BTreeMap::new(),
       my_guid: i.guid,
 
       heartbeat_response_delay: StdDuration::new(0, 500_000_000), // 0,5sec
+      pending_heartbeat_responses: BTreeMap::new(),
       heartbeat_suppression_duration: StdDuration::new(0, 0),
       received_heartbeat_count: 0,
       fragment_assemblers: BTreeMap::new(),
+      fragments_collected: Instant::now(),
+      fragment_streams: i.fragment_streams,
+      changes_staged: false,
       matched_writers: BTreeMap::new(),
       writer_match_count_total: 0,
       requested_deadline_missed_count: 0,
       offered_incompatible_qos_count: 0,
+      sample_lost_count: 0,
+      stats,
+      trace,
       timed_event_timer,
+      hold_back_release: None,
+      notification_flush_requests,
+      notification_flush: None,
       data_reader_command_receiver: i.data_reader_command_receiver,
-      data_reader_waker: i.data_reader_waker,
-      poll_event_sender: i.poll_event_sender,
       participant_status_sender,
 
       security_plugins: i.security_plugins,
@@ -215,6 +300,21 @@ impl Reader {
     self.guid().entity_id.as_token()
   }
 
+  pub(crate) fn set_trace_sampling(&mut self, sampling: TraceSampling) {
+    self.trace.set_sampling(sampling);
+  }
+
+  // Makes the changes received since the previous call visible to the
+  // DataReader. The MessageReceiver calls this after each RTPS message, so
+  // that the DataReader sees all changes of a message at once, and never a
+  // change still being constructed.
+  pub fn commit_changes(&mut self) {
+    if self.changes_staged {
+      self.changes_staged = false;
+      self.acquire_the_topic_cache_guard().commit_changes();
+    }
+  }
+
   pub fn set_requested_deadline_check_timer(&mut self) {
     if let Some(deadline) = self.qos_policy.deadline {
       debug!(
@@ -233,6 +333,23 @@ impl Reader {
     }
   }
 
+  // A coalesced notification is flushed once max_delay is up, unless more
+  // changes flush it before that.
+  fn set_notification_flush_timer(&mut self) {
+    while self.notification_flush_requests.try_recv().is_ok() {
+      if self.notification_flush.is_some() {
+        continue; // One is enough
+      }
+      if let Some(coalescing) = self.qos_policy.notification_coalescing() {
+        self.notification_flush = Some(
+          self
+            .timed_event_timer
+            .set_timeout(coalescing.max_delay.to_std(), TimedEvent::NotificationFlush),
+        );
+      }
+    }
+  }
+
   pub fn send_status_change(&self, change: DataReaderStatus) {
     match self.status_sender.try_send(change) {
       Ok(()) => (), // expected result
@@ -254,6 +371,25 @@ impl Reader {
     }
   }
 
+  // Samples that will never be received were detected. See DDS Spec v1.4
+  // Section 2.2.4.1 "SAMPLE_LOST".
+  fn report_samples_lost(&mut self, writer_guid: GUID, lost_count: i64) {
+    if lost_count <= 0 {
+      return;
+    }
+    self.stats.samples_lost(lost_count as u64);
+    self.trace.samples_lost(writer_guid, lost_count);
+    let count_change = i32::try_from(lost_count).unwrap_or(i32::MAX);
+    self.sample_lost_count = self.sample_lost_count.saturating_add(count_change);
+    debug!(
+      "Lost {} samples from writer {:?} topic={:?}",
+      lost_count, writer_guid, self.topic_name
+    );
+    self.send_status_change(DataReaderStatus::SampleLost {
+      count: CountWithChange::new(self.sample_lost_count, count_change),
+    });
+  }
+
   fn send_participant_status(&self, event: DomainParticipantStatusEvent) {
     self
       .participant_status_sender
@@ -274,10 +410,11 @@ impl Reader {
     };
 
     let mut changes: Vec<DataReaderStatus> = vec![];
-    let now = Timestamp::now();
+    // Monotonic time, so that clock steps do not cause false misses.
+    let now = Instant::now();
     for writer_proxy in self.matched_writers.values_mut() {
-      if let Some(last_change) = writer_proxy.last_change_timestamp() {
-        let since_last = now.duration_since(last_change);
+      if let Some(last_change) = writer_proxy.last_change_instant() {
+        let since_last = Duration::from(now.saturating_duration_since(last_change));
         // if time singe last received message is greater than deadline increase status
         // and return notification.
         trace!(
@@ -308,18 +445,35 @@ impl Reader {
   } // fn
 
   pub fn handle_timed_event(&mut self) {
+    self.set_notification_flush_timer();
     while let Some(e) = self.timed_event_timer.poll() {
       match e {
         TimedEvent::DeadlineMissedCheck => {
           self.handle_requested_deadline_event();
           self.set_requested_deadline_check_timer(); // re-prime timer
         }
+        TimedEvent::HoldBackRelease => {
+          self.hold_back_release = None;
+          self
+            .acquire_the_topic_cache_guard()
+            .notify_subscriber(self.my_guid);
+        }
+        TimedEvent::HeartbeatResponse { writer_guid } => {
+          self.send_delayed_heartbeat_response(writer_guid);
+        }
+        TimedEvent::NotificationFlush => {
+          self.notification_flush = None;
+          self
+            .acquire_the_topic_cache_guard()
+            .flush_notification(self.my_guid);
+        }
       }
     }
   }
 
   pub fn process_command(&mut self) {
     trace!("process_command {:?}", self.my_guid);
+    let mut processed_count = 0;
     loop {
       use std::sync::mpsc::TryRecvError;
       match self.data_reader_command_receiver.try_recv() {
@@ -327,19 +481,62 @@ impl Reader {
           warn!("RESET_REQUESTED_DEADLINE_STATUS not implemented!");
           // TODO: This should be implemented.
         }
+        Ok(ReaderCommand::GetMatchedWriterStatistics { reply }) => {
+          let statistics = self
+            .matched_writers
+            .values()
+            .map(RtpsWriterProxy::statistics)
+            .collect();
+          // If the DataReader has stopped waiting, nobody cares.
+          let _ = reply.try_send(statistics);
+        }
+        Ok(ReaderCommand::UpdateQos { qos_policy }) => {
+          debug!(
+            "QoS updated topic={:?} new QoS={:?}",
+            self.topic_name, qos_policy
+          );
+          let deadline_check_running = self.qos_policy.deadline.is_some();
+          self.qos_policy = *qos_policy;
+          self.qos_updated = true;
+          // A running deadline check picks up the new period when it re-primes
+          // itself. Start one, if there was no deadline before.
+          if !deadline_check_running {
+            self.set_requested_deadline_check_timer();
+          }
+        }
+        Ok(ReaderCommand::WakeUpAfter { delay }) => {
+          // The DataReader asks again for anything later than this, once
+          // woken up.
+          if let Some(timeout) = self.hold_back_release.take() {
+            self.timed_event_timer.cancel_timeout(&timeout);
+          }
+          self.hold_back_release = Some(
+            self
+              .timed_event_timer
+              .set_timeout(delay, TimedEvent::HoldBackRelease),
+          );
+        }
         // Disconnected is normal when terminating
         Err(TryRecvError::Disconnected) => {
           trace!("DataReader disconnected");
           break;
         }
         Err(TryRecvError::Empty) => {
-          warn!("There was no command. Spurious command event??");
+          if processed_count == 0 {
+            warn!("There was no command. Spurious command event??");
+          }
           break;
         }
       }
+      processed_count += 1;
     }
   }
 
+  // Has the QoS been updated since the last call?
+  pub fn take_qos_updated(&mut self) -> bool {
+    std::mem::take(&mut self.qos_updated)
+  }
+
   fn handle_requested_deadline_event(&mut self) {
     debug!("handle_requested_deadline_event");
     for missed_deadline in self.calculate_if_requested_deadline_is_missed() {
@@ -374,7 +571,7 @@ impl Reader {
   }
 
   // updates or adds a new writer proxy, doesn't touch changes
-  pub fn update_writer_proxy(&mut self, proxy: RtpsWriterProxy, offered_qos: &QosPolicies) {
+  pub fn update_writer_proxy(&mut self, mut proxy: RtpsWriterProxy, offered_qos: &QosPolicies) {
     if self.like_stateless {
       debug!(
         "Attempted to update writer proxy for stateless reader. Ignoring. topic={:?}",
@@ -386,9 +583,17 @@ impl Reader {
     debug!("update_writer_proxy topic={:?}", self.topic_name);
     let writer = proxy.remote_writer_guid;
 
+    if local_endpoints::match_ignored(self.my_guid, writer) {
+      debug!("update_writer_proxy - ignoring local writer {writer:?}");
+      self.remove_writer_proxy(writer);
+      return;
+    }
+
     match offered_qos.compliance_failure_wrt(&self.qos_policy) {
       None => {
         // success, update or insert
+        proxy.reliability = self.effective_reliability(writer, offered_qos);
+        let reliability = proxy.reliability;
         let count_change = self.matched_writer_update(proxy);
         if count_change > 0 {
           self.writer_match_count_total += count_change;
@@ -406,6 +611,12 @@ impl Reader {
             "Matched new remote writer on topic={:?} writer={:?}",
             self.topic_name, writer
           );
+          if reliability != self.reliability {
+            info!(
+              "Writer {:?} is not Reliable. Receiving from it as BestEffort. topic={:?}",
+              writer, self.topic_name
+            );
+          }
         }
       }
       Some(bad_policy_id) => {
@@ -424,6 +635,9 @@ impl Reader {
           requested_qos: Box::new(self.qos_policy.clone()),
           offered_qos: Box::new(offered_qos.clone()),
         });
+        // A QoS change, either ours or the writer's, may have made a matched
+        // writer incompatible.
+        self.remove_writer_proxy(writer);
 
         warn!("update_writer_proxy - QoS mismatch {:?}", bad_policy_id);
         info!(
@@ -434,20 +648,41 @@ impl Reader {
     }
   }
 
+  // A Reliable Reader may match a Writer that does not offer Reliable, e.g.
+  // one with no Reliability policy. Such a Writer does not send HEARTBEATs or
+  // repair lost changes, so reliable reception from it would only stall. If
+  // it has sent HEARTBEATs after all, it stays Reliable when re-announced.
+  fn effective_reliability(&self, writer: GUID, offered_qos: &QosPolicies) -> policy::Reliability {
+    let upgraded = self
+      .matched_writer(writer)
+      .is_some_and(RtpsWriterProxy::is_reliable);
+    if offered_qos.is_reliable() || upgraded {
+      self.reliability
+    } else {
+      policy::Reliability::BestEffort
+    }
+  }
+
   // return value counts how many new proxies were added
   fn matched_writer_update(&mut self, proxy: RtpsWriterProxy) -> i32 {
     if let Some(op) = self.matched_writer_mut(proxy.remote_writer_guid) {
       op.update_contents(proxy);
       0
     } else {
+      self.trace.matched(proxy.remote_writer_guid);
       self.matched_writers.insert(proxy.remote_writer_guid, proxy);
+      self.stats.set_matched_writers(self.matched_writers.len());
       1
     }
   }
 
   pub fn remove_writer_proxy(&mut self, writer_guid: GUID) {
-    if self.matched_writers.contains_key(&writer_guid) {
-      self.matched_writers.remove(&writer_guid);
+    if let Some(fragment_assembler) = self.fragment_assemblers.remove(&writer_guid) {
+      fragment_assembler.abandon("The DataWriter was unmatched");
+    }
+    if self.matched_writers.remove(&writer_guid).is_some() {
+      self.stats.set_matched_writers(self.matched_writers.len());
+      self.trace.unmatched(writer_guid);
       #[cfg(feature = "security")]
       if let Some(security_plugins_handle) = &self.security_plugins {
         security_plugins_handle
@@ -514,6 +749,33 @@ impl Reader {
     self.matched_writers.get_mut(&remote_writer_guid)
   }
 
+  // Adds the inline QoS parameters of a DATA or DATA_FRAG that are not
+  // interpreted here: application-level ones, and RustDDS specific ones. The
+  // latter mean something else when sent by other implementations, so they
+  // are dropped.
+  fn with_inline_qos_parameters(
+    mut write_options_b: WriteOptionsBuilder,
+    inline_qos: Option<&ParameterList>,
+    mr_state: &MessageReceiverState,
+  ) -> WriteOptionsBuilder {
+    let Some(inline_qos) = inline_qos else {
+      return write_options_b;
+    };
+    for parameter in InlineQos::custom_parameters(inline_qos) {
+      write_options_b = write_options_b.inline_qos_parameter(parameter);
+    }
+    if compatibility::has_feature(
+      mr_state.source_vendor_id,
+      mr_state.source_version,
+      Feature::RustDdsParameters,
+    ) {
+      for parameter in InlineQos::rustdds_parameters(inline_qos) {
+        write_options_b = write_options_b.rustdds_parameter(parameter);
+      }
+    }
+    write_options_b
+  }
+
   // handles regular data message and updates history cache
   pub fn handle_data_msg(
     &mut self,
@@ -522,7 +784,8 @@ impl Reader {
     mr_state: &MessageReceiverState,
   ) {
     // trace!("handle_data_msg entry");
-    let receive_timestamp = Timestamp::now();
+    let receive_timestamp = Timestamp::now_increasing();
+    let reception_instant = Instant::now();
 
     // parse write_options out of the message
     let mut write_options_b = WriteOptionsBuilder::new();
@@ -543,6 +806,8 @@ impl Reader {
     {
       write_options_b = write_options_b.related_sample_identity(related_sample_identity);
     }
+    write_options_b =
+      Self::with_inline_qos_parameters(write_options_b, data.inline_qos.as_ref(), mr_state);
 
     let writer_guid = GUID::new_with_prefix_and_id(mr_state.source_guid_prefix, data.writer_id);
     let writer_seq_num = data.writer_sn; // for borrow checker
@@ -551,6 +816,7 @@ impl Reader {
       Ok(dds_data) => self.process_received_data(
         dds_data,
         receive_timestamp,
+        reception_instant,
         write_options_b.build(),
         writer_guid,
         writer_seq_num,
@@ -567,7 +833,8 @@ impl Reader {
   ) {
     let writer_guid = GUID::new_with_prefix_and_id(mr_state.source_guid_prefix, datafrag.writer_id);
     let seq_num = datafrag.writer_sn;
-    let receive_timestamp = Timestamp::now();
+    let receive_timestamp = Timestamp::now_increasing();
+    let reception_instant = Instant::now();
     //trace!("DATAFRAG received topic={:?}", self.topic_name);
 
     // check if this submessage is expired already
@@ -609,6 +876,14 @@ impl Reader {
     {
       write_options_b = write_options_b.related_sample_identity(related_sample_identity);
     }
+    write_options_b =
+      Self::with_inline_qos_parameters(write_options_b, datafrag.inline_qos.as_ref(), mr_state);
+
+    let write_options = write_options_b.build();
+    if self.should_stream(writer_guid, datafrag, datafrag_flags, &write_options) {
+      self.handle_streamed_datafrag(datafrag, &write_options, writer_guid, reception_instant);
+      return;
+    }
 
     // Feed to fragment assembler ...
     let writer_seq_num = datafrag.writer_sn; // for borrow checker
@@ -623,7 +898,8 @@ impl Reader {
       self.process_received_data(
         dds_data,
         receive_timestamp,
-        write_options_b.build(),
+        reception_instant,
+        write_options,
         writer_guid,
         writer_seq_num,
       );
@@ -632,6 +908,94 @@ impl Reader {
     }
   }
 
+  // Should the sample be passed to the DataReader as a FragmentStream instead
+  // of assembling it? See SimpleDataReader::set_fragment_streaming().
+  fn should_stream(
+    &self,
+    writer_guid: GUID,
+    datafrag: &DataFrag,
+    datafrag_flags: BitFlags<DATAFRAG_Flags>,
+    write_options: &WriteOptions,
+  ) -> bool {
+    let fragment_assembler = self.fragment_assemblers.get(&writer_guid);
+    if fragment_assembler.is_some_and(|fa| fa.is_streaming(datafrag.writer_sn)) {
+      return true;
+    }
+    // Disposes are not data, and compressed payloads must be decompressed as
+    // a whole.
+    self.fragment_streams.accepts(datafrag.data_size as usize)
+      && !datafrag_flags.contains(DATAFRAG_Flags::Key)
+      && compression::parameter(write_options.rustdds_parameters()).is_none()
+      // Assembly may have started before streaming was enabled
+      && !fragment_assembler.is_some_and(|fa| fa.is_assembling(datafrag.writer_sn))
+  }
+
+  fn handle_streamed_datafrag(
+    &mut self,
+    datafrag: &DataFrag,
+    write_options: &WriteOptions,
+    writer_guid: GUID,
+    reception_instant: Instant,
+  ) {
+    let writer_sn = datafrag.writer_sn;
+    if let Some(writer_proxy) = self.matched_writer(writer_guid) {
+      if writer_proxy.should_ignore_change(writer_sn) {
+        // Do not start another stream for a sample already received
+        debug!("handle_streamed_datafrag already have this seq={writer_sn:?}");
+        return;
+      }
+    }
+    let fragment_streams = self.fragment_streams.clone();
+    let mut started = false;
+    let completed_stream = self
+      .fragment_assembler_mutable(writer_guid, datafrag.fragment_size)
+      .new_streamed_datafrag(datafrag, || {
+        started = true;
+        fragment_streams.start(writer_guid, writer_sn, datafrag.data_size as usize)
+      });
+    if started {
+      // Let the DataReader know that there is a stream to take
+      debug!(
+        "Streaming {writer_sn:?} from {writer_guid:?} topic={:?}",
+        self.topic_name
+      );
+      self
+        .acquire_the_topic_cache_guard()
+        .notify_subscriber(self.my_guid);
+    }
+
+    let Some(stream) = completed_stream else {
+      self.garbage_collect_fragments();
+      return;
+    };
+    if !checksum::is_intact_crc32c(write_options.rustdds_parameters(), stream.crc32c()) {
+      warn!(
+        "Streamed {:?} from {:?} does not match checksum. topic={:?}",
+        writer_sn, writer_guid, self.topic_name
+      );
+      if let Some(writer_proxy) = self.matched_writer_mut(writer_guid) {
+        writer_proxy.corrupted_received();
+      }
+      stream.fail("Payload does not match checksum".to_string());
+      return;
+    }
+    stream.finish();
+    if self.accept_change(writer_guid, writer_sn, reception_instant) {
+      // The sample went to the stream, so the topic cache only learns that it
+      // was received.
+      self.stats.sample_received();
+      self.trace.sample_received(writer_guid, writer_sn);
+      if !self.like_stateless {
+        if let Some(writer_proxy) = self.matched_writer(writer_guid) {
+          let ackable_before = writer_proxy.all_ackable_before();
+          self
+            .acquire_the_topic_cache_guard()
+            .mark_reliably_received_before(writer_guid, ackable_before);
+        }
+      }
+    }
+  }
+
   fn fragment_assembler_mutable(
     &mut self,
     writer_guid: GUID,
@@ -643,13 +1007,21 @@ impl Reader {
       .or_insert_with(|| FragmentAssembler::new(frag_size))
   }
 
+  // Discards the partially received samples that no longer receive
+  // fragments. On most calls, does nothing.
   fn garbage_collect_fragments(&mut self) {
-    // TODO: On most calls, do nothing.
-    //
-    // If GC time/packet limit has been exceeded, iterate through
-    // fragment assemblers and discard those assembly buffers whose
-    // creation / modification timestamps look like it is no longer receiving
-    // data and can therefore be discarded.
+    let now = Instant::now();
+    if now < self.fragments_collected + FRAGMENT_GC_INTERVAL {
+      return;
+    }
+    self.fragments_collected = now;
+    let Some(stale_before) = now.checked_sub(FRAGMENT_TIMEOUT) else {
+      return;
+    };
+    self.fragment_assemblers.retain(|_, fa| {
+      fa.garbage_collect(stale_before);
+      !fa.is_empty()
+    });
   }
 
   fn missing_frags_for(
@@ -667,15 +1039,17 @@ impl Reader {
     self
       .fragment_assemblers
       .get(&writer_guid)
-      .map_or(false, |fa| fa.is_partially_received(seq))
+      .is_some_and(|fa| fa.is_partially_received(seq))
   }
 
   // common parts of processing DATA or a completed DATAFRAG (when all frags are
   // received)
+  #[allow(clippy::too_many_arguments)]
   fn process_received_data(
     &mut self,
     dds_data: DDSData,
     receive_timestamp: Timestamp,
+    reception_instant: Instant,
     write_options: WriteOptions,
     writer_guid: GUID,
     writer_sn: SequenceNumber,
@@ -688,23 +1062,64 @@ impl Reader {
       self.reliability,
       self.like_stateless,
     );
+    if !self.payload_is_intact(&dds_data, &write_options, writer_guid, writer_sn)
+      || !self.accept_change(writer_guid, writer_sn, reception_instant)
+    {
+      return;
+    }
+
+    self.make_cache_change(
+      dds_data,
+      receive_timestamp,
+      reception_instant,
+      write_options,
+      writer_guid,
+      writer_sn,
+    );
+
+    // Add to own track-keeping data structure
+    #[cfg(test)]
+    self.seqnum_instant_map.insert(writer_sn, receive_timestamp);
+  }
+
+  // Writer proxy bookkeeping of a received change. Returns false, if the
+  // change is to be ignored, e.g. as a duplicate.
+  fn accept_change(
+    &mut self,
+    writer_guid: GUID,
+    writer_sn: SequenceNumber,
+    reception_instant: Instant,
+  ) -> bool {
+    let mut lost_count = 0;
     if !self.like_stateless {
       let my_entity_id = self.my_guid.entity_id; // to please borrow checker
+      let is_best_effort = self.reliability == policy::Reliability::BestEffort;
       if let Some(writer_proxy) = self.matched_writer_mut(writer_guid) {
         if writer_proxy.should_ignore_change(writer_sn) {
           // change already present
           debug!("handle_data_msg already have this seq={:?}", writer_sn);
+          writer_proxy.duplicate_received();
           if my_entity_id == EntityId::SPDP_BUILTIN_PARTICIPANT_READER {
             debug!("Accepting duplicate message to participant reader.");
             // This is an attempted workaround to eProsima FastRTPS not
             // incrementing sequence numbers. (eProsima shapes demo 2.1.0 from
             // 2021)
           } else {
-            return;
+            return false;
+          }
+        }
+        // BestEffort reception does not wait for missing samples, so whatever
+        // we skip over is lost.
+        if !writer_proxy.is_reliable() {
+          lost_count = writer_proxy.skipped_before(writer_sn);
+          // A Reliable Reader delivers changes in sequence number order, so it
+          // must give up on the skipped ones, or they would block the rest.
+          if !is_best_effort {
+            writer_proxy.irrelevant_changes_up_to(writer_sn);
           }
         }
         // Add the change and get the instant
-        writer_proxy.received_changes_add(writer_sn, receive_timestamp);
+        writer_proxy.received_changes_add(writer_sn, reception_instant);
       } else {
         // no writer proxy found
         debug!(
@@ -715,26 +1130,44 @@ impl Reader {
         // We just ignore the data in such a case
         // ... unless it is Discovery traffic.
         if writer_guid.entity_id.entity_kind.is_user_defined() {
-          return;
+          return false;
         }
       }
     } else {
       // stateless reader: nothing to do before making cache change
     }
+    self.report_samples_lost(writer_guid, lost_count);
+    true
+  }
 
-    self.make_cache_change(
-      dds_data,
-      receive_timestamp,
-      write_options,
-      writer_guid,
-      writer_sn,
+  // Checks the payload against the checksum sent by the writer, if any. See
+  // the PayloadChecksum QoS policy. A corrupted sample is not recorded as
+  // received, so a reliable reader will request it again.
+  fn payload_is_intact(
+    &mut self,
+    dds_data: &DDSData,
+    write_options: &WriteOptions,
+    writer_guid: GUID,
+    writer_sn: SequenceNumber,
+  ) -> bool {
+    let payload = match dds_data {
+      DDSData::Data {
+        serialized_payload, ..
+      } => &serialized_payload.value,
+      DDSData::DisposeByKey { key, .. } => &key.value,
+      DDSData::DisposeByKeyHash { .. } => return true,
+    };
+    if checksum::is_intact(write_options.rustdds_parameters(), payload) {
+      return true;
+    }
+    warn!(
+      "Discarding {:?} from {:?}: payload does not match checksum. topic={:?}",
+      writer_sn, writer_guid, self.topic_name
     );
-
-    // Add to own track-keeping data structure
-    #[cfg(test)]
-    self.seqnum_instant_map.insert(writer_sn, receive_timestamp);
-
-    self.notify_cache_change();
+    if let Some(writer_proxy) = self.matched_writer_mut(writer_guid) {
+      writer_proxy.corrupted_received();
+    }
+    false
   }
 
   fn data_to_dds_data(
@@ -744,6 +1177,14 @@ impl Reader {
   ) -> Result<DDSData, String> {
     let representation_identifier = DATA_Flags::cdr_representation_identifier(data_flags);
 
+    // Key hash from inline QoS, if the writer sent one
+    let inline_key_hash = data.inline_qos.as_ref().and_then(|inline_qos_parameters| {
+      InlineQos::key_hash(inline_qos_parameters).unwrap_or_else(|e| {
+        error!("Deserializing key_hash: {:?}", &e);
+        None
+      })
+    });
+
     match (
       data.serialized_payload,
       data_flags.contains(DATA_Flags::Data),
@@ -751,29 +1192,30 @@ impl Reader {
     ) {
       (Some(serialized_payload), true, false) => {
         // data
-        Ok(DDSData::new(
-          SerializedPayload::from_bytes(&serialized_payload).map_err(|e| format!("{e:?}"))?,
-        ))
+        Ok(
+          DDSData::new(
+            SerializedPayload::from_bytes(&serialized_payload).map_err(|e| format!("{e:?}"))?,
+          )
+          .with_key_hash(inline_key_hash),
+        )
       }
 
       (Some(serialized_payload), false, true) => {
         // key
-        Ok(DDSData::new_disposed_by_key(
-          Self::deduce_change_kind(&data.inline_qos, false, representation_identifier),
-          SerializedPayload::from_bytes(&serialized_payload).map_err(|e| format!("{e:?}"))?,
-        ))
+        Ok(
+          DDSData::new_disposed_by_key(
+            Self::deduce_change_kind(&data.inline_qos, false, representation_identifier),
+            SerializedPayload::from_bytes(&serialized_payload).map_err(|e| format!("{e:?}"))?,
+          )
+          .with_key_hash(inline_key_hash),
+        )
       }
 
       (None, false, false) => {
         // no data, no key. Maybe there is inline QoS?
         // At least we should find key hash, or we do not know WTF the writer is talking
         // about
-        let key_hash = if let Some(h) = data.inline_qos.as_ref().and_then(|inline_qos_parameters| {
-          InlineQos::key_hash(inline_qos_parameters).unwrap_or_else(|e| {
-            error!("Deserializing key_hash: {:?}", &e);
-            None
-          })
-        }) {
+        let key_hash = if let Some(h) = inline_key_hash {
           Ok(h)
         } else {
           info!("Received DATA that has no payload and no key_hash inline QoS - discarding");
@@ -844,6 +1286,8 @@ impl Reader {
   ) -> bool {
     let writer_guid =
       GUID::new_with_prefix_and_id(mr_state.source_guid_prefix, heartbeat.writer_id);
+    // Fragments may stop arriving, even though the writer is still alive
+    self.garbage_collect_fragments();
 
     if self.reliability == policy::Reliability::BestEffort || self.like_stateless {
       debug!(
@@ -859,12 +1303,26 @@ impl Reader {
       return false;
     }
 
-    if !self.matched_writers.contains_key(&writer_guid) {
-      debug!(
-        "HEARTBEAT from {:?}, but no writer proxy available. topic={:?} reader={:?}",
-        writer_guid, self.topic_name, self.my_guid
-      );
-      return false;
+    match self.matched_writers.get_mut(&writer_guid) {
+      None => {
+        debug!(
+          "HEARTBEAT from {:?}, but no writer proxy available. topic={:?} reader={:?}",
+          writer_guid, self.topic_name, self.my_guid
+        );
+        return false;
+      }
+      // A Writer that did not offer Reliable, but sends HEARTBEATs, is
+      // Reliable after all. Spec default for a DataWriter is Reliable, so some
+      // implementations may omit the policy from Discovery.
+      Some(writer_proxy) if !writer_proxy.is_reliable() => {
+        info!(
+          "HEARTBEAT from {:?}, which did not offer Reliable. Receiving from it as Reliable. \
+           topic={:?}",
+          writer_guid, self.topic_name
+        );
+        writer_proxy.reliability = self.reliability;
+      }
+      Some(_) => (),
     }
     // sanity check
     if heartbeat.first_sn < SequenceNumber::default() {
@@ -889,147 +1347,241 @@ impl Reader {
           // This heartbeat was already seen an processed.
           return false;
         }
-        writer_proxy.received_heartbeat_count = heartbeat.count;
+        this.stats.heartbeat_received();
+        this
+          .trace
+          .heartbeat_received(writer_guid, heartbeat.first_sn, heartbeat.last_sn);
+        let contact_established = writer_proxy.contact_established();
+        writer_proxy.heartbeat_received(heartbeat.count, heartbeat.last_sn, Timestamp::now());
 
         // remove changes until first_sn.
-        writer_proxy.irrelevant_changes_up_to(heartbeat.first_sn);
-        let mut tc = this.acquire_the_topic_cache_guard();
-        tc.mark_reliably_received_before(writer_guid, writer_proxy.all_ackable_before());
-
-        // let received_before = writer_proxy.all_ackable_before();
-        let reader_id = this.entity_id();
-
-        // See if ACKNACK is needed, and generate one.
-        let missing_seqnums = writer_proxy.missing_seqnums(heartbeat.first_sn, heartbeat.last_sn);
-
-        // Interpretation of final flag in RTPS spec
-        // 8.4.2.3.1 Readers must respond eventually after receiving a HEARTBEAT with
-        // final flag not set
-        //
-        // Upon receiving a HEARTBEAT Message with final flag not set, the Reader must
-        // respond with an ACKNACK Message. The ACKNACK Message may acknowledge
-        // having received all the data samples or may indicate that some data
-        // samples are missing. The response may be delayed to avoid message storms.
-
-        if !missing_seqnums.is_empty() || !final_flag_set {
-          let mut partially_received = Vec::new();
-          // report of what we have.
-          // We claim to have received all SNs before "base" and produce a set of missing
-          // sequence numbers that are >= base.
-          let reader_sn_state = match missing_seqnums.get(0) {
-            Some(&first_missing) => {
-              // Here we assume missing_seqnums are returned in order.
-              // Limit the set to maximum that can be sent in acknack submessage.
-
-              SequenceNumberSet::from_base_and_set(
-                first_missing,
-                &missing_seqnums
-                  .iter()
-                  .copied()
-                  .take_while(|sn| sn < &(first_missing + SequenceNumber::new(256)))
-                  .filter(|sn| {
-                    if this.is_frag_partially_received(writer_guid, *sn) {
-                      partially_received.push(*sn);
-                      false
-                    } else {
-                      true
-                    }
-                  })
-                  .collect(),
-              )
-            }
-
-            // Nothing missing. Report that we have all we have.
-            None => SequenceNumberSet::new_empty(writer_proxy.all_ackable_before()),
-          };
-
-          let response_ack_nack = AckNack {
-            reader_id,
-            writer_id: heartbeat.writer_id,
-            reader_sn_state,
-            count: writer_proxy.next_ack_nack_sequence_number(),
-          };
-
-          // Sanity check
-          //
-          // Wrong. This sanity check is invalid. The condition
-          // ack_base > heartbeat.last_sn + 1
-          // May be legitimately true, if there are some changes available, and a GAP
-          // after that. E.g. HEARTBEAT 1..8 and GAP 9..10. Then acknack_base == 11
-          // and 11 > 8 + 1.
-          //
-          //
-          // if response_ack_nack.reader_sn_state.base() > heartbeat.last_sn +
-          // SequenceNumber::new(1) {   error!(
-          //     "OOPS! AckNack sanity check tripped: HEARTBEAT = {:?} ACKNACK = {:?}
-          // missing_seqnums = {:?} all_ackable_before = {:?} writer={:?}",
-          //     &heartbeat, &response_ack_nack, missing_seqnums,
-          // writer_proxy.all_ackable_before(), writer_guid,   );
-          // }
-
-          // The acknack can be sent now or later. The rest of the RTPS message
-          // needs to be constructed. p. 48
-          let acknack_flags = BitFlags::<ACKNACK_Flags>::from_flag(ACKNACK_Flags::Endianness)
-            | BitFlags::<ACKNACK_Flags>::from_flag(ACKNACK_Flags::Final);
-
-          let nackfrag_flags = BitFlags::<NACKFRAG_Flags>::from_flag(NACKFRAG_Flags::Endianness);
-
-          // send NackFrags, if any
-          let mut nackfrags = Vec::new();
-          for sn in partially_received {
-            let count = writer_proxy.next_ack_nack_sequence_number();
-            let mut missing_frags = this.missing_frags_for(writer_guid, sn);
-            let first_missing = missing_frags.next();
-            if let Some(first) = first_missing {
-              let missing_frags_set = iter::once(first).chain(missing_frags).collect(); // "undo" the .next() above
-              let nf = NackFrag {
-                reader_id,
-                writer_id: writer_proxy.remote_writer_guid.entity_id,
-                writer_sn: sn,
-                fragment_number_state: FragmentNumberSet::from_base_and_set(
-                  first,
-                  &missing_frags_set,
-                ),
-                count,
-              };
-              nackfrags.push(nf);
-            } else {
-              error!("The dog ate my missing fragments.");
-              // Really, this should not happen, as we are above checking
-              // that this SN is really partially (and not fully) received.
-            }
-          }
-
-          if !nackfrags.is_empty() {
-            this.send_nackfrags_to(
-              nackfrag_flags,
-              nackfrags,
-              InfoDestination {
-                guid_prefix: mr_state.source_guid_prefix,
-              },
-              &reply_locators,
-              writer_guid,
-            );
-          }
-
-          this.send_acknack_to(
-            acknack_flags,
-            response_ack_nack,
-            InfoDestination {
-              guid_prefix: mr_state.source_guid_prefix,
-            },
-            &reply_locators,
+        // Whatever we were still missing before first_sn is now lost, unless this
+        // is the first we hear from the writer. In that case the samples were
+        // published before we matched.
+        let lost_count = writer_proxy.irrelevant_changes_up_to(heartbeat.first_sn);
+        if contact_established {
+          this.report_samples_lost(writer_guid, lost_count);
+        }
+        this
+          .acquire_the_topic_cache_guard()
+          .mark_reliably_received_before(writer_guid, writer_proxy.all_ackable_before());
+
+        // A multicast HEARTBEAT reaches many Readers at once. If all of them
+        // NACKed right away, the Writer would get a burst of ACKNACKs for the
+        // same changes. Instead, wait a random time, and skip the NACK if the
+        // missing changes have been repaired meanwhile, e.g. because another
+        // Reader asked first.
+        if mr_state.received_via_multicast
+          && !writer_proxy
+            .missing_seqnums(heartbeat.first_sn, heartbeat.last_sn)
+            .is_empty()
+        {
+          this.delay_heartbeat_response(
             writer_guid,
+            PendingHeartbeatResponse {
+              first_sn: heartbeat.first_sn,
+              last_sn: heartbeat.last_sn,
+              final_flag_set,
+              reply_locators,
+            },
           );
-
-          return true;
+          return false;
         }
 
-        false
+        this.respond_to_heartbeat(
+          writer_proxy,
+          heartbeat.first_sn,
+          heartbeat.last_sn,
+          final_flag_set,
+          &reply_locators,
+        )
       }) // worker fn
       .unwrap_or(false) // default false: no writer_proxy -> no acknack
   } // fn
 
+  // Schedules a response to a multicast HEARTBEAT after a random delay. If a
+  // response to the same writer is already waiting, it is updated to answer
+  // the latest HEARTBEAT instead.
+  fn delay_heartbeat_response(&mut self, writer_guid: GUID, pending: PendingHeartbeatResponse) {
+    if self
+      .pending_heartbeat_responses
+      .insert(writer_guid, pending)
+      .is_none()
+    {
+      let delay = rand::thread_rng().gen_range(StdDuration::ZERO..=self.heartbeat_response_delay);
+      self
+        .timed_event_timer
+        .set_timeout(delay, TimedEvent::HeartbeatResponse { writer_guid });
+    }
+  }
+
+  fn send_delayed_heartbeat_response(&mut self, writer_guid: GUID) {
+    let Some(pending) = self.pending_heartbeat_responses.remove(&writer_guid) else {
+      return;
+    };
+    if !self.matched_writers.contains_key(&writer_guid) {
+      return; // writer is gone
+    }
+    self.with_mutable_writer_proxy(writer_guid, |this, writer_proxy| {
+      if writer_proxy
+        .missing_seqnums(pending.first_sn, pending.last_sn)
+        .is_empty()
+      {
+        debug!(
+          "Suppressing NACK to {:?}, missing changes were repaired meanwhile. topic={:?}",
+          writer_guid, this.topic_name
+        );
+        writer_proxy.nack_suppressed();
+      }
+      this.respond_to_heartbeat(
+        writer_proxy,
+        pending.first_sn,
+        pending.last_sn,
+        pending.final_flag_set,
+        &pending.reply_locators,
+      );
+    });
+  }
+
+  // Sends ACKNACK (and NACK_FRAGs) in response to a HEARTBEAT, if needed.
+  // Returns if an ACKNACK was sent.
+  fn respond_to_heartbeat(
+    &mut self,
+    writer_proxy: &mut RtpsWriterProxy,
+    first_sn: SequenceNumber,
+    last_sn: SequenceNumber,
+    final_flag_set: bool,
+    reply_locators: &[Locator],
+  ) -> bool {
+    let writer_guid = writer_proxy.remote_writer_guid;
+    let reader_id = self.entity_id();
+
+    // See if ACKNACK is needed, and generate one.
+    let missing_seqnums = writer_proxy.missing_seqnums(first_sn, last_sn);
+
+    // Interpretation of final flag in RTPS spec
+    // 8.4.2.3.1 Readers must respond eventually after receiving a HEARTBEAT with
+    // final flag not set
+    //
+    // Upon receiving a HEARTBEAT Message with final flag not set, the Reader must
+    // respond with an ACKNACK Message. The ACKNACK Message may acknowledge
+    // having received all the data samples or may indicate that some data
+    // samples are missing. The response may be delayed to avoid message storms.
+
+    if !missing_seqnums.is_empty() || !final_flag_set {
+      let mut partially_received = Vec::new();
+      // report of what we have.
+      // We claim to have received all SNs before "base" and produce a set of missing
+      // sequence numbers that are >= base.
+      let reader_sn_state = match missing_seqnums.get(0) {
+        Some(&first_missing) => {
+          // Here we assume missing_seqnums are returned in order.
+          // Limit the set to maximum that can be sent in acknack submessage.
+
+          SequenceNumberSet::from_base_and_set(
+            first_missing,
+            &missing_seqnums
+              .iter()
+              .copied()
+              .take_while(|sn| sn < &(first_missing + SequenceNumber::new(256)))
+              .filter(|sn| {
+                if self.is_frag_partially_received(writer_guid, *sn) {
+                  partially_received.push(*sn);
+                  false
+                } else {
+                  true
+                }
+              })
+              .collect(),
+          )
+        }
+
+        // Nothing missing. Report that we have all we have.
+        None => SequenceNumberSet::new_empty(writer_proxy.all_ackable_before()),
+      };
+
+      let response_ack_nack = AckNack {
+        reader_id,
+        writer_id: writer_guid.entity_id,
+        reader_sn_state,
+        count: writer_proxy.next_ack_nack_sequence_number(),
+      };
+
+      // Sanity check
+      //
+      // Wrong. This sanity check is invalid. The condition
+      // ack_base > last_sn + 1
+      // May be legitimately true, if there are some changes available, and a GAP
+      // after that. E.g. HEARTBEAT 1..8 and GAP 9..10. Then acknack_base == 11
+      // and 11 > 8 + 1.
+      //
+      //
+      // if response_ack_nack.reader_sn_state.base() > last_sn +
+      // SequenceNumber::new(1) {   error!(
+      //     "OOPS! AckNack sanity check tripped: HEARTBEAT = {:?} ACKNACK = {:?}
+      // missing_seqnums = {:?} all_ackable_before = {:?} writer={:?}",
+      //     &heartbeat, &response_ack_nack, missing_seqnums,
+      // writer_proxy.all_ackable_before(), writer_guid,   );
+      // }
+
+      // The acknack can be sent now or later. The rest of the RTPS message
+      // needs to be constructed. p. 48
+      let acknack_flags = BitFlags::<ACKNACK_Flags>::from_flag(ACKNACK_Flags::Endianness)
+        | BitFlags::<ACKNACK_Flags>::from_flag(ACKNACK_Flags::Final);
+
+      let nackfrag_flags = BitFlags::<NACKFRAG_Flags>::from_flag(NACKFRAG_Flags::Endianness);
+
+      // send NackFrags, if any
+      let mut nackfrags = Vec::new();
+      for sn in partially_received {
+        let count = writer_proxy.next_ack_nack_sequence_number();
+        let mut missing_frags = self.missing_frags_for(writer_guid, sn);
+        let first_missing = missing_frags.next();
+        if let Some(first) = first_missing {
+          let missing_frags_set = iter::once(first).chain(missing_frags).collect(); // "undo" the .next() above
+          let nf = NackFrag {
+            reader_id,
+            writer_id: writer_proxy.remote_writer_guid.entity_id,
+            writer_sn: sn,
+            fragment_number_state: FragmentNumberSet::from_base_and_set(first, &missing_frags_set),
+            count,
+          };
+          nackfrags.push(nf);
+        } else {
+          error!("The dog ate my missing fragments.");
+          // Really, this should not happen, as we are above checking
+          // that this SN is really partially (and not fully) received.
+        }
+      }
+
+      if !nackfrags.is_empty() {
+        self.send_nackfrags_to(
+          nackfrag_flags,
+          nackfrags,
+          InfoDestination {
+            guid_prefix: writer_guid.prefix,
+          },
+          reply_locators,
+          writer_guid,
+        );
+      }
+
+      self.send_acknack_to(
+        acknack_flags,
+        response_ack_nack,
+        InfoDestination {
+          guid_prefix: writer_guid.prefix,
+        },
+        reply_locators,
+        writer_guid,
+      );
+
+      return true;
+    }
+
+    false
+  }
+
   pub fn handle_gap_msg(&mut self, gap: &Gap, mr_state: &MessageReceiverState) {
     // ATM all things related to groups is ignored. TODO?
 
@@ -1043,6 +1595,7 @@ impl Reader {
       return;
     }
     let all_ackable_before;
+    let lost_count;
     {
       let writer_proxy = if let Some(wp) = self.matched_writer_mut(writer_guid) {
         wp
@@ -1075,30 +1628,51 @@ impl Reader {
       }
       // TODO: check that maximum(gap_list) - minimum(gap_list) < 256 ?
 
+      let contact_established = writer_proxy.contact_established();
+
       // Irrelevant sequence numbers communicated in the Gap message are
       // composed of two groups:
       //   1. All sequence numbers in the range gapStart <= sequence_number <
       // gapList.base
-      writer_proxy.irrelevant_changes_range(gap.gap_start, gap.gap_list.base());
+      let mut newly_irrelevant =
+        writer_proxy.irrelevant_changes_range(gap.gap_start, gap.gap_list.base());
 
       //   2. All the sequence numbers that appear explicitly listed in the gapList.
       for seq_num in gap.gap_list.iter() {
-        writer_proxy.set_irrelevant_change(seq_num);
+        newly_irrelevant += writer_proxy.set_irrelevant_change(seq_num);
       }
       all_ackable_before = writer_proxy.all_ackable_before();
+
+      // GAP as the first message from a Writer only tells us what was published
+      // before we matched. Otherwise, the samples are lost.
+      //
+      // TODO: If the the GAP message contained filteredCount (RTPS spec v2.5
+      // Table 8.43), then some of the not-available messages should not be
+      // treated as "lost" but "filtered".
+      lost_count = if contact_established {
+        newly_irrelevant
+      } else {
+        0
+      };
+    }
+    self.report_samples_lost(writer_guid, lost_count);
+
+    // The writer no longer has these, so their remaining fragments are not
+    // coming either
+    if let Some(fragment_assembler) = self.fragment_assemblers.get_mut(&writer_guid) {
+      let gap_list_base = gap.gap_list.base();
+      fragment_assembler.discard(
+        |sn| (gap.gap_start <= sn && sn < gap_list_base) || gap.gap_list.iter().any(|g| g == sn),
+        "The DataWriter no longer has the sample",
+      );
+      if fragment_assembler.is_empty() {
+        self.fragment_assemblers.remove(&writer_guid);
+      }
     }
 
     // Get the topic cache
     let mut tc = self.acquire_the_topic_cache_guard();
     tc.mark_reliably_received_before(writer_guid, all_ackable_before);
-
-    // TODO: If receiving GAP actually moved the reliably received mark forward
-    // in the Topic Cache, then we should generate a SAMPLE_LOST status event
-    // from our Datareader (DDS Spec Section 2.2.4.1)
-    //
-    // If the the GAP message contained filteredCount (RTPS spec v2.5 Table
-    // 8.43), then some of the not-available messages should not be treated
-    // as "lost" but "filtered".
   }
 
   pub fn handle_heartbeatfrag_msg(
@@ -1145,16 +1719,23 @@ impl Reader {
     &mut self,
     data: DDSData,
     receive_timestamp: Timestamp,
+    reception_instant: Instant,
     write_options: WriteOptions,
     writer_guid: GUID,
     writer_sn: SequenceNumber,
   ) {
-    let cache_change = CacheChange::new(writer_guid, writer_sn, write_options, data);
+    let mut cache_change = CacheChange::new(writer_guid, writer_sn, write_options, data);
+    cache_change.reception_instant = reception_instant;
+
+    // Published in commit_changes()
+    self.changes_staged = true;
 
     // Get the topic cache
     let mut tc = self.acquire_the_topic_cache_guard();
 
-    tc.add_change(&receive_timestamp, cache_change);
+    tc.stage_change(&receive_timestamp, cache_change);
+    self.stats.sample_received();
+    self.trace.sample_received(writer_guid, writer_sn);
     // Mark seqnums as received if not behaving statelessly
     if !self.like_stateless {
       self.matched_writer(writer_guid).map(|wp| {
@@ -1163,35 +1744,6 @@ impl Reader {
     }
   }
 
-  // notifies DataReaders (or any listeners that history cache has changed for
-  // this reader) likely use of mio channel
-  pub fn notify_cache_change(&mut self) {
-    // async notify mechanism
-    self
-      .data_reader_waker
-      .lock()
-      .unwrap() // TODO: unwrap
-      .take() // Take to nullify the reference
-      .map(|w| w.wake_by_ref()); // If Some, call wake_by_ref
-
-    // mio-0.8 notify
-    self.poll_event_sender.send();
-
-    // mio-0.6 notify
-    match self.notification_sender.try_send(()) {
-      Ok(()) => (),
-      Err(mio_channel::TrySendError::Full(_)) => (),
-      // This is harmless. There is a notification in already.
-      Err(mio_channel::TrySendError::Disconnected(_)) => {
-        // If we get here, our DataReader has died. The Reader should now
-        // dispose itself. TODO: Implement Reader disposal.
-      }
-      Err(mio_channel::TrySendError::Io(_)) => {
-        // TODO: What does this mean? Can we ever get here?
-      }
-    }
-  }
-
   #[cfg(not(feature = "security"))]
   fn encode_and_send(
     &self,
@@ -1382,6 +1934,19 @@ impl Reader {
   }
 } // impl Reader
 
+impl Drop for Reader {
+  fn drop(&mut self) {
+    local_endpoints::remove_endpoint(self.my_guid);
+
+    // Stop waking up the DataReader
+    self
+      .topic_cache
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .remove_subscriber(self.my_guid);
+  }
+}
+
 impl HasQoSPolicy for Reader {
   fn qos(&self) -> QosPolicies {
     self.qos_policy.clone()
@@ -1397,7 +1962,7 @@ impl RTPSEntity for Reader {
 impl fmt::Debug for Reader {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     f.debug_struct("Reader")
-      .field("notification_sender, dds_cache", &"can't print".to_string())
+      .field("dds_cache", &"can't print".to_string())
       .field("topic_name", &self.topic_name)
       .field("my_guid", &self.my_guid)
       .field("heartbeat_response_delay", &self.heartbeat_response_delay)
@@ -1406,24 +1971,230 @@ impl fmt::Debug for Reader {
   }
 }
 
+#[cfg(test)]
+pub(crate) mod test_util {
+  //! A Reader that runs without a DomainParticipant, for unit tests
+
+  use std::{
+    ops::{Deref, DerefMut},
+    sync::RwLock,
+  };
+
+  use super::*;
+  use crate::{
+    dds::{
+      statusevents::{sync_status_channel, StatusChannelReceiver},
+      typedesc::TypeDesc,
+    },
+    mio_source::PollEventSource,
+    structure::{dds_cache::DDSCache, guid::EntityKind},
+  };
+
+  pub(crate) struct TestReader {
+    pub reader: Reader,
+    pub topic_cache_handle: Arc<Mutex<TopicCache>>,
+    pub status_receiver: StatusChannelReceiver<DataReaderStatus>,
+    pub command_sender: mio_channel::SyncSender<ReaderCommand>,
+    pub notification_receiver: mio_channel::Receiver<()>,
+    pub fragment_streams: Arc<FragmentStreams>,
+    _notification_event_source: PollEventSource,
+    _participant_status_receiver: StatusChannelReceiver<DomainParticipantStatusEvent>,
+  }
+
+  impl TestReader {
+    /// A Reader of a topic of its own
+    pub fn new(qos_policy: QosPolicies) -> Self {
+      let dds_cache = Arc::new(RwLock::new(DDSCache::new()));
+      let topic_cache_handle = dds_cache.write().unwrap().add_new_topic(
+        "test_name".to_string(),
+        TypeDesc::new("test_type".to_string()),
+        &qos_policy,
+      );
+      Self::with_topic_cache(
+        GUID::dummy_test_guid(EntityKind::READER_NO_KEY_USER_DEFINED),
+        qos_policy,
+        topic_cache_handle,
+      )
+    }
+
+    /// A Reader that adds the samples it receives to `topic_cache_handle`,
+    /// e.g. for a DataReader to read
+    pub fn with_topic_cache(
+      guid: GUID,
+      qos_policy: QosPolicies,
+      topic_cache_handle: Arc<Mutex<TopicCache>>,
+    ) -> Self {
+      let (notification_sender, notification_receiver) = mio_channel::sync_channel::<()>(100);
+      let (notification_event_source, poll_event_sender) = mio_source::make_poll_channel().unwrap();
+      let (status_sender, status_receiver) = sync_status_channel::<DataReaderStatus>(4).unwrap();
+      let (participant_status_sender, participant_status_receiver) =
+        sync_status_channel(16).unwrap();
+      let (reader_command_sender, data_reader_command_receiver) =
+        mio_channel::sync_channel::<ReaderCommand>(10);
+      let topic_name = topic_cache_handle.lock().unwrap().topic_name();
+      let fragment_streams = Arc::new(FragmentStreams::default());
+
+      let reader_ing = ReaderIngredients {
+        guid,
+        notification_sender,
+        status_sender,
+        topic_name,
+        topic_cache_handle: topic_cache_handle.clone(),
+        like_stateless: false,
+        qos_policy,
+        data_reader_command_receiver,
+        data_reader_waker: Arc::new(Mutex::new(None)),
+        poll_event_sender,
+        readiness_queue: None,
+        fragment_streams: fragment_streams.clone(),
+        security_plugins: None,
+      };
+      // A short tick, so that tests do not wait long for timed events
+      let timer = mio_extras::timer::Builder::default()
+        .tick_duration(StdDuration::from_millis(1))
+        .build();
+      let reader = Reader::new(
+        reader_ing,
+        Rc::new(UDPSender::new(0).unwrap()),
+        timer,
+        participant_status_sender,
+      );
+      Self {
+        reader,
+        topic_cache_handle,
+        status_receiver,
+        command_sender: reader_command_sender,
+        notification_receiver,
+        fragment_streams,
+        _notification_event_source: notification_event_source,
+        _participant_status_receiver: participant_status_receiver,
+      }
+    }
+
+    /// Matches a writer. Returns the receiver state of messages from it.
+    pub fn add_writer(&mut self, writer_guid: GUID, qos: &QosPolicies) -> MessageReceiverState {
+      let mr_state = MessageReceiverState {
+        source_guid_prefix: writer_guid.prefix,
+        source_vendor_id: VendorId::THIS_IMPLEMENTATION,
+        ..Default::default()
+      };
+      self.reader.matched_writer_add(
+        writer_guid,
+        EntityId::UNKNOWN,
+        mr_state.unicast_reply_locator_list.clone(),
+        mr_state.multicast_reply_locator_list.clone(),
+        qos,
+      );
+      mr_state
+    }
+
+    /// An empty DATA from the writer to this Reader
+    pub fn data(&self, writer_guid: GUID, sequence_number: i64) -> Data {
+      Data {
+        reader_id: self.reader.entity_id(),
+        writer_id: writer_guid.entity_id,
+        writer_sn: SequenceNumber::new(sequence_number),
+        ..Data::default()
+      }
+    }
+
+    /// Handles a little-endian DATA submessage
+    pub fn receive(&mut self, data: Data, mr_state: &MessageReceiverState) {
+      self.stage(data, mr_state);
+      self.commit();
+    }
+
+    /// Handles a little-endian DATA submessage, but leaves the change staged,
+    /// as if the rest of the message was still being processed
+    pub fn stage(&mut self, data: Data, mr_state: &MessageReceiverState) {
+      let mut flags = DATA_Flags::Endianness | DATA_Flags::Data;
+      if data.inline_qos.is_some() {
+        flags |= DATA_Flags::InlineQos;
+      }
+      self.reader.handle_data_msg(data, flags, mr_state);
+    }
+
+    /// Makes the staged changes visible, as at the end of a message
+    pub fn commit(&mut self) {
+      self.reader.commit_changes();
+    }
+
+    /// Handles the timed events that are due, until `done` or a deadline
+    pub fn handle_timed_events_until(&mut self, done: impl Fn(&Reader) -> bool) {
+      let deadline = Instant::now() + StdDuration::from_secs(1);
+      while !done(&self.reader) && Instant::now() < deadline {
+        self.reader.handle_timed_event();
+        std::thread::yield_now();
+      }
+    }
+
+    /// The sequence numbers in the topic cache, in order
+    pub fn cached_sequence_numbers(&self) -> Vec<SequenceNumber> {
+      self
+        .topic_cache_handle
+        .lock()
+        .unwrap()
+        .get_changes_in_range_reliable(&BTreeMap::new())
+        .map(|(_ts, cc)| cc.sequence_number)
+        .collect()
+    }
+
+    pub fn drain_statuses(&self) {
+      while self.status_receiver.try_recv().is_ok() {}
+    }
+  }
+
+  impl Deref for TestReader {
+    type Target = Reader;
+    fn deref(&self) -> &Reader {
+      &self.reader
+    }
+  }
+
+  impl DerefMut for TestReader {
+    fn deref_mut(&mut self) -> &mut Reader {
+      &mut self.reader
+    }
+  }
+
+  pub(crate) fn test_writer_guid() -> GUID {
+    GUID::dummy_test_guid(EntityKind::WRITER_NO_KEY_USER_DEFINED)
+  }
+
+  /// Reliable and KeepAll
+  pub(crate) fn reliable_qos() -> QosPolicies {
+    crate::QosPolicyBuilder::new()
+      .reliability(policy::Reliability::Reliable {
+        max_blocking_time: Duration::from_millis(100),
+      })
+      .history(policy::History::KeepAll)
+      .build()
+  }
+}
+
 #[cfg(test)]
 mod tests {
-  use std::sync::RwLock;
+  use std::{io::Read, sync::RwLock};
+
+  use bytes::Bytes;
 
   use crate::{
     dds::{
       qos::policy::Reliability,
       statusevents::{sync_status_channel, DataReaderStatus},
       typedesc::TypeDesc,
-      with_key::datawriter::WriteOptions,
+      with_key::datawriter::{InlineQosParameter, WriteOptions},
     },
+    messages::submessages::elements::parameter::Parameter,
+    rtps::{MessageBuilder, SubmessageBody},
     structure::{
       dds_cache::DDSCache,
       guid::{EntityId, EntityKind, GUID},
+      parameter_id::ParameterId,
     },
     Duration, QosPolicyBuilder,
   };
-  use super::*;
+  use super::{test_util::*, *};
 
   #[test]
   fn reader_sends_notification_when_receiving_data() {
@@ -1470,6 +2241,8 @@ mod tests {
       data_reader_command_receiver: reader_command_receiver,
       data_reader_waker,
       poll_event_sender: notification_event_sender,
+      readiness_queue: None,
+      fragment_streams: Arc::default(),
       security_plugins: None,
     };
     let mut reader = Reader::new(
@@ -1505,6 +2278,7 @@ mod tests {
 
     // 4. Feed the data for the reader to handle
     reader.handle_data_msg(data, data_flags, &mr_state);
+    reader.commit_changes();
 
     // 5. Verify that the reader sends a notification about the new data
     assert!(
@@ -1555,6 +2329,8 @@ mod tests {
       data_reader_command_receiver: reader_command_receiver,
       data_reader_waker,
       poll_event_sender: notification_event_sender,
+      readiness_queue: None,
+      fragment_streams: Arc::default(),
       security_plugins: None,
     };
     let mut reader = Reader::new(
@@ -1593,6 +2369,14 @@ mod tests {
 
     // 4. Feed the data for the reader to handle
     reader.handle_data_msg(data.clone(), data_flags, &mr_state);
+    // The change is published only when the whole message has been handled
+    let instant = *reader.seqnum_instant_map.get(&sequence_num).unwrap();
+    assert!(topic_cache_handle
+      .lock()
+      .unwrap()
+      .get_change(&instant)
+      .is_none());
+    reader.commit_changes();
 
     // 5. Verify that the reader sent the data to the topic cache
     let topic_cache = topic_cache_handle.lock().unwrap();
@@ -1617,6 +2401,188 @@ mod tests {
     );
   }
 
+  #[test]
+  fn reader_passes_custom_inline_qos_to_cache() {
+    let mut reader = TestReader::new(QosPolicies::qos_none());
+    let writer_guid = test_writer_guid();
+    let mr_state = reader.add_writer(writer_guid, &QosPolicies::qos_none());
+
+    // Build the DATA submessage as the writer would, with a custom inline QoS
+    // parameter and a RustDDS specific one
+    let tag = InlineQosParameter {
+      parameter_id: 0x8123,
+      value: vec![1, 2, 3, 4],
+    };
+    let rustdds_parameter = InlineQosParameter {
+      parameter_id: ParameterId::PID_RUSTDDS_TRACE_CONTEXT.value(),
+      value: vec![5, 6, 7, 8],
+    };
+    let reader_id = reader.entity_id();
+    let data = |sequence_number: i64| {
+      let cache_change = CacheChange::new(
+        writer_guid,
+        SequenceNumber::new(sequence_number),
+        WriteOptionsBuilder::new()
+          .inline_qos_parameter(tag.clone())
+          .build()
+          .with_rustdds_parameter(rustdds_parameter.clone()),
+        DDSData::new(SerializedPayload::new(
+          RepresentationIdentifier::CDR_LE,
+          vec![0, 0, 0, 0],
+        )),
+      );
+      let submessage = MessageBuilder::data_submessage(
+        &cache_change,
+        reader_id,
+        writer_guid,
+        Endianness::LittleEndian,
+        None,
+      )
+      .unwrap();
+      match submessage.body {
+        SubmessageBody::Writer(WriterSubmessage::Data(data, _flags)) => data,
+        _ => panic!("Expected a DATA submessage"),
+      }
+    };
+    let write_options = |reader: &TestReader, sequence_number: i64| {
+      let topic_cache = reader.topic_cache_handle.lock().unwrap();
+      let instant = reader
+        .seqnum_instant_map
+        .get(&SequenceNumber::new(sequence_number))
+        .unwrap();
+      topic_cache
+        .get_change(instant)
+        .unwrap()
+        .write_options
+        .clone()
+    };
+
+    // The custom parameter is given to the application, the RustDDS specific
+    // one is kept apart
+    reader.receive(data(1), &mr_state);
+    let options = write_options(&reader, 1);
+    assert_eq!(options.inline_qos_parameters(), std::slice::from_ref(&tag));
+    assert_eq!(
+      options.rustdds_parameters(),
+      std::slice::from_ref(&rustdds_parameter)
+    );
+
+    // RustDDS specific parameters from other vendors are dropped
+    let other_vendor = MessageReceiverState {
+      source_vendor_id: VendorId { vendor_id: [1, 3] },
+      ..mr_state
+    };
+    reader.receive(data(2), &other_vendor);
+    let options = write_options(&reader, 2);
+    assert_eq!(options.inline_qos_parameters(), &[tag]);
+    assert!(options.rustdds_parameters().is_empty());
+  }
+
+  #[cfg(feature = "opentelemetry")]
+  #[test]
+  fn reader_takes_trace_context_only_from_rustdds() {
+    use opentelemetry::trace::{SpanContext, SpanId, TraceFlags, TraceId, TraceState};
+
+    use crate::dds::trace_context;
+
+    let mut reader = TestReader::new(QosPolicies::qos_none());
+    let writer_guid = test_writer_guid();
+    let mr_state = reader.add_writer(writer_guid, &QosPolicies::qos_none());
+    let span_context = SpanContext::new(
+      TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+      SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+      TraceFlags::SAMPLED,
+      false,
+      TraceState::default(),
+    );
+    let parameter = trace_context::to_inline_qos(&span_context);
+    let data = |sequence_number: i64| {
+      let mut inline_qos = ParameterList::new();
+      inline_qos.push(Parameter::new(
+        ParameterId::new(parameter.parameter_id),
+        parameter.value.clone(),
+      ));
+      Data {
+        inline_qos: Some(inline_qos),
+        ..reader.data(writer_guid, sequence_number)
+      }
+    };
+    let (data_1, data_2) = (data(1), data(2));
+
+    // Another vendor may use the same parameter id for something else
+    let other_vendor = MessageReceiverState {
+      source_vendor_id: VendorId::VENDOR_UNKNOWN,
+      ..mr_state.clone()
+    };
+    reader.receive(data_1, &mr_state);
+    reader.receive(data_2, &other_vendor);
+
+    let trace_context = |sequence_number: i64| {
+      let topic_cache = reader.topic_cache_handle.lock().unwrap();
+      let instant = reader
+        .seqnum_instant_map
+        .get(&SequenceNumber::new(sequence_number))
+        .unwrap();
+      topic_cache
+        .get_change(instant)
+        .unwrap()
+        .write_options
+        .trace_context()
+    };
+    assert_eq!(
+      trace_context(1).map(|c| c.span_id()),
+      Some(span_context.span_id())
+    );
+    assert_eq!(trace_context(2), None);
+  }
+
+  #[test]
+  fn reader_keeps_one_hold_back_release() {
+    let mut reader = TestReader::new(QosPolicies::qos_none());
+    let mut wake_up_after = |delay: StdDuration| {
+      reader
+        .command_sender
+        .try_send(ReaderCommand::WakeUpAfter { delay })
+        .unwrap();
+      reader.process_command();
+    };
+    wake_up_after(StdDuration::from_secs(3600));
+    // The DataReader asks for an earlier wake-up, which replaces the first
+    wake_up_after(StdDuration::from_millis(1));
+    reader.handle_timed_events_until(|reader| reader.hold_back_release.is_none());
+    assert!(reader.hold_back_release.is_none());
+  }
+
+  #[test]
+  fn reader_flushes_coalesced_notification_once() {
+    let qos = QosPolicyBuilder::new()
+      .notification_coalescing(policy::NotificationCoalescing {
+        max_samples: 0,
+        max_delay: Duration::from_millis(10),
+      })
+      .build();
+    let mut reader = TestReader::new(qos);
+    let writer_guid = GUID::dummy_test_guid(EntityKind::WRITER_NO_KEY_USER_DEFINED);
+    let mr_state = reader.add_writer(writer_guid, &QosPolicies::qos_none());
+    // Nothing is held back, so no flush is scheduled
+    reader.handle_timed_event();
+    assert!(reader.notification_flush.is_none());
+
+    let data = reader.data(writer_guid, 1);
+    reader.receive(data, &mr_state);
+    let data = reader.data(writer_guid, 2);
+    reader.receive(data, &mr_state);
+    reader.handle_timed_event();
+    assert!(reader.notification_flush.is_some());
+    reader.handle_timed_events_until(|reader| reader.notification_flush.is_none());
+    assert!(reader.notification_flush.is_none());
+
+    // The flush does not re-arm itself
+    std::thread::sleep(StdDuration::from_millis(20));
+    reader.handle_timed_event();
+    assert!(reader.notification_flush.is_none());
+  }
+
   #[test]
   fn reader_handles_heartbeats() {
     // 1. Create a reader for a topic with Reliable QoS
@@ -1661,6 +2627,8 @@ mod tests {
       data_reader_command_receiver: reader_command_receiver,
       data_reader_waker,
       poll_event_sender: notification_event_sender,
+      readiness_queue: None,
+      fragment_streams: Arc::default(),
       security_plugins: None,
     };
     let mut reader = Reader::new(
@@ -1730,6 +2698,202 @@ mod tests {
     assert_eq!(writer_proxy.sent_ack_nack_count, 2);
   }
 
+  #[test]
+  fn writer_proxy_statistics_follow_heartbeats_and_data() {
+    // 1. Create a Reliable reader and match a writer. Nothing is known yet.
+    let mut reader = TestReader::new(reliable_qos());
+    let writer_guid = test_writer_guid();
+    let mr_state = reader.add_writer(writer_guid, &reliable_qos());
+    let statistics = |reader: &Reader| reader.matched_writer(writer_guid).unwrap().statistics();
+    let initial = statistics(&reader);
+    assert_eq!(initial.writer_guid, writer_guid);
+    assert_eq!(initial.heartbeat_count, 0);
+    assert_eq!(initial.missing_samples, 0);
+    assert!(initial.last_heartbeat_time.is_none());
+
+    // 2. A HEARTBEAT announces samples 1..=3, which are all missing
+    let heartbeat = Heartbeat {
+      reader_id: reader.entity_id(),
+      writer_id: writer_guid.entity_id,
+      first_sn: SequenceNumber::new(1),
+      last_sn: SequenceNumber::new(3),
+      count: 7,
+    };
+    reader.handle_heartbeat_msg(&heartbeat, false, &mr_state);
+    let after_heartbeat = statistics(&reader);
+    assert_eq!(after_heartbeat.heartbeat_count, 7);
+    assert_eq!(after_heartbeat.missing_samples, 3);
+    assert_eq!(
+      after_heartbeat.last_received_sequence_number,
+      SequenceNumber::new(0)
+    );
+    assert!(after_heartbeat.last_heartbeat_time.is_some());
+
+    // 3. Sample 1 arrives
+    let data = reader.data(writer_guid, 1);
+    reader.receive(data, &mr_state);
+    let after_data = statistics(&reader);
+    assert_eq!(after_data.missing_samples, 2);
+    assert_eq!(
+      after_data.last_received_sequence_number,
+      SequenceNumber::new(1)
+    );
+  }
+
+  #[test]
+  fn reader_delays_and_suppresses_multicast_nacks() {
+    // 1. Create a Reliable reader and match a writer
+    let mut reader = TestReader::new(reliable_qos());
+    reader.heartbeat_response_delay = StdDuration::ZERO;
+    let writer_guid = test_writer_guid();
+    let mr_state = MessageReceiverState {
+      received_via_multicast: true,
+      ..reader.add_writer(writer_guid, &reliable_qos())
+    };
+
+    // 2. A multicast HEARTBEAT announces samples 1..2, which we do not have.
+    // The NACK is not sent right away.
+    let heartbeat = |count, last_sn| Heartbeat {
+      reader_id: EntityId::UNKNOWN,
+      writer_id: writer_guid.entity_id,
+      first_sn: SequenceNumber::new(1),
+      last_sn: SequenceNumber::new(last_sn),
+      count,
+    };
+    assert!(!reader.handle_heartbeat_msg(&heartbeat(1, 2), true, &mr_state));
+    let sent_ack_nack_count = |reader: &Reader| {
+      reader
+        .matched_writer(writer_guid)
+        .unwrap()
+        .sent_ack_nack_count
+    };
+    assert_eq!(sent_ack_nack_count(&reader), 0);
+
+    // 3. The samples are repaired before the delay expires, e.g. because
+    // another reader asked for them. The NACK is suppressed.
+    for sn in [1, 2] {
+      let data = reader.data(writer_guid, sn);
+      reader.receive(data, &mr_state);
+    }
+    reader.handle_timed_events_until(|reader| reader.pending_heartbeat_responses.is_empty());
+    let writer_proxy = reader.matched_writer(writer_guid).unwrap();
+    assert_eq!(writer_proxy.sent_ack_nack_count, 0);
+    assert_eq!(writer_proxy.statistics().suppressed_nacks, 1);
+
+    // 4. Sample 3 is announced and does not arrive. The NACK is sent after the
+    // delay.
+    assert!(!reader.handle_heartbeat_msg(&heartbeat(2, 3), true, &mr_state));
+    reader.handle_timed_events_until(|reader| reader.pending_heartbeat_responses.is_empty());
+    let writer_proxy = reader.matched_writer(writer_guid).unwrap();
+    assert_eq!(writer_proxy.sent_ack_nack_count, 1);
+    assert_eq!(writer_proxy.statistics().suppressed_nacks, 1);
+  }
+
+  #[test]
+  fn reader_reports_lost_samples() {
+    // 1. Create a reader for a topic with Reliable QoS, and match a writer
+    let mut reader = TestReader::new(reliable_qos());
+    let writer_guid = test_writer_guid();
+    let mr_state = reader.add_writer(writer_guid, &reliable_qos());
+    // Drain the SubscriptionMatched status
+    reader.drain_statuses();
+
+    // 2. The first heartbeat tells that samples before 3 are no longer
+    // available. They were published before we matched, so they are not lost.
+    let hb_first = Heartbeat {
+      reader_id: reader.entity_id(),
+      writer_id: writer_guid.entity_id,
+      first_sn: SequenceNumber::new(3),
+      last_sn: SequenceNumber::new(5),
+      count: 1,
+    };
+    reader.handle_heartbeat_msg(&hb_first, false, &mr_state);
+    assert!(reader.status_receiver.try_recv().is_err());
+
+    // 3. We receive only sample 3, and then the writer tells that samples
+    // before 6 are no longer available. Samples 4 and 5 are lost.
+    let data = reader.data(writer_guid, 3);
+    reader.receive(data, &mr_state);
+
+    let hb_second = Heartbeat {
+      reader_id: reader.entity_id(),
+      writer_id: writer_guid.entity_id,
+      first_sn: SequenceNumber::new(6),
+      last_sn: SequenceNumber::new(8),
+      count: 2,
+    };
+    reader.handle_heartbeat_msg(&hb_second, false, &mr_state);
+
+    match reader.status_receiver.try_recv() {
+      Ok(DataReaderStatus::SampleLost { count }) => {
+        assert_eq!(count, CountWithChange::new(2, 2));
+      }
+      other => panic!("Expected SampleLost status, got {other:?}"),
+    }
+
+    // 4. A GAP for samples 6 and 7 loses two more.
+    let gap = Gap {
+      reader_id: reader.entity_id(),
+      writer_id: writer_guid.entity_id,
+      gap_start: SequenceNumber::new(6),
+      gap_list: SequenceNumberSet::new_empty(SequenceNumber::new(8)),
+    };
+    reader.handle_gap_msg(&gap, &mr_state);
+
+    match reader.status_receiver.try_recv() {
+      Ok(DataReaderStatus::SampleLost { count }) => {
+        assert_eq!(count, CountWithChange::new(4, 2));
+      }
+      other => panic!("Expected SampleLost status, got {other:?}"),
+    }
+  }
+
+  #[test]
+  fn reliable_reader_receives_best_effort_writer_as_best_effort() {
+    // 1. Create a reader for a topic with Reliable QoS, and match a writer
+    // that does not offer Reliable
+    let mut reader = TestReader::new(reliable_qos());
+    let writer_guid = test_writer_guid();
+    let mr_state = reader.add_writer(writer_guid, &QosPolicies::qos_none());
+    reader.drain_statuses();
+    let statistics = reader.matched_writer(writer_guid).unwrap().statistics();
+    assert_eq!(statistics.reliability, Reliability::BestEffort);
+
+    // 2. Sample 2 never arrives. There are no HEARTBEATs or repairs, so it is
+    // lost, and sample 3 must not wait for it. Sample 3 also arrives twice, as
+    // over redundant networks, and the second copy is discarded.
+    for sn in [1, 3, 3] {
+      let data = reader.data(writer_guid, sn);
+      reader.receive(data, &mr_state);
+    }
+    match reader.status_receiver.try_recv() {
+      Ok(DataReaderStatus::SampleLost { count }) => {
+        assert_eq!(count, CountWithChange::new(1, 1));
+      }
+      other => panic!("Expected SampleLost status, got {other:?}"),
+    }
+    let statistics = reader.matched_writer(writer_guid).unwrap().statistics();
+    assert_eq!(statistics.duplicate_samples, 1);
+    assert_eq!(
+      reader.cached_sequence_numbers(),
+      vec![SequenceNumber::new(1), SequenceNumber::new(3)]
+    );
+
+    // 3. The writer sends a HEARTBEAT, so it is Reliable after all. It stays
+    // so, when Discovery announces it again.
+    let heartbeat = Heartbeat {
+      reader_id: reader.entity_id(),
+      writer_id: writer_guid.entity_id,
+      first_sn: SequenceNumber::new(1),
+      last_sn: SequenceNumber::new(3),
+      count: 1,
+    };
+    reader.handle_heartbeat_msg(&heartbeat, true, &mr_state);
+    reader.add_writer(writer_guid, &QosPolicies::qos_none());
+    let statistics = reader.matched_writer(writer_guid).unwrap().statistics();
+    assert_eq!(statistics.reliability, reader.reliability);
+  }
+
   #[test]
   fn reader_handles_gaps() {
     // 1. Create a reader
@@ -1770,6 +2934,8 @@ mod tests {
       data_reader_command_receiver: reader_command_receiver,
       data_reader_waker,
       poll_event_sender: notification_event_sender,
+      readiness_queue: None,
+      fragment_streams: Arc::default(),
       security_plugins: None,
     };
     let mut reader = Reader::new(
@@ -1908,6 +3074,8 @@ mod tests {
       data_reader_command_receiver: reader_command_receiver,
       data_reader_waker,
       poll_event_sender: notification_event_sender,
+      readiness_queue: None,
+      fragment_streams: Arc::default(),
       security_plugins: None,
     };
     let mut reader = Reader::new(
@@ -1937,4 +3105,225 @@ mod tests {
     // we attempted to add
     assert!(reader.matched_writer(writer_guid).is_none());
   }
+
+  #[test]
+  fn reader_discards_samples_with_bad_checksum() {
+    // 1. Create a reader and match a writer
+    let qos = QosPolicies::qos_none();
+    let mut reader = TestReader::new(qos.clone());
+    let writer_guid = GUID::dummy_test_guid(EntityKind::WRITER_NO_KEY_USER_DEFINED);
+    let mr_state = reader.add_writer(writer_guid, &qos);
+
+    // 2. Sample 1 has the correct checksum, and sample 3 was corrupted on the
+    // way. Sample 2 comes from another implementation, which uses the same
+    // parameter id for something else.
+    let foreign_mr_state = MessageReceiverState {
+      source_vendor_id: VendorId::VENDOR_UNKNOWN,
+      ..mr_state.clone()
+    };
+    let parameter = checksum::to_inline_qos(policy::PayloadChecksum::Crc32c, b"fake data");
+    for (sn, mut value) in [
+      (1, parameter.value.clone()),
+      (2, parameter.value.clone()),
+      (3, parameter.value),
+    ] {
+      value[0] ^= u8::from(sn != 1);
+      let mut inline_qos = ParameterList::new();
+      inline_qos.push(Parameter::new(
+        ParameterId::new(parameter.parameter_id),
+        value,
+      ));
+      let data = Data {
+        inline_qos: Some(inline_qos),
+        ..reader.data(writer_guid, sn)
+      };
+      reader.receive(
+        data,
+        if sn == 2 {
+          &foreign_mr_state
+        } else {
+          &mr_state
+        },
+      );
+    }
+
+    // 3. Samples 1 and 2 are received
+    let writer_proxy = reader.matched_writer(writer_guid).unwrap();
+    assert_eq!(writer_proxy.statistics().corrupted_samples, 1);
+    assert!(writer_proxy.should_ignore_change(SequenceNumber::new(1)));
+    assert!(writer_proxy.should_ignore_change(SequenceNumber::new(2)));
+    assert!(!writer_proxy.should_ignore_change(SequenceNumber::new(3)));
+    assert_eq!(
+      reader.cached_sequence_numbers(),
+      vec![SequenceNumber::new(1), SequenceNumber::new(2)]
+    );
+  }
+
+  // A 24-byte payload that arrives in three fragments
+  const STREAMED_PAYLOAD: &[u8] = b"\x00\x01\x00\x00abcdefghijklmnopqrst";
+
+  // Fragment `fragment_number` of STREAMED_PAYLOAD, as sample `sn` of
+  // `writer_guid`
+  fn streamed_fragment(
+    reader: &TestReader,
+    writer_guid: GUID,
+    sn: i64,
+    fragment_number: u32,
+  ) -> DataFrag {
+    let parameter =
+      checksum::to_inline_qos(policy::PayloadChecksum::Crc32c, &STREAMED_PAYLOAD[4..]);
+    let mut inline_qos = ParameterList::new();
+    inline_qos.push(Parameter::new(
+      ParameterId::new(parameter.parameter_id),
+      parameter.value,
+    ));
+    DataFrag {
+      reader_id: reader.entity_id(),
+      writer_id: writer_guid.entity_id,
+      writer_sn: SequenceNumber::new(sn),
+      fragment_starting_num: FragmentNumber::new(fragment_number),
+      fragments_in_submessage: 1,
+      data_size: STREAMED_PAYLOAD.len() as u32,
+      fragment_size: 8,
+      inline_qos: Some(inline_qos),
+      serialized_payload: Bytes::copy_from_slice(
+        &STREAMED_PAYLOAD[(fragment_number as usize - 1) * 8..fragment_number as usize * 8],
+      ),
+    }
+  }
+
+  #[test]
+  fn reader_streams_large_fragmented_samples() {
+    // 1. Create a reader that streams samples of 16 bytes or more, and match a
+    // writer
+    let mut reader = TestReader::new(QosPolicies::qos_none());
+    reader.fragment_streams.set_min_size(Some(16));
+    let writer_guid = GUID::dummy_test_guid(EntityKind::WRITER_NO_KEY_USER_DEFINED);
+    let mr_state = reader.add_writer(writer_guid, &QosPolicies::qos_none());
+    let flags = BitFlags::<DATAFRAG_Flags>::from_flag(DATAFRAG_Flags::InlineQos);
+
+    // 2. The payload arrives in three fragments: the last one first, and the
+    // first one twice.
+    let datafrag = streamed_fragment(&reader, writer_guid, 1, 3);
+    reader.handle_datafrag_msg(&datafrag, flags, &mr_state);
+    assert!(reader.notification_receiver.try_recv().is_ok());
+    let mut stream = reader.fragment_streams.take().unwrap();
+    assert_eq!(stream.writer_guid(), writer_guid);
+    assert_eq!(stream.size(), STREAMED_PAYLOAD.len());
+
+    let datafrag = streamed_fragment(&reader, writer_guid, 1, 1);
+    reader.handle_datafrag_msg(&datafrag, flags, &mr_state);
+    reader.handle_datafrag_msg(&datafrag, flags, &mr_state);
+    let mut start = [0; 8];
+    stream.read_exact(&mut start).unwrap();
+    assert_eq!(&start, &STREAMED_PAYLOAD[..8]);
+    assert!(!reader
+      .matched_writer(writer_guid)
+      .unwrap()
+      .should_ignore_change(SequenceNumber::new(1)));
+
+    // 3. The stream ends with the last missing fragment. The sample is received,
+    // but not added to the topic cache.
+    let datafrag = streamed_fragment(&reader, writer_guid, 1, 2);
+    reader.handle_datafrag_msg(&datafrag, flags, &mr_state);
+    let mut rest = Vec::new();
+    stream.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, &STREAMED_PAYLOAD[8..]);
+    assert!(reader.fragment_streams.take().is_none());
+    assert!(reader
+      .matched_writer(writer_guid)
+      .unwrap()
+      .should_ignore_change(SequenceNumber::new(1)));
+    assert!(reader.cached_sequence_numbers().is_empty());
+  }
+
+  #[test]
+  fn reader_fails_streams_on_gap_and_unmatch() {
+    let mut reader = TestReader::new(QosPolicies::qos_none());
+    reader.fragment_streams.set_min_size(Some(16));
+    let writer_guid = GUID::dummy_test_guid(EntityKind::WRITER_NO_KEY_USER_DEFINED);
+    let mr_state = reader.add_writer(writer_guid, &QosPolicies::qos_none());
+    let flags = BitFlags::<DATAFRAG_Flags>::from_flag(DATAFRAG_Flags::InlineQos);
+
+    // Samples 1 and 2 start streaming
+    for sn in 1..=2 {
+      let datafrag = streamed_fragment(&reader, writer_guid, sn, 1);
+      reader.handle_datafrag_msg(&datafrag, flags, &mr_state);
+    }
+    let mut stream_1 = reader.fragment_streams.take().unwrap();
+    let mut stream_2 = reader.fragment_streams.take().unwrap();
+    let mut data = Vec::new();
+
+    // The writer no longer has sample 1
+    let gap = Gap {
+      reader_id: reader.entity_id(),
+      writer_id: writer_guid.entity_id,
+      gap_start: SequenceNumber::new(1),
+      gap_list: SequenceNumberSet::new_empty(SequenceNumber::new(2)),
+    };
+    reader.handle_gap_msg(&gap, &mr_state);
+    assert!(stream_1.read_to_end(&mut data).is_err());
+    assert_eq!(data, &STREAMED_PAYLOAD[..8]);
+
+    // The writer goes away during sample 2
+    reader.remove_writer_proxy(writer_guid);
+    assert!(reader.fragment_assemblers.is_empty());
+    data.clear();
+    assert!(stream_2.read_to_end(&mut data).is_err());
+    assert_eq!(data, &STREAMED_PAYLOAD[..8]);
+  }
+
+  #[test]
+  fn re_announced_writer_is_updated_or_unmatched() {
+    let requested = QosPolicyBuilder::new()
+      .durability(policy::Durability::TransientLocal)
+      .build();
+    let mut reader = TestReader::new(requested.clone());
+    let writer_guid = test_writer_guid();
+    let locator = |port: u16| Locator::from(std::net::SocketAddr::from(([127, 0, 0, 1], port)));
+    let proxy =
+      |port| RtpsWriterProxy::new(writer_guid, vec![locator(port)], vec![], EntityId::UNKNOWN);
+    let current_matched = |reader: &TestReader| match reader.status_receiver.try_recv() {
+      Ok(DataReaderStatus::SubscriptionMatched { current, .. }) => current,
+      other => panic!("Expected SubscriptionMatched status, got {other:?}"),
+    };
+
+    reader.update_writer_proxy(proxy(7411), &requested);
+    assert_eq!(current_matched(&reader), CountWithChange::new(1, 1));
+
+    // New locators are applied to the existing proxy, without a status change
+    reader.update_writer_proxy(proxy(7412), &requested);
+    assert_eq!(
+      reader
+        .matched_writer(writer_guid)
+        .unwrap()
+        .unicast_locator_list,
+      vec![locator(7412)]
+    );
+    assert!(reader.status_receiver.try_recv().is_err());
+
+    // The writer no longer offers what we request
+    let volatile = QosPolicyBuilder::new()
+      .durability(policy::Durability::Volatile)
+      .build();
+    reader.update_writer_proxy(proxy(7412), &volatile);
+    assert!(reader.matched_writer(writer_guid).is_none());
+    assert!(matches!(
+      reader.status_receiver.try_recv(),
+      Ok(DataReaderStatus::RequestedIncompatibleQos { .. })
+    ));
+    assert_eq!(current_matched(&reader), CountWithChange::new(0, -1));
+
+    // Matched again, and then re-announced on another topic. Discovery then
+    // removes the proxy.
+    reader.update_writer_proxy(proxy(7412), &requested);
+    assert_eq!(current_matched(&reader), CountWithChange::new(1, 1));
+    reader.remove_writer_proxy(writer_guid);
+    assert!(reader.matched_writer(writer_guid).is_none());
+    assert_eq!(current_matched(&reader), CountWithChange::new(0, -1));
+
+    // Removing an unknown writer does nothing
+    reader.remove_writer_proxy(writer_guid);
+    assert!(reader.status_receiver.try_recv().is_err());
+  }
 }
@@ -1,15 +1,24 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+  cell::Cell,
+  cmp::{max, min},
+  collections::{BTreeMap, BTreeSet},
+  time::{Duration as StdDuration, Instant},
+};
 
 use bit_vec::BitVec;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 
 use crate::{
-  dds::{participant::DomainParticipant, qos::QosPolicies},
+  dds::{
+    participant::DomainParticipant,
+    qos::{policy, QosPolicies},
+  },
   discovery::sedp_messages::DiscoveredReaderData,
   messages::submessages::submessage::AckSubmessage,
   rtps::constant::*,
   structure::{
+    duration::Duration,
     guid::{EntityId, GUID},
     locator::Locator,
     sequence_number::{FragmentNumber, FragmentNumberSet, SequenceNumber, SequenceNumberRange},
@@ -56,8 +65,28 @@ pub(crate) struct RtpsReaderProxy {
   // true = send repair data messages due to NACKs, buffer messages by DataWriter
   // false = send data messages directly from DataWriter
   pub repair_mode: bool,
+  // Id of the periodic heartbeat timer running for this reader, if any
+  pub heartbeat_timer: Option<u64>,
+  // Changes up to this were written before the reader was matched. Sending
+  // them is history replay, which may be paced by the HistoryReplay QoS.
+  pub replay_up_to: SequenceNumber,
   qos: QosPolicies,
   frags_requested: BTreeMap<SequenceNumber, BitVec>,
+  // Counts of the latest ACKNACK and NACK_FRAG processed from this Reader.
+  // Submessages with a count not greater than these are duplicates or stale,
+  // e.g. reordered by UDP.
+  last_acknack_count: Option<i32>,
+  last_nackfrag_count: Option<i32>,
+  // Number of HEARTBEATs sent to this Reader. Counted as they are sent, so it
+  // can change while the proxy is borrowed for sending.
+  heartbeats_sent: Cell<u32>,
+  // heartbeats_sent and the send time at the latest periodic HEARTBEAT to this
+  // Reader that has not been answered yet. Its ACKNACK gives a round trip
+  // time sample.
+  heartbeat_probe: Option<(u32, Instant)>,
+  round_trip: Option<RoundTripEstimate>,
+  // The latest HEARTBEAT to this Reader went to its multicast locators
+  multicast_heartbeat: bool,
 }
 
 impl RtpsReaderProxy {
@@ -73,8 +102,16 @@ impl RtpsReaderProxy {
       unsent_changes: BTreeSet::new(),
       pending_gap: BTreeSet::new(),
       repair_mode: false,
+      heartbeat_timer: None,
+      replay_up_to: SequenceNumber::zero(),
       qos,
       frags_requested: BTreeMap::new(),
+      last_acknack_count: None,
+      last_nackfrag_count: None,
+      heartbeats_sent: Cell::new(0),
+      heartbeat_probe: None,
+      round_trip: None,
+      multicast_heartbeat: false,
     }
   }
 
@@ -135,6 +172,13 @@ impl RtpsReaderProxy {
     self.unsent_changes.remove(&seq_num);
   }
 
+  // Is the next change to send part of the history replay
+  pub fn is_replaying(&self) -> bool {
+    self
+      .first_unsent_change()
+      .is_some_and(|sn| sn <= self.replay_up_to)
+  }
+
   pub fn from_reader(reader: &ReaderIngredients, domain_participant: &DomainParticipant) -> Self {
     let mut self_locators = domain_participant.self_locators(); // This clones a map of locator lists.
     let unicast_locator_list = self_locators
@@ -155,8 +199,16 @@ impl RtpsReaderProxy {
       unsent_changes: BTreeSet::new(),
       pending_gap: BTreeSet::new(),
       repair_mode: false,
+      heartbeat_timer: None,
+      replay_up_to: SequenceNumber::zero(),
       qos: reader.qos_policy.clone(),
       frags_requested: BTreeMap::new(),
+      last_acknack_count: None,
+      last_nackfrag_count: None,
+      heartbeats_sent: Cell::new(0),
+      heartbeat_probe: None,
+      round_trip: None,
+      multicast_heartbeat: false,
     }
   }
 
@@ -193,8 +245,106 @@ impl RtpsReaderProxy {
       unsent_changes: BTreeSet::new(),
       pending_gap: BTreeSet::new(),
       repair_mode: false,
+      heartbeat_timer: None,
+      replay_up_to: SequenceNumber::zero(),
       qos: discovered_reader_data.subscription_topic_data.qos(),
       frags_requested: BTreeMap::new(),
+      last_acknack_count: None,
+      last_nackfrag_count: None,
+      heartbeats_sent: Cell::new(0),
+      heartbeat_probe: None,
+      round_trip: None,
+      multicast_heartbeat: false,
+    }
+  }
+
+  // Checks the count of an ACKNACK or NACK_FRAG from this Reader. Returns true
+  // and records the count, if the submessage is newer than any previously
+  // accepted one of the same kind. Otherwise it is a duplicate or stale and
+  // should be ignored.
+  pub fn accept_ack_count(&mut self, ack_submessage: &AckSubmessage) -> bool {
+    let (last_count, count) = match ack_submessage {
+      AckSubmessage::AckNack(acknack) => (&mut self.last_acknack_count, acknack.count),
+      AckSubmessage::NackFrag(nack_frag) => (&mut self.last_nackfrag_count, nack_frag.count),
+    };
+    if last_count.is_some_and(|last| count <= last) {
+      false
+    } else {
+      *last_count = Some(count);
+      true
+    }
+  }
+
+  pub fn heartbeat_sent(&mut self, via_multicast: bool) {
+    self.multicast_heartbeat = via_multicast;
+  }
+
+  // A NACK in response to a multicast HEARTBEAT is repaired over multicast.
+  // The other Readers in the multicast group likely miss the same changes, and
+  // seeing the repair they suppress their own NACKs.
+  pub fn repairs_via_multicast(&self) -> bool {
+    self.multicast_heartbeat && self.multicast_locator_list.iter().any(Locator::is_udp)
+  }
+
+  // A HEARTBEAT addressed to this Reader, or to all Readers, was sent
+  pub fn count_heartbeat(&self) {
+    self
+      .heartbeats_sent
+      .set(self.heartbeats_sent.get().wrapping_add(1));
+  }
+
+  // The HEARTBEAT counted latest is a probe for the round trip time
+  pub fn heartbeat_probe_sent(&mut self, sent_at: Instant) {
+    self.heartbeat_probe = Some((self.heartbeats_sent.get(), sent_at));
+  }
+
+  // An ACKNACK was received. It is taken as the response to the latest
+  // heartbeat probe only if no other HEARTBEAT has been sent to this Reader
+  // since, as otherwise we cannot know which one it answers (cf. Karn's
+  // algorithm). Returns the updated round trip time, if any.
+  pub fn heartbeat_probe_answered(&mut self, now: Instant) -> Option<StdDuration> {
+    let (probe, sent_at) = self.heartbeat_probe.take()?;
+    if probe != self.heartbeats_sent.get() {
+      return None;
+    }
+    let sample = now.saturating_duration_since(sent_at);
+    match self.round_trip.as_mut() {
+      Some(estimate) => estimate.update(sample),
+      None => self.round_trip = Some(RoundTripEstimate::new(sample)),
+    }
+    self.round_trip_time()
+  }
+
+  /// Smoothed round trip time to this Reader, if measured yet
+  pub fn round_trip_time(&self) -> Option<StdDuration> {
+    self.round_trip.map(|estimate| estimate.smoothed)
+  }
+
+  // Period of heartbeats to this Reader under the AdaptiveTiming QoS policy
+  pub fn adapted_heartbeat_period(
+    &self,
+    period: Duration,
+    adaptive_timing: Option<policy::AdaptiveTiming>,
+  ) -> Duration {
+    match (adaptive_timing, self.round_trip) {
+      (Some(adaptive_timing), Some(estimate)) => {
+        let adapted = Duration::from_std(4 * estimate.retransmission_timeout());
+        min(max(adapted, adaptive_timing.min_heartbeat_period), period)
+      }
+      _ => period,
+    }
+  }
+
+  // Delay before sending repairs requested by this Reader under the
+  // AdaptiveTiming QoS policy
+  pub fn adapted_nack_response_delay(
+    &self,
+    delay: StdDuration,
+    adaptive_timing: Option<policy::AdaptiveTiming>,
+  ) -> StdDuration {
+    match (adaptive_timing, self.round_trip) {
+      (Some(_), Some(estimate)) => min(estimate.smoothed, delay),
+      _ => delay,
     }
   }
 
@@ -205,23 +355,28 @@ impl RtpsReaderProxy {
   ) {
     match ack_submessage {
       AckSubmessage::AckNack(acknack) => {
-        self.all_acked_before = acknack.reader_sn_state.base();
+        // A reader cannot acknowledge what we have not written. Clamp, so that a
+        // misbehaving reader cannot make us believe it has changes we have not
+        // yet sent, or even written.
+        self.all_acked_before = min(acknack.reader_sn_state.base(), last_available.plus_1());
         // clean up unsent_changes:
         // The handy split_off function "Returns everything after the given key,
         // including the key."
         self.unsent_changes = self.unsent_changes.split_off(&self.all_acked_before);
+        // Fragments of acknowledged changes are not needed anymore.
+        self.frags_requested = self.frags_requested.split_off(&self.all_acked_before);
 
-        // Insert the requested changes.
+        // Insert the requested changes. Requests for changes we do not have are
+        // not recorded, as we could never send them.
         for nack_sn in acknack.reader_sn_state.iter() {
-          self.unsent_changes.insert(nack_sn);
-        }
-        // sanity check
-        if let Some(&high) = self.unsent_changes.iter().next_back() {
-          if high > last_available {
+          if nack_sn <= last_available {
+            self.unsent_changes.insert(nack_sn);
+          } else {
             warn!(
               "ReaderProxy {:?} asks for {:?} but I have only up to {:?}. ACKNACK = {:?}",
-              self.remote_reader_guid, self.unsent_changes, last_available, acknack
+              self.remote_reader_guid, nack_sn, last_available, acknack
             );
+            break; // the rest are even higher
           }
         }
         // AckNack also clears pending_gap
@@ -229,22 +384,36 @@ impl RtpsReaderProxy {
       }
 
       AckSubmessage::NackFrag(_nack_frag) => {
-        // TODO
-        error!("NACKFRAG not implemented");
+        // Recording a NACK_FRAG needs the number of fragments in the sample.
+        error!("NACK_FRAG must be handled with mark_frags_requested()");
       }
     }
   }
 
-  pub fn insert_pending_gap(&mut self, seq_num: SequenceNumber) {
+  // Records that the change will never be sent to this Reader as DATA. It is
+  // sent as GAP instead. Everything that withholds a change from a reliable
+  // Reader must come through here, or the Reader keeps NACKing the change.
+  pub fn will_never_send(&mut self, seq_num: SequenceNumber, reason: NotSentReason) {
+    trace!(
+      "Will not send {:?} to {:?}: {:?}",
+      seq_num,
+      self.remote_reader_guid,
+      reason
+    );
     self.pending_gap.insert(seq_num);
   }
 
-  pub fn set_pending_gap_up_to(&mut self, last_gap_sn: SequenceNumber) {
+  // Same as above for all changes up to and including last_gap_sn
+  pub fn will_never_send_up_to(&mut self, last_gap_sn: SequenceNumber, reason: NotSentReason) {
+    trace!(
+      "Will not send changes up to {:?} to {:?}: {:?}",
+      last_gap_sn,
+      self.remote_reader_guid,
+      reason
+    );
     // form SN range from 1 to last_gap_sn (inclusive)
     let gap_sn_range = SequenceNumberRange::new(SequenceNumber::new(1), last_gap_sn);
-    // Convert to a set and insert the SNs to pending_gap
-    let gap_sn_set = BTreeSet::from_iter(gap_sn_range);
-    self.pending_gap.extend(gap_sn_set);
+    self.pending_gap.extend(gap_sn_range);
   }
 
   pub fn get_pending_gap(&self) -> &BTreeSet<SequenceNumber> {
@@ -269,42 +438,47 @@ impl RtpsReaderProxy {
 
   // Fragment handling
 
-  pub fn mark_all_frags_requested(&mut self, seq_num: SequenceNumber, frag_count: u32) {
-    // Insert all ones set with frag_count bits
-    self
-      .frags_requested
-      // TODO: explain why unwrap below succeeds
-      .insert(
-        seq_num,
-        BitVec::from_elem(frag_count.try_into().unwrap(), true),
+  // Records the fragments requested in a NACK_FRAG. The sample has
+  // `frag_count` fragments. Requests for fragments beyond that are ignored.
+  pub fn mark_frags_requested(
+    &mut self,
+    seq_num: SequenceNumber,
+    frag_nums: &FragmentNumberSet,
+    frag_count: u32,
+  ) {
+    let requested: Vec<usize> = frag_nums
+      .iter()
+      .map(u32::from)
+      .filter(|f| (1..=frag_count).contains(f))
+      .map(|f| f as usize)
+      .collect();
+    if requested.is_empty() {
+      warn!(
+        "mark_frags_requested: No valid fragments in NackFrag. reader={:?} SN={:?} frag_nums={:?}",
+        self.remote_reader_guid, seq_num, frag_nums
       );
-  }
+      return;
+    }
 
-  pub fn mark_frags_requested(&mut self, seq_num: SequenceNumber, frag_nums: &FragmentNumberSet) {
     let req_set = self
       .frags_requested
       .entry(seq_num)
-      .or_insert_with(|| BitVec::with_capacity(64)); // default capacity out of hat
-
-    if let Some(max_fn_requested) = req_set.iter().next_back() {
-      // allocate more space if needed
-      let max_fn_requested = usize::from(max_fn_requested);
-      if max_fn_requested > req_set.len() {
-        let growth_need = max_fn_requested - req_set.len();
-        req_set.grow(growth_need, false);
-      }
-      for f in frag_nums.iter() {
-        // -1 because FragmentNumbers start at 1
-        req_set.set(usize::from(f) - 1, true);
-      }
-    } else {
-      warn!(
-        "mark_frags_requested: Empty set in NackFrag??? reader={:?} SN={:?}",
-        self.remote_reader_guid, seq_num
-      );
+      .or_insert_with(|| BitVec::from_elem(frag_count as usize, false));
+    if req_set.len() < frag_count as usize {
+      req_set.grow(frag_count as usize - req_set.len(), false);
+    }
+    for f in requested {
+      // -1 because FragmentNumbers start at 1
+      req_set.set(f - 1, true);
     }
   }
 
+  // All fragments of the sample were sent, e.g. as repair of the whole
+  // sample, so earlier requests for some of them are fulfilled.
+  pub fn mark_all_frags_sent(&mut self, seq_num: SequenceNumber) {
+    self.frags_requested.remove(&seq_num);
+  }
+
   // This just removes the FragmentNumber entry from the set.
   pub fn mark_frag_sent(&mut self, seq_num: SequenceNumber, frag_num: &FragmentNumber) {
     let mut frag_map_emptied = false;
@@ -334,6 +508,51 @@ impl RtpsReaderProxy {
   }
 }
 
+// Why a Writer does not send a change to a Reader as DATA
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NotSentReason {
+  // The change was written for another Reader only
+  OtherReader,
+  // The change was written before this Volatile Reader was matched
+  Volatile,
+  // History replay sends only the latest change of each instance
+  Superseded,
+  // The change is not in the history cache anymore
+  Removed,
+  // The Lifespan of the change expired before it was delivered
+  Expired,
+}
+
+// Smoothed round trip time and its variation, computed as in RFC 6298.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct RoundTripEstimate {
+  smoothed: StdDuration,
+  variation: StdDuration,
+}
+
+impl RoundTripEstimate {
+  fn new(sample: StdDuration) -> Self {
+    Self {
+      smoothed: sample,
+      variation: sample / 2,
+    }
+  }
+
+  fn update(&mut self, sample: StdDuration) {
+    let deviation = if sample > self.smoothed {
+      sample - self.smoothed
+    } else {
+      self.smoothed - sample
+    };
+    self.variation = (3 * self.variation + deviation) / 4;
+    self.smoothed = (7 * self.smoothed + sample) / 8;
+  }
+
+  fn retransmission_timeout(&self) -> StdDuration {
+    self.smoothed + 4 * self.variation
+  }
+}
+
 pub struct FragBitVecIterator {
   sequence_number: SequenceNumber,
   frag_count: FragmentNumber,
@@ -1,10 +1,14 @@
-use core::ops::Bound::{Included, Unbounded};
-use std::{cmp::max, collections::BTreeMap};
+use std::{
+  cmp::{max, min},
+  collections::BTreeMap,
+  time::Instant,
+};
 
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 
 use crate::{
+  dds::qos::policy,
   discovery::sedp_messages::DiscoveredWriterData,
   structure::{
     guid::{EntityId, GUID},
@@ -14,6 +18,40 @@ use crate::{
   },
 };
 
+/// Reception statistics of a DataReader from a single matched remote
+/// DataWriter. This is a RustDDS extension for diagnostics, e.g. to find out
+/// which remote writer is stalling.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WriterProxyStatistics {
+  pub writer_guid: GUID,
+  /// Effective reliability of the match. This is `BestEffort` if either the
+  /// DataReader or the DataWriter is BestEffort, even if the DataReader
+  /// requested `Reliable`.
+  pub reliability: policy::Reliability,
+  /// Highest sequence number received so far. Zero if nothing has been
+  /// received yet.
+  pub last_received_sequence_number: SequenceNumber,
+  /// How many samples the writer has announced in HEARTBEATs, but we have not
+  /// yet received. These are requested from the writer with ACKNACK.
+  /// This is not tracked when reliability is `BestEffort`.
+  pub missing_samples: usize,
+  /// Count from the latest HEARTBEAT received from the writer.
+  pub heartbeat_count: i32,
+  /// When was the latest HEARTBEAT received. `None` if none has been received.
+  pub last_heartbeat_time: Option<Timestamp>,
+  /// How many samples were received again after the first copy, and
+  /// discarded. These are e.g. repairs that crossed with the original, or
+  /// copies arriving over redundant networks.
+  pub duplicate_samples: u64,
+  /// How many times a NACK in response to a multicast HEARTBEAT was not sent,
+  /// because the missing samples arrived while the response was delayed.
+  pub suppressed_nacks: u64,
+  /// Received samples that were discarded, because their payload did not
+  /// match the checksum sent by the DataWriter. See
+  /// [`PayloadChecksum`](crate::policy::PayloadChecksum).
+  pub corrupted_samples: u64,
+}
+
 #[derive(Debug)] // these are not cloneable, because contained data may be large
 pub(crate) struct RtpsWriterProxy {
   /// Identifies the remote matched Writer
@@ -30,6 +68,12 @@ pub(crate) struct RtpsWriterProxy {
   /// Identifies the group to which the matched Reader belongs
   pub remote_group_entity_id: EntityId,
 
+  /// Effective reliability of the match, i.e. BestEffort unless both we and
+  /// the remote Writer are Reliable. When BestEffort, we do not wait for
+  /// missing changes or reply to HEARTBEATs. The Reader sets this when
+  /// matching.
+  pub reliability: policy::Reliability,
+
   // See RTPS Spec v2.5 Section 8.4.10.4 on how the WriterProxy is supposed to
   // operate.
   // And 8.4.10.5 on statuses of the (cache) changes received from a writer.
@@ -47,21 +91,19 @@ pub(crate) struct RtpsWriterProxy {
   // Received cannot transition to anything.
   // Not_available cannot transition to anything.
 
-  // We keep a map "changes" and a sequence number counters "ack_base", to keep track of these.
-
-  // changes.get(sn) is interpreted as follows:
-  // * Some(Some(timestamp)) = received at timestamp
-  // * Some(None) = not_available
-  // * None = any state, see below:
+  // We keep an interval set "known" and a sequence number counter "ack_base", to keep track
+  // of these. Received and not_available need not be distinguished, as neither can
+  // transition to anything else.
   //
   // All changes below ack_base are either received or not_available.
-  // All changes above hb_last are unknown (if they are not in "changes" map)
-  // All changes between ack_base and hb_last (inclusive) are missing.
-
-  // Timestamps are stored, because they are used as keys into the DDS Cache.
-  changes: BTreeMap<SequenceNumber, Option<Timestamp>>,
+  // Changes in "known" are either received or not_available. All of them are above ack_base.
+  // All changes above hb_last are unknown (if they are not in "known")
+  // All changes between ack_base and hb_last (inclusive) are missing, unless in "known".
+  //
+  // The set is stored as ranges, so that contiguous reception, or a GAP covering a large range,
+  // does not need storage or processing per sequence number.
+  known: SequenceNumberIntervals,
 
-  // The changes map is cleaned on heartbeat messages. The changes no longer available are dropped.
   pub received_heartbeat_count: i32,
 
   pub sent_ack_nack_count: i32,
@@ -73,7 +115,15 @@ pub(crate) struct RtpsWriterProxy {
 
   // These are used for quick tracking of
   last_received_sequence_number: SequenceNumber,
-  last_received_timestamp: Timestamp,
+  last_received_instant: Option<Instant>,
+
+  // From the latest HEARTBEAT, for statistics
+  last_heartbeat_timestamp: Option<Timestamp>,
+  last_heartbeat_last_sn: SequenceNumber,
+
+  duplicate_count: u64,
+  suppressed_nack_count: u64,
+  corrupted_count: u64,
   //fragment_assembler: Option<FragmentAssembler>,
 }
 
@@ -89,18 +139,28 @@ impl RtpsWriterProxy {
       unicast_locator_list,
       multicast_locator_list,
       remote_group_entity_id,
-      changes: BTreeMap::new(),
+      reliability: policy::Reliability::BestEffort,
+      known: SequenceNumberIntervals::default(),
       received_heartbeat_count: 0,
       sent_ack_nack_count: 0,
       // Sequence numbering must start at 1.
       // Therefore, we can ACK all sequence numbers below 1 even before receiving anything.
       ack_base: SequenceNumber::new(1),
       last_received_sequence_number: SequenceNumber::new(0),
-      last_received_timestamp: Timestamp::INVALID,
+      last_received_instant: None,
+      last_heartbeat_timestamp: None,
+      last_heartbeat_last_sn: SequenceNumber::zero(),
+      duplicate_count: 0,
+      suppressed_nack_count: 0,
+      corrupted_count: 0,
       //fragment_assembler: None,
     }
   }
 
+  pub fn is_reliable(&self) -> bool {
+    matches!(self.reliability, policy::Reliability::Reliable { .. })
+  }
+
   pub fn next_ack_nack_sequence_number(&mut self) -> i32 {
     let c = self.sent_ack_nack_count;
     self.sent_ack_nack_count += 1;
@@ -117,20 +177,47 @@ impl RtpsWriterProxy {
     self.unicast_locator_list = other.unicast_locator_list;
     self.multicast_locator_list = other.multicast_locator_list;
     self.remote_group_entity_id = other.remote_group_entity_id;
+    self.reliability = other.reliability;
   }
 
-  // This is used to check for DEADLINE policy
-  pub fn last_change_timestamp(&self) -> Option<Timestamp> {
-    if self.last_received_sequence_number > SequenceNumber::new(0) {
-      Some(self.last_received_timestamp)
-    } else {
-      None
+  // Record reception of a (new) HEARTBEAT
+  pub fn heartbeat_received(&mut self, count: i32, last_sn: SequenceNumber, now: Timestamp) {
+    self.received_heartbeat_count = count;
+    self.last_heartbeat_last_sn = last_sn;
+    self.last_heartbeat_timestamp = Some(now);
+  }
+
+  pub fn statistics(&self) -> WriterProxyStatistics {
+    WriterProxyStatistics {
+      writer_guid: self.remote_writer_guid,
+      reliability: self.reliability,
+      last_received_sequence_number: self.last_received_sequence_number,
+      missing_samples: self.missing_count(self.ack_base, self.last_heartbeat_last_sn) as usize,
+      heartbeat_count: self.received_heartbeat_count,
+      last_heartbeat_time: self.last_heartbeat_timestamp,
+      duplicate_samples: self.duplicate_count,
+      suppressed_nacks: self.suppressed_nack_count,
+      corrupted_samples: self.corrupted_count,
     }
   }
 
+  // This is used to check for DEADLINE policy
+  pub fn last_change_instant(&self) -> Option<Instant> {
+    self.last_received_instant
+  }
+
+  // Have we heard anything from this writer yet, i.e. do we know where its
+  // sequence numbering currently is? Before this, sequence numbers skipped over
+  // are not lost, but simply published before we were matched.
+  pub fn contact_established(&self) -> bool {
+    self.received_heartbeat_count > 0
+      || self.last_received_sequence_number > SequenceNumber::zero()
+      || self.ack_base > SequenceNumber::default()
+  }
+
   // Check if we no samples in the received state.
   pub fn no_changes_received(&self) -> bool {
-    self.ack_base == SequenceNumber::new(0) && self.changes.is_empty()
+    self.ack_base == SequenceNumber::new(0) && self.known.is_empty()
   }
 
   // Given an availability range from a HEARTBEAT, find out what we are missing.
@@ -138,12 +225,15 @@ impl RtpsWriterProxy {
   // Note: Heartbeat gives bounds only. Some samples within that range may
   // have been received already, or not really available, i.e. there may be GAPs
   // in the range.
+  //
+  // The result is limited to what can be reported in a single ACKNACK, i.e.
+  // less than MAX_MISSING_SEQNUMS_REPORTED starting from the first missing one.
+  // The rest will be requested after repairs.
   pub fn missing_seqnums(
     &self,
     hb_first_sn: SequenceNumber,
     hb_last_sn: SequenceNumber,
   ) -> Vec<SequenceNumber> {
-    // Need to verify first <= last, or BTreeMap::range will crash
     if hb_first_sn > hb_last_sn {
       if hb_first_sn > hb_last_sn + SequenceNumber::from(1) {
         warn!(
@@ -159,142 +249,151 @@ impl RtpsWriterProxy {
       return vec![];
     }
 
-    let mut missing_seqnums = Vec::with_capacity(32); // out of hat value
-
-    let relevant_interval = SequenceNumber::range_inclusive(
-      max(hb_first_sn, self.ack_base), // ignore those that we already have
-      hb_last_sn,
-    );
+    let mut missing_seqnums = Vec::new();
+    let mut report_limit = None;
 
-    // iterator over known Received and Not_available changes.
-    let known =
-      // again check for negative intervals, or BTreeMap::range will crash
-      if relevant_interval.begin() <= relevant_interval.end() {
-        self.changes
-          .range( relevant_interval )
-          .map(|e| *e.0)
-          .collect()
-      } else { vec![] };
-    let mut known_iter = known.iter();
-    let mut known_head = known_iter.next();
-
-    // Iterate over all SequenceNumbers (indices) in the advertised range.
-    for s in relevant_interval {
-      match known_head {
-        None => missing_seqnums.push(s), // no known changes left => s is missing
-        Some(known_sn) => {
-          // there are known changes left
-          if *known_sn == s {
-            // and the index sequence matches it => not missing
-            // => advance to next known change and continue iteration
-            known_head = known_iter.next();
-          } else {
-            // but it is not yet this index s => s is missing
-            missing_seqnums.push(s);
-          }
-        }
+    // ignore those that we already have
+    let begin = max(hb_first_sn, self.ack_base);
+    for (gap_begin, gap_end) in self.known.gaps(begin, hb_last_sn.plus_1()) {
+      let limit =
+        *report_limit.get_or_insert(gap_begin + SequenceNumber::new(MAX_MISSING_SEQNUMS_REPORTED));
+      if gap_begin >= limit {
+        break;
       }
+      missing_seqnums.extend(SequenceNumber::range_inclusive(
+        gap_begin,
+        min(gap_end, limit) - SequenceNumber::new(1),
+      ));
     }
 
     missing_seqnums
   }
 
+  // Number of missing changes in an availability range from a HEARTBEAT.
+  // Unlike missing_seqnums, this is not limited.
+  pub fn missing_count(&self, hb_first_sn: SequenceNumber, hb_last_sn: SequenceNumber) -> i64 {
+    let begin = max(hb_first_sn, self.ack_base);
+    let end = hb_last_sn.plus_1();
+    if begin >= end {
+      0
+    } else {
+      i64::from(end) - i64::from(begin) - self.known.count_in(begin, end)
+    }
+  }
+
   // Check if we have already received this sequence number
   // or it has been marked as not_available
   pub fn should_ignore_change(&self, seqnum: SequenceNumber) -> bool {
-    seqnum < self.ack_base || self.changes.contains_key(&seqnum)
+    seqnum < self.ack_base || self.known.contains(seqnum)
   }
 
-  // This is used to mark DATA as received.
-  pub fn received_changes_add(&mut self, seq_num: SequenceNumber, receive_timestamp: Timestamp) {
-    self.changes.insert(seq_num, Some(receive_timestamp));
+  pub fn duplicate_received(&mut self) {
+    self.duplicate_count += 1;
+  }
 
+  pub fn nack_suppressed(&mut self) {
+    self.suppressed_nack_count += 1;
+  }
+
+  pub fn corrupted_received(&mut self) {
+    self.corrupted_count += 1;
+  }
+
+  // How many sequence numbers are skipped over, if we next receive seq_num.
+  // This is used by BestEffort Readers, which do not wait for repairs, to
+  // count lost samples.
+  pub fn skipped_before(&self, seq_num: SequenceNumber) -> i64 {
+    if self.last_received_sequence_number > SequenceNumber::zero()
+      && seq_num > self.last_received_sequence_number
+    {
+      i64::from(seq_num) - i64::from(self.last_received_sequence_number) - 1
+    } else {
+      0
+    }
+  }
+
+  // This is used to mark DATA as received.
+  pub fn received_changes_add(&mut self, seq_num: SequenceNumber, reception_instant: Instant) {
     // Update deadline tracker
     if seq_num > self.last_received_sequence_number {
       self.last_received_sequence_number = seq_num;
-      self.last_received_timestamp = receive_timestamp;
+      self.last_received_instant = Some(reception_instant);
     }
 
-    // We get to advance ack_base if it was equal to seq_num
-    // If ack_base < seq_num, we are still missing seq_num-1 or others below
-    // If ack_base > seq_num, this is either a duplicate or ack_base was wrong.
+    // If ack_base > seq_num, this is a duplicate and there is nothing to record.
     // Remember, ack_base is the SN one past the last received/irrelevant SN.
-    if seq_num == self.ack_base {
-      self.advance_ack_base();
+    if seq_num >= self.ack_base {
+      self.known.insert(seq_num);
+      // We get to advance ack_base if it was equal to seq_num
+      // If ack_base < seq_num, we are still missing seq_num-1 or others below
+      if seq_num == self.ack_base {
+        self.advance_ack_base();
+      }
     }
   }
 
   // Used to add individual irrelevant changes from GAP message
-  pub fn set_irrelevant_change(&mut self, seq_num: SequenceNumber) {
+  //
+  // Returns the number of changes that were not received and now never will be,
+  // i.e. 1 or 0.
+  pub fn set_irrelevant_change(&mut self, seq_num: SequenceNumber) -> i64 {
+    let mut lost = 0;
     // If sequence number is still in the relevant range,
-    // insert not_available marker
-    if seq_num >= self.ack_base {
-      self.changes.insert(seq_num, None);
+    // mark it not_available
+    if seq_num >= self.ack_base && self.known.insert(seq_num) {
+      lost = 1;
     }
 
     if seq_num == self.ack_base {
       // ack_base can be advanced
       self.advance_ack_base();
     }
+    lost
   }
 
   // Used to add range of irrelevant changes from GAP submessage or unavailable
   // changes from HEARTBEAT submessage
+  //
+  // Returns the number of changes in the range that were neither received nor
+  // already marked not_available, i.e. changes that we have now lost.
   pub fn irrelevant_changes_range(
     &mut self,
     remove_from: SequenceNumber,
     remove_until_before: SequenceNumber,
-  ) {
+  ) -> i64 {
     // check sanity
     if remove_from > remove_until_before {
       error!(
         "irrelevant_changes_range: negative range: remove_from={:?} remove_until_before={:?}",
         remove_from, remove_until_before
       );
-      return;
+      return 0;
     }
     // now remove_from <= remove_until_before, i.e. at least zero to remove
     //
-    // Two cases here:
-    // If remove_from <= self.ack_base, then we may proceed by moving
-    // ack_base to remove_until_before and clearing "changes" before that.
-    //
-    // Else (remove_from > self.ack_base), which means we must insert not_available
-    // markers to "changes".
-    //
-    if remove_from <= self.ack_base {
-      let mut removed_and_after = self.changes.split_off(&remove_from);
-      let mut after = removed_and_after.split_off(&remove_until_before);
-      // let removed = removed_and_after;
-      self.changes.append(&mut after);
-
-      if remove_until_before > self.ack_base {
-        // Move the base to skip the irrelevant changes
-        self.ack_base = remove_until_before;
-        // The new base might be a sample that we already have, move the base forward
-        // until we hit a missing one
-        self.advance_ack_base();
-      }
-
+    // Everything below ack_base is already received or not_available, so only
+    // the part at or above ack_base is relevant. Within that, everything that we
+    // did not know about is lost.
+    let from = max(remove_from, self.ack_base);
+    let lost = self.known.insert_range(from, remove_until_before);
+
+    let old_ack_base = self.ack_base;
+    // The range may start at ack_base, or connect it to already received changes.
+    self.advance_ack_base();
+    if self.ack_base > old_ack_base {
       debug!(
         "ack_base increased to {:?} by irrelevant_changes_range {:?} to {:?}. writer={:?}",
         self.ack_base, remove_from, remove_until_before, self.remote_writer_guid
       );
-    } else {
-      // TODO: This potentially generates a very large BTreeMap
-      for na in
-        SequenceNumber::range_inclusive(remove_from, remove_until_before - SequenceNumber::new(1))
-      {
-        self.changes.insert(na, None);
-      }
     }
+    lost
   }
 
   // Used to mark messages irrelevant because of a HEARTBEAT message.
   //
   // smallest_seqnum is the lowest key to be retained
-  pub fn irrelevant_changes_up_to(&mut self, smallest_seqnum: SequenceNumber) {
-    self.irrelevant_changes_range(SequenceNumber::new(0), smallest_seqnum);
+  pub fn irrelevant_changes_up_to(&mut self, smallest_seqnum: SequenceNumber) -> i64 {
+    self.irrelevant_changes_range(SequenceNumber::new(0), smallest_seqnum)
   }
 
   fn discovered_or_default(drd: &[Locator], default: &[Locator]) -> Vec<Locator> {
@@ -324,36 +423,227 @@ impl RtpsWriterProxy {
       remote_group_entity_id: EntityId::UNKNOWN,
       unicast_locator_list,
       multicast_locator_list,
-      changes: BTreeMap::new(),
+      reliability: policy::Reliability::BestEffort,
+      known: SequenceNumberIntervals::default(),
       received_heartbeat_count: 0,
       sent_ack_nack_count: 0,
       ack_base: SequenceNumber::default(),
       last_received_sequence_number: SequenceNumber::new(0),
-      last_received_timestamp: Timestamp::INVALID,
+      last_received_instant: None,
+      last_heartbeat_timestamp: None,
+      last_heartbeat_last_sn: SequenceNumber::zero(),
+      duplicate_count: 0,
+      suppressed_nack_count: 0,
+      corrupted_count: 0,
       //fragment_assembler: None,
     }
   } // fn
 
   // Advance ack_base as far as possible
   // This function should be called after the writer proxy has modified its
-  // changes cache (for instance added a new received change) such that ack_base
+  // known changes (for instance added a new received change) such that ack_base
   // could be advanced
   fn advance_ack_base(&mut self) {
-    // Start searching from current ack_base
-    let mut test_sn = self.ack_base;
+    // Ranges are merged when they touch, so at most one range can start at
+    // ack_base.
+    if let Some(end) = self.known.take_range_starting_at(self.ack_base) {
+      self.ack_base = end;
+    }
+  }
+} // impl
 
-    for (&sn, _what) in self.changes.range((Included(&self.ack_base), Unbounded)) {
-      if sn == test_sn {
-        // test_sn found from changes, ack_base can be set to test_sn + 1
-        test_sn = test_sn + SequenceNumber::new(1);
-      } else {
-        // test_sn not found from changes, stop here
-        break;
+// A SequenceNumberSet in ACKNACK can cover at most this many sequence numbers.
+const MAX_MISSING_SEQNUMS_REPORTED: i64 = 256;
+
+// A set of sequence numbers, stored as disjoint half-open ranges
+// begin..end, keyed by begin. Ranges that touch are merged, so a contiguous
+// run of sequence numbers is always a single entry, no matter how long.
+#[derive(Debug, Default)]
+struct SequenceNumberIntervals {
+  ranges: BTreeMap<SequenceNumber, SequenceNumber>,
+}
+
+impl SequenceNumberIntervals {
+  fn is_empty(&self) -> bool {
+    self.ranges.is_empty()
+  }
+
+  fn contains(&self, sn: SequenceNumber) -> bool {
+    self
+      .ranges
+      .range(..=sn)
+      .next_back()
+      .is_some_and(|(_, &end)| sn < end)
+  }
+
+  // Number of members in begin..end
+  fn count_in(&self, begin: SequenceNumber, end: SequenceNumber) -> i64 {
+    if begin >= end {
+      return 0;
+    }
+    // A range that starts before begin may extend past it.
+    let straddling = self
+      .ranges
+      .range(..begin)
+      .next_back()
+      .map(|(_, &e)| (begin, e));
+    let inside = self.ranges.range(begin..end).map(|(&b, &e)| (b, e));
+    straddling
+      .into_iter()
+      .chain(inside)
+      .filter(|(b, e)| b < e)
+      .map(|(b, e)| i64::from(min(e, end)) - i64::from(b))
+      .sum()
+  }
+
+  // Non-members within begin..end, as half-open ranges in ascending order
+  fn gaps(
+    &self,
+    begin: SequenceNumber,
+    end: SequenceNumber,
+  ) -> Vec<(SequenceNumber, SequenceNumber)> {
+    let mut gaps = Vec::new();
+    if begin >= end {
+      return gaps;
+    }
+    let mut cursor = begin;
+    if let Some((_, &e)) = self.ranges.range(..begin).next_back() {
+      cursor = max(cursor, e);
+    }
+    for (&b, &e) in self.ranges.range(begin..end) {
+      if b > cursor {
+        gaps.push((cursor, b));
       }
+      cursor = max(cursor, e);
+    }
+    if cursor < end {
+      gaps.push((cursor, end));
+    }
+    gaps
+  }
+
+  // Returns true if sn was not a member before.
+  fn insert(&mut self, sn: SequenceNumber) -> bool {
+    self.insert_range(sn, sn.plus_1()) == 1
+  }
 
-      // The changes cache contains a string of consecutive sequence numbers from
-      // ack_base-1 up to test_sn (excluded), so ack_base can be set to test_sn
-      self.ack_base = test_sn;
+  // Adds begin..end. Returns the number of sequence numbers that were not
+  // members before.
+  fn insert_range(&mut self, begin: SequenceNumber, end: SequenceNumber) -> i64 {
+    if begin >= end {
+      return 0;
+    }
+    let added = i64::from(end) - i64::from(begin) - self.count_in(begin, end);
+
+    let mut new_begin = begin;
+    let mut new_end = end;
+    // Merge with a preceding range that overlaps or touches begin
+    if let Some((&b, &e)) = self.ranges.range(..begin).next_back() {
+      if e >= begin {
+        new_begin = b;
+        new_end = max(new_end, e);
+        self.ranges.remove(&b);
+      }
+    }
+    // Absorb ranges that start within the new range, or touch its end
+    let absorbed: Vec<_> = self
+      .ranges
+      .range(begin..=end)
+      .map(|(&b, &e)| (b, e))
+      .collect();
+    for (b, e) in absorbed {
+      new_end = max(new_end, e);
+      self.ranges.remove(&b);
     }
+    self.ranges.insert(new_begin, new_end);
+    added
   }
-} // impl
+
+  // Removes the range beginning exactly at begin, and returns its end.
+  fn take_range_starting_at(&mut self, begin: SequenceNumber) -> Option<SequenceNumber> {
+    self.ranges.remove(&begin)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::structure::guid::EntityKind;
+
+  fn sn(n: i64) -> SequenceNumber {
+    SequenceNumber::new(n)
+  }
+
+  fn new_proxy() -> RtpsWriterProxy {
+    RtpsWriterProxy::new(
+      GUID::dummy_test_guid(EntityKind::WRITER_NO_KEY_USER_DEFINED),
+      vec![],
+      vec![],
+      EntityId::UNKNOWN,
+    )
+  }
+
+  #[test]
+  fn intervals_merge_and_count() {
+    let mut set = SequenceNumberIntervals::default();
+    assert!(set.insert(sn(5)));
+    assert!(!set.insert(sn(5)));
+    assert_eq!(set.insert_range(sn(7), sn(10)), 3);
+    assert!(set.insert(sn(6))); // joins 5 and 7..10
+    assert_eq!(set.ranges.len(), 1);
+    assert_eq!(set.count_in(sn(0), sn(100)), 5);
+    assert_eq!(set.insert_range(sn(3), sn(12)), 4);
+    assert_eq!(set.ranges.len(), 1);
+    assert!(set.contains(sn(3)) && set.contains(sn(11)) && !set.contains(sn(12)));
+    assert_eq!(
+      set.gaps(sn(1), sn(15)),
+      vec![(sn(1), sn(3)), (sn(12), sn(15))]
+    );
+  }
+
+  #[test]
+  fn writer_proxy_tracks_received_and_missing() {
+    let mut proxy = new_proxy();
+    let now = Instant::now();
+    proxy.received_changes_add(sn(1), now);
+    proxy.received_changes_add(sn(3), now);
+    proxy.received_changes_add(sn(4), now);
+    assert_eq!(proxy.all_ackable_before(), sn(2));
+    assert_eq!(
+      proxy.missing_seqnums(sn(1), sn(6)),
+      vec![sn(2), sn(5), sn(6)]
+    );
+    assert_eq!(proxy.missing_count(sn(1), sn(6)), 3);
+    assert!(proxy.should_ignore_change(sn(3)));
+
+    // Receiving the missing one joins the ranges
+    proxy.received_changes_add(sn(2), now);
+    assert_eq!(proxy.all_ackable_before(), sn(5));
+
+    // GAP for 5..8, of which 5 and 6 were missing and 7 unknown
+    assert_eq!(proxy.irrelevant_changes_range(sn(5), sn(8)), 3);
+    assert_eq!(proxy.all_ackable_before(), sn(8));
+    assert_eq!(proxy.set_irrelevant_change(sn(7)), 0);
+  }
+
+  #[test]
+  fn writer_proxy_handles_large_sequence_numbers() {
+    let mut proxy = new_proxy();
+    let now = Instant::now();
+    let high = 1_i64 << 40;
+    proxy.received_changes_add(sn(high), now);
+    // This must not iterate over each sequence number.
+    assert_eq!(proxy.missing_count(sn(1), sn(high + 10)), high + 9);
+    let missing = proxy.missing_seqnums(sn(1), sn(high + 10));
+    assert_eq!(missing.len(), MAX_MISSING_SEQNUMS_REPORTED as usize);
+    assert_eq!(missing[0], sn(1));
+
+    assert_eq!(proxy.irrelevant_changes_up_to(sn(high - 2)), high - 3);
+    assert_eq!(
+      proxy.missing_seqnums(sn(1), sn(high + 1)),
+      vec![sn(high - 2), sn(high - 1), sn(high + 1)]
+    );
+    assert_eq!(proxy.irrelevant_changes_up_to(sn(high)), 2);
+    assert_eq!(proxy.all_ackable_before(), sn(high + 1));
+  }
+}
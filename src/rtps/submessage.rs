@@ -4,6 +4,7 @@ use bytes::Bytes;
 use enumflags2::BitFlags;
 use log::{debug, error, trace};
 use speedy::{Context, Readable, Writable, Writer};
+use rustdds_rtps_core as rtps_core;
 
 use crate::{
   messages::submessages::{
@@ -23,6 +24,7 @@ use crate::{
     submessage_kind::SubmessageKind,
     submessages::{Data, DataFrag, Gap, InfoReply, InterpreterSubmessage},
   },
+  rtps::message::SCATTER_GATHER_MIN_PAYLOAD,
   Timestamp,
 };
 #[cfg(feature = "security")]
@@ -45,7 +47,8 @@ pub struct Submessage {
   pub body: SubmessageBody,
   pub original_bytes: Option<Bytes>,
   // original_bytes contains the original bytes if Submessage was created by parsing from Bytes.
-  // If message was constructed from components instead, it is None.
+  // If message was constructed from components instead, it is None, unless it was serialized
+  // for reuse.
 }
 
 // We implement this instead of Speedy trait Readable, because
@@ -54,10 +57,9 @@ pub struct Submessage {
 // endianness. TODO: The error type should be something better
 impl Submessage {
   pub fn read_from_buffer(buffer: &mut Bytes) -> io::Result<Option<Self>> {
-    let sub_header = SubmessageHeader::read_from_buffer(buffer)
-      .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let sub_header = SubmessageHeader::decode(buffer)?;
     // Try to figure out how large this submessage is.
-    let sub_header_length = 4; // 4 bytes
+    let sub_header_length = rtps_core::SUBMESSAGE_HEADER_LEN;
     let proposed_sub_content_length = if sub_header.content_length == 0 {
       // RTPS spec 2.3, section 9.4.5.1.3:
       //           In case octetsToNextHeader==0 and the kind of Submessage is
@@ -156,7 +158,7 @@ impl Submessage {
       SubmessageKind::GAP => {
         let f = BitFlags::<GAP_Flags>::from_bits_truncate(sub_header.flags);
         mk_w_subm(WriterSubmessage::Gap(
-          Gap::read_from_buffer_with_ctx(e, &sub_content_buffer)?,
+          Gap::decode(f, &sub_content_buffer)?,
           f,
         ))
       }
@@ -164,7 +166,7 @@ impl Submessage {
       SubmessageKind::ACKNACK => {
         let f = BitFlags::<ACKNACK_Flags>::from_bits_truncate(sub_header.flags);
         mk_r_subm(ReaderSubmessage::AckNack(
-          AckNack::read_from_buffer_with_ctx(e, &sub_content_buffer)?,
+          AckNack::decode(f, &sub_content_buffer)?,
           f,
         ))
       }
@@ -180,7 +182,7 @@ impl Submessage {
       SubmessageKind::HEARTBEAT => {
         let f = BitFlags::<HEARTBEAT_Flags>::from_bits_truncate(sub_header.flags);
         mk_w_subm(WriterSubmessage::Heartbeat(
-          Heartbeat::read_from_buffer_with_ctx(e, &sub_content_buffer)?,
+          Heartbeat::decode(f, &sub_content_buffer)?,
           f,
         ))
       }
@@ -268,9 +270,7 @@ impl Submessage {
         ))
       }
       unknown_kind => {
-        let kind = u8::from(unknown_kind);
-        if kind >= 0x80 {
-          // Kinds 0x80 - 0xFF are vendor-specific.
+        if unknown_kind.is_vendor_specific() {
           trace!(
             "Received vendor-specific submessage kind {:?}",
             unknown_kind
@@ -285,6 +285,50 @@ impl Submessage {
       }
     } // match
   }
+
+  // The serialized payload of a DATA or DATA_FRAG submessage. It is the last
+  // element of the submessage.
+  pub(crate) fn payload(&self) -> Option<&Bytes> {
+    match &self.body {
+      SubmessageBody::Writer(WriterSubmessage::Data(data, _)) => data.serialized_payload.as_ref(),
+      SubmessageBody::Writer(WriterSubmessage::DataFrag(data_frag, _)) => {
+        Some(&data_frag.serialized_payload)
+      }
+      _ => None,
+    }
+  }
+
+  // A copy of this, where the serialized payload is empty, but the header is
+  // not changed. Serializing this gives the bytes before the payload.
+  pub(crate) fn without_payload(&self) -> Self {
+    let mut submessage = self.clone();
+    match &mut submessage.body {
+      SubmessageBody::Writer(WriterSubmessage::Data(data, _))
+        if data.serialized_payload.is_some() =>
+      {
+        data.serialized_payload = Some(Bytes::new());
+      }
+      SubmessageBody::Writer(WriterSubmessage::DataFrag(data_frag, _)) => {
+        data_frag.serialized_payload = Bytes::new();
+      }
+      _ => {}
+    }
+    submessage
+  }
+
+  // Serializes this into `original_bytes`, when it is sent in several messages.
+  // `Message::write_to_chunks` then copies the bytes instead of serializing
+  // this again. A large payload is not copied at all, so such a submessage is
+  // left as it is.
+  pub(crate) fn serialized_for_reuse(mut self) -> Self {
+    let large_payload = self
+      .payload()
+      .is_some_and(|payload| payload.len() >= SCATTER_GATHER_MIN_PAYLOAD);
+    if !large_payload {
+      self.original_bytes = self.write_to_vec().ok().map(Bytes::from);
+    }
+    self
+  }
 }
 
 /// See section 7.3.1 of the Security specification (v. 1.1)
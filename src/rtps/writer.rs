@@ -1,14 +1,20 @@
 use std::{
-  cmp::max,
-  collections::{BTreeMap, BTreeSet, HashSet},
-  ops::Bound::Included,
+  cell::{Cell, RefCell},
+  cmp::{max, min},
+  collections::{BTreeMap, BTreeSet, HashSet, VecDeque},
+  ops::RangeInclusive,
   rc::Rc,
-  sync::{Arc, Mutex, MutexGuard},
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex, MutexGuard,
+  },
+  time::Instant,
 };
 use core::task::Waker;
 
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
+use rand::Rng;
 use speedy::{Endianness, Writable};
 use mio_extras::{
   channel::{self as mio_channel, TrySendError},
@@ -18,10 +24,13 @@ use mio_06::Token;
 
 use crate::{
   dds::{
+    checksum, compression,
     ddsdata::DDSData,
+    delivery::{DeliveryListenerSlot, DeliveryTracker, DropReason},
+    key::KeyHash,
     qos::{
       policy,
-      policy::{History, Reliability},
+      policy::{History, PublishMode, Reliability},
       HasQoSPolicy, QosPolicies,
     },
     statusevents::{
@@ -29,13 +38,18 @@ use crate::{
     },
     with_key::datawriter::WriteOptions,
   },
-  messages::submessages::submessages::AckSubmessage,
+  messages::submessages::{
+    elements::serialized_payload::SerializedPayload,
+    submessages::{AckSubmessage, InterpreterSubmessage, WriterSubmessage},
+  },
   network::udp_sender::UDPSender,
   rtps::{
-    constant::{NACK_RESPONSE_DELAY, NACK_SUPPRESSION_DURATION},
-    rtps_reader_proxy::RtpsReaderProxy,
-    Message, MessageBuilder,
+    constant::{NACK_RESPONSE_DELAY, NACK_SUPPRESSION_DURATION, SEDP_BATCH_MAX_BYTES},
+    local_endpoints,
+    rtps_reader_proxy::{NotSentReason, RtpsReaderProxy},
+    Message, MessageBuilder, Submessage, SubmessageBody,
   },
+  stats::WriterStats,
   structure::{
     cache_change::CacheChange,
     dds_cache::TopicCache,
@@ -46,12 +60,10 @@ use crate::{
     sequence_number::{FragmentNumber, SequenceNumber},
     time::Timestamp,
   },
+  trace::{EndpointTrace, TraceSampling},
 };
 #[cfg(feature = "security")]
-use crate::{
-  rtps::Submessage,
-  security::{security_plugins::SecurityPluginsHandle, SecurityResult},
-};
+use crate::security::{security_plugins::SecurityPluginsHandle, SecurityResult};
 #[cfg(not(feature = "security"))]
 use crate::no_security::SecurityPluginsHandle;
 
@@ -63,12 +75,68 @@ pub enum DeliveryMode {
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub(crate) enum TimedEvent {
-  Heartbeat,
+  ReaderHeartbeat { to_reader: GUID, timer: u64 },
   CacheCleaning,
+  AsyncSend,
   SendRepairData { to_reader: GUID },
   SendRepairFrags { to_reader: GUID },
+  FlushBatch { batch: u64 },
+}
+
+/// When the DataWriters of a DomainParticipant remove acknowledged samples
+/// from their history. Samples kept by the
+/// [`History`](crate::policy::History) QoS policy are not removed.
+///
+/// The history is cleaned as soon as all matched readers have acknowledged
+/// `acked_samples` samples, or `acked_bytes` bytes of serialized data, since
+/// the previous cleaning. Otherwise it is cleaned `fallback_period` after a
+/// write or acknowledgement, so that idle DataWriters are not woken up.
+///
+/// Use
+/// [`DomainParticipantBuilder::writer_cache_cleaning`](crate::DomainParticipantBuilder::writer_cache_cleaning)
+/// to change these.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WriterCacheCleaning {
+  /// Number of acknowledged samples that triggers cleaning
+  pub acked_samples: usize,
+  /// Number of acknowledged bytes that triggers cleaning
+  pub acked_bytes: usize,
+  /// Delay of cleaning after a write or acknowledgement, if neither of the
+  /// above is reached
+  pub fallback_period: std::time::Duration,
+}
+
+impl Default for WriterCacheCleaning {
+  fn default() -> Self {
+    Self {
+      acked_samples: 256,
+      acked_bytes: 1 << 20,
+      fallback_period: std::time::Duration::from_secs(2 * 60),
+    }
+  }
 }
 
+// Size of the messages that may wait in the send queue of
+// PublishMode::Asynchronous. When the queue is this full, the Writer stops
+// taking samples from the DataWriter until the flow controller has sent some.
+// Then the DataWriter to Writer channel fills up, and writes block as usual.
+const ASYNC_SEND_QUEUE_MAX_BYTES: usize = 4 * 1024 * 1024;
+
+// A message waiting in the send queue of PublishMode::Asynchronous
+struct AsyncSend {
+  message: Message,
+  // The single target reader, or None for all readers
+  target_reader: Option<GUID>,
+  // The message is dropped, if it is still waiting at this time
+  send_deadline: Option<Instant>,
+  // This is the last message of a sample, so a HEARTBEAT is added to it when
+  // it is sent.
+  ends_sample: bool,
+}
+
+// Smoothed round trip times to matched readers, by reader GUID
+pub(crate) type RoundTripTimes = Arc<Mutex<BTreeMap<GUID, std::time::Duration>>>;
+
 // This is used to construct an actual Writer.
 // Ingredients are sendable between threads, whereas the Writer is not.
 pub(crate) struct WriterIngredients {
@@ -81,6 +149,10 @@ pub(crate) struct WriterIngredients {
   pub(crate) like_stateless: bool, // Usually false (see like_stateless attribute of Writer)
   pub qos_policies: QosPolicies,
   pub status_sender: StatusChannelSender<DataWriterStatus>,
+  pub(crate) stale_samples_dropped: Arc<AtomicU64>, // shared with DataWriter
+  pub(crate) round_trip_times: RoundTripTimes,      // shared with DataWriter
+  pub(crate) delivery_listener: DeliveryListenerSlot, // shared with DataWriter
+  pub(crate) endianness: Endianness,                // of RTPS submessages sent
 
   pub(crate) security_plugins: Option<SecurityPluginsHandle>,
 }
@@ -123,7 +195,9 @@ impl AckWaiter {
 
 pub(crate) struct Writer {
   pub endianness: Endianness,
-  pub heartbeat_message_counter: i32,
+  // Count of the latest HEARTBEAT sent. RTPS spec 8.3.7.5: The count is
+  // incremented for each HEARTBEAT, so that readers can detect duplicates.
+  heartbeat_count: Cell<i32>,
   /// Configures the mode in which the
   /// Writer operates. If
   /// pushMode==true, then the Writer
@@ -139,8 +213,19 @@ pub(crate) struct Writer {
   /// availability of data by sending a
   /// Heartbeat Message.
   pub heartbeat_period: Option<Duration>,
-  /// duration to launch cache change remove from DDSCache
-  pub cache_cleaning_period: Duration,
+  /// When to remove acknowledged changes from DDSCache
+  cache_cleaning: WriterCacheCleaning,
+  cache_cleaning_scheduled: bool,
+  // What all readers had acknowledged at the previous cleaning, and the
+  // payload bytes acknowledged after that, counted up to
+  // acked_bytes_counted_before.
+  cleaned_acked_before: SequenceNumber,
+  acked_bytes: usize,
+  acked_bytes_counted_before: SequenceNumber,
+  // Id of the latest per-reader heartbeat timer. A timer that does not match
+  // the id in its reader proxy is stale, e.g. because the reader was removed
+  // and then matched again.
+  heartbeat_timer_id: u64,
   /// Protocol tuning parameter that
   /// allows the RTPS Writer to delay
   /// the response to a request for data
@@ -210,6 +295,10 @@ pub(crate) struct Writer {
   /// Useful when negative acknack is received.
   sequence_number_to_instant: BTreeMap<SequenceNumber, Timestamp>,
 
+  /// Sequence numbers below this that are missing from
+  /// sequence_number_to_instant have been removed by history compaction.
+  compacted_before: SequenceNumber,
+
   /// Maps this writers local sequence numbers to DDSHistoryCache instants.
   /// Useful when datawriter dispose is received.
   // key_to_instant: HashMap<u128, Timestamp>,  // unused?
@@ -231,6 +320,9 @@ pub(crate) struct Writer {
   pub(crate) timed_event_timer: Timer<TimedEvent>,
 
   qos_policies: QosPolicies,
+  // Set when the QoS has been updated. The event loop then re-evaluates
+  // matching with remote readers.
+  qos_updated: bool,
 
   // Used for sending status info about messages sent
   status_sender: StatusChannelSender<DataWriterStatus>,
@@ -238,6 +330,48 @@ pub(crate) struct Writer {
   ack_waiter: Option<AckWaiter>,
   participant_status_sender: StatusChannelSender<DomainParticipantStatusEvent>,
 
+  // Count of samples that were dropped, because they waited in the DataWriter
+  // to Writer queue past their send deadline. The DataWriter reads this.
+  stale_samples_dropped: Arc<AtomicU64>,
+
+  // Smoothed round trip times to the matched readers that have been measured.
+  // The DataWriter reads these.
+  round_trip_times: RoundTripTimes,
+
+  // Reports delivery of samples to the listener of the DataWriter
+  delivery_tracker: DeliveryTracker,
+
+  // Messages waiting to be sent in PublishMode::Asynchronous
+  async_send_queue: VecDeque<AsyncSend>,
+  async_send_queue_bytes: usize,
+  async_send_queue_max_bytes: usize,
+  async_send_scheduled: bool,
+  // Commands from the DataWriter are not processed, because the send queue is
+  // full
+  commands_paused: bool,
+
+  // The most recently built DATA submessage, keyed by sequence number and
+  // reader EntityId, with its serialized bytes. When the same sample is sent
+  // to several readers one by one, e.g. as repairs, it is encoded and
+  // serialized only once. Only the INFO_DST differs between the messages.
+  data_submessage_cache: RefCell<Option<(SequenceNumber, EntityId, Submessage)>>,
+
+  // If set, samples written in a row are sent together in as few messages as
+  // possible. This is done for the SEDP writers, which may have to announce
+  // thousands of endpoints at once.
+  batch_writes: bool,
+  // Changes held back by the Batching QoS policy, and the number of the batch
+  // they belong to. The number tells apart stale flush timers.
+  pending_batch: Vec<Timestamp>,
+  batch_number: u64,
+  // Set while the Publisher has suspended publications. This is the last
+  // sequence number written before that. Later changes are held back in
+  // pending_batch and not announced in HEARTBEATs until resumed.
+  suspended_at: Option<SequenceNumber>,
+
+  stats: WriterStats,
+  trace: EndpointTrace,
+
   security_plugins: Option<SecurityPluginsHandle>,
 }
 //#[derive(Clone)]
@@ -247,10 +381,28 @@ pub enum WriterCommand {
     ddsdata: DDSData,
     write_options: WriteOptions,
     sequence_number: SequenceNumber,
+    // If set, the sample is dropped unsent if it is processed after this.
+    send_deadline: Option<Instant>,
   },
   WaitForAcknowledgments {
     all_acked: StatusChannelSender<()>,
   },
+  // DataWriter QoS was changed. Only changeable policies differ.
+  UpdateQos {
+    qos_policies: Box<QosPolicies>,
+  },
+  GetMatchedReaders {
+    reply: std::sync::mpsc::SyncSender<Vec<GUID>>,
+  },
+  // Send the changes held back by the Batching QoS policy now
+  Flush,
+  // Publisher suspends or resumes publications. Changes written in between
+  // are sent together on resume.
+  SuspendPublications,
+  ResumePublications,
+  SetEndianness {
+    endianness: Endianness,
+  },
   // ResetOfferedDeadlineMissedStatus { writer_guid: GUID },
 }
 
@@ -258,8 +410,9 @@ impl Writer {
   pub fn new(
     i: WriterIngredients,
     udp_sender: Rc<UDPSender>,
-    mut timed_event_timer: Timer<TimedEvent>,
+    timed_event_timer: Timer<TimedEvent>,
     participant_status_sender: StatusChannelSender<DomainParticipantStatusEvent>,
+    cache_cleaning: WriterCacheCleaning,
   ) -> Self {
     // Verify that the topic cache corresponds to the topic of the Reader
     let topic_cache_name = i.topic_cache_handle.lock().unwrap().topic_name();
@@ -298,27 +451,26 @@ impl Writer {
         }
       });
 
-    // TODO: Configuration value
-    let cache_cleaning_period = Duration::from_secs(2 * 60);
-
-    // Start periodic Heartbeat
-    if let Some(period) = heartbeat_period {
-      timed_event_timer.set_timeout(std::time::Duration::from(period), TimedEvent::Heartbeat);
-    }
-    // start periodic cache cleaning
-    timed_event_timer.set_timeout(
-      std::time::Duration::from(cache_cleaning_period),
-      TimedEvent::CacheCleaning,
-    );
+    // Periodic heartbeats are scheduled per reader, when readers are matched.
+    // Cache cleaning is scheduled on writes and acknowledgements.
 
     // TODO: call register_local_datawriter
 
+    let stats = WriterStats::new(&i.topic_name, i.guid);
+    let trace = EndpointTrace::writer(&i.topic_name, i.guid);
+    local_endpoints::add_endpoint(i.guid, i.qos_policies.ignore_local());
+
     Self {
-      endianness: Endianness::LittleEndian,
-      heartbeat_message_counter: 1,
+      endianness: i.endianness,
+      heartbeat_count: Cell::new(0),
       push_mode: true,
       heartbeat_period,
-      cache_cleaning_period,
+      cache_cleaning,
+      cache_cleaning_scheduled: false,
+      cleaned_acked_before: SequenceNumber::from(1),
+      acked_bytes: 0,
+      acked_bytes_counted_before: SequenceNumber::from(1),
+      heartbeat_timer_id: 0,
       nack_response_delay: NACK_RESPONSE_DELAY, // default value from dp_event_loop
       nackfrag_response_delay: NACK_RESPONSE_DELAY, // default value from dp_event_loop
       repairfrags_continue_delay: std::time::Duration::from_millis(1),
@@ -338,13 +490,35 @@ impl Writer {
       topic_cache: i.topic_cache_handle,
       my_topic_name: i.topic_name,
       sequence_number_to_instant: BTreeMap::new(),
+      compacted_before: SequenceNumber::zero(),
       disposed_sequence_numbers: HashSet::new(),
       timed_event_timer,
       like_stateless: i.like_stateless,
       qos_policies: i.qos_policies,
+      qos_updated: false,
       status_sender: i.status_sender,
       participant_status_sender,
       ack_waiter: None,
+      stale_samples_dropped: i.stale_samples_dropped,
+      round_trip_times: i.round_trip_times,
+      delivery_tracker: DeliveryTracker::new(i.delivery_listener),
+      async_send_queue: VecDeque::new(),
+      async_send_queue_bytes: 0,
+      async_send_queue_max_bytes: ASYNC_SEND_QUEUE_MAX_BYTES,
+      async_send_scheduled: false,
+      commands_paused: false,
+      data_submessage_cache: RefCell::new(None),
+      batch_writes: matches!(
+        i.guid.entity_id,
+        EntityId::SEDP_BUILTIN_PUBLICATIONS_WRITER
+          | EntityId::SEDP_BUILTIN_SUBSCRIPTIONS_WRITER
+          | EntityId::SEDP_BUILTIN_TOPIC_WRITER
+      ),
+      pending_batch: Vec::new(),
+      batch_number: 0,
+      suspended_at: None,
+      stats,
+      trace,
 
       security_plugins: i.security_plugins,
     }
@@ -360,21 +534,8 @@ impl Writer {
     self.qos_policies.is_reliable()
   }
 
-  pub fn local_readers(&self) -> Vec<EntityId> {
-    let min = GUID::new_with_prefix_and_id(self.my_guid.prefix, EntityId::MIN);
-    let max = GUID::new_with_prefix_and_id(self.my_guid.prefix, EntityId::MAX);
-
-    self
-      .readers
-      .range((Included(min), Included(max)))
-      .filter_map(|(guid, _)| {
-        if guid.prefix == self.my_guid.prefix {
-          Some(guid.entity_id)
-        } else {
-          None
-        }
-      })
-      .collect()
+  pub(crate) fn set_trace_sampling(&mut self, sampling: TraceSampling) {
+    self.trace.set_sampling(sampling);
   }
 
   // --------------------------------------------------------------
@@ -384,34 +545,39 @@ impl Writer {
   pub fn handle_timed_event(&mut self) {
     while let Some(e) = self.timed_event_timer.poll() {
       match e {
-        TimedEvent::Heartbeat => {
-          self.handle_heartbeat_tick(false);
-          // ^^ false = This is automatic heartbeat by timer, not manual by application
-          // call.
-          if let Some(period) = self.heartbeat_period {
-            self
-              .timed_event_timer
-              .set_timeout(std::time::Duration::from(period), TimedEvent::Heartbeat);
+        TimedEvent::ReaderHeartbeat {
+          to_reader: reader_guid,
+          timer,
+        } => {
+          self.handle_reader_heartbeat(reader_guid, timer);
+        }
+        TimedEvent::AsyncSend => {
+          self.handle_async_send();
+          if self.commands_paused && !self.async_send_queue_full() {
+            // Take the samples that the DataWriter could not hand over
+            self.process_writer_command();
           }
         }
         TimedEvent::CacheCleaning => {
+          self.cache_cleaning_scheduled = false;
           self.handle_cache_cleaning();
-          self.timed_event_timer.set_timeout(
-            std::time::Duration::from(self.cache_cleaning_period),
-            TimedEvent::CacheCleaning,
-          );
         }
         TimedEvent::SendRepairData {
           to_reader: reader_guid,
         } => {
           self.handle_repair_data_send(reader_guid);
-          if let Some(rp) = self.lookup_reader_proxy_mut(reader_guid) {
+          if let Some(rp) = self.readers.get(&reader_guid) {
             if rp.repair_mode {
-              let delay_to_next_repair = self
-                .qos_policies
-                .deadline()
-                .map_or_else(|| Duration::from_millis(100), |dl| dl.0)
-                / 5;
+              let delay_to_next_repair = match self.qos_policies.history_replay() {
+                Some(replay) if rp.is_replaying() => replay.batch_period,
+                _ => {
+                  self
+                    .qos_policies
+                    .deadline()
+                    .map_or_else(|| Duration::from_millis(100), |dl| dl.0)
+                    / 5
+                }
+              };
               self.timed_event_timer.set_timeout(
                 std::time::Duration::from(delay_to_next_repair),
                 TimedEvent::SendRepairData {
@@ -437,11 +603,18 @@ impl Writer {
             } // if
           } // if let
         } // SendRepairFrags
+        TimedEvent::FlushBatch { batch } => {
+          // The batch may have been flushed already
+          if batch == self.batch_number {
+            self.flush_batch();
+          }
+        }
       } // match
     } // while
   } // fn
 
-  /// This is called by dp_wrapper every time cacheCleaning message is received.
+  /// This is called when enough has been acknowledged since the previous
+  /// cleaning, or when the fallback timer expires.
   fn handle_cache_cleaning(&mut self) {
     let resource_limit = 32; // TODO: This limit should be obtained
                              // from Topic and Writer QoS. There should be some reasonable default limit
@@ -461,6 +634,48 @@ impl Writer {
     }
   }
 
+  // Starts the fallback timer of cache cleaning, unless it is running already
+  fn schedule_cache_cleaning(&mut self) {
+    if !self.cache_cleaning_scheduled {
+      self.cache_cleaning_scheduled = true;
+      self.timed_event_timer.set_timeout(
+        self.cache_cleaning.fallback_period,
+        TimedEvent::CacheCleaning,
+      );
+    }
+  }
+
+  // Cleans the cache, if all readers have acknowledged enough since the
+  // previous cleaning. Otherwise the fallback timer will do it.
+  fn clean_cache_on_ack_progress(&mut self) {
+    let acked_before = self.acked_before();
+    if acked_before <= self.cleaned_acked_before {
+      return;
+    }
+    // Count only the newly acknowledged changes
+    let count_from = max(self.acked_bytes_counted_before, self.cleaned_acked_before);
+    if count_from < acked_before {
+      let topic_cache = self.acquire_the_topic_cache_guard();
+      let newly_acked_bytes: usize = self
+        .sequence_number_to_instant
+        .range(count_from..acked_before)
+        .filter_map(|(_, ts)| topic_cache.get_change(ts))
+        .map(|cc| cc.data_value.payload_size())
+        .sum();
+      drop(topic_cache);
+      self.acked_bytes += newly_acked_bytes;
+      self.acked_bytes_counted_before = acked_before;
+    }
+    let acked_samples = i64::from(acked_before) - i64::from(self.cleaned_acked_before);
+    if acked_samples >= self.cache_cleaning.acked_samples as i64
+      || self.acked_bytes >= self.cache_cleaning.acked_bytes
+    {
+      self.handle_cache_cleaning();
+    } else {
+      self.schedule_cache_cleaning();
+    }
+  }
+
   // --------------------------------------------------------------
   // --------------------------------------------------------------
   // --------------------------------------------------------------
@@ -476,12 +691,25 @@ impl Writer {
 
   // Receive new data samples from the DDS DataWriter
   pub fn process_writer_command(&mut self) {
-    while let Ok(cc) = self.writer_command_receiver.try_recv() {
+    // Changes to be sent together after all queued commands are processed
+    let mut batched_changes = Vec::new();
+    self.commands_paused = false;
+    loop {
+      if self.async_send_queue_full() {
+        // Backpressure: the commands wait in the channel until the flow
+        // controller has sent enough.
+        self.commands_paused = true;
+        break;
+      }
+      let Ok(cc) = self.writer_command_receiver.try_recv() else {
+        break;
+      };
       match cc {
         WriterCommand::DDSData {
           ddsdata: dds_data,
           write_options,
           sequence_number,
+          send_deadline,
         } => {
           // Signal that there is now space in the DataWriter to Writer queue
           {
@@ -493,9 +721,29 @@ impl Writer {
               .map(|w| w.wake_by_ref());
           }
 
+          // Drop the sample, if it has been waiting in the queue for too long.
+          // Late data is worse than no data to some applications. The skipped
+          // sequence number looks like a lost sample to BestEffort Readers.
+          if let Some(deadline) = send_deadline {
+            if !self.is_reliable() && deadline < Instant::now() {
+              self.stale_samples_dropped.fetch_add(1, Ordering::Relaxed);
+              self.stats.stale_sample_dropped();
+              debug!(
+                "Dropping stale sample {:?} past its send deadline. topic={:?}",
+                sequence_number, self.my_topic_name
+              );
+              continue;
+            }
+          }
+
+          let (dds_data, write_options) = self.compress(dds_data, write_options);
+          let write_options = self.add_checksum(&dds_data, write_options);
+
           // Insert data to DDS / history cache
           let timestamp =
             self.insert_to_history_cache(dds_data, write_options.clone(), sequence_number);
+          self.stats.sample_written();
+          self.trace.sample_written(sequence_number);
 
           // If not acting stateless-like, notify reader proxies that there is a new
           // sample
@@ -507,31 +755,73 @@ impl Writer {
               // this sequence number.
               if let Some(single_reader_guid) = write_options.to_single_reader() {
                 if reader.remote_reader_guid != single_reader_guid {
-                  reader.insert_pending_gap(sequence_number);
+                  reader.will_never_send(sequence_number, NotSentReason::OtherReader);
                 }
               }
             }
           }
-          self.increase_heartbeat_counter();
+          // Readers that were already fully acked have no heartbeat running.
+          // Now they have something to acknowledge.
+          if let Some(period) = self.heartbeat_period {
+            let idle_readers: Vec<GUID> = self
+              .readers
+              .values()
+              .filter(|rp| rp.heartbeat_timer.is_none())
+              .map(|rp| rp.remote_reader_guid)
+              .collect();
+            for reader_guid in idle_readers {
+              let period = self.reader_heartbeat_period(reader_guid, period);
+              self.schedule_reader_heartbeat(reader_guid, period);
+            }
+          }
 
           if self.push_mode {
             // Send data (DATA or DATAFRAGs) and a Heartbeat
+            let is_async = self.is_async_publish();
+            if self.suspended_at.is_some() && write_options.to_single_reader().is_none() {
+              self.pending_batch.push(timestamp);
+              continue;
+            }
+            if self.batch_writes && !is_async && write_options.to_single_reader().is_none() {
+              batched_changes.push(timestamp);
+              continue;
+            }
+            if let Some(batching) = self.qos_policies.batching() {
+              if !is_async && write_options.to_single_reader().is_none() {
+                self.add_to_batch(timestamp, batching);
+                continue;
+              }
+            }
+            let mut async_messages = Vec::new();
             if let Some(cc) = self.acquire_the_topic_cache_guard().get_change(&timestamp) {
               let target_reader_opt = match write_options.to_single_reader() {
                 Some(guid) => self.readers.get(&guid), // Sending only to this reader
                 None => None,                          // Sending to all matched readers
               };
 
-              let send_also_heartbeat = true;
-              self.send_cache_change(cc, send_also_heartbeat, target_reader_opt);
+              if is_async {
+                // Build the messages now, but leave sending to the flow controller.
+                // The HEARTBEAT is built when sending, so that it is up to date.
+                (async_messages, _) = self.cache_change_messages(cc, false, target_reader_opt);
+              } else {
+                let send_also_heartbeat = true;
+                self.send_cache_change(cc, send_also_heartbeat, target_reader_opt);
+              }
             } else {
               error!("Lost the cache change that was just added?!");
             }
+            if !async_messages.is_empty() {
+              self.enqueue_async_send(
+                async_messages,
+                write_options.to_single_reader(),
+                send_deadline,
+              );
+            }
           } else {
             // Send Heartbeat only.
             // Readers will ask for the DATA with ACKNACK, if they are interested.
             let final_flag = false; // false = request that readers acknowledge with ACKNACK.
-            let liveliness_flag = false; // This is not a manual liveliness assertion (DDS API call), but side-effect of
+            let liveliness_flag = self.writing_asserts_liveliness();
             let hb_message = MessageBuilder::new()
               .heartbeat_msg(self, EntityId::UNKNOWN, final_flag, liveliness_flag)
               .add_header_and_build(self.my_guid.prefix);
@@ -554,7 +844,7 @@ impl Writer {
               self.my_topic_name
             );
             let _ = all_acked.try_send(()); // Let the poor waiter continue.
-            return;
+            continue;
           }
 
           let wait_until = self.last_change_sequence_number;
@@ -584,8 +874,162 @@ impl Writer {
             })
           };
         }
+
+        WriterCommand::UpdateQos { qos_policies } => {
+          debug!(
+            "QoS updated topic={:?} new QoS={:?}",
+            self.my_topic_name, qos_policies
+          );
+          self.qos_policies = *qos_policies;
+          self.qos_updated = true;
+          // The batch was collected under the old policy
+          self.flush_batch();
+        }
+
+        WriterCommand::GetMatchedReaders { reply } => {
+          // If the DataWriter has stopped waiting, nobody cares.
+          let _ = reply.try_send(self.readers.keys().copied().collect());
+        }
+
+        WriterCommand::Flush => self.flush_batch(),
+
+        WriterCommand::SuspendPublications => self.suspend_publications(),
+
+        WriterCommand::ResumePublications => self.resume_publications(),
+
+        WriterCommand::SetEndianness { endianness } => {
+          self.endianness = endianness;
+          // Cached submessages are in the old byte order
+          self.data_submessage_cache.replace(None);
+        }
+      }
+    }
+    if !batched_changes.is_empty() {
+      self.send_batched_changes(&batched_changes);
+    }
+  }
+
+  fn add_to_batch(&mut self, timestamp: Timestamp, batching: policy::Batching) {
+    if self.pending_batch.is_empty() {
+      self.timed_event_timer.set_timeout(
+        std::time::Duration::from(batching.max_delay),
+        TimedEvent::FlushBatch {
+          batch: self.batch_number,
+        },
+      );
+    }
+    self.pending_batch.push(timestamp);
+    if self.pending_batch.len() >= max(batching.max_samples, 1) as usize {
+      self.flush_batch();
+    }
+  }
+
+  // Sends the changes held back by the Batching QoS policy, if any. While
+  // publications are suspended, everything is held back.
+  fn flush_batch(&mut self) {
+    if self.pending_batch.is_empty() || self.suspended_at.is_some() {
+      return;
+    }
+    let batch = std::mem::take(&mut self.pending_batch);
+    self.batch_number += 1;
+    self.send_batched_changes(&batch);
+  }
+
+  fn suspend_publications(&mut self) {
+    if self.suspended_at.is_none() {
+      self.suspended_at = Some(self.last_change_sequence_number);
+    }
+  }
+
+  // Sends the changes written while suspended together
+  fn resume_publications(&mut self) {
+    self.suspended_at = None;
+    self.flush_batch();
+  }
+
+  // The last sequence number that readers may know about. Changes held back
+  // by suspended publications are not announced. If history has already
+  // dropped changes from before the suspension, the announced range is empty.
+  pub(crate) fn announced_sequence_number(&self) -> SequenceNumber {
+    match self.suspended_at {
+      Some(suspended_at) => max(
+        suspended_at,
+        self.first_change_sequence_number - SequenceNumber::from(1),
+      ),
+      None => self.last_change_sequence_number,
+    }
+  }
+
+  // Sends the given changes to all matched readers
+  fn send_batched_changes(&self, timestamps: &[Timestamp]) {
+    for message in self.batched_change_messages(timestamps) {
+      self.send_message_to_target(message, None);
+    }
+  }
+
+  // Builds messages for sending the given changes to all readers, packing as
+  // many DATA submessages into each message as fit in SEDP_BATCH_MAX_BYTES.
+  // Each message ends with a single HEARTBEAT. Changes that need fragmentation
+  // get messages of their own.
+  fn batched_change_messages(&self, timestamps: &[Timestamp]) -> Vec<Message> {
+    let topic_cache = self.acquire_the_topic_cache_guard();
+    let mut messages = Vec::new();
+    let mut message_builder = MessageBuilder::new();
+    let mut batch_bytes = 0;
+    // Source timestamp in effect in the message being built
+    let mut message_timestamp = None;
+
+    for timestamp in timestamps {
+      let cc = match topic_cache.get_change(timestamp) {
+        Some(cc) => cc,
+        None => {
+          error!("Lost the cache change that was just added?!");
+          continue;
+        }
+      };
+      if self.has_expired(cc) {
+        // Readers will NACK this, and get a GAP in response.
+        continue;
+      }
+      let data_size = cc.data_value.payload_size();
+      if data_size > self.data_max_size_serialized {
+        messages.extend(self.cache_change_messages(cc, true, None).0);
+        continue;
+      }
+      if batch_bytes > 0 && batch_bytes + data_size > SEDP_BATCH_MAX_BYTES {
+        messages.push(self.finish_batch(std::mem::take(&mut message_builder)));
+        batch_bytes = 0;
+        message_timestamp = None;
+      }
+      let source_timestamp = cc.write_options.source_timestamp();
+      if source_timestamp != message_timestamp {
+        message_builder = message_builder.ts_msg(self.endianness, source_timestamp);
+        message_timestamp = source_timestamp;
       }
+      if let Some(data_submessage) = self.data_submessage(cc, EntityId::UNKNOWN) {
+        message_builder = message_builder.submessage(data_submessage);
+        batch_bytes += data_size;
+      }
+    }
+    if batch_bytes > 0 {
+      messages.push(self.finish_batch(message_builder));
+    }
+    messages
+  }
+
+  fn finish_batch(&self, mut message_builder: MessageBuilder) -> Message {
+    if !self.like_stateless {
+      let final_flag = false; // false = request that readers acknowledge with ACKNACK.
+      let liveliness_flag = self.writing_asserts_liveliness();
+      message_builder =
+        message_builder.heartbeat_msg(self, EntityId::UNKNOWN, final_flag, liveliness_flag);
     }
+    message_builder.add_header_and_build(self.my_guid.prefix)
+  }
+
+  // Has the QoS been updated since the last call?
+  pub fn take_qos_updated(&mut self) -> bool {
+    std::mem::take(&mut self.qos_updated)
   }
 
   // Returns a boolean telling if the data had to be fragmented
@@ -596,6 +1040,85 @@ impl Writer {
     target_reader_opt: Option<&RtpsReaderProxy>, /* if present, we are asked to send the cache
                                                   * change only to the target reader */
   ) -> bool {
+    let (messages_to_send, fragmentation_needed) =
+      self.cache_change_messages(cc, send_also_heartbeat, target_reader_opt);
+
+    // Send the messages, either to all readers or just one
+    for msg in messages_to_send {
+      self.send_message_to_target(msg, target_reader_opt);
+    }
+
+    // The return value tells if the data had to be fragmented
+    fragmentation_needed
+  }
+
+  /// Send the most recently written sample to a single locator, regardless
+  /// of the matched readers. Used to answer RTPS pings with our SPDP
+  /// announcement.
+  pub fn send_latest_change_to(&self, locator: &Locator) {
+    let timestamp = match self
+      .sequence_number_to_instant
+      .get(&self.last_change_sequence_number)
+    {
+      Some(timestamp) => *timestamp,
+      None => {
+        debug!(
+          "send_latest_change_to: Nothing written yet on {}",
+          self.topic_name()
+        );
+        return;
+      }
+    };
+    let messages = match self.acquire_the_topic_cache_guard().get_change(&timestamp) {
+      Some(cc) => self.cache_change_messages(cc, false, None).0,
+      None => {
+        debug!("send_latest_change_to: Latest change is no longer in cache");
+        return;
+      }
+    };
+    for message in messages {
+      #[cfg(feature = "security")]
+      let encoded = self.security_encode(message, &[]);
+      #[cfg(not(feature = "security"))]
+      let encoded: Result<Message, ()> = Ok(message);
+
+      match encoded {
+        Ok(message) => {
+          let chunks = message.write_to_chunks(self.endianness).unwrap();
+          self
+            .udp_sender
+            .send_chunks_to_locator_list(&chunks, std::slice::from_ref(locator));
+        }
+        Err(e) => error!("send_latest_change_to: Encoding failed: {e:?}"),
+      }
+    }
+  }
+
+  fn send_message_to_target(&self, msg: Message, target_reader_opt: Option<&RtpsReaderProxy>) {
+    match target_reader_opt {
+      None => {
+        // To all
+        self.send_message_to_readers(DeliveryMode::Multicast, msg, &mut self.readers.values());
+      }
+      Some(reader_proxy) => {
+        // To one
+        self.send_message_to_readers(
+          DeliveryMode::Unicast,
+          msg,
+          &mut std::iter::once(reader_proxy),
+        );
+      }
+    }
+  }
+
+  // Builds the DATA or DATAFRAG messages for sending a cache change.
+  // Returns also a boolean telling if the data had to be fragmented.
+  fn cache_change_messages(
+    &self,
+    cc: &CacheChange,
+    send_also_heartbeat: bool,
+    target_reader_opt: Option<&RtpsReaderProxy>,
+  ) -> (Vec<Message>, bool) {
     // First make sure that if the data is meant for a single reader only, we do not
     // accidentally send it to everyone
     if let Some(single_reader_guid) = cc.write_options.to_single_reader() {
@@ -605,7 +1128,7 @@ impl Writer {
             "Data is meant for the single reader {single_reader_guid:?} but a proxy for this \
              reader was not provided. Not sending anything."
           );
-          return false;
+          return (vec![], false);
         }
         Some(target_reader) => {
           // Make the data is meant for the target reader
@@ -615,15 +1138,13 @@ impl Writer {
                different reader {:?}. Not gonna happen.",
               target_reader.remote_reader_guid
             );
-            return false;
+            return (vec![], false);
           }
         }
       }
     }
 
     // All the messages are pushed to a vector first before sending them.
-    // If this hinders performance when many datafrag messages need to be
-    // sent, optimize.
     let mut messages_to_send: Vec<Message> = vec![];
 
     // The EntityId of the destination
@@ -660,19 +1181,14 @@ impl Writer {
       }
 
       // Add the DATA submessage
-      message_builder = message_builder.data_msg(
-        cc,
-        reader_entity_id,
-        self.my_guid, // writer
-        self.endianness,
-        self.security_plugins.as_ref(),
-      );
+      if let Some(data_submessage) = self.data_submessage(cc, reader_entity_id) {
+        message_builder = message_builder.submessage(data_submessage);
+      }
 
       // Add HEARTBEAT if needed
       if send_also_heartbeat && !self.like_stateless {
         let final_flag = false; // false = request that readers acknowledge with ACKNACK.
-        let liveliness_flag = false; // This is not a manual liveliness assertion (DDS API call), but side-effect of
-                                     // writing new data.
+        let liveliness_flag = self.writing_asserts_liveliness();
         message_builder =
           message_builder.heartbeat_msg(self, reader_entity_id, final_flag, liveliness_flag);
       }
@@ -735,35 +1251,99 @@ impl Writer {
       // Add HEARTBEAT message if needed
       if send_also_heartbeat && !self.like_stateless {
         let final_flag = false; // false = request that readers acknowledge with ACKNACK.
-        let liveliness_flag = false; // This is not a manual liveliness assertion (DDS API call), but side-effect of
-                                     // writing new data.
-        let hb_msg = MessageBuilder::new()
+        let liveliness_flag = self.writing_asserts_liveliness();
+        let mut message_builder = MessageBuilder::new();
+        if let Some(reader) = target_reader_opt {
+          message_builder =
+            message_builder.dst_submessage(self.endianness, reader.remote_reader_guid.prefix);
+        }
+        let hb_msg = message_builder
           .heartbeat_msg(self, reader_entity_id, final_flag, liveliness_flag)
           .add_header_and_build(self.my_guid.prefix);
         messages_to_send.push(hb_msg);
       }
     }
 
-    // Send the messages, either to all readers or just one
-    for msg in messages_to_send {
-      match target_reader_opt {
-        None => {
-          // To all
-          self.send_message_to_readers(DeliveryMode::Multicast, msg, &mut self.readers.values());
-        }
-        Some(reader_proxy) => {
-          // To one
-          self.send_message_to_readers(
-            DeliveryMode::Unicast,
-            msg,
-            &mut std::iter::once(reader_proxy),
-          );
+    (messages_to_send, fragmentation_needed)
+  }
+
+  // Gets the DATA submessage for a cache change, building and serializing it
+  // only if it was not the one most recently sent.
+  fn data_submessage(&self, cc: &CacheChange, reader_entity_id: EntityId) -> Option<Submessage> {
+    let mut cache = self.data_submessage_cache.borrow_mut();
+    if let Some((sn, entity_id, submessage)) = cache.as_ref() {
+      if *sn == cc.sequence_number && *entity_id == reader_entity_id {
+        return Some(submessage.clone());
+      }
+    }
+    let submessage = MessageBuilder::data_submessage(
+      cc,
+      reader_entity_id,
+      self.my_guid, // writer
+      self.endianness,
+      self.security_plugins.as_ref(),
+    )?
+    .serialized_for_reuse();
+    *cache = Some((cc.sequence_number, reader_entity_id, submessage.clone()));
+    Some(submessage)
+  }
+
+  // Compresses the payload of a new sample according to the Compression QoS
+  // policy. All matched readers, also those in this participant, must have
+  // announced the algorithm, because the same cache change is sent to all of
+  // them.
+  fn compress(&self, data: DDSData, write_options: WriteOptions) -> (DDSData, WriteOptions) {
+    let Some(policy::Compression {
+      algorithm,
+      threshold,
+    }) = self.qos_policies.compression()
+    else {
+      return (data, write_options);
+    };
+    let accepted_by_all = !self.readers.is_empty()
+      && self
+        .readers
+        .values()
+        .all(|rp| rp.qos().compression().map(|c| c.algorithm) == Some(algorithm));
+    match data {
+      DDSData::Data {
+        ref serialized_payload,
+        key_hash,
+      } if accepted_by_all && serialized_payload.value.len() >= threshold as usize => {
+        match compression::compress(algorithm, &serialized_payload.value) {
+          Some((compressed, parameter)) => (
+            DDSData::Data {
+              serialized_payload: SerializedPayload {
+                value: compressed,
+                ..serialized_payload.clone()
+              },
+              key_hash,
+            },
+            write_options.with_rustdds_parameter(parameter),
+          ),
+          None => (data, write_options),
         }
       }
+      _ => (data, write_options),
     }
+  }
 
-    // The return value tells if the data had to be fragmented
-    fragmentation_needed
+  // Adds a checksum of the payload as sent, if the PayloadChecksum QoS
+  // policy is set
+  fn add_checksum(&self, data: &DDSData, write_options: WriteOptions) -> WriteOptions {
+    let payload = match data {
+      DDSData::Data {
+        serialized_payload, ..
+      } => &serialized_payload.value,
+      DDSData::DisposeByKey { key, .. } => &key.value,
+      DDSData::DisposeByKeyHash { .. } => return write_options,
+    };
+    match self.qos_policies.payload_checksum() {
+      Some(payload_checksum) => {
+        write_options.with_rustdds_parameter(checksum::to_inline_qos(payload_checksum, payload))
+      }
+      None => write_options,
+    }
   }
 
   fn insert_to_history_cache(
@@ -777,10 +1357,13 @@ impl Writer {
     // Create a new CacheChange from DDSData & insert to topic cache
     // The timestamp taken here is used as a unique(!) key in the cache.
     let new_cache_change = CacheChange::new(self.guid(), new_sequence_number, write_options, data);
-    let timestamp = Timestamp::now();
+    let timestamp = Timestamp::now_increasing();
 
+    // The change is complete, so it is published to local DataReaders right
+    // away.
     let mut topic_cache = self.acquire_the_topic_cache_guard();
-    topic_cache.add_change(&timestamp, new_cache_change);
+    topic_cache.stage_change(&timestamp, new_cache_change);
+    topic_cache.commit_changes();
 
     // Set our sequence numbering state right
     let first_available_sn = match topic_cache.writers_smallest_sn_in_cache(self.my_guid) {
@@ -802,6 +1385,7 @@ impl Writer {
     self
       .sequence_number_to_instant
       .insert(new_sequence_number, timestamp);
+    self.schedule_cache_cleaning();
 
     timestamp
   }
@@ -810,7 +1394,9 @@ impl Writer {
   // --------------------------------------------------------------
   // --------------------------------------------------------------
 
-  /// This is called periodically.
+  /// Sends a heartbeat to all matched readers at once. This is used for
+  /// liveliness assertions. Periodic heartbeats are sent per reader, see
+  /// `handle_reader_heartbeat`.
   pub fn handle_heartbeat_tick(&mut self, is_manual_assertion: bool) {
     if self.like_stateless {
       info!(
@@ -820,9 +1406,6 @@ impl Writer {
       );
       return;
     }
-    // Reliable Stateful Writer (that tracks Readers by ReaderProxy) will not set
-    // the final flag.
-    let final_flag = false;
     let liveliness_flag = is_manual_assertion; // RTPS spec "8.3.7.5 Heartbeat"
 
     trace!(
@@ -831,16 +1414,56 @@ impl Writer {
       self.readers.len()
     );
 
-    self.increase_heartbeat_counter();
-    // TODO: This produces same heartbeat count for all messages sent, but
-    // then again, they represent the same writer status.
-
-    if self
+    let all_acked = self
       .readers
       .values()
-      .all(|rp| self.last_change_sequence_number < rp.all_acked_before)
-    {
+      .all(|rp| self.last_change_sequence_number < rp.all_acked_before);
+    // A liveliness assertion is sent even if there is nothing to acknowledge,
+    // but then with the final flag, i.e. no response is required.
+    let final_flag = all_acked;
+
+    // If only one reader is behind, there is no need to bother the others.
+    // A liveliness assertion is for everyone, though.
+    let mut behind = self
+      .readers
+      .values()
+      .filter(|rp| rp.all_acked_before <= self.last_change_sequence_number);
+    let single_behind = match (behind.next(), behind.next()) {
+      (Some(rp), None) if !is_manual_assertion => Some(rp),
+      _ => None,
+    };
+
+    let single_behind_guid = single_behind.map(|rp| rp.remote_reader_guid);
+    let mut multicast_heartbeat_sent = false;
+
+    let probed_readers: Vec<GUID> = if final_flag {
+      vec![] // no response expected
+    } else if let Some(rp) = single_behind {
+      vec![rp.remote_reader_guid]
+    } else {
+      self.readers.keys().copied().collect()
+    };
+
+    if all_acked && !is_manual_assertion {
       trace!("heartbeat tick: all readers have all available data.");
+    } else if let Some(rp) = single_behind {
+      let hb_message = MessageBuilder::new()
+        .ts_msg(self.endianness, Some(Timestamp::now()))
+        .dst_submessage(self.endianness, rp.remote_reader_guid.prefix)
+        .heartbeat_msg(
+          self,
+          rp.remote_reader_guid.entity_id,
+          final_flag,
+          liveliness_flag,
+        )
+        .add_header_and_build(self.my_guid.prefix);
+      debug!(
+        "Writer {:?} topic={:} HEARTBEAT to {:?}",
+        self.guid().entity_id,
+        self.topic_name(),
+        rp.remote_reader_guid
+      );
+      self.send_message_to_readers(DeliveryMode::Unicast, hb_message, &mut std::iter::once(rp));
     } else {
       let hb_message = MessageBuilder::new()
         .ts_msg(self.endianness, Some(Timestamp::now()))
@@ -858,7 +1481,7 @@ impl Writer {
       // means, so we handle that separately.
       if self.entity_id() == EntityId::P2P_BUILTIN_PARTICIPANT_VOLATILE_SECURE_WRITER {
         for rp in self.readers.values() {
-          if self.last_change_sequence_number < rp.all_acked_before {
+          if self.last_change_sequence_number < rp.all_acked_before && !is_manual_assertion {
             // Everything we have has been acknowledged already. Do nothing.
           } else {
             self.send_message_to_readers(
@@ -875,47 +1498,291 @@ impl Writer {
           hb_message,
           &mut self.readers.values(),
         );
+        multicast_heartbeat_sent = true;
+      }
+    }
+    for rp in self.readers.values_mut() {
+      if multicast_heartbeat_sent {
+        rp.heartbeat_sent(true);
+      } else if Some(rp.remote_reader_guid) == single_behind_guid {
+        rp.heartbeat_sent(false);
       }
     }
+    self.heartbeat_probes_sent(&probed_readers);
   }
 
-  /// When receiving an ACKNACK Message indicating a Reader is missing some data
-  /// samples, the Writer must respond by either sending the missing data
-  /// samples, sending a GAP message when the sample is not relevant, or
-  /// sending a HEARTBEAT message when the sample is no longer available
-  pub fn handle_ack_nack(
+  // HEARTBEATs without the final flag were sent to these readers. Their
+  // responses measure the round trip time.
+  fn heartbeat_probes_sent(&mut self, reader_guids: &[GUID]) {
+    let now = Instant::now();
+    for guid in reader_guids {
+      if let Some(rp) = self.readers.get_mut(guid) {
+        rp.heartbeat_probe_sent(now);
+      }
+    }
+  }
+
+  // Period of heartbeats to a reader, adapted to its round trip time if the
+  // AdaptiveTiming QoS policy is set
+  fn reader_heartbeat_period(&self, reader_guid: GUID, period: Duration) -> Duration {
+    self.readers.get(&reader_guid).map_or(period, |rp| {
+      rp.adapted_heartbeat_period(period, self.qos_policies.adaptive_timing())
+    })
+  }
+
+  fn is_async_publish(&self) -> bool {
+    matches!(
+      self.qos_policies.publish_mode(),
+      Some(PublishMode::Asynchronous { .. })
+    )
+  }
+
+  fn async_send_queue_full(&self) -> bool {
+    self.async_send_queue_bytes >= self.async_send_queue_max_bytes
+  }
+
+  fn enqueue_async_send(
     &mut self,
-    reader_guid_prefix: GuidPrefix,
-    ack_submessage: &AckSubmessage,
+    messages: Vec<Message>,
+    target_reader: Option<GUID>,
+    send_deadline: Option<Instant>,
   ) {
-    // sanity check
-    if !self.is_reliable() || self.like_stateless {
-      // Stateless-like Writer currently supports only BestEffort QoS, so ignore
-      // acknack also for it
-      warn!(
-        "Writer {:x?} is best effort or stateless-like! It should not handle acknack messages!",
-        self.entity_id()
-      );
-      return;
+    let message_count = messages.len();
+    for (i, message) in messages.into_iter().enumerate() {
+      self.async_send_queue_bytes += Writable::<Endianness>::bytes_needed(&message).unwrap_or(0);
+      self.async_send_queue.push_back(AsyncSend {
+        message,
+        target_reader,
+        send_deadline,
+        ends_sample: i + 1 == message_count,
+      });
+    }
+    if !self.async_send_scheduled {
+      // Flow controller is idle, so the budget of the current period is
+      // available. Send right away, and schedule the rest.
+      self.handle_async_send();
     }
+  }
 
-    match ack_submessage {
-      AckSubmessage::AckNack(ref an) => {
-        // Update the ReaderProxy
-        let last_seq = self.last_change_sequence_number; // to avoid borrow problems
+  // Flow controller for PublishMode::Asynchronous: Send up to
+  // bytes_per_period from the queue, and come back after period.
+  fn handle_async_send(&mut self) {
+    self.async_send_scheduled = false;
+    let (bytes_per_period, period) = match self.qos_policies.publish_mode() {
+      Some(PublishMode::Asynchronous {
+        bytes_per_period,
+        period,
+      }) => (bytes_per_period as usize, period),
+      _ => (usize::MAX, Duration::ZERO), // Not async (anymore?). Flush everything.
+    };
 
-        // sanity check requested sequence numbers
-        match an.reader_sn_state.iter().next().map(i64::from) {
-          Some(0) => warn!("Request for SN zero! : {:?}", an),
-          Some(_) | None => (), // ok
+    let mut bytes_sent = 0;
+    while let Some(queued) = self.async_send_queue.pop_front() {
+      let msg_size = Writable::<Endianness>::bytes_needed(&queued.message).unwrap_or(0);
+      // Late data is dropped as in process_writer_command(), but only after
+      // it has waited in this queue.
+      if let Some(deadline) = queued.send_deadline {
+        if !self.is_reliable() && deadline < Instant::now() {
+          self.async_send_queue_bytes = self.async_send_queue_bytes.saturating_sub(msg_size);
+          if queued.ends_sample {
+            self.stale_samples_dropped.fetch_add(1, Ordering::Relaxed);
+            self.stats.stale_sample_dropped();
+            debug!(
+              "Async send: dropping stale message past its send deadline. topic={:?}",
+              self.my_topic_name
+            );
+          }
+          continue;
         }
-        let my_topic = self.my_topic_name.clone(); // for debugging
-        let reader_guid = GUID::new(reader_guid_prefix, an.reader_id);
-        self.update_ack_waiters(reader_guid, Some(an.reader_sn_state.base()));
-
-        if let Some(reader_proxy) = self.lookup_reader_proxy_mut(reader_guid) {
-          // Mark requested SNs as "unsent changes"
-          reader_proxy.handle_ack_nack(ack_submessage, last_seq);
+      }
+      if bytes_sent > 0 && bytes_sent + msg_size > bytes_per_period {
+        // Budget used up. Always send at least one message per period, so that
+        // messages larger than the budget do not get stuck.
+        self.async_send_queue.push_front(queued);
+        break;
+      }
+      bytes_sent += msg_size;
+      self.async_send_queue_bytes = self.async_send_queue_bytes.saturating_sub(msg_size);
+      let AsyncSend {
+        mut message,
+        target_reader,
+        ends_sample,
+        ..
+      } = queued;
+      let target_proxy = match target_reader {
+        None => None,
+        Some(guid) => match self.readers.get(&guid) {
+          Some(rp) => Some(rp),
+          None => {
+            debug!("Async send: target reader {guid:?} is gone. Dropping message.");
+            continue;
+          }
+        },
+      };
+      if ends_sample && !self.like_stateless {
+        let reader_entity_id =
+          target_proxy.map_or(EntityId::UNKNOWN, |rp| rp.remote_reader_guid.entity_id);
+        let final_flag = false; // false = request that readers acknowledge with ACKNACK.
+        let liveliness_flag = self.writing_asserts_liveliness();
+        let heartbeat = MessageBuilder::new()
+          .heartbeat_msg(self, reader_entity_id, final_flag, liveliness_flag)
+          .add_header_and_build(self.my_guid.prefix);
+        message.submessages.extend(heartbeat.submessages);
+      }
+      self.send_message_to_target(message, target_proxy);
+    }
+
+    // The budget of this period is used. Whatever is queued next waits for
+    // the next period.
+    if bytes_sent > 0 {
+      self.async_send_scheduled = true;
+      self
+        .timed_event_timer
+        .set_timeout(std::time::Duration::from(period), TimedEvent::AsyncSend);
+    }
+  }
+
+  // Start the heartbeat timer of a reader, unless it is already running.
+  fn schedule_reader_heartbeat(&mut self, reader_guid: GUID, delay: Duration) {
+    if !self.is_reliable() || self.like_stateless {
+      return;
+    }
+    if let Some(rp) = self.readers.get_mut(&reader_guid) {
+      if rp.heartbeat_timer.is_none() {
+        self.heartbeat_timer_id += 1;
+        rp.heartbeat_timer = Some(self.heartbeat_timer_id);
+        self.timed_event_timer.set_timeout(
+          std::time::Duration::from(delay),
+          TimedEvent::ReaderHeartbeat {
+            to_reader: reader_guid,
+            timer: self.heartbeat_timer_id,
+          },
+        );
+      }
+    }
+  }
+
+  // Periodic heartbeat to a single reader. Each reader has its own timer, so
+  // that heartbeats to many readers are spread over the period instead of being
+  // sent in a single burst. The timer stops when the reader has acknowledged
+  // everything, and restarts when there is new data.
+  fn handle_reader_heartbeat(&mut self, reader_guid: GUID, timer: u64) {
+    let period = match self.heartbeat_period {
+      Some(p) => p,
+      None => return,
+    };
+    let last_change_sequence_number = self.last_change_sequence_number;
+    match self.readers.get_mut(&reader_guid) {
+      None => return, // Reader is gone. Let the timer lapse.
+      Some(rp) if rp.heartbeat_timer != Some(timer) => return, // stale timer
+      Some(rp) => {
+        rp.heartbeat_timer = None;
+        if last_change_sequence_number < rp.all_acked_before {
+          trace!(
+            "heartbeat: reader {:?} has all available data. Suspending heartbeats.",
+            reader_guid
+          );
+          return;
+        }
+      }
+    }
+
+    let final_flag = false;
+    let liveliness_flag = false; // This is automatic heartbeat, not manual assertion
+    let hb_message = MessageBuilder::new()
+      .ts_msg(self.endianness, Some(Timestamp::now()))
+      .heartbeat_msg(self, reader_guid.entity_id, final_flag, liveliness_flag)
+      .add_header_and_build(self.my_guid.prefix);
+    if let Some(rp) = self.readers.get(&reader_guid) {
+      self.send_message_to_readers(DeliveryMode::Unicast, hb_message, &mut std::iter::once(rp));
+    }
+    self.heartbeat_probes_sent(&[reader_guid]);
+
+    let period = self.reader_heartbeat_period(reader_guid, period);
+    self.schedule_reader_heartbeat(reader_guid, period);
+  }
+
+  /// When receiving an ACKNACK Message indicating a Reader is missing some data
+  /// samples, the Writer must respond by either sending the missing data
+  /// samples, sending a GAP message when the sample is not relevant, or
+  /// sending a HEARTBEAT message when the sample is no longer available
+  pub fn handle_ack_nack(
+    &mut self,
+    reader_guid_prefix: GuidPrefix,
+    ack_submessage: &AckSubmessage,
+  ) {
+    // sanity check
+    if !self.is_reliable() || self.like_stateless {
+      // Stateless-like Writer currently supports only BestEffort QoS, so ignore
+      // acknack also for it
+      warn!(
+        "Writer {:x?} is best effort or stateless-like! It should not handle acknack messages!",
+        self.entity_id()
+      );
+      return;
+    }
+
+    let reader_id = ack_submessage.reader_id();
+    if reader_id == EntityId::UNKNOWN {
+      // Some minimal readers do not say who they are. If we have exactly one
+      // reader matched at the sending participant, it must be that one.
+      return match self.sole_matched_reader_at(reader_guid_prefix) {
+        Some(reader_id) => self.handle_ack_nack(
+          reader_guid_prefix,
+          &ack_submessage.addressed_to(self.entity_id(), reader_id),
+        ),
+        None => debug!(
+          "Cannot resolve reader of {:?} from {:?} topic={:?}",
+          ack_submessage, reader_guid_prefix, self.my_topic_name
+        ),
+      };
+    }
+    if let Some(reader_proxy) = self
+      .readers
+      .get_mut(&GUID::new(reader_guid_prefix, reader_id))
+    {
+      if !reader_proxy.accept_ack_count(ack_submessage) {
+        debug!(
+          "Ignoring duplicate or stale {:?} from {:?} topic={:?}",
+          ack_submessage, reader_proxy.remote_reader_guid, self.my_topic_name
+        );
+        return;
+      }
+    }
+
+    match ack_submessage {
+      AckSubmessage::AckNack(ref an) => {
+        self.stats.acknack_received();
+        // Update the ReaderProxy
+        let last_seq = self.last_change_sequence_number; // to avoid borrow problems
+
+        // sanity check requested sequence numbers
+        match an.reader_sn_state.iter().next().map(i64::from) {
+          Some(0) => warn!("Request for SN zero! : {:?}", an),
+          Some(_) | None => (), // ok
+        }
+        let my_topic = self.my_topic_name.clone(); // for debugging
+        let replay_is_paced = self.qos_policies.history_replay().is_some();
+        let adaptive_timing = self.qos_policies.adaptive_timing();
+        let nack_response_delay = self.nack_response_delay;
+        let round_trip_times = Arc::clone(&self.round_trip_times);
+        let reader_guid = GUID::new(reader_guid_prefix, an.reader_id);
+        self
+          .trace
+          .acknack_received(reader_guid, an.reader_sn_state.base());
+        // Acks beyond what we have written are clamped. See
+        // RtpsReaderProxy::handle_ack_nack.
+        self.update_ack_waiters(
+          reader_guid,
+          Some(min(an.reader_sn_state.base(), last_seq.plus_1())),
+        );
+
+        if let Some(reader_proxy) = self.lookup_reader_proxy_mut(reader_guid) {
+          if let Some(rtt) = reader_proxy.heartbeat_probe_answered(Instant::now()) {
+            round_trip_times.lock().unwrap().insert(reader_guid, rtt);
+          }
+          // Mark requested SNs as "unsent changes"
+          reader_proxy.handle_ack_nack(ack_submessage, last_seq);
 
           let reader_guid = reader_proxy.remote_reader_guid; // copy to avoid double mut borrow
                                                              // Sanity Check: if the reader asked for something we did not even advertise
@@ -956,14 +1823,22 @@ impl Writer {
           if reader_proxy.all_acked_before > last_seq {
             reader_proxy.repair_mode = false;
           } else {
+            // A paced history replay already has its repair timer running.
+            // Starting another one would exceed the pace.
+            let paced_replay_running =
+              replay_is_paced && reader_proxy.repair_mode && reader_proxy.is_replaying();
             reader_proxy.repair_mode = true; // TODO: Is this correct? Do we need to repair immediately?
                                              // set repair timer to fire
-            self.timed_event_timer.set_timeout(
-              self.nack_response_delay,
-              TimedEvent::SendRepairData {
-                to_reader: reader_guid,
-              },
-            );
+            if !paced_replay_running {
+              let delay =
+                reader_proxy.adapted_nack_response_delay(nack_response_delay, adaptive_timing);
+              self.timed_event_timer.set_timeout(
+                delay,
+                TimedEvent::SendRepairData {
+                  to_reader: reader_guid,
+                },
+              );
+            }
           }
         } // if have reader_proxy
 
@@ -971,6 +1846,7 @@ impl Writer {
         if let Some(reader_proxy) = self.readers.get(&reader_guid) {
           if !reader_proxy.get_pending_gap().is_empty() {
             let gap_message = MessageBuilder::new()
+              .dst_submessage(self.endianness, reader_guid.prefix)
               .gap_msg(
                 reader_proxy.get_pending_gap(),
                 self.my_guid.entity_id,
@@ -985,13 +1861,27 @@ impl Writer {
             );
           }
         }
+        self.report_deliveries();
+        self.clean_cache_on_ack_progress();
       } // AckNack
       AckSubmessage::NackFrag(ref nackfrag) => {
         // NackFrag is negative acknowledgement only, i.e. requesting missing fragments.
+        // Only the requested fragments are sent again, not the whole sample.
 
         let reader_guid = GUID::new(reader_guid_prefix, nackfrag.reader_id);
+        let Some(frag_count) = self.frag_count(nackfrag.writer_sn) else {
+          debug!(
+            "NackFrag from {:?} for {:?}, which is not in history. topic={:?}",
+            reader_guid, nackfrag.writer_sn, self.my_topic_name
+          );
+          return;
+        };
         if let Some(reader_proxy) = self.lookup_reader_proxy_mut(reader_guid) {
-          reader_proxy.mark_frags_requested(nackfrag.writer_sn, &nackfrag.fragment_number_state);
+          reader_proxy.mark_frags_requested(
+            nackfrag.writer_sn,
+            &nackfrag.fragment_number_state,
+            frag_count,
+          );
         }
         self.timed_event_timer.set_timeout(
           self.nackfrag_response_delay,
@@ -1003,6 +1893,46 @@ impl Writer {
     }
   }
 
+  // Number of fragments in the change, if it is still in the history
+  fn frag_count(&self, sequence_number: SequenceNumber) -> Option<u32> {
+    let cc = self
+      .repair_changes_in_range(sequence_number..=sequence_number)
+      .into_values()
+      .next()?;
+    Some(self.num_frags_and_frag_size(cc.data_value.payload_size()).0)
+  }
+
+  // Has the Lifespan of the change passed, so that it must not be delivered
+  // anymore?
+  fn has_expired(&self, cc: &CacheChange) -> bool {
+    self.qos_policies.lifespan.is_some_and(|lifespan| {
+      cc.reception_instant.elapsed() > std::time::Duration::from(lifespan.duration)
+    })
+  }
+
+  // The only reader matched to us in the given participant, if there is
+  // exactly one.
+  fn sole_matched_reader_at(&self, prefix: GuidPrefix) -> Option<EntityId> {
+    let mut at_prefix = self
+      .readers
+      .keys()
+      .filter(|guid| guid.prefix == prefix && guid.entity_id != EntityId::UNKNOWN);
+    match (at_prefix.next(), at_prefix.next()) {
+      (Some(guid), None) => Some(guid.entity_id),
+      _ => None,
+    }
+  }
+
+  /// Should an ACKNACK or NACK_FRAG with writer id UNKNOWN from the given
+  /// reader be handled by this Writer? The reader id may also be UNKNOWN.
+  pub(crate) fn accepts_unaddressed_ack(&self, prefix: GuidPrefix, reader_id: EntityId) -> bool {
+    if reader_id == EntityId::UNKNOWN {
+      self.sole_matched_reader_at(prefix).is_some()
+    } else {
+      self.readers.contains_key(&GUID::new(prefix, reader_id))
+    }
+  }
+
   fn update_ack_waiters(&mut self, guid: GUID, acked_before: Option<SequenceNumber>) {
     let completed = self
       .ack_waiter
@@ -1017,6 +1947,23 @@ impl Writer {
     }
   }
 
+  // Reports the samples that all reliable readers have acknowledged, or been
+  // told they will not get, to the delivery listener.
+  fn report_deliveries(&mut self) {
+    let acked_before = self
+      .readers
+      .values()
+      .filter(|rp| rp.qos().is_reliable())
+      .map(|rp| rp.all_acked_before)
+      .min();
+    // Without reliable readers nothing is delivered
+    if let Some(acked_before) = acked_before {
+      self
+        .delivery_tracker
+        .acked_before(min(acked_before, self.last_change_sequence_number.plus_1()));
+    }
+  }
+
   // Send out missing data
 
   fn handle_repair_data_send(&mut self, to_reader: GUID) {
@@ -1070,6 +2017,59 @@ impl Writer {
   }
 
   fn handle_repair_data_send_worker(&mut self, reader_proxy: &mut RtpsReaderProxy) {
+    // History replay to a late joiner may be sent in batches. Other repairs
+    // are sent one change at a time.
+    let batch_size = match self.qos_policies.history_replay() {
+      Some(replay) if reader_proxy.is_replaying() => max(replay.max_batch_size, 1),
+      _ => 1,
+    };
+    let changes = self.repair_changes(reader_proxy, batch_size as usize);
+    for _ in 0..batch_size {
+      if !self.send_next_repair_data(reader_proxy, &changes) {
+        break;
+      }
+    }
+  }
+
+  // The changes for the next `count` unsent sequence numbers of the reader,
+  // fetched from the topic cache with a single lock. Each repair round sends
+  // at least the first unsent change, so a batch of `count` rounds does not
+  // need any other changes.
+  fn repair_changes(
+    &self,
+    reader_proxy: &RtpsReaderProxy,
+    count: usize,
+  ) -> BTreeMap<SequenceNumber, Arc<CacheChange>> {
+    let mut unsent = reader_proxy.unsent_changes_iter().take(count);
+    let Some(first) = unsent.next() else {
+      return BTreeMap::new();
+    };
+    let last = unsent.last().unwrap_or(first);
+    self.repair_changes_in_range(first..=last)
+  }
+
+  // Our changes in the range, fetched from the topic cache with a single lock
+  fn repair_changes_in_range(
+    &self,
+    range: RangeInclusive<SequenceNumber>,
+  ) -> BTreeMap<SequenceNumber, Arc<CacheChange>> {
+    self
+      .acquire_the_topic_cache_guard()
+      .get_changes_in_sn_range(self.my_guid, range)
+      .into_iter()
+      // Changes removed from our history are not sent, even if the topic
+      // cache still has them.
+      .filter(|(sn, _cc)| self.sequence_number_to_instant.contains_key(sn))
+      .collect()
+  }
+
+  // Send the first unsent change to the reader, as DATA or GAP. Returns false
+  // if there was nothing to send. `changes` are from repair_changes().
+  fn send_next_repair_data(
+    &mut self,
+    reader_proxy: &mut RtpsReaderProxy,
+    changes: &BTreeMap<SequenceNumber, Arc<CacheChange>>,
+  ) -> bool {
     // Note: The reader_proxy is now removed from readers map
     let reader_guid = reader_proxy.remote_reader_guid;
 
@@ -1081,6 +2081,8 @@ impl Writer {
     if let Some(unsent_sn) = reader_proxy.first_unsent_change() {
       // There are unsent changes.
       let mut no_longer_relevant: BTreeSet<SequenceNumber> = BTreeSet::new();
+      // Why the change will not be sent, if it cannot be anymore
+      let mut drop_reason = None;
 
       // If we have set the reader as pending GAP for the unsent sequence number,
       // just send a GAP message
@@ -1088,32 +2090,31 @@ impl Writer {
       if pending_gaps.contains(&unsent_sn) {
         no_longer_relevant.extend(pending_gaps);
       } else {
-        // Reader not pending gap on unsent_sn. Get the cache change from topic cache
-        let topic_cache = self.acquire_the_topic_cache_guard();
-        if let Some(cc) = self
-          .sequence_number_to_instant(unsent_sn)
-          .and_then(|ts| topic_cache.get_change(&ts))
-        {
-          // The cache change was found. Send it to the reader
-          let data_was_fragmented = self.send_cache_change(cc, false, Some(reader_proxy));
-
-          if data_was_fragmented {
-            // Mark the reader as having requested all frags
-            let (num_frags, _frag_size) =
-              self.num_frags_and_frag_size(cc.data_value.payload_size());
-            reader_proxy.mark_all_frags_requested(unsent_sn, num_frags);
-
-            // Set a timer to send repair frags if needed
-            std::mem::drop(topic_cache); // For borrow checker
-            self.timed_event_timer.set_timeout(
-              self.repairfrags_continue_delay,
-              TimedEvent::SendRepairFrags {
-                to_reader: reader_guid,
-              },
-            );
+        // Reader not pending gap on unsent_sn. Get the cache change.
+        if let Some(cc) = changes.get(&unsent_sn) {
+          if self.has_expired(cc) {
+            // Sending it now would only deliver stale data.
+            reader_proxy.will_never_send(unsent_sn, NotSentReason::Expired);
+            drop_reason = Some(DropReason::Expired);
+            no_longer_relevant.insert(unsent_sn);
+          } else {
+            // The cache change was found. Send it to the reader
+            let data_was_fragmented = self.send_repair(cc, reader_proxy);
+            self.stats.repair_sample_sent();
+            self
+              .trace
+              .repair_sample_sent(reader_proxy.remote_reader_guid, unsent_sn);
+
+            if data_was_fragmented {
+              // All fragments were just sent. The reader asks for the ones it
+              // still misses with NACK_FRAG.
+              reader_proxy.mark_all_frags_sent(unsent_sn);
+            }
           }
         } else {
           // Did not find a cache change for the sequence number
+          reader_proxy.will_never_send(unsent_sn, NotSentReason::Removed);
+          drop_reason = Some(DropReason::Removed);
           no_longer_relevant.insert(unsent_sn);
           // Try to find a reason why and log about it
           if unsent_sn < self.first_change_sequence_number {
@@ -1121,6 +2122,11 @@ impl Writer {
               "Reader {:?} requested too old data {:?}. I have only from {:?}. Topic {:?}",
               &reader_proxy, unsent_sn, self.first_change_sequence_number, &self.my_topic_name
             );
+          } else if unsent_sn < self.compacted_before {
+            debug!(
+              "Reader {:?} requested {:?}, which is superseded in its instance. Topic {:?}",
+              &reader_proxy, unsent_sn, &self.my_topic_name
+            );
           } else if self.disposed_sequence_numbers.contains(&unsent_sn) {
             debug!(
               "Reader {:?} requested disposed {:?}. Topic {:?}",
@@ -1136,6 +2142,10 @@ impl Writer {
         }
       }
 
+      if let Some(reason) = drop_reason {
+        self.delivery_tracker.dropped(unsent_sn, reason);
+      }
+
       // Send a GAP if we marked a sequence number as no longer relevant
       if !no_longer_relevant.is_empty() {
         let gap_msg = MessageBuilder::new()
@@ -1156,12 +2166,36 @@ impl Writer {
 
       // Data or GAP was sent => remove from unsent list.
       reader_proxy.mark_change_sent(unsent_sn);
+      for sn in no_longer_relevant {
+        reader_proxy.mark_change_sent(sn);
+      }
+      true
     } else {
       // Unsent list is empty. Switch off repair mode.
       reader_proxy.repair_mode = false;
+      false
     }
   } // fn
 
+  // Sends a repair, over multicast if the reader NACKed in response to a
+  // multicast HEARTBEAT. Returns if the data had to be fragmented.
+  fn send_repair(&self, cc: &CacheChange, reader_proxy: &RtpsReaderProxy) -> bool {
+    if !reader_proxy.repairs_via_multicast() || cc.write_options.to_single_reader().is_some() {
+      return self.send_cache_change(cc, false, Some(reader_proxy));
+    }
+    // Without INFO_DST and reader id, so that all the readers in the group
+    // accept it
+    let (messages, fragmentation_needed) = self.cache_change_messages(cc, false, None);
+    for message in messages {
+      self.send_message_to_readers(
+        DeliveryMode::Multicast,
+        message,
+        &mut std::iter::once(reader_proxy),
+      );
+    }
+    fragmentation_needed
+  }
+
   fn handle_repair_frags_send_worker(
     &mut self,
     reader_proxy: &mut RtpsReaderProxy, /* This is mutable proxy temporarily detached from the
@@ -1172,127 +2206,161 @@ impl Writer {
 
     let reader_guid = reader_proxy.remote_reader_guid;
 
-    // Get (an iterator to) frags requested but not yet sent
-    // reader_proxy.
-    // Iterate over frags to be sent
-    for (seq_num, frag_num) in reader_proxy.frags_requested_iterator().take(max_send_count) {
-      // Sanity check request
-      // ^^^ TODO
-
-      if let Some(timestamp) = self.sequence_number_to_instant(seq_num) {
-        // Try to find the cache change from topic cache
-        if let Some(cache_change) = self.acquire_the_topic_cache_guard().get_change(&timestamp) {
-          // If the data is meant for a single reader only, make sure it is the one we're
-          // about to send frags to.
-          if let Some(single_reader_guid) = cache_change.write_options.to_single_reader() {
-            if single_reader_guid != reader_guid {
-              error!(
-                "We were asked to send datafrags meant for the reader {single_reader_guid:?} to a \
-                 different reader {reader_guid:?}. Not gonna happen."
-              );
-              return;
-            }
-          }
-
-          // Generate datafrag message
-          let mut message_builder = MessageBuilder::new();
-          if let Some(src_ts) = cache_change.write_options.source_timestamp() {
-            message_builder = message_builder.ts_msg(self.endianness, Some(src_ts));
-          }
-
-          let fragment_size: u32 = self.data_max_size_serialized as u32; // TODO: overflow check
-          let data_size: u32 = cache_change.data_value.payload_size() as u32; // TODO: overflow check
+    // Frags requested but not yet sent. They are all from the same sample.
+    let requested: Vec<(SequenceNumber, FragmentNumber)> = reader_proxy
+      .frags_requested_iterator()
+      .take(max_send_count)
+      .collect();
+    let Some(&(seq_num, _)) = requested.first() else {
+      return;
+    };
 
-          message_builder = message_builder.data_frag_msg(
-            cache_change,
-            reader_guid.entity_id, // reader
-            self.my_guid,          // writer
-            frag_num,
-            fragment_size as u16, // TODO: overflow check
-            data_size,
-            self.endianness,
-            self.security_plugins.as_ref(),
-          );
+    // Try to find the cache change from topic cache
+    let cache_change = self
+      .repair_changes_in_range(seq_num..=seq_num)
+      .into_values()
+      .next();
+    let Some(cache_change) = cache_change else {
+      error!(
+        "handle_repair_frags_send_worker: {:?} missing from history. topic={:?}",
+        seq_num, self.my_topic_name
+      );
+      reader_proxy.mark_all_frags_sent(seq_num);
+      return;
+    };
 
-          // TODO: some sort of queuing is needed
-          self.send_message_to_readers(
-            DeliveryMode::Unicast,
-            message_builder.add_header_and_build(self.my_guid.prefix),
-            &mut std::iter::once(&*reader_proxy),
-          );
-        } else {
-          error!(
-            "handle_repair_frags_send_worker: {:?} missing from DDSCache. topic={:?}",
-            seq_num, self.my_topic_name
-          );
-          // TODO: Should we send a GAP message then?
-        }
-      } else {
+    // If the data is meant for a single reader only, make sure it is the one we're
+    // about to send frags to.
+    if let Some(single_reader_guid) = cache_change.write_options.to_single_reader() {
+      if single_reader_guid != reader_guid {
         error!(
-          "handle_repair_frags_send_worker: {:?} missing from instant map. topic={:?}",
-          seq_num, self.my_topic_name
+          "We were asked to send datafrags meant for the reader {single_reader_guid:?} to a \
+           different reader {reader_guid:?}. Not gonna happen."
         );
+        reader_proxy.mark_all_frags_sent(seq_num);
+        return;
+      }
+    }
+
+    let data_size = cache_change.data_value.payload_size();
+    let (_num_frags, fragment_size) = self.num_frags_and_frag_size(data_size);
+
+    for (seq_num, frag_num) in requested {
+      // Generate datafrag message
+      let mut message_builder = MessageBuilder::new();
+      if let Some(src_ts) = cache_change.write_options.source_timestamp() {
+        message_builder = message_builder.ts_msg(self.endianness, Some(src_ts));
       }
+      message_builder = message_builder.dst_submessage(self.endianness, reader_guid.prefix);
+
+      message_builder = message_builder.data_frag_msg(
+        &cache_change,
+        reader_guid.entity_id, // reader
+        self.my_guid,          // writer
+        frag_num,
+        fragment_size,
+        data_size.try_into().unwrap(),
+        self.endianness,
+        self.security_plugins.as_ref(),
+      );
+
+      // TODO: some sort of queuing is needed
+      self.send_message_to_readers(
+        DeliveryMode::Unicast,
+        message_builder.add_header_and_build(self.my_guid.prefix),
+        &mut std::iter::once(&*reader_proxy),
+      );
 
       reader_proxy.mark_frag_sent(seq_num, &frag_num);
     } // for
   } // fn
 
-  /// Removes permanently cacheChanges from DDSCache.
-  /// CacheChanges can be safely removed only if they are acked by all readers.
-  /// (Reliable) Depth is QoS policy History depth.
-  /// Returns SequenceNumbers of removed CacheChanges
+  /// Removes acknowledged changes from the history of this Writer, keeping
+  /// the latest `depth` changes of each instance. Depth is the QoS policy
+  /// History depth. Changes can be removed only if they are acked by all
+  /// readers.
+  ///
+  /// Keeping the depth per instance, and not globally, ensures that a late
+  /// joining reader of a non-volatile Writer still gets the latest sample of
+  /// every instance. Topics without key are a single instance.
+  ///
   /// This is called repeatedly by handle_cache_cleaning action.
   fn remove_all_acked_changes_but_keep_depth(&mut self, depth: usize) {
-    let first_keeper = if !self.like_stateless {
+    let acked_before = self.acked_before();
+    self.cleaned_acked_before = acked_before;
+    self.acked_bytes = 0;
+    self.acked_bytes_counted_before = acked_before;
+
+    let mut topic_cache = self.acquire_the_topic_cache_guard();
+
+    // Walk the history from newest to oldest, counting changes per instance.
+    // Changes that are not in the topic cache anymore have nothing to keep.
+    let mut instance_counts: BTreeMap<Option<KeyHash>, usize> = BTreeMap::new();
+    let removable: Vec<SequenceNumber> = self
+      .sequence_number_to_instant
+      .iter()
+      .rev()
+      .filter(|(sn, ts)| match topic_cache.get_change(ts) {
+        Some(cc) => {
+          let count = instance_counts.entry(cc.data_value.key_hash()).or_default();
+          *count += 1;
+          **sn < acked_before && *count > depth
+        }
+        None => **sn < acked_before,
+      })
+      .map(|(sn, _)| *sn)
+      .collect();
+
+    // The topic cache keeps samples within its resource limits, but never
+    // removes the latest change of an instance.
+    topic_cache.remove_excess_changes();
+    drop(topic_cache);
+
+    for sn in &removable {
+      self.sequence_number_to_instant.remove(sn);
+    }
+    if !removable.is_empty() {
+      self.compacted_before = max(self.compacted_before, acked_before);
+    }
+    if let Some(&first_kept) = self.sequence_number_to_instant.keys().next() {
+      self.first_change_sequence_number = max(self.first_change_sequence_number, first_kept);
+    }
+  }
+
+  // All readers have acked up to this point (SequenceNumber)
+  fn acked_before(&self) -> SequenceNumber {
+    if !self.like_stateless {
       // Regular stateful writer behavior
-      // All readers have acked up to this point (SequenceNumber)
-      let acked_by_all_readers = self
+      self
         .readers
         .values()
         .map(RtpsReaderProxy::acked_up_to_before)
         .min()
-        .unwrap_or_else(SequenceNumber::zero);
-      // If all readers have acked all up to before 5, and depth is 5, we need
-      // to keep samples 0..4, i.e. from acked_up_to_before - depth .
-      max(
-        acked_by_all_readers - SequenceNumber::from(depth),
-        self.first_change_sequence_number,
-      )
+        .unwrap_or_else(SequenceNumber::zero)
     } else {
       // Stateless-like writer currently supports only BestEffort behavior, so here we
       // make it explicit that it does not care about acked sequence numbers
       self.first_change_sequence_number
-    };
-
-    // We notify the topic cache that it can release older samples
-    // as far as this Writer is concerned.
-    if let Some(&keep_instant) = self.sequence_number_to_instant.get(&first_keeper) {
-      self
-        .acquire_the_topic_cache_guard()
-        .remove_changes_before(keep_instant);
-    } else {
-      // if we end up with SequenceNumber(1), it may be due to "max()" above,
-      // and may mean that no messages have ever been received, so it is
-      // normal that we did not find anything.
-      if first_keeper > SequenceNumber::new(1) {
-        warn!(
-          "DDCache garbage collect: {:?} missing from instant map",
-          first_keeper
-        );
-      } else {
-        debug!(
-          "DDCache garbage collect: {:?} missing from instant map. But it is normal for number 1.",
-          first_keeper
-        );
-      }
     }
-    self.first_change_sequence_number = first_keeper;
-    self.sequence_number_to_instant = self.sequence_number_to_instant.split_off(&first_keeper);
   }
 
-  fn increase_heartbeat_counter(&mut self) {
-    self.heartbeat_message_counter += 1;
+  // Count for the next HEARTBEAT to be sent. Every HEARTBEAT gets a new count,
+  // even if it is identical to the previous one, because readers ignore
+  // HEARTBEATs with a count they have already seen.
+  pub(crate) fn next_heartbeat_count(&self) -> i32 {
+    let count = self.heartbeat_count.get().wrapping_add(1);
+    self.heartbeat_count.set(count);
+    count
+  }
+
+  // With ManualByTopic liveliness, writing asserts liveliness. This is
+  // signalled to readers with the liveliness flag in the HEARTBEATs sent along
+  // with the data.
+  fn writing_asserts_liveliness(&self) -> bool {
+    matches!(
+      self.qos_policies.liveliness,
+      Some(policy::Liveliness::ManualByTopic { .. })
+    )
   }
 
   #[cfg(feature = "security")]
@@ -1359,6 +2427,11 @@ impl Writer {
     // and not find it dynamically on every message.
 
     let readers = readers.collect::<Vec<_>>(); // clone iterator
+    for reader in &readers {
+      if has_heartbeat_to(&message, reader.remote_reader_guid) {
+        reader.count_heartbeat();
+      }
+    }
 
     #[cfg(feature = "security")]
     let encoded = self.security_encode(message, &readers);
@@ -1367,8 +2440,10 @@ impl Writer {
 
     match encoded {
       Ok(message) => {
-        let buffer = message.write_to_vec_with_ctx(self.endianness).unwrap();
+        // Large payloads are not copied to the message buffer
+        let chunks = message.write_to_chunks(self.endianness).unwrap();
         let mut already_sent_to = BTreeSet::new();
+        let mut destinations = Vec::new();
 
         macro_rules! send_unless_sent_and_mark {
           ($locs:expr) => {
@@ -1376,7 +2451,7 @@ impl Writer {
               if already_sent_to.contains(loc) {
                 trace!("Already sent to {:?}", loc);
               } else {
-                self.udp_sender.send_to_locator(&buffer, loc);
+                destinations.push(loc.clone());
                 already_sent_to.insert(loc.clone());
               }
             }
@@ -1412,6 +2487,10 @@ impl Writer {
             }
           } // match
         }
+        // Send to all destinations at once, so that the sender can batch them.
+        self
+          .udp_sender
+          .send_chunks_to_locator_list(&chunks, &destinations);
       }
       Err(e) => error!("Failed to send message to readers. Encoding failed: {e:?}"),
     }
@@ -1439,6 +2518,12 @@ impl Writer {
     requested_qos: &QosPolicies,
   ) {
     debug!("update_reader_proxy topic={:?}", self.my_topic_name);
+    let reader = reader_proxy.remote_reader_guid;
+    if local_endpoints::match_ignored(self.my_guid, reader) {
+      debug!("update_reader_proxy - ignoring local reader {reader:?}");
+      self.reader_lost(reader);
+      return;
+    }
     match self.qos_policies.compliance_failure_wrt(requested_qos) {
       // matched QoS
       None => {
@@ -1454,8 +2539,13 @@ impl Writer {
             local_writer: self.my_guid,
             remote_reader: reader_proxy.remote_reader_guid,
           });
-          // If we're reliable, should we send out a heartbeat so that new reader can
-          // catch up?
+          // Start heartbeats to the new reader, so that it can catch up. The first
+          // one is at a random point within the period, so that readers matched at
+          // the same time do not get their heartbeats at the same time.
+          if let Some(period) = self.heartbeat_period {
+            let first_delay = rand::thread_rng().gen_range(0.0..1.0) * period;
+            self.schedule_reader_heartbeat(reader_proxy.remote_reader_guid, first_delay);
+          }
           info!(
             "Matched new remote reader on topic={:?} reader={:?}",
             self.topic_name(),
@@ -1490,6 +2580,10 @@ impl Writer {
           requested_qos: Box::new(requested_qos.clone()),
           offered_qos: Box::new(self.qos_policies.clone()),
         });
+
+        // A QoS change, either ours or the reader's, may have made a matched
+        // reader incompatible.
+        self.reader_lost(reader_proxy.remote_reader_guid);
       }
     } // match
   }
@@ -1498,29 +2592,63 @@ impl Writer {
   // return 0 if the reader already existed
   // return 1 if it was new ( = count of added reader proxies)
   fn matched_reader_update(&mut self, updated_reader_proxy: &RtpsReaderProxy) -> i32 {
-    let mut new = 0;
-    let is_volatile = self.qos().is_volatile(); // Get this in advance to work with the borrow checker
+    if let Some(rp) = self
+      .readers
+      .get_mut(&updated_reader_proxy.remote_reader_guid)
+    {
+      rp.update(updated_reader_proxy);
+      return 0;
+    }
+
+    let mut new_proxy = updated_reader_proxy.clone();
+    if self.qos().is_volatile() {
+      // With Durabilty::Volatile QoS we won't send the sequence numbers which existed
+      // before matching with this reader. Therefore we set the reader as pending GAP
+      // for all existing sequence numbers
+      new_proxy.will_never_send_up_to(self.last_change_sequence_number, NotSentReason::Volatile);
+    } else {
+      // The existing sequence numbers are history replay to this reader.
+      new_proxy.replay_up_to = self.last_change_sequence_number;
+      if self
+        .qos_policies
+        .history_replay()
+        .is_some_and(|replay| replay.latest_per_instance)
+      {
+        for sn in self.superseded_changes() {
+          new_proxy.will_never_send(sn, NotSentReason::Superseded);
+        }
+      }
+    }
     self
       .readers
-      .entry(updated_reader_proxy.remote_reader_guid)
-      .and_modify(|rp| rp.update(updated_reader_proxy))
-      .or_insert_with(|| {
-        new = 1;
-        let mut new_proxy = updated_reader_proxy.clone();
-        if is_volatile {
-          // With Durabilty::Volatile QoS we won't send the sequence numbers which existed
-          // before matching with this reader. Therefore we set the reader as pending GAP
-          // for all existing sequence numbers
-          new_proxy.set_pending_gap_up_to(self.last_change_sequence_number);
+      .insert(updated_reader_proxy.remote_reader_guid, new_proxy);
+    self.stats.set_matched_readers(self.readers.len());
+    self.trace.matched(updated_reader_proxy.remote_reader_guid);
+    1
+  }
+
+  // Sequence numbers of the changes in our history that have a later change
+  // of the same instance. A NO_KEY topic has only one instance.
+  fn superseded_changes(&self) -> BTreeSet<SequenceNumber> {
+    let topic_cache = self.acquire_the_topic_cache_guard();
+    let mut latest: BTreeMap<Option<KeyHash>, SequenceNumber> = BTreeMap::new();
+    let mut superseded = BTreeSet::new();
+    for (sn, ts) in &self.sequence_number_to_instant {
+      if let Some(cc) = topic_cache.get_change(ts) {
+        if let Some(older) = latest.insert(cc.data_value.key_hash(), *sn) {
+          superseded.insert(older);
         }
-        new_proxy
-      });
-    new
+      }
+    }
+    superseded
   }
 
   fn matched_reader_remove(&mut self, guid: GUID) -> Option<RtpsReaderProxy> {
     let removed = self.readers.remove(&guid);
+    self.stats.set_matched_readers(self.readers.len());
+    self.round_trip_times.lock().unwrap().remove(&guid);
     if let Some(ref removed_reader) = removed {
+      self.trace.unmatched(removed_reader.remote_reader_guid);
       info!(
         "Removed reader proxy. topic={:?} reader={:?}",
         self.topic_name(),
@@ -1555,6 +2683,9 @@ impl Writer {
     }
     // also remember to remove reader from ack_waiter
     self.update_ack_waiters(guid, None);
+    // The remaining readers may have acknowledged more
+    self.report_deliveries();
+    self.clean_cache_on_ack_progress();
   }
 
   // Entire remote participant was lost.
@@ -1574,13 +2705,6 @@ impl Writer {
     self.readers.get_mut(&guid)
   }
 
-  pub fn sequence_number_to_instant(&self, sequence_number: SequenceNumber) -> Option<Timestamp> {
-    self
-      .sequence_number_to_instant
-      .get(&sequence_number)
-      .copied()
-  }
-
   pub fn topic_name(&self) -> &String {
     &self.my_topic_name
   }
@@ -1609,6 +2733,16 @@ impl Writer {
   // }
 }
 
+impl Drop for Writer {
+  fn drop(&mut self) {
+    let last = self.last_change_sequence_number;
+    self
+      .delivery_tracker
+      .drop_up_to(last, DropReason::WriterDeleted);
+    local_endpoints::remove_endpoint(self.my_guid);
+  }
+}
+
 impl RTPSEntity for Writer {
   fn guid(&self) -> GUID {
     self.my_guid
@@ -1621,34 +2755,293 @@ impl HasQoSPolicy for Writer {
   }
 }
 
+// Does the message contain a HEARTBEAT that the reader will process?
+fn has_heartbeat_to(message: &Message, reader: GUID) -> bool {
+  let mut destination = GuidPrefix::UNKNOWN;
+  message
+    .submessages
+    .iter()
+    .any(|submessage| match &submessage.body {
+      SubmessageBody::Interpreter(InterpreterSubmessage::InfoDestination(info_dst, _)) => {
+        destination = info_dst.guid_prefix;
+        false
+      }
+      SubmessageBody::Writer(WriterSubmessage::Heartbeat(heartbeat, _)) => {
+        (destination == GuidPrefix::UNKNOWN || destination == reader.prefix)
+          && (heartbeat.reader_id == EntityId::UNKNOWN || heartbeat.reader_id == reader.entity_id)
+      }
+      _ => false,
+    })
+}
+
 // -------------------------------------------------------------------------------------
 // -------------------------------------------------------------------------------------
 // -------------------------------------------------------------------------------------
 
 #[cfg(test)]
-mod tests {
-  use std::thread;
+pub(crate) mod test_util {
+  //! A Writer that runs without a DomainParticipant, for unit tests. The
+  //! messages it sends can be captured from its UDPSender.
 
-  use byteorder::LittleEndian;
-  use log::info;
+  use std::{
+    net::SocketAddr,
+    ops::{Deref, DerefMut},
+  };
 
+  use super::*;
   use crate::{
     dds::{
-      participant::DomainParticipant, qos::QosPolicies, topic::TopicKind,
-      with_key::datawriter::DataWriter,
+      qos::QosPolicyBuilder,
+      statusevents::{sync_status_channel, StatusChannelReceiver},
+      typedesc::TypeDesc,
     },
-    serialization::cdr_serializer::CDRSerializerAdapter,
-    test::random_data::*,
+    messages::submessages::submessages::{AckNack, WriterSubmessage},
+    rtps::SubmessageBody,
+    structure::{dds_cache::DDSCache, guid::EntityKind, sequence_number::SequenceNumberSet},
   };
 
-  #[test]
-  fn test_writer_receives_datawriter_cache_change_notifications() {
-    let domain_participant = DomainParticipant::new(0).expect("Failed to create participant");
-    let qos = QosPolicies::qos_none();
-    let _default_dw_qos = QosPolicies::qos_none();
+  pub(crate) struct TestWriter {
+    pub writer: Writer,
+    pub commands: mio_channel::SyncSender<WriterCommand>,
+    pub udp_sender: Rc<UDPSender>,
+    pub delivery_listener: DeliveryListenerSlot,
+    _status_receiver: StatusChannelReceiver<DataWriterStatus>,
+    _participant_status_receiver: StatusChannelReceiver<DomainParticipantStatusEvent>,
+  }
 
-    let publisher = domain_participant
-      .create_publisher(&qos)
+  impl TestWriter {
+    pub fn new(qos_policies: QosPolicies) -> Self {
+      Self::with_guid(
+        GUID::dummy_test_guid(EntityKind::WRITER_NO_KEY_USER_DEFINED),
+        qos_policies,
+      )
+    }
+
+    pub fn with_guid(guid: GUID, qos_policies: QosPolicies) -> Self {
+      let dds_cache = Arc::new(std::sync::RwLock::new(DDSCache::new()));
+      let topic_cache_handle = dds_cache.write().unwrap().add_new_topic(
+        "test_topic".to_string(),
+        TypeDesc::new("test_type".to_string()),
+        &qos_policies,
+      );
+      let (commands, writer_command_receiver) = mio_channel::sync_channel::<WriterCommand>(10);
+      let (status_sender, status_receiver) = sync_status_channel(4).unwrap();
+      let (participant_status_sender, participant_status_receiver) =
+        sync_status_channel(16).unwrap();
+      let delivery_listener = DeliveryListenerSlot::default();
+      let ingredients = WriterIngredients {
+        guid,
+        writer_command_receiver,
+        writer_command_receiver_waker: Arc::new(Mutex::new(None)),
+        topic_name: "test_topic".to_string(),
+        topic_cache_handle,
+        like_stateless: false,
+        qos_policies,
+        status_sender,
+        stale_samples_dropped: Arc::new(AtomicU64::new(0)),
+        round_trip_times: RoundTripTimes::default(),
+        delivery_listener: delivery_listener.clone(),
+        endianness: Endianness::LittleEndian,
+        security_plugins: None,
+      };
+      let udp_sender = Rc::new(UDPSender::new(0).unwrap());
+      let writer = Writer::new(
+        ingredients,
+        udp_sender.clone(),
+        mio_extras::timer::Builder::default().build(),
+        participant_status_sender,
+        WriterCacheCleaning::default(),
+      );
+      Self {
+        writer,
+        commands,
+        udp_sender,
+        delivery_listener,
+        _status_receiver: status_receiver,
+        _participant_status_receiver: participant_status_receiver,
+      }
+    }
+
+    /// Matches a reader that has the unicast locator 127.0.0.1:`port`
+    pub fn add_reader(&mut self, reader_guid: GUID, port: u16) {
+      self
+        .writer
+        .matched_reader_update(&reader_proxy(reader_guid, port));
+    }
+
+    /// Adds a change to the history cache without sending it
+    pub fn insert(&mut self, sequence_number: i64, payload: SerializedPayload) -> Timestamp {
+      self.writer.insert_to_history_cache(
+        DDSData::new(payload),
+        WriteOptions::default(),
+        SequenceNumber::from(sequence_number),
+      )
+    }
+
+    /// Writes a change like the DataWriter does
+    pub fn write(&mut self, sequence_number: i64, payload: SerializedPayload) {
+      self.write_with_send_deadline(sequence_number, payload, None);
+    }
+
+    /// Like `write`, with the send deadline that the DataWriter derives from
+    /// its max_send_age
+    pub fn write_with_send_deadline(
+      &mut self,
+      sequence_number: i64,
+      payload: SerializedPayload,
+      send_deadline: Option<Instant>,
+    ) {
+      self
+        .commands
+        .send(WriterCommand::DDSData {
+          ddsdata: DDSData::new(payload),
+          write_options: WriteOptions::default(),
+          sequence_number: SequenceNumber::from(sequence_number),
+          send_deadline,
+        })
+        .unwrap();
+      self.writer.process_writer_command();
+    }
+
+    /// Receives an ACKNACK that acknowledges everything before `base`
+    pub fn ack(&mut self, reader_guid: GUID, base: i64, count: i32) {
+      let acknack = AckSubmessage::AckNack(AckNack {
+        reader_id: reader_guid.entity_id,
+        writer_id: self.writer.my_guid.entity_id,
+        reader_sn_state: SequenceNumberSet::new_empty(SequenceNumber::from(base)),
+        count,
+      });
+      self.writer.handle_ack_nack(reader_guid.prefix, &acknack);
+    }
+
+    /// The messages sent while running `f`, with their destinations
+    pub fn sent_by(&mut self, f: impl FnOnce(&mut Self)) -> Vec<(SocketAddr, Message)> {
+      self.udp_sender.start_capture();
+      f(self);
+      self
+        .udp_sender
+        .take_capture()
+        .into_iter()
+        .map(|datagram| {
+          let message = Message::read_from_buffer(&datagram.payload).unwrap();
+          (datagram.destination, message)
+        })
+        .collect()
+    }
+  }
+
+  impl Deref for TestWriter {
+    type Target = Writer;
+    fn deref(&self) -> &Writer {
+      &self.writer
+    }
+  }
+
+  impl DerefMut for TestWriter {
+    fn deref_mut(&mut self) -> &mut Writer {
+      &mut self.writer
+    }
+  }
+
+  /// Reliable and KeepAll
+  pub(crate) fn reliable_qos() -> QosPolicyBuilder {
+    QosPolicyBuilder::new()
+      .reliability(policy::Reliability::Reliable {
+        max_blocking_time: Duration::from_millis(100),
+      })
+      .history(policy::History::KeepAll)
+  }
+
+  /// Reader number `n`, in a remote participant of its own
+  pub(crate) fn test_reader_guid(n: u8) -> GUID {
+    GUID::new(
+      GuidPrefix::new(&[n; 12]),
+      EntityId::create_custom_entity_id([0, 0, n], EntityKind::READER_NO_KEY_USER_DEFINED),
+    )
+  }
+
+  /// A reader that has the unicast locator 127.0.0.1:`port`
+  pub(crate) fn reader_proxy(reader_guid: GUID, port: u16) -> RtpsReaderProxy {
+    reader_proxy_with_qos(reader_guid, QosPolicies::qos_none(), port)
+  }
+
+  pub(crate) fn reader_proxy_with_qos(
+    reader_guid: GUID,
+    qos: QosPolicies,
+    port: u16,
+  ) -> RtpsReaderProxy {
+    let mut proxy = RtpsReaderProxy::new(reader_guid, qos, false);
+    proxy.unicast_locator_list = vec![Locator::from(SocketAddr::from(([127, 0, 0, 1], port)))];
+    proxy
+  }
+
+  /// The writer submessages of `messages`, in order
+  pub(crate) fn writer_submessages(messages: &[(SocketAddr, Message)]) -> Vec<WriterSubmessage> {
+    messages
+      .iter()
+      .flat_map(|(_, message)| &message.submessages)
+      .filter_map(|s| match &s.body {
+        SubmessageBody::Writer(body) => Some(body.clone()),
+        _ => None,
+      })
+      .collect()
+  }
+
+  /// The number of DATA submessages in each of `messages`
+  pub(crate) fn data_counts(messages: &[(SocketAddr, Message)]) -> Vec<usize> {
+    messages
+      .iter()
+      .map(|(_, message)| {
+        message
+          .submessages
+          .iter()
+          .filter(|s| matches!(s.body, SubmessageBody::Writer(WriterSubmessage::Data(..))))
+          .count()
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::thread;
+
+  use byteorder::LittleEndian;
+  use log::info;
+
+  use super::{test_util::*, *};
+  use crate::{
+    dds::{
+      delivery::DeliveryListener,
+      participant::DomainParticipant,
+      qos::{policy, QosPolicies, QosPolicyBuilder},
+      topic::TopicKind,
+      with_key::datawriter::DataWriter,
+    },
+    messages::submessages::{
+      elements::serialized_payload::SerializedPayload,
+      submessage_flag::endianness_flag,
+      submessage_kind::SubmessageKind,
+      submessages::{AckNack, HEARTBEAT_Flags, InterpreterSubmessage, NackFrag, WriterSubmessage},
+    },
+    rtps::{Message, MessageBuilder, SubmessageBody},
+    serialization::cdr_serializer::CDRSerializerAdapter,
+    structure::{
+      cache_change::ChangeKind,
+      guid::{EntityId, EntityKind, GUID},
+      sequence_number::{FragmentNumberSet, SequenceNumberSet},
+    },
+    test::{pcap, random_data::*},
+  };
+
+  #[test]
+  fn test_writer_receives_datawriter_cache_change_notifications() {
+    let domain_participant = DomainParticipant::new(0).expect("Failed to create participant");
+    let qos = QosPolicies::qos_none();
+    let _default_dw_qos = QosPolicies::qos_none();
+
+    let publisher = domain_participant
+      .create_publisher(&qos)
       .expect("Failed to create publisher");
     let topic = domain_participant
       .create_topic(
@@ -1691,4 +3084,1149 @@ mod tests {
     thread::sleep(std::time::Duration::from_millis(100));
     info!("writerResult:  {:?}", write_result);
   }
+
+  fn heartbeat_count_and_flags(writer: &Writer, liveliness: bool) -> (i32, bool, bool) {
+    let message = MessageBuilder::new()
+      .heartbeat_msg(writer, EntityId::UNKNOWN, false, liveliness)
+      .add_header_and_build(writer.my_guid.prefix);
+    match &message.submessages[0].body {
+      SubmessageBody::Writer(WriterSubmessage::Heartbeat(hb, flags)) => (
+        hb.count,
+        flags.contains(HEARTBEAT_Flags::Final),
+        flags.contains(HEARTBEAT_Flags::Liveliness),
+      ),
+      other => panic!("Expected HEARTBEAT, got {other:?}"),
+    }
+  }
+
+  #[test]
+  fn writer_output_captured_to_pcap() {
+    let mut writer = TestWriter::new(QosPolicies::qos_none());
+    writer.insert(
+      1,
+      SerializedPayload::new(crate::RepresentationIdentifier::CDR_LE, vec![7; 12]),
+    );
+
+    writer.udp_sender.start_capture();
+    let destination: std::net::SocketAddr = "127.0.0.1:7499".parse().unwrap();
+    writer.send_latest_change_to(&Locator::from(destination));
+    let capture = pcap::write_pcap(&writer.udp_sender.take_capture());
+
+    let datagrams = pcap::read_capture(&capture).unwrap();
+    assert_eq!(datagrams.len(), 1);
+    assert_eq!(datagrams[0].destination, destination);
+    let message = Message::read_from_buffer(&datagrams[0].payload).unwrap();
+    assert_eq!(message.header.guid_prefix, writer.my_guid.prefix);
+    assert!(message
+      .submessages
+      .iter()
+      .any(|s| s.header.kind == SubmessageKind::DATA));
+  }
+
+  #[test]
+  fn big_endian_output_has_same_content_as_little_endian() {
+    // Writes a small and a fragmented sample, and returns the parsed writer
+    // submessages with their endianness.
+    let writer_output = |endianness: Endianness| {
+      let mut writer = TestWriter::new(QosPolicies::qos_none());
+      writer.add_reader(test_reader_guid(1), 7503);
+      writer
+        .commands
+        .send(WriterCommand::SetEndianness { endianness })
+        .unwrap();
+      writer
+        .sent_by(|writer| {
+          for (sequence_number, size) in [(1, 12), (2, 3000)] {
+            let payload =
+              SerializedPayload::new(crate::RepresentationIdentifier::CDR_BE, vec![7; size]);
+            writer.write(sequence_number, payload);
+          }
+        })
+        .into_iter()
+        .flat_map(|(_, message)| message.submessages)
+        .filter_map(|s| match s.body {
+          SubmessageBody::Writer(body) => Some((endianness_flag(s.header.flags), body)),
+          _ => None,
+        })
+        .collect::<Vec<_>>()
+    };
+
+    let little = writer_output(Endianness::LittleEndian);
+    let big = writer_output(Endianness::BigEndian);
+    assert!(big
+      .iter()
+      .any(|(_, s)| matches!(s, WriterSubmessage::DataFrag(..))));
+    assert!(big.iter().all(|(e, _)| *e == Endianness::BigEndian));
+    assert!(little.iter().all(|(e, _)| *e == Endianness::LittleEndian));
+    assert_eq!(little.len(), big.len());
+    for ((_, l), (_, b)) in little.iter().zip(&big) {
+      match (l, b) {
+        (WriterSubmessage::Data(l, _), WriterSubmessage::Data(b, _)) => assert_eq!(l, b),
+        (WriterSubmessage::DataFrag(l, _), WriterSubmessage::DataFrag(b, _)) => assert_eq!(l, b),
+        (WriterSubmessage::Heartbeat(l, _), WriterSubmessage::Heartbeat(b, _)) => {
+          assert_eq!(l, b);
+        }
+        other => panic!("Different submessages {other:?}"),
+      }
+    }
+  }
+
+  #[test]
+  fn heartbeat_goes_only_to_the_reader_behind() {
+    let mut writer = TestWriter::new(reliable_qos().build());
+    writer.insert(1, SerializedPayload::default());
+
+    let multicast: std::net::SocketAddr = "239.255.0.1:7400".parse().unwrap();
+    let reader_guids = [test_reader_guid(1), test_reader_guid(2)];
+    for (reader_guid, port) in reader_guids.iter().zip([7501, 7502]) {
+      let mut proxy = reader_proxy(*reader_guid, port);
+      proxy.multicast_locator_list = vec![Locator::from(multicast)];
+      writer.matched_reader_update(&proxy);
+    }
+    let heartbeat_destinations = |writer: &mut TestWriter| {
+      writer
+        .sent_by(|writer| writer.handle_heartbeat_tick(false))
+        .into_iter()
+        .map(|(destination, message)| {
+          let info_dst = message.submessages.iter().find_map(|s| match &s.body {
+            SubmessageBody::Interpreter(InterpreterSubmessage::InfoDestination(dst, _)) => {
+              Some(dst.guid_prefix)
+            }
+            _ => None,
+          });
+          (destination, info_dst)
+        })
+        .collect::<Vec<_>>()
+    };
+
+    // Both readers are behind: multicast, no INFO_DST
+    let destinations = heartbeat_destinations(&mut writer);
+    assert!(!destinations.is_empty());
+    assert!(destinations.iter().all(|d| *d == (multicast, None)));
+
+    // The first reader acknowledges everything. Only the second one is asked.
+    writer.ack(reader_guids[0], 2, 1);
+    assert_eq!(
+      heartbeat_destinations(&mut writer),
+      vec![(
+        "127.0.0.1:7502".parse().unwrap(),
+        Some(reader_guids[1].prefix)
+      )]
+    );
+  }
+
+  #[test]
+  fn heartbeats_are_scheduled_per_reader() {
+    let qos = reliable_qos().build();
+    let mut writer = TestWriter::new(qos.clone());
+    let reader_guids = [test_reader_guid(1), test_reader_guid(2)];
+    for (reader_guid, port) in reader_guids.iter().zip([7501, 7502]) {
+      writer.update_reader_proxy(&reader_proxy(*reader_guid, port), &qos);
+    }
+    writer.write(1, SerializedPayload::default());
+    let timers: Vec<u64> = reader_guids
+      .iter()
+      .map(|guid| writer.readers[guid].heartbeat_timer.unwrap())
+      .collect();
+    assert_ne!(timers[0], timers[1]);
+
+    // The timer of a reader heartbeats only that reader, and is restarted.
+    let sent = writer.sent_by(|writer| writer.handle_reader_heartbeat(reader_guids[0], timers[0]));
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].0, "127.0.0.1:7501".parse().unwrap());
+    assert!(matches!(
+      writer_submessages(&sent)[..],
+      [WriterSubmessage::Heartbeat(..)]
+    ));
+    let restarted = writer.readers[&reader_guids[0]].heartbeat_timer.unwrap();
+    assert_ne!(restarted, timers[0]);
+    // The replaced timer is stale
+    assert!(writer
+      .sent_by(|writer| writer.handle_reader_heartbeat(reader_guids[0], timers[0]))
+      .is_empty());
+
+    // A reader that has acknowledged everything is not heartbeated, and its
+    // timer stops until there is new data.
+    writer.ack(reader_guids[1], 2, 1);
+    assert!(writer
+      .sent_by(|writer| writer.handle_reader_heartbeat(reader_guids[1], timers[1]))
+      .is_empty());
+    assert_eq!(writer.readers[&reader_guids[1]].heartbeat_timer, None);
+    writer.write(2, SerializedPayload::default());
+    assert!(writer.readers[&reader_guids[1]].heartbeat_timer.is_some());
+  }
+
+  #[test]
+  fn nack_to_multicast_heartbeat_is_repaired_over_multicast() {
+    let mut writer = TestWriter::new(reliable_qos().build());
+    writer.insert(1, SerializedPayload::default());
+
+    let multicast: std::net::SocketAddr = "239.255.0.1:7400".parse().unwrap();
+    let reader_guids = [test_reader_guid(1), test_reader_guid(2)];
+    for (reader_guid, port) in reader_guids.iter().zip([7501, 7502]) {
+      let mut proxy = reader_proxy(*reader_guid, port);
+      proxy.multicast_locator_list = vec![Locator::from(multicast)];
+      writer.matched_reader_update(&proxy);
+    }
+    let nack = |writer: &mut TestWriter, reader_guid: GUID, count: i32| {
+      let nack = AckSubmessage::AckNack(AckNack {
+        reader_id: reader_guid.entity_id,
+        writer_id: writer.my_guid.entity_id,
+        reader_sn_state: SequenceNumberSet::from_base_and_set(
+          SequenceNumber::from(1),
+          &[SequenceNumber::from(1)].into_iter().collect(),
+        ),
+        count,
+      });
+      writer.handle_ack_nack(reader_guid.prefix, &nack);
+    };
+    // Destinations of the repair, and if it was addressed to the reader
+    let repair = |writer: &mut TestWriter, reader_guid: GUID| {
+      writer
+        .sent_by(|writer| writer.handle_repair_data_send(reader_guid))
+        .into_iter()
+        .map(|(destination, message)| {
+          let addressed = message.submessages.iter().any(|s| {
+            matches!(
+              s.body,
+              SubmessageBody::Interpreter(InterpreterSubmessage::InfoDestination(..))
+            )
+          });
+          (destination, addressed)
+        })
+        // Multicast goes out on each interface
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>()
+    };
+
+    // Both readers are behind, so the HEARTBEAT goes to multicast. So does
+    // the repair, for the other reader to see it.
+    writer.handle_heartbeat_tick(false);
+    nack(&mut writer, reader_guids[0], 1);
+    assert_eq!(
+      repair(&mut writer, reader_guids[0]),
+      vec![(multicast, false)]
+    );
+
+    // The first reader has everything. The HEARTBEAT and the repair go only to
+    // the second one.
+    writer.ack(reader_guids[0], 2, 2);
+    writer.handle_heartbeat_tick(false);
+    nack(&mut writer, reader_guids[1], 1);
+    assert_eq!(
+      repair(&mut writer, reader_guids[1]),
+      vec![("127.0.0.1:7502".parse().unwrap(), true)]
+    );
+  }
+
+  #[test]
+  fn round_trip_time_is_measured_from_heartbeat_to_acknack() {
+    let mut writer = TestWriter::new(
+      reliable_qos()
+        .adaptive_timing(policy::AdaptiveTiming {
+          min_heartbeat_period: Duration::from_millis(200),
+        })
+        .build(),
+    );
+    writer.insert(1, SerializedPayload::default());
+    let (reader_1, reader_2) = (test_reader_guid(1), test_reader_guid(2));
+    writer.add_reader(reader_1, 7501);
+    writer.add_reader(reader_2, 7502);
+    let measured = |writer: &TestWriter| -> Vec<GUID> {
+      writer
+        .round_trip_times
+        .lock()
+        .unwrap()
+        .keys()
+        .copied()
+        .collect()
+    };
+    let period = Duration::from_secs(1);
+
+    // Not measured yet: the usual timing applies
+    writer.ack(reader_1, 1, 1);
+    assert!(measured(&writer).is_empty());
+    assert_eq!(writer.reader_heartbeat_period(reader_1, period), period);
+
+    // The DATA of a write carries another HEARTBEAT to all readers before the
+    // response, so the response is ambiguous.
+    writer.handle_heartbeat_tick(false);
+    writer.write(2, SerializedPayload::default());
+    writer.ack(reader_1, 1, 2);
+    assert!(measured(&writer).is_empty());
+
+    // HEARTBEATs to other readers do not matter.
+    writer.handle_heartbeat_tick(false);
+    writer.readers.get_mut(&reader_2).unwrap().heartbeat_timer = Some(7);
+    writer.handle_reader_heartbeat(reader_2, 7);
+    writer.ack(reader_1, 1, 3);
+    assert_eq!(measured(&writer), vec![reader_1]);
+    writer.ack(reader_2, 1, 1);
+    assert_eq!(measured(&writer), vec![reader_1, reader_2]);
+
+    // A fast reader may get heartbeats more often, but not below the minimum.
+    let adapted = writer.reader_heartbeat_period(reader_1, period);
+    assert!(Duration::from_millis(200) <= adapted && adapted <= period);
+
+    writer.matched_reader_remove(reader_1);
+    assert_eq!(measured(&writer), vec![reader_2]);
+  }
+
+  #[test]
+  fn batching_holds_samples_until_full_or_flushed() {
+    let mut writer = TestWriter::new(
+      QosPolicyBuilder::new()
+        .batching(policy::Batching {
+          max_samples: 3,
+          max_delay: Duration::from_secs(60),
+        })
+        .build(),
+    );
+    writer.add_reader(test_reader_guid(1), 7501);
+    let batching = writer.qos_policies.batching().unwrap();
+
+    // Returns the number of DATA submessages in each message sent
+    let mut sequence_number = 0;
+    let mut write = |writer: &mut TestWriter, count: usize| {
+      let sent = writer.sent_by(|writer| {
+        for _ in 0..count {
+          sequence_number += 1;
+          let timestamp = writer.insert(sequence_number, SerializedPayload::default());
+          writer.add_to_batch(timestamp, batching);
+        }
+      });
+      data_counts(&sent)
+    };
+
+    assert!(write(&mut writer, 2).is_empty());
+    assert_eq!(write(&mut writer, 1), vec![3]);
+    assert!(write(&mut writer, 1).is_empty());
+
+    let sent = writer.sent_by(|writer| writer.flush_batch());
+    assert_eq!(data_counts(&sent), vec![1]);
+    // Nothing left to flush
+    assert!(writer.sent_by(|writer| writer.flush_batch()).is_empty());
+  }
+
+  #[test]
+  fn suspended_publications_are_sent_together_on_resume() {
+    let mut writer = TestWriter::new(QosPolicies::qos_none());
+    writer.add_reader(test_reader_guid(1), 7502);
+
+    let sent = writer.sent_by(|writer| writer.write(1, SerializedPayload::default()));
+    assert_eq!(data_counts(&sent), vec![1]);
+
+    let sent = writer.sent_by(|writer| {
+      writer
+        .commands
+        .send(WriterCommand::SuspendPublications)
+        .unwrap();
+      writer.write(2, SerializedPayload::default());
+      writer.write(3, SerializedPayload::default());
+      writer.commands.send(WriterCommand::Flush).unwrap();
+      writer.process_writer_command();
+      writer.write(4, SerializedPayload::default());
+    });
+    assert!(sent.is_empty());
+    // Held back changes are not announced
+    assert_eq!(writer.announced_sequence_number(), SequenceNumber::from(1));
+
+    let sent = writer.sent_by(|writer| {
+      writer
+        .commands
+        .send(WriterCommand::ResumePublications)
+        .unwrap();
+      writer.process_writer_command();
+    });
+    assert_eq!(data_counts(&sent), vec![3]);
+    assert_eq!(writer.announced_sequence_number(), SequenceNumber::from(4));
+  }
+
+  #[test]
+  fn asynchronous_publishing_is_paced() {
+    let mut writer = TestWriter::new(
+      QosPolicyBuilder::new()
+        .publish_mode(policy::PublishMode::Asynchronous {
+          bytes_per_period: 1,
+          period: Duration::from_millis(100),
+        })
+        .build(),
+    );
+    writer.add_reader(test_reader_guid(1), 7501);
+
+    // One message per period is sent, the rest wait.
+    let sent = writer.sent_by(|writer| {
+      for sn in 1..=3 {
+        writer.write(sn, SerializedPayload::default());
+      }
+    });
+    assert_eq!(data_counts(&sent), vec![1]);
+    assert_eq!(writer.async_send_queue.len(), 2);
+    for _ in 0..2 {
+      let sent = writer.sent_by(|writer| writer.handle_async_send());
+      assert_eq!(data_counts(&sent), vec![1]);
+    }
+    assert!(writer.async_send_queue.is_empty());
+    assert_eq!(writer.async_send_queue_bytes, 0);
+  }
+
+  #[test]
+  fn stale_best_effort_samples_are_dropped() {
+    let mut writer = TestWriter::new(QosPolicies::qos_none());
+    writer.add_reader(test_reader_guid(1), 7501);
+    let later = Instant::now() + std::time::Duration::from_secs(60);
+    let earlier = Instant::now() - std::time::Duration::from_secs(1);
+
+    // Without a deadline, or before it, samples are sent.
+    let sent = writer.sent_by(|writer| {
+      writer.write_with_send_deadline(1, SerializedPayload::default(), None);
+      writer.write_with_send_deadline(2, SerializedPayload::default(), Some(later));
+    });
+    assert_eq!(data_counts(&sent), vec![1, 1]);
+    assert_eq!(writer.stale_samples_dropped.load(Ordering::Relaxed), 0);
+
+    // Past the deadline, a sample is neither sent nor kept.
+    let sent = writer.sent_by(|writer| {
+      writer.write_with_send_deadline(3, SerializedPayload::default(), Some(earlier));
+    });
+    assert!(sent.is_empty());
+    assert_eq!(writer.stale_samples_dropped.load(Ordering::Relaxed), 1);
+    let cached = writer
+      .acquire_the_topic_cache_guard()
+      .get_changes_in_range_best_effort(Timestamp::ZERO)
+      .count();
+    assert_eq!(cached, 2);
+
+    // A Reliable writer sends it anyway.
+    let mut writer = TestWriter::new(reliable_qos().build());
+    writer.add_reader(test_reader_guid(1), 7501);
+    let sent = writer.sent_by(|writer| {
+      writer.write_with_send_deadline(1, SerializedPayload::default(), Some(earlier));
+    });
+    assert_eq!(data_counts(&sent), vec![1]);
+    assert_eq!(writer.stale_samples_dropped.load(Ordering::Relaxed), 0);
+  }
+
+  #[test]
+  fn asynchronous_send_queue_drops_stale_samples() {
+    let mut writer = TestWriter::new(
+      QosPolicyBuilder::new()
+        .publish_mode(policy::PublishMode::Asynchronous {
+          bytes_per_period: 1,
+          period: Duration::from_millis(100),
+        })
+        .build(),
+    );
+    writer.add_reader(test_reader_guid(1), 7501);
+
+    let later = Instant::now() + std::time::Duration::from_secs(60);
+    let sent = writer.sent_by(|writer| {
+      for sn in 1..=3 {
+        writer.write_with_send_deadline(sn, SerializedPayload::default(), Some(later));
+      }
+    });
+    assert_eq!(data_counts(&sent), vec![1]);
+    assert_eq!(writer.async_send_queue.len(), 2);
+
+    // The deadline of the second sample passes while it waits in the queue.
+    writer.async_send_queue[0].send_deadline =
+      Some(Instant::now() - std::time::Duration::from_secs(1));
+    let sent = writer.sent_by(|writer| writer.handle_async_send());
+    assert_eq!(data_counts(&sent), vec![1]);
+    assert_eq!(writer.stale_samples_dropped.load(Ordering::Relaxed), 1);
+    assert!(writer.async_send_queue.is_empty());
+    assert_eq!(writer.async_send_queue_bytes, 0);
+  }
+
+  #[test]
+  fn asynchronous_heartbeat_is_built_when_sent() {
+    let mut writer = TestWriter::new(
+      QosPolicyBuilder::new()
+        .publish_mode(policy::PublishMode::Asynchronous {
+          bytes_per_period: 1,
+          period: Duration::from_millis(100),
+        })
+        .build(),
+    );
+    writer.add_reader(test_reader_guid(1), 7501);
+    let heartbeat_last_sns = |sent: &[(std::net::SocketAddr, Message)]| {
+      sent
+        .iter()
+        .flat_map(|(_, message)| &message.submessages)
+        .filter_map(|s| match &s.body {
+          SubmessageBody::Writer(WriterSubmessage::Heartbeat(hb, _)) => Some(hb.last_sn),
+          _ => None,
+        })
+        .collect::<Vec<_>>()
+    };
+
+    let sent = writer.sent_by(|writer| {
+      for sn in 1..=3 {
+        writer.write(sn, SerializedPayload::default());
+      }
+    });
+    assert_eq!(heartbeat_last_sns(&sent), vec![SequenceNumber::from(1)]);
+
+    // The queued sample announces everything written by the time it is sent.
+    let sent = writer.sent_by(|writer| writer.handle_async_send());
+    assert_eq!(heartbeat_last_sns(&sent), vec![SequenceNumber::from(3)]);
+  }
+
+  #[test]
+  fn full_asynchronous_send_queue_holds_back_writes() {
+    let mut writer = TestWriter::new(
+      QosPolicyBuilder::new()
+        .publish_mode(policy::PublishMode::Asynchronous {
+          bytes_per_period: 1,
+          period: Duration::from_millis(100),
+        })
+        .build(),
+    );
+    writer.add_reader(test_reader_guid(1), 7501);
+    // Room for two samples in the queue
+    let payload = || SerializedPayload::new(crate::RepresentationIdentifier::CDR_LE, vec![0; 96]);
+    writer.async_send_queue_max_bytes = 200;
+
+    // The first sample is sent right away, the next two fill the queue, and
+    // the last two are left to the DataWriter, which blocks when its channel
+    // is full.
+    for sn in 1..=5 {
+      writer
+        .commands
+        .send(WriterCommand::DDSData {
+          ddsdata: DDSData::new(payload()),
+          write_options: WriteOptions::default(),
+          sequence_number: SequenceNumber::from(sn),
+          send_deadline: None,
+        })
+        .unwrap();
+    }
+    writer.process_writer_command();
+    assert_eq!(writer.async_send_queue.len(), 2);
+    assert_eq!(writer.last_change_sequence_number, SequenceNumber::from(3));
+    assert!(writer.commands_paused);
+
+    // When the flow controller has made room, the rest are taken.
+    writer.handle_async_send();
+    writer.process_writer_command();
+    assert_eq!(writer.last_change_sequence_number, SequenceNumber::from(4));
+    assert!(writer.commands_paused);
+    while !writer.async_send_queue.is_empty() {
+      writer.handle_async_send();
+      writer.process_writer_command();
+    }
+    assert_eq!(writer.last_change_sequence_number, SequenceNumber::from(5));
+    assert!(!writer.commands_paused);
+  }
+
+  #[test]
+  fn writer_heartbeat_count_is_monotonic() {
+    let writer = TestWriter::new(QosPolicies::qos_none());
+    let (first, final_flag, liveliness_flag) = heartbeat_count_and_flags(&writer, false);
+    assert!(first > 0);
+    assert!(!final_flag && !liveliness_flag);
+    // Each HEARTBEAT gets a new count, even if the writer state is unchanged.
+    let (second, _, liveliness_flag) = heartbeat_count_and_flags(&writer, true);
+    assert_eq!(second, first + 1);
+    assert!(liveliness_flag);
+    assert!(!writer.writing_asserts_liveliness());
+
+    let manual_writer = TestWriter::new(
+      QosPolicyBuilder::new()
+        .liveliness(policy::Liveliness::ManualByTopic {
+          lease_duration: Duration::from_secs(1),
+        })
+        .build(),
+    );
+    assert!(manual_writer.writing_asserts_liveliness());
+  }
+
+  #[test]
+  fn history_replay_latest_per_instance_and_batches() {
+    let mut writer = TestWriter::new(
+      reliable_qos()
+        .durability(policy::Durability::TransientLocal)
+        .history_replay(policy::HistoryReplay {
+          max_batch_size: 2,
+          batch_period: Duration::from_millis(200),
+          latest_per_instance: true,
+        })
+        .build(),
+    );
+    // Instances a, b, a, b, a
+    let key = |k: u8| Some(KeyHash::from_pl_cdr_bytes(vec![k; 16]).unwrap());
+    for (sn, k) in [1, 2, 1, 2, 1].into_iter().enumerate() {
+      let data = DDSData::new(SerializedPayload::default()).with_key_hash(key(k));
+      writer.insert_to_history_cache(
+        data,
+        WriteOptions::default(),
+        SequenceNumber::from(sn as i64 + 1),
+      );
+    }
+
+    let reader_guid = test_reader_guid(1);
+    assert_eq!(
+      writer.matched_reader_update(&reader_proxy(reader_guid, 7501)),
+      1
+    );
+    let rp = writer.readers.get_mut(&reader_guid).unwrap();
+    assert_eq!(rp.replay_up_to, SequenceNumber::from(5));
+    // Only the latest samples of a (5) and b (4) are replayed.
+    let superseded: BTreeSet<SequenceNumber> =
+      [1, 2, 3].into_iter().map(SequenceNumber::from).collect();
+    assert_eq!(rp.get_pending_gap(), &superseded);
+
+    // The reader requests the whole history.
+    for sn in 1..=5 {
+      rp.notify_new_cache_change(SequenceNumber::from(sn));
+    }
+    assert!(rp.is_replaying());
+    // The first batch is a GAP for 1..=3 and the DATA of 4.
+    writer.handle_repair_data_send(reader_guid);
+    let rp = writer.readers.get(&reader_guid).unwrap();
+    assert_eq!(rp.unsent_changes_debug(), vec![SequenceNumber::from(5)]);
+    writer.handle_repair_data_send(reader_guid);
+    let rp = writer.readers.get(&reader_guid).unwrap();
+    assert!(rp.unsent_changes_debug().is_empty());
+    assert!(!rp.is_replaying());
+  }
+
+  #[test]
+  fn cache_cleaning_keeps_depth_per_instance() {
+    let mut writer = TestWriter::new(
+      reliable_qos()
+        .durability(policy::Durability::TransientLocal)
+        .history(policy::History::KeepLast { depth: 1 })
+        .build(),
+    );
+    // Instances a, b, a, a, a
+    let key = |k: u8| Some(KeyHash::from_pl_cdr_bytes(vec![k; 16]).unwrap());
+    for (sn, k) in [1, 2, 1, 1, 1].into_iter().enumerate() {
+      let data = DDSData::new(SerializedPayload::default()).with_key_hash(key(k));
+      writer.insert_to_history_cache(
+        data,
+        WriteOptions::default(),
+        SequenceNumber::from(sn as i64 + 1),
+      );
+    }
+
+    let reader_guid = test_reader_guid(1);
+    writer.add_reader(reader_guid, 7501);
+
+    // Nothing is removed before the reader has acked
+    writer.handle_cache_cleaning();
+    assert_eq!(writer.sequence_number_to_instant.len(), 5);
+
+    // The only sample of b (2) is kept, even though it is older than the
+    // latest sample of a (5)
+    writer
+      .readers
+      .get_mut(&reader_guid)
+      .unwrap()
+      .all_acked_before = SequenceNumber::from(6);
+    writer.handle_cache_cleaning();
+    let kept: Vec<SequenceNumber> = writer.sequence_number_to_instant.keys().copied().collect();
+    assert_eq!(kept, vec![SequenceNumber::from(2), SequenceNumber::from(5)]);
+    assert_eq!(writer.first_change_sequence_number, SequenceNumber::from(2));
+    assert_eq!(writer.compacted_before, SequenceNumber::from(6));
+  }
+
+  #[test]
+  fn cache_is_cleaned_on_ack_progress() {
+    let mut writer = TestWriter::new(
+      reliable_qos()
+        .history(policy::History::KeepLast { depth: 1 })
+        .build(),
+    );
+    writer.cache_cleaning = WriterCacheCleaning {
+      acked_samples: 3,
+      acked_bytes: usize::MAX,
+      fallback_period: std::time::Duration::from_secs(60),
+    };
+    for sn in 1..=5 {
+      writer.insert(sn, SerializedPayload::default());
+    }
+    assert!(writer.cache_cleaning_scheduled);
+
+    let reader_guid = test_reader_guid(1);
+    writer.add_reader(reader_guid, 7501);
+    let ack = |writer: &mut TestWriter, acked_before: i64| {
+      writer
+        .readers
+        .get_mut(&reader_guid)
+        .unwrap()
+        .all_acked_before = SequenceNumber::from(acked_before);
+      writer.clean_cache_on_ack_progress();
+    };
+
+    // Two acked samples are left for the fallback timer
+    ack(&mut writer, 3);
+    assert_eq!(writer.sequence_number_to_instant.len(), 5);
+    // Four are enough
+    ack(&mut writer, 5);
+    let kept: Vec<SequenceNumber> = writer.sequence_number_to_instant.keys().copied().collect();
+    assert_eq!(kept, vec![SequenceNumber::from(5)]);
+
+    // The byte limit counts only the samples acked after the cleaning
+    writer.cache_cleaning.acked_bytes = 2 * SerializedPayload::default().len_serialized();
+    writer.insert(6, SerializedPayload::default());
+    writer.insert(7, SerializedPayload::default());
+    ack(&mut writer, 6);
+    assert_eq!(writer.sequence_number_to_instant.len(), 3);
+    ack(&mut writer, 7);
+    let kept: Vec<SequenceNumber> = writer.sequence_number_to_instant.keys().copied().collect();
+    assert_eq!(kept, vec![SequenceNumber::from(7)]);
+  }
+
+  #[test]
+  fn remote_reader_update_is_applied_in_place() {
+    let mut writer = TestWriter::new(
+      QosPolicyBuilder::new()
+        .reliability(policy::Reliability::BestEffort)
+        .build(),
+    );
+    let reader_guid = test_reader_guid(1);
+    let best_effort = QosPolicyBuilder::new()
+      .reliability(policy::Reliability::BestEffort)
+      .build();
+    let mut proxy = reader_proxy_with_qos(reader_guid, best_effort.clone(), 7411);
+    writer.update_reader_proxy(&proxy, &best_effort);
+    writer
+      .readers
+      .get_mut(&reader_guid)
+      .unwrap()
+      .all_acked_before = SequenceNumber::from(3);
+
+    // New locators replace the old ones, but the reader state is kept.
+    let new_locators = reader_proxy(reader_guid, 7412).unicast_locator_list;
+    proxy.unicast_locator_list = new_locators.clone();
+    writer.update_reader_proxy(&proxy, &best_effort);
+    assert_eq!(writer.readers.len(), 1);
+    let rp = &writer.readers[&reader_guid];
+    assert_eq!(rp.unicast_locator_list, new_locators);
+    assert_eq!(rp.all_acked_before, SequenceNumber::from(3));
+
+    // Requesting reliability, which we do not offer, unmatches the reader.
+    let reliable = reliable_qos().build();
+    let proxy = RtpsReaderProxy::new(reader_guid, reliable.clone(), false);
+    writer.update_reader_proxy(&proxy, &reliable);
+    assert!(writer.readers.is_empty());
+
+    // Matched again, and then re-announced on another topic. Discovery then
+    // unmatches it.
+    let proxy = RtpsReaderProxy::new(reader_guid, best_effort.clone(), false);
+    writer.update_reader_proxy(&proxy, &best_effort);
+    assert_eq!(writer.readers.len(), 1);
+    writer.reader_lost(reader_guid);
+    assert!(writer.readers.is_empty());
+  }
+
+  #[test]
+  fn matched_readers_query_lists_matched_reader_guids() {
+    let mut writer = TestWriter::new(QosPolicies::qos_none());
+    writer.add_reader(test_reader_guid(1), 7411);
+    writer.add_reader(test_reader_guid(2), 7412);
+    writer.reader_lost(test_reader_guid(1));
+
+    let (reply, reply_receiver) = std::sync::mpsc::sync_channel(1);
+    writer
+      .commands
+      .send(WriterCommand::GetMatchedReaders { reply })
+      .unwrap();
+    writer.process_writer_command();
+    assert_eq!(reply_receiver.try_recv(), Ok(vec![test_reader_guid(2)]));
+  }
+
+  #[test]
+  fn ignore_local_participant_skips_readers_of_the_same_participant() {
+    // Own GUID, because the IgnoreLocal registry is shared by all tests
+    let writer_guid = GUID::new(
+      GuidPrefix::new(&[0x93; 12]),
+      EntityId::create_custom_entity_id([0, 0, 1], EntityKind::WRITER_NO_KEY_USER_DEFINED),
+    );
+    let mut writer = TestWriter::with_guid(
+      writer_guid,
+      QosPolicyBuilder::new()
+        .ignore_local(policy::IgnoreLocal::Participant)
+        .build(),
+    );
+    let local_reader = GUID::new(
+      writer_guid.prefix,
+      EntityId::create_custom_entity_id([0, 0, 2], EntityKind::READER_NO_KEY_USER_DEFINED),
+    );
+    let qos = QosPolicies::qos_none();
+    writer.update_reader_proxy(&reader_proxy(local_reader, 7411), &qos);
+    writer.update_reader_proxy(&reader_proxy(test_reader_guid(2), 7412), &qos);
+    let matched: Vec<GUID> = writer.readers.keys().copied().collect();
+    assert_eq!(matched, vec![test_reader_guid(2)]);
+  }
+
+  #[test]
+  fn delivery_listener_confirms_in_order() {
+    struct Confirmations(Mutex<std::sync::mpsc::Sender<(SequenceNumber, Option<DropReason>)>>);
+    impl DeliveryListener for Confirmations {
+      fn on_acknowledged(&self, sequence_number: SequenceNumber) {
+        let _ = self.0.lock().unwrap().send((sequence_number, None));
+      }
+      fn on_dropped(&self, sequence_number: SequenceNumber, reason: DropReason) {
+        let _ = self.0.lock().unwrap().send((sequence_number, Some(reason)));
+      }
+    }
+
+    let mut writer = TestWriter::new(reliable_qos().build());
+    let reader_guid = test_reader_guid(1);
+    let reliable = reliable_qos().build();
+    writer.update_reader_proxy(
+      &reader_proxy_with_qos(reader_guid, reliable.clone(), 7530),
+      &reliable,
+    );
+    // Sample 1 was written before the listener was set
+    writer.write(
+      1,
+      SerializedPayload::new(crate::RepresentationIdentifier::CDR_LE, vec![1]),
+    );
+    let (sender, confirmations) = std::sync::mpsc::channel();
+    writer.delivery_listener.set(
+      Arc::new(Confirmations(Mutex::new(sender))),
+      SequenceNumber::from(2),
+    );
+    for sn in 2..=4 {
+      writer.write(
+        sn,
+        SerializedPayload::new(crate::RepresentationIdentifier::CDR_LE, vec![1]),
+      );
+    }
+
+    writer.ack(reader_guid, 4, 1);
+    for sn in 2..=3 {
+      let confirmation = confirmations
+        .recv_timeout(std::time::Duration::from_secs(1))
+        .expect("No delivery confirmation");
+      assert_eq!(confirmation, (SequenceNumber::from(sn), None));
+    }
+    // Sample 4 is still pending. Each sample is reported only once.
+    writer.ack(reader_guid, 4, 2);
+    assert!(confirmations
+      .recv_timeout(std::time::Duration::from_millis(100))
+      .is_err());
+  }
+
+  #[test]
+  fn expired_changes_are_answered_with_gap() {
+    let mut writer = TestWriter::new(
+      reliable_qos()
+        .lifespan(policy::Lifespan {
+          duration: Duration::from_millis(10),
+        })
+        .build(),
+    );
+    let reader_guid = test_reader_guid(1);
+    writer.add_reader(reader_guid, 7501);
+
+    let timestamp = writer.insert(1, SerializedPayload::default());
+    thread::sleep(std::time::Duration::from_millis(20));
+    // Not sent anymore in the first place
+    assert!(writer.batched_change_messages(&[timestamp]).is_empty());
+
+    // The reader NACKs it, and gets a GAP instead of stale DATA.
+    writer
+      .readers
+      .get_mut(&reader_guid)
+      .unwrap()
+      .notify_new_cache_change(SequenceNumber::from(1));
+    let sent = writer.sent_by(|writer| writer.handle_repair_data_send(reader_guid));
+    let submessages = writer_submessages(&sent);
+    assert!(submessages
+      .iter()
+      .any(|s| matches!(s, WriterSubmessage::Gap(..))));
+    assert!(!submessages
+      .iter()
+      .any(|s| matches!(s, WriterSubmessage::Data(..))));
+    let rp = writer.readers.get(&reader_guid).unwrap();
+    assert!(rp.unsent_changes_debug().is_empty());
+  }
+
+  #[test]
+  fn nack_frag_repairs_only_the_requested_fragments() {
+    let mut writer = TestWriter::new(reliable_qos().build());
+    let reader_guid = test_reader_guid(1);
+    writer.add_reader(reader_guid, 7502);
+
+    // 5000 bytes is 5 fragments
+    writer.insert(
+      1,
+      SerializedPayload::new(crate::RepresentationIdentifier::CDR_LE, vec![0; 4996]),
+    );
+    assert_eq!(writer.frag_count(SequenceNumber::from(1)), Some(5));
+
+    let writer_id = writer.my_guid.entity_id;
+    let nack_frag = |frags: &[u32], count: i32| {
+      let frags: BTreeSet<FragmentNumber> =
+        frags.iter().map(|f| FragmentNumber::from(*f)).collect();
+      AckSubmessage::NackFrag(NackFrag {
+        reader_id: reader_guid.entity_id,
+        writer_id,
+        writer_sn: SequenceNumber::from(1),
+        fragment_number_state: FragmentNumberSet::from_base_and_set(
+          *frags.first().unwrap(),
+          &frags,
+        ),
+        count,
+      })
+    };
+    let repaired_frags = |writer: &mut TestWriter| -> Vec<FragmentNumber> {
+      let sent = writer.sent_by(|writer| writer.handle_repair_frags_send(reader_guid));
+      writer_submessages(&sent)
+        .into_iter()
+        .filter_map(|s| match s {
+          WriterSubmessage::DataFrag(df, _) => Some(df.fragment_starting_num),
+          _ => None,
+        })
+        .collect()
+    };
+
+    // Fragment 6 does not exist.
+    writer.handle_ack_nack(reader_guid.prefix, &nack_frag(&[2, 4, 6], 1));
+    assert_eq!(
+      repaired_frags(&mut writer),
+      vec![FragmentNumber::from(2u32), FragmentNumber::from(4u32)]
+    );
+    assert!(!writer.readers[&reader_guid].repair_frags_requested());
+    assert!(repaired_frags(&mut writer).is_empty());
+
+    // Acknowledging the sample cancels its fragment requests.
+    writer.handle_ack_nack(reader_guid.prefix, &nack_frag(&[3], 2));
+    assert!(writer.readers[&reader_guid].repair_frags_requested());
+    writer.ack(reader_guid, 2, 1);
+    assert!(!writer.readers[&reader_guid].repair_frags_requested());
+  }
+
+  #[test]
+  fn data_submessage_is_built_once_for_several_readers() {
+    let mut writer = TestWriter::new(reliable_qos().build());
+    // Readers in different participants usually have the same EntityId
+    let reader_entity_id = test_reader_guid(1).entity_id;
+    let reader_guids = [1, 2, 3].map(|n| GUID::new(GuidPrefix::new(&[n; 12]), reader_entity_id));
+    for (reader_guid, port) in reader_guids.iter().zip([7501, 7502, 7503]) {
+      writer.add_reader(*reader_guid, port);
+    }
+    let timestamps = [1, 2].map(|sn| {
+      writer.insert(
+        sn,
+        SerializedPayload::new(crate::RepresentationIdentifier::CDR_LE, vec![7; 100]),
+      )
+    });
+
+    // The destination and the serialized DATA submessage of the message to a
+    // reader
+    let data_message = |writer: &TestWriter, timestamp: Timestamp, reader_guid: GUID| {
+      let topic_cache = writer.acquire_the_topic_cache_guard();
+      let cc = topic_cache.get_change(&timestamp).unwrap();
+      let (messages, _) = writer.cache_change_messages(cc, false, writer.readers.get(&reader_guid));
+      // Sent as the same bytes as a message serialized from scratch
+      assert_eq!(
+        messages[0]
+          .write_to_chunks(Endianness::LittleEndian)
+          .unwrap()
+          .concat(),
+        messages[0]
+          .write_to_vec_with_ctx(Endianness::LittleEndian)
+          .unwrap()
+      );
+      let mut destination = None;
+      let mut serialized = None;
+      for submessage in &messages[0].submessages {
+        match &submessage.body {
+          SubmessageBody::Interpreter(InterpreterSubmessage::InfoDestination(dst, _)) => {
+            destination = Some(dst.guid_prefix);
+          }
+          SubmessageBody::Writer(WriterSubmessage::Data(..)) => {
+            serialized = submessage.original_bytes.clone();
+          }
+          _ => (),
+        }
+      }
+      (destination.unwrap(), serialized.unwrap())
+    };
+
+    let (first_destination, first_bytes) = data_message(&writer, timestamps[0], reader_guids[0]);
+    let (second_destination, second_bytes) = data_message(&writer, timestamps[0], reader_guids[1]);
+    assert_eq!(first_destination, reader_guids[0].prefix);
+    assert_eq!(second_destination, reader_guids[1].prefix);
+    // The same buffer, i.e. not encoded or serialized again
+    assert_eq!(first_bytes.as_ptr(), second_bytes.as_ptr());
+
+    // Another sample is built, and so is the first one again after it.
+    let (_, other_bytes) = data_message(&writer, timestamps[1], reader_guids[2]);
+    assert_ne!(other_bytes.as_ptr(), first_bytes.as_ptr());
+    let (_, third_bytes) = data_message(&writer, timestamps[0], reader_guids[2]);
+    assert_eq!(third_bytes, first_bytes);
+    assert_ne!(third_bytes.as_ptr(), first_bytes.as_ptr());
+  }
+
+  #[test]
+  fn batched_changes_share_messages_and_heartbeats() {
+    let mut writer = TestWriter::new(reliable_qos().build());
+    let payload_size = 1000;
+    let num_changes = 20;
+    let timestamps: Vec<Timestamp> = (1..=num_changes)
+      .map(|sn| {
+        let payload = SerializedPayload::new(
+          crate::RepresentationIdentifier::CDR_LE,
+          vec![0; payload_size - 4],
+        );
+        writer.insert(sn, payload)
+      })
+      .collect();
+
+    let messages = writer.batched_change_messages(&timestamps);
+    let per_message = SEDP_BATCH_MAX_BYTES / payload_size;
+    assert_eq!(
+      messages.len(),
+      (num_changes as usize + per_message - 1) / per_message
+    );
+    let kinds = |m: &Message| -> Vec<SubmessageKind> {
+      m.submessages.iter().map(|s| s.header.kind).collect()
+    };
+    let mut data_count = 0;
+    for message in &messages {
+      let kinds = kinds(message);
+      assert_eq!(kinds.last(), Some(&SubmessageKind::HEARTBEAT));
+      assert_eq!(
+        kinds
+          .iter()
+          .filter(|k| **k == SubmessageKind::HEARTBEAT)
+          .count(),
+        1
+      );
+      data_count += kinds.iter().filter(|k| **k == SubmessageKind::DATA).count();
+    }
+    assert_eq!(data_count, num_changes as usize);
+  }
+
+  #[test]
+  fn stale_and_duplicate_acknacks_are_ignored() {
+    let mut writer = TestWriter::new(reliable_qos().build());
+    for sn in 1..=5 {
+      writer.insert(sn, SerializedPayload::default());
+    }
+    let reader_guid = test_reader_guid(1);
+    writer.add_reader(reader_guid, 7501);
+    let acked_before = |writer: &TestWriter| writer.readers[&reader_guid].all_acked_before;
+
+    writer.ack(reader_guid, 4, 2);
+    assert_eq!(acked_before(&writer), SequenceNumber::from(4));
+    // An older ACKNACK arriving late must not shrink the acked range.
+    writer.ack(reader_guid, 2, 1);
+    assert_eq!(acked_before(&writer), SequenceNumber::from(4));
+    // Neither must a duplicate count.
+    writer.ack(reader_guid, 3, 2);
+    assert_eq!(acked_before(&writer), SequenceNumber::from(4));
+    // A newer one is processed.
+    writer.ack(reader_guid, 6, 3);
+    assert_eq!(acked_before(&writer), SequenceNumber::from(6));
+    // Acking beyond the last change is clamped.
+    writer.ack(reader_guid, 1_000_000_000, 4);
+    assert_eq!(acked_before(&writer), SequenceNumber::from(6));
+  }
+
+  #[test]
+  fn acknack_without_reader_id_is_resolved() {
+    let mut writer = TestWriter::new(reliable_qos().build());
+    for sn in 1..=3 {
+      writer.insert(sn, SerializedPayload::default());
+    }
+    let reader_guid = test_reader_guid(1);
+    writer.add_reader(reader_guid, 7501);
+
+    let unaddressed = |base: i64, count: i32| {
+      AckSubmessage::AckNack(AckNack {
+        reader_id: EntityId::UNKNOWN,
+        writer_id: EntityId::UNKNOWN,
+        reader_sn_state: SequenceNumberSet::new_empty(SequenceNumber::from(base)),
+        count,
+      })
+    };
+    let acked_before = |writer: &TestWriter| writer.readers[&reader_guid].all_acked_before;
+
+    assert!(writer.accepts_unaddressed_ack(reader_guid.prefix, EntityId::UNKNOWN));
+    assert!(writer.accepts_unaddressed_ack(reader_guid.prefix, reader_guid.entity_id));
+    assert!(!writer.accepts_unaddressed_ack(GuidPrefix::UNKNOWN, EntityId::UNKNOWN));
+
+    // The only reader at that participant is the sender
+    writer.handle_ack_nack(reader_guid.prefix, &unaddressed(3, 1));
+    assert_eq!(acked_before(&writer), SequenceNumber::from(3));
+
+    // With two readers there, the sender is ambiguous and the ACKNACK ignored
+    writer.add_reader(GUID::new(reader_guid.prefix, EntityId::MAX), 7502);
+    assert!(!writer.accepts_unaddressed_ack(reader_guid.prefix, EntityId::UNKNOWN));
+    writer.handle_ack_nack(reader_guid.prefix, &unaddressed(4, 2));
+    assert_eq!(acked_before(&writer), SequenceNumber::from(3));
+  }
+
+  #[cfg(feature = "compression")]
+  #[test]
+  fn compression_needs_all_readers_to_accept_it() {
+    let compression = policy::Compression {
+      algorithm: policy::CompressionAlgorithm::Zstd,
+      threshold: 100,
+    };
+    let mut writer = TestWriter::new(QosPolicyBuilder::new().compression(compression).build());
+    let payload_len = |data: &DDSData| match data {
+      DDSData::Data {
+        serialized_payload, ..
+      } => serialized_payload.value.len(),
+      _ => unreachable!(),
+    };
+    let compress = |writer: &Writer, len: usize| {
+      let payload = SerializedPayload::new(crate::RepresentationIdentifier::CDR_LE, vec![7; len]);
+      let (data, write_options) = writer.compress(DDSData::new(payload), WriteOptions::default());
+      (payload_len(&data), write_options.rustdds_parameters().len())
+    };
+    let reader = |id: u8, qos: QosPolicies| reader_proxy_with_qos(test_reader_guid(id), qos, 7501);
+
+    // No readers to tell if they accept compression
+    assert_eq!(compress(&writer, 1000), (1000, 0));
+
+    writer.matched_reader_update(&reader(
+      1,
+      QosPolicyBuilder::new().compression(compression).build(),
+    ));
+    let (compressed_len, parameters) = compress(&writer, 1000);
+    assert!(compressed_len < 100);
+    assert_eq!(parameters, 1);
+    // Below threshold
+    assert_eq!(compress(&writer, 99), (99, 0));
+
+    writer.matched_reader_update(&reader(2, QosPolicies::qos_none()));
+    assert_eq!(compress(&writer, 1000), (1000, 0));
+  }
+
+  #[test]
+  fn checksum_covers_the_payload_as_sent() {
+    let payload = SerializedPayload::new(crate::RepresentationIdentifier::CDR_LE, vec![7; 100]);
+    let data = DDSData::new(payload.clone());
+    let writer = TestWriter::new(
+      QosPolicyBuilder::new()
+        .payload_checksum(policy::PayloadChecksum::Crc32c)
+        .build(),
+    );
+
+    let write_options = writer.add_checksum(&data, WriteOptions::default());
+    let parameters = write_options.rustdds_parameters();
+    assert_eq!(parameters.len(), 1);
+    assert!(checksum::is_intact(parameters, &payload.value));
+    assert!(!checksum::is_intact(parameters, &[7; 99]));
+
+    // Nothing to check in a dispose by key hash
+    let dispose = DDSData::new_disposed_by_key_hash(ChangeKind::NotAliveDisposed, KeyHash::zero());
+    let write_options = writer.add_checksum(&dispose, WriteOptions::default());
+    assert!(write_options.rustdds_parameters().is_empty());
+
+    // No checksum without the policy
+    let writer = TestWriter::new(QosPolicies::qos_none());
+    let write_options = writer.add_checksum(&data, WriteOptions::default());
+    assert!(write_options.rustdds_parameters().is_empty());
+  }
 }
@@ -1,6 +1,7 @@
 pub(crate) mod speedy_pl_cdr_helpers;
 
 pub(crate) mod cdr_deserializer;
+pub(crate) mod cdr_primitive_layout;
 pub(crate) mod cdr_serializer;
 pub mod error;
 pub mod representation_identifier;
@@ -10,6 +11,9 @@ pub(crate) mod pl_cdr_adapters;
 // public exports
 pub use cdr_serializer::{to_writer_endian, CDRSerializerAdapter, CdrSerializer};
 pub use cdr_deserializer::{deserialize_from_cdr, CDRDeserializerAdapter, CdrDeserializer};
+pub use cdr_primitive_layout::{
+  primitive_from_bytes, primitive_to_bytes, CdrPrimitiveAdapter, CdrPrimitiveLayout,
+};
 pub use byteorder::{BigEndian, LittleEndian};
 pub use error::{Error, Result};
 
@@ -21,6 +21,11 @@ use crate::{
 /// CdrDeserializer cannot directly implement
 /// the trait itself, because CdrDeserializer has the type parameter BO open,
 /// and the adapter needs to be bi-endian.
+///
+/// Fixed-layout types that implement
+/// [`CdrPrimitiveLayout`](crate::serialization::CdrPrimitiveLayout) are read
+/// from CDR and CDR_BE faster with
+/// [`CdrPrimitiveAdapter`](crate::serialization::CdrPrimitiveAdapter).
 pub struct CDRDeserializerAdapter<D> {
   phantom: PhantomData<D>,
 }
@@ -46,6 +51,33 @@ where
   }
 }
 
+impl<D> no_key::SeedDeserializerAdapter<D> for CDRDeserializerAdapter<D>
+where
+  D: DeserializeOwned,
+{
+  fn from_bytes_seed<S, T>(
+    input_bytes: &[u8],
+    encoding: RepresentationIdentifier,
+    seed: S,
+  ) -> Result<T>
+  where
+    S: for<'de> DeserializeSeed<'de, Value = T>,
+  {
+    match encoding {
+      RepresentationIdentifier::CDR_LE | RepresentationIdentifier::PL_CDR_LE => {
+        seed.deserialize(&mut CdrDeserializer::<LittleEndian>::new(input_bytes))
+      }
+      RepresentationIdentifier::CDR_BE | RepresentationIdentifier::PL_CDR_BE => {
+        seed.deserialize(&mut CdrDeserializer::<BigEndian>::new(input_bytes))
+      }
+      repr_id => Err(Error::NotSupported(format!(
+        "Unknown serialization format. requested={:?}.",
+        repr_id
+      ))),
+    }
+  }
+}
+
 impl<D> with_key::DeserializerAdapter<D> for CDRDeserializerAdapter<D>
 where
   D: Keyed + DeserializeOwned,
@@ -0,0 +1,350 @@
+use std::{marker::PhantomData, mem::size_of};
+
+use bytes::Bytes;
+use byteorder::{ByteOrder, LittleEndian, NativeEndian};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+  dds::adapters::{no_key, with_key},
+  serialization::{
+    cdr_deserializer::deserialize_from_cdr,
+    cdr_serializer::to_bytes,
+    error::{Error, Result},
+  },
+  Keyed, RepresentationIdentifier,
+};
+
+/// Types whose in-memory representation is their CDR encoding.
+///
+/// Such types can be (de)serialized by copying memory, without going through
+/// serde. This is much faster for small fixed-size samples. Use
+/// [`CdrPrimitiveAdapter`] as the (de)serializer adapter of the DataWriter or
+/// DataReader to do so. [`CDRSerializerAdapter`] and
+/// [`CDRDeserializerAdapter`] do not switch to it by themselves: choosing an
+/// implementation by a trait bound would need specialization, which stable
+/// Rust does not have.
+///
+/// [`CDRSerializerAdapter`]: crate::serialization::CDRSerializerAdapter
+/// [`CDRDeserializerAdapter`]: crate::serialization::CDRDeserializerAdapter
+///
+/// Implemented for the numeric primitive types and arrays of implementing
+/// types. Structs can implement this, too, if they satisfy the safety
+/// requirements below.
+///
+/// # Safety
+///
+/// Implement this only for types such that
+/// * every bit pattern is a valid value, e.g. no `bool`, `char`, or enums,
+/// * the type is `#[repr(C)]` and has no padding bytes, not even at the end,
+/// * every primitive in it, also within nested fields, is at an offset that is
+///   a multiple of its size, and
+/// * all fields implement `CdrPrimitiveLayout`.
+///
+/// Then the native-endian memory contents are the CDR encoding, as CDR aligns
+/// each primitive to its size from the start of the sample.
+///
+/// `#[repr(C)]` aligns fields by their alignment, which is not always their
+/// size. On e.g. i686, `u64` and `f64` are aligned to 4 bytes, so in
+/// `struct { a: u32, b: f64 }` the field `b` is at offset 4, but CDR puts it
+/// at offset 8. Write such padding out as fields, e.g.
+/// `struct { a: u32, _pad: u32, b: f64 }`. This has the CDR layout on all
+/// targets, and no padding bytes.
+///
+/// # Example
+///
+/// ```
+/// use rustdds::serialization::CdrPrimitiveLayout;
+///
+/// #[derive(Clone, Copy)]
+/// #[repr(C)]
+/// struct Position {
+///   id: u32,
+///   seq: u32,
+///   xyz: [f64; 3],
+/// }
+///
+/// unsafe impl CdrPrimitiveLayout for Position {
+///   fn reverse_byte_order(&mut self) {
+///     self.id.reverse_byte_order();
+///     self.seq.reverse_byte_order();
+///     self.xyz.reverse_byte_order();
+///   }
+/// }
+/// ```
+pub unsafe trait CdrPrimitiveLayout: Copy + 'static {
+  /// Reverse the byte order of each primitive field in place.
+  fn reverse_byte_order(&mut self);
+}
+
+macro_rules! impl_cdr_primitive_layout_int {
+  ($($t:ty),*) => {
+    $(
+      unsafe impl CdrPrimitiveLayout for $t {
+        fn reverse_byte_order(&mut self) {
+          *self = self.swap_bytes();
+        }
+      }
+    )*
+  };
+}
+
+impl_cdr_primitive_layout_int!(u8, i8, u16, i16, u32, i32, u64, i64);
+
+unsafe impl CdrPrimitiveLayout for f32 {
+  fn reverse_byte_order(&mut self) {
+    *self = Self::from_bits(self.to_bits().swap_bytes());
+  }
+}
+
+unsafe impl CdrPrimitiveLayout for f64 {
+  fn reverse_byte_order(&mut self) {
+    *self = Self::from_bits(self.to_bits().swap_bytes());
+  }
+}
+
+unsafe impl<T: CdrPrimitiveLayout, const N: usize> CdrPrimitiveLayout for [T; N] {
+  fn reverse_byte_order(&mut self) {
+    self.iter_mut().for_each(T::reverse_byte_order);
+  }
+}
+
+fn is_native<BO: ByteOrder>() -> bool {
+  BO::read_u16(&1u16.to_ne_bytes()) == 1
+}
+
+fn is_little_endian<BO: ByteOrder>() -> bool {
+  BO::read_u16(&[1, 0]) == 1
+}
+
+/// Serialize `value` into CDR by copying it, reversing byte order if `BO` is
+/// not the native byte order.
+pub fn primitive_to_bytes<D, BO>(value: &D) -> Bytes
+where
+  D: CdrPrimitiveLayout,
+  BO: ByteOrder,
+{
+  let mut value = *value;
+  if !is_native::<BO>() {
+    value.reverse_byte_order();
+  }
+  // Safety: CdrPrimitiveLayout guarantees there are no (uninitialized)
+  // padding bytes.
+  let bytes =
+    unsafe { std::slice::from_raw_parts((&value as *const D).cast::<u8>(), size_of::<D>()) };
+  Bytes::copy_from_slice(bytes)
+}
+
+/// Deserialize a value from CDR by copying it. Trailing bytes are ignored.
+pub fn primitive_from_bytes<D>(input_bytes: &[u8], encoding: RepresentationIdentifier) -> Result<D>
+where
+  D: CdrPrimitiveLayout,
+{
+  let input_is_little_endian = match encoding {
+    RepresentationIdentifier::CDR_LE => true,
+    RepresentationIdentifier::CDR_BE => false,
+    other => {
+      return Err(Error::Message(format!(
+        "Unsupported encoding for primitive layout: {other:?}"
+      )))
+    }
+  };
+  if input_bytes.len() < size_of::<D>() {
+    return Err(Error::Eof);
+  }
+  // Safety: Length was checked above, read_unaligned does not require
+  // alignment, and CdrPrimitiveLayout guarantees that any bit pattern is
+  // valid.
+  let mut value = unsafe { std::ptr::read_unaligned(input_bytes.as_ptr().cast::<D>()) };
+  if input_is_little_endian != is_little_endian::<NativeEndian>() {
+    value.reverse_byte_order();
+  }
+  Ok(value)
+}
+
+/// (De)serializer adapter for [`CdrPrimitiveLayout`] types. Produces and
+/// accepts plain CDR, so it interoperates with [`CDRSerializerAdapter`] and
+/// [`CDRDeserializerAdapter`], but bypasses serde.
+///
+/// Keys of WITH_KEY types are still (de)serialized using serde, as they are
+/// usually small.
+///
+/// [`CDRSerializerAdapter`]: crate::serialization::CDRSerializerAdapter
+/// [`CDRDeserializerAdapter`]: crate::serialization::CDRDeserializerAdapter
+pub struct CdrPrimitiveAdapter<D, BO = LittleEndian> {
+  phantom: PhantomData<(D, BO)>,
+}
+
+const REPR_IDS: [RepresentationIdentifier; 2] = [
+  RepresentationIdentifier::CDR_BE,
+  RepresentationIdentifier::CDR_LE,
+];
+
+impl<D, BO> no_key::SerializerAdapter<D> for CdrPrimitiveAdapter<D, BO>
+where
+  D: CdrPrimitiveLayout,
+  BO: ByteOrder,
+{
+  type Error = Error;
+
+  fn output_encoding() -> RepresentationIdentifier {
+    if is_little_endian::<BO>() {
+      RepresentationIdentifier::CDR_LE
+    } else {
+      RepresentationIdentifier::CDR_BE
+    }
+  }
+
+  fn to_bytes(value: &D) -> Result<Bytes> {
+    Ok(primitive_to_bytes::<D, BO>(value))
+  }
+}
+
+impl<D, BO> with_key::SerializerAdapter<D> for CdrPrimitiveAdapter<D, BO>
+where
+  D: Keyed + CdrPrimitiveLayout,
+  <D as Keyed>::K: Serialize,
+  BO: ByteOrder,
+{
+  fn key_to_bytes(value: &D::K) -> Result<Bytes> {
+    to_bytes::<D::K, BO>(value)
+      .map(Bytes::from)
+      .map_err(|e| Error::Message(e.to_string()))
+  }
+}
+
+impl<D, BO> no_key::DeserializerAdapter<D> for CdrPrimitiveAdapter<D, BO>
+where
+  D: CdrPrimitiveLayout,
+{
+  type Error = Error;
+
+  fn supported_encodings() -> &'static [RepresentationIdentifier] {
+    &REPR_IDS
+  }
+
+  fn from_bytes(input_bytes: &[u8], encoding: RepresentationIdentifier) -> Result<D> {
+    primitive_from_bytes(input_bytes, encoding)
+  }
+}
+
+impl<D, BO> with_key::DeserializerAdapter<D> for CdrPrimitiveAdapter<D, BO>
+where
+  D: Keyed + CdrPrimitiveLayout,
+  <D as Keyed>::K: DeserializeOwned,
+{
+  fn key_from_bytes(input_bytes: &[u8], encoding: RepresentationIdentifier) -> Result<D::K> {
+    deserialize_from_cdr(input_bytes, encoding)
+      .map(|(k, _size)| k)
+      .map_err(|e| Error::Message(e.to_string()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use byteorder::BigEndian;
+  use serde::Deserialize;
+
+  use super::*;
+  use crate::serialization::CDRDeserializerAdapter;
+
+  #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+  #[repr(C)]
+  struct Pod {
+    a: u16,
+    b: i16,
+    c: u32,
+    d: f64,
+    e: [u8; 8],
+  }
+
+  unsafe impl CdrPrimitiveLayout for Pod {
+    fn reverse_byte_order(&mut self) {
+      self.a.reverse_byte_order();
+      self.b.reverse_byte_order();
+      self.c.reverse_byte_order();
+      self.d.reverse_byte_order();
+      self.e.reverse_byte_order();
+    }
+  }
+
+  const POD: Pod = Pod {
+    a: 0x0102,
+    b: -3,
+    c: 0x0a0b0c0d,
+    d: 1.5,
+    e: [1, 2, 3, 4, 5, 6, 7, 8],
+  };
+
+  #[test]
+  fn primitive_matches_serde_cdr() {
+    let le = primitive_to_bytes::<Pod, LittleEndian>(&POD);
+    assert_eq!(le, to_bytes::<Pod, LittleEndian>(&POD).unwrap());
+    let be = primitive_to_bytes::<Pod, BigEndian>(&POD);
+    assert_eq!(be, to_bytes::<Pod, BigEndian>(&POD).unwrap());
+
+    for (bytes, encoding) in [
+      (le, RepresentationIdentifier::CDR_LE),
+      (be, RepresentationIdentifier::CDR_BE),
+    ] {
+      assert_eq!(primitive_from_bytes::<Pod>(&bytes, encoding).unwrap(), POD);
+      assert_eq!(
+        <CDRDeserializerAdapter<Pod> as no_key::DeserializerAdapter<Pod>>::from_bytes(
+          &bytes, encoding
+        )
+        .unwrap(),
+        POD
+      );
+    }
+  }
+
+  // The padding that CDR has before `b` is written out as a field, so that
+  // `b` is at offset 8 also where f64 is aligned to 4 bytes.
+  #[derive(Clone, Copy, Debug, PartialEq)]
+  #[repr(C)]
+  struct Padded {
+    a: u32,
+    _pad: u32,
+    b: f64,
+  }
+
+  unsafe impl CdrPrimitiveLayout for Padded {
+    fn reverse_byte_order(&mut self) {
+      self.a.reverse_byte_order();
+      self._pad.reverse_byte_order();
+      self.b.reverse_byte_order();
+    }
+  }
+
+  #[derive(Serialize)]
+  struct Unpadded {
+    a: u32,
+    b: f64,
+  }
+
+  #[test]
+  fn mixed_u32_f64_matches_serde_cdr() {
+    let padded = Padded {
+      a: 7,
+      _pad: 0,
+      b: 2.5,
+    };
+    let unpadded = Unpadded { a: 7, b: 2.5 };
+    assert_eq!(size_of::<Padded>(), 16);
+
+    let le = primitive_to_bytes::<Padded, LittleEndian>(&padded);
+    assert_eq!(le, to_bytes::<Unpadded, LittleEndian>(&unpadded).unwrap());
+    let be = primitive_to_bytes::<Padded, BigEndian>(&padded);
+    assert_eq!(be, to_bytes::<Unpadded, BigEndian>(&unpadded).unwrap());
+    assert_eq!(
+      primitive_from_bytes::<Padded>(&be, RepresentationIdentifier::CDR_BE).unwrap(),
+      padded
+    );
+  }
+
+  #[test]
+  fn primitive_from_bytes_rejects_bad_input() {
+    let le = primitive_to_bytes::<Pod, LittleEndian>(&POD);
+    assert!(primitive_from_bytes::<Pod>(&le[..10], RepresentationIdentifier::CDR_LE).is_err());
+    assert!(primitive_from_bytes::<Pod>(&le, RepresentationIdentifier::PL_CDR_LE).is_err());
+  }
+}
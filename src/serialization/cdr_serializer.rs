@@ -92,6 +92,11 @@ where
 /// [`CdrSerializer`] cannot directly implement the trait itself, because
 /// [`CdrSerializer`] has the type parameter BO open, and the adapter needs to
 /// be bi-endian.
+///
+/// Fixed-layout types that implement
+/// [`CdrPrimitiveLayout`](crate::serialization::CdrPrimitiveLayout) produce
+/// the same bytes faster with
+/// [`CdrPrimitiveAdapter`](crate::serialization::CdrPrimitiveAdapter).
 pub struct CDRSerializerAdapter<D, BO = LittleEndian>
 where
   BO: ByteOrder,
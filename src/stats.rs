@@ -0,0 +1,394 @@
+// Statistics of writers, readers and network transport, reported via the
+// `metrics` crate facade when feature "metrics" is enabled. Without the
+// feature, these types are empty and their methods do nothing, so that call
+// sites need not be feature-gated.
+//
+// Metric handles are registered when the endpoint is created. Therefore, the
+// application must install its metrics recorder (exporter) before creating
+// the DomainParticipant.
+//
+// The metrics facade cannot unregister a metric. When an endpoint is dropped,
+// its gauges are set to zero, so that they do not keep reporting matches that
+// are gone. Removing the series altogether is up to the exporter, e.g. its
+// idle timeout.
+//
+// The metric names are listed in the crate documentation.
+
+#[cfg(feature = "metrics")]
+use metrics::{counter, gauge, histogram, Counter, Gauge, Histogram};
+
+use crate::structure::guid::GUID;
+
+/// Statistics of an RTPS Writer
+pub(crate) struct WriterStats {
+  #[cfg(feature = "metrics")]
+  samples_written: Counter,
+  #[cfg(feature = "metrics")]
+  stale_samples_dropped: Counter,
+  #[cfg(feature = "metrics")]
+  acknacks_received: Counter,
+  #[cfg(feature = "metrics")]
+  repair_samples_sent: Counter,
+  #[cfg(feature = "metrics")]
+  matched_readers: Gauge,
+}
+
+impl WriterStats {
+  #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+  pub fn new(topic_name: &str, guid: GUID) -> Self {
+    #[cfg(feature = "metrics")]
+    let labels = [
+      ("topic", topic_name.to_string()),
+      ("guid", guid.to_string()),
+    ];
+    Self {
+      #[cfg(feature = "metrics")]
+      samples_written: counter!("rustdds_writer_samples_written_total", &labels),
+      #[cfg(feature = "metrics")]
+      stale_samples_dropped: counter!("rustdds_writer_stale_samples_dropped_total", &labels),
+      #[cfg(feature = "metrics")]
+      acknacks_received: counter!("rustdds_writer_acknacks_received_total", &labels),
+      #[cfg(feature = "metrics")]
+      repair_samples_sent: counter!("rustdds_writer_repair_samples_sent_total", &labels),
+      #[cfg(feature = "metrics")]
+      matched_readers: gauge!("rustdds_writer_matched_readers", &labels),
+    }
+  }
+
+  pub fn sample_written(&self) {
+    #[cfg(feature = "metrics")]
+    self.samples_written.increment(1);
+  }
+
+  pub fn stale_sample_dropped(&self) {
+    #[cfg(feature = "metrics")]
+    self.stale_samples_dropped.increment(1);
+  }
+
+  pub fn acknack_received(&self) {
+    #[cfg(feature = "metrics")]
+    self.acknacks_received.increment(1);
+  }
+
+  pub fn repair_sample_sent(&self) {
+    #[cfg(feature = "metrics")]
+    self.repair_samples_sent.increment(1);
+  }
+
+  #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+  pub fn set_matched_readers(&self, count: usize) {
+    #[cfg(feature = "metrics")]
+    self.matched_readers.set(count as f64);
+  }
+}
+
+impl Drop for WriterStats {
+  fn drop(&mut self) {
+    self.set_matched_readers(0);
+  }
+}
+
+/// Statistics of an RTPS Reader
+pub(crate) struct ReaderStats {
+  #[cfg(feature = "metrics")]
+  samples_received: Counter,
+  #[cfg(feature = "metrics")]
+  samples_lost: Counter,
+  #[cfg(feature = "metrics")]
+  heartbeats_received: Counter,
+  #[cfg(feature = "metrics")]
+  matched_writers: Gauge,
+}
+
+impl ReaderStats {
+  #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+  pub fn new(topic_name: &str, guid: GUID) -> Self {
+    #[cfg(feature = "metrics")]
+    let labels = [
+      ("topic", topic_name.to_string()),
+      ("guid", guid.to_string()),
+    ];
+    Self {
+      #[cfg(feature = "metrics")]
+      samples_received: counter!("rustdds_reader_samples_received_total", &labels),
+      #[cfg(feature = "metrics")]
+      samples_lost: counter!("rustdds_reader_samples_lost_total", &labels),
+      #[cfg(feature = "metrics")]
+      heartbeats_received: counter!("rustdds_reader_heartbeats_received_total", &labels),
+      #[cfg(feature = "metrics")]
+      matched_writers: gauge!("rustdds_reader_matched_writers", &labels),
+    }
+  }
+
+  pub fn sample_received(&self) {
+    #[cfg(feature = "metrics")]
+    self.samples_received.increment(1);
+  }
+
+  #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+  pub fn samples_lost(&self, count: u64) {
+    #[cfg(feature = "metrics")]
+    self.samples_lost.increment(count);
+  }
+
+  pub fn heartbeat_received(&self) {
+    #[cfg(feature = "metrics")]
+    self.heartbeats_received.increment(1);
+  }
+
+  #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+  pub fn set_matched_writers(&self, count: usize) {
+    #[cfg(feature = "metrics")]
+    self.matched_writers.set(count as f64);
+  }
+}
+
+impl Drop for ReaderStats {
+  fn drop(&mut self) {
+    self.set_matched_writers(0);
+  }
+}
+
+/// Payload sizes and (de)serialization times of a DataWriter or DataReader,
+/// recorded as histograms.
+pub(crate) struct SerializationStats {
+  #[cfg(feature = "metrics")]
+  payload_bytes: Histogram,
+  #[cfg(feature = "metrics")]
+  duration_seconds: Histogram,
+}
+
+impl SerializationStats {
+  #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+  pub fn for_writer(topic_name: &str, guid: GUID) -> Self {
+    #[cfg(feature = "metrics")]
+    let labels = [
+      ("topic", topic_name.to_string()),
+      ("guid", guid.to_string()),
+    ];
+    Self {
+      #[cfg(feature = "metrics")]
+      payload_bytes: histogram!("rustdds_writer_payload_bytes", &labels),
+      #[cfg(feature = "metrics")]
+      duration_seconds: histogram!("rustdds_writer_serialization_seconds", &labels),
+    }
+  }
+
+  #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+  pub fn for_reader(topic_name: &str, guid: GUID) -> Self {
+    #[cfg(feature = "metrics")]
+    let labels = [
+      ("topic", topic_name.to_string()),
+      ("guid", guid.to_string()),
+    ];
+    Self {
+      #[cfg(feature = "metrics")]
+      payload_bytes: histogram!("rustdds_reader_payload_bytes", &labels),
+      #[cfg(feature = "metrics")]
+      duration_seconds: histogram!("rustdds_reader_deserialization_seconds", &labels),
+    }
+  }
+
+  // A sample of `bytes` serialized size took `elapsed` to serialize or
+  // deserialize
+  #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+  pub fn sample_processed(&self, bytes: usize, elapsed: std::time::Duration) {
+    #[cfg(feature = "metrics")]
+    {
+      self.payload_bytes.record(bytes as f64);
+      self.duration_seconds.record(elapsed);
+    }
+  }
+}
+
+// Network transport statistics are process-wide, so these are plain
+// functions.
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn udp_datagram_sent(bytes: usize) {
+  #[cfg(feature = "metrics")]
+  {
+    counter!("rustdds_udp_datagrams_sent_total").increment(1);
+    counter!("rustdds_udp_bytes_sent_total").increment(bytes as u64);
+  }
+}
+
+pub(crate) fn udp_send_failed() {
+  #[cfg(feature = "metrics")]
+  counter!("rustdds_udp_send_errors_total").increment(1);
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn udp_datagram_received(bytes: usize) {
+  #[cfg(feature = "metrics")]
+  {
+    counter!("rustdds_udp_datagrams_received_total").increment(1);
+    counter!("rustdds_udp_bytes_received_total").increment(bytes as u64);
+  }
+}
+
+// `reason` is one of the field names of MessageReceiverRejections
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn message_rejected(reason: &'static str) {
+  #[cfg(feature = "metrics")]
+  counter!("rustdds_messages_rejected_total", "reason" => reason).increment(1);
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn submessage_skipped(vendor_specific: bool) {
+  #[cfg(feature = "metrics")]
+  {
+    let kind = if vendor_specific {
+      "vendor_specific"
+    } else {
+      "unknown"
+    };
+    counter!("rustdds_submessages_skipped_total", "kind" => kind).increment(1);
+  }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+  use std::{
+    collections::HashMap,
+    sync::{atomic::AtomicU64, Arc, Mutex},
+  };
+
+  use metrics::{HistogramFn, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+
+  use super::*;
+
+  #[derive(Default)]
+  struct TestHistogram(Mutex<Vec<f64>>);
+
+  impl HistogramFn for TestHistogram {
+    fn record(&self, value: f64) {
+      self.0.lock().unwrap().push(value);
+    }
+  }
+
+  // Records counters, gauges and histograms by name
+  #[derive(Default)]
+  struct TestRecorder {
+    counters: Mutex<HashMap<String, Arc<AtomicU64>>>,
+    gauges: Mutex<HashMap<String, Arc<AtomicU64>>>,
+    histograms: Mutex<HashMap<String, Arc<TestHistogram>>>,
+  }
+
+  impl TestRecorder {
+    fn counter_value(&self, name: &str) -> u64 {
+      self.counters.lock().unwrap()[name].load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn gauge_value(&self, name: &str) -> f64 {
+      f64::from_bits(self.gauges.lock().unwrap()[name].load(std::sync::atomic::Ordering::Relaxed))
+    }
+
+    fn histogram_values(&self, name: &str) -> Vec<f64> {
+      self.histograms.lock().unwrap()[name]
+        .0
+        .lock()
+        .unwrap()
+        .clone()
+    }
+  }
+
+  impl Recorder for TestRecorder {
+    fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+    fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+    fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+      let counter = self
+        .counters
+        .lock()
+        .unwrap()
+        .entry(key.name().to_string())
+        .or_default()
+        .clone();
+      Counter::from_arc(counter)
+    }
+
+    fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+      let gauge = self
+        .gauges
+        .lock()
+        .unwrap()
+        .entry(key.name().to_string())
+        .or_default()
+        .clone();
+      Gauge::from_arc(gauge)
+    }
+
+    fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+      let histogram = self
+        .histograms
+        .lock()
+        .unwrap()
+        .entry(key.name().to_string())
+        .or_default()
+        .clone();
+      Histogram::from_arc(histogram)
+    }
+  }
+
+  #[test]
+  fn writer_stats_are_reported_to_recorder() {
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+      let stats = WriterStats::new(
+        "test_topic",
+        GUID::dummy_test_guid(crate::structure::guid::EntityKind::WRITER_WITH_KEY_USER_DEFINED),
+      );
+      stats.sample_written();
+      stats.sample_written();
+      stats.acknack_received();
+    });
+    assert_eq!(
+      recorder.counter_value("rustdds_writer_samples_written_total"),
+      2
+    );
+    assert_eq!(
+      recorder.counter_value("rustdds_writer_acknacks_received_total"),
+      1
+    );
+    assert_eq!(
+      recorder.counter_value("rustdds_writer_repair_samples_sent_total"),
+      0
+    );
+  }
+
+  #[test]
+  fn matched_gauges_are_zeroed_when_endpoint_is_dropped() {
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+      let guid =
+        GUID::dummy_test_guid(crate::structure::guid::EntityKind::READER_WITH_KEY_USER_DEFINED);
+      let stats = ReaderStats::new("test_topic", guid);
+      stats.set_matched_writers(3);
+      assert_eq!(recorder.gauge_value("rustdds_reader_matched_writers"), 3.0);
+      drop(stats);
+    });
+    assert_eq!(recorder.gauge_value("rustdds_reader_matched_writers"), 0.0);
+  }
+
+  #[test]
+  fn serialization_stats_are_recorded_as_histograms() {
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+      let guid =
+        GUID::dummy_test_guid(crate::structure::guid::EntityKind::READER_WITH_KEY_USER_DEFINED);
+      let stats = SerializationStats::for_reader("test_topic", guid);
+      stats.sample_processed(100, std::time::Duration::from_millis(2));
+      stats.sample_processed(1500, std::time::Duration::from_millis(4));
+    });
+    assert_eq!(
+      recorder.histogram_values("rustdds_reader_payload_bytes"),
+      vec![100.0, 1500.0]
+    );
+    assert_eq!(
+      recorder.histogram_values("rustdds_reader_deserialization_seconds"),
+      vec![0.002, 0.004]
+    );
+  }
+}
@@ -1,3 +1,5 @@
+use std::time::Instant;
+
 use crate::{
   dds::{ddsdata::DDSData, with_key::datawriter::WriteOptions},
   structure::{guid::GUID, sequence_number::SequenceNumber},
@@ -16,6 +18,9 @@ pub struct CacheChange {
   pub sequence_number: SequenceNumber,
   pub write_options: WriteOptions,
   pub data_value: DDSData,
+  // Monotonic time of reception, or of creation for local changes. The
+  // corresponding wall-clock time is the key of the change in the TopicCache.
+  pub reception_instant: Instant,
 }
 
 #[cfg(test)]
@@ -40,6 +45,7 @@ impl CacheChange {
       sequence_number,
       write_options,
       data_value,
+      reception_instant: Instant::now(),
     }
   }
 
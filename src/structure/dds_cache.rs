@@ -1,24 +1,34 @@
 use std::{
   cmp::max,
-  collections::{BTreeMap, HashMap},
-  ops::Bound::{Excluded, Included},
+  collections::{BTreeMap, BTreeSet, HashMap},
+  fmt,
+  ops::{
+    Bound::{self, Excluded, Unbounded},
+    RangeBounds,
+  },
   sync::{Arc, Mutex},
+  time::Instant,
 };
+use core::task::Waker;
 
 #[allow(unused_imports)]
 use log::{debug, error, info, trace};
+use mio_extras::channel as mio_channel;
 
 use crate::{
   create_error_internal,
   dds::{
+    key::KeyHash,
     qos::{
-      policy::{History, ResourceLimits},
+      policy::{History, NotificationCoalescing, ResourceLimits},
       QosPolicies,
     },
+    reader_multiplexer::ReadinessQueue,
     typedesc::TypeDesc,
     CreateError, CreateResult,
   },
-  structure::{sequence_number::SequenceNumber, time::Timestamp},
+  mio_source::PollEventSender,
+  structure::{duration::Duration, sequence_number::SequenceNumber, time::Timestamp},
   GUID,
 };
 use super::cache_change::CacheChange;
@@ -88,6 +98,154 @@ impl DDSCache {
   }
 }
 
+/// Wakes up a local DataReader, when changes are added to the TopicCache it
+/// reads from. Each RTPS Reader subscribes its DataReader to the TopicCache of
+/// its topic, so that a new change wakes up exactly the DataReaders of that
+/// topic, no matter whether the change came from a remote or a local writer.
+///
+/// With the NotificationCoalescing QoS policy, the wake-up is held back until
+/// enough samples are pending, or it has waited for max_delay. A held back
+/// notification asks the Reader to flush it when max_delay is up, in case no
+/// more changes arrive.
+pub(crate) struct ChangeNotifier {
+  // async notify mechanism
+  data_reader_waker: Arc<Mutex<Option<Waker>>>,
+  // mio-0.8 notify
+  poll_event_sender: PollEventSender,
+  // mio-0.6 notify
+  notification_sender: mio_channel::SyncSender<()>,
+  coalescing: Option<NotificationCoalescing>,
+  // Asks the Reader to arm its flush timer
+  flush_request_sender: Option<mio_channel::SyncSender<()>>,
+  // Since when a notification is held back, and how many new samples it covers
+  pending_since: Option<Instant>,
+  pending_samples: usize,
+  // Subscriber::take_any() wants to know when this reader is notified
+  readiness: Option<(Arc<ReadinessQueue>, GUID)>,
+}
+
+impl ChangeNotifier {
+  pub fn new(
+    data_reader_waker: Arc<Mutex<Option<Waker>>>,
+    poll_event_sender: PollEventSender,
+    notification_sender: mio_channel::SyncSender<()>,
+  ) -> Self {
+    Self {
+      data_reader_waker,
+      poll_event_sender,
+      notification_sender,
+      coalescing: None,
+      flush_request_sender: None,
+      pending_since: None,
+      pending_samples: 0,
+      readiness: None,
+    }
+  }
+
+  pub fn with_coalescing(
+    mut self,
+    coalescing: Option<NotificationCoalescing>,
+    flush_request_sender: mio_channel::SyncSender<()>,
+  ) -> Self {
+    self.coalescing = coalescing;
+    self.flush_request_sender = Some(flush_request_sender);
+    self
+  }
+
+  pub fn with_readiness_queue(
+    mut self,
+    reader_guid: GUID,
+    readiness_queue: Option<Arc<ReadinessQueue>>,
+  ) -> Self {
+    self.readiness = readiness_queue.map(|queue| (queue, reader_guid));
+    self
+  }
+
+  // There are `new_samples` new changes, or zero if older changes became
+  // available to Reliable DataReaders.
+  fn changes_added(&mut self, new_samples: usize) {
+    match self.coalescing {
+      None => self.notify(),
+      Some(NotificationCoalescing {
+        max_samples,
+        max_delay,
+      }) => {
+        self.pending_samples += new_samples;
+        let newly_pending = self.pending_since.is_none();
+        let pending_since = *self.pending_since.get_or_insert_with(Instant::now);
+        if (max_samples > 0 && self.pending_samples >= max_samples as usize)
+          || pending_since.elapsed() >= max_delay.to_std()
+        {
+          self.notify();
+        } else if newly_pending {
+          // Someone has to flush this, if no more changes come.
+          self.request_flush();
+        }
+      }
+    }
+  }
+
+  // Sends the notification that is held back, if any
+  fn flush(&mut self) {
+    if self.pending_since.is_some() {
+      self.notify();
+    }
+  }
+
+  fn request_flush(&self) {
+    if let Some(sender) = &self.flush_request_sender {
+      match sender.try_send(()) {
+        // A full channel already has a request in
+        Ok(()) | Err(mio_channel::TrySendError::Full(_)) => (),
+        Err(mio_channel::TrySendError::Disconnected(_)) => {
+          // The Reader is gone, and will unsubscribe soon.
+        }
+        Err(mio_channel::TrySendError::Io(e)) => {
+          debug!("ChangeNotifier: io error {e:?}");
+        }
+      }
+    }
+  }
+
+  fn notify(&mut self) {
+    self.pending_since = None;
+    self.pending_samples = 0;
+
+    self
+      .data_reader_waker
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .take() // Take to nullify the reference
+      .map(|w| w.wake_by_ref()); // If Some, call wake_by_ref
+
+    self.poll_event_sender.send();
+
+    if let Some((queue, reader_guid)) = &self.readiness {
+      queue.ready(*reader_guid);
+    }
+
+    match self.notification_sender.try_send(()) {
+      Ok(()) => (),
+      Err(mio_channel::TrySendError::Full(_)) => (),
+      // This is harmless. There is a notification in already.
+      Err(mio_channel::TrySendError::Disconnected(_)) => {
+        // The DataReader has died. Its Reader will unsubscribe when it is
+        // removed.
+      }
+      Err(mio_channel::TrySendError::Io(e)) => {
+        debug!("ChangeNotifier: io error {e:?}");
+      }
+    }
+  }
+}
+
+impl fmt::Debug for ChangeNotifier {
+  // Need manual implementation, because channels cannot be Debug formatted.
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("ChangeNotifier")
+  }
+}
+
 #[derive(Debug)]
 pub(crate) struct TopicCache {
   topic_name: String,
@@ -102,17 +260,39 @@ pub(crate) struct TopicCache {
 
   // Tha main content of the cache is in this map.
   // Timestamp is assumed to be unique id over all the CacheChanges.
-  changes: BTreeMap<Timestamp, CacheChange>,
+  // Changes are shared, so that they can be handed out without holding the
+  // lock, e.g. for a batch of retransmissions.
+  changes: BTreeMap<Timestamp, Arc<CacheChange>>,
 
   // sequence_numbers is an index to "changes" by GUID and SN
   sequence_numbers: BTreeMap<GUID, BTreeMap<SequenceNumber, Timestamp>>,
 
+  // Index to "changes" by source timestamp
+  source_timestamps: BTreeSet<(Timestamp, Timestamp)>,
+
+  // Changes that are completely written, but not yet visible to readers.
+  // Neither "changes" nor "sequence_numbers" contain them until
+  // commit_changes() publishes them all at once.
+  staged_changes: Vec<(Timestamp, CacheChange)>,
+
+  // Incremented by each commit that made changes visible. A reader that has
+  // seen an epoch has seen every change committed up to it.
+  committed_epoch: u64,
+
   // Keep track of how far we have "reliably" received samples from each Writer
   // This means that all data up to this point has either been received, or
   // we have been notified (GAP or HEARTBEAT) that is not available and never will.
   // Therefore, data before the marker SN can be handed off to a Reliable DataReader.
   // Initially, we consider the marker for each Writer (GUID) to be SequenceNumber::new(1)
   received_reliably_before: BTreeMap<GUID, SequenceNumber>,
+
+  // Markers that would skip over staged changes. They take effect when the
+  // changes are committed, so that a DataReader cannot move past a change
+  // before it becomes visible.
+  staged_reliably_received_before: BTreeMap<GUID, SequenceNumber>,
+
+  // Local DataReaders to wake up on new changes, by the GUID of their Reader
+  subscribers: BTreeMap<GUID, ChangeNotifier>,
 }
 
 impl TopicCache {
@@ -126,7 +306,12 @@ impl TopicCache {
       max_keep_samples: 1, // dummy value, next call will overwrite this
       changes: BTreeMap::new(),
       sequence_numbers: BTreeMap::new(),
+      source_timestamps: BTreeSet::new(),
+      staged_changes: Vec::new(),
+      committed_epoch: 0,
       received_reliably_before: BTreeMap::new(),
+      staged_reliably_received_before: BTreeMap::new(),
+      subscribers: BTreeMap::new(),
     };
 
     new_self.update_keep_limits(topic_qos);
@@ -166,30 +351,152 @@ impl TopicCache {
     self.max_keep_samples = max(max_keep_samples, self.max_keep_samples);
   }
 
+  pub fn add_subscriber(&mut self, reader_guid: GUID, notifier: ChangeNotifier) {
+    self.subscribers.insert(reader_guid, notifier);
+  }
+
+  pub fn remove_subscriber(&mut self, reader_guid: GUID) {
+    self.subscribers.remove(&reader_guid);
+  }
+
   pub fn mark_reliably_received_before(&mut self, writer: GUID, sn: SequenceNumber) {
-    self.received_reliably_before.insert(writer, sn);
+    if self
+      .staged_changes
+      .iter()
+      .any(|(_, cc)| cc.writer_guid == writer)
+    {
+      let staged = self
+        .staged_reliably_received_before
+        .entry(writer)
+        .or_insert(sn);
+      *staged = max(*staged, sn);
+      return;
+    }
+    let previous = self.received_reliably_before.insert(writer, sn);
+    // Changes that were already in the cache may now be available to Reliable
+    // DataReaders, so this is news to them, too.
+    if previous.map_or(true, |previous| previous < sn) {
+      self.notify_subscribers(0);
+    }
+  }
+
+  fn notify_subscribers(&mut self, new_samples: usize) {
+    for subscriber in self.subscribers.values_mut() {
+      subscriber.changes_added(new_samples);
+    }
+  }
+
+  // Wake up one DataReader, even though there are no new changes.
+  pub fn notify_subscriber(&mut self, reader_guid: GUID) {
+    if let Some(subscriber) = self.subscribers.get_mut(&reader_guid) {
+      subscriber.notify();
+    }
+  }
+
+  // Wake up one DataReader, if it has a coalesced notification pending.
+  pub fn flush_notification(&mut self, reader_guid: GUID) {
+    if let Some(subscriber) = self.subscribers.get_mut(&reader_guid) {
+      subscriber.flush();
+    }
   }
 
   pub fn get_change(&self, instant: &Timestamp) -> Option<&CacheChange> {
-    self.changes.get(instant)
+    self.changes.get(instant).map(Arc::as_ref)
   }
 
-  pub fn add_change(&mut self, instant: &Timestamp, cache_change: CacheChange) {
+  /// Changes from the writer `writer_guid` with sequence numbers in `range`,
+  /// in sequence number order. Sequence numbers that are not in the cache are
+  /// skipped.
+  ///
+  /// This is for getting a batch of changes with a single lock of the cache.
+  pub fn get_changes_in_sn_range(
+    &self,
+    writer_guid: GUID,
+    range: impl RangeBounds<SequenceNumber>,
+  ) -> Vec<(SequenceNumber, Arc<CacheChange>)> {
+    let range = (range.start_bound().cloned(), range.end_bound().cloned());
     self
-      .add_change_internal(instant, cache_change)
-      .map(|cc_back| {
-        debug!(
-          "DDSCache insert failed topic={:?} cache_change={:?}",
-          self.topic_name, cc_back
-        );
-      });
+      .changes_in_sn_range(writer_guid, range)
+      .map(|(sn, _ts, cc)| (sn, Arc::clone(cc)))
+      .collect()
   }
 
-  fn add_change_internal(
-    &mut self,
-    instant: &Timestamp,
-    cache_change: CacheChange,
-  ) -> Option<CacheChange> {
+  fn changes_in_sn_range(
+    &self,
+    writer_guid: GUID,
+    range: (Bound<SequenceNumber>, Bound<SequenceNumber>),
+  ) -> impl Iterator<Item = (SequenceNumber, Timestamp, &Arc<CacheChange>)> + '_ {
+    self
+      .sequence_numbers
+      .get(&writer_guid)
+      .map(|sn_map| sn_map.range(range))
+      .into_iter()
+      .flatten()
+      .filter_map(|(sn, ts)| self.changes.get(ts).map(|cc| (*sn, *ts, cc)))
+  }
+
+  // Stages and commits a single change
+  #[cfg(test)]
+  pub fn add_change(&mut self, instant: &Timestamp, cache_change: CacheChange) {
+    self.stage_change(instant, cache_change);
+    self.commit_changes();
+  }
+
+  /// Adds a fully constructed change to the cache, but does not show it to
+  /// readers yet. Staged changes become visible in
+  /// [`commit_changes`](Self::commit_changes).
+  pub fn stage_change(&mut self, instant: &Timestamp, cache_change: CacheChange) {
+    let staged_duplicate = self.staged_changes.iter().any(|(_, cc)| {
+      cc.writer_guid == cache_change.writer_guid
+        && cc.sequence_number == cache_change.sequence_number
+    });
+    if let Some(old_instant) = self.find_by_sn(&cache_change) {
+      // Got duplicate DATA for a SN that we already have. It should be discarded.
+      debug!(
+        "add_change: discarding duplicate {:?} from {:?}. old timestamp = {:?}, new = {:?}",
+        cache_change.sequence_number, cache_change.writer_guid, old_instant, instant,
+      );
+    } else if staged_duplicate {
+      debug!(
+        "add_change: discarding duplicate {:?} from {:?}, already staged. topic={:?}",
+        cache_change.sequence_number, cache_change.writer_guid, self.topic_name
+      );
+    } else {
+      // This is a new (to us) SequenceNumber, this is the default processing path.
+      self.staged_changes.push((*instant, cache_change));
+    }
+  }
+
+  /// Makes all staged changes visible to readers at once, and wakes up the
+  /// subscribers. Returns the resulting epoch.
+  pub fn commit_changes(&mut self) -> u64 {
+    if self.staged_changes.is_empty() {
+      return self.committed_epoch;
+    }
+    let staged_changes = std::mem::take(&mut self.staged_changes);
+    let new_samples = staged_changes.len();
+    // A change may have been staged before a change that is already committed,
+    // e.g. from a local DataWriter. It must still come after it, or DataReaders
+    // that have read up to the latest instant would never see it.
+    let mut latest_instant = self.changes.keys().next_back().copied();
+    for (instant, cache_change) in staged_changes {
+      let instant = match latest_instant {
+        Some(latest) if instant <= latest => latest + Duration::from_nanos(1),
+        _ => instant,
+      };
+      latest_instant = Some(instant);
+      self.insert_change(instant, cache_change);
+    }
+    for (writer, sn) in std::mem::take(&mut self.staged_reliably_received_before) {
+      let marker = self.received_reliably_before.entry(writer).or_insert(sn);
+      *marker = max(*marker, sn);
+    }
+    self.committed_epoch += 1;
+    self.notify_subscribers(new_samples);
+    self.committed_epoch
+  }
+
+  fn insert_change(&mut self, instant: Timestamp, cache_change: CacheChange) {
     // First, do garbage collection.
     // But not at every insert, just to save time and effort.
     // Some heuristic to decide if we should collect now.
@@ -199,34 +506,25 @@ impl TopicCache {
     let modulus = fairly_large_constant / payload_size;
     if modulus == 0 || semi_random_number % modulus == 0 {
       debug!("Garbage collecting topic {}", self.topic_name);
-      self.remove_changes_before(Timestamp::ZERO);
-      // remove limit is so low that it has no effect.
-      // We actually only remove to keep cache size between min and max limits.
+      self.remove_excess_changes();
     }
 
-    // Now to the actual adding business.
-    if let Some(old_instant) = self.find_by_sn(&cache_change) {
-      // Got duplicate DATA for a SN that we already have. It should be discarded.
+    if let Some(old_cc) = self.changes.remove(&instant) {
+      // If this happens, cache changes were created at exactly same instant.
+      // This is bad, since we are using instants as keys and assume that they
+      // are unique.
+      error!(
+        "DDSHistoryCache already contained element with key {:?} !!!",
+        instant
+      );
+      self.remove_sn(instant, &old_cc);
       debug!(
-        "add_change: discarding duplicate {:?} from {:?}. old timestamp = {:?}, new = {:?}",
-        cache_change.sequence_number, cache_change.writer_guid, old_instant, instant,
+        "DDSCache insert failed topic={:?} cache_change={:?}",
+        self.topic_name, old_cc
       );
-      Some(cache_change)
-    } else {
-      // This is a new (to us) SequenceNumber, this is the default processing path.
-      self.insert_sn(*instant, &cache_change);
-      self.changes.insert(*instant, cache_change).map(|old_cc| {
-        // If this happens, cache changes were created at exactly same instant.
-        // This is bad, since we are using instants as keys and assume that they
-        // are unique.
-        error!(
-          "DDSHistoryCache already contained element with key {:?} !!!",
-          instant
-        );
-        self.remove_sn(&old_cc);
-        old_cc
-      })
     }
+    self.insert_sn(instant, &cache_change);
+    self.changes.insert(instant, Arc::new(cache_change));
   }
 
   fn find_by_sn(&self, cc: &CacheChange) -> Option<Timestamp> {
@@ -243,18 +541,41 @@ impl TopicCache {
       .entry(cc.writer_guid)
       .or_default()
       .insert(cc.sequence_number, instant);
+    self
+      .source_timestamps
+      .insert((Self::source_timestamp(instant, cc), instant));
+  }
+
+  // Changes without a source timestamp are considered to be written when they
+  // were received.
+  fn source_timestamp(instant: Timestamp, cc: &CacheChange) -> Timestamp {
+    cc.write_options.source_timestamp().unwrap_or(instant)
+  }
+
+  /// Changes with source timestamp at or before `source_time`, in source
+  /// timestamp order.
+  pub fn get_changes_as_of(
+    &self,
+    source_time: Timestamp,
+  ) -> impl Iterator<Item = (Timestamp, &CacheChange)> + '_ {
+    self
+      .source_timestamps
+      .range(..=(source_time, Timestamp::INVALID))
+      .filter_map(|(_source, instant)| self.get_change(instant).map(|cc| (*instant, cc)))
   }
 
+  // Changes after `start_instant`. There is no upper bound, because the keys
+  // may be ahead of the wall clock after it has been stepped backwards. See
+  // Timestamp::now_increasing.
   pub fn get_changes_in_range_best_effort(
     &self,
     start_instant: Timestamp,
-    end_instant: Timestamp,
   ) -> Box<dyn Iterator<Item = (Timestamp, &CacheChange)> + '_> {
     Box::new(
       self
         .changes
-        .range((Excluded(start_instant), Included(end_instant)))
-        .map(|(i, c)| (*i, c)),
+        .range((Excluded(start_instant), Unbounded))
+        .map(|(i, c)| (*i, c.as_ref())),
     )
   }
 
@@ -265,8 +586,8 @@ impl TopicCache {
     Box::new(
       self
         .sequence_numbers
-        .iter()
-        .flat_map(|(guid, sn_map)| {
+        .keys()
+        .flat_map(|guid| {
           let lower_bound_exc = last_read_sn
             .get(guid)
             .cloned()
@@ -274,9 +595,12 @@ impl TopicCache {
           let upper_bound_exc = self.reliable_before(*guid);
           // make sure lower < upper, so that `.range()` does not panic.
           let upper_bound_exc = max(upper_bound_exc, lower_bound_exc.plus_1());
-          sn_map.range((Excluded(lower_bound_exc), Excluded(upper_bound_exc)))
-        }) // we get iterator of Timestamp
-        .filter_map(|(_sn, t)| self.get_change(t).map(|cc| (*t, cc))),
+          self.changes_in_sn_range(
+            *guid,
+            (Excluded(lower_bound_exc), Excluded(upper_bound_exc)),
+          )
+        })
+        .map(|(_sn, t, cc)| (t, cc.as_ref())),
     )
   }
 
@@ -299,7 +623,10 @@ impl TopicCache {
     // received reliably, since no such samples exist.
   }
 
-  fn remove_sn(&mut self, cc: &CacheChange) {
+  fn remove_sn(&mut self, instant: Timestamp, cc: &CacheChange) {
+    self
+      .source_timestamps
+      .remove(&(Self::source_timestamp(instant, cc), instant));
     let mut emptied = false;
 
     self.sequence_numbers.entry(cc.writer_guid).and_modify(|s| {
@@ -311,43 +638,36 @@ impl TopicCache {
     }
   }
 
-  /// remove changes before given Timestamp, but keep at least
-  /// min_keep_samples.
-  /// We must always keep below max_keep_samples.
-  pub fn remove_changes_before(&mut self, remove_before: Timestamp) {
-    let min_remove_count = max(
-      0,
-      self
-        .changes
-        .len()
-        .wrapping_sub(self.max_keep_samples as usize),
-    );
+  /// Removes the oldest changes until the cache is within max_keep_samples.
+  /// The latest change of each instance from each writer is never removed,
+  /// because it may still be needed for late joining DataReaders.
+  pub fn remove_excess_changes(&mut self) {
+    let remove_count = self
+      .changes
+      .len()
+      .saturating_sub(self.max_keep_samples as usize);
+    if remove_count == 0 {
+      return;
+    }
 
-    let max_remove_count = min_remove_count;
-    // Only observe max cache size
-    // TODO: Can we do better without being able to distinguish between instances?
+    let mut latest_of_instance: BTreeMap<(GUID, Option<KeyHash>), Timestamp> = BTreeMap::new();
+    for (ts, cc) in &self.changes {
+      latest_of_instance.insert((cc.writer_guid, cc.data_value.key_hash()), *ts);
+    }
+    let protected: BTreeSet<Timestamp> = latest_of_instance.into_values().collect();
 
-    // Find the first key that is to be retained, i.e. enumerate
-    // one past the items to be removed.
-    let split_key = *self
+    let to_remove: Vec<Timestamp> = self
       .changes
       .keys()
-      .take(max_remove_count)
-      .enumerate()
-      .skip_while(|(i, ts)| {
-        *i < min_remove_count || (**ts < remove_before && *i < max_remove_count)
-      })
-      .map(|(_, ts)| ts) // un-enumerate
-      .next() // the next element would be the first to retain
-      .unwrap_or(&Timestamp::ZERO); // if there is no next, then everything from ZERO onwards
-
-    // split_off: Returns everything after the given key, including the key.
-    let to_retain = self.changes.split_off(&split_key);
-    let to_remove = std::mem::replace(&mut self.changes, to_retain);
+      .filter(|ts| !protected.contains(ts))
+      .take(remove_count)
+      .copied()
+      .collect();
 
-    // update also SequenceNumber map
-    for r in to_remove.values() {
-      self.remove_sn(r);
+    for ts in to_remove {
+      if let Some(cc) = self.changes.remove(&ts) {
+        self.remove_sn(ts, &cc);
+      }
     }
   }
 
@@ -363,17 +683,31 @@ impl TopicCache {
 #[cfg(test)]
 mod tests {
   use std::{
+    collections::BTreeMap,
     sync::{Arc, RwLock},
     thread,
   };
 
-  use super::DDSCache;
+  use mio_extras::channel as mio_channel;
+
+  use super::{ChangeNotifier, DDSCache, TopicCache};
   use crate::{
     dds::{
-      ddsdata::DDSData, qos::QosPolicies, typedesc::TypeDesc, with_key::datawriter::WriteOptions,
+      ddsdata::DDSData,
+      key::KeyHash,
+      qos::{policy, QosPolicies, QosPolicyBuilder},
+      typedesc::TypeDesc,
+      with_key::datawriter::WriteOptions,
     },
     messages::submessages::elements::serialized_payload::SerializedPayload,
-    structure::{cache_change::CacheChange, guid::GUID, sequence_number::SequenceNumber},
+    mio_source,
+    structure::{
+      cache_change::CacheChange,
+      duration::Duration,
+      guid::{EntityKind, GUID},
+      sequence_number::SequenceNumber,
+      time::Timestamp,
+    },
   };
 
   #[test]
@@ -436,12 +770,284 @@ mod tests {
       topic_cache_handle
         .lock()
         .unwrap()
-        .get_changes_in_range_best_effort(
-          crate::Timestamp::now() - crate::Duration::from_secs(23),
-          crate::Timestamp::now()
-        )
+        .get_changes_in_range_best_effort(crate::Timestamp::now() - crate::Duration::from_secs(23))
         .count(),
       3
     );
   }
+
+  #[test]
+  fn new_change_notifies_subscribers_of_the_topic_only() {
+    let mut dds_cache = DDSCache::new();
+    let qos = QosPolicies::qos_none();
+    let topic_a = dds_cache.add_new_topic("A".to_string(), TypeDesc::new("T".to_string()), &qos);
+    let topic_b = dds_cache.add_new_topic("B".to_string(), TypeDesc::new("T".to_string()), &qos);
+
+    let (notification_sender, notification_receiver) = mio_channel::sync_channel::<()>(10);
+    let (_poll_event_source, poll_event_sender) = mio_source::make_poll_channel().unwrap();
+    let reader_guid = GUID::dummy_test_guid(EntityKind::READER_NO_KEY_USER_DEFINED);
+    topic_a.lock().unwrap().add_subscriber(
+      reader_guid,
+      ChangeNotifier::new(
+        Arc::new(std::sync::Mutex::new(None)),
+        poll_event_sender,
+        notification_sender,
+      ),
+    );
+
+    let change = |sn| {
+      CacheChange::new(
+        GUID::GUID_UNKNOWN,
+        SequenceNumber::new(sn),
+        WriteOptions::default(),
+        DDSData::new(SerializedPayload::default()),
+      )
+    };
+
+    // A change to another topic does not wake up the subscriber.
+    topic_b
+      .lock()
+      .unwrap()
+      .add_change(&crate::Timestamp::now(), change(1));
+    assert!(notification_receiver.try_recv().is_err());
+
+    topic_a
+      .lock()
+      .unwrap()
+      .add_change(&crate::Timestamp::now(), change(1));
+    assert!(notification_receiver.try_recv().is_ok());
+
+    // Duplicates are discarded, so there is nothing new to read.
+    topic_a
+      .lock()
+      .unwrap()
+      .add_change(&crate::Timestamp::now(), change(1));
+    assert!(notification_receiver.try_recv().is_err());
+
+    topic_a.lock().unwrap().remove_subscriber(reader_guid);
+    topic_a
+      .lock()
+      .unwrap()
+      .add_change(&crate::Timestamp::now(), change(2));
+    assert!(notification_receiver.try_recv().is_err());
+  }
+
+  #[test]
+  fn staged_changes_are_invisible_until_committed() {
+    let mut topic_cache = TopicCache::new(
+      "A".to_string(),
+      TypeDesc::new("T".to_string()),
+      &QosPolicies::qos_none(),
+    );
+    let (notification_sender, notification_receiver) = mio_channel::sync_channel::<()>(10);
+    let (_poll_event_source, poll_event_sender) = mio_source::make_poll_channel().unwrap();
+    topic_cache.add_subscriber(
+      GUID::dummy_test_guid(EntityKind::READER_NO_KEY_USER_DEFINED),
+      ChangeNotifier::new(
+        Arc::new(std::sync::Mutex::new(None)),
+        poll_event_sender,
+        notification_sender,
+      ),
+    );
+    let writer = GUID::dummy_test_guid(EntityKind::WRITER_NO_KEY_USER_DEFINED);
+    topic_cache.mark_reliably_received_before(writer, SequenceNumber::new(10));
+    while notification_receiver.try_recv().is_ok() {}
+
+    let change = |sn| {
+      CacheChange::new(
+        writer,
+        SequenceNumber::new(sn),
+        WriteOptions::default(),
+        DDSData::new(SerializedPayload::default()),
+      )
+    };
+    let start = Timestamp::now();
+    let t1 = Timestamp::now();
+    let t2 = t1 + Duration::from_millis(1);
+    topic_cache.stage_change(&t1, change(1));
+    topic_cache.stage_change(&t2, change(2));
+    // Staging the same SN again is a duplicate
+    topic_cache.stage_change(&(t2 + Duration::from_millis(1)), change(2));
+
+    let visible = |tc: &TopicCache| {
+      let best_effort = tc.get_changes_in_range_best_effort(start).count();
+      let reliable = tc.get_changes_in_range_reliable(&BTreeMap::new()).count();
+      assert_eq!(best_effort, reliable);
+      best_effort
+    };
+    assert_eq!(visible(&topic_cache), 0);
+    assert!(topic_cache.get_change(&t1).is_none());
+    assert!(topic_cache.get_changes_in_sn_range(writer, ..).is_empty());
+    assert!(notification_receiver.try_recv().is_err());
+
+    assert_eq!(topic_cache.commit_changes(), 1);
+    assert_eq!(visible(&topic_cache), 2);
+    // One wakeup for the whole commit
+    assert!(notification_receiver.try_recv().is_ok());
+    assert!(notification_receiver.try_recv().is_err());
+
+    // Nothing staged: nothing changes
+    assert_eq!(topic_cache.commit_changes(), 1);
+    assert!(notification_receiver.try_recv().is_err());
+  }
+
+  #[test]
+  fn excess_changes_are_removed_but_not_the_latest_of_an_instance() {
+    let qos = QosPolicyBuilder::new()
+      .resource_limits(policy::ResourceLimits {
+        max_samples: 2,
+        max_instances: 2,
+        max_samples_per_instance: 2,
+      })
+      .build();
+    let mut topic_cache = TopicCache::new("A".to_string(), TypeDesc::new("T".to_string()), &qos);
+    let writer = GUID::dummy_test_guid(EntityKind::WRITER_WITH_KEY_USER_DEFINED);
+
+    // Instances b, a, a, a
+    let instances = [2, 1, 1, 1];
+    for (i, k) in instances.into_iter().enumerate() {
+      let data = DDSData::new(SerializedPayload::default())
+        .with_key_hash(Some(KeyHash::from_pl_cdr_bytes(vec![k; 16]).unwrap()));
+      let cc = CacheChange::new(
+        writer,
+        SequenceNumber::from(i as i64 + 1),
+        WriteOptions::default(),
+        data,
+      );
+      topic_cache.add_change(&(Timestamp::ZERO + Duration::from_millis(i as i64 + 1)), cc);
+    }
+
+    topic_cache.remove_excess_changes();
+    // The oldest changes of a are removed, but b is kept.
+    let sns: Vec<SequenceNumber> = topic_cache
+      .changes
+      .values()
+      .map(|cc| cc.sequence_number)
+      .collect();
+    assert_eq!(sns, vec![SequenceNumber::from(1), SequenceNumber::from(4)]);
+    assert_eq!(
+      topic_cache.writers_smallest_sn_in_cache(writer),
+      Some(SequenceNumber::from(1))
+    );
+  }
+
+  #[test]
+  fn changes_are_fetched_by_sequence_number_range() {
+    let mut topic_cache = TopicCache::new(
+      "A".to_string(),
+      TypeDesc::new("T".to_string()),
+      &QosPolicies::qos_none(),
+    );
+    let writer = GUID::dummy_test_guid(EntityKind::WRITER_WITH_KEY_USER_DEFINED);
+    let other_writer = GUID::dummy_test_guid(EntityKind::WRITER_NO_KEY_USER_DEFINED);
+    let sn = SequenceNumber::from;
+
+    // Writer has 1..=5 except 3. The other writer has 1..=5.
+    for (i, (w, n)) in [1, 2, 4, 5]
+      .map(|n| (writer, n))
+      .into_iter()
+      .chain((1..=5).map(|n| (other_writer, n)))
+      .enumerate()
+    {
+      let cc = CacheChange::new(
+        w,
+        sn(n),
+        WriteOptions::default(),
+        DDSData::new(SerializedPayload::default()),
+      );
+      topic_cache.add_change(&(Timestamp::ZERO + Duration::from_millis(i as i64 + 1)), cc);
+    }
+
+    let sns = |changes: Vec<(SequenceNumber, Arc<CacheChange>)>| {
+      changes
+        .into_iter()
+        .map(|(n, cc)| {
+          assert_eq!(n, cc.sequence_number);
+          assert_eq!(cc.writer_guid, writer);
+          n
+        })
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(
+      sns(topic_cache.get_changes_in_sn_range(writer, sn(2)..=sn(4))),
+      vec![sn(2), sn(4)]
+    );
+    assert_eq!(
+      sns(topic_cache.get_changes_in_sn_range(writer, sn(4)..)),
+      vec![sn(4), sn(5)]
+    );
+    assert!(topic_cache
+      .get_changes_in_sn_range(writer, sn(6)..sn(10))
+      .is_empty());
+    let unknown = GUID::dummy_test_guid(EntityKind::WRITER_WITH_KEY_BUILT_IN);
+    assert!(topic_cache.get_changes_in_sn_range(unknown, ..).is_empty());
+  }
+
+  #[test]
+  fn coalesced_notifications_wait_for_enough_samples_or_max_delay() {
+    let mut topic_cache = TopicCache::new(
+      "A".to_string(),
+      TypeDesc::new("T".to_string()),
+      &QosPolicies::qos_none(),
+    );
+    let (notification_sender, notification_receiver) = mio_channel::sync_channel::<()>(10);
+    let (_poll_event_source, poll_event_sender) = mio_source::make_poll_channel().unwrap();
+    let (flush_request_sender, flush_requests) = mio_channel::sync_channel::<()>(1);
+    let reader_guid = GUID::dummy_test_guid(EntityKind::READER_NO_KEY_USER_DEFINED);
+    let max_delay = Duration::from_millis(200);
+    topic_cache.add_subscriber(
+      reader_guid,
+      ChangeNotifier::new(
+        Arc::new(std::sync::Mutex::new(None)),
+        poll_event_sender,
+        notification_sender,
+      )
+      .with_coalescing(
+        Some(policy::NotificationCoalescing {
+          max_samples: 3,
+          max_delay,
+        }),
+        flush_request_sender,
+      ),
+    );
+    let writer = GUID::dummy_test_guid(EntityKind::WRITER_NO_KEY_USER_DEFINED);
+    let add_change = |topic_cache: &mut TopicCache, sn| {
+      topic_cache.add_change(
+        &Timestamp::now(),
+        CacheChange::new(
+          writer,
+          SequenceNumber::new(sn),
+          WriteOptions::default(),
+          DDSData::new(SerializedPayload::default()),
+        ),
+      );
+    };
+
+    // The Reader is asked once to flush the held back notification
+    add_change(&mut topic_cache, 1);
+    assert!(flush_requests.try_recv().is_ok());
+    add_change(&mut topic_cache, 2);
+    assert!(flush_requests.try_recv().is_err());
+    assert!(notification_receiver.try_recv().is_err());
+    add_change(&mut topic_cache, 3);
+    assert!(notification_receiver.try_recv().is_ok());
+
+    // Nothing pending, nothing to flush
+    topic_cache.flush_notification(reader_guid);
+    assert!(notification_receiver.try_recv().is_err());
+
+    add_change(&mut topic_cache, 4);
+    assert!(flush_requests.try_recv().is_ok());
+    assert!(notification_receiver.try_recv().is_err());
+    topic_cache.flush_notification(reader_guid);
+    assert!(notification_receiver.try_recv().is_ok());
+
+    // A change after max_delay notifies right away
+    add_change(&mut topic_cache, 5);
+    assert!(flush_requests.try_recv().is_ok());
+    std::thread::sleep(max_delay.to_std());
+    add_change(&mut topic_cache, 6);
+    assert!(notification_receiver.try_recv().is_ok());
+    assert!(flush_requests.try_recv().is_err());
+  }
 }
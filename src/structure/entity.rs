@@ -1,4 +1,4 @@
-use crate::structure::guid::{EntityId, GuidPrefix, GUID};
+use crate::structure::guid::{EntityId, EntityKind, GuidPrefix, GUID};
 
 /// Trait for things that have a [`GUID`].
 ///
@@ -6,7 +6,10 @@ use crate::structure::guid::{EntityId, GuidPrefix, GUID};
 /// objects that are visible to other RTPS Entities on the network. As such,
 /// RTPS Entity objects have a globally-unique identifier (GUID) and can be
 /// referenced inside RTPS messages.
-/// (for usage, DomainParticipant, DataReader and DataWriter implement this)
+/// (for usage, DomainParticipant, Publisher, Subscriber, Topic, DataReader and
+/// DataWriter implement this)
+///
+/// The GUID can be used to find the entity in network traces, e.g. Wireshark.
 /// RTPS 2.3 specification section 8.2.4
 pub trait RTPSEntity {
   fn guid(&self) -> GUID;
@@ -17,4 +20,7 @@ pub trait RTPSEntity {
   fn guid_prefix(&self) -> GuidPrefix {
     self.guid().prefix
   }
+  fn entity_kind(&self) -> EntityKind {
+    self.guid().entity_id.entity_kind
+  }
 }
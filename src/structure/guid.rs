@@ -1,4 +1,4 @@
-use std::{fmt, hash::Hash, ops::RangeBounds};
+use std::{fmt, hash::Hash, ops::RangeBounds, str::FromStr};
 
 use speedy::{Context, Readable, Reader, Writable, Writer};
 use serde::{Deserialize, Serialize};
@@ -9,6 +9,40 @@ use static_assertions as sa;
 
 use crate::dds::key::Key;
 
+/// Error from parsing a [`GuidPrefix`], [`EntityId`] or [`GUID`] from its
+/// text representation.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum GuidParseError {
+  #[error("Expected {expected} hex digits, found {found}")]
+  BadLength { expected: usize, found: usize },
+  #[error("Invalid character {0:?}")]
+  InvalidCharacter(char),
+}
+
+// Parses hex digits into a byte array. The separators ':' and '.' are allowed
+// anywhere, so that the output of the Display impls parses back, but also the
+// plain hex digits are accepted.
+fn parse_hex_bytes<const N: usize>(s: &str) -> Result<[u8; N], GuidParseError> {
+  let mut digits = Vec::with_capacity(2 * N);
+  for c in s.trim().chars() {
+    match c {
+      ':' | '.' => (),
+      _ => digits.push(c.to_digit(16).ok_or(GuidParseError::InvalidCharacter(c))? as u8),
+    }
+  }
+  if digits.len() != 2 * N {
+    return Err(GuidParseError::BadLength {
+      expected: 2 * N,
+      found: digits.len(),
+    });
+  }
+  let mut bytes = [0; N];
+  for (b, pair) in bytes.iter_mut().zip(digits.chunks(2)) {
+    *b = (pair[0] << 4) | pair[1];
+  }
+  Ok(bytes)
+}
+
 /// DDS/RTPS Participant GuidPrefix
 #[derive(
   Copy, Clone, PartialOrd, PartialEq, Ord, Eq, Hash, Serialize, Deserialize, CdrEncodingSize,
@@ -69,6 +103,42 @@ impl fmt::Debug for GuidPrefix {
   }
 }
 
+/// Formats the prefix as three colon-separated groups of eight hex digits,
+/// e.g. `01120e4a:6bd42f73:00000001`.
+impl fmt::Display for GuidPrefix {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (i, group) in self.bytes.chunks(4).enumerate() {
+      if i > 0 {
+        f.write_str(":")?;
+      }
+      for b in group {
+        write!(f, "{:02x}", b)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+impl FromStr for GuidPrefix {
+  type Err = GuidParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    parse_hex_bytes(s).map(|bytes| Self { bytes })
+  }
+}
+
+impl From<[u8; 12]> for GuidPrefix {
+  fn from(bytes: [u8; 12]) -> Self {
+    Self { bytes }
+  }
+}
+
+impl From<GuidPrefix> for [u8; 12] {
+  fn from(prefix: GuidPrefix) -> Self {
+    prefix.bytes
+  }
+}
+
 impl Default for GuidPrefix {
   fn default() -> Self {
     Self::UNKNOWN
@@ -127,6 +197,11 @@ impl EntityKind {
   pub const WRITER_GROUP_BUILT_IN: Self = Self(0xC8);
   pub const READER_GROUP_BUILT_IN: Self = Self(0xC9);
 
+  // Topics are not RTPS entities, so Table 9.1 has no kind for them. This is
+  // from the vendor-specific range (upper bits 01), and is used only for the
+  // GUIDs that RustDDS gives to its Topic objects.
+  pub const TOPIC_VENDOR_SPECIFIC: Self = Self(0x4A);
+
   pub const MIN: Self = Self(0x00);
   pub const MAX: Self = Self(0xFF);
 
@@ -168,6 +243,35 @@ impl EntityKind {
   pub fn is_user_defined(&self) -> bool {
     (self.0 & 0xF0) == 0x00
   }
+
+  pub fn is_vendor_specific(&self) -> bool {
+    (self.0 & 0xC0) == 0x40
+  }
+
+  pub fn is_participant(&self) -> bool {
+    self.0 & 0x0F == 0x01
+  }
+
+  /// Publisher (writer group) or Subscriber (reader group)
+  pub fn is_group(&self) -> bool {
+    let e = self.0 & 0x0F;
+    e == 0x08 || e == 0x09
+  }
+
+  /// DataReader or DataWriter, i.e. a reader or writer that is not a group.
+  pub fn is_endpoint(&self) -> bool {
+    (self.is_reader() || self.is_writer()) && !self.is_group()
+  }
+
+  /// Endpoint of a WITH_KEY topic
+  pub fn is_with_key(&self) -> bool {
+    let e = self.0 & 0x0F;
+    e == 0x02 || e == 0x07
+  }
+
+  pub fn is_topic(&self) -> bool {
+    *self == Self::TOPIC_VENDOR_SPECIFIC
+  }
 }
 
 impl From<u8> for EntityKind {
@@ -200,6 +304,7 @@ impl fmt::Debug for EntityKind {
       Self::READER_WITH_KEY_BUILT_IN => f.write_str("EntityKind::READER_WITH_KEY_BUILT_IN"),
       Self::WRITER_GROUP_BUILT_IN => f.write_str("EntityKind::WRITER_GROUP_BUILT_IN"),
       Self::READER_GROUP_BUILT_IN => f.write_str("EntityKind::READER_GROUP_BUILT_IN"),
+      Self::TOPIC_VENDOR_SPECIFIC => f.write_str("EntityKind::TOPIC_VENDOR_SPECIFIC"),
       _ => f.write_fmt(format_args!("EntityKind({:x?})", self.0)),
     }
   }
@@ -329,6 +434,25 @@ impl EntityId {
     entity_kind: EntityKind::READER_WITH_KEY_BUILT_IN,
   };
 
+  // RustDDS-specific built-in endpoints for measuring round-trip times, see
+  // crate::ping
+  pub const RUSTDDS_PING_REQUEST_WRITER: Self = Self {
+    entity_key: [0x00, 0x80, 0x01],
+    entity_kind: EntityKind::WRITER_NO_KEY_BUILT_IN,
+  };
+  pub const RUSTDDS_PING_REQUEST_READER: Self = Self {
+    entity_key: [0x00, 0x80, 0x01],
+    entity_kind: EntityKind::READER_NO_KEY_BUILT_IN,
+  };
+  pub const RUSTDDS_PING_REPLY_WRITER: Self = Self {
+    entity_key: [0x00, 0x80, 0x02],
+    entity_kind: EntityKind::WRITER_NO_KEY_BUILT_IN,
+  };
+  pub const RUSTDDS_PING_REPLY_READER: Self = Self {
+    entity_key: [0x00, 0x80, 0x02],
+    entity_kind: EntityKind::READER_NO_KEY_BUILT_IN,
+  };
+
   pub const MIN: Self = Self {
     entity_key: [0x00; 3],
     entity_kind: EntityKind::MIN,
@@ -485,6 +609,37 @@ impl fmt::Debug for EntityId {
   }
 }
 
+/// Formats the EntityId as eight hex digits, the entity key followed by the
+/// entity kind, e.g. `000001c1` for [`EntityId::PARTICIPANT`].
+impl fmt::Display for EntityId {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for b in self.to_slice() {
+      write!(f, "{:02x}", b)?;
+    }
+    Ok(())
+  }
+}
+
+impl FromStr for EntityId {
+  type Err = GuidParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    parse_hex_bytes(s).map(Self::from_slice)
+  }
+}
+
+impl From<[u8; 4]> for EntityId {
+  fn from(bytes: [u8; 4]) -> Self {
+    Self::from_slice(bytes)
+  }
+}
+
+impl From<EntityId> for [u8; 4] {
+  fn from(entity_id: EntityId) -> Self {
+    entity_id.to_slice()
+  }
+}
+
 impl<'a, C: Context> Readable<'a, C> for EntityId {
   #[inline]
   fn read_from<R: Reader<'a, C>>(reader: &mut R) -> Result<Self, C::Error> {
@@ -624,6 +779,37 @@ impl fmt::Debug for GUID {
   }
 }
 
+/// Formats the GUID as the [`GuidPrefix`] and the [`EntityId`] separated by
+/// a colon, i.e. four groups of eight hex digits, e.g.
+/// `01120e4a:6bd42f73:00000001:000001c1`. This is the format also used by
+/// several other DDS implementations, and it can be parsed back with
+/// [`str::parse`].
+impl fmt::Display for GUID {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}:{}", self.prefix, self.entity_id)
+  }
+}
+
+impl FromStr for GUID {
+  type Err = GuidParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    parse_hex_bytes(s).map(Self::from_bytes)
+  }
+}
+
+impl From<[u8; 16]> for GUID {
+  fn from(bytes: [u8; 16]) -> Self {
+    Self::from_bytes(bytes)
+  }
+}
+
+impl From<GUID> for [u8; 16] {
+  fn from(guid: GUID) -> Self {
+    guid.to_bytes()
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use speedy::Endianness;
@@ -815,6 +1001,38 @@ mod tests {
     }
   );
 
+  #[test]
+  fn text_format_round_trip() {
+    let guid = GUID::from_bytes([
+      0x01, 0x12, 0x0e, 0x4a, 0x6b, 0xd4, 0x2f, 0x73, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x01,
+      0xc1,
+    ]);
+    let text = guid.to_string();
+    assert_eq!(text, "01120e4a:6bd42f73:00000001:000001c1");
+    assert_eq!(text.parse::<GUID>(), Ok(guid));
+    assert_eq!("01120e4a6bd42f7300000001000001C1".parse::<GUID>(), Ok(guid));
+
+    assert_eq!(guid.prefix.to_string(), "01120e4a:6bd42f73:00000001");
+    assert_eq!(
+      guid.prefix.to_string().parse::<GuidPrefix>(),
+      Ok(guid.prefix)
+    );
+    assert_eq!(EntityId::PARTICIPANT.to_string(), "000001c1");
+    assert_eq!("000001c1".parse::<EntityId>(), Ok(EntityId::PARTICIPANT));
+
+    assert_eq!(
+      "01120e4a:6bd42f73".parse::<GUID>(),
+      Err(GuidParseError::BadLength {
+        expected: 32,
+        found: 16
+      })
+    );
+    assert_eq!(
+      "0000x1c1".parse::<EntityId>(),
+      Err(GuidParseError::InvalidCharacter('x'))
+    );
+  }
+
   #[test]
   fn guid_unknown_is_a_combination_of_unknown_members() {
     assert_eq!(
@@ -2,6 +2,7 @@ use std::net::{SocketAddrV4, SocketAddrV6};
 pub use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 
 use speedy::{Context, Readable, Reader, Writable, Writer};
+use serde::Serialize;
 
 mod kind {
   pub const INVALID: i32 = -1;
@@ -13,7 +14,7 @@ mod kind {
 const INVALID_PORT: u16 = 0;
 const INVALID_ADDRESS: [u8; 16] = [0; 16];
 
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash, Serialize)]
 pub enum Locator {
   Invalid,
   Reserved,
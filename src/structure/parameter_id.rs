@@ -9,6 +9,20 @@ pub struct ParameterId {
 }
 
 impl ParameterId {
+  pub const fn new(value: u16) -> Self {
+    Self { value }
+  }
+
+  pub const fn value(self) -> u16 {
+    self.value
+  }
+
+  // One of the PID_RUSTDDS_* parameters below. These are meaningful only when
+  // sent by RustDDS, see rtps::compatibility.
+  pub const fn is_rustdds_specific(self) -> bool {
+    self.value & 0xff00 == 0x8b00
+  }
+
   pub const PID_PAD: Self = Self { value: 0x0000 };
   pub const PID_SENTINEL: Self = Self { value: 0x0001 };
   pub const PID_USER_DATA: Self = Self { value: 0x002c };
@@ -80,6 +94,13 @@ impl ParameterId {
   // Wireshark calls this "PID_RELATED_ORIGINAL_WRITER_INFO".
   pub const PID_RELATED_SAMPLE_IDENTITY: Self = Self { value: /*0x0083*/ 0x800f };
 
+  // RustDDS vendor-specific parameters
+  pub const PID_RUSTDDS_SHARED_SUBSCRIPTION: Self = Self { value: 0x8b01 };
+  pub const PID_RUSTDDS_TRACE_CONTEXT: Self = Self { value: 0x8b02 };
+  pub const PID_RUSTDDS_COMPRESSION: Self = Self { value: 0x8b03 };
+  pub const PID_RUSTDDS_COMPRESSED_PAYLOAD: Self = Self { value: 0x8b04 };
+  pub const PID_RUSTDDS_PAYLOAD_CHECKSUM: Self = Self { value: 0x8b05 };
+
   // DDS Security spec v1.1:
 
   // Section 7.4.1.4 Extension to RTPS Standard DCPSParticipants Builtin Topic
@@ -1,9 +1,12 @@
 use std::{
   cmp::min,
+  fmt,
   fmt::Debug,
   hash::Hash,
   mem::size_of,
+  num::ParseIntError,
   ops::{Bound, RangeBounds},
+  str::FromStr,
 };
 // use crate::messages::fragment_number::FragmentNumber;
 use std::collections::BTreeSet;
@@ -13,6 +16,7 @@ use num_derive::{FromPrimitive, NumOps, ToPrimitive};
 use serde::{Deserialize, Serialize};
 use speedy::{Context, Readable, Reader, Writable, Writer};
 use log::error;
+use rustdds_rtps_core as rtps_core;
 
 //
 /// RTPS Specification v2.3 Section "8.3.5.4 SequenceNumber"
@@ -36,6 +40,12 @@ pub struct SequenceNumber(i64);
 impl SequenceNumber {
   pub const SEQUENCENUMBER_UNKNOWN: Self = Self((std::u32::MAX as i64) << 32);
 
+  /// Largest sequence number accepted from remote participants. No writer
+  /// can get anywhere near this by publishing, but staying well below
+  /// `i64::MAX` guarantees that sequence number arithmetic on received values
+  /// cannot overflow.
+  pub const MAX_ACCEPTED: Self = Self(i64::MAX >> 1);
+
   pub fn new(value: i64) -> Self {
     Self::from(value)
   }
@@ -56,6 +66,13 @@ impl SequenceNumber {
     Self(((high as i64) << 32) + (low as i64))
   }
 
+  /// Is this a valid sequence number of a change from a remote participant,
+  /// i.e. positive, not `SEQUENCENUMBER_UNKNOWN`, and not above
+  /// [`Self::MAX_ACCEPTED`].
+  pub fn is_valid(&self) -> bool {
+    Self::zero() < *self && *self <= Self::MAX_ACCEPTED
+  }
+
   pub fn high(&self) -> i32 {
     (self.0 >> 32) as i32
   }
@@ -95,6 +112,20 @@ impl From<SequenceNumber> for i64 {
   }
 }
 
+impl fmt::Display for SequenceNumber {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fmt::Display::fmt(&self.0, f)
+  }
+}
+
+impl FromStr for SequenceNumber {
+  type Err = ParseIntError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    s.trim().parse().map(Self)
+  }
+}
+
 // ---------------------------------------
 
 #[derive(Clone, Copy, Debug)]
@@ -445,6 +476,17 @@ where
   }
 }
 
+impl From<rtps_core::SequenceNumberSet> for SequenceNumberSet {
+  fn from(set: rtps_core::SequenceNumberSet) -> Self {
+    let word_count = (set.num_bits as usize + 31) / 32;
+    Self {
+      bitmap_base: SequenceNumber::new(set.base),
+      num_bits: set.num_bits,
+      bitmap: set.bitmap[..word_count].to_vec(),
+    }
+  }
+}
+
 impl<'a, C: Context, N> Readable<'a, C> for NumberSet<N>
 where
   N:
@@ -568,6 +610,14 @@ mod tests {
     assert_eq!(SequenceNumber::from(1), SequenceNumber::default());
   }
 
+  #[test]
+  fn sequence_number_text_format_round_trip() {
+    let sn = SequenceNumber::from_high_low(1, 2);
+    assert_eq!(sn.to_string(), "4294967298");
+    assert_eq!("4294967298".parse(), Ok(sn));
+    assert!("12a".parse::<SequenceNumber>().is_err());
+  }
+
   #[test]
   fn fragment_number_starts_by_default_from_one() {
     assert_eq!(FragmentNumber::from(1u32), FragmentNumber::default());
@@ -1,4 +1,8 @@
-use std::ops::{Add, Sub};
+use std::{
+  cmp::max,
+  ops::{Add, Sub},
+  sync::atomic::{AtomicU64, Ordering},
+};
 
 use speedy::{Readable, Writable};
 use serde::{Deserialize, Serialize};
@@ -68,6 +72,15 @@ impl Timestamp {
     }
   }
 
+  // Like now(), but later than any timestamp from this function before, even
+  // if the system clock has been stepped backwards, e.g. by NTP. Used as the
+  // keys of the topic cache, which must be unique and increase in reception
+  // order.
+  pub(crate) fn now_increasing() -> Self {
+    static CACHE_KEYS: IncreasingTimestamps = IncreasingTimestamps::new();
+    CACHE_KEYS.next(Self::now())
+  }
+
   fn to_ticks(self) -> u64 {
     (u64::from(self.seconds) << 32) + u64::from(self.fraction)
   }
@@ -91,6 +104,37 @@ impl Timestamp {
   }
 }
 
+// Follows the wall clock, but never goes backwards. After the wall clock is
+// stepped back, this advances by the smallest step until the wall clock
+// catches up.
+pub(crate) struct IncreasingTimestamps {
+  last_ticks: AtomicU64,
+}
+
+impl IncreasingTimestamps {
+  pub const fn new() -> Self {
+    Self {
+      last_ticks: AtomicU64::new(0),
+    }
+  }
+
+  pub fn next(&self, wall_clock: Timestamp) -> Timestamp {
+    if wall_clock == Timestamp::INVALID {
+      return wall_clock;
+    }
+    let wall_ticks = wall_clock.to_ticks();
+    let next = |last: u64| max(wall_ticks, last.saturating_add(1));
+    // The closure always gives Some, so this is always Ok.
+    let last = self
+      .last_ticks
+      .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| {
+        Some(next(last))
+      })
+      .unwrap_or_else(|last| last);
+    Timestamp::from_ticks(next(last))
+  }
+}
+
 impl Sub for Timestamp {
   type Output = Duration;
 
@@ -143,6 +187,24 @@ impl Add<Duration> for Timestamp {
 mod tests {
   use super::*;
 
+  #[test]
+  fn increasing_timestamps_survive_clock_steps() {
+    let timestamps = IncreasingTimestamps::new();
+    let t = |secs: u64| Timestamp::from_nanos(secs * 1_000_000_000);
+
+    let first = timestamps.next(t(5000));
+    assert_eq!(first, t(5000));
+    // Same wall clock reading
+    let second = timestamps.next(t(5000));
+    assert!(second > first);
+    // NTP steps the clock an hour back
+    let third = timestamps.next(t(5000 - 3600));
+    assert!(third > second);
+    assert!(third.duration_since(first) < Duration::from_secs(1));
+    // ... and forwards again
+    assert_eq!(timestamps.next(t(6000)), t(6000));
+  }
+
   serialization_test!( type = Timestamp,
   {
       time_zero,
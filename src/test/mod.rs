@@ -1,5 +1,6 @@
 #![cfg(test)]
 
+pub(crate) mod pcap;
 pub(crate) mod random_data;
 pub(crate) mod shape_type;
 pub(crate) mod test_data;
@@ -0,0 +1,620 @@
+// Reading and writing packet captures, for regression tests from captured
+// RTPS traffic.
+//
+// Captures can be in pcap or pcapng format, e.g. from Wireshark or tcpdump.
+// UDP datagrams are extracted from Ethernet (also VLAN-tagged), Linux cooked
+// (SLL and SLL2), BSD loopback, and raw IP link types. Fragmented IPv4
+// packets are reassembled. IPv6 packets with extension headers are skipped.
+//
+// Captures are written in pcap format with the raw IP link type, so they can
+// be opened in Wireshark.
+//
+// Setting the environment variable `RUSTDDS_REPLAY_PCAP` to a directory
+// replays all captures in it through a MessageReceiver in test
+// `replay_pcap_dir`, to check that they are processed without panicking.
+
+use std::{
+  collections::{BTreeMap, HashMap},
+  fs, io,
+  net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+  path::Path,
+  time::Duration,
+};
+
+use bytes::Bytes;
+
+use crate::rtps::message_receiver::MessageReceiver;
+
+// A UDP datagram from a capture
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Datagram {
+  // Since the Unix epoch
+  pub timestamp: Duration,
+  pub source: SocketAddr,
+  pub destination: SocketAddr,
+  pub payload: Bytes,
+}
+
+impl Datagram {
+  pub fn is_rtps(&self) -> bool {
+    self.payload.starts_with(b"RTPS")
+  }
+}
+
+const PCAP_MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+const PCAP_MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+const PCAPNG_SECTION_HEADER: u32 = 0x0a0d_0d0a;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const IP_PROTOCOL_UDP: u8 = 17;
+
+fn invalid(msg: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+#[derive(Clone, Copy)]
+struct Cursor<'a> {
+  data: &'a [u8],
+  big_endian: bool,
+}
+
+impl<'a> Cursor<'a> {
+  fn bytes(&self, offset: usize, len: usize) -> io::Result<&'a [u8]> {
+    self
+      .data
+      .get(offset..offset.saturating_add(len))
+      .ok_or_else(|| invalid("Capture is truncated"))
+  }
+
+  fn u16(&self, offset: usize) -> io::Result<u16> {
+    let b: [u8; 2] = self.bytes(offset, 2)?.try_into().unwrap();
+    Ok(if self.big_endian {
+      u16::from_be_bytes(b)
+    } else {
+      u16::from_le_bytes(b)
+    })
+  }
+
+  fn u32(&self, offset: usize) -> io::Result<u32> {
+    let b: [u8; 4] = self.bytes(offset, 4)?.try_into().unwrap();
+    Ok(if self.big_endian {
+      u32::from_be_bytes(b)
+    } else {
+      u32::from_le_bytes(b)
+    })
+  }
+}
+
+pub(crate) fn read_capture_file(path: &Path) -> io::Result<Vec<Datagram>> {
+  read_capture(&fs::read(path)?)
+}
+
+// Extract all UDP datagrams from a pcap or pcapng capture, in capture order.
+pub(crate) fn read_capture(data: &[u8]) -> io::Result<Vec<Datagram>> {
+  let magic = data
+    .get(0..4)
+    .ok_or_else(|| invalid("Capture is truncated"))?;
+  let magic = u32::from_le_bytes(magic.try_into().unwrap());
+  let mut extractor = UdpExtractor::default();
+  if magic == PCAPNG_SECTION_HEADER {
+    read_pcapng(data, &mut extractor)?;
+  } else {
+    read_pcap(data, &mut extractor)?;
+  }
+  Ok(extractor.datagrams)
+}
+
+fn read_pcap(data: &[u8], extractor: &mut UdpExtractor) -> io::Result<()> {
+  let le = Cursor {
+    data,
+    big_endian: false,
+  };
+  let (cursor, nanos) = match le.u32(0)? {
+    PCAP_MAGIC_MICROS => (le, false),
+    PCAP_MAGIC_NANOS => (le, true),
+    m if m.swap_bytes() == PCAP_MAGIC_MICROS => (
+      Cursor {
+        big_endian: true,
+        ..le
+      },
+      false,
+    ),
+    m if m.swap_bytes() == PCAP_MAGIC_NANOS => (
+      Cursor {
+        big_endian: true,
+        ..le
+      },
+      true,
+    ),
+    _ => return Err(invalid("Not a pcap or pcapng file")),
+  };
+  let link_type = cursor.u32(20)? & 0x0fff_ffff; // upper bits are FCS info
+  let mut offset = 24;
+  while offset < data.len() {
+    let seconds = cursor.u32(offset)?;
+    let fraction = cursor.u32(offset + 4)?;
+    let captured_len = cursor.u32(offset + 8)? as usize;
+    let frame = cursor.bytes(offset + 16, captured_len)?;
+    let timestamp = Duration::from_secs(seconds.into())
+      + if nanos {
+        Duration::from_nanos(fraction.into())
+      } else {
+        Duration::from_micros(fraction.into())
+      };
+    extractor.frame(link_type, frame, timestamp);
+    offset += 16 + captured_len;
+  }
+  Ok(())
+}
+
+// Per-interface information in pcapng
+struct Interface {
+  link_type: u32,
+  // Timestamp units per second
+  resolution: u64,
+}
+
+fn read_pcapng(data: &[u8], extractor: &mut UdpExtractor) -> io::Result<()> {
+  let mut cursor = Cursor {
+    data,
+    big_endian: false,
+  };
+  let mut interfaces: Vec<Interface> = Vec::new();
+  let mut offset = 0;
+  while offset < data.len() {
+    let block_type = cursor.u32(offset)?;
+    if block_type == PCAPNG_SECTION_HEADER {
+      // Byte order is given in each section header
+      cursor.big_endian = false;
+      if cursor.u32(offset + 8)? != PCAPNG_BYTE_ORDER_MAGIC {
+        cursor.big_endian = true;
+      }
+      interfaces.clear();
+    }
+    let block_len = cursor.u32(offset + 4)? as usize;
+    if block_len < 12 || block_len % 4 != 0 {
+      return Err(invalid("Bad pcapng block length"));
+    }
+    let body = Cursor {
+      data: cursor.bytes(offset + 8, block_len - 12)?,
+      ..cursor
+    };
+    match block_type {
+      // Interface Description Block
+      1 => interfaces.push(Interface {
+        link_type: body.u16(0)?.into(),
+        resolution: interface_resolution(&body)?,
+      }),
+      // Enhanced Packet Block
+      6 => {
+        let interface = interfaces
+          .get(body.u32(0)? as usize)
+          .ok_or_else(|| invalid("Packet refers to unknown interface"))?;
+        let ticks = (u64::from(body.u32(4)?) << 32) | u64::from(body.u32(8)?);
+        let captured_len = body.u32(12)? as usize;
+        let timestamp = Duration::from_secs(ticks / interface.resolution)
+          + Duration::from_nanos(
+            ((ticks % interface.resolution) as u128 * 1_000_000_000 / interface.resolution as u128)
+              as u64,
+          );
+        extractor.frame(
+          interface.link_type,
+          body.bytes(20, captured_len)?,
+          timestamp,
+        );
+      }
+      // Simple Packet Block: no timestamp, always interface 0
+      3 => {
+        let interface = interfaces
+          .first()
+          .ok_or_else(|| invalid("Packet refers to unknown interface"))?;
+        let original_len = body.u32(0)? as usize;
+        let captured_len = original_len.min(body.data.len().saturating_sub(4));
+        extractor.frame(
+          interface.link_type,
+          body.bytes(4, captured_len)?,
+          Duration::ZERO,
+        );
+      }
+      _ => (), // Other blocks do not carry packets
+    }
+    offset += block_len;
+  }
+  Ok(())
+}
+
+// The if_tsresol option of an Interface Description Block
+fn interface_resolution(body: &Cursor) -> io::Result<u64> {
+  let mut offset = 8;
+  while offset + 4 <= body.data.len() {
+    let code = body.u16(offset)?;
+    let len = body.u16(offset + 2)? as usize;
+    if code == 0 {
+      break; // end of options
+    }
+    if code == 9 && len == 1 {
+      let value = body.bytes(offset + 4, 1)?[0];
+      let exponent = u32::from(value & 0x7f);
+      let base: u64 = if value & 0x80 == 0 { 10 } else { 2 };
+      return base
+        .checked_pow(exponent)
+        .ok_or_else(|| invalid("Bad timestamp resolution"));
+    }
+    offset += 4 + (len + 3) / 4 * 4;
+  }
+  Ok(1_000_000)
+}
+
+#[derive(Default)]
+struct UdpExtractor {
+  datagrams: Vec<Datagram>,
+  // IPv4 fragments by (source, destination, identification)
+  fragments: HashMap<(Ipv4Addr, Ipv4Addr, u16), Fragments>,
+}
+
+#[derive(Default)]
+struct Fragments {
+  // by offset
+  parts: BTreeMap<usize, Vec<u8>>,
+  total_len: Option<usize>,
+}
+
+impl Fragments {
+  fn reassemble(&self) -> Option<Vec<u8>> {
+    let total_len = self.total_len?;
+    let mut packet = Vec::with_capacity(total_len);
+    for (offset, part) in &self.parts {
+      if *offset > packet.len() {
+        return None; // gap
+      }
+      let skip = packet.len() - offset;
+      packet.extend_from_slice(part.get(skip..).unwrap_or_default());
+    }
+    (packet.len() >= total_len).then_some(packet)
+  }
+}
+
+impl UdpExtractor {
+  fn frame(&mut self, link_type: u32, frame: &[u8], timestamp: Duration) {
+    let ip_packet = match link_type {
+      LINKTYPE_ETHERNET => {
+        // Skip VLAN tags
+        let mut offset = 12;
+        while frame.get(offset..offset + 2) == Some(&[0x81, 0x00]) {
+          offset += 4;
+        }
+        frame.get(offset + 2..)
+      }
+      LINKTYPE_NULL => frame.get(4..),
+      LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => Some(frame),
+      LINKTYPE_LINUX_SLL => frame.get(16..),
+      LINKTYPE_LINUX_SLL2 => frame.get(20..),
+      _ => None,
+    };
+    if let Some(ip_packet) = ip_packet {
+      self.ip_packet(ip_packet, timestamp);
+    }
+  }
+
+  // The IP version is taken from the packet itself, rather than the link
+  // layer header.
+  fn ip_packet(&mut self, packet: &[u8], timestamp: Duration) {
+    match packet.first().map(|b| b >> 4) {
+      Some(4) => self.ipv4_packet(packet, timestamp),
+      Some(6) => self.ipv6_packet(packet, timestamp),
+      _ => (),
+    }
+  }
+
+  fn ipv4_packet(&mut self, packet: &[u8], timestamp: Duration) {
+    if packet.len() < 20 || packet[9] != IP_PROTOCOL_UDP {
+      return;
+    }
+    let header_len = usize::from(packet[0] & 0x0f) * 4;
+    let total_len = usize::from(u16::from_be_bytes([packet[2], packet[3]])).min(packet.len());
+    let payload = match packet.get(header_len..total_len) {
+      Some(p) => p,
+      None => return,
+    };
+    let source = Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]);
+    let destination = Ipv4Addr::new(packet[16], packet[17], packet[18], packet[19]);
+    let flags_and_offset = u16::from_be_bytes([packet[6], packet[7]]);
+    let more_fragments = flags_and_offset & 0x2000 != 0;
+    let fragment_offset = usize::from(flags_and_offset & 0x1fff) * 8;
+
+    if !more_fragments && fragment_offset == 0 {
+      self.udp_datagram(source.into(), destination.into(), payload, timestamp);
+      return;
+    }
+    let key = (
+      source,
+      destination,
+      u16::from_be_bytes([packet[4], packet[5]]),
+    );
+    let fragments = self.fragments.entry(key).or_default();
+    fragments.parts.insert(fragment_offset, payload.to_vec());
+    if !more_fragments {
+      fragments.total_len = Some(fragment_offset + payload.len());
+    }
+    if let Some(reassembled) = fragments.reassemble() {
+      self.fragments.remove(&key);
+      self.udp_datagram(source.into(), destination.into(), &reassembled, timestamp);
+    }
+  }
+
+  fn ipv6_packet(&mut self, packet: &[u8], timestamp: Duration) {
+    if packet.len() < 40 || packet[6] != IP_PROTOCOL_UDP {
+      return;
+    }
+    let payload_len = usize::from(u16::from_be_bytes([packet[4], packet[5]]));
+    let address = |offset: usize| {
+      let octets: [u8; 16] = packet[offset..offset + 16].try_into().unwrap();
+      IpAddr::from(Ipv6Addr::from(octets))
+    };
+    let end = (40 + payload_len).min(packet.len());
+    self.udp_datagram(address(8), address(24), &packet[40..end], timestamp);
+  }
+
+  fn udp_datagram(
+    &mut self,
+    source: IpAddr,
+    destination: IpAddr,
+    segment: &[u8],
+    timestamp: Duration,
+  ) {
+    if segment.len() < 8 {
+      return;
+    }
+    let port = |offset: usize| u16::from_be_bytes([segment[offset], segment[offset + 1]]);
+    let len = usize::from(port(4)).clamp(8, segment.len());
+    self.datagrams.push(Datagram {
+      timestamp,
+      source: SocketAddr::new(source, port(0)),
+      destination: SocketAddr::new(destination, port(2)),
+      payload: Bytes::copy_from_slice(&segment[8..len]),
+    });
+  }
+}
+
+pub(crate) fn write_pcap_file(path: &Path, datagrams: &[Datagram]) -> io::Result<()> {
+  fs::write(path, write_pcap(datagrams))
+}
+
+// Write datagrams as a pcap capture with raw IP link type. IP and UDP
+// headers are synthesized. The UDP checksum is left out, which is allowed
+// for IPv4 only, but Wireshark accepts it also for IPv6.
+pub(crate) fn write_pcap(datagrams: &[Datagram]) -> Vec<u8> {
+  let mut out = Vec::new();
+  out.extend_from_slice(&PCAP_MAGIC_MICROS.to_le_bytes());
+  out.extend_from_slice(&2u16.to_le_bytes()); // version 2.4
+  out.extend_from_slice(&4u16.to_le_bytes());
+  out.extend_from_slice(&0i32.to_le_bytes()); // timezone
+  out.extend_from_slice(&0u32.to_le_bytes()); // sigfigs
+  out.extend_from_slice(&u32::from(u16::MAX).to_le_bytes()); // snaplen
+  out.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+
+  for datagram in datagrams {
+    let packet = ip_udp_packet(datagram);
+    out.extend_from_slice(&(datagram.timestamp.as_secs() as u32).to_le_bytes());
+    out.extend_from_slice(&datagram.timestamp.subsec_micros().to_le_bytes());
+    out.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+    out.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+    out.extend_from_slice(&packet);
+  }
+  out
+}
+
+fn ip_udp_packet(datagram: &Datagram) -> Vec<u8> {
+  let udp_len = (8 + datagram.payload.len()) as u16;
+  let mut packet = Vec::new();
+  match (datagram.source.ip(), datagram.destination.ip()) {
+    (IpAddr::V4(source), IpAddr::V4(destination)) => {
+      packet.extend_from_slice(&[0x45, 0]);
+      packet.extend_from_slice(&(20 + udp_len).to_be_bytes());
+      packet.extend_from_slice(&[0, 0, 0x40, 0, 64, IP_PROTOCOL_UDP, 0, 0]); // don't fragment
+      packet.extend_from_slice(&source.octets());
+      packet.extend_from_slice(&destination.octets());
+      let checksum = !packet
+        .chunks(2)
+        .map(|w| u32::from(u16::from_be_bytes([w[0], w[1]])))
+        .fold(0u32, |sum, w| {
+          let sum = sum + w;
+          (sum & 0xffff) + (sum >> 16)
+        }) as u16;
+      packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+    }
+    (source, destination) => {
+      let v6 = |a: IpAddr| match a {
+        IpAddr::V4(a) => a.to_ipv6_mapped(),
+        IpAddr::V6(a) => a,
+      };
+      packet.extend_from_slice(&[0x60, 0, 0, 0]);
+      packet.extend_from_slice(&udp_len.to_be_bytes());
+      packet.extend_from_slice(&[IP_PROTOCOL_UDP, 64]);
+      packet.extend_from_slice(&v6(source).octets());
+      packet.extend_from_slice(&v6(destination).octets());
+    }
+  }
+  packet.extend_from_slice(&datagram.source.port().to_be_bytes());
+  packet.extend_from_slice(&datagram.destination.port().to_be_bytes());
+  packet.extend_from_slice(&udp_len.to_be_bytes());
+  packet.extend_from_slice(&[0, 0]); // no checksum
+  packet.extend_from_slice(&datagram.payload);
+  packet
+}
+
+// Feed the RTPS datagrams to a MessageReceiver, in capture order. Returns the
+// number of datagrams fed. Readers that should receive the data must have
+// been added to the MessageReceiver.
+pub(crate) fn replay(message_receiver: &mut MessageReceiver, datagrams: &[Datagram]) -> usize {
+  let mut count = 0;
+  for datagram in datagrams.iter().filter(|d| d.is_rtps()) {
+    message_receiver.handle_received_packet(&datagram.payload);
+    count += 1;
+  }
+  count
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use log::info;
+  use mio_extras::channel as mio_channel;
+
+  use super::*;
+  use crate::{
+    messages::submessages::submessages::AckSubmessage, structure::guid::GuidPrefix,
+    test::test_data::spdp_participant_data_raw,
+  };
+
+  fn datagram(source: &str, destination: &str, payload: &[u8]) -> Datagram {
+    Datagram {
+      timestamp: Duration::new(1_700_000_000, 123_456_000),
+      source: source.parse().unwrap(),
+      destination: destination.parse().unwrap(),
+      payload: Bytes::copy_from_slice(payload),
+    }
+  }
+
+  fn message_receiver() -> MessageReceiver {
+    let (acknack_sender, _) = mio_channel::sync_channel::<(GuidPrefix, AckSubmessage)>(10);
+    let (spdp_liveness_sender, _) = mio_channel::sync_channel(8);
+    MessageReceiver::new(
+      GuidPrefix::new(&[1; 12]),
+      acknack_sender,
+      spdp_liveness_sender,
+      None,
+      Default::default(),
+      Arc::default(),
+    )
+  }
+
+  #[test]
+  fn pcap_round_trip() {
+    let datagrams = vec![
+      datagram(
+        "192.168.1.2:7410",
+        "239.255.0.1:7400",
+        &spdp_participant_data_raw(),
+      ),
+      datagram("[fe80::1]:7410", "[fe80::2]:7411", b"RTPS\x02\x03"),
+      datagram("10.0.0.1:1", "10.0.0.2:2", b""),
+    ];
+    let capture = write_pcap(&datagrams);
+    assert_eq!(read_capture(&capture).unwrap(), datagrams);
+
+    let path = std::env::temp_dir().join(format!("rustdds-pcap-{}.pcap", std::process::id()));
+    write_pcap_file(&path, &datagrams).unwrap();
+    let from_file = read_capture_file(&path);
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(from_file.unwrap(), datagrams);
+  }
+
+  #[test]
+  fn pcapng_ethernet_and_fragments() {
+    // IPv4 UDP datagram in two fragments, each in an Ethernet frame with a
+    // VLAN tag
+    let payload: Vec<u8> = (0..100u8).collect();
+    let whole = ip_udp_packet(&datagram("10.0.0.1:7410", "10.0.0.2:7411", &payload));
+    let (header, body) = whole.split_at(20);
+    let fragment = |flags_and_offset: u16, part: &[u8]| {
+      let mut frame = vec![0; 12];
+      frame.extend_from_slice(&[0x81, 0x00, 0x00, 0x05, 0x08, 0x00]);
+      let mut ip = header.to_vec();
+      ip[2..4].copy_from_slice(&((20 + part.len()) as u16).to_be_bytes());
+      ip[4..6].copy_from_slice(&[0x12, 0x34]);
+      ip[6..8].copy_from_slice(&flags_and_offset.to_be_bytes());
+      frame.extend_from_slice(&ip);
+      frame.extend_from_slice(part);
+      frame
+    };
+    let frames = [fragment(0x2000, &body[..48]), fragment(48 / 8, &body[48..])];
+
+    let block = |block_type: u32, body: &[u8]| {
+      let len = (12 + (body.len() + 3) / 4 * 4) as u32;
+      let mut b = Vec::new();
+      b.extend_from_slice(&block_type.to_le_bytes());
+      b.extend_from_slice(&len.to_le_bytes());
+      b.extend_from_slice(body);
+      b.resize(len as usize - 4, 0);
+      b.extend_from_slice(&len.to_le_bytes());
+      b
+    };
+    let mut capture = block(
+      PCAPNG_SECTION_HEADER,
+      &[
+        &PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes()[..],
+        &[1, 0, 0, 0],
+        &[0xff; 8],
+      ]
+      .concat(),
+    );
+    // Ethernet, with nanosecond timestamps
+    capture.extend(block(
+      1,
+      &[1, 0, 0, 0, 0, 0, 0, 0, 9, 0, 1, 0, 9, 0, 0, 0, 0, 0, 0, 0],
+    ));
+    let ticks: u64 = 1_700_000_000_000_000_001;
+    for frame in &frames {
+      let mut epb = Vec::new();
+      epb.extend_from_slice(&0u32.to_le_bytes());
+      epb.extend_from_slice(&((ticks >> 32) as u32).to_le_bytes());
+      epb.extend_from_slice(&(ticks as u32).to_le_bytes());
+      epb.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+      epb.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+      epb.extend_from_slice(frame);
+      capture.extend(block(6, &epb));
+    }
+
+    let datagrams = read_capture(&capture).unwrap();
+    assert_eq!(datagrams.len(), 1);
+    assert_eq!(datagrams[0].payload.as_ref(), payload.as_slice());
+    assert_eq!(datagrams[0].destination, "10.0.0.2:7411".parse().unwrap());
+    assert_eq!(datagrams[0].timestamp, Duration::from_nanos(ticks));
+  }
+
+  #[test]
+  fn replay_captured_spdp() {
+    let capture = write_pcap(&[
+      datagram("10.0.0.1:7410", "239.255.0.1:7400", b"not rtps"),
+      datagram(
+        "10.0.0.1:7410",
+        "239.255.0.1:7400",
+        &spdp_participant_data_raw(),
+      ),
+    ]);
+    let datagrams = read_capture(&capture).unwrap();
+    let mut message_receiver = message_receiver();
+    assert_eq!(replay(&mut message_receiver, &datagrams), 1);
+    assert_eq!(message_receiver.submessage_count(), 2); // INFO_TS, DATA
+  }
+
+  #[test]
+  fn replay_pcap_dir() {
+    let dir = match std::env::var("RUSTDDS_REPLAY_PCAP") {
+      Ok(dir) => dir,
+      Err(_) => return, // Nothing to replay
+    };
+    for entry in fs::read_dir(dir).unwrap() {
+      let path = entry.unwrap().path();
+      if !matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("pcap" | "pcapng")
+      ) {
+        continue;
+      }
+      let datagrams = read_capture_file(&path).unwrap();
+      let count = replay(&mut message_receiver(), &datagrams);
+      info!("Replayed {count} RTPS datagrams from {path:?}");
+    }
+  }
+}
@@ -85,9 +85,9 @@ use crate::{
     adapters::no_key::DeserializerAdapter,
     qos::{
       policy::{
-        Deadline, DestinationOrder, Durability, History, LatencyBudget, Lifespan, Liveliness,
-        Ownership, Presentation, PresentationAccessScope, Reliability, ResourceLimits,
-        TimeBasedFilter,
+        Deadline, DestinationOrder, Durability, GroupData, History, LatencyBudget, Lifespan,
+        Liveliness, Ownership, Presentation, PresentationAccessScope, Reliability, ResourceLimits,
+        TimeBasedFilter, TopicData, UserData,
       },
       QosPolicyBuilder,
     },
@@ -260,6 +260,15 @@ pub(crate) fn subscription_builtin_topic_data() -> Option<SubscriptionBuiltinTop
     .lifespan(Lifespan {
       duration: Duration::from(StdDuration::from_secs(6 * 60)),
     })
+    .user_data(UserData {
+      value: b"reader user data".to_vec(),
+    })
+    .topic_data(TopicData {
+      value: vec![1, 2, 3],
+    })
+    .group_data(GroupData {
+      value: b"subscriber".to_vec(),
+    })
     .build();
 
   let sub_topic_data = SubscriptionBuiltinTopicData::new(
@@ -302,6 +311,11 @@ pub(crate) fn publication_builtin_topic_data() -> Option<PublicationBuiltinTopic
       coherent_access: true,
       ordered_access: false,
     }),
+    user_data: Some(UserData {
+      value: b"deployment=test".to_vec(),
+    }),
+    topic_data: None,
+    group_data: Some(GroupData { value: vec![7] }),
     related_datareader_key: None,
     service_instance_name: None,
     topic_aliases: None,
@@ -342,6 +356,9 @@ pub(crate) fn topic_data() -> Option<TopicBuiltinTopicData> {
       max_samples_per_instance: 15,
     }),
     ownership: Some(Ownership::Exclusive { strength: 432 }),
+    topic_data: Some(TopicData {
+      value: b"topic".to_vec(),
+    }),
   };
 
   Some(topic_data)
@@ -0,0 +1,214 @@
+// Tracing spans and events of participants and endpoints, reported via the
+// `tracing` crate when feature "tracing" is enabled. Without the feature,
+// these types are empty and their methods do nothing, so that call sites need
+// not be feature-gated.
+//
+// The event loop of a DomainParticipant runs within a "participant" span.
+// Writers and Readers are created in the event loop, so their "writer" and
+// "reader" spans are children of it. Events are emitted within the span of
+// the endpoint they concern, with the remote GUID and sequence numbers as
+// fields. This way a subscriber can filter by participant, topic or GUID.
+
+#[cfg(feature = "tracing")]
+use std::cell::Cell;
+
+use crate::structure::{guid::GUID, sequence_number::SequenceNumber};
+
+/// Volume of the per-sample events of feature `tracing`, see
+/// [`DomainParticipantBuilder::trace_sampling`](crate::DomainParticipantBuilder::trace_sampling).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceSampling {
+  /// Of the events about individual samples, i.e. a sample written, received
+  /// or resent, only every `sample_interval`th of each endpoint is emitted.
+  /// Zero disables them.
+  pub sample_interval: u32,
+}
+
+impl Default for TraceSampling {
+  fn default() -> Self {
+    Self { sample_interval: 1 }
+  }
+}
+
+/// Span of a DomainParticipant
+pub(crate) struct ParticipantTrace {
+  #[cfg(feature = "tracing")]
+  span: tracing::Span,
+}
+
+impl ParticipantTrace {
+  #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+  pub fn new(domain_id: u16, guid: GUID) -> Self {
+    Self {
+      #[cfg(feature = "tracing")]
+      span: tracing::info_span!("participant", domain_id, guid = %guid),
+    }
+  }
+
+  // Runs `f` within the span
+  pub fn in_scope<R>(&self, f: impl FnOnce() -> R) -> R {
+    #[cfg(feature = "tracing")]
+    return self.span.in_scope(f);
+    #[cfg(not(feature = "tracing"))]
+    f()
+  }
+}
+
+/// Span and events of an RTPS Writer or Reader
+pub(crate) struct EndpointTrace {
+  #[cfg(feature = "tracing")]
+  span: tracing::Span,
+  #[cfg(feature = "tracing")]
+  sample_interval: u32,
+  // Per-sample events since the latest one emitted
+  #[cfg(feature = "tracing")]
+  samples_skipped: Cell<u32>,
+}
+
+impl EndpointTrace {
+  #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+  pub fn writer(topic_name: &str, guid: GUID) -> Self {
+    #[cfg(feature = "tracing")]
+    return Self::new(tracing::info_span!("writer", topic = topic_name, guid = %guid));
+    #[cfg(not(feature = "tracing"))]
+    Self {}
+  }
+
+  #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+  pub fn reader(topic_name: &str, guid: GUID) -> Self {
+    #[cfg(feature = "tracing")]
+    return Self::new(tracing::info_span!("reader", topic = topic_name, guid = %guid));
+    #[cfg(not(feature = "tracing"))]
+    Self {}
+  }
+
+  #[cfg(feature = "tracing")]
+  fn new(span: tracing::Span) -> Self {
+    Self {
+      span,
+      sample_interval: TraceSampling::default().sample_interval,
+      samples_skipped: Cell::new(0),
+    }
+  }
+
+  #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+  pub fn set_sampling(&mut self, sampling: TraceSampling) {
+    #[cfg(feature = "tracing")]
+    {
+      self.sample_interval = sampling.sample_interval;
+      self.samples_skipped.set(0);
+    }
+  }
+
+  // Should this per-sample event be emitted?
+  #[cfg(feature = "tracing")]
+  fn sampled(&self) -> bool {
+    if self.sample_interval == 0 {
+      return false;
+    }
+    // The first one of each interval
+    let skipped = self.samples_skipped.get();
+    self
+      .samples_skipped
+      .set((skipped + 1) % self.sample_interval);
+    skipped == 0
+  }
+
+  #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+  pub fn sample_written(&self, sequence_number: SequenceNumber) {
+    #[cfg(feature = "tracing")]
+    if self.sampled() {
+      tracing::trace!(parent: &self.span, sn = i64::from(sequence_number), "sample written");
+    }
+  }
+
+  #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+  pub fn sample_received(&self, writer: GUID, sequence_number: SequenceNumber) {
+    #[cfg(feature = "tracing")]
+    if self.sampled() {
+      tracing::trace!(
+        parent: &self.span,
+        writer = %writer,
+        sn = i64::from(sequence_number),
+        "sample received"
+      );
+    }
+  }
+
+  #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+  pub fn repair_sample_sent(&self, reader: GUID, sequence_number: SequenceNumber) {
+    #[cfg(feature = "tracing")]
+    if self.sampled() {
+      tracing::trace!(
+        parent: &self.span,
+        reader = %reader,
+        sn = i64::from(sequence_number),
+        "repair sample sent"
+      );
+    }
+  }
+
+  #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+  pub fn samples_lost(&self, writer: GUID, count: i64) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(parent: &self.span, writer = %writer, count, "samples lost");
+  }
+
+  #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+  pub fn acknack_received(&self, reader: GUID, acked_before: SequenceNumber) {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(
+      parent: &self.span,
+      reader = %reader,
+      acked_before = i64::from(acked_before),
+      "ACKNACK received"
+    );
+  }
+
+  #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+  pub fn heartbeat_received(
+    &self,
+    writer: GUID,
+    first_sn: SequenceNumber,
+    last_sn: SequenceNumber,
+  ) {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(
+      parent: &self.span,
+      writer = %writer,
+      first_sn = i64::from(first_sn),
+      last_sn = i64::from(last_sn),
+      "HEARTBEAT received"
+    );
+  }
+
+  #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+  pub fn matched(&self, remote: GUID) {
+    #[cfg(feature = "tracing")]
+    tracing::info!(parent: &self.span, remote = %remote, "matched");
+  }
+
+  #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+  pub fn unmatched(&self, remote: GUID) {
+    #[cfg(feature = "tracing")]
+    tracing::info!(parent: &self.span, remote = %remote, "unmatched");
+  }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn sample_events_are_sampled() {
+    let mut trace = EndpointTrace::writer("test", GUID::GUID_UNKNOWN);
+    trace.set_sampling(TraceSampling { sample_interval: 3 });
+    let emitted: Vec<bool> = (0..7).map(|_| trace.sampled()).collect();
+    assert_eq!(emitted, vec![true, false, false, true, false, false, true]);
+
+    trace.set_sampling(TraceSampling { sample_interval: 0 });
+    assert!((0..5).all(|_| !trace.sampled()));
+    trace.set_sampling(TraceSampling::default());
+    assert!((0..5).all(|_| trace.sampled()));
+  }
+}